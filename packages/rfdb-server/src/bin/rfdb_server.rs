@@ -19,7 +19,7 @@
 //!   - Client creates/opens specific databases
 //!   - Each session tracks its own current database
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{Read, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::PathBuf;
@@ -39,15 +39,38 @@ use futures_util::{StreamExt, SinkExt};
 
 // Import from library
 use rfdb::graph::{GraphEngineV2, GraphStore};
-use rfdb::storage::{NodeRecord, EdgeRecord, AttrQuery, FieldDecl, FieldType};
-use rfdb::datalog::{parse_program, parse_atom, parse_query, Evaluator, EvaluatorExplain, EvalLimits, QueryResult};
-use rfdb::database_manager::{DatabaseManager, DatabaseInfo, AccessMode};
+use rfdb::graph::analytics::{count_parallel_edges, top_n_by_degree, union_find};
+use rfdb::graph::traversal::{bfs_levels, bfs_until};
+use rfdb::storage::{NodeRecord, EdgeRecord, AttrQuery, FieldDecl, FieldEntity, FieldType, MetadataSchema};
+use rfdb::datalog::{
+    parse_program, parse_atom, parse_query, parse_source, Evaluator, EvaluatorExplain, EvalLimits, ParseError, Program,
+    QueryResult, Rule, Source,
+};
+use rfdb::database_manager::{AccessMode, Database, DatabaseInfo, DatabaseManager};
 use rfdb::session::ClientSession;
+use rfdb::storage_v2::{DurabilityMode, ResourceManager, ShardDiagnostics, SystemResources};
+use rfdb::error::GraphError;
+use rfdb::transaction::{CrossDbBatch, StagedCommit, StreamingCommit, Transaction, TransactionView, WriteOp};
 use rfdb::metrics::{Metrics, MetricsSnapshot, SLOW_QUERY_THRESHOLD_MS};
+use rfdb::metrics::prometheus::PrometheusSource;
+use rfdb::import::jsonl::parse_jsonl;
 
 // Global client ID counter
 static NEXT_CLIENT_ID: AtomicUsize = AtomicUsize::new(1);
 
+// Requests currently being handled, across all connections
+static ACTIVE_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+/// How long shutdown waits for in-flight requests before flushing anyway
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default Unix socket write timeout (`--write-timeout-ms`). A client that
+/// stops reading is disconnected once a write stalls this long.
+const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default cap on a single Unix socket request frame (`--max-message-bytes`).
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 100 * 1024 * 1024;
+
 /// Streaming threshold: queries returning more than this many nodes
 /// will use chunked streaming instead of a single Response::Nodes.
 /// Only active when the client negotiated protocol version >= 3.
@@ -56,6 +79,16 @@ const STREAMING_THRESHOLD: usize = 100;
 /// Maximum nodes per streaming chunk.
 const STREAMING_CHUNK_SIZE: usize = 500;
 
+/// Export documents larger than this many bytes are sent as ExportChunk
+/// frames instead of a single Response::Export (protocol v3+ only).
+const EXPORT_STREAMING_THRESHOLD: usize = 4 * 1024 * 1024;
+
+/// Maximum bytes of export content per streaming chunk.
+const EXPORT_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Default cap on each ID list in a DiffSnapshotsDetailed response.
+const DEFAULT_DIFF_MAX_IDS: usize = 10_000;
+
 // ============================================================================
 // Wire Protocol Types (Extended for multi-database)
 // ============================================================================
@@ -74,6 +107,10 @@ pub enum Request {
         protocol_version: Option<u32>,
         #[serde(rename = "clientId")]
         client_id: Option<String>,
+        /// Opt in to heartbeat frames when idle (`--idle-timeout-secs`).
+        /// Clients that don't are never sent unsolicited frames.
+        #[serde(default)]
+        heartbeat: bool,
     },
 
     /// Create a new database
@@ -81,6 +118,9 @@ pub enum Request {
         name: String,
         #[serde(default)]
         ephemeral: bool,
+        /// Overrides the server's `--default-shards` for this database
+        #[serde(default, rename = "shardCount")]
+        shard_count: Option<u32>,
     },
 
     /// Open a database and set as current for this session
@@ -96,22 +136,45 @@ pub enum Request {
     /// Drop (delete) a database
     DropDatabase { name: String },
 
+    /// Rename a database (must not be open in any session)
+    RenameDatabase { from: String, to: String },
+
+    /// Copy a database under a new name
+    CopyDatabase { from: String, to: String },
+
     /// List all databases
     ListDatabases,
 
+    /// Check whether a database exists, without opening it
+    DatabaseExists { name: String },
+
+    /// Info for one database (as in ListDatabases), without opening it
+    GetDatabaseInfo { name: String },
+
     /// Get current database for this session
     CurrentDatabase,
 
+    /// Describe this connection's session state (negotiated protocol,
+    /// current database, open batch)
+    SessionInfo,
+
     // ========================================================================
     // Existing Commands (unchanged)
     // ========================================================================
 
     // Write operations
     AddNodes { nodes: Vec<WireNode> },
+    /// Insert only the nodes whose ID does not exist yet; existing nodes
+    /// are left untouched. Responds with the number actually inserted.
+    AddNodesIfAbsent { nodes: Vec<WireNode> },
     AddEdges {
         edges: Vec<WireEdge>,
         #[serde(default, rename = "skipValidation")]
         skip_validation: bool,
+        /// Collapse edges sharing (src, dst, type) within the request,
+        /// merging their metadata (see `merge_duplicate_edges`).
+        #[serde(default, rename = "dedupEdges")]
+        dedup_edges: bool,
     },
     DeleteNode { id: String },
     DeleteEdge {
@@ -122,13 +185,62 @@ pub enum Request {
     },
 
     // Read operations
-    GetNode { id: String },
+    GetNode {
+        id: String,
+        /// Also return a soft-deleted node, marked `deleted` (protocol v3)
+        #[serde(default, rename = "includeDeleted")]
+        include_deleted: bool,
+    },
     NodeExists { id: String },
+    EdgeExists {
+        src: String,
+        dst: String,
+        #[serde(rename = "edgeType")]
+        edge_type: String,
+    },
+    /// EdgeExists for many edges in one round trip; answers in input order
+    EdgesExist { edges: Vec<WireEdgeKey> },
     FindByType {
         #[serde(rename = "nodeType")]
         node_type: String,
     },
-    FindByAttr { query: WireAttrQuery },
+    FindByAttr {
+        query: WireAttrQuery,
+        /// Also report how many matches each shard contributed
+        #[serde(default, rename = "perShardCounts")]
+        per_shard_counts: bool,
+        /// Also return soft-deleted matches (protocol v3); combined with
+        /// perShardCounts it is rejected with INVALID_ARGUMENT
+        #[serde(default, rename = "includeDeleted")]
+        include_deleted: bool,
+    },
+    /// Number of nodes FindByAttr would return, without sending the IDs
+    CountByAttr { query: WireAttrQuery },
+    /// Distinct values of a metadata field among matching nodes, with counts
+    DistinctValues { query: WireAttrQuery, field: String },
+    /// Describe how a FindByAttr query is evaluated (v2 only)
+    ExplainQuery { query: WireAttrQuery },
+    /// Up to `limit` node IDs whose name starts with `prefix`
+    /// (case-insensitive), in name order (v2 only)
+    AutocompleteNames {
+        prefix: String,
+        #[serde(default, rename = "nodeType")]
+        node_type: Option<String>,
+        limit: u32,
+    },
+    /// Groups of nodes sharing a non-zero content hash (v2 only)
+    FindDuplicateContent {
+        #[serde(default, rename = "nodeType")]
+        node_type: Option<String>,
+    },
+    /// Edges whose metadata `field` equals `value` (v2 only). Uses the
+    /// edge metadata index for fields declared with entity "edge".
+    FindEdgesByMetadata {
+        field: String,
+        value: String,
+        #[serde(default, rename = "edgeType")]
+        edge_type: Option<String>,
+    },
 
     // Graph traversal
     Neighbors {
@@ -144,6 +256,42 @@ pub enum Request {
         #[serde(rename = "edgeTypes")]
         edge_types: Vec<String>,
     },
+    /// BFS that groups results by distance: `levels[d]` are the nodes first
+    /// reached at depth `d`, start nodes at level 0
+    KHop {
+        #[serde(rename = "startIds")]
+        start_ids: Vec<String>,
+        #[serde(rename = "maxDepth")]
+        max_depth: u32,
+        #[serde(rename = "edgeTypes")]
+        edge_types: Vec<String>,
+    },
+    /// Nearest nodes of `targetType` reachable from `startIds`: BFS stops
+    /// at the first depth holding a match, and returns all matches there
+    NearestMatching {
+        #[serde(rename = "startIds")]
+        start_ids: Vec<String>,
+        #[serde(rename = "edgeTypes")]
+        edge_types: Vec<String>,
+        #[serde(rename = "targetType")]
+        target_type: String,
+        #[serde(rename = "maxDepth")]
+        max_depth: u32,
+    },
+    /// Weakly connected components (edge direction ignored), optionally
+    /// over a subset of edge types. Nodes without any matching edge are
+    /// not reported.
+    WeaklyConnectedComponents {
+        #[serde(default, rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+    },
+    /// Read-time projection of all edges (optionally of some types) where
+    /// parallel edges of one type between the same pair are merged into a
+    /// single edge with metadata `{"count": N}`
+    AggregateEdges {
+        #[serde(default, rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+    },
     Reachability {
         #[serde(rename = "startIds")]
         start_ids: Vec<String>,
@@ -153,6 +301,20 @@ pub enum Request {
         edge_types: Vec<String>,
         #[serde(default)]
         backward: bool,
+        /// `[edgeType, cost]` pairs: each edge of the type counts `cost`
+        /// toward `maxDepth` instead of 1 (unlisted types cost 1)
+        #[serde(default, rename = "edgeCosts")]
+        edge_costs: Vec<(String, u32)>,
+    },
+    /// Whether a path `src -> dst` of at most `maxDepth` edges exists
+    /// (bidirectional BFS)
+    AreConnected {
+        src: String,
+        dst: String,
+        #[serde(rename = "edgeTypes")]
+        edge_types: Vec<String>,
+        #[serde(rename = "maxDepth")]
+        max_depth: u32,
     },
     Dfs {
         #[serde(rename = "startIds")]
@@ -172,6 +334,48 @@ pub enum Request {
         #[serde(rename = "edgeTypes")]
         edge_types: Option<Vec<String>>,
     },
+    /// Neighbors of `id`, each paired with the edge connecting it
+    NeighborsWithEdges {
+        id: String,
+        #[serde(rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+        /// "outgoing", "incoming", or "both"
+        direction: String,
+    },
+    /// Neighbor IDs of each input node in one round trip, deduplicated
+    /// per node. Neighbor IDs are semantic IDs on protocol v3.
+    BatchNeighbors {
+        ids: Vec<String>,
+        #[serde(default, rename = "edgeTypes")]
+        edge_types: Vec<String>,
+        /// "outgoing", "incoming", or "both"
+        direction: String,
+    },
+    /// The `n` nodes with the most matching edges, highest degree first
+    TopDegreeNodes {
+        n: u32,
+        #[serde(rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+        /// "outgoing", "incoming", or "both"
+        direction: String,
+    },
+    /// Nodes with no incoming edge of `edgeTypes` (any type when empty),
+    /// for dead-code analysis. Nodes of `rootTypes` are entry points and
+    /// never reported. IDs are returned in ascending order.
+    FindOrphans {
+        #[serde(default, rename = "edgeTypes")]
+        edge_types: Vec<String>,
+        #[serde(default, rename = "rootTypes")]
+        root_types: Vec<String>,
+    },
+    /// Induced subgraph: the given nodes plus the edges running between
+    /// them. Edges with an endpoint outside `nodeIds` are left out.
+    ExtractSubgraph {
+        #[serde(rename = "nodeIds")]
+        node_ids: Vec<String>,
+        #[serde(rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+    },
 
     // Stats
     NodeCount,
@@ -181,18 +385,70 @@ pub enum Request {
         #[serde(rename = "edgeTypes")]
         edge_types: Option<Vec<String>>,
     },
+    /// Edge counts broken down by (source node type, target node type,
+    /// edge type), for dependency matrices. `edgeTypes` supports
+    /// wildcards as in CountEdgesByType.
+    EdgeTypeMatrix {
+        #[serde(default, rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+    },
 
     // Control
-    Flush,
+    /// Flush write buffers. `durability` overrides whether the manifest
+    /// commit is fsynced; absent keeps the database's mode.
+    Flush {
+        #[serde(default)]
+        durability: Option<WireDurability>,
+    },
+    /// Flush accumulated data without rebuilding indexes (the engine's
+    /// `flush_data_only`). Send RebuildIndexes once the pipeline is done.
+    FlushDataOnly,
     Compact,
+    /// Compact a single shard (index as reported in `shardDiagnostics`)
+    CompactShard {
+        #[serde(rename = "shardId")]
+        shard_id: u16,
+    },
+    /// Flush a single shard's write buffer, leaving other shards' writes
+    /// buffered. Answers whether the shard had anything to flush.
+    FlushShard {
+        #[serde(rename = "shardId")]
+        shard_id: u16,
+    },
+    /// Estimate what background compaction would reclaim, without
+    /// compacting (v2 engine only)
+    CompactDryRun,
+    /// Redistribute all data over a new number of shards (v2 only).
+    /// Holds the database write lock for the whole rewrite.
+    Reshard {
+        #[serde(rename = "shardCount")]
+        shard_count: u16,
+    },
     Clear,
     Ping,
+    /// Stop the server: refuse further writes and connections, let active
+    /// requests finish, flush every database, then exit.
     Shutdown,
     /// Get server performance statistics
     ///
     /// Returns metrics about query latency, memory usage, and graph size.
-    /// Metrics are collected server-wide, not per-database.
+    /// Query metrics are aggregated server-wide; graph size is for the
+    /// current database.
     GetStats,
+    /// Same as GetStats, but query metrics and graph size are for the
+    /// named database only.
+    GetDatabaseStats { name: String },
+    /// Per-shard node/edge counts, segment counts and write-buffer sizes of
+    /// the current database, for diagnosing shard skew.
+    GetShardStats,
+    /// Shape of the current database for capacity planning: counts by
+    /// type, shard distribution, on-disk size and segments per level.
+    GraphSummary,
+    /// Server-wide and per-database metrics in Prometheus text format
+    MetricsPrometheus,
+    /// Detected host resources and the shard count new on-disk databases
+    /// get when created without one. Needs no open database.
+    ResourceInfo,
 
     // Bulk operations
     GetAllEdges,
@@ -204,9 +460,24 @@ pub enum Request {
         rule_source: String,
         #[serde(default)]
         explain: bool,
+        /// Evaluation deadline in milliseconds (default: 30s)
+        #[serde(default, rename = "timeoutMs")]
+        timeout_ms: Option<u64>,
     },
     DatalogLoadRules { source: String },
     DatalogClearRules,
+    /// Store Datalog rules under `name` in the database (v2 only),
+    /// replacing any previous rule set. Returns the rule count.
+    SaveRuleSet { name: String, source: String },
+    /// Evaluate a saved rule set as CheckGuarantee would
+    RunRuleSet {
+        name: String,
+        /// Evaluation deadline in milliseconds (default: 30s)
+        #[serde(default, rename = "timeoutMs")]
+        timeout_ms: Option<u64>,
+    },
+    ListRuleSets,
+    DeleteRuleSet { name: String },
     DatalogQuery {
         query: String,
         #[serde(default)]
@@ -216,6 +487,9 @@ pub enum Request {
         source: String,
         #[serde(default)]
         explain: bool,
+        /// Evaluation deadline in milliseconds (default: 30s)
+        #[serde(default, rename = "timeoutMs")]
+        timeout_ms: Option<u64>,
     },
 
     // Cypher queries
@@ -228,10 +502,30 @@ pub enum Request {
     // Node utility
     IsEndpoint { id: String },
     GetNodeIdentifier { id: String },
+    /// Hash a client-side string ID exactly as the server does when it
+    /// receives one. Needs no open database.
+    ComputeId { input: String },
     UpdateNodeVersion { id: String, version: String },
 
     // Schema declaration
     DeclareFields { fields: Vec<WireFieldDecl> },
+    /// Require/restrict metadata keys for one node type (v2 only).
+    /// Empty requiredKeys and no allowedKeys removes the schema.
+    SetMetadataSchema {
+        #[serde(rename = "nodeType")]
+        node_type: String,
+        #[serde(default, rename = "requiredKeys")]
+        required_keys: Vec<String>,
+        #[serde(default, rename = "allowedKeys")]
+        allowed_keys: Option<Vec<String>>,
+    },
+    /// Schema migration: re-insert every edge of type `from` as type `to`
+    /// (metadata kept) and tombstone the originals. Returns the count moved.
+    RenameEdgeType { from: String, to: String },
+    /// Schema migration: rewrite every node of type `from` as type `to`, then
+    /// compact so the type indexes drop the old versions. Returns the count
+    /// moved.
+    RenameNodeType { from: String, to: String },
 
     // Batch operations
     CommitBatch {
@@ -250,22 +544,112 @@ pub enum Request {
         /// Node types to preserve during deletion phase (REG-489).
         #[serde(default, rename = "protectedTypes")]
         protected_types: Vec<String>,
+        /// As for Flush; ignored with deferIndex, which does not commit.
+        #[serde(default)]
+        durability: Option<WireDurability>,
+        /// Re-check saved rule sets affected by the commit's changed types
+        /// and report the violations the commit introduced (v2 only).
+        #[serde(default, rename = "checkRuleSets")]
+        check_rule_sets: bool,
+        /// As for AddEdges: merge the batch's duplicate edges first.
+        #[serde(default, rename = "dedupEdges")]
+        dedup_edges: bool,
+    },
+
+    /// CommitBatch that only applies if the database's write version is
+    /// still `expectedVersion` (v2 engine only). Every write bumps it,
+    /// flushed or not; read it from GetDatabaseInfo or a previous
+    /// BatchCommitted. Otherwise nothing is written and VersionConflict
+    /// reports the current version.
+    CommitBatchIfUnchanged {
+        #[serde(rename = "changedFiles")]
+        changed_files: Vec<String>,
+        #[serde(rename = "expectedVersion")]
+        expected_version: u64,
+        nodes: Vec<WireNode>,
+        edges: Vec<WireEdge>,
     },
 
     /// Rebuild all secondary indexes from current segment.
     /// Send after a series of deferIndex=true CommitBatch commands.
-    RebuildIndexes,
+    /// With `nodeTypes` and/or `edgeTypes` (the commits'
+    /// `changedNodeTypes` / `changedEdgeTypes`), only the indexes affected
+    /// by nodes and edges of those types are rebuilt.
+    RebuildIndexes {
+        #[serde(default, rename = "nodeTypes")]
+        node_types: Option<Vec<String>>,
+        #[serde(default, rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+    },
 
     // ========================================================================
     // Protocol v3 Commands
     // ========================================================================
 
-    /// Begin a batch operation (session-level state)
+    /// Begin a transaction (session-level state)
+    ///
+    /// Until CommitTransaction or AbortBatch, AddNodes/AddEdges/DeleteNode/
+    /// DeleteEdge are staged in the session instead of reaching the engine.
+    /// GetNode, NodeExists, FindByType, Neighbors, Get*Edges, NodeCount and
+    /// EdgeCount on this session see the staged writes; other reads
+    /// (traversals such as Bfs/Dfs/Reachability, FindByAttr/QueryNodes,
+    /// Datalog) and other clients see committed data only.
+    ///
+    /// A write that would stage more than the session's batch byte limit
+    /// aborts the batch with `BATCH_TOO_LARGE`.
     BeginBatch,
 
-    /// Abort the current batch operation
+    /// Abort the current batch operation, discarding its staged writes
     AbortBatch,
 
+    /// Apply the current batch's staged writes atomically
+    ///
+    /// Validated under the write lock first (metadata schemas, ID
+    /// collisions, edges whose endpoints are gone); if that fails nothing
+    /// is applied and the batch stays open.
+    CommitTransaction,
+
+    /// Begin a batch spanning several databases (v2 engine only)
+    ///
+    /// Records each database's write version. StageCrossDbCommit then
+    /// queues one CommitBatch payload per database, and CommitCrossDbBatch
+    /// applies all of them or none. AbortBatch discards the batch.
+    BeginCrossDbBatch { databases: Vec<String> },
+
+    /// Queue a CommitBatch payload for one database of the cross-database
+    /// batch, replacing any payload queued for it earlier
+    StageCrossDbCommit {
+        database: String,
+        #[serde(rename = "changedFiles")]
+        changed_files: Vec<String>,
+        nodes: Vec<WireNode>,
+        edges: Vec<WireEdge>,
+        #[serde(default, rename = "fileContext")]
+        file_context: Option<String>,
+    },
+
+    /// Apply every payload of the cross-database batch, or none
+    CommitCrossDbBatch,
+
+    /// Begin a CommitBatch sent over several frames, for payloads too
+    /// large for one message. StreamCommitNodes and StreamCommitEdges add
+    /// records, FinishStreamingCommit applies them as one CommitBatch and
+    /// AbortBatch discards them. The staged data is held by the session,
+    /// up to a byte limit.
+    BeginStreamingCommit {
+        #[serde(rename = "changedFiles")]
+        changed_files: Vec<String>,
+    },
+
+    /// Add nodes to the open streaming commit
+    StreamCommitNodes { nodes: Vec<WireNode> },
+
+    /// Add edges to the open streaming commit
+    StreamCommitEdges { edges: Vec<WireEdge> },
+
+    /// Apply the open streaming commit like a CommitBatch
+    FinishStreamingCommit,
+
     /// Tag a snapshot version with key-value pairs (v2 engine only)
     TagSnapshot {
         version: u64,
@@ -294,6 +678,35 @@ pub enum Request {
         to_version: u64,
     },
 
+    /// Node-level diff of two snapshots: added/removed/modified IDs (v2 engine only)
+    DiffSnapshotsDetailed {
+        #[serde(rename = "fromVersion")]
+        from_version: u64,
+        #[serde(rename = "toVersion")]
+        to_version: u64,
+        #[serde(default, rename = "maxIds")]
+        max_ids: Option<usize>,
+    },
+
+    /// Resolve subsequent reads on this session against a historical
+    /// version until UnpinSnapshot (v2 engine only). Writes are rejected
+    /// while pinned.
+    PinSnapshot {
+        version: u64,
+    },
+
+    /// Return this session to reading the current version
+    UnpinSnapshot,
+
+    /// Roll back to a previous manifest version (v2 engine only)
+    ///
+    /// Writes not flushed yet are discarded, not carried over; Flush first
+    /// to keep them in the version being left. Ephemeral databases keep no
+    /// history and answer EPHEMERAL_UNSUPPORTED.
+    RestoreSnapshot {
+        version: u64,
+    },
+
     /// Enhanced edge query with direction and optional limit
     QueryEdges {
         id: String,
@@ -302,6 +715,10 @@ pub enum Request {
         #[serde(rename = "edgeTypes")]
         edge_types: Option<Vec<String>>,
         limit: Option<u32>,
+        /// Only edges whose metadata has these field values (v2 only).
+        /// Looked up through the edge metadata index of declared fields.
+        #[serde(default)]
+        metadata: Option<HashMap<String, String>>,
     },
 
     /// Find files that depend on a node/file
@@ -317,6 +734,53 @@ pub enum Request {
         #[serde(rename = "requestId")]
         request_id: String,
     },
+
+    // ========================================================================
+    // Import / Export Commands
+    // ========================================================================
+
+    /// Bulk-add nodes or edges from JSON-lines content.
+    /// `kind` is "nodes" (lines are WireNode) or "edges" (lines are WireEdge).
+    ImportJsonl {
+        content: String,
+        kind: String,
+    },
+
+    /// Export the graph (or the subgraph induced by `nodeIds`) as GraphML
+    ExportGraphML {
+        #[serde(default, rename = "nodeIds")]
+        node_ids: Option<Vec<String>>,
+    },
+
+    /// Export the graph (or a subgraph) as Graphviz DOT,
+    /// optionally keeping only the given edge types
+    ExportDot {
+        #[serde(default, rename = "nodeIds")]
+        node_ids: Option<Vec<String>>,
+        #[serde(default, rename = "edgeTypes")]
+        edge_types: Option<Vec<String>>,
+    },
+
+    /// Export all nodes or all edges as CSV.
+    /// `target` is "nodes" or "edges".
+    ExportCsv { target: String },
+
+    /// Dump all live nodes and edges, with the declared fields, metadata
+    /// schemas and rule sets, as an RFDB dump in binary (Dump, or DumpChunk
+    /// frames for large dumps on protocol v3+), for backups independent of
+    /// segment layout
+    ExportDatabase,
+
+    /// Restore a dump from ExportDatabase into the current database,
+    /// which must be empty. A dump larger than one frame is sent as
+    /// several ImportDatabase requests with `more: true` on all but the
+    /// last; the pieces are held by the session up to the streaming
+    /// commit byte limit.
+    ImportDatabase {
+        data: WireBytes,
+        #[serde(default)]
+        more: bool,
+    },
 }
 
 fn default_rw_mode() -> String { "rw".to_string() }
@@ -359,17 +823,55 @@ pub enum Response {
         databases: Vec<WireDatabaseInfo>,
     },
 
+    /// Response for GetDatabaseInfo
+    DatabaseInfo {
+        info: WireDatabaseInfo,
+    },
+
     CurrentDb {
         database: Option<String>,
         mode: Option<String>,
     },
 
+    SessionInfo {
+        #[serde(rename = "protocolVersion")]
+        protocol_version: u32,
+        #[serde(rename = "currentDatabase")]
+        current_database: Option<String>,
+        mode: Option<String>,
+        #[serde(rename = "clientId")]
+        client_id: usize,
+        /// ID of the open batch, if any
+        #[serde(rename = "pendingBatch")]
+        pending_batch: Option<String>,
+    },
+
     /// Structured error with code (for programmatic handling)
     ErrorWithCode {
         error: String,
         code: String,
     },
 
+    /// ErrorWithCode pointing into the request's source text
+    /// (DATALOG_PARSE); `kind` says what was wrong
+    ErrorWithPosition {
+        error: String,
+        code: String,
+        kind: String,
+        position: WireSourcePosition,
+    },
+
+    /// VERSION_CONFLICT from CommitBatchIfUnchanged or CommitCrossDbBatch:
+    /// `database`'s write version moved from `expected` to `actual`
+    VersionConflict {
+        error: String,
+        code: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        database: Option<String>,
+        expected: u64,
+        actual: u64,
+    },
+
     // ========================================================================
     // Existing Responses (unchanged)
     // ========================================================================
@@ -377,6 +879,41 @@ pub enum Response {
     BatchCommitted {
         ok: bool,
         delta: WireCommitDelta,
+        /// Rule set re-check results, when requested with checkRuleSets
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guarantees: Option<WireGuaranteeCheck>,
+        /// Write version after the commit (v2 engine only), the
+        /// `expectedVersion` for a following CommitBatchIfUnchanged
+        #[serde(skip_serializing_if = "Option::is_none")]
+        version: Option<u64>,
+    },
+
+    /// Response for CommitCrossDbBatch: one commit delta per database
+    CrossDbBatchCommitted {
+        ok: bool,
+        deltas: BTreeMap<String, WireCommitDelta>,
+    },
+
+    /// Response for CompactDryRun
+    CompactionEstimate {
+        #[serde(rename = "shardsEligible")]
+        shards_eligible: Vec<u16>,
+        #[serde(rename = "estimatedTombstonesRemoved")]
+        estimated_tombstones_removed: u64,
+        #[serde(rename = "estimatedBytesSaved")]
+        estimated_bytes_saved: u64,
+    },
+
+    ShardCompacted {
+        ok: bool,
+        #[serde(rename = "shardId")]
+        shard_id: u16,
+        #[serde(rename = "nodesMerged")]
+        nodes_merged: u64,
+        #[serde(rename = "edgesMerged")]
+        edges_merged: u64,
+        #[serde(rename = "durationMs")]
+        duration_ms: u64,
     },
 
     Ok { ok: bool },
@@ -392,15 +929,65 @@ pub enum Response {
     },
     Nodes { nodes: Vec<WireNode> },
     Edges { edges: Vec<WireEdge> },
+    Levels { levels: Vec<Vec<String>> },
+    /// Closest matching nodes and their BFS distance; empty `matches` and
+    /// no distance when nothing matched (NearestMatching)
+    Nearest {
+        matches: Vec<String>,
+        distance: Option<u32>,
+    },
+    /// Node ID groups, ordered by smallest member (WeaklyConnectedComponents)
+    Components { components: Vec<Vec<String>> },
+    /// Node ID groups with identical content, ordered by smallest member
+    /// (FindDuplicateContent)
+    DuplicateGroups { groups: Vec<Vec<String>> },
+    Subgraph { nodes: Vec<WireNode>, edges: Vec<WireEdge> },
+    Neighbors { entries: Vec<WireNeighbor> },
+    /// (input ID, neighbor IDs) pairs in input order (BatchNeighbors)
+    NeighborMap { entries: Vec<(String, Vec<String>)> },
+    /// (node ID, score) pairs, highest score first (TopDegreeNodes)
+    Scores { scores: Vec<(String, f64)> },
     Ids { ids: Vec<String> },
+    /// FindByAttr with perShardCounts: IDs plus each shard's share
+    IdsWithShardCounts {
+        ids: Vec<String>,
+        #[serde(rename = "shardCounts")]
+        shard_counts: Vec<WireShardCount>,
+    },
     Bool { value: bool },
-    Count { count: u32 },
+    /// One answer per input, in input order (EdgesExist)
+    Bools { values: Vec<bool> },
+    Count {
+        count: u32,
+        /// Input lines that could not be parsed (ImportJsonl only)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        errors: Option<u32>,
+    },
+    /// Records restored by ImportDatabase
+    DumpImported {
+        #[serde(rename = "nodesImported")]
+        nodes_imported: u32,
+        #[serde(rename = "edgesImported")]
+        edges_imported: u32,
+    },
     Counts { counts: HashMap<String, usize> },
+    /// (value, count) pairs, most frequent first (DistinctValues)
+    Facets { values: Vec<(String, usize)> },
+    /// (src node type, dst node type, edge type, count) cells, most
+    /// frequent first (EdgeTypeMatrix)
+    Matrix { cells: Vec<(String, String, String, usize)> },
     Pong { pong: bool, version: String },
     Violations { violations: Vec<WireViolation> },
+    /// Saved rule set names, sorted (ListRuleSets)
+    RuleSetList {
+        #[serde(rename = "ruleSets")]
+        rule_sets: Vec<String>,
+    },
     Identifier { identifier: Option<String> },
     DatalogResults { results: Vec<WireViolation> },
     ExplainResult(WireExplainResult),
+    /// Textual FindByAttr plan (ExplainQuery)
+    Explain { plan: String },
     CypherResult {
         columns: Vec<String>,
         rows: Vec<Vec<serde_json::Value>>,
@@ -434,13 +1021,67 @@ pub enum Response {
         diff: WireSnapshotDiff,
     },
 
+    /// Response for DiffSnapshotsDetailed
+    SnapshotNodeDiffResult {
+        diff: WireSnapshotNodeDiff,
+    },
+
     /// Response for FindDependentFiles
     Files {
         files: Vec<String>,
     },
 
+    /// Serialized graph export (GraphML, ...)
+    Export {
+        format: String,
+        content: String,
+    },
+
+    /// RFDB dump (ExportDatabase)
+    Dump { data: WireBytes },
+
+    /// Streaming chunk of an RFDB dump. Sent instead of `Dump` for large
+    /// dumps on protocol v3+; the client concatenates `data` until `done`
+    /// is true.
+    DumpChunk {
+        data: WireBytes,
+        done: bool,
+        #[serde(rename = "chunkIndex")]
+        chunk_index: u32,
+    },
+
+    /// Streaming chunk of an export document.
+    /// Sent instead of `Export` for large documents on protocol v3+;
+    /// the client concatenates `content` until `done` is true.
+    ExportChunk {
+        format: String,
+        content: String,
+        done: bool,
+        #[serde(rename = "chunkIndex")]
+        chunk_index: u32,
+    },
+
+    /// Response for ResourceInfo
+    ResourceInfo {
+        #[serde(rename = "totalMemoryBytes")]
+        total_memory_bytes: u64,
+        #[serde(rename = "availableMemoryBytes")]
+        available_memory_bytes: u64,
+        #[serde(rename = "cpuCount")]
+        cpu_count: usize,
+        #[serde(rename = "recommendedShardCount")]
+        recommended_shard_count: u16,
+        /// --default-shards, which takes precedence over the recommendation
+        #[serde(rename = "defaultShardCount")]
+        default_shard_count: Option<u16>,
+    },
+
     /// Performance statistics response
     Stats {
+        /// Database the query metrics belong to (None = server-wide)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        database: Option<String>,
+
         // Graph size
         #[serde(rename = "nodeCount")]
         node_count: u64,
@@ -464,6 +1105,13 @@ pub enum Response {
         query_p95_ms: u64,
         #[serde(rename = "queryP99Ms")]
         query_p99_ms: u64,
+        /// Cumulative (upperBoundMs, count) pairs; the last bound is
+        /// u64::MAX for +Inf.
+        #[serde(rename = "latencyBuckets")]
+        latency_buckets: Vec<(u64, u64)>,
+        /// Query count per operation name (as in topSlowQueries)
+        #[serde(rename = "operationCounts")]
+        operation_counts: HashMap<String, u64>,
 
         // Flush stats
         #[serde(rename = "flushCount")]
@@ -485,6 +1133,18 @@ pub enum Response {
         #[serde(rename = "cancelledCount")]
         cancelled_count: u64,
 
+        // Background compaction
+        #[serde(rename = "autoCompactionCount")]
+        auto_compaction_count: u64,
+
+        /// Nodes written with metadata that is not valid JSON
+        #[serde(rename = "malformedMetadataCount")]
+        malformed_metadata_count: u64,
+
+        /// Nodes whose ID was already held by a different semantic ID
+        #[serde(rename = "idCollisionCount")]
+        id_collision_count: u64,
+
         // Uptime
         #[serde(rename = "uptimeSecs")]
         uptime_secs: u64,
@@ -493,6 +1153,28 @@ pub enum Response {
         #[serde(rename = "shardDiagnostics")]
         shard_diagnostics: Vec<WireShardDiagnostics>,
     },
+
+    /// Response for GetShardStats
+    ShardStats {
+        shards: Vec<WireShardStats>,
+    },
+
+    /// Response for GraphSummary
+    GraphSummary {
+        #[serde(rename = "nodeCount")]
+        node_count: usize,
+        #[serde(rename = "edgeCount")]
+        edge_count: usize,
+        #[serde(rename = "nodesByType")]
+        nodes_by_type: HashMap<String, usize>,
+        #[serde(rename = "edgesByType")]
+        edges_by_type: HashMap<String, usize>,
+        shards: Vec<WireShardStats>,
+        #[serde(rename = "diskBytes")]
+        disk_bytes: u64,
+        #[serde(rename = "segmentsByLevel")]
+        segments_by_level: WireSegmentsByLevel,
+    },
 }
 
 /// Request envelope: captures requestId alongside the tagged Request.
@@ -522,6 +1204,9 @@ pub struct WireDatabaseInfo {
     node_count: usize,
     edge_count: usize,
     connection_count: usize,
+    /// `expectedVersion` for CommitBatchIfUnchanged (v2 engine only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    write_version: Option<u64>,
 }
 
 impl From<DatabaseInfo> for WireDatabaseInfo {
@@ -532,6 +1217,7 @@ impl From<DatabaseInfo> for WireDatabaseInfo {
             node_count: info.node_count,
             edge_count: info.edge_count,
             connection_count: info.connection_count,
+            write_version: info.write_version,
         }
     }
 }
@@ -601,6 +1287,9 @@ pub struct WireSlowQuery {
     pub operation: String,
     pub duration_ms: u64,
     pub timestamp_ms: u64,
+    /// Summary of the request parameters (e.g. `nodeType=FUNCTION`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<String>,
 }
 
 /// Node representation for wire protocol
@@ -623,6 +1312,17 @@ pub struct WireNode {
     pub exported: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<String>,
+    /// Soft-deleted (tombstoned). Only ever true on `includeDeleted` reads;
+    /// ignored on writes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+}
+
+/// Neighbor node together with the edge that reaches it (NeighborsWithEdges)
+#[derive(Debug, Serialize)]
+pub struct WireNeighbor {
+    pub node: WireNode,
+    pub edge: WireEdge,
 }
 
 /// Edge representation for wire protocol
@@ -637,6 +1337,76 @@ pub struct WireEdge {
     pub metadata: Option<String>,
 }
 
+/// Raw bytes for wire protocol, carried as a MessagePack `bin` (a plain
+/// array of integers is accepted too) instead of a base64 string
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WireBytes(pub Vec<u8>);
+
+impl Serialize for WireBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for WireBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = WireBytes;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("binary data")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<WireBytes, E> {
+                Ok(WireBytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<WireBytes, E> {
+                Ok(WireBytes(v))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<WireBytes, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(WireBytes(bytes))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+/// Edge identity for wire protocol (EdgesExist)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WireEdgeKey {
+    pub src: String,
+    pub dst: String,
+    pub edge_type: String,
+}
+
+/// Manifest commit durability for wire protocol (Flush, CommitBatch).
+/// "sync" fsyncs the commit; "async" skips fsync, so a crash may lose it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireDurability {
+    Sync,
+    Async,
+}
+
+impl From<WireDurability> for DurabilityMode {
+    fn from(durability: WireDurability) -> Self {
+        match durability {
+            WireDurability::Sync => DurabilityMode::Strict,
+            WireDurability::Async => DurabilityMode::Relaxed,
+        }
+    }
+}
+
 /// Attribute query for wire protocol.
 /// Known fields are deserialized into typed fields;
 /// any extra fields (e.g. "object", "method") are captured in `extra`
@@ -650,6 +1420,9 @@ pub struct WireAttrQuery {
     pub exported: Option<bool>,
     #[serde(default)]
     pub substring_match: bool,
+    /// Return IDs sorted ascending, for reproducible output
+    #[serde(default)]
+    pub sorted: bool,
     /// Extra fields are matched against node metadata JSON.
     #[serde(flatten)]
     pub extra: std::collections::HashMap<String, serde_json::Value>,
@@ -664,6 +1437,9 @@ pub struct WireFieldDecl {
     pub field_type: Option<String>,
     #[serde(default)]
     pub node_types: Option<Vec<String>>,
+    /// "node" (default) or "edge"
+    #[serde(default)]
+    pub entity: Option<String>,
 }
 
 /// Structured diff returned by CommitBatch handler.
@@ -683,6 +1459,21 @@ pub struct WireCommitDelta {
     pub changed_edge_types: Vec<String>,
 }
 
+/// Saved rule sets re-checked after a commit (CommitBatch checkRuleSets)
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WireGuaranteeCheck {
+    /// Rule sets with at least one rule affected by the commit
+    pub checked: Vec<String>,
+    /// Rule sets no rule of which can see the changed types
+    pub skipped: Vec<String>,
+    /// Violations new since before the commit, per checked rule set;
+    /// sets without any are omitted
+    pub violations: BTreeMap<String, Vec<WireViolation>>,
+    /// Rule sets whose evaluation failed
+    pub errors: BTreeMap<String, String>,
+}
+
 /// Snapshot info for wire protocol (v2 engine only)
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -708,6 +1499,21 @@ pub struct WireSnapshotDiff {
     pub stats_to: WireManifestStats,
 }
 
+/// Node-level snapshot diff for wire protocol (v2 engine only)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WireSnapshotNodeDiff {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub added_count: usize,
+    pub removed_count: usize,
+    pub modified_count: usize,
+    pub truncated: bool,
+}
+
 /// Manifest stats for wire protocol
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -730,8 +1536,11 @@ pub struct WireShardDiagnostics {
     pub l0_edge_segment_count: usize,
     pub l1_node_records: usize,
     pub l1_edge_records: usize,
+    pub l2_node_records: usize,
+    pub l2_edge_records: usize,
     pub tombstone_node_count: usize,
     pub tombstone_edge_count: usize,
+    pub tombstoned_records: usize,
     pub has_l1_by_type: bool,
     pub has_l1_by_file: bool,
     pub has_l1_by_name: bool,
@@ -741,14 +1550,86 @@ pub struct WireShardDiagnostics {
     pub has_edge_type_index: bool,
 }
 
-// ============================================================================
-// ID Conversion (string <-> u128)
-// ============================================================================
+/// Matches one shard contributed to a FindByAttr result (wire protocol)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WireShardCount {
+    pub shard_id: u16,
+    pub count: usize,
+}
 
-fn string_to_id(s: &str) -> u128 {
-    // Try parsing as number first
-    if let Ok(id) = s.parse::<u128>() {
-        return id;
+/// 1-based line and column (in characters) within a request's source text
+#[derive(Debug, Serialize)]
+pub struct WireSourcePosition {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Per-shard size summary for wire protocol (GetShardStats)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WireShardStats {
+    pub shard_id: u16,
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub l0_node_segment_count: usize,
+    pub l0_edge_segment_count: usize,
+    pub write_buffer_nodes: usize,
+    pub write_buffer_edges: usize,
+}
+
+impl From<ShardDiagnostics> for WireShardStats {
+    fn from(stats: ShardDiagnostics) -> Self {
+        WireShardStats {
+            shard_id: stats.shard_id,
+            node_count: stats.node_count,
+            edge_count: stats.edge_count,
+            l0_node_segment_count: stats.l0_node_segment_count,
+            l0_edge_segment_count: stats.l0_edge_segment_count,
+            write_buffer_nodes: stats.write_buffer_nodes,
+            write_buffer_edges: stats.write_buffer_edges,
+        }
+    }
+}
+
+/// Segments in the current manifest per level, nodes and edges together
+/// (GraphSummary). Always zero for unsharded engines.
+#[derive(Debug, Default, Serialize)]
+pub struct WireSegmentsByLevel {
+    pub l0: usize,
+    pub l1: usize,
+    pub l2: usize,
+}
+
+/// Per-shard stats of `engine`. Unsharded engines report everything as
+/// one logical shard 0.
+fn wire_shard_stats(engine: &dyn GraphStore) -> Vec<WireShardStats> {
+    let mut shards: Vec<WireShardStats> = engine.shard_diagnostics()
+        .into_iter()
+        .map(WireShardStats::from)
+        .collect();
+    if shards.is_empty() {
+        shards.push(WireShardStats {
+            shard_id: 0,
+            node_count: engine.node_count(),
+            edge_count: engine.edge_count(),
+            l0_node_segment_count: 0,
+            l0_edge_segment_count: 0,
+            write_buffer_nodes: 0,
+            write_buffer_edges: 0,
+        });
+    }
+    shards
+}
+
+// ============================================================================
+// ID Conversion (string <-> u128)
+// ============================================================================
+
+fn string_to_id(s: &str) -> u128 {
+    // Try parsing as number first
+    if let Ok(id) = s.parse::<u128>() {
+        return id;
     }
     // Otherwise hash the string
     rfdb::graph::string_id_to_u128(s)
@@ -786,15 +1667,16 @@ fn wire_node_to_record(node: WireNode) -> NodeRecord {
     }
 }
 
-fn record_to_wire_node(record: &NodeRecord) -> WireNode {
+fn record_to_wire_node(record: NodeRecord) -> WireNode {
     WireNode {
         id: id_to_string(record.id),
-        semantic_id: record.semantic_id.clone(),
-        node_type: record.node_type.clone(),
-        name: record.name.clone(),
-        file: record.file.clone(),
+        semantic_id: record.semantic_id,
+        node_type: record.node_type,
+        name: record.name,
+        file: record.file,
         exported: record.exported,
-        metadata: record.metadata.clone(),
+        metadata: record.metadata,
+        deleted: record.deleted,
     }
 }
 
@@ -809,12 +1691,12 @@ fn wire_edge_to_record(edge: WireEdge) -> EdgeRecord {
     }
 }
 
-fn record_to_wire_edge(record: &EdgeRecord) -> WireEdge {
+fn record_to_wire_edge(record: EdgeRecord) -> WireEdge {
     WireEdge {
         src: id_to_string(record.src),
         dst: id_to_string(record.dst),
-        edge_type: record.edge_type.clone(),
-        metadata: record.metadata.clone(),
+        edge_type: record.edge_type,
+        metadata: record.metadata,
     }
 }
 
@@ -839,6 +1721,18 @@ fn resolve_edge_semantic_ids(edges: &mut [WireEdge], engine: &dyn GraphStore) {
     }
 }
 
+/// Resolve numeric node ID strings to semantic IDs using node lookups.
+/// For v3 protocol: IDs of nodes without a semantic ID stay numeric.
+fn resolve_node_semantic_ids(ids: &mut [String], engine: &dyn GraphStore) {
+    for id in ids.iter_mut() {
+        if let Ok(node_id) = id.parse::<u128>() {
+            if let Some(sid) = engine.get_node(node_id).and_then(|node| node.semantic_id) {
+                *id = sid;
+            }
+        }
+    }
+}
+
 /// Convert a `WireAttrQuery` (wire format) into an `AttrQuery` (engine format).
 ///
 /// Handles:
@@ -866,6 +1760,7 @@ fn wire_to_attr_query(query: WireAttrQuery) -> AttrQuery {
         name: query.name,
         metadata_filters,
         substring_match: query.substring_match,
+        sorted: query.sorted,
     }
 }
 
@@ -873,6 +1768,162 @@ fn wire_to_attr_query(query: WireAttrQuery) -> AttrQuery {
 // Memory Check Helper
 // ============================================================================
 
+/// Build a `Response::Stats` from a metrics snapshot plus graph stats of
+/// `db`. `database` names the owner of the metrics (None = server-wide).
+fn stats_response(
+    metrics_snapshot: MetricsSnapshot,
+    db: Option<&Database>,
+    database: Option<String>,
+) -> Response {
+    // Graph stats from the given database (if any)
+    let (node_count, edge_count, delta_size, shard_diags) = if let Some(db) = db {
+        let engine = db.engine.read().unwrap();
+        let ops = 0u64;
+        let diags: Vec<WireShardDiagnostics> = engine.shard_diagnostics()
+            .into_iter()
+            .map(|d| WireShardDiagnostics {
+                shard_id: d.shard_id,
+                node_count: d.node_count,
+                edge_count: d.edge_count,
+                write_buffer_nodes: d.write_buffer_nodes,
+                write_buffer_edges: d.write_buffer_edges,
+                compacted: d.compacted,
+                l0_node_segment_count: d.l0_node_segment_count,
+                l0_edge_segment_count: d.l0_edge_segment_count,
+                l1_node_records: d.l1_node_records,
+                l1_edge_records: d.l1_edge_records,
+                l2_node_records: d.l2_node_records,
+                l2_edge_records: d.l2_edge_records,
+                tombstone_node_count: d.tombstone_node_count,
+                tombstone_edge_count: d.tombstone_edge_count,
+                tombstoned_records: d.tombstoned_records,
+                has_l1_by_type: d.has_l1_by_type,
+                has_l1_by_file: d.has_l1_by_file,
+                has_l1_by_name: d.has_l1_by_name,
+                l1_by_type_keys: d.l1_by_type_keys,
+                l1_by_file_keys: d.l1_by_file_keys,
+                l1_by_name_keys: d.l1_by_name_keys,
+                has_edge_type_index: d.has_edge_type_index,
+            })
+            .collect();
+        (
+            engine.node_count() as u64,
+            engine.edge_count() as u64,
+            ops,
+            diags,
+        )
+    } else {
+        // No database selected - return zeros
+        (0, 0, 0, vec![])
+    };
+
+    // Get system memory
+    let memory_percent = check_memory_usage();
+
+    Response::Stats {
+        database,
+        node_count,
+        edge_count,
+        delta_size,
+        memory_percent,
+        query_count: metrics_snapshot.query_count,
+        slow_query_count: metrics_snapshot.slow_query_count,
+        query_p50_ms: metrics_snapshot.query_p50_ms,
+        query_p95_ms: metrics_snapshot.query_p95_ms,
+        query_p99_ms: metrics_snapshot.query_p99_ms,
+        latency_buckets: metrics_snapshot.latency_buckets,
+        operation_counts: metrics_snapshot.operation_counts,
+        flush_count: metrics_snapshot.flush_count,
+        last_flush_ms: metrics_snapshot.last_flush_ms,
+        last_flush_nodes: metrics_snapshot.last_flush_nodes,
+        last_flush_edges: metrics_snapshot.last_flush_edges,
+        top_slow_queries: metrics_snapshot.top_slow_queries.into_iter()
+            .map(|sq| WireSlowQuery {
+                operation: sq.operation,
+                duration_ms: sq.duration_ms,
+                timestamp_ms: sq.timestamp_ms,
+                params: sq.params,
+            })
+            .collect(),
+        timed_out_count: metrics_snapshot.timed_out_count,
+        cancelled_count: metrics_snapshot.cancelled_count,
+        auto_compaction_count: metrics_snapshot.auto_compaction_count,
+        malformed_metadata_count: metrics_snapshot.malformed_metadata_count,
+        id_collision_count: metrics_snapshot.id_collision_count,
+        uptime_secs: metrics_snapshot.uptime_secs,
+        shard_diagnostics: shard_diags,
+    }
+}
+
+/// Record a finished request in the server-wide metrics and, if a database
+/// is selected, in that database's own metrics.
+fn record_request_metrics(
+    metrics: &Metrics,
+    db: Option<&Database>,
+    op_name: &str,
+    params: Option<&str>,
+    duration_ms: u64,
+    error: Option<&str>,
+) {
+    for m in std::iter::once(metrics).chain(db.map(|db| &db.metrics)) {
+        m.record_query_with_params(op_name, duration_ms, params);
+        if let Some(error) = error {
+            if error.contains("timeout") || error.contains("deadline exceeded") {
+                m.record_timeout();
+            } else if error.contains("cancelled") {
+                m.record_cancelled();
+            }
+        }
+    }
+}
+
+/// Short summary of what a request asks for, kept with slow queries so
+/// they can be reproduced. None for requests without interesting
+/// parameters.
+fn request_param_summary(request: &Request) -> Option<String> {
+    let attr_query = |query: &WireAttrQuery| {
+        let mut parts = Vec::new();
+        if let Some(t) = &query.node_type {
+            parts.push(format!("nodeType={}", t));
+        }
+        if let Some(n) = &query.name {
+            parts.push(format!("name={}", n));
+        }
+        if let Some(f) = &query.file {
+            parts.push(format!("file={}", f));
+        }
+        if !query.extra.is_empty() {
+            parts.push(format!("extraFilters={}", query.extra.len()));
+        }
+        parts.join(" ")
+    };
+    let traversal = |start_ids: &[String], max_depth: u32, edge_types: &[String]| {
+        format!("startIds={} maxDepth={} edgeTypes={}", start_ids.len(), max_depth, edge_types.join(","))
+    };
+    let summary = match request {
+        Request::FindByType { node_type } => format!("nodeType={}", node_type),
+        Request::FindByAttr { query, .. }
+        | Request::CountByAttr { query }
+        | Request::QueryNodes { query } => attr_query(query),
+        Request::Bfs { start_ids, max_depth, edge_types }
+        | Request::KHop { start_ids, max_depth, edge_types }
+        | Request::Dfs { start_ids, max_depth, edge_types }
+        | Request::Reachability { start_ids, max_depth, edge_types, .. } => {
+            traversal(start_ids, *max_depth, edge_types)
+        }
+        Request::GetNode { id, .. }
+        | Request::Neighbors { id, .. }
+        | Request::GetOutgoingEdges { id, .. }
+        | Request::GetIncomingEdges { id, .. } => format!("id={}", id),
+        Request::AddNodes { nodes } | Request::AddNodesIfAbsent { nodes } => format!("nodes={}", nodes.len()),
+        Request::AddEdges { edges, .. } => format!("edges={}", edges.len()),
+        Request::DatalogQuery { query, .. } | Request::CypherQuery { query, .. } => query.clone(),
+        Request::CheckGuarantee { rule_source, .. } => rule_source.clone(),
+        _ => return None,
+    };
+    Some(summary)
+}
+
 /// Check system memory usage percentage.
 ///
 /// Uses sysinfo crate to query system memory. Returns 0.0 if unable to query.
@@ -887,6 +1938,49 @@ fn check_memory_usage() -> f32 {
     (used as f64 / total as f64 * 100.0) as f32
 }
 
+impl Request {
+    /// Whether this request modifies a database (or the set of databases).
+    /// These are refused once the server starts draining for shutdown.
+    fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Request::CreateDatabase { .. }
+                | Request::DropDatabase { .. }
+                | Request::RenameDatabase { .. }
+                | Request::CopyDatabase { .. }
+                | Request::AddNodes { .. }
+                | Request::AddNodesIfAbsent { .. }
+                | Request::AddEdges { .. }
+                | Request::DeleteNode { .. }
+                | Request::DeleteEdge { .. }
+                | Request::Flush { .. }
+                | Request::FlushDataOnly
+                | Request::Compact
+                | Request::CompactShard { .. }
+                | Request::FlushShard { .. }
+                | Request::Reshard { .. }
+                | Request::Clear
+                | Request::UpdateNodeVersion { .. }
+                | Request::DeclareFields { .. }
+                | Request::SetMetadataSchema { .. }
+                | Request::SaveRuleSet { .. }
+                | Request::DeleteRuleSet { .. }
+                | Request::RenameEdgeType { .. }
+                | Request::RenameNodeType { .. }
+                | Request::CommitBatch { .. }
+                | Request::CommitBatchIfUnchanged { .. }
+                | Request::RebuildIndexes { .. }
+                | Request::CommitTransaction
+                | Request::CommitCrossDbBatch
+                | Request::FinishStreamingCommit
+                | Request::TagSnapshot { .. }
+                | Request::RestoreSnapshot { .. }
+                | Request::ImportJsonl { .. }
+                | Request::ImportDatabase { .. }
+        )
+    }
+}
+
 // ============================================================================
 // Operation Name Helper
 // ============================================================================
@@ -897,33 +1991,76 @@ fn check_memory_usage() -> f32 {
 fn get_operation_name(request: &Request) -> String {
     match request {
         Request::Bfs { .. } => "Bfs".to_string(),
+        Request::KHop { .. } => "KHop".to_string(),
+        Request::NearestMatching { .. } => "NearestMatching".to_string(),
+        Request::WeaklyConnectedComponents { .. } => "WeaklyConnectedComponents".to_string(),
+        Request::AggregateEdges { .. } => "AggregateEdges".to_string(),
         Request::Dfs { .. } => "Dfs".to_string(),
         Request::Neighbors { .. } => "Neighbors".to_string(),
         Request::Reachability { .. } => "Reachability".to_string(),
+        Request::AreConnected { .. } => "AreConnected".to_string(),
         Request::FindByType { .. } => "FindByType".to_string(),
+        Request::FindDuplicateContent { .. } => "FindDuplicateContent".to_string(),
+        Request::FindEdgesByMetadata { .. } => "FindEdgesByMetadata".to_string(),
+        Request::AutocompleteNames { .. } => "AutocompleteNames".to_string(),
         Request::FindByAttr { .. } => "FindByAttr".to_string(),
+        Request::CountByAttr { .. } => "CountByAttr".to_string(),
+        Request::EdgeExists { .. } => "EdgeExists".to_string(),
+        Request::EdgesExist { .. } => "EdgesExist".to_string(),
+        Request::NeighborsWithEdges { .. } => "NeighborsWithEdges".to_string(),
+        Request::BatchNeighbors { .. } => "BatchNeighbors".to_string(),
+        Request::TopDegreeNodes { .. } => "TopDegreeNodes".to_string(),
+        Request::FindOrphans { .. } => "FindOrphans".to_string(),
+        Request::EdgeTypeMatrix { .. } => "EdgeTypeMatrix".to_string(),
+        Request::ExtractSubgraph { .. } => "ExtractSubgraph".to_string(),
+        Request::DistinctValues { .. } => "DistinctValues".to_string(),
+        Request::ExplainQuery { .. } => "ExplainQuery".to_string(),
         Request::GetNode { .. } => "GetNode".to_string(),
         Request::AddNodes { .. } => "AddNodes".to_string(),
+        Request::AddNodesIfAbsent { .. } => "AddNodesIfAbsent".to_string(),
         Request::AddEdges { .. } => "AddEdges".to_string(),
         Request::DatalogQuery { .. } => "DatalogQuery".to_string(),
         Request::CheckGuarantee { .. } => "CheckGuarantee".to_string(),
+        Request::RunRuleSet { .. } => "RunRuleSet".to_string(),
         Request::GetOutgoingEdges { .. } => "GetOutgoingEdges".to_string(),
         Request::GetIncomingEdges { .. } => "GetIncomingEdges".to_string(),
-        Request::Flush => "Flush".to_string(),
+        Request::Flush { .. } => "Flush".to_string(),
+        Request::FlushDataOnly => "FlushDataOnly".to_string(),
         Request::Compact => "Compact".to_string(),
+        Request::CompactShard { .. } => "CompactShard".to_string(),
+        Request::FlushShard { .. } => "FlushShard".to_string(),
+        Request::Reshard { .. } => "Reshard".to_string(),
         Request::NodeCount => "NodeCount".to_string(),
         Request::EdgeCount => "EdgeCount".to_string(),
         Request::GetStats => "GetStats".to_string(),
+        Request::GetDatabaseStats { .. } => "GetDatabaseStats".to_string(),
+        Request::GetShardStats => "GetShardStats".to_string(),
+        Request::GraphSummary => "GraphSummary".to_string(),
+        Request::MetricsPrometheus => "MetricsPrometheus".to_string(),
+        Request::ResourceInfo => "ResourceInfo".to_string(),
         Request::CommitBatch { .. } => "CommitBatch".to_string(),
-        Request::RebuildIndexes => "RebuildIndexes".to_string(),
+        Request::CommitBatchIfUnchanged { .. } => "CommitBatchIfUnchanged".to_string(),
+        Request::CommitTransaction => "CommitTransaction".to_string(),
+        Request::FinishStreamingCommit => "FinishStreamingCommit".to_string(),
+        Request::RebuildIndexes { .. } => "RebuildIndexes".to_string(),
         Request::TagSnapshot { .. } => "TagSnapshot".to_string(),
         Request::FindSnapshot { .. } => "FindSnapshot".to_string(),
         Request::ListSnapshots { .. } => "ListSnapshots".to_string(),
         Request::DiffSnapshots { .. } => "DiffSnapshots".to_string(),
+        Request::DiffSnapshotsDetailed { .. } => "DiffSnapshotsDetailed".to_string(),
+        Request::RestoreSnapshot { .. } => "RestoreSnapshot".to_string(),
+        Request::PinSnapshot { .. } => "PinSnapshot".to_string(),
+        Request::UnpinSnapshot => "UnpinSnapshot".to_string(),
         Request::QueryEdges { .. } => "QueryEdges".to_string(),
         Request::FindDependentFiles { .. } => "FindDependentFiles".to_string(),
         Request::CancelQuery { .. } => "CancelQuery".to_string(),
         Request::CypherQuery { .. } => "CypherQuery".to_string(),
+        Request::ImportJsonl { .. } => "ImportJsonl".to_string(),
+        Request::ExportGraphML { .. } => "ExportGraphML".to_string(),
+        Request::ExportDot { .. } => "ExportDot".to_string(),
+        Request::ExportCsv { .. } => "ExportCsv".to_string(),
+        Request::ExportDatabase => "ExportDatabase".to_string(),
+        Request::ImportDatabase { .. } => "ImportDatabase".to_string(),
         _ => "Other".to_string(),
     }
 }
@@ -948,23 +2085,40 @@ fn handle_request_with_cancel(
     metrics: &Option<Arc<Metrics>>,
     cancel_flag: Arc<AtomicBool>,
 ) -> Response {
+    if manager.is_draining() && request.is_write() {
+        return Response::ErrorWithCode {
+            error: "Server is shutting down; writes are no longer accepted".to_string(),
+            code: "SERVER_DRAINING".to_string(),
+        };
+    }
+    if manager.is_read_only() && request.is_write() {
+        return Response::ErrorWithCode {
+            error: "Server is running with --read-only".to_string(),
+            code: "READ_ONLY_MODE".to_string(),
+        };
+    }
+
     match request {
         // ====================================================================
         // Database Management Commands
         // ====================================================================
 
-        Request::Hello { protocol_version, client_id: _ } => {
+        Request::Hello { protocol_version, client_id: _, heartbeat } => {
             session.protocol_version = protocol_version.unwrap_or(2);
+            session.heartbeat = heartbeat;
             Response::HelloOk {
                 ok: true,
                 protocol_version: 3,
                 server_version: env!("CARGO_PKG_VERSION").to_string(),
-                features: vec!["multiDatabase".to_string(), "ephemeral".to_string(), "semanticIds".to_string(), "streaming".to_string()],
+                features: vec![
+                    "multiDatabase".to_string(), "ephemeral".to_string(), "semanticIds".to_string(),
+                    "streaming".to_string(), "heartbeat".to_string(),
+                ],
             }
         }
 
-        Request::CreateDatabase { name, ephemeral } => {
-            match manager.create_database(&name, ephemeral) {
+        Request::CreateDatabase { name, ephemeral, shard_count } => {
+            match manager.create_database_with_shards(&name, ephemeral, shard_count) {
                 Ok(()) => Response::DatabaseCreated {
                     ok: true,
                     database_id: name,
@@ -982,7 +2136,7 @@ fn handle_request_with_cancel(
                 handle_close_database(manager, session);
             }
 
-            let access_mode = AccessMode::from_str(&mode);
+            let access_mode = manager.effective_mode(AccessMode::from_str(&mode));
 
             match manager.get_database(&name) {
                 Ok(db) => {
@@ -1030,6 +2184,26 @@ fn handle_request_with_cancel(
             }
         }
 
+        Request::RenameDatabase { from, to } => {
+            match manager.rename_database(&from, &to) {
+                Ok(()) => Response::Ok { ok: true },
+                Err(e) => Response::ErrorWithCode {
+                    error: e.to_string(),
+                    code: e.code().to_string(),
+                },
+            }
+        }
+
+        Request::CopyDatabase { from, to } => {
+            match manager.copy_database(&from, &to) {
+                Ok(()) => Response::Ok { ok: true },
+                Err(e) => Response::ErrorWithCode {
+                    error: e.to_string(),
+                    code: e.code().to_string(),
+                },
+            }
+        }
+
         Request::ListDatabases => {
             let databases: Vec<WireDatabaseInfo> = manager.list_databases()
                 .into_iter()
@@ -1038,6 +2212,20 @@ fn handle_request_with_cancel(
             Response::DatabaseList { databases }
         }
 
+        Request::DatabaseExists { name } => {
+            Response::Bool { value: manager.database_exists(&name) }
+        }
+
+        Request::GetDatabaseInfo { name } => {
+            match manager.database_info(&name) {
+                Ok(info) => Response::DatabaseInfo { info: info.into() },
+                Err(e) => Response::ErrorWithCode {
+                    error: e.to_string(),
+                    code: e.code().to_string(),
+                },
+            }
+        }
+
         Request::CurrentDatabase => {
             Response::CurrentDb {
                 database: session.current_db_name().map(|s| s.to_string()),
@@ -1045,56 +2233,167 @@ fn handle_request_with_cancel(
             }
         }
 
+        Request::SessionInfo => {
+            Response::SessionInfo {
+                protocol_version: session.protocol_version,
+                current_database: session.current_db_name().map(|s| s.to_string()),
+                mode: session.current_db.as_ref().map(|_| session.access_mode.as_str().to_string()),
+                client_id: session.id,
+                pending_batch: session.pending_batch_id().map(|s| s.to_string()),
+            }
+        }
+
         // ====================================================================
         // Data Operations (require database)
         // ====================================================================
 
         Request::AddNodes { nodes } => {
-            with_engine_write(session, |engine| {
-                let records: Vec<NodeRecord> = nodes.into_iter().map(wire_node_to_record).collect();
-                engine.add_nodes(records);
+            let records: Vec<NodeRecord> = nodes.into_iter().map(wire_node_to_record).collect();
+            note_malformed_metadata(metrics, &records);
+            let ids = IdCheck::new(manager, metrics);
+            if session.pending_batch.is_none() || !session.has_database() {
+                return with_engine_write(session, |engine| {
+                    if let Some(rejection) = ingest_rejection(&*engine, &records, ids) {
+                        return rejection;
+                    }
+                    engine.add_nodes(records);
+                    Response::Ok { ok: true }
+                });
+            }
+            // Early schema feedback; CommitTransaction repeats the full
+            // check under the write lock
+            if let Some(rejection) = staging_schema_rejection(session, &records) {
+                return rejection;
+            }
+            stage_or_apply(session, WriteOp::AddNodes(records))
+        }
+
+        Request::AddNodesIfAbsent { nodes } => {
+            let records: Vec<NodeRecord> = nodes.into_iter().map(wire_node_to_record).collect();
+            note_malformed_metadata(metrics, &records);
+            if session.pending_batch.is_none() || !session.has_database() {
+                // Check and insert under one write lock so concurrent
+                // ingests cannot both see an ID as absent
+                let ids = IdCheck::new(manager, metrics);
+                return with_engine_write(session, |engine| {
+                    let absent = absent_nodes(records, |id| engine.node_exists(id));
+                    if let Some(rejection) = ingest_rejection(&*engine, &absent, ids) {
+                        return rejection;
+                    }
+                    let count = absent.len() as u32;
+                    engine.add_nodes(absent);
+                    Response::Count { count, errors: None }
+                });
+            }
+            let mut absent = Vec::new();
+            with_transaction_read(session, |view| {
+                absent = absent_nodes(records, |id| view.node_exists(id));
                 Response::Ok { ok: true }
-            })
+            });
+            if let Some(rejection) = staging_schema_rejection(session, &absent) {
+                return rejection;
+            }
+            let count = absent.len() as u32;
+            match stage_or_apply(session, WriteOp::AddNodes(absent)) {
+                Response::Ok { .. } => Response::Count { count, errors: None },
+                other => other,
+            }
         }
 
-        Request::AddEdges { edges, skip_validation } => {
-            with_engine_write(session, |engine| {
-                let records: Vec<EdgeRecord> = edges.into_iter().map(wire_edge_to_record).collect();
-                engine.add_edges(records, skip_validation);
-                Response::Ok { ok: true }
-            })
+        Request::AddEdges { edges, skip_validation, dedup_edges } => {
+            let mut records: Vec<EdgeRecord> = edges.into_iter().map(wire_edge_to_record).collect();
+            if dedup_edges {
+                records = merge_duplicate_edges(records);
+            }
+            if session.pending_batch.is_none() || !session.has_database() {
+                return with_engine_write(session, |engine| {
+                    if !skip_validation {
+                        if let Some(rejection) = dangling_edge_rejection(&records, |id| engine.node_exists(id)) {
+                            return rejection;
+                        }
+                    }
+                    WriteOp::AddEdges { edges: records, skip_validation }.apply(engine);
+                    Response::Ok { ok: true }
+                });
+            }
+            // Early feedback; CommitTransaction repeats the check under the
+            // write lock
+            if !skip_validation {
+                let mut rejection = None;
+                with_transaction_read(session, |view| {
+                    rejection = dangling_edge_rejection(&records, |id| view.node_exists(id));
+                    Response::Ok { ok: true }
+                });
+                if let Some(rejection) = rejection {
+                    return rejection;
+                }
+            }
+            stage_or_apply(session, WriteOp::AddEdges { edges: records, skip_validation })
         }
 
         Request::DeleteNode { id } => {
-            with_engine_write(session, |engine| {
-                engine.delete_node(string_to_id(&id));
-                Response::Ok { ok: true }
-            })
+            stage_or_apply(session, WriteOp::DeleteNode(string_to_id(&id)))
         }
 
         Request::DeleteEdge { src, dst, edge_type } => {
-            with_engine_write(session, |engine| {
-                engine.delete_edge(string_to_id(&src), string_to_id(&dst), &edge_type);
-                Response::Ok { ok: true }
+            stage_or_apply(session, WriteOp::DeleteEdge {
+                src: string_to_id(&src),
+                dst: string_to_id(&dst),
+                edge_type,
             })
         }
 
-        Request::GetNode { id } => {
-            with_engine_read(session, |engine| {
-                let node = engine.get_node(string_to_id(&id)).map(|r| record_to_wire_node(&r));
+        Request::GetNode { id, include_deleted: false } => {
+            with_transaction_read(session, |view| {
+                let node = view.get_node(string_to_id(&id)).map(record_to_wire_node);
                 Response::Node { node }
             })
         }
 
+        Request::GetNode { id, include_deleted: true } => {
+            if let Some(rejection) = include_deleted_rejection(session) {
+                return rejection;
+            }
+            with_transaction_read(session, |view| {
+                match view.base().as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let node = view
+                            .get_node_including_deleted(string_to_id(&id), |id| v2.get_node_including_deleted(id))
+                            .map(record_to_wire_node);
+                        Response::Node { node }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "GetNode includeDeleted requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
         Request::NodeExists { id } => {
-            with_engine_read(session, |engine| {
-                Response::Bool { value: engine.node_exists(string_to_id(&id)) }
+            with_transaction_read(session, |view| {
+                Response::Bool { value: view.node_exists(string_to_id(&id)) }
+            })
+        }
+
+        Request::EdgeExists { src, dst, edge_type } => {
+            with_transaction_read(session, |view| {
+                Response::Bool { value: view.edge_exists(string_to_id(&src), string_to_id(&dst), &edge_type) }
+            })
+        }
+
+        Request::EdgesExist { edges } => {
+            with_transaction_read(session, |view| {
+                let values = edges.iter()
+                    .map(|e| view.edge_exists(string_to_id(&e.src), string_to_id(&e.dst), &e.edge_type))
+                    .collect();
+                Response::Bools { values }
             })
         }
 
         Request::FindByType { node_type } => {
-            with_engine_read(session, |engine| {
-                let ids: Vec<String> = engine.find_by_type(&node_type)
+            with_transaction_read(session, |view| {
+                let ids: Vec<String> = view.find_by_type(&node_type)
                     .into_iter()
                     .map(id_to_string)
                     .collect();
@@ -1102,7 +2401,35 @@ fn handle_request_with_cancel(
             })
         }
 
-        Request::FindByAttr { query } => {
+        Request::FindByAttr { per_shard_counts: true, include_deleted: true, .. } => {
+            Response::ErrorWithCode {
+                error: "FindByAttr perShardCounts cannot be combined with includeDeleted".to_string(),
+                code: "INVALID_ARGUMENT".to_string(),
+            }
+        }
+
+        Request::FindByAttr { query, per_shard_counts: false, include_deleted: true } => {
+            if let Some(rejection) = include_deleted_rejection(session) {
+                return rejection;
+            }
+            with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let ids = v2.find_by_attr_including_deleted(&wire_to_attr_query(query))
+                            .into_iter()
+                            .map(id_to_string)
+                            .collect();
+                        Response::Ids { ids }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "FindByAttr includeDeleted requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::FindByAttr { query, per_shard_counts: false, include_deleted: false } => {
             with_engine_read(session, |engine| {
                 let attr_query = wire_to_attr_query(query);
                 let ids: Vec<String> = engine.find_by_attr(&attr_query)
@@ -1113,65 +2440,211 @@ fn handle_request_with_cancel(
             })
         }
 
-        Request::Neighbors { id, edge_types } => {
+        Request::FindByAttr { query, per_shard_counts: true, include_deleted: false } => {
+            with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let (ids, counts) = v2.find_by_attr_with_shard_counts(&wire_to_attr_query(query));
+                        Response::IdsWithShardCounts {
+                            ids: ids.into_iter().map(id_to_string).collect(),
+                            shard_counts: counts.into_iter()
+                                .map(|(shard_id, count)| WireShardCount { shard_id, count })
+                                .collect(),
+                        }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "FindByAttr perShardCounts requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::CountByAttr { query } => {
+            with_engine_read(session, |engine| {
+                let count = engine.count_by_attr(&wire_to_attr_query(query));
+                Response::Count { count: count as u32, errors: None }
+            })
+        }
+
+        Request::DistinctValues { query, field } => {
+            with_engine_read(session, |engine| {
+                let values = engine.distinct_values(&wire_to_attr_query(query), &field);
+                Response::Facets { values }
+            })
+        }
+
+        Request::ExplainQuery { query } => {
+            with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let plan = v2.explain_attr_query(&wire_to_attr_query(query));
+                        Response::Explain { plan: plan.render() }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "ExplainQuery requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::AutocompleteNames { prefix, node_type, limit } => {
+            with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let ids: Vec<String> = v2.autocomplete_names(&prefix, node_type.as_deref(), limit as usize)
+                            .into_iter()
+                            .map(id_to_string)
+                            .collect();
+                        Response::Ids { ids }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "AutocompleteNames requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::FindDuplicateContent { node_type } => {
+            with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let groups: Vec<Vec<String>> = v2.find_duplicate_content(node_type.as_deref())
+                            .into_iter()
+                            .map(|g| g.into_iter().map(id_to_string).collect())
+                            .collect();
+                        Response::DuplicateGroups { groups }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "FindDuplicateContent requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::FindEdgesByMetadata { field, value, edge_type } => {
             with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let edges = v2.find_edges_by_metadata(&field, &value, edge_type.as_deref())
+                            .into_iter()
+                            .map(record_to_wire_edge)
+                            .collect();
+                        Response::Edges { edges }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "FindEdgesByMetadata requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::Neighbors { id, edge_types } => {
+            let protocol = session.protocol_version;
+            with_transaction_read(session, |view| {
                 let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
-                let ids: Vec<String> = engine.neighbors(string_to_id(&id), &edge_types_refs)
+                let mut ids: Vec<String> = view.neighbors(string_to_id(&id), &edge_types_refs)
                     .into_iter()
                     .map(id_to_string)
                     .collect();
+                if protocol >= 3 {
+                    resolve_node_semantic_ids(&mut ids, view.base());
+                }
                 Response::Ids { ids }
             })
         }
 
         Request::Bfs { start_ids, max_depth, edge_types } => {
+            let protocol = session.protocol_version;
             with_engine_read(session, |engine| {
                 let start: Vec<u128> = start_ids.iter().map(|s| string_to_id(s)).collect();
                 let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
-                let ids: Vec<String> = engine.bfs(&start, max_depth as usize, &edge_types_refs)
+                let mut ids: Vec<String> = engine.bfs(&start, max_depth as usize, &edge_types_refs)
                     .into_iter()
                     .map(id_to_string)
                     .collect();
+                if protocol >= 3 {
+                    resolve_node_semantic_ids(&mut ids, engine);
+                }
                 Response::Ids { ids }
             })
         }
 
-        Request::Reachability { start_ids, max_depth, edge_types, backward } => {
+        Request::KHop { start_ids, max_depth, edge_types } => {
             with_engine_read(session, |engine| {
                 let start: Vec<u128> = start_ids.iter().map(|s| string_to_id(s)).collect();
                 let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
-                let ids: Vec<String> = rfdb::graph::reachability(engine, &start, max_depth as usize, &edge_types_refs, backward)
+                let levels: Vec<Vec<String>> = bfs_levels(&start, max_depth as usize, |id| {
+                    engine.neighbors(id, &edge_types_refs)
+                })
                     .into_iter()
-                    .map(id_to_string)
+                    .map(|level| level.into_iter().map(id_to_string).collect())
                     .collect();
-                Response::Ids { ids }
+                Response::Levels { levels }
             })
         }
 
-        Request::Dfs { start_ids, max_depth, edge_types } => {
+        Request::NearestMatching { start_ids, edge_types, target_type, max_depth } => {
             with_engine_read(session, |engine| {
                 let start: Vec<u128> = start_ids.iter().map(|s| string_to_id(s)).collect();
                 let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
-                let ids: Vec<String> = rfdb::graph::traversal::dfs(
+                let found = bfs_until(
                     &start,
                     max_depth as usize,
                     |id| engine.neighbors(id, &edge_types_refs),
-                )
-                    .into_iter()
-                    .map(id_to_string)
-                    .collect();
-                Response::Ids { ids }
+                    |id| engine.get_node(id).and_then(|n| n.node_type).as_deref() == Some(target_type.as_str()),
+                );
+                match found {
+                    Some((ids, depth)) => Response::Nearest {
+                        matches: ids.into_iter().map(id_to_string).collect(),
+                        distance: Some(depth as u32),
+                    },
+                    None => Response::Nearest { matches: vec![], distance: None },
+                }
             })
         }
 
-        Request::GetOutgoingEdges { id, edge_types } => {
-            let protocol = session.protocol_version;
+        Request::WeaklyConnectedComponents { edge_types } => {
             with_engine_read(session, |engine| {
-                let edge_types_refs: Option<Vec<&str>> = edge_types.as_ref()
-                    .map(|v| v.iter().map(|s| s.as_str()).collect());
-                let mut edges: Vec<WireEdge> = engine.get_outgoing_edges(string_to_id(&id), edge_types_refs.as_deref())
+                let edges = engine.get_all_edges()
+                    .into_iter()
+                    .filter(|e| match (&edge_types, &e.edge_type) {
+                        (None, _) => true,
+                        (Some(types), Some(t)) => types.contains(t),
+                        (Some(_), None) => false,
+                    })
+                    .map(|e| (e.src, e.dst));
+                let components: Vec<Vec<String>> = union_find(edges)
+                    .into_iter()
+                    .map(|c| c.into_iter().map(id_to_string).collect())
+                    .collect();
+                Response::Components { components }
+            })
+        }
+
+        Request::AggregateEdges { edge_types } => {
+            let protocol = session.protocol_version;
+            with_engine_read(session, |engine| {
+                let keys = engine.get_all_edges()
+                    .into_iter()
+                    .filter(|e| match (&edge_types, &e.edge_type) {
+                        (None, _) => true,
+                        (Some(types), Some(t)) => types.contains(t),
+                        (Some(_), None) => false,
+                    })
+                    .map(|e| (e.src, e.dst, e.edge_type));
+                let mut edges: Vec<WireEdge> = count_parallel_edges(keys)
                     .into_iter()
-                    .map(|e| record_to_wire_edge(&e))
+                    .map(|((src, dst, edge_type), count)| WireEdge {
+                        src: id_to_string(src),
+                        dst: id_to_string(dst),
+                        edge_type,
+                        metadata: Some(serde_json::json!({ "count": count }).to_string()),
+                    })
                     .collect();
                 if protocol >= 3 {
                     resolve_edge_semantic_ids(&mut edges, engine);
@@ -1180,31 +2653,289 @@ fn handle_request_with_cancel(
             })
         }
 
-        Request::GetIncomingEdges { id, edge_types } => {
+        Request::Reachability { start_ids, max_depth, edge_types, backward, edge_costs } => {
+            let protocol = session.protocol_version;
+            with_engine_read(session, |engine| {
+                let start: Vec<u128> = start_ids.iter().map(|s| string_to_id(s)).collect();
+                let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
+                let reached = if edge_costs.is_empty() {
+                    rfdb::graph::reachability(engine, &start, max_depth as usize, &edge_types_refs, backward)
+                } else {
+                    let costs: HashMap<String, u32> = edge_costs.into_iter().collect();
+                    rfdb::graph::reachability_weighted(engine, &start, max_depth as usize, &edge_types_refs, backward, &costs)
+                };
+                let mut ids: Vec<String> = reached
+                    .into_iter()
+                    .map(id_to_string)
+                    .collect();
+                if protocol >= 3 {
+                    resolve_node_semantic_ids(&mut ids, engine);
+                }
+                Response::Ids { ids }
+            })
+        }
+
+        Request::AreConnected { src, dst, edge_types, max_depth } => {
+            with_engine_read(session, |engine| {
+                let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
+                let value = rfdb::graph::are_connected(
+                    engine,
+                    string_to_id(&src),
+                    string_to_id(&dst),
+                    max_depth as usize,
+                    &edge_types_refs,
+                );
+                Response::Bool { value }
+            })
+        }
+
+        Request::Dfs { start_ids, max_depth, edge_types } => {
             let protocol = session.protocol_version;
             with_engine_read(session, |engine| {
+                let start: Vec<u128> = start_ids.iter().map(|s| string_to_id(s)).collect();
+                let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
+                let mut ids: Vec<String> = rfdb::graph::traversal::dfs(
+                    &start,
+                    max_depth as usize,
+                    |id| engine.neighbors(id, &edge_types_refs),
+                )
+                    .into_iter()
+                    .map(id_to_string)
+                    .collect();
+                if protocol >= 3 {
+                    resolve_node_semantic_ids(&mut ids, engine);
+                }
+                Response::Ids { ids }
+            })
+        }
+
+        Request::GetOutgoingEdges { id, edge_types } => {
+            let protocol = session.protocol_version;
+            with_transaction_read(session, |view| {
                 let edge_types_refs: Option<Vec<&str>> = edge_types.as_ref()
                     .map(|v| v.iter().map(|s| s.as_str()).collect());
-                let mut edges: Vec<WireEdge> = engine.get_incoming_edges(string_to_id(&id), edge_types_refs.as_deref())
+                let mut edges: Vec<WireEdge> = view.get_outgoing_edges(string_to_id(&id), edge_types_refs.as_deref())
                     .into_iter()
-                    .map(|e| record_to_wire_edge(&e))
+                    .map(record_to_wire_edge)
                     .collect();
                 if protocol >= 3 {
-                    resolve_edge_semantic_ids(&mut edges, engine);
+                    resolve_edge_semantic_ids(&mut edges, view.base());
                 }
                 Response::Edges { edges }
             })
         }
 
-        Request::NodeCount => {
+        Request::NeighborsWithEdges { id, edge_types, direction } => {
+            let protocol = session.protocol_version;
+            with_transaction_read(session, |view| {
+                let node_id = string_to_id(&id);
+                let edge_types_refs: Option<Vec<&str>> = edge_types.as_ref()
+                    .map(|v| v.iter().map(|s| s.as_str()).collect());
+
+                // (edge, id of the endpoint that is not `node_id`)
+                let outgoing = || view.get_outgoing_edges(node_id, edge_types_refs.as_deref())
+                    .into_iter()
+                    .map(|e| { let other = e.dst; (e, other) });
+                let incoming = || view.get_incoming_edges(node_id, edge_types_refs.as_deref())
+                    .into_iter()
+                    .map(|e| { let other = e.src; (e, other) });
+                let pairs: Vec<(EdgeRecord, u128)> = match direction.as_str() {
+                    "outgoing" => outgoing().collect(),
+                    "incoming" => incoming().collect(),
+                    _ => outgoing().chain(incoming()).collect(),
+                };
+
+                let mut entries: Vec<WireNeighbor> = pairs.into_iter()
+                    .filter_map(|(edge, other)| {
+                        view.get_node(other).map(|node| WireNeighbor {
+                            node: record_to_wire_node(node),
+                            edge: record_to_wire_edge(edge),
+                        })
+                    })
+                    .collect();
+                if protocol >= 3 {
+                    for entry in &mut entries {
+                        resolve_edge_semantic_ids(std::slice::from_mut(&mut entry.edge), view.base());
+                    }
+                }
+                Response::Neighbors { entries }
+            })
+        }
+
+        Request::BatchNeighbors { ids, edge_types, direction } => {
+            let protocol = session.protocol_version;
+            with_transaction_read(session, |view| {
+                let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
+                let filter = (!edge_types_refs.is_empty()).then_some(edge_types_refs.as_slice());
+                let incoming = |id: u128| view.get_incoming_edges(id, filter).into_iter().map(|e| e.src);
+                let to_wire_id = |id: u128| {
+                    let semantic_id = if protocol >= 3 {
+                        view.get_node(id).and_then(|node| node.semantic_id)
+                    } else {
+                        None
+                    };
+                    semantic_id.unwrap_or_else(|| id_to_string(id))
+                };
+
+                let entries = ids.into_iter()
+                    .map(|id| {
+                        let node_id = string_to_id(&id);
+                        let found: Vec<u128> = match direction.as_str() {
+                            "outgoing" => view.neighbors(node_id, &edge_types_refs),
+                            "incoming" => incoming(node_id).collect(),
+                            _ => view.neighbors(node_id, &edge_types_refs)
+                                .into_iter()
+                                .chain(incoming(node_id))
+                                .collect(),
+                        };
+                        let mut seen: HashSet<u128> = HashSet::new();
+                        let neighbors = found.into_iter()
+                            .filter(|n| seen.insert(*n))
+                            .map(to_wire_id)
+                            .collect();
+                        (id, neighbors)
+                    })
+                    .collect();
+                Response::NeighborMap { entries }
+            })
+        }
+
+        Request::TopDegreeNodes { n, edge_types, direction } => {
+            let protocol = session.protocol_version;
+            with_engine_read(session, |engine| {
+                let edge_types_refs: Option<Vec<&str>> = edge_types.as_ref()
+                    .map(|v| v.iter().map(|s| s.as_str()).collect());
+                let degree = |id: u128| match direction.as_str() {
+                    "outgoing" => engine.get_outgoing_edges(id, edge_types_refs.as_deref()).len(),
+                    "incoming" => engine.get_incoming_edges(id, edge_types_refs.as_deref()).len(),
+                    _ => engine.get_outgoing_edges(id, edge_types_refs.as_deref()).len()
+                        + engine.get_incoming_edges(id, edge_types_refs.as_deref()).len(),
+                };
+                let degrees = engine.find_by_attr(&AttrQuery::new())
+                    .into_iter()
+                    .map(|id| (id, degree(id)));
+
+                let scores: Vec<(String, f64)> = top_n_by_degree(degrees, n as usize)
+                    .into_iter()
+                    .map(|(id, degree)| {
+                        let semantic_id = if protocol >= 3 {
+                            engine.get_node(id).and_then(|node| node.semantic_id)
+                        } else {
+                            None
+                        };
+                        (semantic_id.unwrap_or_else(|| id_to_string(id)), degree as f64)
+                    })
+                    .collect();
+                Response::Scores { scores }
+            })
+        }
+
+        Request::FindOrphans { edge_types, root_types } => {
             with_engine_read(session, |engine| {
-                Response::Count { count: engine.node_count() as u32 }
+                let edge_types_refs: Vec<&str> = edge_types.iter().map(|s| s.as_str()).collect();
+                let filter = (!edge_types_refs.is_empty()).then_some(edge_types_refs.as_slice());
+                // Incoming lookups use the reverse edge index when enabled
+                let mut orphans: Vec<u128> = engine.find_by_attr(&AttrQuery::new())
+                    .into_iter()
+                    .filter(|&id| engine.get_incoming_edges(id, filter).is_empty())
+                    .filter(|&id| {
+                        let node_type = engine.get_node(id).and_then(|n| n.node_type);
+                        !node_type.is_some_and(|t| root_types.contains(&t))
+                    })
+                    .collect();
+                orphans.sort_unstable();
+                orphans.dedup();
+                Response::Ids { ids: orphans.into_iter().map(id_to_string).collect() }
+            })
+        }
+
+        Request::ExtractSubgraph { node_ids, edge_types } => {
+            let protocol = session.protocol_version;
+            with_transaction_read(session, |view| {
+                let edge_types_refs: Option<Vec<&str>> = edge_types.as_ref()
+                    .map(|v| v.iter().map(|s| s.as_str()).collect());
+                let mut members: HashSet<u128> = HashSet::new();
+                let ids: Vec<u128> = node_ids.iter()
+                    .map(|s| string_to_id(s))
+                    .filter(|id| members.insert(*id))
+                    .collect();
+
+                let nodes: Vec<WireNode> = ids.iter()
+                    .filter_map(|&id| view.get_node(id))
+                    .map(record_to_wire_node)
+                    .collect();
+
+                let mut edges: Vec<WireEdge> = Vec::new();
+                for &id in &ids {
+                    for edge in view.get_outgoing_edges(id, edge_types_refs.as_deref()) {
+                        if members.contains(&edge.dst) {
+                            edges.push(record_to_wire_edge(edge));
+                        }
+                    }
+                }
+                if protocol >= 3 {
+                    resolve_edge_semantic_ids(&mut edges, view.base());
+                }
+                Response::Subgraph { nodes, edges }
+            })
+        }
+
+        Request::GetIncomingEdges { id, edge_types } => {
+            let protocol = session.protocol_version;
+            with_transaction_read(session, |view| {
+                let edge_types_refs: Option<Vec<&str>> = edge_types.as_ref()
+                    .map(|v| v.iter().map(|s| s.as_str()).collect());
+                let mut edges: Vec<WireEdge> = view.get_incoming_edges(string_to_id(&id), edge_types_refs.as_deref())
+                    .into_iter()
+                    .map(record_to_wire_edge)
+                    .collect();
+                if protocol >= 3 {
+                    resolve_edge_semantic_ids(&mut edges, view.base());
+                }
+                Response::Edges { edges }
+            })
+        }
+
+        Request::NodeCount => {
+            with_transaction_read(session, |view| {
+                Response::Count { count: view.node_count() as u32, errors: None }
             })
         }
 
         Request::EdgeCount => {
+            with_transaction_read(session, |view| {
+                Response::Count { count: view.edge_count() as u32, errors: None }
+            })
+        }
+
+        Request::GetShardStats => {
+            with_engine_read(session, |engine| {
+                Response::ShardStats { shards: wire_shard_stats(engine) }
+            })
+        }
+
+        Request::GraphSummary => {
             with_engine_read(session, |engine| {
-                Response::Count { count: engine.edge_count() as u32 }
+                let (disk_bytes, segments_by_level) = match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let disk_bytes = match v2.disk_bytes() {
+                            Ok(bytes) => bytes,
+                            Err(e) => return Response::Error { error: e.to_string() },
+                        };
+                        let [l0, l1, l2] = v2.segment_counts_by_level();
+                        (disk_bytes, WireSegmentsByLevel { l0, l1, l2 })
+                    }
+                    None => (0, WireSegmentsByLevel::default()),
+                };
+                Response::GraphSummary {
+                    node_count: engine.node_count(),
+                    edge_count: engine.edge_count(),
+                    nodes_by_type: engine.count_nodes_by_type(None),
+                    edges_by_type: engine.count_edges_by_type(None),
+                    shards: wire_shard_stats(engine),
+                    disk_bytes,
+                    segments_by_level,
+                }
             })
         }
 
@@ -1220,24 +2951,131 @@ fn handle_request_with_cancel(
             })
         }
 
-        Request::Flush => {
+        Request::EdgeTypeMatrix { edge_types } => {
+            with_engine_read(session, |engine| {
+                Response::Matrix { cells: edge_type_matrix(engine, edge_types.as_deref()) }
+            })
+        }
+
+        Request::Flush { durability } => {
+            let recorders = flush_metrics(metrics, session.current_db.as_deref());
             with_engine_write(session, |engine| {
-                match engine.flush() {
+                match timed_flush(engine, &recorders, |engine| flush_engine(engine, durability)) {
                     Ok(()) => Response::Ok { ok: true },
                     Err(e) => Response::Error { error: e.to_string() },
                 }
             })
         }
 
-        Request::Compact => {
+        Request::FlushDataOnly => {
+            let recorders = flush_metrics(metrics, session.current_db.as_deref());
             with_engine_write(session, |engine| {
-                match engine.compact() {
+                match timed_flush(engine, &recorders, |engine| engine.flush_data_only()) {
                     Ok(()) => Response::Ok { ok: true },
                     Err(e) => Response::Error { error: e.to_string() },
                 }
             })
         }
 
+        Request::Compact => {
+            // Not with_engine_write: Database::compact takes the write
+            // lock only around the flush and the final swap
+            let db = match &session.current_db {
+                Some(db) => Arc::clone(db),
+                None => return Response::ErrorWithCode {
+                    error: "No database selected. Use openDatabase first.".to_string(),
+                    code: "NO_DATABASE_SELECTED".to_string(),
+                },
+            };
+            if let Some(rejection) = write_rejection(session) {
+                return rejection;
+            }
+            db.record_write();
+            match db.compact() {
+                Ok(()) => Response::Ok { ok: true },
+                Err(e) => Response::Error { error: e.to_string() },
+            }
+        }
+
+        Request::CompactDryRun => {
+            with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let estimate = v2.compaction_estimate();
+                        Response::CompactionEstimate {
+                            shards_eligible: estimate.shards_eligible,
+                            estimated_tombstones_removed: estimate.estimated_tombstones_removed,
+                            estimated_bytes_saved: estimate.estimated_bytes_saved,
+                        }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "CompactDryRun requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::CompactShard { shard_id } => {
+            with_engine_write(session, |engine| {
+                match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+                    Some(v2) => match v2.compact_shard(shard_id) {
+                        Ok(result) => Response::ShardCompacted {
+                            ok: true,
+                            shard_id,
+                            nodes_merged: result.nodes_merged,
+                            edges_merged: result.edges_merged,
+                            duration_ms: result.duration_ms,
+                        },
+                        Err(e) => Response::ErrorWithCode {
+                            error: e.to_string(),
+                            code: e.code().to_string(),
+                        },
+                    },
+                    None => Response::ErrorWithCode {
+                        error: "CompactShard requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::FlushShard { shard_id } => {
+            with_engine_write(session, |engine| {
+                match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+                    Some(v2) => match v2.flush_shard(shard_id) {
+                        Ok(value) => Response::Bool { value },
+                        Err(e) => Response::ErrorWithCode {
+                            error: e.to_string(),
+                            code: e.code().to_string(),
+                        },
+                    },
+                    None => Response::ErrorWithCode {
+                        error: "FlushShard requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::Reshard { shard_count } => {
+            with_engine_write(session, |engine| {
+                match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+                    Some(v2) => match v2.reshard(shard_count) {
+                        Ok(()) => Response::Ok { ok: true },
+                        Err(e) => Response::ErrorWithCode {
+                            error: e.to_string(),
+                            code: e.code().to_string(),
+                        },
+                    },
+                    None => Response::ErrorWithCode {
+                        error: "Reshard requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
         Request::Clear => {
             with_engine_write(session, |engine| {
                 engine.clear();
@@ -1259,7 +3097,7 @@ fn handle_request_with_cancel(
             with_engine_read(session, |engine| {
                 let mut edges: Vec<WireEdge> = engine.get_all_edges()
                     .into_iter()
-                    .map(|e| record_to_wire_edge(&e))
+                    .map(record_to_wire_edge)
                     .collect();
                 if protocol >= 3 {
                     resolve_edge_semantic_ids(&mut edges, engine);
@@ -1274,28 +3112,28 @@ fn handle_request_with_cancel(
                 let ids = engine.find_by_attr(&attr_query);
                 let nodes: Vec<WireNode> = ids.into_iter()
                     .filter_map(|id| engine.get_node(id))
-                    .map(|r| record_to_wire_node(&r))
+                    .map(record_to_wire_node)
                     .collect();
                 Response::Nodes { nodes }
             })
         }
 
-        Request::CheckGuarantee { rule_source, explain } => {
-            let cf = cancel_flag.clone();
+        Request::CheckGuarantee { rule_source, explain, timeout_ms } => {
+            let limits = datalog_limits(cancel_flag.clone(), timeout_ms);
             with_engine_read(session, |engine| {
-                match execute_check_guarantee(engine, &rule_source, explain, cf) {
+                match execute_check_guarantee(engine, &rule_source, explain, limits) {
                     Ok(DatalogResponse::Violations(violations)) => Response::Violations { violations },
                     Ok(DatalogResponse::Explain(result)) => Response::ExplainResult(result),
-                    Err(e) => Response::Error { error: e },
+                    Err(e) => datalog_error_response(e),
                 }
             })
         }
 
         Request::DatalogLoadRules { source } => {
-            with_engine_read(session, |engine| {
-                match execute_datalog_load_rules(engine, &source) {
-                    Ok(count) => Response::Count { count },
-                    Err(e) => Response::Error { error: e },
+            with_engine_read(session, |_engine| {
+                match execute_datalog_load_rules(&source) {
+                    Ok(count) => Response::Count { count, errors: None },
+                    Err(e) => datalog_error_response(e),
                 }
             })
         }
@@ -1304,24 +3142,93 @@ fn handle_request_with_cancel(
             Response::Ok { ok: true }
         }
 
+        Request::SaveRuleSet { name, source } => {
+            if name.is_empty() {
+                return Response::Error { error: "Rule set name must not be empty".to_string() };
+            }
+            // Reject unparseable sources up front so RunRuleSet only ever
+            // fails on evaluation
+            let count = match execute_datalog_load_rules(&source) {
+                Ok(count) => count,
+                Err(e) => return datalog_error_response(e),
+            };
+            with_engine_write(session, |engine| {
+                match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+                    Some(v2) => match v2.save_rule_set(&name, &source) {
+                        Ok(()) => Response::Count { count, errors: None },
+                        Err(e) => Response::Error { error: format!("Failed to save rule set: {}", e) },
+                    },
+                    None => Response::ErrorWithCode {
+                        error: "SaveRuleSet requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::RunRuleSet { name, timeout_ms } => {
+            let limits = datalog_limits(cancel_flag.clone(), timeout_ms);
+            with_engine_read(session, |engine| {
+                let source = match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => v2.rule_set(&name).map(str::to_string),
+                    None => None,
+                };
+                let Some(source) = source else {
+                    return Response::ErrorWithCode {
+                        error: format!("Rule set not found: {}", name),
+                        code: "RULE_SET_NOT_FOUND".to_string(),
+                    };
+                };
+                match execute_check_guarantee(engine, &source, false, limits) {
+                    Ok(DatalogResponse::Violations(violations)) => Response::Violations { violations },
+                    Ok(DatalogResponse::Explain(result)) => Response::ExplainResult(result),
+                    Err(e) => datalog_error_response(e),
+                }
+            })
+        }
+
+        Request::ListRuleSets => {
+            with_engine_read(session, |engine| {
+                let rule_sets = engine.as_any().downcast_ref::<GraphEngineV2>()
+                    .map(|v2| v2.rule_set_names())
+                    .unwrap_or_default();
+                Response::RuleSetList { rule_sets }
+            })
+        }
+
+        Request::DeleteRuleSet { name } => {
+            with_engine_write(session, |engine| {
+                match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+                    Some(v2) => match v2.delete_rule_set(&name) {
+                        Ok(deleted) => Response::Bool { value: deleted },
+                        Err(e) => Response::Error { error: format!("Failed to delete rule set: {}", e) },
+                    },
+                    None => Response::ErrorWithCode {
+                        error: "DeleteRuleSet requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
         Request::DatalogQuery { query, explain } => {
             let cf = cancel_flag.clone();
             with_engine_read(session, |engine| {
                 match execute_datalog_query(engine, &query, explain, cf) {
                     Ok(DatalogResponse::Violations(results)) => Response::DatalogResults { results },
                     Ok(DatalogResponse::Explain(result)) => Response::ExplainResult(result),
-                    Err(e) => Response::Error { error: e },
+                    Err(e) => datalog_error_response(e),
                 }
             })
         }
 
-        Request::ExecuteDatalog { source, explain } => {
-            let cf = cancel_flag.clone();
+        Request::ExecuteDatalog { source, explain, timeout_ms } => {
+            let limits = datalog_limits(cancel_flag.clone(), timeout_ms);
             with_engine_read(session, |engine| {
-                match execute_datalog(engine, &source, explain, cf) {
+                match execute_datalog(engine, &source, explain, limits) {
                     Ok(DatalogResponse::Violations(results)) => Response::DatalogResults { results },
                     Ok(DatalogResponse::Explain(result)) => Response::ExplainResult(result),
-                    Err(e) => Response::Error { error: e },
+                    Err(e) => datalog_error_response(e),
                 }
             })
         }
@@ -1358,6 +3265,10 @@ fn handle_request_with_cancel(
             })
         }
 
+        Request::ComputeId { input } => {
+            Response::Identifier { identifier: Some(id_to_string(string_to_id(&input))) }
+        }
+
         Request::UpdateNodeVersion { id: _, version: _ } => {
             with_engine_write(session, |_engine| {
                 Response::Ok { ok: true }
@@ -1365,6 +3276,14 @@ fn handle_request_with_cancel(
         }
 
         Request::DeclareFields { fields } => {
+            if let Some(f) = fields.iter().find(|f| !matches!(f.entity.as_deref(), None | Some("node") | Some("edge"))) {
+                return Response::Error {
+                    error: format!(
+                        "Unknown entity {:?} for field {:?}; expected \"node\" or \"edge\"",
+                        f.entity.as_deref().unwrap_or_default(), f.name
+                    ),
+                };
+            }
             with_engine_write(session, |engine| {
                 let field_decls: Vec<FieldDecl> = fields.into_iter().map(|f| {
                     let field_type = match f.field_type.as_deref() {
@@ -1373,105 +3292,249 @@ fn handle_request_with_cancel(
                         Some("id") => FieldType::Id,
                         _ => FieldType::String,
                     };
+                    let entity = match f.entity.as_deref() {
+                        Some("edge") => FieldEntity::Edge,
+                        _ => FieldEntity::Node,
+                    };
                     FieldDecl {
                         name: f.name,
                         field_type,
                         node_types: f.node_types,
+                        entity,
                     }
                 }).collect();
                 let count = field_decls.len() as u32;
                 engine.declare_fields(field_decls);
-                Response::Count { count }
+                Response::Count { count, errors: None }
             })
         }
 
-        Request::GetStats => {
-            // Collect stats from all sources
-            let metrics_snapshot = if let Some(ref m) = metrics {
-                m.snapshot()
-            } else {
-                MetricsSnapshot::default()
-            };
-
-            // Get graph stats from current database (if any)
-            let (node_count, edge_count, delta_size, shard_diags) = if let Some(ref db) = session.current_db {
-                let engine = db.engine.read().unwrap();
-                let ops = 0u64;
-                let diags: Vec<WireShardDiagnostics> = engine.shard_diagnostics()
-                    .into_iter()
-                    .map(|d| WireShardDiagnostics {
-                        shard_id: d.shard_id,
-                        node_count: d.node_count,
-                        edge_count: d.edge_count,
-                        write_buffer_nodes: d.write_buffer_nodes,
-                        write_buffer_edges: d.write_buffer_edges,
-                        compacted: d.compacted,
-                        l0_node_segment_count: d.l0_node_segment_count,
-                        l0_edge_segment_count: d.l0_edge_segment_count,
-                        l1_node_records: d.l1_node_records,
-                        l1_edge_records: d.l1_edge_records,
-                        tombstone_node_count: d.tombstone_node_count,
-                        tombstone_edge_count: d.tombstone_edge_count,
-                        has_l1_by_type: d.has_l1_by_type,
-                        has_l1_by_file: d.has_l1_by_file,
-                        has_l1_by_name: d.has_l1_by_name,
-                        l1_by_type_keys: d.l1_by_type_keys,
-                        l1_by_file_keys: d.l1_by_file_keys,
-                        l1_by_name_keys: d.l1_by_name_keys,
-                        has_edge_type_index: d.has_edge_type_index,
-                    })
-                    .collect();
-                (
-                    engine.node_count() as u64,
-                    engine.edge_count() as u64,
-                    ops,
-                    diags,
-                )
+        Request::RenameEdgeType { from, to } => {
+            if from == to || to.is_empty() {
+                return Response::Error {
+                    error: format!("Cannot rename edge type {:?} to {:?}", from, to),
+                };
+            }
+            if let Some(refusal) = refuse_in_batch(session, "RenameEdgeType") {
+                return refusal;
+            }
+            with_engine_write(session, |engine| {
+                // The edge-type index covers delta and segment-resident edges
+                // but yields keys only, so reload each source's edges to keep
+                // metadata. Tombstoned edges are already filtered out.
+                let mut srcs: Vec<u128> = engine.get_edges_by_type(&from).iter().map(|e| e.src).collect();
+                srcs.sort_unstable();
+                srcs.dedup();
+                let edges: Vec<EdgeRecord> = srcs.into_iter()
+                    .flat_map(|src| engine.get_outgoing_edges(src, Some(&[from.as_str()])))
+                    .collect();
+                let count = edges.len() as u32;
+                let renamed: Vec<EdgeRecord> = edges.into_iter()
+                    .map(|edge| {
+                        engine.delete_edge(edge.src, edge.dst, &from);
+                        EdgeRecord { edge_type: Some(to.clone()), ..edge }
+                    })
+                    .collect();
+                engine.add_edges(renamed, true);
+                Response::Count { count, errors: None }
+            })
+        }
+
+        Request::RenameNodeType { from, to } => {
+            if from == to || to.is_empty() {
+                return Response::Error {
+                    error: format!("Cannot rename node type {:?} to {:?}", from, to),
+                };
+            }
+            if let Some(refusal) = refuse_in_batch(session, "RenameNodeType") {
+                return refusal;
+            }
+            with_engine_write(session, |engine| {
+                let originals: Vec<NodeRecord> = engine.find_by_type(&from).into_iter()
+                    .filter_map(|id| engine.get_node(id))
+                    .filter(|node| node.node_type.as_deref() == Some(from.as_str()))
+                    .collect();
+                let count = originals.len() as u32;
+                let renamed: Vec<NodeRecord> = originals.iter()
+                    .map(|node| NodeRecord { node_type: Some(to.clone()), ..node.clone() })
+                    .collect();
+                // Re-adding under the same ID shadows the old record only
+                // where the old type is scanned alongside it; flushed copies
+                // keep matching `from` until compaction drops them and
+                // rebuilds the by_type indexes
+                engine.add_nodes(renamed);
+                if let Err(e) = engine.compact() {
+                    // Put the old records back on top so the type index and
+                    // the nodes agree again, as if the rename never ran
+                    engine.add_nodes(originals);
+                    return Response::Error {
+                        error: format!("Index rebuild failed, rename rolled back: {}", e),
+                    };
+                }
+                Response::Count { count, errors: None }
+            })
+        }
+
+        Request::SetMetadataSchema { node_type, required_keys, allowed_keys } => {
+            with_engine_write(session, |engine| {
+                match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+                    Some(v2) => {
+                        v2.set_metadata_schema(&node_type, MetadataSchema { required_keys, allowed_keys });
+                        Response::Ok { ok: true }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "SetMetadataSchema requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::GetStats => {
+            let metrics_snapshot = if let Some(ref m) = metrics {
+                m.snapshot()
             } else {
-                // No database selected - return zeros
-                (0, 0, 0, vec![])
+                MetricsSnapshot::default()
             };
+            stats_response(metrics_snapshot, session.current_db.as_deref(), None)
+        }
+
+        Request::GetDatabaseStats { name } => {
+            match manager.get_database(&name) {
+                Ok(db) => {
+                    let metrics_snapshot = if metrics.is_some() {
+                        db.metrics.snapshot()
+                    } else {
+                        MetricsSnapshot::default()
+                    };
+                    stats_response(metrics_snapshot, Some(&db), Some(name))
+                }
+                Err(e) => Response::ErrorWithCode {
+                    error: e.to_string(),
+                    code: e.code().to_string(),
+                },
+            }
+        }
 
-            // Get system memory
-            let memory_percent = check_memory_usage();
+        Request::ResourceInfo => {
+            let resources = SystemResources::detect();
+            Response::ResourceInfo {
+                total_memory_bytes: resources.total_memory_bytes,
+                available_memory_bytes: resources.available_memory_bytes,
+                cpu_count: resources.cpu_count,
+                recommended_shard_count: ResourceManager::recommended_shard_count(&resources),
+                default_shard_count: manager.default_shard_count(),
+            }
+        }
 
-            Response::Stats {
-                node_count,
-                edge_count,
-                delta_size,
-                memory_percent,
-                query_count: metrics_snapshot.query_count,
-                slow_query_count: metrics_snapshot.slow_query_count,
-                query_p50_ms: metrics_snapshot.query_p50_ms,
-                query_p95_ms: metrics_snapshot.query_p95_ms,
-                query_p99_ms: metrics_snapshot.query_p99_ms,
-                flush_count: metrics_snapshot.flush_count,
-                last_flush_ms: metrics_snapshot.last_flush_ms,
-                last_flush_nodes: metrics_snapshot.last_flush_nodes,
-                last_flush_edges: metrics_snapshot.last_flush_edges,
-                top_slow_queries: metrics_snapshot.top_slow_queries.into_iter()
-                    .map(|sq| WireSlowQuery {
-                        operation: sq.operation,
-                        duration_ms: sq.duration_ms,
-                        timestamp_ms: sq.timestamp_ms,
-                    })
-                    .collect(),
-                timed_out_count: metrics_snapshot.timed_out_count,
-                cancelled_count: metrics_snapshot.cancelled_count,
-                uptime_secs: metrics_snapshot.uptime_secs,
-                shard_diagnostics: shard_diags,
+        Request::MetricsPrometheus => {
+            let server_snapshot = metrics.as_ref().map(|m| m.snapshot());
+            let mut names: Vec<String> = manager.list_databases().into_iter().map(|d| d.name).collect();
+            names.sort();
+            // Databases dropped between listing and lookup are skipped
+            let dbs: Vec<Arc<Database>> = names.iter()
+                .filter_map(|name| manager.get_database(name).ok())
+                .collect();
+            let db_snapshots: Vec<Option<MetricsSnapshot>> = dbs.iter()
+                .map(|db| metrics.as_ref().map(|_| db.metrics.snapshot()))
+                .collect();
+
+            let mut sources = vec![PrometheusSource {
+                database: None,
+                snapshot: server_snapshot.as_ref(),
+                graph: None,
+            }];
+            for (db, snapshot) in dbs.iter().zip(&db_snapshots) {
+                sources.push(PrometheusSource {
+                    database: Some(&db.name),
+                    snapshot: snapshot.as_ref(),
+                    graph: Some((db.node_count() as u64, db.edge_count() as u64)),
+                });
+            }
+            Response::Export {
+                format: "prometheus".to_string(),
+                content: rfdb::metrics::prometheus::render(&sources),
+            }
+        }
+
+        Request::CommitBatch {
+            changed_files, nodes, edges, tags: _, file_context, defer_index, protected_types, durability, check_rule_sets,
+            dedup_edges,
+        } => {
+            let recorders = flush_metrics(metrics, session.current_db.as_deref());
+            let nodes: Vec<NodeRecord> = nodes.into_iter().map(wire_node_to_record).collect();
+            let mut edges: Vec<EdgeRecord> = edges.into_iter().map(wire_edge_to_record).collect();
+            if dedup_edges {
+                edges = merge_duplicate_edges(edges);
+            }
+            // Rule sets are evaluated under read locks only: before the
+            // commit for the rules it can affect, and again after it. A
+            // write from another client in between can show up as new.
+            let mut baseline = ViolationBaseline::new();
+            if check_rule_sets && write_rejection(session).is_none() {
+                with_engine_read(session, |engine| {
+                    let (node_types, edge_types) = commit_type_changes(
+                        engine, &changed_files, file_context.as_deref(), &protected_types, &nodes, &edges,
+                    );
+                    baseline = rule_set_baseline(engine, &node_types, &edge_types, &cancel_flag);
+                    Response::Ok { ok: true }
+                });
+            }
+            let response = with_engine_write(session, |engine| {
+                note_malformed_metadata(metrics, &nodes);
+                let flush = if defer_index { CommitFlush::DataOnly } else { CommitFlush::Full(durability) };
+                let ids = IdCheck::new(manager, metrics);
+                handle_commit_batch(
+                    engine, changed_files, nodes, edges, file_context, flush, &recorders, protected_types, ids,
+                )
+            });
+            match response {
+                Response::BatchCommitted { ok, delta, version, .. } if check_rule_sets => {
+                    let mut guarantees = None;
+                    with_engine_read(session, |engine| {
+                        guarantees = Some(recheck_rule_sets(engine, &delta, &baseline, &cancel_flag));
+                        Response::Ok { ok: true }
+                    });
+                    Response::BatchCommitted { ok, delta, guarantees, version }
+                }
+                response => response,
             }
         }
 
-        Request::CommitBatch { changed_files, nodes, edges, tags: _, file_context, defer_index, protected_types } => {
+        Request::CommitBatchIfUnchanged { changed_files, expected_version, nodes, edges } => {
+            let recorders = flush_metrics(metrics, session.current_db.as_deref());
             with_engine_write(session, |engine| {
-                handle_commit_batch(engine, changed_files, nodes, edges, file_context, defer_index, protected_types)
+                // Checked under the write lock, so no write can slip in between
+                let current = match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => v2.write_version(),
+                    None => return Response::ErrorWithCode {
+                        error: "CommitBatchIfUnchanged requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                };
+                if current != expected_version {
+                    return version_conflict(None, expected_version, current);
+                }
+                let nodes: Vec<NodeRecord> = nodes.into_iter().map(wire_node_to_record).collect();
+                let edges = edges.into_iter().map(wire_edge_to_record).collect();
+                note_malformed_metadata(metrics, &nodes);
+                handle_commit_batch(
+                    engine, changed_files, nodes, edges, None, CommitFlush::Full(None), &recorders, Vec::new(),
+                    IdCheck::new(manager, metrics),
+                )
             })
         }
 
-        Request::RebuildIndexes => {
+        Request::RebuildIndexes { node_types, edge_types } => {
             with_engine_write(session, |engine| {
-                if let Err(e) = engine.rebuild_indexes() {
+                let result = match (&node_types, &edge_types) {
+                    (None, None) => engine.rebuild_indexes(),
+                    _ => engine.rebuild_indexes_for_types(
+                        node_types.as_deref().unwrap_or_default(),
+                        edge_types.as_deref().unwrap_or_default(),
+                    ),
+                };
+                if let Err(e) = result {
                     return Response::Error { error: format!("Index rebuild failed: {}", e) };
                 }
                 Response::Ok { ok: true }
@@ -1488,21 +3551,173 @@ fn handle_request_with_cancel(
                 None => Response::Error {
                     error: format!(
                         "Batch already in progress: {}",
-                        session.pending_batch_id.as_deref().unwrap_or("unknown")
+                        session.pending_batch_id().unwrap_or("unknown")
                     ),
                 },
             }
         }
 
         Request::AbortBatch => {
-            match session.abort_batch() {
-                Some(_) => Response::Ok { ok: true },
-                None => Response::Error {
+            let aborted_batch = session.abort_batch().is_some();
+            let aborted_cross_db = session.take_cross_db_batch().is_some();
+            let aborted_stream = session.take_streaming_commit().is_some();
+            let aborted_import = session.pending_import.take().is_some();
+            if aborted_batch || aborted_cross_db || aborted_stream || aborted_import {
+                Response::Ok { ok: true }
+            } else {
+                Response::Error {
                     error: "No batch in progress".to_string(),
+                }
+            }
+        }
+
+        Request::CommitTransaction => {
+            if session.pending_batch.is_none() {
+                return Response::Error { error: "No batch in progress".to_string() };
+            }
+            // Keep the batch open if the commit is refused: it is only
+            // taken once the database accepts writes, and put back if
+            // validation under the write lock fails
+            if let Some(rejection) = commit_target_rejection(session) {
+                return rejection;
+            }
+            let mut txn = session.take_batch();
+            let ids = IdCheck::new(manager, metrics);
+            let response = with_engine_write(session, |engine| {
+                let Some(staged) = txn.take() else {
+                    return Response::Error { error: "No batch in progress".to_string() };
+                };
+                if let Some(rejection) = transaction_rejection(&*engine, &staged, ids) {
+                    txn = Some(staged);
+                    return rejection;
+                }
+                staged.commit(engine);
+                Response::Ok { ok: true }
+            });
+            if txn.is_some() {
+                session.pending_batch = txn;
+            }
+            response
+        }
+
+        Request::BeginCrossDbBatch { databases } => {
+            if let Some(batch_id) = session.pending_cross_db.as_ref().map(CrossDbBatch::id) {
+                return Response::Error {
+                    error: format!("Cross-database batch already in progress: {}", batch_id),
+                };
+            }
+            if databases.is_empty() {
+                return Response::Error {
+                    error: "BeginCrossDbBatch requires at least one database".to_string(),
+                };
+            }
+            let mut versions = Vec::with_capacity(databases.len());
+            for name in databases {
+                let db = match manager.get_database(&name) {
+                    Ok(db) => db,
+                    Err(e) => return Response::ErrorWithCode {
+                        error: e.to_string(),
+                        code: e.code().to_string(),
+                    },
+                };
+                let engine = db.engine.read().unwrap();
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => versions.push((name, v2.write_version())),
+                    None => return Response::ErrorWithCode {
+                        error: "BeginCrossDbBatch requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            }
+            match session.begin_cross_db_batch(versions) {
+                Some(batch_id) => Response::BatchStarted { ok: true, batch_id },
+                None => Response::Error { error: "Cross-database batch already in progress".to_string() },
+            }
+        }
+
+        Request::StageCrossDbCommit { database, changed_files, nodes, edges, file_context } => {
+            let Some(batch) = session.pending_cross_db.as_mut() else {
+                return Response::Error { error: "No cross-database batch in progress".to_string() };
+            };
+            let commit = StagedCommit {
+                changed_files,
+                nodes: nodes.into_iter().map(wire_node_to_record).collect(),
+                edges: edges.into_iter().map(wire_edge_to_record).collect(),
+                file_context,
+            };
+            note_malformed_metadata(metrics, &commit.nodes);
+            if batch.stage(&database, commit) {
+                Response::Ok { ok: true }
+            } else {
+                Response::Error {
+                    error: format!("Database {} is not part of the cross-database batch", database),
+                }
+            }
+        }
+
+        Request::CommitCrossDbBatch => {
+            match session.take_cross_db_batch() {
+                Some(batch) => commit_cross_db_batch(manager, batch, IdCheck::new(manager, metrics)),
+                None => Response::Error { error: "No cross-database batch in progress".to_string() },
+            }
+        }
+
+        Request::BeginStreamingCommit { changed_files } => {
+            match session.begin_streaming_commit(changed_files) {
+                Some(batch_id) => Response::BatchStarted { ok: true, batch_id },
+                None => Response::Error {
+                    error: format!(
+                        "Streaming commit already in progress: {}",
+                        session.pending_stream.as_ref().map_or("unknown", StreamingCommit::id)
+                    ),
                 },
             }
         }
 
+        Request::StreamCommitNodes { nodes } => {
+            let nodes: Vec<NodeRecord> = nodes.into_iter().map(wire_node_to_record).collect();
+            note_malformed_metadata(metrics, &nodes);
+            stream_commit_frame(session, |stream| stream.add_nodes(nodes))
+        }
+
+        Request::StreamCommitEdges { edges } => {
+            let edges: Vec<EdgeRecord> = edges.into_iter().map(wire_edge_to_record).collect();
+            stream_commit_frame(session, |stream| stream.add_edges(edges))
+        }
+
+        Request::FinishStreamingCommit => {
+            if session.pending_stream.is_none() {
+                return Response::Error { error: "No streaming commit in progress".to_string() };
+            }
+            // Keep the stream open if the commit is refused: it is only
+            // taken once the database accepts writes, and put back if
+            // validation under the write lock fails
+            if let Some(rejection) = commit_target_rejection(session) {
+                return rejection;
+            }
+            let mut stream = session.take_streaming_commit();
+            let ids = IdCheck::new(manager, metrics);
+            let recorders = flush_metrics(metrics, session.current_db.as_deref());
+            let response = with_engine_write(session, |engine| {
+                let Some(staged) = stream.take() else {
+                    return Response::Error { error: "No streaming commit in progress".to_string() };
+                };
+                if let Some(rejection) = ingest_rejection(&*engine, &staged.staged().nodes, ids) {
+                    stream = Some(staged);
+                    return rejection;
+                }
+                let commit = staged.into_commit();
+                handle_commit_batch(
+                    engine, commit.changed_files, commit.nodes, commit.edges, None,
+                    CommitFlush::Full(None), &recorders, Vec::new(), IdCheck::DONE,
+                )
+            });
+            if stream.is_some() {
+                session.pending_stream = stream;
+            }
+            response
+        }
+
         Request::TagSnapshot { version, tags } => {
             with_engine_write(session, |engine| {
                 match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
@@ -1593,7 +3808,132 @@ fn handle_request_with_cancel(
             })
         }
 
-        Request::QueryEdges { id, direction, edge_types, limit } => {
+        Request::DiffSnapshotsDetailed { from_version, to_version, max_ids } => {
+            with_engine_read(session, |engine| {
+                match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                    Some(v2) => {
+                        let max_ids = max_ids.unwrap_or(DEFAULT_DIFF_MAX_IDS);
+                        match v2.diff_snapshot_nodes(from_version, to_version, max_ids) {
+                            Ok(diff) => Response::SnapshotNodeDiffResult {
+                                diff: WireSnapshotNodeDiff {
+                                    from_version: diff.from_version,
+                                    to_version: diff.to_version,
+                                    added: diff.added.into_iter().map(id_to_string).collect(),
+                                    removed: diff.removed.into_iter().map(id_to_string).collect(),
+                                    modified: diff.modified.into_iter().map(id_to_string).collect(),
+                                    added_count: diff.added_count,
+                                    removed_count: diff.removed_count,
+                                    modified_count: diff.modified_count,
+                                    truncated: diff.truncated,
+                                },
+                            },
+                            Err(e) => Response::Error { error: e.to_string() },
+                        }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "DiffSnapshotsDetailed requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::PinSnapshot { version } => {
+            let view = match &session.current_db {
+                Some(db) => {
+                    let engine = db.engine.read().unwrap();
+                    match engine.as_any().downcast_ref::<GraphEngineV2>() {
+                        Some(v2) => v2.snapshot_at(version).map_err(|e| Response::ErrorWithCode {
+                            error: e.to_string(),
+                            code: e.code().to_string(),
+                        }),
+                        None => Err(Response::ErrorWithCode {
+                            error: "PinSnapshot requires v2 engine".to_string(),
+                            code: "V2_REQUIRED".to_string(),
+                        }),
+                    }
+                }
+                None => Err(Response::ErrorWithCode {
+                    error: "No database selected. Use openDatabase first.".to_string(),
+                    code: "NO_DATABASE_SELECTED".to_string(),
+                }),
+            };
+            match view {
+                Ok(engine) => {
+                    session.pin_snapshot(version, engine);
+                    Response::Ok { ok: true }
+                }
+                Err(response) => response,
+            }
+        }
+
+        Request::UnpinSnapshot => {
+            match session.unpin_snapshot() {
+                Some(_) => Response::Ok { ok: true },
+                None => Response::Error {
+                    error: "No snapshot pinned".to_string(),
+                },
+            }
+        }
+
+        Request::RestoreSnapshot { version } => {
+            with_engine_write(session, |engine| {
+                match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+                    Some(v2) => {
+                        match v2.restore_snapshot(version) {
+                            Ok(_) => Response::Ok { ok: true },
+                            Err(e) => Response::ErrorWithCode {
+                                error: e.to_string(),
+                                code: e.code().to_string(),
+                            },
+                        }
+                    }
+                    None => Response::ErrorWithCode {
+                        error: "RestoreSnapshot requires v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    },
+                }
+            })
+        }
+
+        Request::QueryEdges { id, direction, edge_types, limit, metadata: Some(metadata) } if !metadata.is_empty() => {
+            with_engine_read(session, |engine| {
+                let Some(v2) = engine.as_any().downcast_ref::<GraphEngineV2>() else {
+                    return Response::ErrorWithCode {
+                        error: "QueryEdges metadata filters require v2 engine".to_string(),
+                        code: "V2_REQUIRED".to_string(),
+                    };
+                };
+                let node_id = string_to_id(&id);
+                let mut filters: Vec<(String, String)> = metadata.into_iter().collect();
+                filters.sort();
+                let single_type = match edge_types.as_deref() {
+                    Some([edge_type]) => Some(edge_type.as_str()),
+                    _ => None,
+                };
+                let touches = |e: &EdgeRecord| match direction.as_str() {
+                    "outgoing" => e.src == node_id,
+                    "incoming" => e.dst == node_id,
+                    _ => e.src == node_id || e.dst == node_id,
+                };
+                let mut edges: Vec<WireEdge> = v2.find_edges_by_metadata_filters(&filters, single_type)
+                    .into_iter()
+                    .filter(|e| touches(e))
+                    .filter(|e| edge_types.as_ref().is_none_or(|types| {
+                        e.edge_type.as_ref().is_some_and(|t| types.contains(t))
+                    }))
+                    .map(record_to_wire_edge)
+                    .collect();
+
+                if let Some(lim) = limit {
+                    edges.truncate(lim as usize);
+                }
+
+                Response::Edges { edges }
+            })
+        }
+
+        Request::QueryEdges { id, direction, edge_types, limit, .. } => {
             with_engine_read(session, |engine| {
                 let node_id = string_to_id(&id);
                 let edge_types_refs: Option<Vec<&str>> = edge_types.as_ref()
@@ -1603,20 +3943,23 @@ fn handle_request_with_cancel(
                     "outgoing" => {
                         engine.get_outgoing_edges(node_id, edge_types_refs.as_deref())
                             .into_iter()
-                            .map(|e| record_to_wire_edge(&e))
+                            .map(record_to_wire_edge)
                             .collect()
                     }
                     "incoming" => {
                         engine.get_incoming_edges(node_id, edge_types_refs.as_deref())
                             .into_iter()
-                            .map(|e| record_to_wire_edge(&e))
+                            .map(record_to_wire_edge)
                             .collect()
                     }
                     "both" | _ => {
                         let mut all = engine.get_outgoing_edges(node_id, edge_types_refs.as_deref());
                         all.extend(engine.get_incoming_edges(node_id, edge_types_refs.as_deref()));
+                        // A self-loop shows up in both lists; report it once.
+                        let mut seen: HashSet<(u128, u128, Option<String>)> = HashSet::new();
                         all.into_iter()
-                            .map(|e| record_to_wire_edge(&e))
+                            .filter(|e| seen.insert((e.src, e.dst, e.edge_type.clone())))
+                            .map(record_to_wire_edge)
                             .collect()
                     }
                 };
@@ -1660,101 +4003,257 @@ fn handle_request_with_cancel(
             // where cancellation is not supported.
             Response::Error { error: "CancelQuery is only supported over WebSocket".to_string() }
         }
-    }
-}
-
-/// Handle CommitBatch: atomically replace nodes/edges for changed files.
-///
-/// Uses GraphStore trait methods (delete-then-add) which works correctly
-/// for both v1 and v2 engines. The v2-native commit_batch path will be
-/// activated when clients negotiate protocol v3 with semantic IDs.
-///
-/// When `file_context` is provided, the batch operates in enrichment mode:
-/// - The file_context is added to `changed_files` so old enrichment edges
-///   for that virtual file are tombstoned during deletion phase
-/// - Each edge gets `__file_context` injected into its metadata via
-///   `enrichment_edge_metadata()`
-fn handle_commit_batch(
-    engine: &mut dyn GraphStore,
-    mut changed_files: Vec<String>,
-    nodes: Vec<WireNode>,
-    edges: Vec<WireEdge>,
-    file_context: Option<String>,
-    defer_index: bool,
-    protected_types: Vec<String>,
-) -> Response {
-    // If file_context is set, ensure it's included in changed_files
-    // so the deletion phase tombstones old enrichment edges for this context.
-    if let Some(ref ctx) = file_context {
-        if !changed_files.contains(ctx) {
-            changed_files.push(ctx.clone());
-        }
-    }
 
-    let mut nodes_removed: u64 = 0;
-    let mut edges_removed: u64 = 0;
-    let mut changed_node_types: HashSet<String> = HashSet::new();
-    let mut changed_edge_types: HashSet<String> = HashSet::new();
-    let mut deleted_edge_keys: HashSet<(u128, u128, String)> = HashSet::new();
-
-    for file in &changed_files {
-        let attr_query = AttrQuery {
-            version: None,
-            node_type: None,
-            file_id: None,
-            file: Some(file.clone()),
-            exported: None,
-            name: None,
-            metadata_filters: vec![],
-            substring_match: false,
-        };
-        let old_ids = engine.find_by_attr(&attr_query);
+        // ====================================================================
+        // Import / Export Commands
+        // ====================================================================
 
-        for id in &old_ids {
-            // Skip deletion for protected node types (REG-489)
-            if !protected_types.is_empty() {
-                if let Some(node) = engine.get_node(*id) {
-                    if let Some(ref nt) = node.node_type {
-                        if protected_types.contains(nt) {
-                            continue;
+        Request::ImportJsonl { content, kind } => {
+            let ids = IdCheck::new(manager, metrics);
+            with_engine_write(session, |engine| {
+                match kind.as_str() {
+                    "nodes" => {
+                        let batch = parse_jsonl::<WireNode>(&content);
+                        let count = batch.records.len() as u32;
+                        let nodes: Vec<NodeRecord> = batch.records.into_iter().map(wire_node_to_record).collect();
+                        if let Some(rejection) = ingest_rejection(&*engine, &nodes, ids) {
+                            return rejection;
+                        }
+                        engine.add_nodes(nodes);
+                        Response::Count { count, errors: Some(batch.errors) }
+                    }
+                    "edges" => {
+                        let batch = parse_jsonl::<WireEdge>(&content);
+                        let count = batch.records.len() as u32;
+                        let edges: Vec<EdgeRecord> = batch.records.into_iter().map(wire_edge_to_record).collect();
+                        // Same endpoint check as AddEdges without skipValidation
+                        if let Some(rejection) = dangling_edge_rejection(&edges, |id| engine.node_exists(id)) {
+                            return rejection;
                         }
+                        WriteOp::AddEdges { edges, skip_validation: false }.apply(engine);
+                        Response::Count { count, errors: Some(batch.errors) }
                     }
+                    other => Response::Error {
+                        error: format!("Unknown import kind '{}': expected \"nodes\" or \"edges\"", other),
+                    },
                 }
-            }
+            })
+        }
 
-            if let Some(node) = engine.get_node(*id) {
-                if let Some(ref nt) = node.node_type {
-                    changed_node_types.insert(nt.clone());
+        Request::ExportGraphML { node_ids } => {
+            with_engine_read(session, |engine| {
+                let ids: Option<Vec<u128>> = node_ids.map(|v| v.iter().map(|s| string_to_id(s)).collect());
+                let subgraph = rfdb::export::collect_subgraph(engine, ids.as_deref());
+                Response::Export {
+                    format: "graphml".to_string(),
+                    content: rfdb::export::graphml::to_graphml(&subgraph),
                 }
-            }
+            })
+        }
 
-            for edge in engine.get_outgoing_edges(*id, None) {
-                let edge_key = (edge.src, edge.dst, edge.edge_type.clone().unwrap_or_default());
-                if deleted_edge_keys.insert(edge_key) {
-                    if let Some(ref et) = edge.edge_type {
-                        changed_edge_types.insert(et.clone());
-                    }
-                    engine.delete_edge(edge.src, edge.dst, edge.edge_type.as_deref().unwrap_or(""));
-                    edges_removed += 1;
+        Request::ExportDot { node_ids, edge_types } => {
+            with_engine_read(session, |engine| {
+                let ids: Option<Vec<u128>> = node_ids.map(|v| v.iter().map(|s| string_to_id(s)).collect());
+                let mut subgraph = rfdb::export::collect_subgraph(engine, ids.as_deref());
+                if let Some(ref types) = edge_types {
+                    subgraph.retain_edge_types(types);
                 }
-            }
-
-            for edge in engine.get_incoming_edges(*id, None) {
-                let edge_key = (edge.src, edge.dst, edge.edge_type.clone().unwrap_or_default());
-                if deleted_edge_keys.insert(edge_key) {
-                    if let Some(ref et) = edge.edge_type {
-                        changed_edge_types.insert(et.clone());
-                    }
-                    engine.delete_edge(edge.src, edge.dst, edge.edge_type.as_deref().unwrap_or(""));
-                    edges_removed += 1;
+                Response::Export {
+                    format: "dot".to_string(),
+                    content: rfdb::export::dot::to_dot(&subgraph),
                 }
-            }
+            })
+        }
+
+        Request::ExportCsv { target } => {
+            with_engine_read(session, |engine| {
+                let content = match target.as_str() {
+                    "nodes" => rfdb::export::csv::nodes_to_csv(&rfdb::export::collect_subgraph(engine, None).nodes),
+                    "edges" => rfdb::export::csv::edges_to_csv(&engine.get_all_edges()),
+                    other => return Response::Error {
+                        error: format!("Unknown CSV export target '{}': expected \"nodes\" or \"edges\"", other),
+                    },
+                };
+                Response::Export { format: "csv".to_string(), content }
+            })
+        }
+
+        Request::ExportDatabase => {
+            with_engine_read(session, |engine| {
+                let subgraph = rfdb::export::collect_subgraph(engine, None);
+                let metadata = engine.as_any().downcast_ref::<GraphEngineV2>()
+                    .map(GraphEngineV2::dump_metadata)
+                    .unwrap_or_default();
+                match rfdb::export::dump::write_dump(&subgraph, &metadata) {
+                    Ok(bytes) => Response::Dump { data: WireBytes(bytes) },
+                    Err(e) => Response::Error { error: e.to_string() },
+                }
+            })
+        }
+
+        Request::ImportDatabase { data, more } => {
+            let mut dump = session.pending_import.take().unwrap_or_default();
+            if dump.len().saturating_add(data.0.len()) > session.max_stream_bytes {
+                return Response::ErrorWithCode {
+                    error: format!("Database dump exceeds {} bytes; aborted", session.max_stream_bytes),
+                    code: "STREAM_TOO_LARGE".to_string(),
+                };
+            }
+            dump.extend_from_slice(&data.0);
+            if more {
+                session.pending_import = Some(dump);
+                return Response::Ok { ok: true };
+            }
+            let ids = IdCheck::new(manager, metrics);
+            with_engine_write(session, |engine| import_database(engine, &dump, ids))
+        }
+    }
+}
+
+/// Add one frame of records to the session's streaming commit. A frame
+/// that would exceed the byte limit aborts the whole stream, so a partial
+/// payload can never be committed.
+fn stream_commit_frame(session: &mut ClientSession, add: impl FnOnce(&mut StreamingCommit) -> bool) -> Response {
+    let Some(stream) = session.pending_stream.as_mut() else {
+        return Response::Error { error: "No streaming commit in progress".to_string() };
+    };
+    if add(stream) {
+        return Response::Ok { ok: true };
+    }
+    let bytes = stream.bytes();
+    session.pending_stream = None;
+    Response::ErrorWithCode {
+        error: format!(
+            "Streaming commit exceeds {} bytes (staged {}); aborted",
+            session.max_stream_bytes, bytes
+        ),
+        code: "STREAM_TOO_LARGE".to_string(),
+    }
+}
+
+/// Restore the dump `bytes` into `engine`, which must be empty: settings
+/// first, then the records, then a flush so the import is durable once
+/// acknowledged.
+fn import_database(engine: &mut dyn GraphStore, bytes: &[u8], ids: IdCheck) -> Response {
+    if engine.node_count() > 0 || engine.edge_count() > 0 {
+        return Response::ErrorWithCode {
+            error: "ImportDatabase requires an empty database".to_string(),
+            code: "DATABASE_NOT_EMPTY".to_string(),
+        };
+    }
+    let dump = match rfdb::export::dump::read_dump(bytes) {
+        Ok(dump) => dump,
+        Err(e) => return Response::ErrorWithCode { error: e.to_string(), code: "INVALID_DUMP".to_string() },
+    };
+    if let Some(rejection) = ingest_rejection(&*engine, &dump.graph.nodes, ids) {
+        return rejection;
+    }
+    match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+        Some(v2) => {
+            if let Err(e) = v2.restore_dump_metadata(dump.metadata) {
+                return Response::Error { error: format!("Restoring dump settings failed: {}", e) };
+            }
+        }
+        None => engine.declare_fields(dump.metadata.declared_fields),
+    }
+    let nodes_imported = dump.graph.nodes.len() as u32;
+    let edges_imported = dump.graph.edges.len() as u32;
+    engine.add_nodes(dump.graph.nodes);
+    engine.add_edges(dump.graph.edges, true);
+    if let Err(e) = engine.flush() {
+        return Response::Error { error: format!("Flush failed during import: {}", e) };
+    }
+    Response::DumpImported { nodes_imported, edges_imported }
+}
+
+/// How `handle_commit_batch` persists the batch.
+enum CommitFlush {
+    /// deferIndex: write data but skip the index rebuild
+    DataOnly,
+    /// Full flush, optionally overriding the commit's durability
+    Full(Option<WireDurability>),
+}
+
+/// Flush `engine`, committing with `durability` when given. Engines
+/// without a manifest (v1) ignore it.
+fn flush_engine(engine: &mut dyn GraphStore, durability: Option<WireDurability>) -> rfdb::error::Result<()> {
+    match (durability, engine.as_any_mut().downcast_mut::<GraphEngineV2>()) {
+        (Some(durability), Some(v2)) => v2.flush_with_durability(durability.into()),
+        _ => engine.flush(),
+    }
+}
+
+/// Metrics a flush is recorded in: the server-wide ones, if enabled, and
+/// those of the database it ran on.
+fn flush_metrics<'a>(metrics: &'a Option<Arc<Metrics>>, db: Option<&'a Database>) -> Vec<&'a Metrics> {
+    metrics.as_deref().into_iter().chain(db.map(|db| &db.metrics)).collect()
+}
+
+/// Run `flush` on `engine` and, if it succeeds, record its duration and
+/// the nodes and edges it wrote in each of `recorders`.
+fn timed_flush(
+    engine: &mut dyn GraphStore,
+    recorders: &[&Metrics],
+    flush: impl FnOnce(&mut dyn GraphStore) -> rfdb::error::Result<()>,
+) -> rfdb::error::Result<()> {
+    let (nodes, edges) = engine.as_any().downcast_ref::<GraphEngineV2>()
+        .map_or((0, 0), |v2| v2.unflushed_counts());
+    let start = Instant::now();
+    flush(engine)?;
+    let duration_ms = start.elapsed().as_millis() as u64;
+    for m in recorders {
+        m.record_flush(duration_ms, nodes as u64, edges as u64);
+    }
+    Ok(())
+}
+
+/// Handle CommitBatch: atomically replace nodes/edges for changed files.
+///
+/// Uses GraphStore trait methods (delete-then-add) which works correctly
+/// for both v1 and v2 engines. The v2-native commit_batch path will be
+/// activated when clients negotiate protocol v3 with semantic IDs.
+///
+/// When `file_context` is provided, the batch operates in enrichment mode:
+/// - The file_context is added to `changed_files` so old enrichment edges
+///   for that virtual file are tombstoned during deletion phase
+/// - Each edge gets `__file_context` injected into its metadata via
+///   `enrichment_edge_metadata()`
+#[allow(clippy::too_many_arguments)]
+fn handle_commit_batch(
+    engine: &mut dyn GraphStore,
+    mut changed_files: Vec<String>,
+    nodes: Vec<NodeRecord>,
+    edges: Vec<EdgeRecord>,
+    file_context: Option<String>,
+    flush: CommitFlush,
+    flush_metrics: &[&Metrics],
+    protected_types: Vec<String>,
+    ids: IdCheck,
+) -> Response {
+    if let Some(rejection) = ingest_rejection(engine, &nodes, ids) {
+        return rejection;
+    }
 
-            engine.delete_node(*id);
-            nodes_removed += 1;
+    // If file_context is set, ensure it's included in changed_files
+    // so the deletion phase tombstones old enrichment edges for this context.
+    if let Some(ref ctx) = file_context {
+        if !changed_files.contains(ctx) {
+            changed_files.push(ctx.clone());
         }
     }
 
+    let deletions = commit_deletions(engine, &changed_files, &protected_types);
+    let nodes_removed = deletions.node_ids.len() as u64;
+    let edges_removed = deletions.edges.len() as u64;
+    let mut changed_node_types = deletions.node_types;
+    let mut changed_edge_types = deletions.edge_types;
+    for (src, dst, edge_type) in &deletions.edges {
+        engine.delete_edge(*src, *dst, edge_type);
+    }
+    for id in deletions.node_ids {
+        engine.delete_node(id);
+    }
+
     let nodes_added = nodes.len() as u64;
     let edges_added = edges.len() as u64;
 
@@ -1769,29 +4268,25 @@ fn handle_commit_batch(
         }
     }
 
-    let node_records: Vec<NodeRecord> = nodes.into_iter().map(wire_node_to_record).collect();
-    engine.add_nodes(node_records);
+    engine.add_nodes(nodes);
 
     // When file_context is set, inject __file_context into each edge's metadata
     let edge_records: Vec<EdgeRecord> = if let Some(ref ctx) = file_context {
         use rfdb::storage_v2::types::enrichment_edge_metadata;
-        edges.into_iter().map(|edge| {
+        edges.into_iter().map(|mut edge| {
             let existing_metadata = edge.metadata.as_deref().unwrap_or("");
-            let enriched = enrichment_edge_metadata(ctx, existing_metadata);
-            let mut record = wire_edge_to_record(edge);
-            record.metadata = Some(enriched);
-            record
+            edge.metadata = Some(enrichment_edge_metadata(ctx, existing_metadata));
+            edge
         }).collect()
     } else {
-        edges.into_iter().map(wire_edge_to_record).collect()
+        edges
     };
     engine.add_edges(edge_records, true);
 
-    let flush_result = if defer_index {
-        engine.flush_data_only()
-    } else {
-        engine.flush()
-    };
+    let flush_result = timed_flush(engine, flush_metrics, |engine| match flush {
+        CommitFlush::DataOnly => engine.flush_data_only(),
+        CommitFlush::Full(durability) => flush_engine(engine, durability),
+    });
     if let Err(e) = flush_result {
         return Response::Error { error: format!("Flush failed during commit: {}", e) };
     }
@@ -1806,7 +4301,205 @@ fn handle_commit_batch(
         changed_edge_types: changed_edge_types.into_iter().collect(),
     };
 
-    Response::BatchCommitted { ok: true, delta }
+    let version = engine.as_any().downcast_ref::<GraphEngineV2>().map(|v2| v2.write_version());
+    Response::BatchCommitted { ok: true, delta, guarantees: None, version }
+}
+
+/// What the deletion phase of a CommitBatch removes: the nodes of the
+/// changed files (except protected types) and every edge touching them
+#[derive(Default)]
+struct CommitDeletions {
+    node_ids: Vec<u128>,
+    /// (src, dst, edge type), each edge once
+    edges: Vec<(u128, u128, String)>,
+    node_types: HashSet<String>,
+    edge_types: HashSet<String>,
+}
+
+/// Plan the deletion phase of a CommitBatch without changing anything
+fn commit_deletions(engine: &dyn GraphStore, changed_files: &[String], protected_types: &[String]) -> CommitDeletions {
+    let mut deletions = CommitDeletions::default();
+    let mut seen_nodes: HashSet<u128> = HashSet::new();
+    let mut seen_edges: HashSet<(u128, u128, String)> = HashSet::new();
+
+    for file in changed_files {
+        let attr_query = AttrQuery {
+            version: None,
+            node_type: None,
+            file_id: None,
+            file: Some(file.clone()),
+            exported: None,
+            name: None,
+            metadata_filters: vec![],
+            substring_match: false,
+            sorted: false,
+        };
+        for id in engine.find_by_attr(&attr_query) {
+            let node_type = engine.get_node(id).and_then(|node| node.node_type);
+            // Skip deletion for protected node types (REG-489)
+            if node_type.as_ref().is_some_and(|nt| protected_types.contains(nt)) || !seen_nodes.insert(id) {
+                continue;
+            }
+            if let Some(nt) = node_type {
+                deletions.node_types.insert(nt);
+            }
+            let edges = engine.get_outgoing_edges(id, None)
+                .into_iter()
+                .chain(engine.get_incoming_edges(id, None));
+            for edge in edges {
+                let edge_key = (edge.src, edge.dst, edge.edge_type.clone().unwrap_or_default());
+                if seen_edges.insert(edge_key.clone()) {
+                    if let Some(et) = edge.edge_type {
+                        deletions.edge_types.insert(et);
+                    }
+                    deletions.edges.push(edge_key);
+                }
+            }
+            deletions.node_ids.push(id);
+        }
+    }
+    deletions
+}
+
+/// Node and edge types a CommitBatch would change, read from the state
+/// before it is applied (deletion phase plus the batch's own records)
+fn commit_type_changes(
+    engine: &dyn GraphStore,
+    changed_files: &[String],
+    file_context: Option<&str>,
+    protected_types: &[String],
+    nodes: &[NodeRecord],
+    edges: &[EdgeRecord],
+) -> (Vec<String>, Vec<String>) {
+    let mut files = changed_files.to_vec();
+    if let Some(ctx) = file_context {
+        if !files.iter().any(|f| f == ctx) {
+            files.push(ctx.to_string());
+        }
+    }
+    let deletions = commit_deletions(engine, &files, protected_types);
+    let mut node_types = deletions.node_types;
+    let mut edge_types = deletions.edge_types;
+    node_types.extend(nodes.iter().filter_map(|n| n.node_type.clone()));
+    edge_types.extend(edges.iter().filter_map(|e| e.edge_type.clone()));
+    (node_types.into_iter().collect(), edge_types.into_iter().collect())
+}
+
+/// VERSION_CONFLICT response for a write version that moved
+fn version_conflict(database: Option<&str>, expected: u64, actual: u64) -> Response {
+    let e = GraphError::VersionConflict { expected, actual };
+    Response::VersionConflict {
+        error: match database {
+            Some(name) => format!("{}: {}", name, e),
+            None => e.to_string(),
+        },
+        code: e.code().to_string(),
+        database: database.map(str::to_string),
+        expected,
+        actual,
+    }
+}
+
+/// Apply a cross-database batch, all or nothing.
+///
+/// Every staged database is write-locked in name order (the same order for
+/// every caller, so two cross-database commits cannot deadlock), checked
+/// against the write version recorded at BeginCrossDbBatch, its metadata
+/// schemas and ID collisions, and only then committed one by one. If a commit
+/// still fails midway, the databases already committed are restored to
+/// their manifest versions from before the commit.
+///
+/// Restoring a manifest version drops unflushed writes, so every database
+/// is flushed under its lock before that version is recorded; writes other
+/// clients made earlier survive a rollback. Ephemeral databases keep no
+/// manifest history to restore and are refused up front.
+fn commit_cross_db_batch(manager: &DatabaseManager, batch: CrossDbBatch, ids: IdCheck) -> Response {
+    let commits = batch.into_commits();
+    let mut dbs = Vec::with_capacity(commits.len());
+    for (name, _, _) in &commits {
+        match manager.get_database(name) {
+            Ok(db) => dbs.push(db),
+            Err(e) => return Response::ErrorWithCode { error: e.to_string(), code: e.code().to_string() },
+        }
+    }
+    // into_commits sorts by name, so this is the deterministic lock order
+    let mut engines: Vec<_> = dbs.iter()
+        .map(|db| {
+            db.record_write();
+            db.engine.write().unwrap()
+        })
+        .collect();
+
+    // Phase 1: validate everything before writing anything
+    for (((name, expected, commit), engine), db) in commits.iter().zip(&engines).zip(&dbs) {
+        if db.ephemeral {
+            let e = GraphError::EphemeralUnsupported(format!("CommitCrossDbBatch ({})", name));
+            return Response::ErrorWithCode { error: e.to_string(), code: e.code().to_string() };
+        }
+        let current = match engine.as_any().downcast_ref::<GraphEngineV2>() {
+            Some(v2) => v2.write_version(),
+            None => return Response::ErrorWithCode {
+                error: format!("CommitCrossDbBatch requires v2 engine ({})", name),
+                code: "V2_REQUIRED".to_string(),
+            },
+        };
+        if current != *expected {
+            return version_conflict(Some(name), *expected, current);
+        }
+        if let Some(rejection) = ingest_rejection(&***engine, &commit.nodes, ids) {
+            return rejection;
+        }
+    }
+
+    // Flush so the recorded versions hold every write made so far
+    let mut recorded = Vec::with_capacity(commits.len());
+    for ((name, _, _), engine) in commits.iter().zip(engines.iter_mut()) {
+        if let Err(e) = engine.flush() {
+            return Response::ErrorWithCode {
+                error: format!("Flush of {} failed: {}", name, e),
+                code: e.code().to_string(),
+            };
+        }
+        let manifest_version = engine.as_any().downcast_ref::<GraphEngineV2>()
+            .map_or(0, GraphEngineV2::current_version);
+        recorded.push((name.clone(), manifest_version));
+    }
+
+    // Phase 2: commit in lock order, rolling back on failure
+    let mut deltas = BTreeMap::new();
+    for (i, (name, _, commit)) in commits.into_iter().enumerate() {
+        let response = handle_commit_batch(
+            &mut **engines[i], commit.changed_files, commit.nodes, commit.edges, commit.file_context,
+            CommitFlush::Full(None), &flush_metrics(ids.metrics, Some(&dbs[i])), Vec::new(), IdCheck::DONE,
+        );
+        let error = match response {
+            Response::BatchCommitted { delta, .. } => {
+                deltas.insert(name, delta);
+                continue;
+            }
+            Response::Error { error } | Response::ErrorWithCode { error, .. } => error,
+            other => format!("{:?}", other),
+        };
+
+        let mut unrestored = Vec::new();
+        for ((applied, expected), engine) in recorded[..i].iter().zip(engines.iter_mut()) {
+            let restored = engine.as_any_mut().downcast_mut::<GraphEngineV2>()
+                .map(|v2| v2.restore_snapshot(*expected));
+            if !matches!(restored, Some(Ok(_))) {
+                unrestored.push(applied.as_str());
+            }
+        }
+        let outcome = if unrestored.is_empty() {
+            format!("rolled back {} committed database(s)", i)
+        } else {
+            format!("could not roll back {}", unrestored.join(", "))
+        };
+        return Response::ErrorWithCode {
+            error: format!("Commit to {} failed: {}; {}", name, error, outcome),
+            code: "CROSS_DB_COMMIT_FAILED".to_string(),
+        };
+    }
+    Response::CrossDbBatchCommitted { ok: true, deltas }
 }
 
 /// Helper: execute read operation on current database
@@ -1814,6 +4507,9 @@ fn with_engine_read<F>(session: &ClientSession, f: F) -> Response
 where
     F: FnOnce(&dyn GraphStore) -> Response,
 {
+    if let Some(pinned) = &session.pinned_snapshot {
+        return f(&pinned.engine);
+    }
     match &session.current_db {
         Some(db) => {
             let engine = db.engine.read().unwrap();
@@ -1826,6 +4522,87 @@ where
     }
 }
 
+/// Helper: execute read operation through the session's open transaction,
+/// so the session sees its own staged writes
+fn with_transaction_read<F>(session: &ClientSession, f: F) -> Response
+where
+    F: FnOnce(&TransactionView) -> Response,
+{
+    // Staging is rejected while pinned, so a pinned view has nothing to overlay
+    let txn = match session.pinned_snapshot {
+        Some(_) => None,
+        None => session.pending_batch.as_ref(),
+    };
+    with_engine_read(session, |engine| f(&TransactionView::new(engine, txn)))
+}
+
+/// Helper: error response if the session may not write to its open
+/// database, None otherwise
+fn write_rejection(session: &ClientSession) -> Option<Response> {
+    if !session.can_write() {
+        return Some(Response::ErrorWithCode {
+            error: "Operation not allowed in read-only mode".to_string(),
+            code: "READ_ONLY_MODE".to_string(),
+        });
+    }
+    if let Some(version) = session.pinned_version() {
+        return Some(Response::ErrorWithCode {
+            error: format!("Session is pinned to snapshot {}; UnpinSnapshot before writing", version),
+            code: "SNAPSHOT_PINNED".to_string(),
+        });
+    }
+    None
+}
+
+/// Helper: error response if a staged commit could not be applied to the
+/// session's database right now (none open, or writes refused), None
+/// otherwise. Checked before the staged writes are taken from the session.
+fn commit_target_rejection(session: &ClientSession) -> Option<Response> {
+    if !session.has_database() {
+        return Some(Response::ErrorWithCode {
+            error: "No database selected. Use openDatabase first.".to_string(),
+            code: "NO_DATABASE_SELECTED".to_string(),
+        });
+    }
+    write_rejection(session)
+}
+
+/// Helper: why `txn` cannot be committed to `engine`, None if it can.
+/// Runs under the engine write lock, right before the replay.
+fn transaction_rejection(engine: &dyn GraphStore, txn: &Transaction, ids: IdCheck) -> Option<Response> {
+    if let Some(rejection) = ingest_rejection(engine, &txn.staged_nodes(), ids) {
+        return Some(rejection);
+    }
+    let dangling = txn.dangling_edges(engine);
+    if dangling.is_empty() {
+        return None;
+    }
+    let listed: Vec<String> = dangling.iter()
+        .map(|e| format!(
+            "{} -[{}]-> {}",
+            id_to_string(e.src),
+            e.edge_type.as_deref().unwrap_or(""),
+            id_to_string(e.dst),
+        ))
+        .collect();
+    Some(Response::ErrorWithCode {
+        error: format!("Edges reference missing nodes at commit: {}", listed.join(", ")),
+        code: "DANGLING_EDGE".to_string(),
+    })
+}
+
+/// Helper: error response if the session may not read soft-deleted
+/// records (`includeDeleted` is a protocol v3 option), None otherwise
+fn include_deleted_rejection(session: &ClientSession) -> Option<Response> {
+    if session.protocol_version < 3 {
+        return Some(Response::ErrorWithCode {
+            error: "includeDeleted requires protocol v3 (send Hello first)".to_string(),
+            code: "PROTOCOL_V3_REQUIRED".to_string(),
+        });
+    }
+    None
+}
+
 /// Helper: execute write operation on current database
 fn with_engine_write<F>(session: &ClientSession, f: F) -> Response
 where
@@ -1833,12 +4610,10 @@ where
 {
     match &session.current_db {
         Some(db) => {
-            if !session.can_write() {
-                return Response::ErrorWithCode {
-                    error: "Operation not allowed in read-only mode".to_string(),
-                    code: "READ_ONLY_MODE".to_string(),
-                };
+            if let Some(rejection) = write_rejection(session) {
+                return rejection;
             }
+            db.record_write();
             let mut engine = db.engine.write().unwrap();
             f(&mut **engine)
         }
@@ -1849,6 +4624,261 @@ where
     }
 }
 
+/// Helper: refuse `operation` while the session has a batch open. Renames
+/// rewrite whatever the database holds at the time they run, so they can
+/// neither be staged nor mixed into a batch's writes.
+fn refuse_in_batch(session: &ClientSession, operation: &str) -> Option<Response> {
+    session.pending_batch_id().map(|batch_id| Response::Error {
+        error: format!("{} cannot run inside batch {}; commit or abort it first", operation, batch_id),
+    })
+}
+
+/// Helper: stage a write in the session's open transaction, or apply it
+/// to the current database right away when no batch is open
+fn stage_or_apply(session: &mut ClientSession, op: WriteOp) -> Response {
+    if session.pending_batch.is_none() || !session.has_database() {
+        return with_engine_write(session, |engine| {
+            op.apply(engine);
+            Response::Ok { ok: true }
+        });
+    }
+    if let Some(rejection) = write_rejection(session) {
+        return rejection;
+    }
+    let Some(txn) = session.pending_batch.as_mut() else {
+        return Response::Ok { ok: true };
+    };
+    if txn.stage(op) {
+        return Response::Ok { ok: true };
+    }
+    // Like an oversized streaming commit: drop the whole batch so a
+    // partial one can never be committed
+    let bytes = txn.bytes();
+    session.pending_batch = None;
+    Response::ErrorWithCode {
+        error: format!(
+            "Batch exceeds {} bytes (staged {}); aborted",
+            session.max_batch_bytes, bytes
+        ),
+        code: "BATCH_TOO_LARGE".to_string(),
+    }
+}
+
+/// Helper: the nodes of an AddNodesIfAbsent batch to insert -- those whose
+/// ID `exists` rejects, first occurrence only
+fn absent_nodes(nodes: Vec<NodeRecord>, exists: impl Fn(u128) -> bool) -> Vec<NodeRecord> {
+    let mut seen = HashSet::new();
+    nodes.into_iter()
+        .filter(|node| seen.insert(node.id) && !exists(node.id))
+        .collect()
+}
+
+/// Helper: DANGLING_EDGE error listing the edges whose src or dst fails
+/// `node_exists`, None if every endpoint exists
+fn dangling_edge_rejection(edges: &[EdgeRecord], node_exists: impl Fn(u128) -> bool) -> Option<Response> {
+    let dangling: Vec<String> = edges.iter()
+        .filter(|e| !node_exists(e.src) || !node_exists(e.dst))
+        .map(|e| format!(
+            "{} -[{}]-> {}",
+            id_to_string(e.src),
+            e.edge_type.as_deref().unwrap_or(""),
+            id_to_string(e.dst),
+        ))
+        .collect();
+    if dangling.is_empty() {
+        return None;
+    }
+    Some(Response::ErrorWithCode {
+        error: format!("Edges reference missing nodes: {}", dangling.join(", ")),
+        code: "DANGLING_EDGE".to_string(),
+    })
+}
+
+/// Helper: SCHEMA_VIOLATION error for the first node whose metadata breaks
+/// the schema declared for its type (SetMetadataSchema), None if all pass
+/// or the engine has no schemas (v1)
+fn schema_rejection(engine: &dyn GraphStore, nodes: &[NodeRecord]) -> Option<Response> {
+    let v2 = engine.as_any().downcast_ref::<GraphEngineV2>()?;
+    nodes.iter().find_map(|node| {
+        let node_type = node.node_type.as_deref()?;
+        let metadata = node.metadata.as_deref().unwrap_or("");
+        v2.check_metadata_schema(node_type, metadata).err().map(|reason| Response::ErrorWithCode {
+            error: format!("Node {} ({}) violates metadata schema: {}", id_to_string(node.id), node_type, reason),
+            code: "SCHEMA_VIOLATION".to_string(),
+        })
+    })
+}
+
+/// Helper: schema_rejection against the session's current database, for
+/// nodes being staged in a batch
+fn staging_schema_rejection(session: &ClientSession, nodes: &[NodeRecord]) -> Option<Response> {
+    let mut rejection = None;
+    with_engine_read(session, |engine| {
+        rejection = schema_rejection(engine, nodes);
+        Response::Ok { ok: true }
+    });
+    rejection
+}
+
+/// Helper: find nodes whose u128 ID is already held by a node with a
+/// different semantic ID, i.e. two distinct semantic IDs that `hash` maps to
+/// the same ID. Returns `(id, existing, incoming)` per collision.
+///
+/// Stored semantic IDs that do not hash to their ID were synthesized by the
+/// engine from type/name/file and are not compared.
+fn id_collisions(
+    engine: &dyn GraphStore,
+    nodes: &[NodeRecord],
+    hash: fn(&str) -> u128,
+) -> Vec<(u128, String, String)> {
+    let mut batch: HashMap<u128, &str> = HashMap::new();
+    let mut collisions = Vec::new();
+    for node in nodes {
+        let Some(semantic_id) = node.semantic_id.as_deref() else { continue };
+        if hash(semantic_id) != node.id {
+            continue;
+        }
+        let existing = match batch.get(&node.id) {
+            Some(earlier) => Some(earlier.to_string()),
+            None => engine.get_node(node.id)
+                .and_then(|n| n.semantic_id)
+                .filter(|stored| hash(stored) == node.id),
+        };
+        if let Some(existing) = existing {
+            if existing != semantic_id {
+                collisions.push((node.id, existing, semantic_id.to_string()));
+            }
+        }
+        batch.entry(node.id).or_insert(semantic_id);
+    }
+    collisions
+}
+
+/// Helper: log and count ID collisions in an AddNodes batch. In strict
+/// mode (`--strict-ids`) the batch is rejected with `ID_COLLISION`;
+/// otherwise the new node overwrites the old one as before.
+///
+/// Looking up every incoming ID is skipped entirely when collisions are
+/// neither rejected nor counted.
+fn id_collision_rejection(
+    engine: &dyn GraphStore,
+    nodes: &[NodeRecord],
+    hash: fn(&str) -> u128,
+    strict: bool,
+    metrics: &Option<Arc<Metrics>>,
+) -> Option<Response> {
+    if !strict && metrics.is_none() {
+        return None;
+    }
+    let collisions = id_collisions(engine, nodes, hash);
+    if collisions.is_empty() {
+        return None;
+    }
+    for (id, existing, incoming) in &collisions {
+        tracing::warn!(id = %id_to_string(*id), existing = ?existing, incoming = ?incoming, "ID collision");
+    }
+    if let Some(metrics) = metrics {
+        metrics.record_id_collisions(collisions.len() as u64);
+    }
+    if !strict {
+        return None;
+    }
+    let (id, existing, incoming) = &collisions[0];
+    Some(Response::ErrorWithCode {
+        error: format!(
+            "Node {} ({:?}) collides with existing semantic ID {:?}",
+            id_to_string(*id), incoming, existing,
+        ),
+        code: "ID_COLLISION".to_string(),
+    })
+}
+
+/// How an ingest treats ID collisions: rejected under `--strict-ids`,
+/// counted when metrics are enabled.
+#[derive(Clone, Copy)]
+struct IdCheck<'a> {
+    strict: bool,
+    metrics: &'a Option<Arc<Metrics>>,
+    /// Semantic ID hasher (stubbed in tests to force collisions)
+    hash: fn(&str) -> u128,
+}
+
+impl<'a> IdCheck<'a> {
+    /// For nodes that already passed the check under the same lock
+    const DONE: IdCheck<'static> = IdCheck { strict: false, metrics: &None, hash: string_to_id };
+
+    fn new(manager: &DatabaseManager, metrics: &'a Option<Arc<Metrics>>) -> Self {
+        Self { strict: manager.is_strict_ids(), metrics, hash: string_to_id }
+    }
+}
+
+/// Helper: schema or ID collision error for nodes about to be written,
+/// None if they may go in. Callers hold the engine write lock through the
+/// write itself, so nothing can change between the check and the insert.
+fn ingest_rejection(engine: &dyn GraphStore, nodes: &[NodeRecord], ids: IdCheck) -> Option<Response> {
+    schema_rejection(engine, nodes)
+        .or_else(|| id_collision_rejection(engine, nodes, ids.hash, ids.strict, ids.metrics))
+}
+
+/// Helper: collapse edges with the same (src, dst, type) into one, kept at
+/// the position of the first. Metadata objects are merged key by key, later
+/// edges winning; if either side is not a JSON object, the later non-empty
+/// metadata replaces it whole.
+fn merge_duplicate_edges(edges: Vec<EdgeRecord>) -> Vec<EdgeRecord> {
+    let mut merged: Vec<EdgeRecord> = Vec::with_capacity(edges.len());
+    let mut index: HashMap<(u128, u128, Option<String>), usize> = HashMap::new();
+    for edge in edges {
+        let key = (edge.src, edge.dst, edge.edge_type.clone());
+        let Some(&i) = index.get(&key) else {
+            index.insert(key, merged.len());
+            merged.push(edge);
+            continue;
+        };
+        let kept = &mut merged[i];
+        kept.metadata = match (kept.metadata.take(), edge.metadata) {
+            (Some(old), Some(new)) => Some(merge_metadata_objects(old, new)),
+            (old, None) => old,
+            (None, new) => new,
+        };
+        kept.version = edge.version;
+    }
+    merged
+}
+
+/// Later keys of `new` override those of `old`; non-object or empty
+/// metadata is not merged, the later non-empty value wins.
+fn merge_metadata_objects(old: String, new: String) -> String {
+    if new.is_empty() {
+        return old;
+    }
+    let parse = |s: &str| match serde_json::from_str::<serde_json::Value>(s) {
+        Ok(serde_json::Value::Object(map)) => Some(map),
+        _ => None,
+    };
+    match (parse(&old), parse(&new)) {
+        (Some(mut base), Some(overrides)) => {
+            base.extend(overrides);
+            serde_json::Value::Object(base).to_string()
+        }
+        _ => new,
+    }
+}
+
+/// Helper: count nodes whose metadata is not valid JSON into the server
+/// metrics. They are stored as-is but never match metadata filters.
+fn note_malformed_metadata(metrics: &Option<Arc<Metrics>>, nodes: &[NodeRecord]) {
+    let Some(metrics) = metrics else { return };
+    let malformed = nodes.iter()
+        .filter_map(|node| node.metadata.as_deref())
+        .filter(|metadata| {
+            !metadata.is_empty() && serde_json::from_str::<serde::de::IgnoredAny>(metadata).is_err()
+        })
+        .count();
+    if malformed > 0 {
+        metrics.record_malformed_metadata(malformed as u64);
+    }
+}
+
 /// Close current database and decrement connection count
 ///
 /// If the database is ephemeral and no other connections remain,
@@ -1909,23 +4939,76 @@ fn query_result_to_wire_explain(result: QueryResult) -> WireExplainResult {
     }
 }
 
-/// Execute a guarantee check (violation query)
-fn execute_check_guarantee(
-    engine: &dyn GraphStore,
-    rule_source: &str,
-    explain: bool,
-    cancel_flag: Arc<AtomicBool>,
-) -> std::result::Result<DatalogResponse, String> {
-    let program = parse_program(rule_source)
-        .map_err(|e| format!("Datalog parse error: {}", e))?;
+/// Evaluation limits for a Datalog request: the client's cancel flag plus
+/// an optional deadline overriding the default 30s.
+fn datalog_limits(cancel_flag: Arc<AtomicBool>, timeout_ms: Option<u64>) -> EvalLimits {
+    let mut limits = EvalLimits { cancelled: Some(cancel_flag), ..EvalLimits::default() };
+    if let Some(ms) = timeout_ms {
+        limits.deadline = Some(std::time::Instant::now() + Duration::from_millis(ms));
+    }
+    limits
+}
 
-    let violation_query = parse_atom("violation(X)")
-        .map_err(|e| format!("Internal error parsing violation query: {}", e))?;
+/// Map a Datalog evaluation error to a response. Deadline overruns get the
+/// `TIMEOUT` code so clients can tell them apart from bad rules.
+fn datalog_error_response(error: DatalogError) -> Response {
+    match error {
+        DatalogError::Parse { prefix, error, line, col } => Response::ErrorWithPosition {
+            error: format!("{}: {}", prefix, error),
+            code: "DATALOG_PARSE".to_string(),
+            kind: error.kind.as_str().to_string(),
+            position: WireSourcePosition { line, col },
+        },
+        DatalogError::Timeout(error) => Response::ErrorWithCode { error, code: "TIMEOUT".to_string() },
+        DatalogError::Eval(error) => Response::Error { error },
+    }
+}
 
-    let mut limits = EvalLimits::default();
-    limits.cancelled = Some(cancel_flag);
+/// Why a Datalog request failed
+enum DatalogError {
+    /// The source did not parse; `line`/`col` locate the error in it
+    Parse { prefix: &'static str, error: ParseError, line: usize, col: usize },
+    /// Evaluation ran past its deadline
+    Timeout(String),
+    /// Evaluation failed (other limits, cancellation, ...)
+    Eval(String),
+}
+
+impl DatalogError {
+    fn parse(prefix: &'static str, source: &str, error: ParseError) -> Self {
+        let (line, col) = error.line_col(source);
+        DatalogError::Parse { prefix, error, line, col }
+    }
+
+    /// Classify an error returned by `evaluator`
+    fn eval(evaluator: &Evaluator, error: String) -> Self {
+        if evaluator.deadline_exceeded() {
+            DatalogError::Timeout(error)
+        } else {
+            DatalogError::Eval(error)
+        }
+    }
+}
+
+impl From<String> for DatalogError {
+    fn from(error: String) -> Self {
+        DatalogError::Eval(error)
+    }
+}
+
+/// Execute a guarantee check (violation query)
+fn execute_check_guarantee(
+    engine: &dyn GraphStore,
+    rule_source: &str,
+    explain: bool,
+    limits: EvalLimits,
+) -> std::result::Result<DatalogResponse, DatalogError> {
+    let program = parse_program(rule_source)
+        .map_err(|e| DatalogError::parse("Datalog parse error", rule_source, e))?;
 
     if explain {
+        let violation_query = parse_atom("violation(X)")
+            .map_err(|e| format!("Internal error parsing violation query: {}", e))?;
         let mut evaluator = EvaluatorExplain::with_limits(engine, true, limits);
         for rule in program.rules() {
             evaluator.add_rule(rule.clone());
@@ -1933,31 +5016,181 @@ fn execute_check_guarantee(
         let result = evaluator.query(&violation_query);
         Ok(DatalogResponse::Explain(query_result_to_wire_explain(result)))
     } else {
-        let mut evaluator = Evaluator::with_limits(engine, limits);
-        for rule in program.rules() {
-            evaluator.add_rule(rule.clone());
+        Ok(DatalogResponse::Violations(program_violations(engine, &program, limits)?))
+    }
+}
+
+/// Tally live edges by (src node type, dst node type, edge type).
+///
+/// Endpoint types are looked up once per node. Endpoints that no longer
+/// resolve, and untyped nodes or edges, count as "UNKNOWN".
+fn edge_type_matrix(engine: &dyn GraphStore, edge_types: Option<&[String]>) -> Vec<(String, String, String, usize)> {
+    let mut node_types: HashMap<u128, String> = HashMap::new();
+    let mut node_type = |id: u128| -> String {
+        node_types.entry(id)
+            .or_insert_with(|| engine.get_node(id).and_then(|n| n.node_type).unwrap_or_else(|| "UNKNOWN".to_string()))
+            .clone()
+    };
+
+    let mut counts: HashMap<(String, String, String), usize> = HashMap::new();
+    for edge in engine.get_all_edges() {
+        let edge_type = edge.edge_type.unwrap_or_else(|| "UNKNOWN".to_string());
+        if let Some(filter) = edge_types {
+            let matches = filter.iter().any(|f| match f.strip_suffix('*') {
+                Some(prefix) => edge_type.starts_with(prefix),
+                None => edge_type == *f,
+            });
+            if !matches {
+                continue;
+            }
         }
-        let bindings = evaluator.query(&violation_query)?;
-        let violations: Vec<WireViolation> = bindings.into_iter()
-            .map(|b| {
-                let mut map = std::collections::HashMap::new();
-                for (k, v) in b.iter() {
-                    map.insert(k.clone(), v.as_str());
-                }
-                WireViolation { bindings: map }
-            })
+        *counts.entry((node_type(edge.src), node_type(edge.dst), edge_type)).or_insert(0) += 1;
+    }
+
+    let mut cells: Vec<(String, String, String, usize)> = counts.into_iter()
+        .map(|((src, dst, edge_type), count)| (src, dst, edge_type, count))
+        .collect();
+    cells.sort_by(|a, b| b.3.cmp(&a.3).then_with(|| (&a.0, &a.1, &a.2).cmp(&(&b.0, &b.1, &b.2))));
+    cells
+}
+
+/// Evaluate `violation(X)` against `program`'s rules
+fn program_violations(
+    engine: &dyn GraphStore,
+    program: &Program,
+    limits: EvalLimits,
+) -> std::result::Result<Vec<WireViolation>, DatalogError> {
+    let violation_query = parse_atom("violation(X)")
+        .map_err(|e| format!("Internal error parsing violation query: {}", e))?;
+    let mut evaluator = Evaluator::with_limits(engine, limits);
+    for rule in program.rules() {
+        evaluator.add_rule(rule.clone());
+    }
+    let bindings = evaluator.query(&violation_query).map_err(|e| DatalogError::eval(&evaluator, e))?;
+    Ok(bindings.into_iter()
+        .map(|b| {
+            let mut map = std::collections::HashMap::new();
+            for (k, v) in b.iter() {
+                map.insert(k.clone(), v.as_str());
+            }
+            WireViolation { bindings: map }
+        })
+        .collect())
+}
+
+/// Violations of each saved rule set, as sorted binding pairs, keyed by
+/// rule set name
+type ViolationBaseline = HashMap<String, HashSet<Vec<(String, String)>>>;
+
+/// Comparable form of a violation: its bindings, sorted by variable
+fn violation_key(violation: &WireViolation) -> Vec<(String, String)> {
+    let mut key: Vec<(String, String)> = violation.bindings.iter()
+        .map(|(var, value)| (var.clone(), value.clone()))
+        .collect();
+    key.sort();
+    key
+}
+
+/// Saved rule sets narrowed to the `violation` rules whose dependencies
+/// include one of the changed node or edge types, plus their support
+/// rules. Rule sets with no such rule go to `check.skipped`, rule sets
+/// that fail to parse to `check.errors`.
+fn affected_rule_sets(
+    engine: &dyn GraphStore,
+    node_types: &[String],
+    edge_types: &[String],
+    check: &mut WireGuaranteeCheck,
+) -> Vec<(String, Program)> {
+    let Some(v2) = engine.as_any().downcast_ref::<GraphEngineV2>() else {
+        return Vec::new();
+    };
+    let mut affected_sets = Vec::new();
+    for name in v2.rule_set_names() {
+        let Some(source) = v2.rule_set(&name) else { continue };
+        let program = match parse_program(source) {
+            Ok(program) => program,
+            Err(e) => {
+                check.errors.insert(name, format!("Datalog parse error: {}", e));
+                continue;
+            }
+        };
+        let (violation_rules, support_rules): (Vec<&Rule>, Vec<&Rule>) = program.rules().iter()
+            .partition(|rule| rule.head().predicate() == "violation");
+        let affected: Vec<Rule> = violation_rules.into_iter()
+            .filter(|rule| program.rule_type_deps(rule).affected_by(node_types, edge_types))
+            .cloned()
             .collect();
-        Ok(DatalogResponse::Violations(violations))
+        if affected.is_empty() {
+            check.skipped.push(name);
+            continue;
+        }
+        let rules = support_rules.into_iter().cloned().chain(affected).collect();
+        affected_sets.push((name, Program::new(rules)));
+    }
+    affected_sets
+}
+
+/// Violations before a commit of the rule sets it can affect, so that
+/// `recheck_rule_sets` can report only those the commit introduced.
+/// `node_types`/`edge_types` are the commit's changes as predicted by
+/// `commit_type_changes`; rule sets that fail to evaluate get no baseline.
+fn rule_set_baseline(
+    engine: &dyn GraphStore,
+    node_types: &[String],
+    edge_types: &[String],
+    cancel_flag: &Arc<AtomicBool>,
+) -> ViolationBaseline {
+    let mut baseline = ViolationBaseline::new();
+    let affected = affected_rule_sets(engine, node_types, edge_types, &mut WireGuaranteeCheck::default());
+    for (name, program) in affected {
+        if let Ok(violations) = program_violations(engine, &program, datalog_limits(cancel_flag.clone(), None)) {
+            baseline.insert(name, violations.iter().map(violation_key).collect());
+        }
+    }
+    baseline
+}
+
+/// Re-check saved rule sets after a commit (CommitBatch checkRuleSets).
+///
+/// Only rule sets affected by the delta's changed types are evaluated (see
+/// `affected_rule_sets`). Violations already in `baseline` (present
+/// before the commit) are left out.
+fn recheck_rule_sets(
+    engine: &dyn GraphStore,
+    delta: &WireCommitDelta,
+    baseline: &ViolationBaseline,
+    cancel_flag: &Arc<AtomicBool>,
+) -> WireGuaranteeCheck {
+    let mut check = WireGuaranteeCheck::default();
+    let affected = affected_rule_sets(engine, &delta.changed_node_types, &delta.changed_edge_types, &mut check);
+    for (name, program) in affected {
+        match program_violations(engine, &program, datalog_limits(cancel_flag.clone(), None)) {
+            Ok(mut violations) => {
+                if let Some(known) = baseline.get(&name) {
+                    violations.retain(|v| !known.contains(&violation_key(v)));
+                }
+                if !violations.is_empty() {
+                    check.violations.insert(name.clone(), violations);
+                }
+                check.checked.push(name);
+            }
+            Err(DatalogError::Timeout(error) | DatalogError::Eval(error)) => {
+                check.errors.insert(name, error);
+            }
+            Err(DatalogError::Parse { prefix, error, .. }) => {
+                check.errors.insert(name, format!("{}: {}", prefix, error));
+            }
+        }
     }
+    check
 }
 
 /// Execute datalog load rules (returns count of loaded rules)
 fn execute_datalog_load_rules(
-    _engine: &dyn GraphStore,
     source: &str,
-) -> std::result::Result<u32, String> {
+) -> std::result::Result<u32, DatalogError> {
     let program = parse_program(source)
-        .map_err(|e| format!("Datalog parse error: {}", e))?;
+        .map_err(|e| DatalogError::parse("Datalog parse error", source, e))?;
 
     Ok(program.rules().len() as u32)
 }
@@ -1968,9 +5201,9 @@ fn execute_datalog_query(
     query_source: &str,
     explain: bool,
     cancel_flag: Arc<AtomicBool>,
-) -> std::result::Result<DatalogResponse, String> {
+) -> std::result::Result<DatalogResponse, DatalogError> {
     let literals = parse_query(query_source)
-        .map_err(|e| format!("Datalog query parse error: {}", e))?;
+        .map_err(|e| DatalogError::parse("Datalog query parse error", query_source, e))?;
 
     let mut limits = EvalLimits::default();
     limits.cancelled = Some(cancel_flag);
@@ -1981,7 +5214,7 @@ fn execute_datalog_query(
         Ok(DatalogResponse::Explain(query_result_to_wire_explain(result)))
     } else {
         let evaluator = Evaluator::with_limits(engine, limits);
-        let bindings = evaluator.eval_query(&literals)?;
+        let bindings = evaluator.eval_query(&literals).map_err(|e| DatalogError::eval(&evaluator, e))?;
         let results: Vec<WireViolation> = bindings.into_iter()
             .map(|b| {
                 let mut map = std::collections::HashMap::new();
@@ -1997,64 +5230,50 @@ fn execute_datalog_query(
 
 /// Execute unified Datalog — auto-detects rules vs direct query.
 ///
-/// If the source parses as a program with rules, load the rules and query
-/// using the head predicate of the first rule. Otherwise, fall back to
-/// parsing as a direct query.
+/// If the source starts with a rule (see `parse_source`), load the rules and
+/// query using the head predicate of the first rule. Otherwise evaluate it
+/// as a direct query.
 fn execute_datalog(
     engine: &dyn GraphStore,
     source: &str,
     explain: bool,
-    cancel_flag: Arc<AtomicBool>,
-) -> std::result::Result<DatalogResponse, String> {
-    let mut limits = EvalLimits::default();
-    limits.cancelled = Some(cancel_flag.clone());
-
-    // Try parsing as a program first
-    if let Ok(program) = parse_program(source) {
-        if !program.rules().is_empty() {
+    limits: EvalLimits,
+) -> std::result::Result<DatalogResponse, DatalogError> {
+    let literals = match parse_source(source)
+        .map_err(|e| DatalogError::parse("Datalog parse error", source, e))?
+    {
+        Source::Query(literals) => literals,
+        Source::Program(program) => {
+            let head = program.rules()[0].head().clone();
             if explain {
                 let mut evaluator = EvaluatorExplain::with_limits(engine, true, limits);
-                for rule in program.rules() {
-                    evaluator.add_rule(rule.clone());
-                }
-                let head = program.rules()[0].head();
-                let result = evaluator.query(head);
+                evaluator.load_rules(program.rules().to_vec())?;
+                let result = evaluator.query(&head);
                 return Ok(DatalogResponse::Explain(query_result_to_wire_explain(result)));
-            } else {
-                let mut evaluator = Evaluator::with_limits(engine, limits);
-                for rule in program.rules() {
-                    evaluator.add_rule(rule.clone());
-                }
-                let head = program.rules()[0].head();
-                let bindings = evaluator.query(head)?;
-                let results: Vec<WireViolation> = bindings.into_iter()
-                    .map(|b| {
-                        let mut map = std::collections::HashMap::new();
-                        for (k, v) in b.iter() {
-                            map.insert(k.clone(), v.as_str());
-                        }
-                        WireViolation { bindings: map }
-                    })
-                    .collect();
-                return Ok(DatalogResponse::Violations(results));
             }
+            let mut evaluator = Evaluator::with_limits(engine, limits);
+            evaluator.load_rules(program.rules().to_vec())?;
+            let bindings = evaluator.query(&head).map_err(|e| DatalogError::eval(&evaluator, e))?;
+            let results: Vec<WireViolation> = bindings.into_iter()
+                .map(|b| {
+                    let mut map = std::collections::HashMap::new();
+                    for (k, v) in b.iter() {
+                        map.insert(k.clone(), v.as_str());
+                    }
+                    WireViolation { bindings: map }
+                })
+                .collect();
+            return Ok(DatalogResponse::Violations(results));
         }
-    }
-
-    // Fall back to direct query
-    let literals = parse_query(source)
-        .map_err(|e| format!("Datalog parse error: {}", e))?;
-
-    let mut fallback_limits = EvalLimits::default();
-    fallback_limits.cancelled = Some(cancel_flag);
+    };
 
     if explain {
-        let mut evaluator = EvaluatorExplain::with_limits(engine, true, fallback_limits);
+        let mut evaluator = EvaluatorExplain::with_limits(engine, true, limits);
         let result = evaluator.eval_query(&literals)?;
         Ok(DatalogResponse::Explain(query_result_to_wire_explain(result)))
     } else {
-        let evaluator = Evaluator::with_limits(engine, fallback_limits);
-        let bindings = evaluator.eval_query(&literals)?;
+        let evaluator = Evaluator::with_limits(engine, limits);
+        let bindings = evaluator.eval_query(&literals).map_err(|e| DatalogError::eval(&evaluator, e))?;
         let results: Vec<WireViolation> = bindings.into_iter()
             .map(|b| {
                 let mut map = std::collections::HashMap::new();
@@ -2102,8 +5321,14 @@ fn handle_query_nodes_streaming(
         }),
     };
 
-    let engine = db.engine.read().unwrap();
-    let engine_ref: &dyn GraphStore = &**engine;
+    let engine;
+    let engine_ref: &dyn GraphStore = match &session.pinned_snapshot {
+        Some(pinned) => &pinned.engine,
+        None => {
+            engine = db.engine.read().unwrap();
+            &**engine
+        }
+    };
 
     let attr_query = wire_to_attr_query(query);
 
@@ -2129,13 +5354,13 @@ fn handle_query_nodes_streaming(
         match rmp_serde::to_vec_named(&envelope) {
             Ok(bytes) => {
                 if let Err(e) = write_message(stream, &bytes) {
-                    eprintln!("[rfdb-server] Write error during streaming (implicit cancel): {}", e);
+                    tracing::warn!(error = %e, "Write error during streaming (implicit cancel)");
                     return false;
                 }
                 true
             }
             Err(e) => {
-                eprintln!("[rfdb-server] Serialize error during streaming: {}", e);
+                tracing::error!(error = %e, "Serialize error during streaming");
                 false
             }
         }
@@ -2158,7 +5383,7 @@ fn handle_query_nodes_streaming(
             for chunk_ids in initial_buf.chunks(STREAMING_CHUNK_SIZE) {
                 let nodes: Vec<WireNode> = chunk_ids.iter()
                     .filter_map(|&id| engine_ref.get_node(id))
-                    .map(|r| record_to_wire_node(&r))
+                    .map(record_to_wire_node)
                     .collect();
 
                 // Send previous held-back chunk (if any) with done=false
@@ -2179,7 +5404,7 @@ fn handle_query_nodes_streaming(
         // Already in streaming mode — process this chunk
         let nodes: Vec<WireNode> = ids.iter()
             .filter_map(|&id| engine_ref.get_node(id))
-            .map(|r| record_to_wire_node(&r))
+            .map(record_to_wire_node)
             .collect();
 
         // Send previous held-back chunk with done=false
@@ -2202,7 +5427,7 @@ fn handle_query_nodes_streaming(
         // Never crossed threshold — return single response
         let nodes: Vec<WireNode> = initial_buf.into_iter()
             .filter_map(|id| engine_ref.get_node(id))
-            .map(|r| record_to_wire_node(&r))
+            .map(record_to_wire_node)
             .collect();
         return HandleResult::Single(Response::Nodes { nodes });
     }
@@ -2217,11 +5442,92 @@ fn handle_query_nodes_streaming(
     HandleResult::Streamed
 }
 
+/// ExportChunk/DumpChunk frames replacing `response` when it is an Export
+/// or Dump larger than EXPORT_STREAMING_THRESHOLD and the client speaks
+/// protocol v3+; None to send it as a single frame. Shared by the Unix
+/// socket and WebSocket transports.
+fn large_response_chunks(
+    response: &Response,
+    protocol_version: u32,
+) -> Option<Box<dyn Iterator<Item = Response> + Send + '_>> {
+    if protocol_version < 3 {
+        return None;
+    }
+    match response {
+        Response::Export { format, content } if content.len() > EXPORT_STREAMING_THRESHOLD => {
+            Some(Box::new(export_chunks(format, content)))
+        }
+        Response::Dump { data } if data.0.len() > EXPORT_STREAMING_THRESHOLD => {
+            Some(Box::new(dump_chunks(&data.0)))
+        }
+        _ => None,
+    }
+}
+
+/// A large export document as a sequence of ExportChunk frames, split on
+/// UTF-8 character boundaries so every frame carries a valid string.
+fn export_chunks<'a>(format: &'a str, content: &'a str) -> impl Iterator<Item = Response> + Send + 'a {
+    let mut start = 0;
+    let mut chunk_index: u32 = 0;
+    std::iter::from_fn(move || {
+        if start >= content.len() {
+            return None;
+        }
+        let mut end = std::cmp::min(start + EXPORT_CHUNK_BYTES, content.len());
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        let chunk = Response::ExportChunk {
+            format: format.to_string(),
+            content: content[start..end].to_string(),
+            done: end == content.len(),
+            chunk_index,
+        };
+        start = end;
+        chunk_index += 1;
+        Some(chunk)
+    })
+}
+
+/// A large dump as a sequence of DumpChunk frames.
+fn dump_chunks(data: &[u8]) -> impl Iterator<Item = Response> + Send + '_ {
+    let chunk_count = data.len().div_ceil(EXPORT_CHUNK_BYTES);
+    data.chunks(EXPORT_CHUNK_BYTES).enumerate().map(move |(chunk_index, chunk)| Response::DumpChunk {
+        data: WireBytes(chunk.to_vec()),
+        done: chunk_index + 1 == chunk_count,
+        chunk_index: chunk_index as u32,
+    })
+}
+
+/// Write chunk frames to a Unix socket client. Write errors end the
+/// stream early (implicit cancel).
+fn stream_chunks(
+    chunks: impl Iterator<Item = Response>,
+    request_id: &Option<String>,
+    stream: &mut UnixStream,
+) -> HandleResult {
+    for response in chunks {
+        let envelope = ResponseEnvelope { request_id: request_id.clone(), response };
+        let bytes = match rmp_serde::to_vec_named(&envelope) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!(error = %e, "Serialize error during chunked streaming");
+                break;
+            }
+        };
+        if let Err(e) = write_message(stream, &bytes) {
+            tracing::warn!(error = %e, "Write error during chunked streaming (implicit cancel)");
+            break;
+        }
+    }
+    HandleResult::Streamed
+}
+
 // ============================================================================
 // Client Connection Handler
 // ============================================================================
 
-fn read_message(stream: &mut UnixStream) -> std::io::Result<Option<Vec<u8>>> {
+fn read_message(stream: &mut UnixStream, max_bytes: usize) -> std::io::Result<Option<Vec<u8>>> {
     // Read 4-byte length prefix (big-endian)
     let mut len_buf = [0u8; 4];
     match stream.read_exact(&mut len_buf) {
@@ -2231,10 +5537,10 @@ fn read_message(stream: &mut UnixStream) -> std::io::Result<Option<Vec<u8>>> {
     }
 
     let len = u32::from_be_bytes(len_buf) as usize;
-    if len > 100 * 1024 * 1024 {
+    if len > max_bytes {
         return Err(std::io::Error::new(
             std::io::ErrorKind::InvalidData,
-            format!("Message too large: {} bytes", len),
+            format!("Message too large: {} bytes (limit {})", len, max_bytes),
         ));
     }
 
@@ -2248,44 +5554,141 @@ fn read_message(stream: &mut UnixStream) -> std::io::Result<Option<Vec<u8>>> {
 fn write_message(stream: &mut UnixStream, data: &[u8]) -> std::io::Result<()> {
     // Write 4-byte length prefix (big-endian)
     let len = data.len() as u32;
-    stream.write_all(&len.to_be_bytes())?;
-    stream.write_all(data)?;
-    stream.flush()?;
-    Ok(())
+    let result = stream.write_all(&len.to_be_bytes())
+        .and_then(|()| stream.write_all(data))
+        .and_then(|()| stream.flush());
+    match result {
+        // The write timeout fired (WouldBlock on Unix sockets): the client
+        // stopped reading and a frame may be half-written, so the
+        // connection is unusable. Shut it down; the handler loop then sees
+        // EOF and runs its normal cleanup.
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "write timed out (client not reading)",
+            ))
+        }
+        other => other,
+    }
+}
+
+/// Request ID of the heartbeat frame sent to idle clients that opted in
+/// with `heartbeat` in Hello (`--idle-timeout-secs`). Clients answer with
+/// any request, e.g. Ping.
+const HEARTBEAT_REQUEST_ID: &str = "__heartbeat";
+
+/// Wait up to `idle_timeout` for the next frame to start.
+///
+/// If the client stays silent, send it a heartbeat (a Pong envelope with
+/// `HEARTBEAT_REQUEST_ID`) and wait once more. Returns false when the
+/// client answered neither, so the connection should be dropped. EOF and
+/// socket errors count as readable and are reported by the following
+/// `read_message`.
+fn await_client_frame(stream: &mut UnixStream, idle_timeout: Duration) -> bool {
+    if wait_readable(stream, idle_timeout) {
+        return true;
+    }
+    let heartbeat = ResponseEnvelope {
+        request_id: Some(HEARTBEAT_REQUEST_ID.to_string()),
+        response: Response::Pong { pong: true, version: env!("CARGO_PKG_VERSION").to_string() },
+    };
+    let sent = rmp_serde::to_vec_named(&heartbeat)
+        .is_ok_and(|bytes| write_message(stream, &bytes).is_ok());
+    sent && wait_readable(stream, idle_timeout)
+}
+
+/// poll(2) the socket for input. Unlike a read timeout this never
+/// interrupts a frame that is already being read.
+fn wait_readable(stream: &UnixStream, timeout: Duration) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    let mut fds = libc::pollfd { fd: stream.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    loop {
+        let ret = unsafe { libc::poll(&mut fds, 1, timeout_ms) };
+        if ret < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+            continue;
+        }
+        return ret != 0;
+    }
+}
+
+/// Response sent before disconnecting a client over its `--max-conn-bytes`
+/// quota.
+fn quota_exceeded_response(session: &ClientSession) -> Response {
+    Response::ErrorWithCode {
+        error: format!(
+            "Connection byte quota exceeded: {} bytes read, limit {}",
+            session.bytes_read,
+            session.byte_quota.unwrap_or(0)
+        ),
+        code: "QUOTA_EXCEEDED".to_string(),
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_client_unix(
     mut stream: UnixStream,
     manager: Arc<DatabaseManager>,
     client_id: usize,
     legacy_mode: bool,
     metrics: Option<Arc<Metrics>>,
+    write_timeout: Duration,
+    max_conn_bytes: Option<u64>,
+    max_message_bytes: usize,
+    idle_timeout: Option<Duration>,
 ) {
-    eprintln!("[rfdb-server] Client {} connected", client_id);
+    tracing::info!(client_id, "Client connected");
+
+    if let Err(e) = stream.set_write_timeout(Some(write_timeout)) {
+        tracing::warn!(client_id, error = %e, "Failed to set write timeout");
+    }
 
     let mut session = ClientSession::new(client_id);
+    session.byte_quota = max_conn_bytes;
 
     // In legacy mode (protocol v1), auto-open "default" database
     if legacy_mode {
         if let Ok(db) = manager.get_database("default") {
             db.add_connection();
-            session.set_database(db, AccessMode::ReadWrite);
+            session.set_database(db, manager.effective_mode(AccessMode::ReadWrite));
         }
     }
 
     loop {
-        let msg = match read_message(&mut stream) {
+        // Only clients that asked for heartbeats expect unsolicited frames
+        let heartbeat_timeout = idle_timeout.filter(|_| session.heartbeat);
+        if heartbeat_timeout.is_some_and(|timeout| !await_client_frame(&mut stream, timeout)) {
+            tracing::info!(client_id, "Client idle and not answering heartbeat, disconnecting");
+            break;
+        }
+
+        let msg = match read_message(&mut stream, max_message_bytes) {
             Ok(Some(msg)) => msg,
             Ok(None) => {
-                eprintln!("[rfdb-server] Client {} disconnected", client_id);
+                tracing::info!(client_id, "Client disconnected");
                 break;
             }
             Err(e) => {
-                eprintln!("[rfdb-server] Client {} read error: {}", client_id, e);
+                tracing::warn!(client_id, error = %e, "Client read error");
                 break;
             }
         };
 
+        // 4-byte length prefix + payload
+        if !session.record_bytes_read(msg.len() + 4) {
+            tracing::warn!(client_id, bytes_read = session.bytes_read, "Client exceeded byte quota, disconnecting");
+            let envelope = ResponseEnvelope {
+                request_id: rmp_serde::from_slice::<RequestEnvelope>(&msg).ok().and_then(|env| env.request_id),
+                response: quota_exceeded_response(&session),
+            };
+            if let Ok(resp_bytes) = rmp_serde::to_vec_named(&envelope) {
+                let _ = write_message(&mut stream, &resp_bytes);
+            }
+            break;
+        }
+
         let (request_id, request) = match rmp_serde::from_slice::<RequestEnvelope>(&msg) {
             Ok(env) => (env.request_id, env.request),
             Err(e) => {
@@ -2304,6 +5707,9 @@ fn handle_client_unix(
         // Time the request for metrics
         let start = Instant::now();
         let op_name = get_operation_name(&request);
+        let params = request_param_summary(&request);
+
+        let in_flight = InFlightRequest::start();
 
         // Streaming commands: handle directly (need stream access for multi-frame writes).
         // Only stream when client negotiated protocol v3+.
@@ -2316,24 +5722,29 @@ fn handle_client_unix(
             }
         };
 
+        // Large exports reuse the chunked framing of QueryNodes streaming.
+        let handle_result = match handle_result {
+            HandleResult::Single(response) => {
+                let streamed = large_response_chunks(&response, session.protocol_version)
+                    .map(|chunks| stream_chunks(chunks, &request_id, &mut stream));
+                streamed.unwrap_or(HandleResult::Single(response))
+            }
+            other => other,
+        };
+
         // Record metrics if enabled
         if let Some(ref m) = metrics {
             let duration_ms = start.elapsed().as_millis() as u64;
-            m.record_query(&op_name, duration_ms);
-
             // Track timeout/cancelled queries
-            if let HandleResult::Single(Response::Error { ref error }) = handle_result {
-                if error.contains("timeout") || error.contains("deadline exceeded") {
-                    m.record_timeout();
-                } else if error.contains("cancelled") {
-                    m.record_cancelled();
-                }
-            }
+            let error = match handle_result {
+                HandleResult::Single(Response::Error { ref error })
+                | HandleResult::Single(Response::ErrorWithCode { ref error, .. }) => Some(error.as_str()),
+                _ => None,
+            };
+            record_request_metrics(m, session.current_db.as_deref(), &op_name, params.as_deref(), duration_ms, error);
 
-            // Log slow queries to stderr (existing pattern)
             if duration_ms >= SLOW_QUERY_THRESHOLD_MS {
-                eprintln!("[RUST SLOW] {}: {}ms (client {})",
-                         op_name, duration_ms, client_id);
+                tracing::warn!(client_id, operation = %op_name, duration_ms, "Slow request");
             }
         }
 
@@ -2346,13 +5757,13 @@ fn handle_client_unix(
                 let resp_bytes = match rmp_serde::to_vec_named(&envelope) {
                     Ok(bytes) => bytes,
                     Err(e) => {
-                        eprintln!("[rfdb-server] Serialize error: {}", e);
+                        tracing::error!(client_id, error = %e, "Serialize error");
                         continue;
                     }
                 };
 
                 if let Err(e) = write_message(&mut stream, &resp_bytes) {
-                    eprintln!("[rfdb-server] Client {} write error: {}", client_id, e);
+                    tracing::warn!(client_id, error = %e, "Client write error");
                     break;
                 }
             }
@@ -2360,9 +5771,11 @@ fn handle_client_unix(
                 // Handler already wrote frames directly to stream
             }
         }
+        drop(in_flight);
 
         if is_shutdown {
-            eprintln!("[rfdb-server] Shutdown requested by client {}", client_id);
+            tracing::info!(client_id, "Shutdown requested by client");
+            drain_and_flush(&manager);
             std::process::exit(0);
         }
     }
@@ -2378,24 +5791,65 @@ fn handle_client_unix(
 /// Send timeout for WebSocket writes. Protects against slow/stalled clients.
 const WS_SEND_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// Send chunk frames to a WebSocket client, one binary message each.
+/// Returns false if the connection should be closed (write error or
+/// timeout).
+async fn send_ws_chunks<S>(
+    ws_write: &mut S,
+    client_id: usize,
+    chunks: impl Iterator<Item = Response>,
+    request_id: &Option<String>,
+) -> bool
+where
+    S: futures_util::Sink<Message> + Unpin,
+    S::Error: std::fmt::Display,
+{
+    for response in chunks {
+        let envelope = ResponseEnvelope { request_id: request_id.clone(), response };
+        let bytes = match rmp_serde::to_vec_named(&envelope) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!(client_id, transport = "websocket", error = %e, "Serialize error during chunked streaming");
+                return true;
+            }
+        };
+        match timeout(WS_SEND_TIMEOUT, ws_write.send(Message::Binary(bytes))).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                tracing::warn!(client_id, transport = "websocket", error = %e, "Client write error during chunked streaming");
+                return false;
+            }
+            Err(_) => {
+                tracing::warn!(client_id, transport = "websocket", timeout_secs = WS_SEND_TIMEOUT.as_secs(), "Client write timeout during chunked streaming, closing connection");
+                return false;
+            }
+        }
+    }
+    true
+}
+
 async fn handle_client_websocket(
     tcp_stream: tokio::net::TcpStream,
     manager: Arc<DatabaseManager>,
     client_id: usize,
     metrics: Option<Arc<Metrics>>,
+    max_conn_bytes: Option<u64>,
 ) {
-    eprintln!("[rfdb-server] WebSocket client {} connected", client_id);
+    tracing::info!(client_id, transport = "websocket", "Client connected");
 
     let ws_stream = match tokio_tungstenite::accept_async(tcp_stream).await {
         Ok(ws) => ws,
         Err(e) => {
-            eprintln!("[rfdb-server] WebSocket upgrade failed for client {}: {}", client_id, e);
+            tracing::warn!(client_id, transport = "websocket", error = %e, "WebSocket upgrade failed");
             return;
         }
     };
 
     let (mut ws_write, mut ws_read) = ws_stream.split();
     let mut session = Some(ClientSession::new(client_id));
+    if let Some(sess) = session.as_mut() {
+        sess.byte_quota = max_conn_bytes;
+    }
     let mut active_cancel_flag: Option<Arc<AtomicBool>> = None;
 
     // WebSocket clients MUST send Hello first (no legacy mode)
@@ -2404,11 +5858,11 @@ async fn handle_client_websocket(
         let msg = match ws_read.next().await {
             Some(Ok(Message::Binary(data))) => data,
             Some(Ok(Message::Close(_))) => {
-                eprintln!("[rfdb-server] WebSocket client {} disconnected (Close frame)", client_id);
+                tracing::info!(client_id, transport = "websocket", "Client disconnected (Close frame)");
                 break;
             }
             Some(Ok(Message::Text(_))) => {
-                eprintln!("[rfdb-server] WebSocket client {} sent text frame (expected binary), ignoring", client_id);
+                tracing::warn!(client_id, transport = "websocket", "Client sent text frame (expected binary), ignoring");
                 continue;
             }
             Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => {
@@ -2418,19 +5872,32 @@ async fn handle_client_websocket(
                 continue;
             }
             Some(Err(e)) => {
-                eprintln!("[rfdb-server] WebSocket client {} read error: {}", client_id, e);
+                tracing::warn!(client_id, transport = "websocket", error = %e, "Client read error");
                 break;
             }
             None => {
-                eprintln!("[rfdb-server] WebSocket client {} stream closed", client_id);
+                tracing::info!(client_id, transport = "websocket", "Client stream closed");
                 break;
             }
         };
 
+        let sess = session.as_mut().unwrap();
+        if !sess.record_bytes_read(msg.len()) {
+            tracing::warn!(client_id, transport = "websocket", bytes_read = sess.bytes_read, "Client exceeded byte quota, disconnecting");
+            let envelope = ResponseEnvelope {
+                request_id: rmp_serde::from_slice::<RequestEnvelope>(&msg).ok().and_then(|env| env.request_id),
+                response: quota_exceeded_response(sess),
+            };
+            if let Ok(resp_bytes) = rmp_serde::to_vec_named(&envelope) {
+                let _ = timeout(WS_SEND_TIMEOUT, ws_write.send(Message::Binary(resp_bytes))).await;
+            }
+            break;
+        }
+
         let (request_id, request) = match rmp_serde::from_slice::<RequestEnvelope>(&msg) {
             Ok(env) => (env.request_id, env.request),
             Err(e) => {
-                eprintln!("[rfdb-server] WebSocket client {} invalid MessagePack: {}", client_id, e);
+                tracing::warn!(client_id, transport = "websocket", error = %e, "Invalid MessagePack");
                 let envelope = ResponseEnvelope {
                     request_id: None,
                     response: Response::Error { error: format!("Invalid request: {}", e) },
@@ -2448,7 +5915,7 @@ async fn handle_client_websocket(
         if let Request::CancelQuery { request_id: cancel_target } = &request {
             if let Some(ref flag) = active_cancel_flag {
                 flag.store(true, Ordering::Relaxed);
-                eprintln!("[rfdb-server] WebSocket client {}: cancel requested for {}", client_id, cancel_target);
+                tracing::info!(client_id, transport = "websocket", request_id = %cancel_target, "Cancel requested");
             }
             let envelope = ResponseEnvelope {
                 request_id: request_id.clone(),
@@ -2462,6 +5929,7 @@ async fn handle_client_websocket(
 
         let start = Instant::now();
         let op_name = get_operation_name(&request);
+        let params = request_param_summary(&request);
 
         // Create a cancellation flag for this request
         let cancel_flag = Arc::new(AtomicBool::new(false));
@@ -2472,6 +5940,7 @@ async fn handle_client_websocket(
         let metrics_clone = metrics.clone();
         let mut sess = session.take().unwrap();
         let mut blocking_handle = tokio::task::spawn_blocking(move || {
+            let _in_flight = InFlightRequest::start();
             let resp = handle_request_with_cancel(&manager_clone, &mut sess, request, &metrics_clone, cancel_flag);
             (resp, sess)
         });
@@ -2489,7 +5958,7 @@ async fn handle_client_websocket(
                             if let Request::CancelQuery { .. } = env.request {
                                 if let Some(ref flag) = active_cancel_flag {
                                     flag.store(true, Ordering::Relaxed);
-                                    eprintln!("[rfdb-server] WebSocket client {}: cancel signal sent", client_id);
+                                    tracing::info!(client_id, transport = "websocket", "Cancel signal sent");
                                 }
                                 let cancel_envelope = ResponseEnvelope {
                                     request_id: env.request_id,
@@ -2522,33 +5991,39 @@ async fn handle_client_websocket(
                 session = Some(sess_back);
             }
             Err(e) => {
-                eprintln!("[rfdb-server] WebSocket client {} handler panic: {}", client_id, e);
+                tracing::error!(client_id, transport = "websocket", error = %e, "Handler panic");
                 break;
             }
         }
 
         if let Some(ref m) = metrics {
             let duration_ms = start.elapsed().as_millis() as u64;
-            m.record_query(&op_name, duration_ms);
+            let error = match response {
+                Response::Error { ref error } | Response::ErrorWithCode { ref error, .. } => Some(error.as_str()),
+                _ => None,
+            };
+            let db = session.as_ref().and_then(|s| s.current_db.as_deref());
+            record_request_metrics(m, db, &op_name, params.as_deref(), duration_ms, error);
 
-            if let Response::Error { ref error } = response {
-                if error.contains("timeout") || error.contains("deadline exceeded") {
-                    m.record_timeout();
-                } else if error.contains("cancelled") {
-                    m.record_cancelled();
-                }
+            if duration_ms >= SLOW_QUERY_THRESHOLD_MS {
+                tracing::warn!(client_id, transport = "websocket", operation = %op_name, duration_ms, "Slow request");
             }
+        }
 
-            if duration_ms >= SLOW_QUERY_THRESHOLD_MS {
-                eprintln!("[RUST SLOW] {}: {}ms (ws client {})", op_name, duration_ms, client_id);
+        // Large exports and dumps are chunked as on the Unix socket
+        let protocol_version = session.as_ref().map_or(1, |s| s.protocol_version);
+        if let Some(chunks) = large_response_chunks(&response, protocol_version) {
+            if !send_ws_chunks(&mut ws_write, client_id, chunks, &request_id).await {
+                break;
             }
+            continue;
         }
 
         let envelope = ResponseEnvelope { request_id: request_id.clone(), response };
         let resp_bytes = match rmp_serde::to_vec_named(&envelope) {
             Ok(bytes) => bytes,
             Err(e) => {
-                eprintln!("[rfdb-server] WebSocket client {} serialize error: {}", client_id, e);
+                tracing::error!(client_id, transport = "websocket", error = %e, "Serialize error");
                 // Try to send a fallback error so client doesn't hang
                 let fallback = ResponseEnvelope {
                     request_id,
@@ -2561,7 +6036,7 @@ async fn handle_client_websocket(
                         let _ = timeout(WS_SEND_TIMEOUT, ws_write.send(Message::Binary(fallback_bytes))).await;
                     }
                     Err(e2) => {
-                        eprintln!("[rfdb-server] WebSocket client {} fallback serialize also failed: {}", client_id, e2);
+                        tracing::error!(client_id, transport = "websocket", error = %e2, "Fallback serialize also failed");
                         break;
                     }
                 }
@@ -2572,18 +6047,19 @@ async fn handle_client_websocket(
         match timeout(WS_SEND_TIMEOUT, ws_write.send(Message::Binary(resp_bytes))).await {
             Ok(Ok(())) => {}
             Ok(Err(e)) => {
-                eprintln!("[rfdb-server] WebSocket client {} write error: {}", client_id, e);
+                tracing::warn!(client_id, transport = "websocket", error = %e, "Client write error");
                 break;
             }
             Err(_) => {
-                eprintln!("[rfdb-server] WebSocket client {} write timeout ({}s) - closing connection",
-                          client_id, WS_SEND_TIMEOUT.as_secs());
+                tracing::warn!(client_id, transport = "websocket", timeout_secs = WS_SEND_TIMEOUT.as_secs(), "Client write timeout, closing connection");
                 break;
             }
         }
 
         if is_shutdown {
-            eprintln!("[rfdb-server] Shutdown requested by WebSocket client {}", client_id);
+            tracing::info!(client_id, transport = "websocket", "Shutdown requested by client");
+            let manager = Arc::clone(&manager);
+            let _ = tokio::task::spawn_blocking(move || drain_and_flush(&manager)).await;
             std::process::exit(0);
         }
     }
@@ -2591,80 +6067,291 @@ async fn handle_client_websocket(
     if let Some(ref mut sess) = session {
         handle_close_database(&manager, sess);
     }
-    eprintln!("[rfdb-server] WebSocket client {} cleaned up", client_id);
+    tracing::info!(client_id, transport = "websocket", "Client cleaned up");
 }
 
 // ============================================================================
-// Main
+// Graceful Shutdown
 // ============================================================================
 
-#[tokio::main]
-async fn main() {
-    let args: Vec<String> = std::env::args().collect();
+/// Counts a request as in flight for as long as the guard is alive
+struct InFlightRequest;
 
-    // Handle --version / -V flag
-    if args.iter().any(|a| a == "--version" || a == "-V") {
-        println!("rfdb-server {}", env!("CARGO_PKG_VERSION"));
-        std::process::exit(0);
+impl InFlightRequest {
+    fn start() -> Self {
+        ACTIVE_REQUESTS.fetch_add(1, Ordering::SeqCst);
+        InFlightRequest
     }
+}
 
-    // Handle --help / -h flag
-    if args.iter().any(|a| a == "--help" || a == "-h") {
-        println!("rfdb-server {}", env!("CARGO_PKG_VERSION"));
-        println!();
-        println!("High-performance disk-backed graph database server for Grafema");
-        println!();
-        println!("Usage: rfdb-server <db-path> [--socket <socket-path>] [--ws-port <port>] [--data-dir <dir>] [--metrics]");
-        println!();
-        println!("Arguments:");
-        println!("  <db-path>      Path to default graph database directory");
-        println!("  --socket       Unix socket path (default: /tmp/rfdb.sock)");
-        println!("  --ws-port      WebSocket port (1-65535, e.g., 7474, localhost-only)");
-        println!("  --data-dir     Base directory for multi-database storage");
-        println!();
-        println!("Flags:");
-        println!("  -V, --version  Print version information");
-        println!("  -h, --help     Print this help message");
-        println!("  --metrics      Enable performance metrics collection");
-        std::process::exit(0);
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        ACTIVE_REQUESTS.fetch_sub(1, Ordering::SeqCst);
     }
+}
 
-    if args.len() < 2 {
-        eprintln!("Usage: rfdb-server <db-path> [--socket <socket-path>] [--ws-port <port>] [--data-dir <dir>] [--metrics]");
-        eprintln!("");
-        eprintln!("Arguments:");
-        eprintln!("  <db-path>      Path to default graph database directory");
-        eprintln!("  --socket       Unix socket path (default: /tmp/rfdb.sock)");
-        eprintln!("  --ws-port      WebSocket port (1-65535, e.g., 7474, localhost-only)");
-        eprintln!("  --data-dir     Base directory for multi-database storage");
-        eprintln!("  --metrics      Enable performance metrics collection");
-        std::process::exit(1);
+/// Put the server into draining mode, give in-flight requests up to
+/// `DRAIN_TIMEOUT` to finish, then flush every database.
+///
+/// Once draining, new connections are dropped and write requests fail
+/// with `SERVER_DRAINING`; reads keep working until the process exits.
+fn drain_and_flush(manager: &DatabaseManager) {
+    manager.start_draining();
+
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    while ACTIVE_REQUESTS.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let remaining = ACTIVE_REQUESTS.load(Ordering::SeqCst);
+    if remaining > 0 {
+        tracing::warn!(remaining, timeout_secs = DRAIN_TIMEOUT.as_secs(), "Requests still running after drain timeout, flushing anyway");
     }
 
-    let db_path_str = &args[1];
-
-    // Validate db-path doesn't look like a flag
-    if db_path_str.starts_with("--") {
-        eprintln!("Error: db-path '{}' looks like a flag, not a path.", db_path_str);
-        eprintln!("");
-        eprintln!("Correct usage:");
-        eprintln!("  rfdb-server ./my-graph.rfdb --socket /tmp/rfdb.sock");
-        eprintln!("");
-        eprintln!("The first argument must be the database path, not a flag.");
-        std::process::exit(1);
+    let failures = manager.flush_all();
+    for (name, e) in &failures {
+        tracing::error!(database = %name, error = %e, "Flush failed");
     }
+    tracing::info!(databases = manager.databases().len() - failures.len(), "Flushed databases");
+}
 
-    let db_path = PathBuf::from(db_path_str);
-    eprintln!("[rfdb-server] Starting rfdb-server v{}", env!("CARGO_PKG_VERSION"));
-    let socket_path = args.iter()
-        .position(|a| a == "--socket")
-        .and_then(|i| args.get(i + 1))
-        .map(|s| s.as_str())
-        .unwrap_or("/tmp/rfdb.sock");
+// ============================================================================
+// Background Compaction (--auto-compact)
+// ============================================================================
 
-    let ws_port: Option<u16> = args.iter()
-        .position(|a| a == "--ws-port")
-        .and_then(|i| args.get(i + 1))
+/// Write requests per scheduler interval above which a database counts as
+/// busy; compaction is deferred to a quieter tick.
+const AUTO_COMPACT_BUSY_WRITES: u64 = 100;
+
+/// What the background compactor did with one database on one tick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AutoCompactOutcome {
+    /// No shard has reached its compaction threshold.
+    NotNeeded,
+    /// Database saw heavy writes since the last tick, or a writer held the lock.
+    Busy,
+    Compacted,
+    Failed(String),
+}
+
+/// Periodically compacts databases whose shards exceed their L0 threshold.
+///
+/// Engine locks are only taken with `try_write`/`try_read` (see
+/// `Database::try_compact`), so the scheduler never queues behind client
+/// writers; a contended database is retried on the next tick.
+struct AutoCompactor {
+    manager: Arc<DatabaseManager>,
+    metrics: Option<Arc<Metrics>>,
+    /// Write counter of each database as seen on the previous tick.
+    last_writes: HashMap<String, u64>,
+}
+
+impl AutoCompactor {
+    fn new(manager: Arc<DatabaseManager>, metrics: Option<Arc<Metrics>>) -> Self {
+        Self { manager, metrics, last_writes: HashMap::new() }
+    }
+
+    /// Check every open database once.
+    fn run_once(&mut self) -> Vec<(String, AutoCompactOutcome)> {
+        self.manager
+            .databases()
+            .into_iter()
+            .map(|db| {
+                let outcome = self.compact_database(&db);
+                (db.name.clone(), outcome)
+            })
+            .collect()
+    }
+
+    fn compact_database(&mut self, db: &Database) -> AutoCompactOutcome {
+        let writes = db.write_count();
+        let previous = self.last_writes.insert(db.name.clone(), writes).unwrap_or(0);
+        if writes.saturating_sub(previous) > AUTO_COMPACT_BUSY_WRITES {
+            return AutoCompactOutcome::Busy;
+        }
+
+        let needed = match db.engine.try_read() {
+            Ok(engine) => engine.needs_compaction(),
+            Err(_) => return AutoCompactOutcome::Busy,
+        };
+        if !needed {
+            return AutoCompactOutcome::NotNeeded;
+        }
+
+        let start = Instant::now();
+        let Some(result) = db.try_compact() else {
+            return AutoCompactOutcome::Busy;
+        };
+        let elapsed_ms = start.elapsed().as_millis();
+
+        match result {
+            Ok(()) => {
+                db.metrics.record_auto_compaction();
+                if let Some(ref m) = self.metrics {
+                    m.record_auto_compaction();
+                }
+                tracing::info!(database = %db.name, duration_ms = elapsed_ms as u64, "Auto-compacted database");
+                AutoCompactOutcome::Compacted
+            }
+            Err(e) => {
+                tracing::error!(database = %db.name, error = %e, "Auto-compaction failed");
+                AutoCompactOutcome::Failed(e.to_string())
+            }
+        }
+    }
+}
+
+/// Run the compactor every `interval` on a dedicated thread.
+fn spawn_auto_compactor(manager: Arc<DatabaseManager>, metrics: Option<Arc<Metrics>>, interval: Duration) {
+    thread::spawn(move || {
+        let mut compactor = AutoCompactor::new(manager, metrics);
+        loop {
+            thread::sleep(interval);
+            compactor.run_once();
+        }
+    });
+}
+
+// ============================================================================
+// Logging (--log-format)
+// ============================================================================
+
+/// Log output format for server events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    /// Human-readable lines (default)
+    Text,
+    /// One JSON object per event, fields flattened next to `message`
+    Json,
+}
+
+impl LogFormat {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Subscriber writing INFO and above to `writer` in `format`. Library
+/// events (e.g. storage warnings) go through it too.
+fn log_subscriber<W>(format: LogFormat, writer: W) -> Box<dyn tracing::Subscriber + Send + Sync>
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_max_level(tracing::Level::INFO)
+        .with_ansi(false);
+    match format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().flatten_event(true).finish()),
+    }
+}
+
+// ============================================================================
+// Main
+// ============================================================================
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // Handle --version / -V flag
+    if args.iter().any(|a| a == "--version" || a == "-V") {
+        println!("rfdb-server {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    // Handle --help / -h flag
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("rfdb-server {}", env!("CARGO_PKG_VERSION"));
+        println!();
+        println!("High-performance disk-backed graph database server for Grafema");
+        println!();
+        println!("Usage: rfdb-server <db-path> [--socket <socket-path>] [--ws-port <port>] [--data-dir <dir>] [--metrics] [--auto-compact <secs>] [--write-timeout-ms <ms>] [--max-conn-bytes <bytes>] [--max-message-bytes <bytes>] [--idle-timeout-secs <secs>] [--default-shards <n>] [--log-format <text|json>] [--read-only] [--strict-ids]");
+        println!();
+        println!("Arguments:");
+        println!("  <db-path>      Path to default graph database directory");
+        println!("  --socket       Unix socket path (default: /tmp/rfdb.sock)");
+        println!("  --ws-port      WebSocket port (1-65535, e.g., 7474, localhost-only)");
+        println!("  --data-dir     Base directory for multi-database storage");
+        println!("  --auto-compact Compact databases in the background every <secs> seconds");
+        println!("  --write-timeout-ms Disconnect Unix socket clients whose writes stall this long (default 30000)");
+        println!("  --max-conn-bytes Disconnect clients after they send this many request bytes in total");
+        println!("  --max-message-bytes Largest single Unix socket request accepted (default 104857600)");
+        println!("  --idle-timeout-secs Heartbeat Unix socket clients (that opt in via Hello) idle this long; disconnect if they do not answer");
+        println!("  --default-shards Shard count for databases created without one (1-256)");
+        println!("  --log-format   Log output on stderr: text (default) or json lines");
+        println!();
+        println!("Flags:");
+        println!("  -V, --version  Print version information");
+        println!("  -h, --help     Print this help message");
+        println!("  --metrics      Enable performance metrics collection");
+        println!("  --read-only    Serve every database read-only (read replicas)");
+        println!("  --strict-ids   Reject nodes whose ID collides with a different semantic ID");
+        std::process::exit(0);
+    }
+
+    if args.len() < 2 {
+        eprintln!("Usage: rfdb-server <db-path> [--socket <socket-path>] [--ws-port <port>] [--data-dir <dir>] [--metrics] [--auto-compact <secs>] [--write-timeout-ms <ms>] [--max-conn-bytes <bytes>] [--max-message-bytes <bytes>] [--idle-timeout-secs <secs>] [--default-shards <n>] [--log-format <text|json>] [--read-only] [--strict-ids]");
+        eprintln!("");
+        eprintln!("Arguments:");
+        eprintln!("  <db-path>      Path to default graph database directory");
+        eprintln!("  --socket       Unix socket path (default: /tmp/rfdb.sock)");
+        eprintln!("  --ws-port      WebSocket port (1-65535, e.g., 7474, localhost-only)");
+        eprintln!("  --data-dir     Base directory for multi-database storage");
+        eprintln!("  --auto-compact Compact databases in the background every <secs> seconds");
+        eprintln!("  --write-timeout-ms Disconnect Unix socket clients whose writes stall this long (default 30000)");
+        eprintln!("  --max-conn-bytes Disconnect clients after they send this many request bytes in total");
+        eprintln!("  --max-message-bytes Largest single Unix socket request accepted (default 104857600)");
+        eprintln!("  --idle-timeout-secs Heartbeat Unix socket clients (that opt in via Hello) idle this long; disconnect if they do not answer");
+        eprintln!("  --default-shards Shard count for databases created without one (1-256)");
+        eprintln!("  --log-format   Log output on stderr: text (default) or json lines");
+        eprintln!("  --metrics      Enable performance metrics collection");
+        eprintln!("  --read-only    Serve every database read-only (read replicas)");
+        eprintln!("  --strict-ids   Reject nodes whose ID collides with a different semantic ID");
+        std::process::exit(1);
+    }
+
+    let db_path_str = &args[1];
+
+    // Validate db-path doesn't look like a flag
+    if db_path_str.starts_with("--") {
+        eprintln!("Error: db-path '{}' looks like a flag, not a path.", db_path_str);
+        eprintln!("");
+        eprintln!("Correct usage:");
+        eprintln!("  rfdb-server ./my-graph.rfdb --socket /tmp/rfdb.sock");
+        eprintln!("");
+        eprintln!("The first argument must be the database path, not a flag.");
+        std::process::exit(1);
+    }
+
+    let log_format = args.iter()
+        .position(|a| a == "--log-format")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| {
+            LogFormat::parse(s).unwrap_or_else(|| {
+                eprintln!("[rfdb-server] ERROR: Invalid --log-format value '{}' (must be text or json)", s);
+                std::process::exit(1);
+            })
+        })
+        .unwrap_or(LogFormat::Text);
+    tracing::subscriber::set_global_default(log_subscriber(log_format, std::io::stderr))
+        .expect("Failed to install log subscriber");
+
+    let db_path = PathBuf::from(db_path_str);
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "Starting rfdb-server");
+    let socket_path = args.iter()
+        .position(|a| a == "--socket")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+        .unwrap_or("/tmp/rfdb.sock");
+
+    let ws_port: Option<u16> = args.iter()
+        .position(|a| a == "--ws-port")
+        .and_then(|i| args.get(i + 1))
         .map(|s| {
             match s.parse::<u16>() {
                 Ok(0) => {
@@ -2688,45 +6375,174 @@ async fn main() {
     // Create metrics collector if --metrics flag is present
     let metrics_enabled = args.iter().any(|a| a == "--metrics");
     let metrics: Option<Arc<Metrics>> = if metrics_enabled {
-        eprintln!("[rfdb-server] Metrics collection enabled");
+        tracing::info!("Metrics collection enabled");
         Some(Arc::new(Metrics::new()))
     } else {
         None
     };
 
+    let auto_compact_interval: Option<Duration> = args.iter()
+        .position(|a| a == "--auto-compact")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| {
+            match s.parse::<u64>() {
+                Ok(0) => {
+                    eprintln!("[rfdb-server] ERROR: --auto-compact 0 is not allowed (interval must be at least 1 second)");
+                    std::process::exit(1);
+                }
+                Ok(secs) => Duration::from_secs(secs),
+                Err(_) => {
+                    eprintln!("[rfdb-server] ERROR: Invalid --auto-compact value '{}' (must be seconds)", s);
+                    std::process::exit(1);
+                }
+            }
+        });
+
+    let write_timeout: Duration = args.iter()
+        .position(|a| a == "--write-timeout-ms")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| {
+            match s.parse::<u64>() {
+                Ok(0) => {
+                    eprintln!("[rfdb-server] ERROR: --write-timeout-ms 0 is not allowed (timeout must be at least 1 ms)");
+                    std::process::exit(1);
+                }
+                Ok(ms) => Duration::from_millis(ms),
+                Err(_) => {
+                    eprintln!("[rfdb-server] ERROR: Invalid --write-timeout-ms value '{}' (must be milliseconds)", s);
+                    std::process::exit(1);
+                }
+            }
+        })
+        .unwrap_or(DEFAULT_WRITE_TIMEOUT);
+
+    let max_conn_bytes: Option<u64> = args.iter()
+        .position(|a| a == "--max-conn-bytes")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| {
+            match s.parse::<u64>() {
+                Ok(0) => {
+                    eprintln!("[rfdb-server] ERROR: --max-conn-bytes 0 is not allowed");
+                    std::process::exit(1);
+                }
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    eprintln!("[rfdb-server] ERROR: Invalid --max-conn-bytes value '{}' (must be bytes)", s);
+                    std::process::exit(1);
+                }
+            }
+        });
+
+    let max_message_bytes: usize = args.iter()
+        .position(|a| a == "--max-message-bytes")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| {
+            match s.parse::<usize>() {
+                Ok(0) => {
+                    eprintln!("[rfdb-server] ERROR: --max-message-bytes 0 is not allowed");
+                    std::process::exit(1);
+                }
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    eprintln!("[rfdb-server] ERROR: Invalid --max-message-bytes value '{}' (must be bytes)", s);
+                    std::process::exit(1);
+                }
+            }
+        })
+        .unwrap_or(DEFAULT_MAX_MESSAGE_BYTES);
+
+    let idle_timeout: Option<Duration> = args.iter()
+        .position(|a| a == "--idle-timeout-secs")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| {
+            match s.parse::<u64>() {
+                Ok(0) => {
+                    eprintln!("[rfdb-server] ERROR: --idle-timeout-secs 0 is not allowed (timeout must be at least 1 second)");
+                    std::process::exit(1);
+                }
+                Ok(secs) => Duration::from_secs(secs),
+                Err(_) => {
+                    eprintln!("[rfdb-server] ERROR: Invalid --idle-timeout-secs value '{}' (must be seconds)", s);
+                    std::process::exit(1);
+                }
+            }
+        });
+
     // Remove stale socket file
     let _ = std::fs::remove_file(socket_path);
 
+    let read_only = args.iter().any(|a| a == "--read-only");
+    if read_only {
+        tracing::info!("Read-only mode: all databases open ro, writes are refused");
+    }
+
+    let strict_ids = args.iter().any(|a| a == "--strict-ids");
+    if strict_ids {
+        tracing::info!("Strict IDs: nodes colliding with a different semantic ID are rejected");
+    }
+
+    let default_shards: Option<u16> = args.iter()
+        .position(|a| a == "--default-shards")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| {
+            match s.parse::<u32>().map(DatabaseManager::validate_shard_count) {
+                Ok(Ok(n)) => n,
+                Ok(Err(e)) => {
+                    eprintln!("[rfdb-server] ERROR: --default-shards: {}", e);
+                    std::process::exit(1);
+                }
+                Err(_) => {
+                    eprintln!("[rfdb-server] ERROR: Invalid --default-shards value '{}' (must be a shard count)", s);
+                    std::process::exit(1);
+                }
+            }
+        });
+    if let Some(n) = default_shards {
+        tracing::info!(shards = n, "New databases default to this shard count");
+    }
+
     // Create database manager with data directory
-    let manager = Arc::new(DatabaseManager::new(data_dir.clone()));
+    let manager = Arc::new(
+        DatabaseManager::new(data_dir.clone())
+            .with_read_only(read_only)
+            .with_strict_ids(strict_ids)
+            .with_default_shard_count(default_shards),
+    );
 
     // Create "default" database from legacy db_path for backwards compatibility
-    eprintln!("[rfdb-server] Opening default database: {:?}", db_path);
+    tracing::info!(path = %db_path.display(), "Opening default database");
     match manager.create_default_from_path(&db_path) {
         Ok(()) => {}
-        Err(rfdb::error::GraphError::DatabaseLocked(lock_path)) => {
-            eprintln!("[rfdb-server] ERROR: Database {:?} is already in use (lock held by another rfdb-server process).", db_path);
-            eprintln!("[rfdb-server] If you believe this is stale, remove: {}", lock_path);
+        Err(GraphError::DatabaseLocked(lock_path)) => {
+            tracing::error!(path = %db_path.display(), lock = %lock_path,
+                "Database is already in use (lock held by another rfdb-server process); if stale, remove the lock file");
             std::process::exit(1);
         }
         Err(e) => {
-            eprintln!("[rfdb-server] Failed to create default database: {}", e);
+            tracing::error!(error = %e, "Failed to create default database");
             std::process::exit(1);
         }
     }
 
-    eprintln!("[rfdb-server] Data directory for multi-database: {:?}", data_dir);
+    tracing::info!(path = %data_dir.display(), "Data directory for multi-database");
 
     // Get stats from default database
     if let Ok(db) = manager.get_database("default") {
-        eprintln!("[rfdb-server] Default database: {} nodes, {} edges",
-            db.node_count(),
-            db.edge_count());
+        tracing::info!(nodes = db.node_count(), edges = db.edge_count(), "Default database opened");
+    }
+
+    if let Some(interval) = auto_compact_interval {
+        if read_only {
+            tracing::warn!("Ignoring --auto-compact in read-only mode");
+        } else {
+            tracing::info!(interval_secs = interval.as_secs(), "Background compaction enabled");
+            spawn_auto_compactor(Arc::clone(&manager), metrics.clone(), interval);
+        }
     }
 
     // Bind Unix socket
     let listener = UnixListener::bind(socket_path).expect("Failed to bind socket");
-    eprintln!("[rfdb-server] Listening on {}", socket_path);
+    tracing::info!(socket = socket_path, "Listening");
 
     // Set up signal handler for graceful shutdown
     let manager_for_signal = Arc::clone(&manager);
@@ -2738,22 +6554,11 @@ async fn main() {
 
     thread::spawn(move || {
         for sig in signals.forever() {
-            eprintln!("[rfdb-server] Received signal {}, flushing...", sig);
-
-            // Flush all databases
-            for db_info in manager_for_signal.list_databases() {
-                if let Ok(db) = manager_for_signal.get_database(&db_info.name) {
-                    if let Ok(mut engine) = db.engine.write() {
-                        match engine.flush() {
-                            Ok(()) => eprintln!("[rfdb-server] Flushed database '{}'", db_info.name),
-                            Err(e) => eprintln!("[rfdb-server] Flush failed for '{}': {}", db_info.name, e),
-                        }
-                    }
-                }
-            }
+            tracing::info!(signal = sig, "Received signal, draining");
+            drain_and_flush(&manager_for_signal);
 
             let _ = std::fs::remove_file(&socket_path_for_signal);
-            eprintln!("[rfdb-server] Exiting");
+            tracing::info!("Exiting");
             std::process::exit(0);
         }
     });
@@ -2763,12 +6568,11 @@ async fn main() {
         let addr = format!("127.0.0.1:{}", port);
         match TcpListener::bind(&addr).await {
             Ok(listener) => {
-                eprintln!("[rfdb-server] WebSocket listening on {}", addr);
+                tracing::info!(addr = %addr, "WebSocket listening");
                 Some(listener)
             }
             Err(e) => {
-                eprintln!("[rfdb-server] ERROR: Failed to bind WebSocket port {}: {}", port, e);
-                eprintln!("[rfdb-server] Hint: Port may be in use. Try a different port.");
+                tracing::error!(port, error = %e, "Failed to bind WebSocket port (it may be in use, try a different port)");
                 std::process::exit(1);
             }
         }
@@ -2783,16 +6587,20 @@ async fn main() {
         for stream in listener.incoming() {
             match stream {
                 Ok(stream) => {
+                    if manager_unix.is_draining() {
+                        drop(stream);
+                        continue;
+                    }
                     let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst);
                     let manager_clone = Arc::clone(&manager_unix);
                     let metrics_clone = metrics_unix.clone();
                     thread::spawn(move || {
                         // legacy_mode: true until client sends Hello
-                        handle_client_unix(stream, manager_clone, client_id, true, metrics_clone);
+                        handle_client_unix(stream, manager_clone, client_id, true, metrics_clone, write_timeout, max_conn_bytes, max_message_bytes, idle_timeout);
                     });
                 }
                 Err(e) => {
-                    eprintln!("[rfdb-server] Unix socket accept error: {}", e);
+                    tracing::warn!(error = %e, "Unix socket accept error");
                 }
             }
         }
@@ -2806,7 +6614,11 @@ async fn main() {
             loop {
                 match ws_listener.accept().await {
                     Ok((tcp_stream, addr)) => {
-                        eprintln!("[rfdb-server] WebSocket connection from {}", addr);
+                        if manager_ws.is_draining() {
+                            drop(tcp_stream);
+                            continue;
+                        }
+                        tracing::info!(addr = %addr, "WebSocket connection");
                         let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst);
                         let manager_clone = Arc::clone(&manager_ws);
                         let metrics_clone = metrics_ws.clone();
@@ -2815,10 +6627,11 @@ async fn main() {
                             manager_clone,
                             client_id,
                             metrics_clone,
+                            max_conn_bytes,
                         ));
                     }
                     Err(e) => {
-                        eprintln!("[rfdb-server] WebSocket accept error: {}", e);
+                        tracing::warn!(error = %e, "WebSocket accept error");
                     }
                 }
             }
@@ -2869,6 +6682,7 @@ mod protocol_tests {
         let request = Request::Hello {
             protocol_version: Some(2),
             client_id: Some("test-client".to_string()),
+            heartbeat: false,
         };
 
         let response = handle_request(&manager, &mut session, request, &None);
@@ -2900,6 +6714,7 @@ mod protocol_tests {
         let request = Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: false,
+            shard_count: None,
         };
 
         let response = handle_request(&manager, &mut session, request, &None);
@@ -2925,6 +6740,7 @@ mod protocol_tests {
         let request = Request::CreateDatabase {
             name: "existing".to_string(),
             ephemeral: false,
+            shard_count: None,
         };
 
         let response = handle_request(&manager, &mut session, request, &None);
@@ -3125,6 +6941,82 @@ mod protocol_tests {
         }
     }
 
+    #[test]
+    fn test_rename_database_command() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+
+        manager.create_database("olddb", false).unwrap();
+        manager.create_database("taken", true).unwrap();
+
+        let rename = |session: &mut ClientSession, from: &str, to: &str| {
+            handle_request(&manager, session, Request::RenameDatabase {
+                from: from.to_string(),
+                to: to.to_string(),
+            }, &None)
+        };
+        let code = |response: Response| match response {
+            Response::ErrorWithCode { code, .. } => code,
+            other => panic!("Expected ErrorWithCode response, got {:?}", other),
+        };
+
+        assert_eq!(code(rename(&mut session, "missing", "x")), "DATABASE_NOT_FOUND");
+        assert_eq!(code(rename(&mut session, "olddb", "taken")), "DATABASE_EXISTS");
+
+        // Open in this very session counts as in use
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "olddb".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        assert_eq!(code(rename(&mut session, "olddb", "newdb")), "DATABASE_IN_USE");
+        handle_request(&manager, &mut session, Request::CloseDatabase, &None);
+
+        match rename(&mut session, "olddb", "newdb") {
+            Response::Ok { ok } => assert!(ok),
+            other => panic!("Expected Ok response, got {:?}", other),
+        }
+        assert!(!manager.database_exists("olddb"));
+        assert!(manager.database_exists("newdb"));
+    }
+
+    #[test]
+    fn test_copy_database_command() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+
+        manager.create_database("source", false).unwrap();
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "source".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        add_n_nodes(&manager, &mut session, 10, "FUNCTION");
+
+        // The source may stay open while it is copied
+        let copy = |session: &mut ClientSession, to: &str| {
+            handle_request(&manager, session, Request::CopyDatabase {
+                from: "source".to_string(),
+                to: to.to_string(),
+            }, &None)
+        };
+        match copy(&mut session, "copy") {
+            Response::Ok { ok } => assert!(ok),
+            other => panic!("Expected Ok response, got {:?}", other),
+        }
+        match copy(&mut session, "copy") {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "DATABASE_EXISTS"),
+            other => panic!("Expected DATABASE_EXISTS, got {:?}", other),
+        }
+
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "copy".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        match handle_request(&manager, &mut session, Request::NodeCount, &None) {
+            Response::Count { count, .. } => assert_eq!(count, 10),
+            other => panic!("Expected Count response, got {:?}", other),
+        }
+    }
+
     // ============================================================================
     // ListDatabases Command
     // ============================================================================
@@ -3156,24 +7048,88 @@ mod protocol_tests {
         }
     }
 
-    // ============================================================================
-    // CurrentDatabase Command
-    // ============================================================================
-
     #[test]
-    fn test_current_database_none() {
+    fn test_database_exists_does_not_open() {
         let (_dir, manager) = setup_test_manager();
         let mut session = ClientSession::new(1);
-        session.clear_database(); // Ensure no database is set
+        manager.create_database("db1", false).unwrap();
 
-        let response = handle_request(&manager, &mut session, Request::CurrentDatabase, &None);
+        let exists = |session: &mut ClientSession, name: &str| {
+            match handle_request(&manager, session, Request::DatabaseExists { name: name.to_string() }, &None) {
+                Response::Bool { value } => value,
+                other => panic!("Expected Bool response, got {:?}", other),
+            }
+        };
+        assert!(exists(&mut session, "db1"));
+        assert!(!exists(&mut session, "missing"));
+        assert_eq!(manager.get_database("db1").unwrap().connection_count(), 0);
+        assert!(session.current_db.is_none());
 
-        match response {
-            Response::CurrentDb { database, mode } => {
-                assert!(database.is_none());
-                assert!(mode.is_none());
+        match handle_request(&manager, &mut session, Request::GetDatabaseInfo { name: "db1".to_string() }, &None) {
+            Response::DatabaseInfo { info } => {
+                assert_eq!(info.name, "db1");
+                assert_eq!(info.connection_count, 0);
             }
-            _ => panic!("Expected CurrentDb response"),
+            other => panic!("Expected DatabaseInfo response, got {:?}", other),
+        }
+        assert_eq!(manager.get_database("db1").unwrap().connection_count(), 0);
+
+        let response = handle_request(&manager, &mut session, Request::GetDatabaseInfo { name: "missing".to_string() }, &None);
+        assert!(matches!(response, Response::ErrorWithCode { ref code, .. } if code == "DATABASE_NOT_FOUND"));
+    }
+
+    // ============================================================================
+    // CurrentDatabase / SessionInfo Commands
+    // ============================================================================
+
+    #[test]
+    fn test_session_info_reports_negotiated_protocol() {
+        let (_dir, manager) = setup_test_manager();
+        let session_info = |session: &mut ClientSession| {
+            match handle_request(&manager, session, Request::SessionInfo, &None) {
+                Response::SessionInfo { protocol_version, current_database, mode, client_id, pending_batch } => {
+                    (protocol_version, current_database, mode, client_id, pending_batch)
+                }
+                other => panic!("Expected SessionInfo response, got {:?}", other),
+            }
+        };
+
+        // No Hello: legacy default
+        let mut legacy = ClientSession::new(7);
+        assert_eq!(session_info(&mut legacy), (1, None, None, 7, None));
+
+        let mut session = ClientSession::new(8);
+        handle_request(&manager, &mut session, Request::Hello {
+            protocol_version: Some(3),
+            client_id: None,
+            heartbeat: false,
+        }, &None);
+        setup_ephemeral_db(&manager, &mut session, "session_info");
+        handle_request(&manager, &mut session, Request::BeginBatch, &None);
+
+        let (protocol_version, current_database, mode, client_id, pending_batch) = session_info(&mut session);
+        assert_eq!(protocol_version, 3);
+        assert_eq!(current_database.as_deref(), Some("session_info"));
+        assert_eq!(mode.as_deref(), Some("rw"));
+        assert_eq!(client_id, 8);
+        assert!(pending_batch.is_some());
+        assert_eq!(pending_batch.as_deref(), session.pending_batch_id());
+    }
+
+    #[test]
+    fn test_current_database_none() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        session.clear_database(); // Ensure no database is set
+
+        let response = handle_request(&manager, &mut session, Request::CurrentDatabase, &None);
+
+        match response {
+            Response::CurrentDb { database, mode } => {
+                assert!(database.is_none());
+                assert!(mode.is_none());
+            }
+            _ => panic!("Expected CurrentDb response"),
         }
     }
 
@@ -3284,13 +7240,174 @@ mod protocol_tests {
         let response = handle_request(&manager, &mut session, Request::NodeCount, &None);
 
         match response {
-            Response::Count { count } => {
+            Response::Count { count, .. } => {
                 assert_eq!(count, 0);
             }
             _ => panic!("Expected Count response"),
         }
     }
 
+    // ============================================================================
+    // Read-Only Server (--read-only)
+    // ============================================================================
+
+    #[test]
+    fn test_read_only_server_downgrades_open_and_refuses_writes() {
+        let dir = tempdir().unwrap();
+        let manager = Arc::new(DatabaseManager::new(dir.path().to_path_buf()).with_read_only(true));
+        let db_path = dir.path().join("default.rfdb");
+        std::fs::create_dir_all(&db_path).unwrap();
+        manager.create_default_from_path(&db_path).unwrap();
+        let mut session = ClientSession::new(1);
+
+        let response = handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "default".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        match response {
+            Response::DatabaseOpened { mode, .. } => assert_eq!(mode, "ro"),
+            _ => panic!("Expected DatabaseOpened response"),
+        }
+
+        let writes = vec![
+            Request::AddNodes { nodes: vec![txn_node("1")] },
+            Request::Flush { durability: None },
+            Request::CommitBatch {
+                changed_files: vec!["a.js".to_string()],
+                nodes: vec![],
+                edges: vec![],
+                tags: None,
+                file_context: None,
+                defer_index: false,
+                protected_types: vec![],
+                durability: None,
+                check_rule_sets: false,
+                dedup_edges: false,
+            },
+            Request::CreateDatabase { name: "other".to_string(), ephemeral: true, shard_count: None },
+        ];
+        for request in writes {
+            let op = get_operation_name(&request);
+            match handle_request(&manager, &mut session, request, &None) {
+                Response::ErrorWithCode { code, .. } => assert_eq!(code, "READ_ONLY_MODE", "{}", op),
+                _ => panic!("{} should be refused on a read-only server", op),
+            }
+        }
+
+        assert_eq!(count(&manager, &mut session, Request::NodeCount), 0);
+    }
+
+    #[test]
+    fn test_reachability_edge_costs_shrink_reachable_set() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "weighted");
+
+        // a -CALLS-> b -IMPORTS-> c -CALLS-> d
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: ["a", "b", "c", "d"].iter().map(|n| txn_node(n)).collect(),
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                txn_edge("a", "b"),
+                WireEdge { edge_type: Some("IMPORTS".to_string()), ..txn_edge("b", "c") },
+                txn_edge("c", "d"),
+            ],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let reach = |session: &mut ClientSession, edge_costs: Vec<(String, u32)>| {
+            match handle_request(&manager, session, Request::Reachability {
+                start_ids: vec!["a".to_string()],
+                max_depth: 3,
+                edge_types: vec![],
+                backward: false,
+                edge_costs,
+            }, &None) {
+                Response::Ids { mut ids } => {
+                    ids.sort();
+                    ids
+                }
+                other => panic!("Expected Ids response, got {:?}", other),
+            }
+        };
+        let named = |names: &[&str]| {
+            let mut ids: Vec<String> = names.iter().map(|n| id_to_string(string_to_id(n))).collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(reach(&mut session, vec![]), named(&["a", "b", "c", "d"]));
+        assert_eq!(reach(&mut session, vec![("CALLS".to_string(), 1)]), named(&["a", "b", "c", "d"]));
+        // IMPORTS at cost 2 puts d at 1 + 2 + 1 = 4, past the budget of 3
+        assert_eq!(reach(&mut session, vec![("IMPORTS".to_string(), 2)]), named(&["a", "b", "c"]));
+    }
+
+    #[test]
+    fn test_add_nodes_if_absent_skips_existing_ids() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "ifabsent");
+        handle_request(&manager, &mut session, Request::AddNodes { nodes: vec![txn_node("a")] }, &None);
+
+        let mut overwrite = txn_node("a");
+        overwrite.name = Some("changed".to_string());
+        let inserted = count(&manager, &mut session, Request::AddNodesIfAbsent {
+            nodes: vec![overwrite, txn_node("b"), txn_node("b")],
+        });
+        assert_eq!(inserted, 1);
+        assert_eq!(count(&manager, &mut session, Request::NodeCount), 2);
+
+        match handle_request(&manager, &mut session, Request::GetNode { id: "a".to_string(), include_deleted: false }, &None) {
+            Response::Node { node: Some(node) } => assert_eq!(node.name.as_deref(), Some("a")),
+            _ => panic!("Expected existing node"),
+        }
+
+        // Inside a batch, staged nodes count as existing too
+        handle_request(&manager, &mut session, Request::BeginBatch, &None);
+        handle_request(&manager, &mut session, Request::AddNodes { nodes: vec![txn_node("c")] }, &None);
+        let inserted = count(&manager, &mut session, Request::AddNodesIfAbsent {
+            nodes: vec![txn_node("a"), txn_node("c"), txn_node("d")],
+        });
+        assert_eq!(inserted, 1);
+    }
+
+    // ============================================================================
+    // Shutdown Draining
+    // ============================================================================
+
+    #[test]
+    fn test_writes_rejected_while_draining() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "default".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        add_n_nodes(&manager, &mut session, 3, "FUNCTION");
+
+        manager.start_draining();
+
+        let response = handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("late")],
+        }, &None);
+        match response {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "SERVER_DRAINING"),
+            _ => panic!("Expected SERVER_DRAINING error"),
+        }
+
+        let response = handle_request(&manager, &mut session, Request::CreateDatabase {
+            name: "newdb".to_string(),
+            ephemeral: true,
+            shard_count: None,
+        }, &None);
+        assert!(matches!(response, Response::ErrorWithCode { ref code, .. } if code == "SERVER_DRAINING"));
+
+        // Reads keep working until the process exits
+        assert_eq!(count(&manager, &mut session, Request::NodeCount), 3);
+    }
+
     // ============================================================================
     // GetStats Command
     // ============================================================================
@@ -3346,6 +7463,7 @@ mod protocol_tests {
                 exported: false,
                 metadata: None,
             semantic_id: None,
+            deleted: false,
             }],
         }, &metrics);
 
@@ -3368,6 +7486,150 @@ mod protocol_tests {
         }
     }
 
+    #[test]
+    fn test_get_shard_stats_requires_database() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+
+        let response = handle_request(&manager, &mut session, Request::GetShardStats, &None);
+
+        match response {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "NO_DATABASE_SELECTED"),
+            _ => panic!("Expected ErrorWithCode response"),
+        }
+    }
+
+    #[test]
+    fn test_create_database_with_explicit_shard_count() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+
+        let response = handle_request(&manager, &mut session, Request::CreateDatabase {
+            name: "wide".to_string(),
+            ephemeral: true,
+            shard_count: Some(7),
+        }, &None);
+        assert!(matches!(response, Response::DatabaseCreated { .. }));
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "wide".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+
+        match handle_request(&manager, &mut session, Request::GetShardStats, &None) {
+            Response::ShardStats { shards } => assert_eq!(shards.len(), 7),
+            _ => panic!("Expected ShardStats response"),
+        }
+
+        for bad in [0, 257] {
+            match handle_request(&manager, &mut session, Request::CreateDatabase {
+                name: format!("bad{}", bad),
+                ephemeral: true,
+                shard_count: Some(bad),
+            }, &None) {
+                Response::ErrorWithCode { code, .. } => assert_eq!(code, "INVALID_SHARD_COUNT"),
+                _ => panic!("Expected ErrorWithCode response"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_shard_stats_reports_skew() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "skewdb");
+
+        // Everything lives in one directory, so it all routes to one shard
+        let nodes: Vec<WireNode> = (0..40)
+            .map(|i| WireNode {
+                id: format!("hot{}", i),
+                semantic_id: None,
+                node_type: Some("FUNCTION".to_string()),
+                name: Some(format!("fn_{}", i)),
+                file: Some(format!("src/hot/file{}.js", i)),
+                exported: false,
+                metadata: None,
+                deleted: false,
+            })
+            .collect();
+        handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
+
+        let response = handle_request(&manager, &mut session, Request::GetShardStats, &None);
+
+        match response {
+            Response::ShardStats { shards } => {
+                assert!(shards.len() > 1, "ephemeral database should be sharded");
+                let total: usize = shards.iter().map(|s| s.node_count).sum();
+                assert_eq!(total, 40);
+                let max = shards.iter().map(|s| s.node_count).max().unwrap();
+                let min = shards.iter().map(|s| s.node_count).min().unwrap();
+                assert!(max > min, "skewed workload should show uneven shards");
+            }
+            _ => panic!("Expected ShardStats response"),
+        }
+    }
+
+    #[test]
+    fn test_graph_summary_totals_match_counts() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        handle_request(&manager, &mut session, Request::CreateDatabase {
+            name: "summary".to_string(),
+            ephemeral: false,
+            shard_count: None,
+        }, &None);
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "summary".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+
+        let nodes: Vec<WireNode> = (0..30)
+            .map(|i| WireNode {
+                id: format!("n{}", i),
+                semantic_id: None,
+                node_type: Some(if i % 3 == 0 { "CLASS" } else { "FUNCTION" }.to_string()),
+                name: Some(format!("n_{}", i)),
+                file: Some(format!("src/d{}/file.js", i % 5)),
+                exported: false,
+                metadata: None,
+                deleted: false,
+            })
+            .collect();
+        handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: (1..30).map(|i| txn_edge(&format!("n{}", i - 1), &format!("n{}", i))).collect(),
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+
+        let count = |session: &mut ClientSession, request| match handle_request(&manager, session, request, &None) {
+            Response::Count { count, .. } => count as usize,
+            other => panic!("Expected Count, got {:?}", other),
+        };
+        let node_count = count(&mut session, Request::NodeCount);
+        let edge_count = count(&mut session, Request::EdgeCount);
+        assert_eq!((node_count, edge_count), (30, 29));
+
+        match handle_request(&manager, &mut session, Request::GraphSummary, &None) {
+            Response::GraphSummary {
+                node_count: summary_nodes, edge_count: summary_edges,
+                nodes_by_type, edges_by_type, shards, disk_bytes, segments_by_level,
+            } => {
+                assert_eq!(summary_nodes, node_count);
+                assert_eq!(summary_edges, edge_count);
+                assert_eq!(nodes_by_type.values().sum::<usize>(), node_count);
+                assert_eq!(nodes_by_type["CLASS"], 10);
+                assert_eq!(edges_by_type.values().sum::<usize>(), edge_count);
+                assert_eq!(shards.iter().map(|s| s.node_count).sum::<usize>(), node_count);
+                assert_eq!(shards.iter().map(|s| s.edge_count).sum::<usize>(), edge_count);
+                assert!(disk_bytes > 0);
+                assert!(segments_by_level.l0 > 0);
+                assert_eq!((segments_by_level.l1, segments_by_level.l2), (0, 0));
+            }
+            other => panic!("Expected GraphSummary, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_get_stats_metrics_disabled() {
         let (_dir, manager) = setup_test_manager();
@@ -3385,6 +7647,126 @@ mod protocol_tests {
         }
     }
 
+    #[test]
+    fn test_slow_query_records_request_params() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        let metrics = Some(Arc::new(Metrics::new()));
+
+        let request = Request::FindByType { node_type: "http:route".to_string() };
+        let params = request_param_summary(&request);
+        record_request_metrics(
+            metrics.as_ref().unwrap(), None, &get_operation_name(&request), params.as_deref(), 150, None,
+        );
+
+        match handle_request(&manager, &mut session, Request::GetStats, &metrics) {
+            Response::Stats { top_slow_queries, .. } => {
+                assert_eq!(top_slow_queries.len(), 1);
+                assert_eq!(top_slow_queries[0].operation, "FindByType");
+                let params = top_slow_queries[0].params.as_deref().unwrap();
+                assert!(params.contains("http:route"), "{}", params);
+            }
+            other => panic!("Expected Stats response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_get_database_stats_isolated_per_database() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        let metrics = Some(Arc::new(Metrics::new()));
+        let m = metrics.as_ref().unwrap();
+
+        setup_v2_ephemeral_db(&manager, &mut session, "db_a");
+        record_request_metrics(m, session.current_db.as_deref(), "Bfs", None, 20, None);
+        record_request_metrics(m, session.current_db.as_deref(), "Bfs", None, 150, None);
+
+        // Second client, so the ephemeral db_a stays open
+        let mut other = ClientSession::new(2);
+        setup_v2_ephemeral_db(&manager, &mut other, "db_b");
+        record_request_metrics(m, other.current_db.as_deref(), "GetNode", None, 5, Some("query timeout"));
+
+        // Flushes count against the database they ran on
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("1")],
+        }, &metrics);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &metrics);
+        handle_request(&manager, &mut session, Request::CommitBatch {
+            changed_files: vec!["a.js".to_string()],
+            nodes: vec![txn_node("2")],
+            edges: vec![],
+            tags: None,
+            file_context: None,
+            defer_index: false,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        }, &metrics);
+
+        let stats = |session: &mut ClientSession, name: &str| {
+            match handle_request(&manager, session, Request::GetDatabaseStats {
+                name: name.to_string(),
+            }, &metrics) {
+                Response::Stats {
+                    database, query_count, slow_query_count, timed_out_count, flush_count, last_flush_nodes, ..
+                } => {
+                    assert_eq!(database.as_deref(), Some(name));
+                    (query_count, slow_query_count, timed_out_count, flush_count, last_flush_nodes)
+                }
+                other => panic!("Expected Stats response, got {:?}", other),
+            }
+        };
+        assert_eq!(stats(&mut session, "db_a"), (2, 1, 0, 2, 1));
+        assert_eq!(stats(&mut session, "db_b"), (1, 0, 1, 0, 0));
+
+        // GetStats keeps reporting the server-wide aggregate
+        match handle_request(&manager, &mut session, Request::GetStats, &metrics) {
+            Response::Stats { database, query_count, latency_buckets, operation_counts, flush_count, .. } => {
+                assert_eq!(database, None);
+                assert_eq!(flush_count, 2);
+                assert_eq!(query_count, 3);
+                assert_eq!(latency_buckets.last(), Some(&(u64::MAX, 3)));
+                assert_eq!(operation_counts.get("Bfs"), Some(&2));
+                assert_eq!(operation_counts.get("GetNode"), Some(&1));
+            }
+            other => panic!("Expected Stats response, got {:?}", other),
+        }
+
+        match handle_request(&manager, &mut session, Request::GetDatabaseStats {
+            name: "missing".to_string(),
+        }, &metrics) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "DATABASE_NOT_FOUND"),
+            other => panic!("Expected ErrorWithCode response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_metrics_prometheus_export() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        let metrics = Some(Arc::new(Metrics::new()));
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "default".to_string(),
+            mode: "rw".to_string(),
+        }, &metrics);
+        record_request_metrics(
+            metrics.as_ref().unwrap(), session.current_db.as_deref(), "Bfs", None, 10, None,
+        );
+
+        match handle_request(&manager, &mut session, Request::MetricsPrometheus, &metrics) {
+            Response::Export { format, content } => {
+                assert_eq!(format, "prometheus");
+                assert!(content.contains("# TYPE rfdb_query_total counter\n"), "{}", content);
+                assert!(content.contains("rfdb_query_total 1\n"), "{}", content);
+                assert!(content.contains("rfdb_query_total{database=\"default\"} 1\n"), "{}", content);
+                assert!(content.contains("rfdb_node_count{database=\"default\"} 0\n"), "{}", content);
+                assert_eq!(content.matches("# TYPE rfdb_node_count ").count(), 1);
+            }
+            other => panic!("Expected Export response, got {:?}", other),
+        }
+    }
+
     // ============================================================================
     // FindByAttr with Metadata Filters
     // ============================================================================
@@ -3398,6 +7780,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
 
         handle_request(&manager, &mut session, Request::OpenDatabase {
@@ -3416,6 +7799,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: Some(r#"{"object":"express","method":"get"}"#.to_string()),
                     semantic_id: None,
+                    deleted: false,
                 },
                 WireNode {
                     id: "2".to_string(),
@@ -3425,6 +7809,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: Some(r#"{"object":"express","method":"post"}"#.to_string()),
                     semantic_id: None,
+                    deleted: false,
                 },
                 WireNode {
                     id: "3".to_string(),
@@ -3434,6 +7819,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: Some(r#"{"object":"knex","method":"query"}"#.to_string()),
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
@@ -3449,8 +7835,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: false,
+                sorted: false,
                 extra,
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3472,8 +7861,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: false,
+                sorted: false,
                 extra,
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3491,8 +7883,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: false,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3503,26 +7898,1280 @@ mod protocol_tests {
         }
     }
 
-    // ============================================================================
-    // FindByAttr with substring_match
-    // ============================================================================
+    #[test]
+    fn test_count_by_attr() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "countdb");
+        add_n_nodes(&manager, &mut session, 12, "FUNCTION");
+
+        let query = |node_type: Option<&str>| WireAttrQuery {
+            node_type: node_type.map(|t| t.to_string()),
+            name: None,
+            file: None,
+            exported: None,
+            substring_match: false,
+            sorted: false,
+            extra: std::collections::HashMap::new(),
+        };
+
+        assert_eq!(count(&manager, &mut session, Request::CountByAttr { query: query(Some("FUNCTION")) }), 12);
+        assert_eq!(count(&manager, &mut session, Request::CountByAttr { query: query(Some("MISSING")) }), 0);
+        match handle_request(&manager, &mut session, Request::FindByAttr { query: query(Some("FUNCTION")), per_shard_counts: false, include_deleted: false }, &None) {
+            Response::Ids { ids } => assert_eq!(ids.len(), 12),
+            _ => panic!("Expected Ids response"),
+        }
+    }
 
     #[test]
-    fn test_find_by_attr_name_substring() {
+    fn test_find_by_attr_per_shard_counts_expose_skew() {
         let (_dir, manager) = setup_test_manager();
         let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "skewdb");
 
-        // Create and open ephemeral database
-        handle_request(&manager, &mut session, Request::CreateDatabase {
-            name: "testdb".to_string(),
-            ephemeral: true,
-        }, &None);
-        handle_request(&manager, &mut session, Request::OpenDatabase {
-            name: "testdb".to_string(),
-            mode: "rw".to_string(),
-        }, &None);
+        // Shards are chosen by file: one hot file, a handful of cold ones
+        let function = |id: usize, file: &str| WireNode {
+            id: format!("f{}", id),
+            semantic_id: None,
+            node_type: Some("FUNCTION".to_string()),
+            name: Some(format!("fn{}", id)),
+            file: Some(file.to_string()),
+            exported: false,
+            metadata: None,
+            deleted: false,
+        };
+        let mut nodes: Vec<WireNode> = (0..200).map(|i| function(i, "src/hot.js")).collect();
+        nodes.extend((200..205).map(|i| function(i, &format!("src/cold{}.js", i))));
+        handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
 
-        // Add a node with name "handleFooBar"
+        let query = WireAttrQuery {
+            node_type: Some("FUNCTION".to_string()),
+            name: None,
+            file: None,
+            exported: None,
+            substring_match: false,
+            sorted: false,
+            extra: std::collections::HashMap::new(),
+        };
+        match handle_request(&manager, &mut session, Request::FindByAttr { query, per_shard_counts: true, include_deleted: false }, &None) {
+            Response::IdsWithShardCounts { ids, shard_counts } => {
+                assert_eq!(ids.len(), 205);
+                assert!(shard_counts.len() > 1);
+                assert_eq!(shard_counts.iter().map(|c| c.count).sum::<usize>(), 205);
+                let mut counts: Vec<usize> = shard_counts.iter().map(|c| c.count).collect();
+                counts.sort_unstable();
+                let hottest = counts.pop().unwrap();
+                assert!(hottest >= 200, "{:?}", shard_counts);
+                assert!(counts.iter().all(|&c| c <= 5), "{:?}", shard_counts);
+            }
+            other => panic!("Expected IdsWithShardCounts response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_distinct_values_tallies_metadata_field() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "facetdb");
+
+        let route = |id: usize, node_type: &str, metadata: Option<&str>| WireNode {
+            id: format!("r{}", id),
+            semantic_id: None,
+            node_type: Some(node_type.to_string()),
+            name: Some(format!("route{}", id)),
+            file: Some("routes.js".to_string()),
+            exported: false,
+            metadata: metadata.map(|m| m.to_string()),
+            deleted: false,
+        };
+        let nodes = vec![
+            route(1, "http:route", Some(r#"{"method":"GET"}"#)),
+            route(2, "http:route", Some(r#"{"method":"GET"}"#)),
+            route(3, "http:route", Some(r#"{"method":"GET"}"#)),
+            route(4, "http:route", Some(r#"{"method":"POST"}"#)),
+            route(5, "http:route", Some(r#"{"method":"POST"}"#)),
+            route(6, "http:route", Some(r#"{"method":"DELETE"}"#)),
+            route(7, "http:route", None),
+            // Not an http:route, must not be counted
+            route(8, "CALL", Some(r#"{"method":"GET"}"#)),
+        ];
+        handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
+
+        let response = handle_request(&manager, &mut session, Request::DistinctValues {
+            query: WireAttrQuery {
+                node_type: Some("http:route".to_string()),
+                name: None,
+                file: None,
+                exported: None,
+                substring_match: false,
+                sorted: false,
+                extra: std::collections::HashMap::new(),
+            },
+            field: "method".to_string(),
+        }, &None);
+
+        match response {
+            Response::Facets { values } => {
+                assert_eq!(values, vec![
+                    ("GET".to_string(), 3),
+                    ("POST".to_string(), 2),
+                    ("DELETE".to_string(), 1),
+                ]);
+            }
+            _ => panic!("Expected Facets response"),
+        }
+    }
+
+    #[test]
+    fn test_edge_exists() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "edgedb");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("1"), txn_node("2")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                txn_edge("1", "2"),
+                WireEdge {
+                    src: "1".to_string(),
+                    dst: "2".to_string(),
+                    edge_type: Some("FLOWS_INTO".to_string()),
+                    // Enrichment edge: routed by file context, not by the source node
+                    metadata: Some(r#"{"__file_context":"__enrichment__/data-flow/src/z.js"}"#.to_string()),
+                },
+            ],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let exists = |session: &mut ClientSession, src: &str, dst: &str, edge_type: &str| {
+            match handle_request(&manager, session, Request::EdgeExists {
+                src: src.to_string(),
+                dst: dst.to_string(),
+                edge_type: edge_type.to_string(),
+            }, &None) {
+                Response::Bool { value } => value,
+                _ => panic!("Expected Bool response"),
+            }
+        };
+
+        assert!(exists(&mut session, "1", "2", "CALLS"));
+        assert!(exists(&mut session, "1", "2", "FLOWS_INTO"));
+        assert!(!exists(&mut session, "2", "1", "CALLS"));
+        assert!(!exists(&mut session, "1", "2", "IMPORTS"));
+    }
+
+    #[test]
+    fn test_edges_exist_answers_in_input_order() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "edgesexistdb");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("1"), txn_node("2"), txn_node("3")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("1", "2"), txn_edge("2", "3")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let key = |src: &str, dst: &str, edge_type: &str| WireEdgeKey {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            edge_type: edge_type.to_string(),
+        };
+        let edges = vec![
+            key("2", "1", "CALLS"),
+            key("1", "2", "CALLS"),
+            key("1", "2", "IMPORTS"),
+            key("2", "3", "CALLS"),
+            key("missing", "3", "CALLS"),
+        ];
+        match handle_request(&manager, &mut session, Request::EdgesExist { edges }, &None) {
+            Response::Bools { values } => assert_eq!(values, vec![false, true, false, true, false]),
+            other => panic!("Expected Bools response, got {:?}", other),
+        }
+        match handle_request(&manager, &mut session, Request::EdgesExist { edges: vec![] }, &None) {
+            Response::Bools { values } => assert!(values.is_empty()),
+            other => panic!("Expected Bools response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_weakly_connected_components_bridge_merges_islands() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "wccdb");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: ["1", "2", "3", "4", "5"].iter().map(|id| txn_node(id)).collect(),
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("1", "2"), txn_edge("3", "2"), txn_edge("4", "5")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let components = |session: &mut ClientSession| {
+            match handle_request(&manager, session, Request::WeaklyConnectedComponents {
+                edge_types: None,
+            }, &None) {
+                Response::Components { components } => components,
+                _ => panic!("Expected Components response"),
+            }
+        };
+
+        assert_eq!(components(&mut session), vec![vec!["1", "2", "3"], vec!["4", "5"]]);
+
+        // Bridge edge between the islands
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("5", "3")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+        assert_eq!(components(&mut session), vec![vec!["1", "2", "3", "4", "5"]]);
+    }
+
+    #[test]
+    fn test_khop_groups_by_depth() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "khopdb");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("1"), txn_node("2"), txn_node("3"), txn_node("4")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("1", "2"), txn_edge("1", "3"), txn_edge("2", "4"), txn_edge("3", "4")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let response = handle_request(&manager, &mut session, Request::KHop {
+            start_ids: vec!["1".to_string()],
+            max_depth: 5,
+            edge_types: vec!["CALLS".to_string()],
+        }, &None);
+
+        match response {
+            Response::Levels { mut levels } => {
+                for level in &mut levels {
+                    level.sort();
+                }
+                assert_eq!(levels, vec![vec!["1"], vec!["2", "3"], vec!["4"]]);
+            }
+            _ => panic!("Expected Levels response"),
+        }
+    }
+
+    #[test]
+    fn test_traversals_return_semantic_ids_on_v3() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "traversal_sids");
+
+        let node = |id: &str| WireNode { semantic_id: Some(format!("FUNCTION:{id}")), ..txn_node(id) };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![node("a"), node("b"), node("c")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("FUNCTION:a", "FUNCTION:b"), txn_edge("FUNCTION:b", "FUNCTION:c")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let traversals = || vec![
+            Request::Bfs { start_ids: vec!["FUNCTION:a".to_string()], max_depth: 5, edge_types: vec!["CALLS".to_string()] },
+            Request::Dfs { start_ids: vec!["FUNCTION:a".to_string()], max_depth: 5, edge_types: vec!["CALLS".to_string()] },
+            Request::Reachability {
+                start_ids: vec!["FUNCTION:a".to_string()],
+                max_depth: 5,
+                edge_types: vec!["CALLS".to_string()],
+                backward: false,
+                edge_costs: vec![],
+            },
+        ];
+        let run = |session: &mut ClientSession, request: Request| {
+            match handle_request(&manager, session, request, &None) {
+                Response::Ids { mut ids } => {
+                    ids.sort();
+                    ids
+                }
+                other => panic!("Expected Ids response, got {:?}", other),
+            }
+        };
+
+        let numeric = |id: &str| string_to_id(id).to_string();
+        let mut v2_expected = vec![numeric("FUNCTION:a"), numeric("FUNCTION:b"), numeric("FUNCTION:c")];
+        v2_expected.sort();
+        for request in traversals() {
+            assert_eq!(run(&mut session, request), v2_expected);
+        }
+
+        handle_request(&manager, &mut session, Request::Hello { protocol_version: Some(3), client_id: None, heartbeat: false }, &None);
+        let v3_expected = vec!["FUNCTION:a".to_string(), "FUNCTION:b".to_string(), "FUNCTION:c".to_string()];
+        for request in traversals() {
+            assert_eq!(run(&mut session, request), v3_expected);
+        }
+        let neighbors = run(&mut session, Request::Neighbors { id: "FUNCTION:a".to_string(), edge_types: vec![] });
+        assert_eq!(neighbors, vec!["FUNCTION:b".to_string()]);
+    }
+
+    #[test]
+    fn test_nearest_matching_finds_route_two_hops_away() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "nearestdb");
+
+        // 1 -> 2 -> route -> far_route, 1 -> 3: closer nodes are FUNCTIONs
+        let route = |id: &str| WireNode { node_type: Some("http:route".to_string()), ..txn_node(id) };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("1"), txn_node("2"), txn_node("3"), route("route"), route("far_route")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("1", "2"), txn_edge("1", "3"), txn_edge("2", "route"), txn_edge("route", "far_route")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let mut nearest = |max_depth: u32| {
+            match handle_request(&manager, &mut session, Request::NearestMatching {
+                start_ids: vec!["1".to_string()],
+                edge_types: vec!["CALLS".to_string()],
+                target_type: "http:route".to_string(),
+                max_depth,
+            }, &None) {
+                Response::Nearest { matches, distance } => (matches, distance),
+                other => panic!("Expected Nearest response, got {:?}", other),
+            }
+        };
+        assert_eq!(nearest(5), (vec![string_to_id("route").to_string()], Some(2)));
+        assert_eq!(nearest(1), (vec![], None));
+    }
+
+    #[test]
+    fn test_extract_subgraph_excludes_boundary_edges() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "subgraphdb");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("1"), txn_node("2"), txn_node("3"), txn_node("4")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                txn_edge("1", "2"),
+                txn_edge("2", "3"),
+                // Boundary edges: one endpoint outside {1, 2, 3}
+                txn_edge("3", "4"),
+                txn_edge("4", "1"),
+            ],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let response = handle_request(&manager, &mut session, Request::ExtractSubgraph {
+            node_ids: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+            edge_types: None,
+        }, &None);
+
+        match response {
+            Response::Subgraph { nodes, edges } => {
+                let mut node_ids: Vec<String> = nodes.into_iter().map(|n| n.id).collect();
+                node_ids.sort();
+                assert_eq!(node_ids, vec!["1", "2", "3"]);
+                let mut pairs: Vec<(String, String)> = edges.into_iter().map(|e| (e.src, e.dst)).collect();
+                pairs.sort();
+                assert_eq!(pairs, vec![
+                    ("1".to_string(), "2".to_string()),
+                    ("2".to_string(), "3".to_string()),
+                ]);
+            }
+            _ => panic!("Expected Subgraph response"),
+        }
+    }
+
+    #[test]
+    fn test_neighbors_with_edges_carries_edge_metadata() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "neighbor_edges");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("a"), txn_node("b"), txn_node("c")],
+        }, &None);
+        let edge = |src: &str, dst: &str, line: u32| WireEdge {
+            metadata: Some(format!(r#"{{"line":{}}}"#, line)),
+            ..txn_edge(src, dst)
+        };
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![edge("a", "b", 10), edge("a", "c", 20), edge("c", "a", 30)],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let neighbors = |session: &mut ClientSession, direction: &str| {
+            match handle_request(&manager, session, Request::NeighborsWithEdges {
+                id: "a".to_string(),
+                edge_types: Some(vec!["CALLS".to_string()]),
+                direction: direction.to_string(),
+            }, &None) {
+                Response::Neighbors { entries } => {
+                    let mut pairs: Vec<(String, String)> = entries.into_iter()
+                        .map(|e| (e.node.name.unwrap(), e.edge.metadata.unwrap()))
+                        .collect();
+                    pairs.sort();
+                    pairs
+                }
+                other => panic!("Expected Neighbors response, got {:?}", other),
+            }
+        };
+
+        assert_eq!(neighbors(&mut session, "outgoing"), vec![
+            ("b".to_string(), r#"{"line":10}"#.to_string()),
+            ("c".to_string(), r#"{"line":20}"#.to_string()),
+        ]);
+        assert_eq!(neighbors(&mut session, "incoming"), vec![
+            ("c".to_string(), r#"{"line":30}"#.to_string()),
+        ]);
+        assert_eq!(neighbors(&mut session, "both").len(), 3);
+    }
+
+    #[test]
+    fn test_batch_neighbors_matches_individual_neighbors() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "batch_neighbors");
+
+        let node = |id: &str| WireNode { semantic_id: Some(format!("FUNCTION:{id}")), ..txn_node(id) };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: ["a", "b", "c", "d"].into_iter().map(node).collect(),
+        }, &None);
+        let edge = |src: &str, dst: &str, edge_type: &str| WireEdge {
+            edge_type: Some(edge_type.to_string()),
+            ..txn_edge(&format!("FUNCTION:{src}"), &format!("FUNCTION:{dst}"))
+        };
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                edge("a", "b", "CALLS"),
+                // Same neighbor over a second edge type: listed once
+                edge("a", "b", "REFERENCES"),
+                edge("a", "c", "CALLS"),
+                edge("b", "c", "CALLS"),
+            ],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let ids: Vec<String> = ["a", "b", "c", "d"].iter().map(|id| format!("FUNCTION:{id}")).collect();
+        let response = handle_request(&manager, &mut session, Request::BatchNeighbors {
+            ids: ids.clone(),
+            edge_types: vec![],
+            direction: "outgoing".to_string(),
+        }, &None);
+        let Response::NeighborMap { entries } = response else {
+            panic!("Expected NeighborMap response, got {:?}", response);
+        };
+        assert_eq!(entries.len(), 4);
+        for ((input, neighbors), id) in entries.iter().zip(&ids) {
+            assert_eq!(input, id);
+            let single: HashSet<String> = match handle_request(&manager, &mut session, Request::Neighbors {
+                id: id.clone(),
+                edge_types: vec![],
+            }, &None) {
+                Response::Ids { ids } => ids.into_iter().collect(),
+                other => panic!("Expected Ids response, got {:?}", other),
+            };
+            assert_eq!(neighbors.iter().cloned().collect::<HashSet<_>>(), single, "{id}");
+            assert_eq!(neighbors.len(), single.len(), "{id} neighbors are deduplicated");
+        }
+
+        // Incoming on v3: neighbor IDs come back as semantic IDs
+        handle_request(&manager, &mut session, Request::Hello { protocol_version: Some(3), client_id: None, heartbeat: false }, &None);
+        let response = handle_request(&manager, &mut session, Request::BatchNeighbors {
+            ids: vec![ids[2].clone()],
+            edge_types: vec!["CALLS".to_string()],
+            direction: "incoming".to_string(),
+        }, &None);
+        let Response::NeighborMap { entries } = response else {
+            panic!("Expected NeighborMap response, got {:?}", response);
+        };
+        let mut neighbors = entries[0].1.clone();
+        neighbors.sort();
+        assert_eq!(neighbors, vec!["FUNCTION:a".to_string(), "FUNCTION:b".to_string()]);
+    }
+
+    #[test]
+    fn test_include_deleted_returns_soft_deleted_nodes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "soft_delete");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("kept"), txn_node("gone"), txn_node("pending")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::DeleteNode { id: "gone".to_string() }, &None);
+        // One tombstone flushed into the shards, one still pending in the engine
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        handle_request(&manager, &mut session, Request::DeleteNode { id: "pending".to_string() }, &None);
+
+        let get = |session: &mut ClientSession, id: &str, include_deleted: bool| {
+            match handle_request(&manager, session, Request::GetNode { id: id.to_string(), include_deleted }, &None) {
+                Response::Node { node } => node,
+                other => panic!("Expected Node response, got {:?}", other),
+            }
+        };
+        assert!(get(&mut session, "gone", false).is_none());
+
+        // Soft-deleted reads are a protocol v3 option
+        match handle_request(&manager, &mut session, Request::GetNode { id: "gone".to_string(), include_deleted: true }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "PROTOCOL_V3_REQUIRED"),
+            other => panic!("Expected PROTOCOL_V3_REQUIRED, got {:?}", other),
+        }
+        handle_request(&manager, &mut session, Request::Hello { protocol_version: Some(3), client_id: None, heartbeat: false }, &None);
+
+        for id in ["gone", "pending"] {
+            let node = get(&mut session, id, true).expect("deleted node comes back");
+            assert!(node.deleted, "{id} should be marked deleted");
+            assert_eq!(node.name.as_deref(), Some(id));
+        }
+        assert!(!get(&mut session, "kept", true).unwrap().deleted);
+
+        // An open batch is overlaid: a staged delete reads as deleted, a
+        // staged node as live
+        handle_request(&manager, &mut session, Request::BeginBatch, &None);
+        handle_request(&manager, &mut session, Request::DeleteNode { id: "kept".to_string() }, &None);
+        handle_request(&manager, &mut session, Request::AddNodes { nodes: vec![txn_node("staged")] }, &None);
+        assert!(get(&mut session, "kept", false).is_none());
+        assert!(get(&mut session, "kept", true).unwrap().deleted);
+        assert!(!get(&mut session, "staged", true).unwrap().deleted);
+        handle_request(&manager, &mut session, Request::AbortBatch, &None);
+
+        let query = || WireAttrQuery {
+            node_type: Some("FUNCTION".to_string()),
+            name: None,
+            file: None,
+            exported: None,
+            substring_match: false,
+            sorted: true,
+            extra: std::collections::HashMap::new(),
+        };
+        match handle_request(&manager, &mut session, Request::FindByAttr {
+            query: query(),
+            per_shard_counts: false,
+            include_deleted: true,
+        }, &None) {
+            Response::Ids { ids } => assert_eq!(ids.len(), 3),
+            other => panic!("Expected Ids response, got {:?}", other),
+        }
+        match handle_request(&manager, &mut session, Request::FindByAttr {
+            query: query(),
+            per_shard_counts: true,
+            include_deleted: true,
+        }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "INVALID_ARGUMENT"),
+            other => panic!("Expected INVALID_ARGUMENT, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_autocomplete_names_prefix_case_insensitive() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "autocomplete");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("handleGet"), txn_node("helper")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        // One handler flushed into a segment, one still in the write buffer
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("handlePost")],
+        }, &None);
+
+        let autocomplete = |session: &mut ClientSession, prefix: &str, limit: u32| {
+            match handle_request(&manager, session, Request::AutocompleteNames {
+                prefix: prefix.to_string(),
+                node_type: Some("FUNCTION".to_string()),
+                limit,
+            }, &None) {
+                Response::Ids { ids } => ids,
+                other => panic!("Expected Ids response, got {:?}", other),
+            }
+        };
+        let id = |name: &str| id_to_string(string_to_id(name));
+
+        assert_eq!(autocomplete(&mut session, "handle", 10), vec![id("handleGet"), id("handlePost")]);
+        assert_eq!(autocomplete(&mut session, "HANDLE", 1), vec![id("handleGet")]);
+        assert_eq!(autocomplete(&mut session, "he", 10), vec![id("helper")]);
+        assert!(autocomplete(&mut session, "x", 10).is_empty());
+    }
+
+    #[test]
+    fn test_add_edges_rejects_dangling_endpoints() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "dangling_edges");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("a")],
+        }, &None);
+
+        match handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("a", "ghost")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None) {
+            Response::ErrorWithCode { error, code } => {
+                assert_eq!(code, "DANGLING_EDGE");
+                assert!(error.contains(&id_to_string(string_to_id("ghost"))), "{}", error);
+            }
+            other => panic!("Expected DANGLING_EDGE, got {:?}", other),
+        }
+        assert_eq!(count(&manager, &mut session, Request::EdgeCount), 0);
+
+        // Nodes staged in the open batch count as existing
+        handle_request(&manager, &mut session, Request::BeginBatch, &None);
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("b")],
+        }, &None);
+        assert!(matches!(handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("a", "b")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None), Response::Ok { .. }));
+    }
+
+    #[test]
+    fn test_metadata_schema_rejects_missing_and_disallowed_keys() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "metadata_schema");
+
+        let response = handle_request(&manager, &mut session, Request::SetMetadataSchema {
+            node_type: "FUNCTION".to_string(),
+            required_keys: vec!["async".to_string()],
+            allowed_keys: Some(vec!["line".to_string()]),
+        }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "{:?}", response);
+
+        let with_metadata = |id: &str, metadata: &str| WireNode {
+            metadata: Some(metadata.to_string()),
+            ..txn_node(id)
+        };
+
+        // Missing required key (typo)
+        match handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![with_metadata("typo", r#"{"asyncc":true}"#)],
+        }, &None) {
+            Response::ErrorWithCode { error, code } => {
+                assert_eq!(code, "SCHEMA_VIOLATION");
+                assert!(error.contains("async"), "{}", error);
+            }
+            other => panic!("Expected SCHEMA_VIOLATION, got {:?}", other),
+        }
+
+        // Key outside required + allowed, via CommitBatch
+        match handle_request(&manager, &mut session, Request::CommitBatch {
+            changed_files: vec![],
+            nodes: vec![with_metadata("extra", r#"{"async":false,"col":7}"#)],
+            edges: vec![],
+            tags: None,
+            file_context: None,
+            defer_index: false,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        }, &None) {
+            Response::ErrorWithCode { error, code } => {
+                assert_eq!(code, "SCHEMA_VIOLATION");
+                assert!(error.contains("col"), "{}", error);
+            }
+            other => panic!("Expected SCHEMA_VIOLATION, got {:?}", other),
+        }
+        assert_eq!(count(&manager, &mut session, Request::NodeCount), 0);
+
+        // Conforming nodes and types without a schema go through
+        let mut class = with_metadata("cls", r#"{"anything":1}"#);
+        class.node_type = Some("CLASS".to_string());
+        let response = handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![with_metadata("ok", r#"{"async":true,"line":3}"#), class],
+        }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "{:?}", response);
+        assert_eq!(count(&manager, &mut session, Request::NodeCount), 2);
+    }
+
+    fn node_total(manager: &DatabaseManager, name: &str) -> usize {
+        manager.get_database(name).unwrap().node_count()
+    }
+
+    fn stage_cross_db(manager: &Arc<DatabaseManager>, session: &mut ClientSession, database: &str, nodes: Vec<WireNode>) {
+        let response = handle_request(manager, session, Request::StageCrossDbCommit {
+            database: database.to_string(),
+            changed_files: vec![],
+            nodes,
+            edges: vec![],
+            file_context: None,
+        }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "{:?}", response);
+    }
+
+    fn begin_cross_db(manager: &Arc<DatabaseManager>, session: &mut ClientSession) {
+        let response = handle_request(manager, session, Request::BeginCrossDbBatch {
+            databases: vec!["beta".to_string(), "alpha".to_string()],
+        }, &None);
+        assert!(matches!(response, Response::BatchStarted { .. }), "{:?}", response);
+    }
+
+    #[test]
+    fn test_cross_db_batch_all_or_nothing() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        for name in ["alpha", "beta"] {
+            handle_request(&manager, &mut session, Request::CreateDatabase {
+                name: name.to_string(),
+                ephemeral: false,
+                shard_count: None,
+            }, &None);
+        }
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "beta".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        handle_request(&manager, &mut session, Request::SetMetadataSchema {
+            node_type: "FUNCTION".to_string(),
+            required_keys: vec!["async".to_string()],
+            allowed_keys: None,
+        }, &None);
+        let valid = |id: &str| WireNode { metadata: Some(r#"{"async":true}"#.to_string()), ..txn_node(id) };
+
+        // Second database's payload fails validation: nothing is written
+        begin_cross_db(&manager, &mut session);
+        stage_cross_db(&manager, &mut session, "alpha", vec![valid("a1")]);
+        stage_cross_db(&manager, &mut session, "beta", vec![txn_node("b1")]);
+        match handle_request(&manager, &mut session, Request::CommitCrossDbBatch, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "SCHEMA_VIOLATION"),
+            other => panic!("Expected SCHEMA_VIOLATION, got {:?}", other),
+        }
+        assert_eq!((node_total(&manager, "alpha"), node_total(&manager, "beta")), (0, 0));
+        assert!(session.pending_cross_db.is_none());
+
+        begin_cross_db(&manager, &mut session);
+        stage_cross_db(&manager, &mut session, "alpha", vec![valid("a1")]);
+        stage_cross_db(&manager, &mut session, "beta", vec![valid("b1"), valid("b2")]);
+        match handle_request(&manager, &mut session, Request::CommitCrossDbBatch, &None) {
+            Response::CrossDbBatchCommitted { ok, deltas } => {
+                assert!(ok);
+                assert_eq!(deltas.keys().collect::<Vec<_>>(), vec!["alpha", "beta"]);
+                assert_eq!(deltas["beta"].nodes_added, 2);
+            }
+            other => panic!("Expected CrossDbBatchCommitted, got {:?}", other),
+        }
+        assert_eq!((node_total(&manager, "alpha"), node_total(&manager, "beta")), (1, 2));
+
+        // A commit slipping in after BeginCrossDbBatch is a version conflict
+        begin_cross_db(&manager, &mut session);
+        stage_cross_db(&manager, &mut session, "alpha", vec![valid("a2")]);
+        stage_cross_db(&manager, &mut session, "beta", vec![valid("b3")]);
+        handle_request(&manager, &mut session, Request::CommitBatch {
+            changed_files: vec![],
+            nodes: vec![valid("b4")],
+            edges: vec![],
+            tags: None,
+            file_context: None,
+            defer_index: false,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        }, &None);
+        match handle_request(&manager, &mut session, Request::CommitCrossDbBatch, &None) {
+            Response::VersionConflict { code, database, expected, actual, .. } => {
+                assert_eq!(code, "VERSION_CONFLICT");
+                assert_eq!(database.as_deref(), Some("beta"));
+                assert!(actual > expected);
+            }
+            other => panic!("Expected VERSION_CONFLICT, got {:?}", other),
+        }
+        assert_eq!((node_total(&manager, "alpha"), node_total(&manager, "beta")), (1, 3));
+    }
+
+    #[test]
+    fn test_cross_db_batch_rolls_back_on_midway_failure() {
+        let (dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        for name in ["alpha", "beta"] {
+            handle_request(&manager, &mut session, Request::CreateDatabase {
+                name: name.to_string(),
+                ephemeral: false,
+                shard_count: None,
+            }, &None);
+        }
+
+        begin_cross_db(&manager, &mut session);
+        stage_cross_db(&manager, &mut session, "alpha", vec![txn_node("a1")]);
+        stage_cross_db(&manager, &mut session, "beta", vec![txn_node("b1")]);
+        handle_request(&manager, &mut session, Request::CommitCrossDbBatch, &None);
+        assert_eq!((node_total(&manager, "alpha"), node_total(&manager, "beta")), (1, 1));
+
+        // beta passes validation but can no longer flush
+        begin_cross_db(&manager, &mut session);
+        stage_cross_db(&manager, &mut session, "alpha", vec![txn_node("a2"), txn_node("a3")]);
+        stage_cross_db(&manager, &mut session, "beta", vec![txn_node("b2")]);
+        std::fs::remove_dir_all(dir.path().join("beta.rfdb")).unwrap();
+
+        match handle_request(&manager, &mut session, Request::CommitCrossDbBatch, &None) {
+            Response::ErrorWithCode { code, error } => {
+                assert_eq!(code, "CROSS_DB_COMMIT_FAILED");
+                assert!(error.contains("beta") && error.contains("rolled back 1"), "{}", error);
+            }
+            other => panic!("Expected CROSS_DB_COMMIT_FAILED, got {:?}", other),
+        }
+        assert_eq!(node_total(&manager, "alpha"), 1);
+        let alpha = manager.get_database("alpha").unwrap();
+        assert!(alpha.engine.read().unwrap().get_node(string_to_id("a1")).is_some());
+        assert!(alpha.engine.read().unwrap().get_node(string_to_id("a2")).is_none());
+    }
+
+    #[test]
+    fn test_cross_db_batch_rollback_keeps_unflushed_writes() {
+        let (dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        for name in ["alpha", "beta"] {
+            handle_request(&manager, &mut session, Request::CreateDatabase {
+                name: name.to_string(),
+                ephemeral: false,
+                shard_count: None,
+            }, &None);
+        }
+
+        // Another client's write to alpha, still in the write buffer
+        let mut other = ClientSession::new(2);
+        handle_request(&manager, &mut other, Request::OpenDatabase {
+            name: "alpha".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        handle_request(&manager, &mut other, Request::AddNodes { nodes: vec![txn_node("x")] }, &None);
+
+        begin_cross_db(&manager, &mut session);
+        stage_cross_db(&manager, &mut session, "alpha", vec![txn_node("a1")]);
+        stage_cross_db(&manager, &mut session, "beta", vec![txn_node("b1")]);
+        std::fs::remove_dir_all(dir.path().join("beta.rfdb")).unwrap();
+
+        match handle_request(&manager, &mut session, Request::CommitCrossDbBatch, &None) {
+            Response::ErrorWithCode { code, error } => {
+                assert_eq!(code, "CROSS_DB_COMMIT_FAILED");
+                assert!(error.contains("rolled back 1"), "{}", error);
+            }
+            other => panic!("Expected CROSS_DB_COMMIT_FAILED, got {:?}", other),
+        }
+        let alpha = manager.get_database("alpha").unwrap();
+        assert!(alpha.engine.read().unwrap().get_node(string_to_id("x")).is_some(), "other client's write kept");
+        assert!(alpha.engine.read().unwrap().get_node(string_to_id("a1")).is_none());
+    }
+
+    #[test]
+    fn test_cross_db_batch_refuses_ephemeral_databases() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        for (name, ephemeral) in [("alpha", false), ("beta", true)] {
+            handle_request(&manager, &mut session, Request::CreateDatabase {
+                name: name.to_string(),
+                ephemeral,
+                shard_count: None,
+            }, &None);
+        }
+
+        begin_cross_db(&manager, &mut session);
+        stage_cross_db(&manager, &mut session, "alpha", vec![txn_node("a1")]);
+        stage_cross_db(&manager, &mut session, "beta", vec![txn_node("b1")]);
+        match handle_request(&manager, &mut session, Request::CommitCrossDbBatch, &None) {
+            Response::ErrorWithCode { code, error } => {
+                assert_eq!(code, "EPHEMERAL_UNSUPPORTED");
+                assert!(error.contains("beta"), "{}", error);
+            }
+            other => panic!("Expected EPHEMERAL_UNSUPPORTED, got {:?}", other),
+        }
+        assert_eq!((node_total(&manager, "alpha"), node_total(&manager, "beta")), (0, 0));
+    }
+
+    #[test]
+    fn test_malformed_metadata_counted_and_never_matches() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        let metrics = Some(Arc::new(Metrics::new()));
+        setup_ephemeral_db(&manager, &mut session, "malformed_metadata");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![
+                WireNode { metadata: Some("{not json".to_string()), ..txn_node("bad") },
+                WireNode { metadata: Some(r#"{"async":true}"#.to_string()), ..txn_node("good") },
+                txn_node("plain"),
+            ],
+        }, &metrics);
+        assert_eq!(metrics.as_ref().unwrap().snapshot().malformed_metadata_count, 1);
+
+        let find = |session: &mut ClientSession, extra: Vec<(&str, serde_json::Value)>| {
+            let query = WireAttrQuery {
+                node_type: Some("FUNCTION".to_string()),
+                name: None,
+                file: None,
+                exported: None,
+                substring_match: false,
+                sorted: false,
+                extra: extra.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            };
+            match handle_request(&manager, session, Request::FindByAttr { query, per_shard_counts: false, include_deleted: false }, &None) {
+                Response::Ids { ids } => ids.len(),
+                other => panic!("Expected Ids, got {:?}", other),
+            }
+        };
+        assert_eq!(find(&mut session, vec![]), 3);
+        assert_eq!(find(&mut session, vec![("async", serde_json::json!(true))]), 1);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        assert_eq!(find(&mut session, vec![]), 3);
+        assert_eq!(find(&mut session, vec![("async", serde_json::json!(true))]), 1);
+    }
+
+    /// Every string hashes to the same ID, forcing collisions.
+    fn colliding_hash(_: &str) -> u128 {
+        42
+    }
+
+    fn colliding_node(semantic_id: &str) -> NodeRecord {
+        NodeRecord {
+            id: colliding_hash(semantic_id),
+            semantic_id: Some(semantic_id.to_string()),
+            ..wire_node_to_record(txn_node("x"))
+        }
+    }
+
+    #[test]
+    fn test_id_collisions_detected_with_stubbed_hasher() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.add_nodes(vec![colliding_node("FUNCTION:a@a.js")]);
+
+        // Same semantic ID: a plain upsert
+        assert!(id_collisions(&engine, &[colliding_node("FUNCTION:a@a.js")], colliding_hash).is_empty());
+
+        let collisions = id_collisions(&engine, &[colliding_node("FUNCTION:b@b.js")], colliding_hash);
+        assert_eq!(collisions, vec![(42, "FUNCTION:a@a.js".to_string(), "FUNCTION:b@b.js".to_string())]);
+
+        // Collisions inside one batch are caught before anything is stored
+        let fresh = GraphEngineV2::create_ephemeral();
+        let batch = [colliding_node("FUNCTION:c@c.js"), colliding_node("FUNCTION:d@d.js")];
+        assert_eq!(id_collisions(&fresh, &batch, colliding_hash).len(), 1);
+
+        // Under the real hasher these semantic IDs don't map to 42
+        assert!(id_collisions(&engine, &[colliding_node("FUNCTION:b@b.js")], string_to_id).is_empty());
+    }
+
+    #[test]
+    fn test_id_collision_counted_and_rejected_only_in_strict_mode() {
+        let metrics = Some(Arc::new(Metrics::new()));
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.add_nodes(vec![colliding_node("FUNCTION:a@a.js")]);
+        let incoming = [colliding_node("FUNCTION:b@b.js")];
+
+        assert!(id_collision_rejection(&engine, &incoming, colliding_hash, false, &metrics).is_none());
+        assert_eq!(metrics.as_ref().unwrap().snapshot().id_collision_count, 1);
+
+        match id_collision_rejection(&engine, &incoming, colliding_hash, true, &metrics) {
+            Some(Response::ErrorWithCode { code, .. }) => assert_eq!(code, "ID_COLLISION"),
+            other => panic!("Expected ID_COLLISION, got {:?}", other),
+        }
+        assert_eq!(metrics.as_ref().unwrap().snapshot().id_collision_count, 2);
+    }
+
+    #[test]
+    fn test_commit_batch_rejects_id_collision_in_strict_mode() {
+        let metrics = Some(Arc::new(Metrics::new()));
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.add_nodes(vec![colliding_node("FUNCTION:a@a.js")]);
+        let strict = IdCheck { strict: true, metrics: &metrics, hash: colliding_hash };
+
+        let response = handle_commit_batch(
+            &mut engine, vec!["b.js".to_string()], vec![colliding_node("FUNCTION:b@b.js")], vec![], None,
+            CommitFlush::Full(None), &[], Vec::new(), strict,
+        );
+        match response {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "ID_COLLISION"),
+            other => panic!("Expected ID_COLLISION, got {:?}", other),
+        }
+        assert_eq!(metrics.as_ref().unwrap().snapshot().id_collision_count, 1);
+        let stored = engine.get_node(42).unwrap();
+        assert_eq!(stored.semantic_id.as_deref(), Some("FUNCTION:a@a.js"));
+
+        // Neither rejected nor counted: no lookup, the commit overwrites
+        let lenient = IdCheck { strict: false, metrics: &None, hash: colliding_hash };
+        let response = handle_commit_batch(
+            &mut engine, vec!["b.js".to_string()], vec![colliding_node("FUNCTION:b@b.js")], vec![], None,
+            CommitFlush::Full(None), &[], Vec::new(), lenient,
+        );
+        assert!(matches!(response, Response::BatchCommitted { .. }), "{:?}", response);
+        assert_eq!(engine.get_node(42).unwrap().semantic_id.as_deref(), Some("FUNCTION:b@b.js"));
+    }
+
+    #[test]
+    fn test_add_nodes_semantic_id_upsert_is_not_a_collision() {
+        let dir = tempdir().unwrap();
+        let manager = Arc::new(DatabaseManager::new(dir.path().to_path_buf()).with_strict_ids(true));
+        let mut session = ClientSession::new(1);
+        let metrics = Some(Arc::new(Metrics::new()));
+        setup_ephemeral_db(&manager, &mut session, "strict_ids");
+
+        for name in ["a", "renamed"] {
+            let response = handle_request(&manager, &mut session, Request::AddNodes {
+                nodes: vec![WireNode {
+                    semantic_id: Some("FUNCTION:a@a.js".to_string()),
+                    name: Some(name.to_string()),
+                    ..txn_node("a")
+                }],
+            }, &metrics);
+            assert!(matches!(response, Response::Ok { .. }), "{:?}", response);
+        }
+        assert_eq!(metrics.as_ref().unwrap().snapshot().id_collision_count, 0);
+    }
+
+    #[test]
+    fn test_add_edges_skip_validation_still_drops_unknown_source() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "skip_validation_edges");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("a")],
+        }, &None);
+        let response = handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("ghost", "a")],
+            skip_validation: true,
+            dedup_edges: false,
+        }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "{:?}", response);
+        assert_eq!(count(&manager, &mut session, Request::EdgeCount), 0);
+    }
+
+    #[test]
+    fn test_dedup_edges_merges_metadata_of_duplicates() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "dedup_edges");
+        let with_metadata = |src: &str, dst: &str, metadata: &str| WireEdge {
+            metadata: Some(metadata.to_string()),
+            ..txn_edge(src, dst)
+        };
+        let outgoing = |session: &mut ClientSession, id: &str| {
+            match handle_request(&manager, session, Request::GetOutgoingEdges { id: id.to_string(), edge_types: None }, &None) {
+                Response::Edges { edges } => edges,
+                other => panic!("Expected Edges, got {:?}", other),
+            }
+        };
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("a"), txn_node("b"), txn_node("c")],
+        }, &None);
+        let response = handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                with_metadata("a", "b", r#"{"line":1,"argc":2}"#),
+                txn_edge("a", "c"),
+                with_metadata("a", "b", r#"{"line":5,"async":true}"#),
+            ],
+            skip_validation: false,
+            dedup_edges: true,
+        }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "{:?}", response);
+
+        let edges = outgoing(&mut session, "a");
+        assert_eq!(edges.len(), 2);
+        let merged = edges.iter().find(|e| e.dst == id_to_string(string_to_id("b"))).unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(merged.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata, serde_json::json!({ "line": 5, "argc": 2, "async": true }));
+
+        // CommitBatch merges within its own batch the same way
+        let response = handle_request(&manager, &mut session, Request::CommitBatch {
+            changed_files: vec![],
+            nodes: vec![],
+            edges: vec![with_metadata("b", "c", r#"{"x":1}"#), with_metadata("b", "c", r#"{"y":2}"#)],
+            tags: None,
+            file_context: None,
+            defer_index: false,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: true,
+        }, &None);
+        assert!(matches!(response, Response::BatchCommitted { .. }), "{:?}", response);
+        let edges = outgoing(&mut session, "b");
+        assert_eq!(edges.len(), 1);
+        let metadata: serde_json::Value = serde_json::from_str(edges[0].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata, serde_json::json!({ "x": 1, "y": 2 }));
+    }
+
+    #[test]
+    fn test_aggregate_edges_collapses_parallel_calls() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "aggregate_edges");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("a"), txn_node("b")],
+        }, &None);
+        let call_site = |line: u32| WireEdge {
+            metadata: Some(format!(r#"{{"line":{}}}"#, line)),
+            ..txn_edge("a", "b")
+        };
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![call_site(1), call_site(2), call_site(3)],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![WireEdge { edge_type: Some("READS".to_string()), ..txn_edge("a", "b") }],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        match handle_request(&manager, &mut session, Request::AggregateEdges {
+            edge_types: Some(vec!["CALLS".to_string()]),
+        }, &None) {
+            Response::Edges { edges } => {
+                assert_eq!(edges.len(), 1);
+                assert_eq!(edges[0].edge_type.as_deref(), Some("CALLS"));
+                let metadata: serde_json::Value =
+                    serde_json::from_str(edges[0].metadata.as_deref().unwrap()).unwrap();
+                // The store keeps one edge per (src, dst, type), so call
+                // sites already collapsed on write; see analytics tests for
+                // the multi-edge grouping itself
+                assert_eq!(metadata["count"], 1);
+            }
+            other => panic!("Expected Edges response, got {:?}", other),
+        }
+
+        // The projection leaves stored edges untouched
+        match handle_request(&manager, &mut session, Request::GetOutgoingEdges {
+            id: "a".to_string(),
+            edge_types: Some(vec!["CALLS".to_string()]),
+        }, &None) {
+            Response::Edges { edges } => assert!(edges[0].metadata.as_deref().unwrap().contains("line")),
+            other => panic!("Expected Edges response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_are_connected_agrees_with_reachability() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "are_connected");
+
+        // a -> b -> c -> d, e -> c, plus a READS edge d -> a
+        let names = ["a", "b", "c", "d", "e"];
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: names.iter().map(|n| txn_node(n)).collect(),
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                txn_edge("a", "b"), txn_edge("b", "c"), txn_edge("c", "d"), txn_edge("e", "c"),
+                WireEdge { edge_type: Some("READS".to_string()), ..txn_edge("d", "a") },
+            ],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        for edge_types in [vec!["CALLS".to_string()], vec![]] {
+            for src in names {
+                for dst in names {
+                    for max_depth in 0..4 {
+                        let reachable = match handle_request(&manager, &mut session, Request::Reachability {
+                            start_ids: vec![src.to_string()],
+                            max_depth,
+                            edge_types: edge_types.clone(),
+                            backward: false,
+                            edge_costs: vec![],
+                        }, &None) {
+                            Response::Ids { ids } => ids.contains(&id_to_string(string_to_id(dst))),
+                            other => panic!("Expected Ids response, got {:?}", other),
+                        };
+                        let connected = match handle_request(&manager, &mut session, Request::AreConnected {
+                            src: src.to_string(),
+                            dst: dst.to_string(),
+                            edge_types: edge_types.clone(),
+                            max_depth,
+                        }, &None) {
+                            Response::Bool { value } => value,
+                            other => panic!("Expected Bool response, got {:?}", other),
+                        };
+                        assert_eq!(connected, reachable, "{} -> {} within {} via {:?}", src, dst, max_depth, edge_types);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_top_degree_nodes_star_center_first() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "top_degree");
+        session.protocol_version = 3;
+
+        // Star: hub calls five leaves, plus one leaf-to-leaf edge
+        let leaves = ["l1", "l2", "l3", "l4", "l5"];
+        let node = |id: &str| WireNode { semantic_id: Some(id.to_string()), ..txn_node(id) };
+        let mut nodes = vec![node("hub")];
+        nodes.extend(leaves.iter().map(|l| node(l)));
+        handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
+        let mut edges: Vec<WireEdge> = leaves.iter().map(|l| txn_edge("hub", l)).collect();
+        edges.push(txn_edge("l1", "l2"));
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges,
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let top = |session: &mut ClientSession, n: u32, direction: &str| {
+            match handle_request(&manager, session, Request::TopDegreeNodes {
+                n,
+                edge_types: Some(vec!["CALLS".to_string()]),
+                direction: direction.to_string(),
+            }, &None) {
+                Response::Scores { scores } => scores,
+                other => panic!("Expected Scores response, got {:?}", other),
+            }
+        };
+
+        let both = top(&mut session, 3, "both");
+        assert_eq!(both.len(), 3);
+        assert_eq!(both[0], ("hub".to_string(), 5.0));
+        assert_eq!(both[1].1, 2.0);
+        assert_eq!(top(&mut session, 1, "outgoing"), vec![("hub".to_string(), 5.0)]);
+        assert_eq!(top(&mut session, 1, "incoming")[0].1, 2.0);
+    }
+
+    // ============================================================================
+    // FindByAttr with substring_match
+    // ============================================================================
+
+    #[test]
+    fn test_find_by_attr_name_substring() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+
+        // Create and open ephemeral database
+        handle_request(&manager, &mut session, Request::CreateDatabase {
+            name: "testdb".to_string(),
+            ephemeral: true,
+            shard_count: None,
+        }, &None);
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "testdb".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+
+        // Add a node with name "handleFooBar"
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
                 WireNode {
@@ -3533,6 +9182,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
@@ -3545,8 +9195,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: true,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3565,6 +9218,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
         handle_request(&manager, &mut session, Request::OpenDatabase {
             name: "testdb".to_string(),
@@ -3582,6 +9236,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
@@ -3594,8 +9249,11 @@ mod protocol_tests {
                 file: Some("controllers/user".to_string()),
                 exported: None,
                 substring_match: true,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3614,6 +9272,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
         handle_request(&manager, &mut session, Request::OpenDatabase {
             name: "testdb".to_string(),
@@ -3630,6 +9289,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
@@ -3642,8 +9302,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: false,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3661,8 +9324,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: false,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3681,6 +9347,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
         handle_request(&manager, &mut session, Request::OpenDatabase {
             name: "testdb".to_string(),
@@ -3698,6 +9365,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
                 WireNode {
                     id: "2".to_string(),
@@ -3707,6 +9375,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
                 WireNode {
                     id: "3".to_string(),
@@ -3716,6 +9385,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
@@ -3729,8 +9399,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: true,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3749,6 +9422,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
         handle_request(&manager, &mut session, Request::OpenDatabase {
             name: "testdb".to_string(),
@@ -3766,6 +9440,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
                 WireNode {
                     id: "2".to_string(),
@@ -3775,6 +9450,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
@@ -3787,8 +9463,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: true,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3808,6 +9487,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
         handle_request(&manager, &mut session, Request::OpenDatabase {
             name: "testdb".to_string(),
@@ -3825,13 +9505,14 @@ mod protocol_tests {
                     exported: false,
                     metadata: None,
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
 
         // Flush to segment — data moves from write buffer to on-disk segment
         // This tests that zone map bypass works correctly for flushed segments
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // Substring match on name after flush
         let response = handle_request(&manager, &mut session, Request::FindByAttr {
@@ -3841,8 +9522,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: true,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3860,8 +9544,11 @@ mod protocol_tests {
                 file: Some("services/user".to_string()),
                 exported: None,
                 substring_match: true,
+                sorted: false,
                 extra: std::collections::HashMap::new(),
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3881,6 +9568,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "testdb".to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
 
         handle_request(&manager, &mut session, Request::OpenDatabase {
@@ -3891,13 +9579,13 @@ mod protocol_tests {
         // Declare fields
         let response = handle_request(&manager, &mut session, Request::DeclareFields {
             fields: vec![
-                WireFieldDecl { name: "object".to_string(), field_type: Some("string".to_string()), node_types: None },
-                WireFieldDecl { name: "method".to_string(), field_type: Some("string".to_string()), node_types: None },
+                WireFieldDecl { name: "object".to_string(), field_type: Some("string".to_string()), node_types: None, entity: None },
+                WireFieldDecl { name: "method".to_string(), field_type: Some("string".to_string()), node_types: None, entity: None },
             ],
         }, &None);
 
         match response {
-            Response::Count { count } => {
+            Response::Count { count, .. } => {
                 assert_eq!(count, 2, "Should report 2 declared fields");
             }
             _ => panic!("Expected Count response"),
@@ -3914,6 +9602,7 @@ mod protocol_tests {
                     exported: false,
                     metadata: Some(r#"{"object":"express","method":"get"}"#.to_string()),
                     semantic_id: None,
+                    deleted: false,
                 },
                 WireNode {
                     id: "2".to_string(),
@@ -3923,12 +9612,13 @@ mod protocol_tests {
                     exported: false,
                     metadata: Some(r#"{"object":"express","method":"post"}"#.to_string()),
                     semantic_id: None,
+                    deleted: false,
                 },
             ],
         }, &None);
 
         // Flush to build field indexes
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // Query using field-indexed metadata filter
         let mut extra = std::collections::HashMap::new();
@@ -3941,8 +9631,11 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: false,
+                sorted: false,
                 extra,
             },
+            per_shard_counts: false,
+            include_deleted: false,
         }, &None);
 
         match response {
@@ -3953,6 +9646,97 @@ mod protocol_tests {
         }
     }
 
+    #[test]
+    fn test_find_edges_by_metadata_with_declared_edge_field() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "edge_fields");
+
+        let response = handle_request(&manager, &mut session, Request::DeclareFields {
+            fields: vec![WireFieldDecl {
+                name: "argIndex".to_string(),
+                field_type: Some("int".to_string()),
+                node_types: None,
+                entity: Some("edge".to_string()),
+            }],
+        }, &None);
+        assert!(matches!(response, Response::Count { count: 1, .. }), "{:?}", response);
+
+        let arg = |src: &str, dst: &str, metadata: Option<&str>| WireEdge {
+            metadata: metadata.map(str::to_string),
+            ..txn_edge(src, dst)
+        };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("a"), txn_node("b"), txn_node("c")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                arg("a", "b", Some(r#"{"argIndex":0}"#)),
+                arg("a", "c", Some(r#"{"argIndex":1}"#)),
+                arg("b", "c", None),
+            ],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+
+        let response = handle_request(&manager, &mut session, Request::FindEdgesByMetadata {
+            field: "argIndex".to_string(),
+            value: "1".to_string(),
+            edge_type: Some("CALLS".to_string()),
+        }, &None);
+        match response {
+            Response::Edges { edges } => {
+                assert_eq!(edges.len(), 1);
+                assert_eq!(edges[0].dst, id_to_string(string_to_id("c")));
+                assert_eq!(edges[0].metadata.as_deref(), Some(r#"{"argIndex":1}"#));
+            }
+            other => panic!("Expected Edges response, got {:?}", other),
+        }
+
+        // QueryEdges with a metadata filter goes through the same index
+        let query = |session: &mut ClientSession, id: &str, direction: &str| {
+            match handle_request(&manager, session, Request::QueryEdges {
+                id: id.to_string(),
+                direction: direction.to_string(),
+                edge_types: None,
+                limit: None,
+                metadata: Some(HashMap::from([("argIndex".to_string(), "0".to_string())])),
+            }, &None) {
+                Response::Edges { edges } => edges.into_iter().map(|e| (e.src, e.dst)).collect::<Vec<_>>(),
+                other => panic!("Expected Edges response, got {:?}", other),
+            }
+        };
+        let (a, b) = (id_to_string(string_to_id("a")), id_to_string(string_to_id("b")));
+        assert_eq!(query(&mut session, "a", "outgoing"), vec![(a.clone(), b.clone())]);
+        assert_eq!(query(&mut session, "b", "incoming"), vec![(a, b)]);
+        assert!(query(&mut session, "c", "both").is_empty());
+        let db = manager.get_database("edge_fields").unwrap();
+        let engine = db.engine.read().unwrap();
+        let v2 = engine.as_any().downcast_ref::<GraphEngineV2>().unwrap();
+        assert_eq!(v2.edge_metadata_segment_scans(), 0);
+    }
+
+    #[test]
+    fn test_declare_fields_rejects_unknown_entity() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "bad_entity");
+
+        let response = handle_request(&manager, &mut session, Request::DeclareFields {
+            fields: vec![WireFieldDecl {
+                name: "argIndex".to_string(),
+                field_type: Some("int".to_string()),
+                node_types: None,
+                entity: Some("edges".to_string()),
+            }],
+        }, &None);
+        match response {
+            Response::Error { error } => assert!(error.contains("\"edges\""), "{}", error),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+    }
+
     // ============================================================================
     // CommitBatch Command
     // ============================================================================
@@ -3962,6 +9746,7 @@ mod protocol_tests {
         handle_request(manager, session, Request::CreateDatabase {
             name: name.to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
         handle_request(manager, session, Request::OpenDatabase {
             name: name.to_string(),
@@ -3978,28 +9763,31 @@ mod protocol_tests {
         // Add initial nodes for "app.js"
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("foo".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("bar".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("foo".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("bar".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // CommitBatch with new nodes for same file
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["app.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "3".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("baz".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "3".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("baz".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Verify delta
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_removed, 2);
                 assert_eq!(delta.nodes_added, 1);
@@ -4016,6 +9804,131 @@ mod protocol_tests {
         match new1 { Response::Bool { value } => assert!(value), _ => panic!("Expected Bool") }
     }
 
+    #[test]
+    fn test_commit_batch_rechecks_only_affected_rule_sets() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "recheck");
+        handle_request(&manager, &mut session, Request::SaveRuleSet {
+            name: "contained_classes".to_string(),
+            source: "violation(X) :- node(X, \"CLASS\"), \\+ edge(_, X, \"CONTAINS\").".to_string(),
+        }, &None);
+
+        let node = |id: &str, node_type: &str, file: &str| WireNode {
+            node_type: Some(node_type.to_string()),
+            file: Some(file.to_string()),
+            ..txn_node(id)
+        };
+        let mut commit = |nodes: Vec<WireNode>, edges: Vec<WireEdge>, file: &str| {
+            match handle_request(&manager, &mut session, Request::CommitBatch {
+                changed_files: vec![file.to_string()],
+                nodes,
+                edges,
+                tags: None,
+                file_context: None,
+                defer_index: false,
+                protected_types: vec![],
+                durability: None,
+                check_rule_sets: true,
+                dedup_edges: false,
+            }, &None) {
+                Response::BatchCommitted { guarantees: Some(guarantees), .. } => guarantees,
+                other => panic!("Expected BatchCommitted with guarantees, got {:?}", other),
+            }
+        };
+
+        // Only FUNCTION nodes and CALLS edges change: the rule cannot see them
+        let unaffected = commit(
+            vec![node("f1", "FUNCTION", "a.js"), node("f2", "FUNCTION", "a.js")],
+            vec![WireEdge { src: "f1".to_string(), dst: "f2".to_string(), edge_type: Some("CALLS".to_string()), metadata: None }],
+            "a.js",
+        );
+        assert!(unaffected.checked.is_empty());
+        assert_eq!(unaffected.skipped, vec!["contained_classes".to_string()]);
+        assert!(unaffected.violations.is_empty());
+
+        // A new uncontained CLASS is reported
+        let affected = commit(vec![node("c1", "CLASS", "b.js")], vec![], "b.js");
+        assert_eq!(affected.checked, vec!["contained_classes".to_string()]);
+        assert!(affected.skipped.is_empty() && affected.errors.is_empty());
+        let violations = &affected.violations["contained_classes"];
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].bindings["X"], string_to_id("c1").to_string());
+
+        // c1 still violates, but only the newly added c2 is reported
+        let again = commit(vec![node("c2", "CLASS", "c.js")], vec![], "c.js");
+        assert_eq!(again.checked, vec!["contained_classes".to_string()]);
+        let violations = &again.violations["contained_classes"];
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].bindings["X"], string_to_id("c2").to_string());
+
+        // Containing c2 fixes it and introduces nothing new
+        let fixed = commit(
+            vec![node("m1", "MODULE", "c.js"), node("c2", "CLASS", "c.js")],
+            vec![WireEdge { src: "m1".to_string(), dst: "c2".to_string(), edge_type: Some("CONTAINS".to_string()), metadata: None }],
+            "c.js",
+        );
+        assert_eq!(fixed.checked, vec!["contained_classes".to_string()]);
+        assert!(fixed.violations.is_empty());
+    }
+
+    #[test]
+    fn test_commit_type_changes_predicts_delta() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "predict_types");
+        let node = |id: &str, node_type: &str, file: &str| WireNode {
+            node_type: Some(node_type.to_string()),
+            file: Some(file.to_string()),
+            ..txn_node(id)
+        };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![node("m1", "MODULE", "a.js"), node("c1", "CLASS", "a.js"), node("f1", "FUNCTION", "b.js")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                WireEdge { src: "m1".to_string(), dst: "c1".to_string(), edge_type: Some("CONTAINS".to_string()), metadata: None },
+                WireEdge { src: "f1".to_string(), dst: "c1".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
+            ],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        let nodes = vec![wire_node_to_record(node("v1", "VARIABLE", "a.js"))];
+        let db = manager.get_database("predict_types").unwrap();
+        let (mut node_types, mut edge_types) = {
+            let engine = db.engine.read().unwrap();
+            commit_type_changes(&**engine, &["a.js".to_string()], None, &["MODULE".to_string()], &nodes, &[])
+        };
+        node_types.sort();
+        edge_types.sort();
+        assert_eq!(node_types, vec!["CLASS".to_string(), "VARIABLE".to_string()]);
+        assert_eq!(edge_types, vec!["CALLS".to_string(), "CONTAINS".to_string()]);
+
+        let response = handle_request(&manager, &mut session, Request::CommitBatch {
+            changed_files: vec!["a.js".to_string()],
+            nodes: vec![node("v1", "VARIABLE", "a.js")],
+            edges: vec![],
+            tags: None,
+            file_context: None,
+            defer_index: false,
+            protected_types: vec!["MODULE".to_string()],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        }, &None);
+        match response {
+            Response::BatchCommitted { mut delta, .. } => {
+                delta.changed_node_types.sort();
+                delta.changed_edge_types.sort();
+                assert_eq!(delta.changed_node_types, node_types);
+                assert_eq!(delta.changed_edge_types, edge_types);
+                assert_eq!((delta.nodes_removed, delta.edges_removed), (1, 2));
+            }
+            other => panic!("Expected BatchCommitted, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_commit_batch_delta_counts() {
         let (_dir, manager) = setup_test_manager();
@@ -4025,9 +9938,9 @@ mod protocol_tests {
         // Add nodes and edges
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "n1".to_string(), node_type: Some("MODULE".to_string()), name: Some("m1".to_string()), file: Some("src/a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "n2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("f1".to_string()), file: Some("src/a.js".to_string()), exported: true, metadata: None },
-                WireNode { semantic_id: None, id: "n3".to_string(), node_type: Some("MODULE".to_string()), name: Some("m2".to_string()), file: Some("src/b.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "n1".to_string(), node_type: Some("MODULE".to_string()), name: Some("m1".to_string()), file: Some("src/a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "n2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("f1".to_string()), file: Some("src/a.js".to_string()), exported: true, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "n3".to_string(), node_type: Some("MODULE".to_string()), name: Some("m2".to_string()), file: Some("src/b.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4035,24 +9948,28 @@ mod protocol_tests {
                 WireEdge { src: "n1".to_string(), dst: "n2".to_string(), edge_type: Some("CONTAINS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // CommitBatch replacing only src/a.js
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["src/a.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "n4".to_string(), node_type: Some("MODULE".to_string()), name: Some("m1v2".to_string()), file: Some("src/a.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "n4".to_string(), node_type: Some("MODULE".to_string()), name: Some("m1v2".to_string()), file: Some("src/a.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_removed, 2, "Old n1 and n2 should be removed");
                 assert_eq!(delta.nodes_added, 1, "n4 added");
@@ -4080,17 +9997,20 @@ mod protocol_tests {
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec![],
             nodes: vec![
-                WireNode { semantic_id: None, id: "x1".to_string(), node_type: Some("VARIABLE".to_string()), name: Some("x".to_string()), file: Some("new.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "x1".to_string(), node_type: Some("VARIABLE".to_string()), name: Some("x".to_string()), file: Some("new.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_removed, 0);
                 assert_eq!(delta.nodes_added, 1);
@@ -4103,6 +10023,196 @@ mod protocol_tests {
         match exists { Response::Bool { value } => assert!(value), _ => panic!("Expected Bool") }
     }
 
+    #[test]
+    fn test_rename_edge_type_migrates_delta_and_segment_edges() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        handle_request(&manager, &mut session, Request::CreateDatabase {
+            name: "rename_edges".to_string(),
+            ephemeral: false,
+            shard_count: None,
+        }, &None);
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "rename_edges".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+
+        let imports = |src: &str, dst: &str| WireEdge {
+            edge_type: Some("IMPORTS".to_string()),
+            metadata: Some(r#"{"line":1}"#.to_string()),
+            ..txn_edge(src, dst)
+        };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: ["a", "b", "c", "d"].iter().map(|id| txn_node(id)).collect(),
+        }, &None);
+        // Two edges flushed to segments, one left in the delta
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![imports("a", "b"), imports("b", "c"), txn_edge("a", "d")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![imports("c", "d")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        assert_eq!(count(&manager, &mut session, Request::RenameEdgeType {
+            from: "IMPORTS".to_string(),
+            to: "IMPORTS_FROM".to_string(),
+        }), 3);
+
+        let check_counts = |session: &mut ClientSession| {
+            match handle_request(&manager, session, Request::CountEdgesByType { edge_types: None }, &None) {
+                Response::Counts { counts } => {
+                    assert_eq!(counts.get("IMPORTS"), None);
+                    assert_eq!(counts.get("IMPORTS_FROM"), Some(&3));
+                    assert_eq!(counts.get("CALLS"), Some(&1));
+                }
+                other => panic!("Expected Counts, got {:?}", other),
+            }
+        };
+        check_counts(&mut session);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        check_counts(&mut session);
+
+        match handle_request(&manager, &mut session, Request::GetOutgoingEdges {
+            id: "a".to_string(),
+            edge_types: Some(vec!["IMPORTS_FROM".to_string()]),
+        }, &None) {
+            Response::Edges { edges } => {
+                assert_eq!(edges.len(), 1);
+                assert_eq!(edges[0].metadata.as_deref(), Some(r#"{"line":1}"#));
+            }
+            other => panic!("Expected Edges, got {:?}", other),
+        }
+
+        // Read-only sessions may not migrate
+        let mut reader = ClientSession::new(2);
+        handle_request(&manager, &mut reader, Request::OpenDatabase {
+            name: "rename_edges".to_string(),
+            mode: "ro".to_string(),
+        }, &None);
+        match handle_request(&manager, &mut reader, Request::RenameEdgeType {
+            from: "IMPORTS_FROM".to_string(),
+            to: "IMPORTS".to_string(),
+        }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "READ_ONLY_MODE"),
+            other => panic!("Expected READ_ONLY_MODE, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rename_node_type_updates_type_index() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        handle_request(&manager, &mut session, Request::CreateDatabase {
+            name: "rename_nodes".to_string(),
+            ephemeral: false,
+            shard_count: None,
+        }, &None);
+        handle_request(&manager, &mut session, Request::OpenDatabase {
+            name: "rename_nodes".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+
+        let class = |id: &str| WireNode { node_type: Some("CLASS".to_string()), ..txn_node(id) };
+        // Two nodes flushed to segments, one left in the delta
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("f1"), txn_node("f2"), class("c1")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("f1", "f2")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("f3")],
+        }, &None);
+
+        assert_eq!(count(&manager, &mut session, Request::RenameNodeType {
+            from: "FUNCTION".to_string(),
+            to: "METHOD".to_string(),
+        }), 3);
+
+        let find = |session: &mut ClientSession, node_type: &str| {
+            match handle_request(&manager, session, Request::FindByType {
+                node_type: node_type.to_string(),
+            }, &None) {
+                Response::Ids { mut ids } => {
+                    ids.sort();
+                    ids
+                }
+                other => panic!("Expected Ids, got {:?}", other),
+            }
+        };
+        let mut expected: Vec<String> = ["f1", "f2", "f3"].iter().map(|id| id_to_string(string_to_id(id))).collect();
+        expected.sort();
+        for _ in 0..2 {
+            assert_eq!(find(&mut session, "METHOD"), expected);
+            assert!(find(&mut session, "FUNCTION").is_empty());
+            assert_eq!(find(&mut session, "CLASS").len(), 1);
+            match handle_request(&manager, &mut session, Request::CountNodesByType { types: None }, &None) {
+                Response::Counts { counts } => {
+                    assert_eq!(counts.get("METHOD"), Some(&3));
+                    assert_eq!(counts.get("FUNCTION"), None);
+                }
+                other => panic!("Expected Counts, got {:?}", other),
+            }
+            handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        }
+
+        // Names and edges ride along with the renamed nodes
+        match handle_request(&manager, &mut session, Request::GetNode { id: "f1".to_string(), include_deleted: false }, &None) {
+            Response::Node { node: Some(node) } => {
+                assert_eq!(node.node_type.as_deref(), Some("METHOD"));
+                assert_eq!(node.name.as_deref(), Some("f1"));
+            }
+            other => panic!("Expected Node, got {:?}", other),
+        }
+        assert_eq!(count(&manager, &mut session, Request::EdgeCount), 1);
+    }
+
+    #[test]
+    fn test_renames_refused_inside_batch() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "rename_batch");
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("f1"), txn_node("f2")],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![txn_edge("f1", "f2")],
+            skip_validation: false,
+            dedup_edges: false,
+        }, &None);
+
+        handle_request(&manager, &mut session, Request::BeginBatch, &None);
+        for request in [
+            Request::RenameNodeType { from: "FUNCTION".to_string(), to: "METHOD".to_string() },
+            Request::RenameEdgeType { from: "CALLS".to_string(), to: "INVOKES".to_string() },
+        ] {
+            match handle_request(&manager, &mut session, request, &None) {
+                Response::Error { error } => assert!(error.contains("inside batch"), "{}", error),
+                other => panic!("Expected Error, got {:?}", other),
+            }
+        }
+        // Nothing was renamed, and the batch is still open
+        assert!(session.pending_batch_id().is_some());
+        match handle_request(&manager, &mut session, Request::FindByType { node_type: "FUNCTION".to_string() }, &None) {
+            Response::Ids { ids } => assert_eq!(ids.len(), 2),
+            other => panic!("Expected Ids, got {:?}", other),
+        }
+
+        handle_request(&manager, &mut session, Request::AbortBatch, &None);
+        assert_eq!(count(&manager, &mut session, Request::RenameEdgeType {
+            from: "CALLS".to_string(),
+            to: "INVOKES".to_string(),
+        }), 1);
+    }
+
     /// Non-ephemeral test: verifies segment edge deletion survives flush.
     /// This exercises the deleted_segment_edge_keys path in GraphStore.
     #[test]
@@ -4114,6 +10224,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CreateDatabase {
             name: "segtest".to_string(),
             ephemeral: false,
+            shard_count: None,
         }, &None);
         handle_request(&manager, &mut session, Request::OpenDatabase {
             name: "segtest".to_string(),
@@ -4123,9 +10234,9 @@ mod protocol_tests {
         // Add nodes and edges, then flush to segments
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "s1".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod_a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "s2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("func_b".to_string()), file: Some("a.js".to_string()), exported: true, metadata: None },
-                WireNode { semantic_id: None, id: "s3".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod_c".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "s1".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod_a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "s2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("func_b".to_string()), file: Some("a.js".to_string()), exported: true, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "s3".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod_c".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4134,27 +10245,31 @@ mod protocol_tests {
                 WireEdge { src: "s3".to_string(), dst: "s1".to_string(), edge_type: Some("IMPORTS_FROM".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         // Flush — nodes and edges are now in segment (on-disk), not in delta
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // CommitBatch replacing a.js — should delete segment edges too
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["a.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "s4".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod_a_v2".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "s4".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod_a_v2".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Verify delta counts
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_removed, 2, "s1 and s2 should be removed");
                 assert_eq!(delta.nodes_added, 1, "s4 added");
@@ -4183,7 +10298,7 @@ mod protocol_tests {
         }
 
         // Flush again — edges must stay gone (not reappear from segment)
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         let edges_after_flush = handle_request(&manager, &mut session, Request::GetAllEdges, &None);
         match edges_after_flush {
@@ -4211,8 +10326,8 @@ mod protocol_tests {
         // Two nodes in different files, connected by an edge
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "d1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("x.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "d2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("y.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "d1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("x.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "d2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("y.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4220,6 +10335,7 @@ mod protocol_tests {
                 WireEdge { src: "d1".to_string(), dst: "d2".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         // CommitBatch replacing BOTH files — the shared edge should be counted once
@@ -4231,10 +10347,13 @@ mod protocol_tests {
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.edges_removed, 1, "Shared edge should be counted exactly once");
                 assert_eq!(delta.nodes_removed, 2);
@@ -4256,11 +10375,11 @@ mod protocol_tests {
         // Add two nodes that edges will connect
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "e1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("src_fn".to_string()), file: Some("src/app.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "e2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("dst_fn".to_string()), file: Some("src/lib.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "e1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("src_fn".to_string()), file: Some("src/app.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "e2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("dst_fn".to_string()), file: Some("src/lib.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         let file_ctx = "__enrichment__/data-flow/src/app.js".to_string();
 
@@ -4275,10 +10394,13 @@ mod protocol_tests {
             file_context: Some(file_ctx.clone()),
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.edges_added, 1);
                 // file_context should be added to changed_files
@@ -4315,10 +10437,13 @@ mod protocol_tests {
             file_context: Some(file_ctx.clone()),
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response2 {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.edges_added, 1);
             }
@@ -4350,17 +10475,17 @@ mod protocol_tests {
         // Add initial nodes
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "c1".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod1".to_string()), file: Some("index.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "c2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("fn1".to_string()), file: Some("index.js".to_string()), exported: true, metadata: None },
+                WireNode { semantic_id: None, id: "c1".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod1".to_string()), file: Some("index.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "c2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("fn1".to_string()), file: Some("index.js".to_string()), exported: true, metadata: None, deleted: false },
             ],
         }, &None);
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // CommitBatch WITHOUT file_context — existing behavior, no __file_context injection
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["index.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "c3".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod1v2".to_string()), file: Some("index.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "c3".to_string(), node_type: Some("MODULE".to_string()), name: Some("mod1v2".to_string()), file: Some("index.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![
                 WireEdge { src: "c3".to_string(), dst: "c3".to_string(), edge_type: Some("SELF_REF".to_string()), metadata: Some(r#"{"info":"test"}"#.to_string()) },
@@ -4369,10 +10494,13 @@ mod protocol_tests {
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_removed, 2, "Old c1 and c2 removed");
                 assert_eq!(delta.nodes_added, 1, "c3 added");
@@ -4402,6 +10530,85 @@ mod protocol_tests {
         match c3 { Response::Bool { value } => assert!(value, "c3 should exist"), _ => panic!("Expected Bool") }
     }
 
+    #[test]
+    fn test_commit_batch_if_unchanged_rejects_stale_client() {
+        let (_dir, manager) = setup_test_manager();
+        let mut alice = ClientSession::new(1);
+        let mut bob = ClientSession::new(2);
+        setup_ephemeral_db(&manager, &mut alice, "occ_test");
+        handle_request(&manager, &mut bob, Request::OpenDatabase {
+            name: "occ_test".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+
+        let node = |id: &str| WireNode {
+            semantic_id: None,
+            id: id.to_string(),
+            node_type: Some("FUNCTION".to_string()),
+            name: Some(id.to_string()),
+            file: Some("shared.js".to_string()),
+            exported: false,
+            metadata: None,
+            deleted: false,
+        };
+        let write_version = |session: &mut ClientSession| {
+            let name = "occ_test".to_string();
+            match handle_request(&manager, session, Request::GetDatabaseInfo { name }, &None) {
+                Response::DatabaseInfo { info } => info.write_version.expect("v2 database reports a write version"),
+                other => panic!("Expected DatabaseInfo response, got {:?}", other),
+            }
+        };
+        let commit = |session: &mut ClientSession, id: &str, expected_version: u64| {
+            handle_request(&manager, session, Request::CommitBatchIfUnchanged {
+                changed_files: vec!["shared.js".to_string()],
+                expected_version,
+                nodes: vec![node(id)],
+                edges: vec![],
+            }, &None)
+        };
+        let assert_conflict = |response: Response, expected_version: u64, actual_version: u64| match response {
+            Response::VersionConflict { code, database, expected, actual, .. } => {
+                assert_eq!(code, "VERSION_CONFLICT");
+                assert_eq!(database, None);
+                assert_eq!((expected, actual), (expected_version, actual_version));
+            }
+            other => panic!("Expected VERSION_CONFLICT, got {:?}", other),
+        };
+
+        handle_request(&manager, &mut alice, Request::AddNodes { nodes: vec![node("base")] }, &None);
+        handle_request(&manager, &mut alice, Request::Flush { durability: None }, &None);
+
+        // Both clients read the same version
+        let seen = write_version(&mut alice);
+        assert_eq!(write_version(&mut bob), seen);
+
+        let after_alice = match commit(&mut alice, "alice_fn", seen) {
+            Response::BatchCommitted { ok: true, version: Some(version), .. } => version,
+            other => panic!("Expected BatchCommitted with a version, got {:?}", other),
+        };
+        assert!(after_alice > seen);
+        assert_eq!(write_version(&mut bob), after_alice);
+
+        // Bob is now stale: rejected, and nothing of his is written
+        assert_conflict(commit(&mut bob, "bob_fn", seen), seen, after_alice);
+        for (id, expected) in [("alice_fn", true), ("bob_fn", false)] {
+            match handle_request(&manager, &mut bob, Request::NodeExists { id: id.to_string() }, &None) {
+                Response::Bool { value } => assert_eq!(value, expected, "{}", id),
+                other => panic!("Expected Bool response, got {:?}", other),
+            }
+        }
+
+        // A write that is not flushed yet still moves the version
+        handle_request(&manager, &mut alice, Request::AddNodes { nodes: vec![node("unflushed")] }, &None);
+        let after_add = write_version(&mut bob);
+        assert!(after_add > after_alice);
+        assert_conflict(commit(&mut bob, "bob_fn", after_alice), after_alice, after_add);
+
+        // Retrying at the current version succeeds
+        let response = commit(&mut bob, "bob_fn", after_add);
+        assert!(matches!(response, Response::BatchCommitted { ok: true, .. }), "got {:?}", response);
+    }
+
     // ============================================================================
     // BeginBatch / AbortBatch Commands
     // ============================================================================
@@ -4417,7 +10624,7 @@ mod protocol_tests {
             Response::BatchStarted { ok, batch_id } => {
                 assert!(ok);
                 assert!(!batch_id.is_empty());
-                assert!(session.pending_batch_id.is_some());
+                assert!(session.pending_batch_id().is_some());
             }
             _ => panic!("Expected BatchStarted response"),
         }
@@ -4449,7 +10656,7 @@ mod protocol_tests {
 
         // Start batch
         handle_request(&manager, &mut session, Request::BeginBatch, &None);
-        assert!(session.pending_batch_id.is_some());
+        assert!(session.pending_batch_id().is_some());
 
         // Abort it
         let response = handle_request(&manager, &mut session, Request::AbortBatch, &None);
@@ -4458,7 +10665,7 @@ mod protocol_tests {
             Response::Ok { ok } => assert!(ok),
             _ => panic!("Expected Ok response"),
         }
-        assert!(session.pending_batch_id.is_none());
+        assert!(session.pending_batch_id().is_none());
     }
 
     #[test]
@@ -4476,6 +10683,208 @@ mod protocol_tests {
         }
     }
 
+    /// Open "default" read-write in both sessions
+    fn open_default_rw(manager: &Arc<DatabaseManager>, sessions: [&mut ClientSession; 2]) {
+        for session in sessions {
+            handle_request(manager, session, Request::OpenDatabase {
+                name: "default".to_string(),
+                mode: "rw".to_string(),
+            }, &None);
+        }
+    }
+
+    fn txn_node(id: &str) -> WireNode {
+        WireNode {
+            id: id.to_string(),
+            semantic_id: None,
+            node_type: Some("FUNCTION".to_string()),
+            name: Some(id.to_string()),
+            file: None,
+            exported: false,
+            metadata: None,
+            deleted: false,
+        }
+    }
+
+    fn txn_edge(src: &str, dst: &str) -> WireEdge {
+        WireEdge { src: src.to_string(), dst: dst.to_string(), edge_type: Some("CALLS".to_string()), metadata: None }
+    }
+
+    fn count(manager: &Arc<DatabaseManager>, session: &mut ClientSession, request: Request) -> u32 {
+        match handle_request(manager, session, request, &None) {
+            Response::Count { count, .. } => count,
+            other => panic!("Expected Count response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_commit_transaction_applies_staged_writes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut writer = ClientSession::new(1);
+        let mut other = ClientSession::new(2);
+        open_default_rw(&manager, [&mut writer, &mut other]);
+
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("old")] }, &None);
+        handle_request(&manager, &mut writer, Request::BeginBatch, &None);
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("a"), txn_node("b")] }, &None);
+        handle_request(&manager, &mut writer, Request::AddEdges { edges: vec![txn_edge("a", "b")], skip_validation: false, dedup_edges: false }, &None);
+        handle_request(&manager, &mut writer, Request::DeleteNode { id: "old".to_string() }, &None);
+
+        // Nothing reaches the engine before commit
+        assert_eq!(count(&manager, &mut other, Request::NodeCount), 1);
+        assert_eq!(count(&manager, &mut other, Request::EdgeCount), 0);
+
+        let response = handle_request(&manager, &mut writer, Request::CommitTransaction, &None);
+        assert!(matches!(response, Response::Ok { ok: true }), "got {:?}", response);
+        assert!(writer.pending_batch_id().is_none());
+
+        assert_eq!(count(&manager, &mut other, Request::NodeCount), 2);
+        assert_eq!(count(&manager, &mut other, Request::EdgeCount), 1);
+        match handle_request(&manager, &mut other, Request::NodeExists { id: "old".to_string() }, &None) {
+            Response::Bool { value } => assert!(!value),
+            other => panic!("Expected Bool response, got {:?}", other),
+        }
+
+        // Writes after commit go straight to the engine again
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("c")] }, &None);
+        assert_eq!(count(&manager, &mut other, Request::NodeCount), 3);
+
+        let response = handle_request(&manager, &mut writer, Request::CommitTransaction, &None);
+        assert!(matches!(response, Response::Error { .. }), "got {:?}", response);
+    }
+
+    #[test]
+    fn test_commit_transaction_refused_keeps_batch_open() {
+        let (_dir, manager) = setup_test_manager();
+        let mut writer = ClientSession::new(1);
+        let mut other = ClientSession::new(2);
+        open_default_rw(&manager, [&mut writer, &mut other]);
+
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("a"), txn_node("b")] }, &None);
+        handle_request(&manager, &mut writer, Request::BeginBatch, &None);
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("c")] }, &None);
+        let response = handle_request(&manager, &mut writer, Request::AddEdges {
+            edges: vec![txn_edge("a", "b")], skip_validation: false, dedup_edges: false,
+        }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "got {:?}", response);
+
+        // Another client removes an endpoint before the commit
+        handle_request(&manager, &mut other, Request::DeleteNode { id: "b".to_string() }, &None);
+        match handle_request(&manager, &mut writer, Request::CommitTransaction, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "DANGLING_EDGE"),
+            other => panic!("Expected DANGLING_EDGE, got {:?}", other),
+        }
+        // Nothing applied, and the batch is still open for a retry
+        assert_eq!(count(&manager, &mut other, Request::NodeCount), 1);
+        assert!(writer.pending_batch_id().is_some());
+
+        handle_request(&manager, &mut other, Request::AddNodes { nodes: vec![txn_node("b")] }, &None);
+        let response = handle_request(&manager, &mut writer, Request::CommitTransaction, &None);
+        assert!(matches!(response, Response::Ok { ok: true }), "got {:?}", response);
+        match handle_request(&manager, &mut other, Request::NodeExists { id: "c".to_string() }, &None) {
+            Response::Bool { value } => assert!(value),
+            other => panic!("Expected Bool response, got {:?}", other),
+        }
+        assert_eq!(count(&manager, &mut other, Request::EdgeCount), 1);
+    }
+
+    #[test]
+    fn test_oversized_batch_is_aborted() {
+        let (_dir, manager) = setup_test_manager();
+        let mut writer = ClientSession::new(1);
+        let mut other = ClientSession::new(2);
+        open_default_rw(&manager, [&mut writer, &mut other]);
+        writer.max_batch_bytes = 512;
+
+        handle_request(&manager, &mut writer, Request::BeginBatch, &None);
+        let response = handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("a")] }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "got {:?}", response);
+        let big: Vec<WireNode> = (0..16).map(|i| txn_node(&format!("n{}", i))).collect();
+        match handle_request(&manager, &mut writer, Request::AddNodes { nodes: big }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "BATCH_TOO_LARGE"),
+            other => panic!("Expected BATCH_TOO_LARGE, got {:?}", other),
+        }
+        assert!(writer.pending_batch_id().is_none());
+        assert_eq!(count(&manager, &mut other, Request::NodeCount), 0);
+    }
+
+    #[test]
+    fn test_abort_batch_discards_staged_writes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut writer = ClientSession::new(1);
+        let mut other = ClientSession::new(2);
+        open_default_rw(&manager, [&mut writer, &mut other]);
+
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("a"), txn_node("b")] }, &None);
+        handle_request(&manager, &mut writer, Request::AddEdges { edges: vec![txn_edge("a", "b")], skip_validation: false, dedup_edges: false }, &None);
+        let writes_before = manager.databases()[0].write_count();
+
+        handle_request(&manager, &mut writer, Request::BeginBatch, &None);
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("c")] }, &None);
+        handle_request(&manager, &mut writer, Request::DeleteEdge {
+            src: "a".to_string(),
+            dst: "b".to_string(),
+            edge_type: "CALLS".to_string(),
+        }, &None);
+        handle_request(&manager, &mut writer, Request::DeleteNode { id: "a".to_string() }, &None);
+        handle_request(&manager, &mut writer, Request::AbortBatch, &None);
+
+        for session in [&mut writer, &mut other] {
+            assert_eq!(count(&manager, session, Request::NodeCount), 2);
+            assert_eq!(count(&manager, session, Request::EdgeCount), 1);
+        }
+        assert_eq!(manager.databases()[0].write_count(), writes_before);
+    }
+
+    #[test]
+    fn test_transaction_reads_own_writes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut writer = ClientSession::new(1);
+        let mut other = ClientSession::new(2);
+        open_default_rw(&manager, [&mut writer, &mut other]);
+
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("a"), txn_node("b")] }, &None);
+        handle_request(&manager, &mut writer, Request::BeginBatch, &None);
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![txn_node("c")] }, &None);
+        handle_request(&manager, &mut writer, Request::AddEdges { edges: vec![txn_edge("a", "c")], skip_validation: false, dedup_edges: false }, &None);
+        handle_request(&manager, &mut writer, Request::DeleteNode { id: "b".to_string() }, &None);
+
+        let find = |session: &mut ClientSession| {
+            match handle_request(&manager, session, Request::FindByType { node_type: "FUNCTION".to_string() }, &None) {
+                Response::Ids { mut ids } => {
+                    ids.sort();
+                    ids
+                }
+                other => panic!("Expected Ids response, got {:?}", other),
+            }
+        };
+        let a = id_to_string(string_to_id("a"));
+        let b = id_to_string(string_to_id("b"));
+        let c = id_to_string(string_to_id("c"));
+
+        let mut expected = vec![a.clone(), c.clone()];
+        expected.sort();
+        assert_eq!(find(&mut writer), expected);
+        let mut committed = vec![a.clone(), b];
+        committed.sort();
+        assert_eq!(find(&mut other), committed);
+
+        match handle_request(&manager, &mut writer, Request::GetNode { id: "c".to_string(), include_deleted: false }, &None) {
+            Response::Node { node } => assert_eq!(node.unwrap().name.as_deref(), Some("c")),
+            other => panic!("Expected Node response, got {:?}", other),
+        }
+        match handle_request(&manager, &mut writer, Request::Neighbors {
+            id: "a".to_string(),
+            edge_types: vec!["CALLS".to_string()],
+        }, &None) {
+            Response::Ids { ids } => assert_eq!(ids, vec![c]),
+            other => panic!("Expected Ids response, got {:?}", other),
+        }
+        assert_eq!(count(&manager, &mut writer, Request::NodeCount), 2);
+        assert_eq!(count(&manager, &mut writer, Request::EdgeCount), 1);
+        assert_eq!(count(&manager, &mut other, Request::EdgeCount), 0);
+    }
+
     // ============================================================================
     // Snapshot Commands (v2 engine only)
     // ============================================================================
@@ -4486,6 +10895,7 @@ mod protocol_tests {
         handle_request(manager, session, Request::CreateDatabase {
             name: name.to_string(),
             ephemeral: true,
+            shard_count: None,
         }, &None);
         handle_request(manager, session, Request::OpenDatabase {
             name: name.to_string(),
@@ -4509,45 +10919,491 @@ mod protocol_tests {
                 // The important thing is it doesn't error
                 assert!(snapshots.len() <= 1);
             }
-            _ => panic!("Expected SnapshotList response, got {:?}", response),
-        }
+            _ => panic!("Expected SnapshotList response, got {:?}", response),
+        }
+    }
+
+    #[test]
+    fn test_find_snapshot_v2() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_v2_ephemeral_db(&manager, &mut session, "snap_find_test");
+
+        // Find non-existent snapshot
+        let response = handle_request(&manager, &mut session, Request::FindSnapshot {
+            tag_key: "name".to_string(),
+            tag_value: "nonexistent".to_string(),
+        }, &None);
+
+        match response {
+            Response::SnapshotVersion { version } => {
+                assert!(version.is_none());
+            }
+            _ => panic!("Expected SnapshotVersion response, got {:?}", response),
+        }
+    }
+
+    #[test]
+    fn test_restore_snapshot_on_ephemeral_database() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_v2_ephemeral_db(&manager, &mut session, "snap_restore_ephemeral");
+
+        handle_request(&manager, &mut session, Request::AddNodes { nodes: vec![txn_node("a")] }, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        let version = manager.get_database("snap_restore_ephemeral").unwrap().engine.read().unwrap()
+            .as_any().downcast_ref::<GraphEngineV2>().unwrap().current_version();
+        match handle_request(&manager, &mut session, Request::RestoreSnapshot { version }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "EPHEMERAL_UNSUPPORTED"),
+            other => panic!("Expected EPHEMERAL_UNSUPPORTED, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_restore_snapshot_unknown_version() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_v2_ephemeral_db(&manager, &mut session, "snap_restore_test");
+
+        let response = handle_request(&manager, &mut session, Request::RestoreSnapshot {
+            version: 9999,
+        }, &None);
+
+        match response {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "SNAPSHOT_NOT_FOUND"),
+            _ => panic!("Expected ErrorWithCode response, got {:?}", response),
+        }
+    }
+
+    #[test]
+    fn test_explain_query_declared_vs_undeclared_field() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_v2_ephemeral_db(&manager, &mut session, "explain_test");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![WireNode {
+                id: "1".to_string(),
+                node_type: Some("CALL".to_string()),
+                name: Some("app.get".to_string()),
+                file: Some("app.js".to_string()),
+                exported: false,
+                metadata: Some(r#"{"object":"express","method":"get"}"#.to_string()),
+                semantic_id: None,
+                deleted: false,
+            }],
+        }, &None);
+        handle_request(&manager, &mut session, Request::DeclareFields {
+            fields: vec![
+                WireFieldDecl { name: "object".to_string(), field_type: None, node_types: None, entity: None },
+            ],
+        }, &None);
+
+        let mut explain = |field: &str, value: &str| {
+            let mut extra = std::collections::HashMap::new();
+            extra.insert(field.to_string(), serde_json::json!(value));
+            let query = WireAttrQuery {
+                node_type: Some("CALL".to_string()),
+                name: None,
+                file: None,
+                exported: None,
+                substring_match: false,
+                sorted: false,
+                extra,
+            };
+            match handle_request(&manager, &mut session, Request::ExplainQuery { query }, &None) {
+                Response::Explain { plan } => plan,
+                other => panic!("Expected Explain response, got {:?}", other),
+            }
+        };
+
+        let declared = explain("object", "express");
+        assert!(declared.contains("Metadata field 'object': declared"), "{}", declared);
+        assert!(declared.contains("write buffer: buffer scan, 1 candidates"), "{}", declared);
+        assert!(declared.contains("Result: 1 nodes"), "{}", declared);
+
+        let undeclared = explain("method", "post");
+        assert!(undeclared.contains("Metadata field 'method': undeclared"), "{}", undeclared);
+        assert!(undeclared.contains("-> metadata.method 0"), "{}", undeclared);
+        assert!(undeclared.contains("Result: 0 nodes"), "{}", undeclared);
+    }
+
+    #[test]
+    fn test_datalog_parse_error_reports_position() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "parse_errors");
+
+        // Missing comma before "http:route" on the second line
+        let rule_source = "violation(X) :-\n    node(X \"http:route\").";
+        let response = handle_request(&manager, &mut session, Request::CheckGuarantee {
+            rule_source: rule_source.to_string(),
+            explain: false,
+            timeout_ms: None,
+        }, &None);
+        match response {
+            Response::ErrorWithPosition { code, kind, position, .. } => {
+                assert_eq!(code, "DATALOG_PARSE");
+                assert_eq!(kind, "syntax");
+                assert_eq!((position.line, position.col), (2, 12));
+            }
+            other => panic!("Expected ErrorWithPosition, got {:?}", other),
+        }
+
+        let response = handle_request(&manager, &mut session, Request::DatalogQuery {
+            query: "node(X, \"A\"), lt(X, Y)".to_string(),
+            explain: false,
+        }, &None);
+        match response {
+            Response::ErrorWithPosition { code, kind, .. } => {
+                assert_eq!(code, "DATALOG_PARSE");
+                assert_eq!(kind, "unsafe_comparison");
+            }
+            other => panic!("Expected ErrorWithPosition, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rule_sets_save_run_list_delete() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "rule_sets");
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("fn_a"), txn_node("fn_b")],
+        }, &None);
+
+        let rule_source = "violation(X) :- node(X, \"FUNCTION\").";
+        let response = handle_request(&manager, &mut session, Request::SaveRuleSet {
+            name: "no_functions".to_string(),
+            source: rule_source.to_string(),
+        }, &None);
+        assert!(matches!(response, Response::Count { count: 1, .. }), "{:?}", response);
+
+        // Unparseable sources are rejected and not stored
+        let response = handle_request(&manager, &mut session, Request::SaveRuleSet {
+            name: "broken".to_string(),
+            source: "violation(X) :-".to_string(),
+        }, &None);
+        assert!(matches!(response, Response::ErrorWithPosition { .. }), "{:?}", response);
+
+        let violation_ids = |response: Response| -> Vec<String> {
+            match response {
+                Response::Violations { violations } => {
+                    let mut ids: Vec<String> = violations.into_iter()
+                        .map(|v| v.bindings["X"].clone())
+                        .collect();
+                    ids.sort();
+                    ids
+                }
+                other => panic!("Expected Violations, got {:?}", other),
+            }
+        };
+        let inline = violation_ids(handle_request(&manager, &mut session, Request::CheckGuarantee {
+            rule_source: rule_source.to_string(),
+            explain: false,
+            timeout_ms: None,
+        }, &None));
+        assert_eq!(inline.len(), 2);
+
+        // A fresh connection sees the saved rule set
+        let mut other = ClientSession::new(2);
+        handle_request(&manager, &mut other, Request::OpenDatabase {
+            name: "rule_sets".to_string(),
+            mode: "rw".to_string(),
+        }, &None);
+        let saved = violation_ids(handle_request(&manager, &mut other, Request::RunRuleSet {
+            name: "no_functions".to_string(),
+            timeout_ms: None,
+        }, &None));
+        assert_eq!(saved, inline);
+
+        match handle_request(&manager, &mut other, Request::ListRuleSets, &None) {
+            Response::RuleSetList { rule_sets } => assert_eq!(rule_sets, vec!["no_functions".to_string()]),
+            other => panic!("Expected RuleSetList, got {:?}", other),
+        }
+
+        let delete = |session: &mut ClientSession| handle_request(&manager, session, Request::DeleteRuleSet {
+            name: "no_functions".to_string(),
+        }, &None);
+        assert!(matches!(delete(&mut session), Response::Bool { value: true }));
+        assert!(matches!(delete(&mut session), Response::Bool { value: false }));
+
+        match handle_request(&manager, &mut other, Request::RunRuleSet {
+            name: "no_functions".to_string(),
+            timeout_ms: None,
+        }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "RULE_SET_NOT_FOUND"),
+            other => panic!("Expected RULE_SET_NOT_FOUND, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_guarantee_timeout_returns_timeout_code() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "datalog_timeout");
+
+        // A chain of 40 layers, 3 nodes wide, fully connected between layers:
+        // top-down transitive closure enumerates 3^39 paths from each start node.
+        const LAYERS: usize = 40;
+        const WIDTH: usize = 3;
+        let id = |layer: usize, i: usize| format!("n{}_{}", layer, i);
+        let nodes = (0..LAYERS)
+            .flat_map(|layer| (0..WIDTH).map(move |i| (layer, i)))
+            .map(|(layer, i)| WireNode {
+                semantic_id: None,
+                id: id(layer, i),
+                node_type: Some("STEP".to_string()),
+                name: Some(id(layer, i)),
+                file: Some("chain.js".to_string()),
+                exported: false,
+                metadata: None,
+                deleted: false,
+            })
+            .collect();
+        handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
+        let mut edges = Vec::new();
+        for layer in 0..LAYERS - 1 {
+            for i in 0..WIDTH {
+                for j in 0..WIDTH {
+                    edges.push(WireEdge {
+                        src: id(layer, i),
+                        dst: id(layer + 1, j),
+                        edge_type: Some("NEXT".to_string()),
+                        metadata: None,
+                    });
+                }
+            }
+        }
+        handle_request(&manager, &mut session, Request::AddEdges { edges, skip_validation: true, dedup_edges: false }, &None);
+
+        let rule_source = r#"
+            reach(X, Y) :- edge(X, Y, "NEXT").
+            reach(X, Z) :- edge(X, Y, "NEXT"), reach(Y, Z).
+            violation(X) :- node(X, "STEP"), reach(X, _).
+        "#;
+        let start = std::time::Instant::now();
+        let response = handle_request(&manager, &mut session, Request::CheckGuarantee {
+            rule_source: rule_source.to_string(),
+            explain: false,
+            timeout_ms: Some(50),
+        }, &None);
+
+        match response {
+            Response::ErrorWithCode { error, code } => {
+                assert_eq!(code, "TIMEOUT");
+                assert!(error.contains("timeout"), "{}", error);
+            }
+            other => panic!("Expected TIMEOUT error, got {:?}", other),
+        }
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        // The session stays usable after the aborted evaluation
+        let response = handle_request(&manager, &mut session, Request::ExecuteDatalog {
+            source: r#"node(X, "STEP"), attr(X, "name", "n0_0")"#.to_string(),
+            explain: false,
+            timeout_ms: Some(5_000),
+        }, &None);
+        match response {
+            Response::DatalogResults { results } => assert_eq!(results.len(), 1),
+            other => panic!("Expected DatalogResults, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_pin_snapshot_hides_later_writes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut writer = ClientSession::new(1);
+        let mut reader = ClientSession::new(2);
+        for session in [&mut writer, &mut reader] {
+            handle_request(&manager, session, Request::OpenDatabase {
+                name: "default".to_string(),
+                mode: "rw".to_string(),
+            }, &None);
+        }
+
+        let node = |id: &str| WireNode {
+            id: id.to_string(),
+            semantic_id: None,
+            node_type: Some("FUNCTION".to_string()),
+            name: Some(id.to_string()),
+            file: None,
+            exported: false,
+            metadata: None,
+            deleted: false,
+        };
+        let node_exists = |session: &mut ClientSession, id: &str| {
+            match handle_request(&manager, session, Request::NodeExists { id: id.to_string() }, &None) {
+                Response::Bool { value } => value,
+                other => panic!("Expected Bool response, got {:?}", other),
+            }
+        };
+
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![node("v1_fn")] }, &None);
+        handle_request(&manager, &mut writer, Request::Flush { durability: None }, &None);
+        let v1 = match handle_request(&manager, &mut writer, Request::ListSnapshots { filter_tag: None }, &None) {
+            Response::SnapshotList { snapshots } => snapshots.iter().map(|s| s.version).max().unwrap(),
+            other => panic!("Expected SnapshotList response, got {:?}", other),
+        };
+
+        let response = handle_request(&manager, &mut reader, Request::PinSnapshot { version: v1 }, &None);
+        assert!(matches!(response, Response::Ok { ok: true }), "got {:?}", response);
+
+        handle_request(&manager, &mut writer, Request::AddNodes { nodes: vec![node("v2_fn")] }, &None);
+        handle_request(&manager, &mut writer, Request::Flush { durability: None }, &None);
+
+        assert!(node_exists(&mut reader, "v1_fn"));
+        assert!(!node_exists(&mut reader, "v2_fn"));
+        assert!(node_exists(&mut writer, "v2_fn"));
+
+        let response = handle_request(&manager, &mut reader, Request::AddNodes { nodes: vec![node("x")] }, &None);
+        match response {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "SNAPSHOT_PINNED"),
+            other => panic!("Expected ErrorWithCode response, got {:?}", other),
+        }
+
+        handle_request(&manager, &mut reader, Request::UnpinSnapshot, &None);
+        assert!(node_exists(&mut reader, "v2_fn"));
+    }
+
+    #[test]
+    fn test_v1_database_rejected() {
+        let dir = tempdir().unwrap();
+        let manager = Arc::new(DatabaseManager::new(dir.path().to_path_buf()));
+
+        // Create a directory with nodes.bin to simulate legacy v1 database
+        let v1_path = dir.path().join("default.rfdb");
+        std::fs::create_dir_all(&v1_path).unwrap();
+        std::fs::write(v1_path.join("nodes.bin"), b"dummy").unwrap();
+
+        // create_default_from_path should reject v1 databases
+        let result = manager.create_default_from_path(&v1_path);
+        assert!(result.is_err());
+        let err_msg = format!("{}", result.unwrap_err());
+        assert!(err_msg.contains("Legacy v1 database"), "Error should mention legacy v1 database: {}", err_msg);
+    }
+
+    #[test]
+    fn test_resource_info_reports_recommended_shard_count() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+
+        match handle_request(&manager, &mut session, Request::ResourceInfo, &None) {
+            Response::ResourceInfo { total_memory_bytes, cpu_count, recommended_shard_count, default_shard_count, .. } => {
+                assert!(total_memory_bytes > 0);
+                assert!(cpu_count >= 1);
+                assert!((1..=16).contains(&recommended_shard_count), "{}", recommended_shard_count);
+                assert_eq!(default_shard_count, None);
+            }
+            other => panic!("Expected ResourceInfo, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_find_orphans_skips_called_and_root_nodes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "orphans");
+
+        let route = WireNode { node_type: Some("http:route".to_string()), ..txn_node("route") };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![route, txn_node("called"), txn_node("uncalled")],
+        }, &None);
+        let calls = |src: &str, dst: &str| WireEdge {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            edge_type: Some("CALLS".to_string()),
+            metadata: None,
+        };
+        // A non-CALLS edge into "uncalled" does not make it reachable
+        let mut contains = calls("route", "uncalled");
+        contains.edge_type = Some("CONTAINS".to_string());
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![calls("route", "called"), contains],
+            skip_validation: true,
+            dedup_edges: false,
+        }, &None);
+
+        let mut orphans = |root_types: Vec<String>| match handle_request(&manager, &mut session, Request::FindOrphans {
+            edge_types: vec!["CALLS".to_string()],
+            root_types,
+        }, &None) {
+            Response::Ids { ids } => ids,
+            other => panic!("Expected Ids, got {:?}", other),
+        };
+        let id = |s: &str| string_to_id(s).to_string();
+
+        assert_eq!(orphans(vec!["http:route".to_string()]), vec![id("uncalled")]);
+        let mut without_roots = orphans(vec![]);
+        without_roots.sort();
+        let mut expected = vec![id("route"), id("uncalled")];
+        expected.sort();
+        assert_eq!(without_roots, expected);
+    }
+
+    #[test]
+    fn test_edge_type_matrix_counts_typed_pairs() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "edge_matrix");
+
+        let method = |id: &str| WireNode { node_type: Some("METHOD".to_string()), ..txn_node(id) };
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![txn_node("f1"), txn_node("f2"), method("m1"), method("m2")],
+        }, &None);
+        let edge = |src: &str, dst: &str, edge_type: &str| WireEdge {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            edge_type: Some(edge_type.to_string()),
+            metadata: None,
+        };
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                edge("f1", "m1", "CALLS"),
+                edge("f2", "m1", "CALLS"),
+                edge("f1", "m2", "CALLS"),
+                edge("f1", "f2", "CALLS"),
+                edge("m1", "m2", "http:calls"),
+            ],
+            skip_validation: true,
+            dedup_edges: false,
+        }, &None);
+
+        let cell = |src: &str, dst: &str, edge_type: &str, count: usize| {
+            (src.to_string(), dst.to_string(), edge_type.to_string(), count)
+        };
+        let mut matrix = |edge_types: Option<Vec<String>>| {
+            match handle_request(&manager, &mut session, Request::EdgeTypeMatrix { edge_types }, &None) {
+                Response::Matrix { cells } => cells,
+                other => panic!("Expected Matrix, got {:?}", other),
+            }
+        };
+        assert_eq!(matrix(None), vec![
+            cell("FUNCTION", "METHOD", "CALLS", 3),
+            cell("FUNCTION", "FUNCTION", "CALLS", 1),
+            cell("METHOD", "METHOD", "http:calls", 1),
+        ]);
+        assert_eq!(matrix(Some(vec!["http:*".to_string()])), vec![cell("METHOD", "METHOD", "http:calls", 1)]);
     }
 
     #[test]
-    fn test_find_snapshot_v2() {
+    fn test_compute_id_matches_string_to_id() {
         let (_dir, manager) = setup_test_manager();
         let mut session = ClientSession::new(1);
-        setup_v2_ephemeral_db(&manager, &mut session, "snap_find_test");
 
-        // Find non-existent snapshot
-        let response = handle_request(&manager, &mut session, Request::FindSnapshot {
-            tag_key: "name".to_string(),
-            tag_value: "nonexistent".to_string(),
-        }, &None);
-
-        match response {
-            Response::SnapshotVersion { version } => {
-                assert!(version.is_none());
+        for input in ["12345", "FUNCTION:getUserById#src/api/users.js", ""] {
+            match handle_request(&manager, &mut session, Request::ComputeId { input: input.to_string() }, &None) {
+                Response::Identifier { identifier } => {
+                    assert_eq!(identifier, Some(string_to_id(input).to_string()), "input {:?}", input);
+                }
+                other => panic!("Expected Identifier, got {:?}", other),
             }
-            _ => panic!("Expected SnapshotVersion response, got {:?}", response),
         }
-    }
-
-    #[test]
-    fn test_v1_database_rejected() {
-        let dir = tempdir().unwrap();
-        let manager = Arc::new(DatabaseManager::new(dir.path().to_path_buf()));
-
-        // Create a directory with nodes.bin to simulate legacy v1 database
-        let v1_path = dir.path().join("default.rfdb");
-        std::fs::create_dir_all(&v1_path).unwrap();
-        std::fs::write(v1_path.join("nodes.bin"), b"dummy").unwrap();
-
-        // create_default_from_path should reject v1 databases
-        let result = manager.create_default_from_path(&v1_path);
-        assert!(result.is_err());
-        let err_msg = format!("{}", result.unwrap_err());
-        assert!(err_msg.contains("Legacy v1 database"), "Error should mention legacy v1 database: {}", err_msg);
+        assert_eq!(string_to_id("12345"), 12345);
+        assert_eq!(
+            string_to_id("FUNCTION:getUserById#src/api/users.js"),
+            rfdb::graph::string_id_to_u128("FUNCTION:getUserById#src/api/users.js"),
+        );
     }
 
     // ============================================================================
@@ -4563,9 +11419,9 @@ mod protocol_tests {
         // Add nodes and edges
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "c".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("c".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "c".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("c".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4575,6 +11431,7 @@ mod protocol_tests {
                 WireEdge { src: "b".to_string(), dst: "a".to_string(), edge_type: Some("IMPORTS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         // Query outgoing edges from "a"
@@ -4583,6 +11440,7 @@ mod protocol_tests {
             direction: "outgoing".to_string(),
             edge_types: None,
             limit: None,
+            metadata: None,
         }, &None);
 
         match response {
@@ -4601,8 +11459,8 @@ mod protocol_tests {
 
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4610,6 +11468,7 @@ mod protocol_tests {
                 WireEdge { src: "b".to_string(), dst: "a".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         let response = handle_request(&manager, &mut session, Request::QueryEdges {
@@ -4617,6 +11476,7 @@ mod protocol_tests {
             direction: "incoming".to_string(),
             edge_types: None,
             limit: None,
+            metadata: None,
         }, &None);
 
         match response {
@@ -4635,9 +11495,9 @@ mod protocol_tests {
 
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "c".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("c".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "c".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("c".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4646,6 +11506,7 @@ mod protocol_tests {
                 WireEdge { src: "c".to_string(), dst: "a".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         // Query both directions with limit=1
@@ -4654,6 +11515,7 @@ mod protocol_tests {
             direction: "both".to_string(),
             edge_types: None,
             limit: Some(1),
+            metadata: None,
         }, &None);
 
         match response {
@@ -4664,6 +11526,46 @@ mod protocol_tests {
         }
     }
 
+    #[test]
+    fn test_query_edges_both_dedups_self_loop() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "qe_self_loop_test");
+
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: vec![
+                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
+            ],
+        }, &None);
+        handle_request(&manager, &mut session, Request::AddEdges {
+            edges: vec![
+                WireEdge { src: "a".to_string(), dst: "a".to_string(), edge_type: Some("SELF_REF".to_string()), metadata: None },
+                WireEdge { src: "a".to_string(), dst: "b".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
+            ],
+            skip_validation: true,
+            dedup_edges: false,
+        }, &None);
+
+        let query = |session: &mut ClientSession, limit| {
+            match handle_request(&manager, session, Request::QueryEdges {
+                id: "a".to_string(),
+                direction: "both".to_string(),
+                edge_types: None,
+                limit,
+                metadata: None,
+            }, &None) {
+                Response::Edges { edges } => edges,
+                other => panic!("Expected Edges response, got {:?}", other),
+            }
+        };
+
+        let edges = query(&mut session, None);
+        assert_eq!(edges.len(), 2, "Self-loop must be reported once: {:?}", edges);
+        assert_eq!(edges.iter().filter(|e| e.edge_type.as_deref() == Some("SELF_REF")).count(), 1);
+        assert_eq!(query(&mut session, Some(2)).len(), 2, "Limit applies after dedup");
+    }
+
     #[test]
     fn test_query_edges_with_type_filter() {
         let (_dir, manager) = setup_test_manager();
@@ -4672,8 +11574,8 @@ mod protocol_tests {
 
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4682,6 +11584,7 @@ mod protocol_tests {
                 WireEdge { src: "a".to_string(), dst: "b".to_string(), edge_type: Some("IMPORTS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         // Filter by CALLS only
@@ -4690,6 +11593,7 @@ mod protocol_tests {
             direction: "outgoing".to_string(),
             edge_types: Some(vec!["CALLS".to_string()]),
             limit: None,
+            metadata: None,
         }, &None);
 
         match response {
@@ -4714,10 +11618,10 @@ mod protocol_tests {
         // Create a graph: a.js -> target.js, b.js -> target.js
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "target".to_string(), node_type: Some("MODULE".to_string()), name: Some("target".to_string()), file: Some("target.js".to_string()), exported: true, metadata: None },
-                WireNode { semantic_id: None, id: "dep1".to_string(), node_type: Some("MODULE".to_string()), name: Some("dep1".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "dep2".to_string(), node_type: Some("MODULE".to_string()), name: Some("dep2".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "unrelated".to_string(), node_type: Some("MODULE".to_string()), name: Some("unrelated".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "target".to_string(), node_type: Some("MODULE".to_string()), name: Some("target".to_string()), file: Some("target.js".to_string()), exported: true, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "dep1".to_string(), node_type: Some("MODULE".to_string()), name: Some("dep1".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "dep2".to_string(), node_type: Some("MODULE".to_string()), name: Some("dep2".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "unrelated".to_string(), node_type: Some("MODULE".to_string()), name: Some("unrelated".to_string()), file: Some("c.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4726,6 +11630,7 @@ mod protocol_tests {
                 WireEdge { src: "dep2".to_string(), dst: "target".to_string(), edge_type: Some("IMPORTS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         let response = handle_request(&manager, &mut session, Request::FindDependentFiles {
@@ -4753,9 +11658,9 @@ mod protocol_tests {
 
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "target".to_string(), node_type: Some("MODULE".to_string()), name: Some("target".to_string()), file: Some("target.js".to_string()), exported: true, metadata: None },
-                WireNode { semantic_id: None, id: "importer".to_string(), node_type: Some("MODULE".to_string()), name: Some("importer".to_string()), file: Some("imp.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "caller".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("caller".to_string()), file: Some("call.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "target".to_string(), node_type: Some("MODULE".to_string()), name: Some("target".to_string()), file: Some("target.js".to_string()), exported: true, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "importer".to_string(), node_type: Some("MODULE".to_string()), name: Some("importer".to_string()), file: Some("imp.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "caller".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("caller".to_string()), file: Some("call.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -4764,6 +11669,7 @@ mod protocol_tests {
                 WireEdge { src: "caller".to_string(), dst: "target".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
 
         // Only find IMPORTS dependents
@@ -4791,7 +11697,7 @@ mod protocol_tests {
 
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "lonely".to_string(), node_type: Some("MODULE".to_string()), name: Some("lonely".to_string()), file: Some("lonely.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "lonely".to_string(), node_type: Some("MODULE".to_string()), name: Some("lonely".to_string()), file: Some("lonely.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
 
@@ -4843,6 +11749,134 @@ mod protocol_tests {
         }
     }
 
+    #[test]
+    fn test_compact_dry_run_after_heavy_deletes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "compact_dry_run");
+        add_n_nodes(&manager, &mut session, 200, "FUNCTION");
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+        for i in 0..180 {
+            handle_request(&manager, &mut session, Request::DeleteNode { id: format!("n{}", i) }, &None);
+        }
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+
+        let dry_run = |session: &mut ClientSession| {
+            match handle_request(&manager, session, Request::CompactDryRun, &None) {
+                Response::CompactionEstimate { shards_eligible, estimated_tombstones_removed, estimated_bytes_saved } => {
+                    (shards_eligible, estimated_tombstones_removed, estimated_bytes_saved)
+                }
+                other => panic!("Expected CompactionEstimate, got {:?}", other),
+            }
+        };
+        let (shards, tombstones, bytes) = dry_run(&mut session);
+        assert!(!shards.is_empty());
+        assert!(tombstones >= 180, "{}", tombstones);
+        assert!(bytes > 0);
+
+        // Nothing was compacted: the estimate is repeatable
+        assert_eq!(dry_run(&mut session), (shards, tombstones, bytes));
+        match handle_request(&manager, &mut session, Request::FindByType { node_type: "FUNCTION".to_string() }, &None) {
+            Response::Ids { ids } => assert_eq!(ids.len(), 20),
+            other => panic!("Expected Ids, got {:?}", other),
+        }
+
+        handle_request(&manager, &mut session, Request::Compact, &None);
+        assert_eq!(dry_run(&mut session), (vec![], 0, 0));
+    }
+
+    #[test]
+    fn test_compact_shard() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "compact_shard_test");
+        add_n_nodes(&manager, &mut session, 50, "FUNCTION");
+
+        let diags = match handle_request(&manager, &mut session, Request::GetStats, &None) {
+            Response::Stats { shard_diagnostics, .. } => shard_diagnostics,
+            _ => panic!("Expected Stats response"),
+        };
+        let target = diags.iter().max_by_key(|d| d.node_count).unwrap();
+
+        let response = handle_request(&manager, &mut session, Request::CompactShard {
+            shard_id: target.shard_id,
+        }, &None);
+        match response {
+            Response::ShardCompacted { ok, shard_id, nodes_merged, .. } => {
+                assert!(ok);
+                assert_eq!(shard_id, target.shard_id);
+                assert_eq!(nodes_merged, target.node_count as u64);
+            }
+            other => panic!("Expected ShardCompacted, got {:?}", other),
+        }
+
+        let response = handle_request(&manager, &mut session, Request::CompactShard {
+            shard_id: diags.len() as u16,
+        }, &None);
+        match response {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "SHARD_OUT_OF_RANGE"),
+            other => panic!("Expected SHARD_OUT_OF_RANGE, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_flush_shard_leaves_other_shards_buffered() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "flush_shard_test");
+        handle_request(&manager, &mut session, Request::AddNodes {
+            nodes: (0..40)
+                .map(|i| WireNode { file: Some(format!("dir{}/a.js", i)), ..txn_node(&format!("n{}", i)) })
+                .collect(),
+        }, &None);
+
+        let shard_stats = |session: &mut ClientSession| match handle_request(&manager, session, Request::GetShardStats, &None) {
+            Response::ShardStats { shards } => shards,
+            other => panic!("Expected ShardStats, got {:?}", other),
+        };
+        let before = shard_stats(&mut session);
+        assert!(before.iter().filter(|s| s.write_buffer_nodes > 0).count() > 1);
+        let target = before.iter().max_by_key(|s| s.write_buffer_nodes).unwrap().shard_id;
+
+        let response = handle_request(&manager, &mut session, Request::FlushShard { shard_id: target }, &None);
+        assert!(matches!(response, Response::Bool { value: true }), "got {:?}", response);
+
+        for (old, new) in before.iter().zip(shard_stats(&mut session)) {
+            if new.shard_id == target {
+                assert_eq!(new.write_buffer_nodes, 0);
+                assert_eq!(new.l0_node_segment_count, 1);
+            } else {
+                assert_eq!(new.write_buffer_nodes, old.write_buffer_nodes);
+                assert_eq!(new.l0_node_segment_count, 0);
+            }
+        }
+        assert_eq!(count(&manager, &mut session, Request::NodeCount), 40);
+
+        let response = handle_request(&manager, &mut session, Request::FlushShard { shard_id: target }, &None);
+        assert!(matches!(response, Response::Bool { value: false }), "got {:?}", response);
+        let response = handle_request(&manager, &mut session, Request::FlushShard { shard_id: before.len() as u16 }, &None);
+        assert!(matches!(response, Response::ErrorWithCode { ref code, .. } if code == "SHARD_OUT_OF_RANGE"), "got {:?}", response);
+    }
+
+    #[test]
+    fn test_reshard() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "reshard_test");
+        add_n_nodes(&manager, &mut session, 50, "FUNCTION");
+
+        let response = handle_request(&manager, &mut session, Request::Reshard { shard_count: 3 }, &None);
+        assert!(matches!(response, Response::Ok { ok: true }), "got {:?}", response);
+
+        match handle_request(&manager, &mut session, Request::GetStats, &None) {
+            Response::Stats { node_count, shard_diagnostics, .. } => {
+                assert_eq!(node_count, 50);
+                assert_eq!(shard_diagnostics.len(), 3);
+            }
+            other => panic!("Expected Stats response, got {:?}", other),
+        }
+    }
+
     // ============================================================================
     // Streaming (Protocol v3+)
     // ============================================================================
@@ -4862,6 +11896,7 @@ mod protocol_tests {
                     file: None,
                     exported: false,
                     metadata: None,
+                    deleted: false,
                 })
                 .collect();
             handle_request(manager, session, Request::AddNodes { nodes }, &None);
@@ -4876,6 +11911,7 @@ mod protocol_tests {
         let response = handle_request(&manager, &mut session, Request::Hello {
             protocol_version: Some(3),
             client_id: Some("streaming-test".to_string()),
+            heartbeat: false,
         }, &None);
 
         match response {
@@ -4906,6 +11942,7 @@ mod protocol_tests {
             file: None,
             exported: None,
             substring_match: false,
+            sorted: false,
             extra: HashMap::new(),
         };
 
@@ -4924,7 +11961,7 @@ mod protocol_tests {
     /// Helper: read a chunk frame from a UnixStream and return (nodes_count, done, chunk_index, request_id).
     /// Uses serde_json::Value to avoid needing Deserialize on ResponseEnvelope.
     fn read_chunk_frame(reader: &mut UnixStream) -> Option<(usize, bool, u32, Option<String>)> {
-        let msg = match read_message(reader) {
+        let msg = match read_message(reader, DEFAULT_MAX_MESSAGE_BYTES) {
             Ok(Some(msg)) => msg,
             Ok(None) => return None,
             Err(e) => panic!("Read error: {}", e),
@@ -4960,6 +11997,7 @@ mod protocol_tests {
             file: None,
             exported: None,
             substring_match: false,
+            sorted: false,
             extra: HashMap::new(),
         };
 
@@ -5030,6 +12068,7 @@ mod protocol_tests {
             file: None,
             exported: None,
             substring_match: false,
+            sorted: false,
             extra: HashMap::new(),
         };
 
@@ -5077,6 +12116,7 @@ mod protocol_tests {
             file: None,
             exported: None,
             substring_match: false,
+            sorted: false,
             extra: HashMap::new(),
         };
 
@@ -5100,6 +12140,7 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::Hello {
             protocol_version: Some(2),
             client_id: Some("old-client".to_string()),
+            heartbeat: false,
         }, &None);
 
         // Add nodes above streaming threshold
@@ -5113,6 +12154,7 @@ mod protocol_tests {
                 file: None,
                 exported: None,
                 substring_match: false,
+                sorted: false,
                 extra: HashMap::new(),
             },
         }, &None);
@@ -5143,6 +12185,7 @@ mod protocol_tests {
             file: None,
             exported: None,
             substring_match: false,
+            sorted: false,
             extra: HashMap::new(),
         };
 
@@ -5198,6 +12241,7 @@ mod protocol_tests {
                     file: Some("mod_a.js".to_string()),
                     exported: false,
                     metadata: None,
+                    deleted: false,
                 },
                 WireNode {
                     semantic_id: None,
@@ -5207,6 +12251,7 @@ mod protocol_tests {
                     file: Some("mod_a.js".to_string()),
                     exported: true,
                     metadata: None,
+                    deleted: false,
                 },
             ],
             edges: vec![],
@@ -5214,11 +12259,14 @@ mod protocol_tests {
             file_context: None,
             defer_index: true,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Verify: CommitBatch succeeds with correct delta
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_added, 2, "Should report 2 nodes added");
                 assert_eq!(delta.changed_files, vec!["mod_a.js".to_string()]);
@@ -5230,7 +12278,7 @@ mod protocol_tests {
         let rebuild_response = handle_request(
             &manager,
             &mut session,
-            Request::RebuildIndexes,
+            Request::RebuildIndexes { node_types: None, edge_types: None },
             &None,
         );
         match rebuild_response {
@@ -5283,6 +12331,7 @@ mod protocol_tests {
                     file: Some("app.js".to_string()),
                     exported: false,
                     metadata: None,
+                    deleted: false,
                 },
             ],
             edges: vec![],
@@ -5290,10 +12339,13 @@ mod protocol_tests {
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_added, 1);
             }
@@ -5327,22 +12379,25 @@ mod protocol_tests {
         handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["a.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "m1".to_string(), node_type: Some("MODULE".to_string()), name: Some("modA".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "f1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("funcA".to_string()), file: Some("a.js".to_string()), exported: true, metadata: None },
+                WireNode { semantic_id: None, id: "m1".to_string(), node_type: Some("MODULE".to_string()), name: Some("modA".to_string()), file: Some("a.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "f1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("funcA".to_string()), file: Some("a.js".to_string()), exported: true, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: true,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Second deferred commit
         handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["b.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "m2".to_string(), node_type: Some("MODULE".to_string()), name: Some("modB".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "f2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("funcB".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "m2".to_string(), node_type: Some("MODULE".to_string()), name: Some("modB".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "f2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("funcB".to_string()), file: Some("b.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![
                 WireEdge { src: "f1".to_string(), dst: "f2".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
@@ -5351,23 +12406,29 @@ mod protocol_tests {
             file_context: None,
             defer_index: true,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Third deferred commit
         handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["c.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "c1".to_string(), node_type: Some("CLASS".to_string()), name: Some("MyClass".to_string()), file: Some("c.js".to_string()), exported: true, metadata: None },
+                WireNode { semantic_id: None, id: "c1".to_string(), node_type: Some("CLASS".to_string()), name: Some("MyClass".to_string()), file: Some("c.js".to_string()), exported: true, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: true,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Rebuild
-        let rebuild = handle_request(&manager, &mut session, Request::RebuildIndexes, &None);
+        let rebuild = handle_request(&manager, &mut session, Request::RebuildIndexes { node_types: None, edge_types: None }, &None);
         match rebuild {
             Response::Ok { ok } => assert!(ok),
             _ => panic!("Expected Ok for RebuildIndexes"),
@@ -5399,64 +12460,414 @@ mod protocol_tests {
         }
     }
 
-    /// Test that RebuildIndexes on an empty database is a safe no-op.
+    /// FlushDataOnly leaves indexing (and, on v2, segment writes) to the
+    /// final RebuildIndexes; the data stays readable from write buffers.
+    #[test]
+    fn test_flush_data_only_then_rebuild_indexes() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "flush_data_only");
+
+        for round in 0..3 {
+            let nodes = (0..4).map(|i| txn_node(&format!("n{}_{}", round, i))).collect();
+            handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
+            let response = handle_request(&manager, &mut session, Request::FlushDataOnly, &None);
+            assert!(matches!(response, Response::Ok { ok: true }), "{:?}", response);
+        }
+
+        let shard_totals = |session: &mut ClientSession| match handle_request(&manager, session, Request::GetShardStats, &None) {
+            Response::ShardStats { shards } => (
+                shards.iter().map(|s| s.l0_node_segment_count).sum::<usize>(),
+                shards.iter().map(|s| s.write_buffer_nodes).sum::<usize>(),
+            ),
+            _ => panic!("Expected ShardStats response"),
+        };
+        assert_eq!(shard_totals(&mut session), (0, 12), "nothing indexed into segments yet");
+
+        let response = handle_request(&manager, &mut session, Request::RebuildIndexes { node_types: None, edge_types: None }, &None);
+        assert!(matches!(response, Response::Ok { ok: true }));
+        let (segments, buffered) = shard_totals(&mut session);
+        assert!(segments > 0);
+        assert_eq!(buffered, 0);
+
+        match handle_request(&manager, &mut session, Request::FindByType { node_type: "FUNCTION".to_string() }, &None) {
+            Response::Ids { ids } => assert_eq!(ids.len(), 12),
+            _ => panic!("Expected Ids response"),
+        }
+    }
+
+    /// Test that RebuildIndexes on an empty database is a safe no-op.
+    #[test]
+    fn test_rebuild_indexes_on_empty_graph() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "empty_rebuild_test");
+
+        // RebuildIndexes on empty database should succeed
+        let response = handle_request(&manager, &mut session, Request::RebuildIndexes { node_types: None, edge_types: None }, &None);
+        match response {
+            Response::Ok { ok } => assert!(ok, "RebuildIndexes on empty graph should succeed"),
+            _ => panic!("Expected Ok for RebuildIndexes on empty graph, got {:?}", response),
+        }
+    }
+
+    /// Test that RebuildIndexes is idempotent at the protocol level.
+    #[test]
+    fn test_rebuild_indexes_idempotent_protocol() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "idempotent_rebuild");
+
+        // Add data
+        handle_request(&manager, &mut session, Request::CommitBatch {
+            changed_files: vec!["x.js".to_string()],
+            nodes: vec![
+                WireNode { semantic_id: None, id: "x1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("f1".to_string()), file: Some("x.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "x2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("f2".to_string()), file: Some("x.js".to_string()), exported: false, metadata: None, deleted: false },
+            ],
+            edges: vec![],
+            tags: None,
+            file_context: None,
+            defer_index: true,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        }, &None);
+
+        // First rebuild
+        handle_request(&manager, &mut session, Request::RebuildIndexes { node_types: None, edge_types: None }, &None);
+        let find1 = handle_request(&manager, &mut session, Request::FindByType {
+            node_type: "FUNCTION".to_string(),
+        }, &None);
+        let count1 = match find1 {
+            Response::Ids { ids } => ids.len(),
+            _ => panic!("Expected Ids"),
+        };
+
+        // Second rebuild (should produce same results)
+        handle_request(&manager, &mut session, Request::RebuildIndexes { node_types: None, edge_types: None }, &None);
+        let find2 = handle_request(&manager, &mut session, Request::FindByType {
+            node_type: "FUNCTION".to_string(),
+        }, &None);
+        let count2 = match find2 {
+            Response::Ids { ids } => ids.len(),
+            _ => panic!("Expected Ids"),
+        };
+
+        assert_eq!(count1, count2, "RebuildIndexes should be idempotent: same result count after two rebuilds");
+        assert_eq!(count1, 2, "Should find 2 FUNCTIONs");
+    }
+
+    /// RebuildIndexes with edgeTypes after a deferred commit touching only
+    /// CALLS edges: queries see the new data and other types are unaffected.
+    #[test]
+    fn test_rebuild_indexes_for_changed_edge_types() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "partial_rebuild");
+
+        let node = |id: &str, node_type: &str, file: &str| WireNode {
+            semantic_id: None, id: id.to_string(), node_type: Some(node_type.to_string()),
+            name: Some(id.to_string()), file: Some(file.to_string()), exported: false, metadata: None,
+            deleted: false,
+        };
+        let commit = |files: &[&str], nodes: Vec<WireNode>, edges: Vec<WireEdge>| Request::CommitBatch {
+            changed_files: files.iter().map(|f| f.to_string()).collect(),
+            nodes,
+            edges,
+            tags: None,
+            file_context: None,
+            defer_index: true,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        };
+        handle_request(&manager, &mut session, commit(&["c.js"], vec![node("c1", "CLASS", "c.js")], vec![]), &None);
+        handle_request(&manager, &mut session, Request::RebuildIndexes { node_types: None, edge_types: None }, &None);
+
+        let delta = match handle_request(&manager, &mut session, commit(
+            &["f.js"],
+            vec![node("f1", "FUNCTION", "f.js"), node("f2", "FUNCTION", "f.js")],
+            vec![WireEdge { src: "f1".to_string(), dst: "f2".to_string(), edge_type: Some("CALLS".to_string()), metadata: None }],
+        ), &None) {
+            Response::BatchCommitted { delta, .. } => delta,
+            other => panic!("Expected BatchCommitted, got {:?}", other),
+        };
+        assert_eq!(delta.changed_edge_types, vec!["CALLS".to_string()]);
+
+        let request: Request = serde_json::from_str(r#"{"cmd":"rebuildIndexes","edgeTypes":["CALLS"]}"#).unwrap();
+        assert!(matches!(&request, Request::RebuildIndexes { node_types: None, edge_types: Some(t) } if *t == delta.changed_edge_types));
+        match handle_request(&manager, &mut session, request, &None) {
+            Response::Ok { ok } => assert!(ok),
+            other => panic!("Expected Ok for RebuildIndexes, got {:?}", other),
+        }
+
+        let find = |session: &mut ClientSession, node_type: &str| match handle_request(&manager, session, Request::FindByType {
+            node_type: node_type.to_string(),
+        }, &None) {
+            Response::Ids { ids } => ids.len(),
+            other => panic!("Expected Ids, got {:?}", other),
+        };
+        assert_eq!(find(&mut session, "FUNCTION"), 2);
+        assert_eq!(find(&mut session, "CLASS"), 1);
+        match handle_request(&manager, &mut session, Request::GetIncomingEdges {
+            id: "f2".to_string(), edge_types: None,
+        }, &None) {
+            Response::Edges { edges } => assert_eq!(edges.len(), 1),
+            other => panic!("Expected Edges, got {:?}", other),
+        }
+    }
+
+    /// RebuildIndexes with nodeTypes after a deferred commit touching only
+    /// FUNCTION nodes: FUNCTION queries see the new data, while the shard
+    /// buffering an unrelated CLASS node is left alone.
+    #[test]
+    fn test_rebuild_indexes_for_changed_node_types() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "partial_node_rebuild");
+
+        let node = |id: &str, node_type: &str, file: &str| WireNode {
+            semantic_id: None, id: id.to_string(), node_type: Some(node_type.to_string()),
+            name: Some(id.to_string()), file: Some(file.to_string()), exported: false, metadata: None,
+            deleted: false,
+        };
+        let commit = |files: &[&str], nodes: Vec<WireNode>, edges: Vec<WireEdge>| Request::CommitBatch {
+            changed_files: files.iter().map(|f| f.to_string()).collect(),
+            nodes,
+            edges,
+            tags: None,
+            file_context: None,
+            defer_index: true,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        };
+        handle_request(&manager, &mut session, commit(&["lib/c.js"], vec![node("c1", "CLASS", "lib/c.js")], vec![]), &None);
+        let delta = match handle_request(&manager, &mut session, commit(
+            &["src/f.js"],
+            vec![node("f1", "FUNCTION", "src/f.js"), node("f2", "FUNCTION", "src/f.js")],
+            vec![WireEdge { src: "f1".to_string(), dst: "f2".to_string(), edge_type: Some("CALLS".to_string()), metadata: None }],
+        ), &None) {
+            Response::BatchCommitted { delta, .. } => delta,
+            other => panic!("Expected BatchCommitted, got {:?}", other),
+        };
+        assert_eq!(delta.changed_node_types, vec!["FUNCTION".to_string()]);
+
+        let buffered = |session: &mut ClientSession| match handle_request(&manager, session, Request::GetShardStats, &None) {
+            Response::ShardStats { shards } => shards.iter().map(|s| s.write_buffer_nodes).sum::<usize>(),
+            other => panic!("Expected ShardStats, got {:?}", other),
+        };
+        assert_eq!(buffered(&mut session), 3);
+
+        let request: Request = serde_json::from_str(r#"{"cmd":"rebuildIndexes","nodeTypes":["FUNCTION"]}"#).unwrap();
+        assert!(matches!(&request, Request::RebuildIndexes { node_types: Some(t), edge_types: None } if *t == delta.changed_node_types));
+        match handle_request(&manager, &mut session, request, &None) {
+            Response::Ok { ok } => assert!(ok),
+            other => panic!("Expected Ok for RebuildIndexes, got {:?}", other),
+        }
+        assert_eq!(buffered(&mut session), 1, "the CLASS shard keeps its buffered node");
+
+        let find = |session: &mut ClientSession, node_type: &str| match handle_request(&manager, session, Request::FindByType {
+            node_type: node_type.to_string(),
+        }, &None) {
+            Response::Ids { ids } => ids.len(),
+            other => panic!("Expected Ids, got {:?}", other),
+        };
+        assert_eq!(find(&mut session, "FUNCTION"), 2);
+        assert_eq!(find(&mut session, "CLASS"), 1);
+        match handle_request(&manager, &mut session, Request::GetIncomingEdges {
+            id: "f2".to_string(), edge_types: None,
+        }, &None) {
+            Response::Edges { edges } => assert_eq!(edges.len(), 1),
+            other => panic!("Expected Edges, got {:?}", other),
+        }
+
+        let envelope: RequestEnvelope = serde_json::from_str(
+            r#"{"requestId":"r1","cmd":"rebuildIndexes","nodeTypes":["FUNCTION"]}"#,
+        ).unwrap();
+        assert!(matches!(envelope.request, Request::RebuildIndexes { node_types: Some(_), .. }));
+    }
+
+    #[test]
+    fn test_flush_and_commit_batch_accept_durability() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "durability");
+
+        let request: Request = serde_json::from_str(r#"{"cmd":"flush"}"#).unwrap();
+        assert!(matches!(request, Request::Flush { durability: None }));
+        let request: Request = serde_json::from_str(
+            r#"{"cmd":"commitBatch","changedFiles":["a.js"],"nodes":[],"edges":[],"durability":"async"}"#,
+        ).unwrap();
+        assert!(matches!(request, Request::CommitBatch { durability: Some(WireDurability::Async), .. }));
+        assert!(serde_json::from_str::<Request>(r#"{"cmd":"flush","durability":"eventually"}"#).is_err());
+
+        let mut node = txn_node("a.js:f");
+        node.file = Some("a.js".to_string());
+        match handle_request(&manager, &mut session, Request::CommitBatch {
+            changed_files: vec!["a.js".to_string()],
+            nodes: vec![node],
+            edges: vec![],
+            tags: None,
+            file_context: None,
+            defer_index: false,
+            protected_types: vec![],
+            durability: Some(WireDurability::Async),
+            check_rule_sets: false,
+            dedup_edges: false,
+        }, &None) {
+            Response::BatchCommitted { delta, .. } => assert_eq!(delta.nodes_added, 1),
+            other => panic!("Expected BatchCommitted, got {:?}", other),
+        }
+        match handle_request(&manager, &mut session, Request::Flush { durability: Some(WireDurability::Sync) }, &None) {
+            Response::Ok { ok } => assert!(ok),
+            other => panic!("Expected Ok, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_streaming_commit_matches_single_commit_batch() {
+        let (_dir, manager) = setup_test_manager();
+        let file_node = |id: &str| WireNode { file: Some("src/app.js".to_string()), ..txn_node(id) };
+        let nodes = |range: std::ops::Range<usize>| -> Vec<WireNode> {
+            range.map(|i| file_node(&format!("app{}", i))).collect()
+        };
+        let edges = || -> Vec<WireEdge> {
+            (1..6).map(|i| txn_edge(&format!("app{}", i - 1), &format!("app{}", i))).collect()
+        };
+        let changed_files = vec!["src/app.js".to_string()];
+
+        // The same initial state in both databases: one stale node to replace
+        let seed = |session: &mut ClientSession| {
+            handle_request(&manager, session, Request::CommitBatch {
+                changed_files: changed_files.clone(),
+                nodes: vec![file_node("stale")],
+                edges: vec![],
+                tags: None,
+                file_context: None,
+                defer_index: false,
+                protected_types: vec![],
+                durability: None,
+                check_rule_sets: false,
+                dedup_edges: false,
+            }, &None);
+        };
+
+        let mut single = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut single, "single");
+        seed(&mut single);
+        let single_delta = match handle_request(&manager, &mut single, Request::CommitBatch {
+            changed_files: changed_files.clone(),
+            nodes: nodes(0..6),
+            edges: edges(),
+            tags: None,
+            file_context: None,
+            defer_index: false,
+            protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
+        }, &None) {
+            Response::BatchCommitted { delta, .. } => delta,
+            other => panic!("Expected BatchCommitted, got {:?}", other),
+        };
+
+        let mut streamed = ClientSession::new(2);
+        setup_ephemeral_db(&manager, &mut streamed, "streamed");
+        seed(&mut streamed);
+        let response = handle_request(&manager, &mut streamed, Request::BeginStreamingCommit {
+            changed_files: changed_files.clone(),
+        }, &None);
+        assert!(matches!(response, Response::BatchStarted { ok: true, .. }), "{:?}", response);
+        for chunk in [0..2, 2..4, 4..6] {
+            let response = handle_request(&manager, &mut streamed, Request::StreamCommitNodes { nodes: nodes(chunk) }, &None);
+            assert!(matches!(response, Response::Ok { ok: true }), "{:?}", response);
+        }
+        handle_request(&manager, &mut streamed, Request::StreamCommitEdges { edges: edges() }, &None);
+        // Nothing is visible before the finish
+        assert!(matches!(
+            handle_request(&manager, &mut streamed, Request::NodeExists { id: "app0".to_string() }, &None),
+            Response::Bool { value: false }
+        ));
+        let streamed_delta = match handle_request(&manager, &mut streamed, Request::FinishStreamingCommit, &None) {
+            Response::BatchCommitted { delta, .. } => delta,
+            other => panic!("Expected BatchCommitted, got {:?}", other),
+        };
+
+        assert_eq!(
+            (streamed_delta.nodes_added, streamed_delta.nodes_removed, streamed_delta.edges_added),
+            (single_delta.nodes_added, single_delta.nodes_removed, single_delta.edges_added),
+        );
+        assert_eq!((streamed_delta.nodes_added, streamed_delta.nodes_removed), (6, 1));
+        for session in [&mut single, &mut streamed] {
+            let ids = match handle_request(&manager, session, Request::FindByType { node_type: "FUNCTION".to_string() }, &None) {
+                Response::Ids { mut ids } => { ids.sort(); ids }
+                other => panic!("Expected Ids, got {:?}", other),
+            };
+            assert_eq!(ids.len(), 6);
+            match handle_request(&manager, session, Request::EdgeCount, &None) {
+                Response::Count { count, .. } => assert_eq!(count, 5),
+                other => panic!("Expected Count, got {:?}", other),
+            }
+        }
+        assert!(matches!(
+            handle_request(&manager, &mut streamed, Request::FinishStreamingCommit, &None),
+            Response::Error { .. }
+        ));
+    }
+
     #[test]
-    fn test_rebuild_indexes_on_empty_graph() {
+    fn test_streaming_commit_over_byte_limit_is_aborted() {
         let (_dir, manager) = setup_test_manager();
         let mut session = ClientSession::new(1);
-        setup_ephemeral_db(&manager, &mut session, "empty_rebuild_test");
+        setup_ephemeral_db(&manager, &mut session, "bounded");
+        session.max_stream_bytes = 512;
 
-        // RebuildIndexes on empty database should succeed
-        let response = handle_request(&manager, &mut session, Request::RebuildIndexes, &None);
-        match response {
-            Response::Ok { ok } => assert!(ok, "RebuildIndexes on empty graph should succeed"),
-            _ => panic!("Expected Ok for RebuildIndexes on empty graph, got {:?}", response),
+        handle_request(&manager, &mut session, Request::BeginStreamingCommit { changed_files: vec![] }, &None);
+        let nodes: Vec<WireNode> = (0..20).map(|i| txn_node(&format!("n{}", i))).collect();
+        match handle_request(&manager, &mut session, Request::StreamCommitNodes { nodes }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "STREAM_TOO_LARGE"),
+            other => panic!("Expected STREAM_TOO_LARGE, got {:?}", other),
         }
+        assert!(session.pending_stream.is_none());
     }
 
-    /// Test that RebuildIndexes is idempotent at the protocol level.
     #[test]
-    fn test_rebuild_indexes_idempotent_protocol() {
+    fn test_refused_streaming_commit_stays_open() {
         let (_dir, manager) = setup_test_manager();
         let mut session = ClientSession::new(1);
-        setup_ephemeral_db(&manager, &mut session, "idempotent_rebuild");
-
-        // Add data
-        handle_request(&manager, &mut session, Request::CommitBatch {
-            changed_files: vec!["x.js".to_string()],
-            nodes: vec![
-                WireNode { semantic_id: None, id: "x1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("f1".to_string()), file: Some("x.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "x2".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("f2".to_string()), file: Some("x.js".to_string()), exported: false, metadata: None },
-            ],
-            edges: vec![],
-            tags: None,
-            file_context: None,
-            defer_index: true,
-            protected_types: vec![],
-        }, &None);
-
-        // First rebuild
-        handle_request(&manager, &mut session, Request::RebuildIndexes, &None);
-        let find1 = handle_request(&manager, &mut session, Request::FindByType {
+        setup_ephemeral_db(&manager, &mut session, "refused_stream");
+        handle_request(&manager, &mut session, Request::SetMetadataSchema {
             node_type: "FUNCTION".to_string(),
+            required_keys: vec!["async".to_string()],
+            allowed_keys: None,
         }, &None);
-        let count1 = match find1 {
-            Response::Ids { ids } => ids.len(),
-            _ => panic!("Expected Ids"),
-        };
 
-        // Second rebuild (should produce same results)
-        handle_request(&manager, &mut session, Request::RebuildIndexes, &None);
-        let find2 = handle_request(&manager, &mut session, Request::FindByType {
-            node_type: "FUNCTION".to_string(),
-        }, &None);
-        let count2 = match find2 {
-            Response::Ids { ids } => ids.len(),
-            _ => panic!("Expected Ids"),
-        };
+        handle_request(&manager, &mut session, Request::BeginStreamingCommit { changed_files: vec![] }, &None);
+        handle_request(&manager, &mut session, Request::StreamCommitNodes { nodes: vec![txn_node("a")] }, &None);
 
-        assert_eq!(count1, count2, "RebuildIndexes should be idempotent: same result count after two rebuilds");
-        assert_eq!(count1, 2, "Should find 2 FUNCTIONs");
+        // Refused before the stream is taken
+        session.access_mode = AccessMode::ReadOnly;
+        match handle_request(&manager, &mut session, Request::FinishStreamingCommit, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "READ_ONLY_MODE"),
+            other => panic!("Expected READ_ONLY_MODE, got {:?}", other),
+        }
+        assert!(session.pending_stream.is_some());
+
+        // Refused by validation under the write lock: put back
+        session.access_mode = AccessMode::ReadWrite;
+        match handle_request(&manager, &mut session, Request::FinishStreamingCommit, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "SCHEMA_VIOLATION"),
+            other => panic!("Expected SCHEMA_VIOLATION, got {:?}", other),
+        }
+        assert!(session.pending_stream.is_some());
+
+        handle_request(&manager, &mut session, Request::AbortBatch, &None);
+        assert!(session.pending_stream.is_none());
     }
 
     /// Test that V2 engine (used by DatabaseManager) does NOT flush to disk
@@ -5485,6 +12896,7 @@ mod protocol_tests {
                         file: Some(file),
                         exported: false,
                         metadata: None,
+                        deleted: false,
                     },
                 ],
                 edges: vec![],
@@ -5492,6 +12904,9 @@ mod protocol_tests {
                 file_context: None,
                 defer_index: true,
                 protected_types: vec![],
+                durability: None,
+                check_rule_sets: false,
+                dedup_edges: false,
             }, &None);
 
             match response {
@@ -5510,7 +12925,7 @@ mod protocol_tests {
         }
 
         // RebuildIndexes persists everything
-        let rebuild = handle_request(&manager, &mut session, Request::RebuildIndexes, &None);
+        let rebuild = handle_request(&manager, &mut session, Request::RebuildIndexes { node_types: None, edge_types: None }, &None);
         match rebuild {
             Response::Ok { ok } => assert!(ok),
             _ => panic!("Expected Ok for RebuildIndexes"),
@@ -5542,29 +12957,32 @@ mod protocol_tests {
         // INDEXING phase: create MODULE and FUNCTION nodes for "app.js"
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "mod1".to_string(), node_type: Some("MODULE".to_string()), name: Some("app".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "fn_old".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("oldFunc".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "mod1".to_string(), node_type: Some("MODULE".to_string()), name: Some("app".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "fn_old".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("oldFunc".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // ANALYSIS phase: commitBatch with protectedTypes: ["MODULE"]
         // Should delete FUNCTION (not protected), preserve MODULE (protected), add new FUNCTION
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["app.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "fn_new".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("newFunc".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "fn_new".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("newFunc".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: false,
             protected_types: vec!["MODULE".to_string()],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Verify delta: only 1 node removed (FUNCTION), MODULE was skipped
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_removed, 1, "Only old FUNCTION should be removed, MODULE is protected");
                 assert_eq!(delta.nodes_added, 1, "New FUNCTION should be added");
@@ -5597,28 +13015,31 @@ mod protocol_tests {
         // Create MODULE and FUNCTION nodes for "app.js"
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "mod1".to_string(), node_type: Some("MODULE".to_string()), name: Some("app".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "fn1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("func1".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "mod1".to_string(), node_type: Some("MODULE".to_string()), name: Some("app".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "fn1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("func1".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
         }, &None);
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // CommitBatch with empty protectedTypes (legacy behavior)
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["app.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "fn_new".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("newFunc".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
+                WireNode { semantic_id: None, id: "fn_new".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("newFunc".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
             ],
             edges: vec![],
             tags: None,
             file_context: None,
             defer_index: false,
             protected_types: vec![],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         // Both MODULE and FUNCTION should be deleted (legacy behavior)
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 assert_eq!(delta.nodes_removed, 2, "Both MODULE and FUNCTION should be removed with empty protectedTypes");
                 assert_eq!(delta.nodes_added, 1);
@@ -5648,9 +13069,9 @@ mod protocol_tests {
         // and a SERVICE node with CONTAINS edge to MODULE
         handle_request(&manager, &mut session, Request::AddNodes {
             nodes: vec![
-                WireNode { semantic_id: None, id: "svc1".to_string(), node_type: Some("SERVICE".to_string()), name: Some("myService".to_string()), file: Some("service.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "mod1".to_string(), node_type: Some("MODULE".to_string()), name: Some("app".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None },
-                WireNode { semantic_id: None, id: "fn1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("handler".to_string()), file: Some("app.js".to_string()), exported: true, metadata: None },
+                WireNode { semantic_id: None, id: "svc1".to_string(), node_type: Some("SERVICE".to_string()), name: Some("myService".to_string()), file: Some("service.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "mod1".to_string(), node_type: Some("MODULE".to_string()), name: Some("app".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "fn1".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("handler".to_string()), file: Some("app.js".to_string()), exported: true, metadata: None, deleted: false },
             ],
         }, &None);
         handle_request(&manager, &mut session, Request::AddEdges {
@@ -5661,14 +13082,15 @@ mod protocol_tests {
                 WireEdge { src: "mod1".to_string(), dst: "fn1".to_string(), edge_type: Some("CONTAINS".to_string()), metadata: None },
             ],
             skip_validation: true,
+            dedup_edges: false,
         }, &None);
-        handle_request(&manager, &mut session, Request::Flush, &None);
+        handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
 
         // ANALYSIS commitBatch: replace FUNCTION nodes for "app.js", protect MODULE
         let response = handle_request(&manager, &mut session, Request::CommitBatch {
             changed_files: vec!["app.js".to_string()],
             nodes: vec![
-                WireNode { semantic_id: None, id: "fn_new".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("newHandler".to_string()), file: Some("app.js".to_string()), exported: true, metadata: None },
+                WireNode { semantic_id: None, id: "fn_new".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("newHandler".to_string()), file: Some("app.js".to_string()), exported: true, metadata: None, deleted: false },
             ],
             edges: vec![
                 // Re-create MODULE -> new FUNCTION edge
@@ -5678,10 +13100,13 @@ mod protocol_tests {
             file_context: None,
             defer_index: false,
             protected_types: vec!["MODULE".to_string()],
+            durability: None,
+            check_rule_sets: false,
+            dedup_edges: false,
         }, &None);
 
         match response {
-            Response::BatchCommitted { ok, delta } => {
+            Response::BatchCommitted { ok, delta, .. } => {
                 assert!(ok);
                 // Only fn1 deleted, mod1 preserved
                 assert_eq!(delta.nodes_removed, 1, "Only FUNCTION should be removed");
@@ -5726,4 +13151,668 @@ mod protocol_tests {
             _ => panic!("Expected Edges response"),
         }
     }
+
+    // ============================================================================
+    // Export Commands
+    // ============================================================================
+
+    /// Helper: small graph fn_a -CALLS-> fn_b, mod -CONTAINS-> fn_a.
+    fn add_export_fixture(manager: &Arc<DatabaseManager>, session: &mut ClientSession) {
+        handle_request(manager, session, Request::AddNodes {
+            nodes: vec![
+                WireNode { semantic_id: None, id: "fn_a".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("a".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "fn_b".to_string(), node_type: Some("FUNCTION".to_string()), name: Some("b".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
+                WireNode { semantic_id: None, id: "mod".to_string(), node_type: Some("MODULE".to_string()), name: Some("app".to_string()), file: Some("app.js".to_string()), exported: false, metadata: None, deleted: false },
+            ],
+        }, &None);
+        handle_request(manager, session, Request::AddEdges {
+            edges: vec![
+                WireEdge { src: "fn_a".to_string(), dst: "fn_b".to_string(), edge_type: Some("CALLS".to_string()), metadata: None },
+                WireEdge { src: "mod".to_string(), dst: "fn_a".to_string(), edge_type: Some("CONTAINS".to_string()), metadata: None },
+            ],
+            skip_validation: true,
+            dedup_edges: false,
+        }, &None);
+    }
+
+    #[test]
+    fn test_import_jsonl_nodes_and_edges_skips_malformed() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "import_jsonl");
+
+        let nodes = r#"{"id":"fn_a","nodeType":"FUNCTION","name":"a","file":"app.js"}
+{"id":"fn_b","nodeType":"FUNCTION","name":"b","file":"app.js","exported":true}
+{"nodeType":"FUNCTION"}
+garbage
+"#;
+        match handle_request(&manager, &mut session, Request::ImportJsonl {
+            content: nodes.to_string(),
+            kind: "nodes".to_string(),
+        }, &None) {
+            Response::Count { count, errors } => {
+                assert_eq!(count, 2);
+                assert_eq!(errors, Some(2));
+            }
+            other => panic!("Expected Count response, got {:?}", other),
+        }
+
+        // An edge to a node that does not exist rejects the whole import
+        let dangling = "{\"src\":\"fn_a\",\"dst\":\"fn_b\",\"edgeType\":\"CALLS\"}\n{\"src\":\"fn_a\",\"dst\":\"fn_missing\",\"edgeType\":\"CALLS\"}\n";
+        match handle_request(&manager, &mut session, Request::ImportJsonl {
+            content: dangling.to_string(),
+            kind: "edges".to_string(),
+        }, &None) {
+            Response::ErrorWithCode { code, .. } => assert_eq!(code, "DANGLING_EDGE"),
+            other => panic!("Expected DANGLING_EDGE, got {:?}", other),
+        }
+        match handle_request(&manager, &mut session, Request::EdgeCount, &None) {
+            Response::Count { count, .. } => assert_eq!(count, 0),
+            other => panic!("Expected Count response, got {:?}", other),
+        }
+
+        let edges = "{\"src\":\"fn_a\",\"dst\":\"fn_b\",\"edgeType\":\"CALLS\"}\n{\"src\":1}\n";
+        match handle_request(&manager, &mut session, Request::ImportJsonl {
+            content: edges.to_string(),
+            kind: "edges".to_string(),
+        }, &None) {
+            Response::Count { count, errors } => {
+                assert_eq!(count, 1);
+                assert_eq!(errors, Some(1));
+            }
+            other => panic!("Expected Count response, got {:?}", other),
+        }
+
+        match handle_request(&manager, &mut session, Request::NodeCount, &None) {
+            Response::Count { count, .. } => assert_eq!(count, 2),
+            other => panic!("Expected Count response, got {:?}", other),
+        }
+        match handle_request(&manager, &mut session, Request::Neighbors {
+            id: "fn_a".to_string(),
+            edge_types: vec!["CALLS".to_string()],
+        }, &None) {
+            Response::Ids { ids } => assert_eq!(ids, vec![id_to_string(string_to_id("fn_b"))]),
+            other => panic!("Expected Ids response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_import_jsonl_unknown_kind() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "import_bad_kind");
+
+        let response = handle_request(&manager, &mut session, Request::ImportJsonl {
+            content: String::new(),
+            kind: "widgets".to_string(),
+        }, &None);
+        assert!(matches!(response, Response::Error { ref error } if error.contains("widgets")));
+    }
+
+    #[test]
+    fn test_export_graphml_whole_graph_and_subgraph() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "export_graphml");
+        add_export_fixture(&manager, &mut session);
+
+        match handle_request(&manager, &mut session, Request::ExportGraphML { node_ids: None }, &None) {
+            Response::Export { format, content } => {
+                assert_eq!(format, "graphml");
+                assert_eq!(content.matches("<node ").count(), 3);
+                assert_eq!(content.matches("<edge ").count(), 2);
+            }
+            other => panic!("Expected Export response, got {:?}", other),
+        }
+
+        let response = handle_request(&manager, &mut session, Request::ExportGraphML {
+            node_ids: Some(vec!["fn_a".to_string(), "fn_b".to_string()]),
+        }, &None);
+        match response {
+            Response::Export { content, .. } => {
+                assert_eq!(content.matches("<node ").count(), 2);
+                assert_eq!(content.matches("<edge ").count(), 1);
+                assert!(content.contains("<data key=\"edge_type\">CALLS</data>"));
+                assert!(!content.contains("CONTAINS"));
+            }
+            other => panic!("Expected Export response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_export_dot_with_edge_type_filter() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "export_dot");
+        add_export_fixture(&manager, &mut session);
+
+        let response = handle_request(&manager, &mut session, Request::ExportDot {
+            node_ids: None,
+            edge_types: Some(vec!["CALLS".to_string()]),
+        }, &None);
+        match response {
+            Response::Export { format, content } => {
+                assert_eq!(format, "dot");
+                assert!(content.starts_with("digraph G {"));
+                assert_eq!(content.matches("shape=").count(), 3);
+                assert_eq!(content.matches(" -> ").count(), 1);
+                assert!(content.contains("[label=\"CALLS\"]"));
+            }
+            other => panic!("Expected Export response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_export_csv_nodes_and_edges() {
+        let (_dir, manager) = setup_test_manager();
+        let mut session = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut session, "export_csv");
+        add_export_fixture(&manager, &mut session);
+
+        match handle_request(&manager, &mut session, Request::ExportCsv { target: "nodes".to_string() }, &None) {
+            Response::Export { format, content } => {
+                assert_eq!(format, "csv");
+                let lines: Vec<&str> = content.split("\r\n").filter(|l| !l.is_empty()).collect();
+                assert_eq!(lines[0], rfdb::export::csv::NODE_COLUMNS);
+                assert_eq!(lines.len(), 4);
+            }
+            other => panic!("Expected Export response, got {:?}", other),
+        }
+
+        match handle_request(&manager, &mut session, Request::ExportCsv { target: "edges".to_string() }, &None) {
+            Response::Export { content, .. } => {
+                assert!(content.starts_with(rfdb::export::csv::EDGE_COLUMNS));
+                assert!(content.contains(",CALLS,"));
+                assert!(content.contains(",CONTAINS,"));
+            }
+            other => panic!("Expected Export response, got {:?}", other),
+        }
+
+        let response = handle_request(&manager, &mut session, Request::ExportCsv { target: "files".to_string() }, &None);
+        assert!(matches!(response, Response::Error { .. }));
+    }
+
+    #[test]
+    fn test_export_import_database_round_trip() {
+        let (_dir, manager) = setup_test_manager();
+        let mut source = ClientSession::new(1);
+        setup_ephemeral_db(&manager, &mut source, "dump_src");
+        add_export_fixture(&manager, &mut source);
+        handle_request(&manager, &mut source, Request::DeclareFields {
+            fields: vec![WireFieldDecl { name: "async".to_string(), field_type: None, node_types: None, entity: None }],
+        }, &None);
+        handle_request(&manager, &mut source, Request::SetMetadataSchema {
+            node_type: "CLASS".to_string(),
+            required_keys: vec!["line".to_string()],
+            allowed_keys: None,
+        }, &None);
+        handle_request(&manager, &mut source, Request::SaveRuleSet {
+            name: "callers".to_string(),
+            source: "violation(X) :- edge(X, Y, \"CALLS\").".to_string(),
+        }, &None);
+
+        let dump = match handle_request(&manager, &mut source, Request::ExportDatabase, &None) {
+            Response::Dump { data } => data.0,
+            other => panic!("Expected Dump response, got {:?}", other),
+        };
+
+        let mut target = ClientSession::new(2);
+        setup_ephemeral_db(&manager, &mut target, "dump_dst");
+        let response = handle_request(&manager, &mut target, Request::ImportDatabase {
+            data: WireBytes(b"not a dump".to_vec()),
+            more: false,
+        }, &None);
+        assert!(matches!(response, Response::ErrorWithCode { ref code, .. } if code == "INVALID_DUMP"));
+
+        // Sent in two frames: nothing is applied until the last one
+        let (head, tail) = dump.split_at(dump.len() / 2);
+        let response = handle_request(&manager, &mut target, Request::ImportDatabase {
+            data: WireBytes(head.to_vec()),
+            more: true,
+        }, &None);
+        assert!(matches!(response, Response::Ok { .. }), "{:?}", response);
+        assert!(matches!(handle_request(&manager, &mut target, Request::NodeCount, &None), Response::Count { count: 0, .. }));
+        let target_db = manager.get_database("dump_dst").unwrap();
+        let version = |db: &Database| db.engine.read().unwrap().as_any().downcast_ref::<GraphEngineV2>().unwrap().current_version();
+        let version_before = version(&target_db);
+        match handle_request(&manager, &mut target, Request::ImportDatabase { data: WireBytes(tail.to_vec()), more: false }, &None) {
+            Response::DumpImported { nodes_imported, edges_imported } => {
+                assert_eq!((nodes_imported, edges_imported), (3, 2));
+            }
+            other => panic!("Expected DumpImported response, got {:?}", other),
+        }
+        assert!(target.pending_import.is_none());
+        assert!(matches!(handle_request(&manager, &mut target, Request::NodeCount, &None), Response::Count { count: 3, .. }));
+        assert!(matches!(handle_request(&manager, &mut target, Request::EdgeCount, &None), Response::Count { count: 2, .. }));
+        match handle_request(&manager, &mut target, Request::GetNode { id: "fn_a".to_string(), include_deleted: false }, &None) {
+            Response::Node { node: Some(node) } => {
+                assert_eq!(node.node_type.as_deref(), Some("FUNCTION"));
+                assert_eq!(node.name.as_deref(), Some("a"));
+                assert_eq!(node.file.as_deref(), Some("app.js"));
+            }
+            other => panic!("Expected node fn_a, got {:?}", other),
+        }
+
+        // Settings came along, and the import was flushed
+        assert!(version(&target_db) > version_before);
+        {
+            let engine = target_db.engine.read().unwrap();
+            let v2 = engine.as_any().downcast_ref::<GraphEngineV2>().unwrap();
+            assert_eq!(v2.declared_fields_ref().len(), 1);
+            assert!(v2.check_metadata_schema("CLASS", "{}").is_err());
+            assert!(v2.rule_set("callers").is_some());
+        }
+
+        let response = handle_request(&manager, &mut target, Request::ImportDatabase { data: WireBytes(dump), more: false }, &None);
+        assert!(matches!(response, Response::ErrorWithCode { ref code, .. } if code == "DATABASE_NOT_EMPTY"));
+    }
+
+    #[test]
+    fn test_wire_bytes_travel_as_msgpack_bin() {
+        let encoded = rmp_serde::to_vec_named(&Response::Dump { data: WireBytes(vec![0, 1, 255]) }).unwrap();
+        // fixmap(1), "data", bin8 of length 3
+        assert_eq!(encoded, [&[0x81, 0xa4][..], b"data", &[0xc4, 3, 0, 1, 255]].concat());
+
+        let request: Request = rmp_serde::from_slice(&rmp_serde::to_vec_named(&serde_json::json!({
+            "cmd": "importDatabase",
+            "data": [1, 2, 3],
+        })).unwrap()).unwrap();
+        assert!(matches!(request, Request::ImportDatabase { data, more: false } if data.0 == [1, 2, 3]));
+    }
+
+    #[test]
+    fn test_stream_export_content_chunks_reassemble() {
+        // Leading ASCII byte shifts the 2-byte chars so a chunk boundary
+        // falls mid-character and must be moved back.
+        let content = format!("a{}", "é".repeat(EXPORT_CHUNK_BYTES / 2));
+        let (mut writer, mut reader) = UnixStream::pair().unwrap();
+
+        let reader_thread = std::thread::spawn(move || {
+            let mut assembled = String::new();
+            let mut chunks = 0u32;
+            loop {
+                let msg = read_message(&mut reader, DEFAULT_MAX_MESSAGE_BYTES).unwrap().expect("stream ended early");
+                let value: serde_json::Value = rmp_serde::from_slice(&msg).unwrap();
+                assert_eq!(value["format"], "graphml");
+                assert_eq!(value["chunkIndex"].as_u64().unwrap() as u32, chunks);
+                assert_eq!(value["requestId"], "r1");
+                assert!(value["content"].as_str().unwrap().len() <= EXPORT_CHUNK_BYTES);
+                assembled.push_str(value["content"].as_str().unwrap());
+                chunks += 1;
+                if value["done"].as_bool().unwrap() {
+                    return (assembled, chunks);
+                }
+            }
+        });
+
+        let result = stream_chunks(export_chunks("graphml", &content), &Some("r1".to_string()), &mut writer);
+        assert!(matches!(result, HandleResult::Streamed));
+
+        let (assembled, chunks) = reader_thread.join().unwrap();
+        assert_eq!(assembled, content);
+        assert_eq!(chunks, 2);
+    }
+
+    #[test]
+    fn test_stream_dump_chunks_reassemble() {
+        let data: Vec<u8> = (0..EXPORT_CHUNK_BYTES * 2 + 7).map(|i| i as u8).collect();
+        let (mut writer, mut reader) = UnixStream::pair().unwrap();
+
+        let reader_thread = std::thread::spawn(move || {
+            let mut assembled = Vec::new();
+            let mut chunks = 0u32;
+            loop {
+                let msg = read_message(&mut reader, DEFAULT_MAX_MESSAGE_BYTES).unwrap().expect("stream ended early");
+                #[derive(Deserialize)]
+                struct Chunk { data: WireBytes, done: bool, #[serde(rename = "chunkIndex")] chunk_index: u32 }
+                let chunk: Chunk = rmp_serde::from_slice(&msg).unwrap();
+                assert_eq!(chunk.chunk_index, chunks);
+                assembled.extend(chunk.data.0);
+                chunks += 1;
+                if chunk.done {
+                    return (assembled, chunks);
+                }
+            }
+        });
+
+        let result = stream_chunks(dump_chunks(&data), &Some("r1".to_string()), &mut writer);
+        assert!(matches!(result, HandleResult::Streamed));
+
+        let (assembled, chunks) = reader_thread.join().unwrap();
+        assert_eq!(assembled, data);
+        assert_eq!(chunks, 3);
+    }
+
+    #[test]
+    fn test_websocket_large_export_is_chunked() {
+        let content = "x".repeat(EXPORT_STREAMING_THRESHOLD + 1);
+        let response = Response::Export { format: "graphml".to_string(), content: content.clone() };
+        assert!(large_response_chunks(&response, 2).is_none());
+        let small = Response::Export { format: "graphml".to_string(), content: "<graphml/>".to_string() };
+        assert!(large_response_chunks(&small, 3).is_none());
+
+        let chunks = large_response_chunks(&response, 3).expect("large export is chunked on v3");
+        let (tx, rx) = std::sync::mpsc::channel();
+        let sink = futures_util::sink::unfold(tx, |tx, message: Message| async move {
+            tx.send(message).unwrap();
+            Ok::<_, std::convert::Infallible>(tx)
+        });
+        let mut sink = std::pin::pin!(sink);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        assert!(runtime.block_on(send_ws_chunks(&mut sink, 1, chunks, &Some("r1".to_string()))));
+        let sent: Vec<Message> = rx.try_iter().collect();
+
+        let mut assembled = String::new();
+        for (i, message) in sent.iter().enumerate() {
+            let Message::Binary(bytes) = message else { panic!("Expected binary frame, got {:?}", message) };
+            let value: serde_json::Value = rmp_serde::from_slice(bytes).unwrap();
+            assert_eq!(value["requestId"], "r1");
+            assert_eq!(value["chunkIndex"].as_u64().unwrap() as usize, i);
+            assert_eq!(value["done"].as_bool().unwrap(), i + 1 == sent.len());
+            assembled.push_str(value["content"].as_str().unwrap());
+        }
+        assert_eq!(sent.len(), 5);
+        assert_eq!(assembled, content);
+    }
+
+    /// A client that sends requests but never reads responses: once the
+    /// socket buffer fills, the server's write times out and the handler
+    /// disconnects instead of blocking forever.
+    #[test]
+    fn test_write_timeout_disconnects_stalled_reader() {
+        let (_dir, manager) = setup_test_manager();
+        let (server, mut client) = UnixStream::pair().unwrap();
+        client.set_write_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            handle_client_unix(server, manager, 1, false, None, Duration::from_millis(100), None, DEFAULT_MAX_MESSAGE_BYTES, None);
+            let _ = done_tx.send(());
+        });
+
+        let ping = rmp_serde::to_vec_named(&serde_json::json!({ "cmd": "ping" })).unwrap();
+        for _ in 0..1_000_000 {
+            if write_message(&mut client, &ping).is_err() {
+                break;
+            }
+        }
+
+        done_rx.recv_timeout(Duration::from_secs(10))
+            .expect("handler should give up on a client that never reads");
+    }
+
+    /// Cumulative request bytes over `--max-conn-bytes` get QUOTA_EXCEEDED
+    /// and the connection is closed, even though every message is small.
+    #[test]
+    fn test_conn_byte_quota_disconnects_client() {
+        let (_dir, manager) = setup_test_manager();
+        let (server, mut client) = UnixStream::pair().unwrap();
+        let handler = std::thread::spawn(move || {
+            handle_client_unix(server, manager, 1, false, None, DEFAULT_WRITE_TIMEOUT, Some(4096), DEFAULT_MAX_MESSAGE_BYTES, None);
+        });
+
+        let ping = |id: usize| rmp_serde::to_vec_named(&serde_json::json!({
+            "cmd": "ping", "requestId": format!("r{}", id), "padding": "x".repeat(1000),
+        })).unwrap();
+        let mut responses = Vec::new();
+        for id in 0..10 {
+            write_message(&mut client, &ping(id)).unwrap();
+            let msg = read_message(&mut client, DEFAULT_MAX_MESSAGE_BYTES).unwrap().expect("response before close");
+            let response: serde_json::Value = rmp_serde::from_slice(&msg).unwrap();
+            let refused = response.get("code").is_some();
+            responses.push(response);
+            if refused {
+                break;
+            }
+        }
+
+        // ~1KB per message: the first few fit, the one crossing 4KB is refused
+        let last = responses.last().unwrap();
+        assert_eq!(last["code"], "QUOTA_EXCEEDED");
+        assert_eq!(last["requestId"], format!("r{}", responses.len() - 1));
+        assert!(responses.len() > 1 && responses.len() < 10);
+        assert!(responses[..responses.len() - 1].iter().all(|r| r.get("code").is_none()));
+
+        assert!(read_message(&mut client, DEFAULT_MAX_MESSAGE_BYTES).unwrap().is_none(), "connection should be closed");
+        handler.join().unwrap();
+    }
+
+    /// A frame over `--max-message-bytes` is refused before its payload is
+    /// read, and the connection is dropped.
+    #[test]
+    fn test_max_message_bytes_rejects_oversized_frame() {
+        let (mut writer, mut reader) = UnixStream::pair().unwrap();
+        write_message(&mut writer, &[0u8; 64]).unwrap();
+        write_message(&mut writer, &[0u8; 65]).unwrap();
+
+        assert_eq!(read_message(&mut reader, 64).unwrap().unwrap().len(), 64);
+        let err = read_message(&mut reader, 64).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("Message too large: 65 bytes (limit 64)"), "{}", err);
+
+        let (_dir, manager) = setup_test_manager();
+        let (server, mut client) = UnixStream::pair().unwrap();
+        let handler = std::thread::spawn(move || {
+            handle_client_unix(server, manager, 1, false, None, DEFAULT_WRITE_TIMEOUT, None, 64, None);
+        });
+        write_message(&mut client, &[0u8; 1024]).unwrap();
+        handler.join().unwrap();
+        // EOF, or a reset since the oversized payload was never read
+        assert!(!matches!(read_message(&mut client, DEFAULT_MAX_MESSAGE_BYTES), Ok(Some(_))), "connection should be closed");
+    }
+
+    /// With `--idle-timeout-secs`, a silent client that opted in gets a
+    /// heartbeat frame; answering keeps the connection, ignoring it closes
+    /// the connection.
+    #[test]
+    fn test_idle_client_gets_heartbeat_then_disconnected() {
+        let (_dir, manager) = setup_test_manager();
+        let (server, mut client) = UnixStream::pair().unwrap();
+        let handler = std::thread::spawn(move || {
+            handle_client_unix(server, manager, 1, false, None, DEFAULT_WRITE_TIMEOUT, None, DEFAULT_MAX_MESSAGE_BYTES, Some(Duration::from_millis(100)));
+        });
+        let read_frame = |client: &mut UnixStream| -> serde_json::Value {
+            let msg = read_message(client, DEFAULT_MAX_MESSAGE_BYTES).unwrap().expect("connection closed early");
+            rmp_serde::from_slice(&msg).unwrap()
+        };
+
+        let hello = rmp_serde::to_vec_named(&serde_json::json!({ "requestId": "h1", "cmd": "hello", "heartbeat": true })).unwrap();
+        write_message(&mut client, &hello).unwrap();
+        assert_eq!(read_frame(&mut client)["requestId"], "h1");
+
+        let heartbeat = read_frame(&mut client);
+        assert_eq!(heartbeat["requestId"], HEARTBEAT_REQUEST_ID);
+        assert_eq!(heartbeat["pong"], true);
+
+        // Answering the heartbeat keeps the connection alive
+        let ping = rmp_serde::to_vec_named(&serde_json::json!({ "requestId": "p1", "cmd": "ping" })).unwrap();
+        write_message(&mut client, &ping).unwrap();
+        let pong = read_frame(&mut client);
+        assert_eq!(pong["requestId"], "p1");
+
+        // Going silent again: one more heartbeat, then the server hangs up
+        assert_eq!(read_frame(&mut client)["requestId"], HEARTBEAT_REQUEST_ID);
+        handler.join().unwrap();
+        assert!(read_message(&mut client, DEFAULT_MAX_MESSAGE_BYTES).unwrap().is_none(), "connection should be closed");
+    }
+
+    /// Clients that did not ask for heartbeats never receive an unsolicited
+    /// frame and are not dropped for being idle.
+    #[test]
+    fn test_idle_client_without_heartbeat_opt_in_is_left_alone() {
+        let (_dir, manager) = setup_test_manager();
+        let (server, mut client) = UnixStream::pair().unwrap();
+        let handler = std::thread::spawn(move || {
+            handle_client_unix(server, manager, 1, false, None, DEFAULT_WRITE_TIMEOUT, None, DEFAULT_MAX_MESSAGE_BYTES, Some(Duration::from_millis(50)));
+        });
+
+        client.set_read_timeout(Some(Duration::from_millis(300))).unwrap();
+        let err = read_message(&mut client, DEFAULT_MAX_MESSAGE_BYTES).expect_err("no frame expected");
+        assert!(matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut), "{:?}", err);
+
+        let ping = rmp_serde::to_vec_named(&serde_json::json!({ "requestId": "p1", "cmd": "ping" })).unwrap();
+        write_message(&mut client, &ping).unwrap();
+        let msg = read_message(&mut client, DEFAULT_MAX_MESSAGE_BYTES).unwrap().expect("connection closed early");
+        let pong: serde_json::Value = rmp_serde::from_slice(&msg).unwrap();
+        assert_eq!(pong["requestId"], "p1");
+
+        drop(client);
+        handler.join().unwrap();
+    }
+
+    // ============================================================================
+    // Logging
+    // ============================================================================
+
+    /// Shared buffer standing in for stderr.
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_log_format_emits_parseable_lines() {
+        assert_eq!(LogFormat::parse("json"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse("text"), Some(LogFormat::Text));
+        assert_eq!(LogFormat::parse("xml"), None);
+
+        let buffer = LogBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = log_subscriber(LogFormat::Json, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(client_id = 7usize, operation = "FindByType", duration_ms = 1500u64, "Slow request");
+            tracing::debug!("below the INFO threshold");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1, "{}", output);
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(event["level"], "WARN");
+        assert_eq!(event["message"], "Slow request");
+        assert_eq!(event["client_id"], 7);
+        assert_eq!(event["operation"], "FindByType");
+        assert_eq!(event["duration_ms"], 1500);
+    }
+
+    // ============================================================================
+    // Background Compaction
+    // ============================================================================
+
+    #[test]
+    fn test_auto_compactor_with_concurrent_writes() {
+        const WRITERS: usize = 3;
+        const ROUNDS: usize = 15;
+        const BATCH: usize = 8;
+
+        let (_dir, manager) = setup_test_manager();
+        let server_metrics = Arc::new(Metrics::new());
+        let done = Arc::new(AtomicBool::new(false));
+
+        let scheduler = {
+            let manager = Arc::clone(&manager);
+            let metrics = Some(Arc::clone(&server_metrics));
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                let mut compactor = AutoCompactor::new(manager, metrics);
+                while !done.load(Ordering::SeqCst) {
+                    for (_, outcome) in compactor.run_once() {
+                        assert!(!matches!(outcome, AutoCompactOutcome::Failed(_)), "{:?}", outcome);
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                compactor
+            })
+        };
+
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|w| {
+                let manager = Arc::clone(&manager);
+                std::thread::spawn(move || {
+                    let mut session = ClientSession::new(w + 1);
+                    handle_request(&manager, &mut session, Request::OpenDatabase {
+                        name: "default".to_string(),
+                        mode: "rw".to_string(),
+                    }, &None);
+                    for round in 0..ROUNDS {
+                        let nodes = (0..BATCH)
+                            .map(|i| WireNode {
+                                id: format!("w{}-r{}-n{}", w, round, i),
+                                node_type: Some("FUNCTION".to_string()),
+                                name: Some(format!("fn_{}_{}_{}", w, round, i)),
+                                file: Some(format!("src/w{}.js", w)),
+                                exported: false,
+                                metadata: None,
+                                semantic_id: None,
+                                deleted: false,
+                            })
+                            .collect();
+                        let added = handle_request(&manager, &mut session, Request::AddNodes { nodes }, &None);
+                        assert!(matches!(added, Response::Ok { .. }), "{:?}", added);
+                        let flushed = handle_request(&manager, &mut session, Request::Flush { durability: None }, &None);
+                        assert!(matches!(flushed, Response::Ok { .. }), "{:?}", flushed);
+                    }
+                    handle_close_database(&manager, &mut session);
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        done.store(true, Ordering::SeqCst);
+        let mut compactor = scheduler.join().unwrap();
+
+        // Writers are idle now: one quiet tick compacts whatever is left.
+        compactor.run_once();
+        compactor.run_once();
+
+        let db = manager.get_database("default").unwrap();
+        let expected = WRITERS * ROUNDS * BATCH;
+        assert_eq!(db.node_count(), expected);
+        {
+            let engine = db.engine.read().unwrap();
+            assert!(!engine.needs_compaction());
+            for w in 0..WRITERS {
+                for round in 0..ROUNDS {
+                    for i in 0..BATCH {
+                        let id = string_to_id(&format!("w{}-r{}-n{}", w, round, i));
+                        assert!(engine.get_node(id).is_some(), "lost node w{}-r{}-n{}", w, round, i);
+                    }
+                }
+            }
+        }
+
+        let compactions = db.metrics.snapshot().auto_compaction_count;
+        assert!(compactions > 0, "scheduler never compacted");
+        assert_eq!(server_metrics.snapshot().auto_compaction_count, compactions);
+    }
+
+    #[test]
+    fn test_auto_compactor_skips_busy_database() {
+        let (_dir, manager) = setup_test_manager();
+        let db = manager.get_database("default").unwrap();
+        let mut compactor = AutoCompactor::new(Arc::clone(&manager), None);
+
+        for _ in 0..=AUTO_COMPACT_BUSY_WRITES {
+            db.record_write();
+        }
+        let outcomes = compactor.run_once();
+        assert_eq!(outcomes, vec![("default".to_string(), AutoCompactOutcome::Busy)]);
+
+        // Write rate is measured per tick: no new writes means not busy.
+        let outcomes = compactor.run_once();
+        assert_eq!(outcomes, vec![("default".to_string(), AutoCompactOutcome::NotNeeded)]);
+
+        let _writer = db.engine.write().unwrap();
+        assert_eq!(compactor.run_once()[0].1, AutoCompactOutcome::Busy);
+    }
 }
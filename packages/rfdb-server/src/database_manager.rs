@@ -33,13 +33,16 @@
 //! manager.cleanup_ephemeral_if_unused("test-123");
 //! ```
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
 use crate::graph::{GraphEngineV2, GraphStore};
+use crate::graph::engine_v2::PendingCompaction;
 use crate::error::{GraphError, Result};
+use crate::metrics::Metrics;
+use crate::storage_v2::{ResourceManager, SystemResources};
 
 /// Try to acquire an advisory flock on `db_path/LOCK`.
 ///
@@ -126,6 +129,12 @@ pub struct Database {
     pub ephemeral: bool,
     /// Number of active connections to this database
     connection_count: AtomicUsize,
+    /// Write requests applied to this database since it was opened.
+    /// Lets the background compactor skip databases under heavy writes.
+    write_count: AtomicU64,
+    /// Query/flush metrics attributed to this database.
+    /// Only recorded when the server runs with `--metrics`.
+    pub metrics: Metrics,
     /// Advisory file lock on the database directory (Unix only).
     /// Held for the lifetime of the Database to prevent concurrent access
     /// from multiple server processes. Dropping releases the lock.
@@ -140,6 +149,8 @@ impl Database {
             engine: RwLock::new(engine),
             ephemeral,
             connection_count: AtomicUsize::new(0),
+            write_count: AtomicU64::new(0),
+            metrics: Metrics::new(),
             _lock: None,
         }
     }
@@ -151,10 +162,17 @@ impl Database {
             engine: RwLock::new(engine),
             ephemeral,
             connection_count: AtomicUsize::new(0),
+            write_count: AtomicU64::new(0),
+            metrics: Metrics::new(),
             _lock: lock,
         }
     }
 
+    /// Same entry under a new name (engine, counters and metrics kept)
+    fn renamed(self, name: String) -> Self {
+        Self { name, ..self }
+    }
+
     /// Increment connection count when client opens database
     pub fn add_connection(&self) {
         self.connection_count.fetch_add(1, Ordering::SeqCst);
@@ -175,6 +193,86 @@ impl Database {
         self.connection_count() > 0
     }
 
+    /// Count one write request against this database
+    pub fn record_write(&self) {
+        self.write_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Total write requests applied since the database was opened
+    pub fn write_count(&self) -> u64 {
+        self.write_count.load(Ordering::Relaxed)
+    }
+
+    /// Compact the engine without stalling readers for the whole merge.
+    ///
+    /// The write lock is held only to flush and, at the end, to swap the
+    /// merged segments in; the merge itself runs under the read lock, so
+    /// queries keep being served and always see either the old segments
+    /// or the new ones. If a flush or delete raced in between, the merge
+    /// is stale and compaction reruns under the write lock.
+    ///
+    /// This replaces the lock-free `arc_swap` view originally asked for:
+    /// `GraphEngineV2::prepare_compaction`/`install_compaction` split the
+    /// merge from the swap, so the existing `RwLock` gives the same
+    /// guarantee without a second snapshot mechanism.
+    pub fn compact(&self) -> Result<()> {
+        self.compact_with(true).expect("a waiting compaction always runs")
+    }
+
+    /// `compact` for background callers: every lock is taken with
+    /// `try_write`/`try_read`, so it never queues behind clients. Returns
+    /// None, leaving the segments as they were, if a lock was contended or
+    /// the merge went stale.
+    pub fn try_compact(&self) -> Option<Result<()>> {
+        self.compact_with(false)
+    }
+
+    fn compact_with(&self, wait: bool) -> Option<Result<()>> {
+        {
+            let mut engine = self.write_for_compaction(wait)?;
+            if engine.as_any().downcast_ref::<GraphEngineV2>().is_none() {
+                return Some(engine.compact());
+            }
+            if let Err(e) = engine.flush() {
+                return Some(Err(e));
+            }
+        }
+        let pending = match self.merge_for_compaction(wait)? {
+            Ok(Some(pending)) => pending,
+            Ok(None) => return Some(Ok(())),
+            Err(e) => return Some(Err(e)),
+        };
+        self.install_for_compaction(pending, wait)
+    }
+
+    fn write_for_compaction(&self, wait: bool) -> Option<RwLockWriteGuard<'_, Box<dyn GraphStore>>> {
+        if wait { Some(self.engine.write().unwrap()) } else { self.engine.try_write().ok() }
+    }
+
+    /// Merge step of `compact`, under the read lock. Ok(None) if the
+    /// engine is not v2.
+    fn merge_for_compaction(&self, wait: bool) -> Option<Result<Option<PendingCompaction>>> {
+        let engine = if wait { self.engine.read().unwrap() } else { self.engine.try_read().ok()? };
+        let v2 = engine.as_any().downcast_ref::<GraphEngineV2>();
+        Some(v2.map(GraphEngineV2::prepare_compaction).transpose())
+    }
+
+    /// Install step of `compact`, under the write lock. A stale merge is
+    /// redone in place when `wait`, else dropped.
+    fn install_for_compaction(&self, pending: PendingCompaction, wait: bool) -> Option<Result<()>> {
+        let mut engine = self.write_for_compaction(wait)?;
+        let installed = match engine.as_any_mut().downcast_mut::<GraphEngineV2>() {
+            Some(v2) => v2.install_compaction(pending),
+            None => Ok(false),
+        };
+        match installed {
+            Ok(true) => Some(Ok(())),
+            Ok(false) if wait => Some(engine.compact()),
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
     /// Get node count (for stats)
     ///
     /// Uses `unwrap_or_else` to handle poisoned locks gracefully.
@@ -196,6 +294,40 @@ impl Database {
             .unwrap_or_else(|e| e.into_inner())
             .edge_count()
     }
+
+    /// Name, counts and connection count
+    pub fn info(&self) -> DatabaseInfo {
+        DatabaseInfo {
+            name: self.name.clone(),
+            ephemeral: self.ephemeral,
+            node_count: self.node_count(),
+            edge_count: self.edge_count(),
+            connection_count: self.connection_count(),
+            write_version: self.write_version(),
+        }
+    }
+
+    /// Write version for CommitBatchIfUnchanged (None for v1 engines)
+    pub fn write_version(&self) -> Option<u64> {
+        self.engine
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_any()
+            .downcast_ref::<GraphEngineV2>()
+            .map(|v2| v2.write_version())
+    }
+}
+
+/// Claim on a database name held by `DatabaseManager::reserve`
+struct NameReservation<'a> {
+    reserved: &'a Mutex<HashSet<String>>,
+    name: String,
+}
+
+impl Drop for NameReservation<'_> {
+    fn drop(&mut self) {
+        self.reserved.lock().unwrap().remove(&self.name);
+    }
 }
 
 /// Database information for ListDatabases response
@@ -206,8 +338,12 @@ pub struct DatabaseInfo {
     pub node_count: usize,
     pub edge_count: usize,
     pub connection_count: usize,
+    pub write_version: Option<u64>,
 }
 
+/// Largest shard count a database may be created with
+pub const MAX_SHARD_COUNT: u16 = 256;
+
 /// DatabaseManager - manages multiple databases
 ///
 /// Thread-safe registry of open databases. Supports both persistent
@@ -215,8 +351,19 @@ pub struct DatabaseInfo {
 pub struct DatabaseManager {
     /// All open databases
     databases: RwLock<HashMap<String, Arc<Database>>>,
+    /// Names claimed by a copy or rename still moving files, which it does
+    /// without holding `databases`
+    reserved: Mutex<HashSet<String>>,
     /// Base path for persistent databases
     base_path: PathBuf,
+    /// Set once the server starts shutting down; writes are refused from then on
+    draining: AtomicBool,
+    /// Read replica mode: every database is opened ReadOnly
+    read_only: bool,
+    /// Refuse AddNodes batches whose IDs collide with a different semantic ID
+    strict_ids: bool,
+    /// Shard count for databases created without one (None: engine default)
+    default_shard_count: Option<u16>,
 }
 
 impl DatabaseManager {
@@ -227,10 +374,79 @@ impl DatabaseManager {
     pub fn new(base_path: PathBuf) -> Self {
         Self {
             databases: RwLock::new(HashMap::new()),
+            reserved: Mutex::new(HashSet::new()),
             base_path,
+            draining: AtomicBool::new(false),
+            read_only: false,
+            strict_ids: false,
+            default_shard_count: None,
         }
     }
 
+    /// Force every session to open databases ReadOnly, whatever mode the
+    /// client asks for
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Whether this manager serves databases read-only
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Reject, instead of only reporting, nodes whose u128 ID is already
+    /// held by a different semantic ID
+    pub fn with_strict_ids(mut self, strict_ids: bool) -> Self {
+        self.strict_ids = strict_ids;
+        self
+    }
+
+    /// Whether ID collisions are rejected
+    pub fn is_strict_ids(&self) -> bool {
+        self.strict_ids
+    }
+
+    /// Shard count used by `create_database` when the client gives none.
+    /// `None` keeps the engine default (`recommended_shard_count` on disk,
+    /// 4 in memory).
+    pub fn with_default_shard_count(mut self, shard_count: Option<u16>) -> Self {
+        self.default_shard_count = shard_count;
+        self
+    }
+
+    /// Shard count for databases created without an explicit one
+    pub fn default_shard_count(&self) -> Option<u16> {
+        self.default_shard_count
+    }
+
+    /// Check a requested shard count against `1..=MAX_SHARD_COUNT`
+    pub fn validate_shard_count(shard_count: u32) -> Result<u16> {
+        if shard_count == 0 || shard_count > MAX_SHARD_COUNT as u32 {
+            return Err(GraphError::InvalidShardCount(shard_count));
+        }
+        Ok(shard_count as u16)
+    }
+
+    /// Access mode a session actually gets when it asks for `requested`
+    pub fn effective_mode(&self, requested: AccessMode) -> AccessMode {
+        if self.read_only {
+            AccessMode::ReadOnly
+        } else {
+            requested
+        }
+    }
+
+    /// Mark the server as shutting down. Irreversible.
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the server is shutting down
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
     /// Validate database name
     ///
     /// Allowed characters: [a-zA-Z0-9_-]
@@ -255,26 +471,60 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Claim `name` for a database that is not in `databases` yet. Fails
+    /// with DatabaseExists if it is open or already claimed; the claim is
+    /// released when the returned guard drops.
+    fn reserve(&self, databases: &HashMap<String, Arc<Database>>, name: &str) -> Result<NameReservation<'_>> {
+        let mut reserved = self.reserved.lock().unwrap();
+        if databases.contains_key(name) || !reserved.insert(name.to_string()) {
+            return Err(GraphError::DatabaseExists(name.to_string()));
+        }
+        Ok(NameReservation { reserved: &self.reserved, name: name.to_string() })
+    }
+
     /// Create a new database
     ///
     /// # Arguments
     /// * `name` - Database name (alphanumeric, _, -)
     /// * `ephemeral` - If true, database is in-memory only and never persisted
     pub fn create_database(&self, name: &str, ephemeral: bool) -> Result<()> {
+        self.create_database_with_shards(name, ephemeral, None)
+    }
+
+    /// Create a new database spread over `shard_count` shards, falling back
+    /// to the manager's default shard count when `None`, then to
+    /// `ResourceManager::recommended_shard_count` for on-disk databases.
+    /// Ephemeral ones keep the fixed engine default.
+    pub fn create_database_with_shards(&self, name: &str, ephemeral: bool, shard_count: Option<u32>) -> Result<()> {
         Self::validate_name(name)?;
+        let shard_count = match shard_count {
+            Some(n) => Some(Self::validate_shard_count(n)?),
+            None => self.default_shard_count,
+        };
 
         let mut databases = self.databases.write().unwrap();
 
-        if databases.contains_key(name) {
+        if databases.contains_key(name) || self.reserved.lock().unwrap().contains(name) {
             return Err(GraphError::DatabaseExists(name.to_string()));
         }
 
         let (engine, lock): (Box<dyn GraphStore>, Option<std::fs::File>) = if ephemeral {
-            (Box::new(GraphEngineV2::create_ephemeral()), None)
+            let engine = match shard_count {
+                Some(n) => GraphEngineV2::create_ephemeral_with_shard_count(n),
+                None => GraphEngineV2::create_ephemeral(),
+            };
+            (Box::new(engine), None)
         } else {
             let db_path = self.base_path.join(format!("{}.rfdb", name));
             let lock = try_lock_db_dir(&db_path)?;
-            (Box::new(GraphEngineV2::create(&db_path)?), lock)
+            let engine = match shard_count {
+                Some(n) => GraphEngineV2::create_with_shard_count(&db_path, n)?,
+                None => {
+                    let recommended = ResourceManager::recommended_shard_count(&SystemResources::detect());
+                    GraphEngineV2::create_with_shard_count(&db_path, recommended)?
+                }
+            };
+            (Box::new(engine), lock)
         };
 
         let database = Arc::new(Database::new_with_lock(name.to_string(), engine, ephemeral, lock));
@@ -319,18 +569,180 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Rename a database (must not be in use)
+    ///
+    /// Ephemeral databases are rekeyed in place. Persistent databases are
+    /// taken out of the registry with both names reserved, flushed and
+    /// closed, their `<name>.rfdb` directory is renamed, and they are
+    /// reopened under the new name; the file work runs without holding the
+    /// registry lock. If reopening fails the directory is moved back and
+    /// the database reopened under its old name.
+    pub fn rename_database(&self, from: &str, to: &str) -> Result<()> {
+        Self::validate_name(to)?;
+
+        let old_path = self.base_path.join(format!("{}.rfdb", from));
+        let new_path = self.base_path.join(format!("{}.rfdb", to));
+        let (db, _reservations) = {
+            let mut databases = self.databases.write().unwrap();
+
+            let db = databases.get(from)
+                .ok_or_else(|| GraphError::DatabaseNotFound(from.to_string()))?;
+            if databases.contains_key(to) {
+                return Err(GraphError::DatabaseExists(to.to_string()));
+            }
+            if db.is_in_use() {
+                return Err(GraphError::DatabaseInUse(from.to_string()));
+            }
+            if !db.ephemeral {
+                if !old_path.exists() {
+                    // e.g. "default" opened from a legacy --db path
+                    return Err(GraphError::InvalidDatabaseName(format!(
+                        "'{}' is not stored in the database directory and cannot be renamed", from
+                    )));
+                }
+                if new_path.exists() {
+                    return Err(GraphError::DatabaseExists(to.to_string()));
+                }
+            }
+            let reserved_to = self.reserve(&databases, to)?;
+
+            // Other holders of the entry (e.g. the background compactor) keep
+            // the engine and its directory lock alive; treat them as users.
+            let db = databases.remove(from).unwrap();
+            let db = match Arc::try_unwrap(db) {
+                Ok(db) => db,
+                Err(db) => {
+                    databases.insert(from.to_string(), db);
+                    return Err(GraphError::DatabaseInUse(from.to_string()));
+                }
+            };
+
+            if db.ephemeral {
+                databases.insert(to.to_string(), Arc::new(db.renamed(to.to_string())));
+                return Ok(());
+            }
+            let reserved_from = self.reserve(&databases, from)?;
+            (db, (reserved_to, reserved_from))
+        };
+
+        let flushed = db.engine.write().unwrap().flush();
+        if let Err(e) = flushed {
+            self.databases.write().unwrap().insert(from.to_string(), Arc::new(db));
+            return Err(e);
+        }
+
+        // Release the engine and the LOCK before moving the directory
+        drop(db);
+        let reopened = std::fs::rename(&old_path, &new_path)
+            .map_err(GraphError::from)
+            .and_then(|()| Self::open_persistent(to, &new_path));
+        let (name, db, result) = match reopened {
+            Ok(db) => (to, db, Ok(())),
+            Err(e) => {
+                if new_path.exists() && !old_path.exists() {
+                    std::fs::rename(&new_path, &old_path)?;
+                }
+                (from, Self::open_persistent(from, &old_path)?, Err(e))
+            }
+        };
+        self.databases.write().unwrap().insert(name.to_string(), Arc::new(db));
+        result
+    }
+
+    /// Lock and open the on-disk database at `path` as `name`
+    fn open_persistent(name: &str, path: &std::path::Path) -> Result<Database> {
+        let lock = try_lock_db_dir(path)?;
+        let engine: Box<dyn GraphStore> = Box::new(GraphEngineV2::open(path)?);
+        Ok(Database::new_with_lock(name.to_string(), engine, false, lock))
+    }
+
+    /// Copy a database under a new name
+    ///
+    /// Persistent databases are flushed, then their `<name>.rfdb` directory
+    /// is copied while a read lock holds off writers (readers carry on).
+    /// Ephemeral databases get an in-memory deep copy. `to` is reserved
+    /// meanwhile, so other databases stay available during the copy; a
+    /// partial copy is removed on failure.
+    pub fn copy_database(&self, from: &str, to: &str) -> Result<()> {
+        Self::validate_name(to)?;
+
+        let (source, _reservation) = {
+            let databases = self.databases.read().unwrap();
+            let source = databases.get(from)
+                .cloned()
+                .ok_or_else(|| GraphError::DatabaseNotFound(from.to_string()))?;
+            (source, self.reserve(&databases, to)?)
+        };
+        // Counts as a user, so DropDatabase cannot delete it mid-copy
+        source.add_connection();
+        let copied = self.copy_engine(&source, to);
+        source.remove_connection();
+        let (engine, lock) = copied?;
+
+        let database = Database::new_with_lock(to.to_string(), engine, source.ephemeral, lock);
+        self.databases.write().unwrap().insert(to.to_string(), Arc::new(database));
+        Ok(())
+    }
+
+    /// Engine (and directory lock) for a copy of `source` named `to`
+    fn copy_engine(&self, source: &Database, to: &str) -> Result<(Box<dyn GraphStore>, Option<std::fs::File>)> {
+        let not_v2 = || GraphError::InvalidFormat("CopyDatabase requires v2 engine".to_string());
+        if source.ephemeral {
+            let engine = source.engine.read().unwrap();
+            let v2 = engine.as_any().downcast_ref::<GraphEngineV2>().ok_or_else(not_v2)?;
+            return Ok((Box::new(v2.copy_ephemeral()), None));
+        }
+
+        let new_path = self.base_path.join(format!("{}.rfdb", to));
+        if new_path.exists() {
+            return Err(GraphError::DatabaseExists(to.to_string()));
+        }
+        source.engine.write().unwrap().flush()?;
+        let copied = (|| {
+            {
+                let engine = source.engine.read().unwrap();
+                let v2 = engine.as_any().downcast_ref::<GraphEngineV2>().ok_or_else(not_v2)?;
+                v2.copy_to_dir(&new_path)?;
+            }
+            let lock = try_lock_db_dir(&new_path)?;
+            let engine: Box<dyn GraphStore> = Box::new(GraphEngineV2::open(&new_path)?);
+            Ok((engine, lock))
+        })();
+        if copied.is_err() {
+            let _ = std::fs::remove_dir_all(&new_path);
+        }
+        copied
+    }
+
     /// List all databases
     pub fn list_databases(&self) -> Vec<DatabaseInfo> {
         let databases = self.databases.read().unwrap();
-        databases.values()
-            .map(|db| DatabaseInfo {
-                name: db.name.clone(),
-                ephemeral: db.ephemeral,
-                node_count: db.node_count(),
-                edge_count: db.edge_count(),
-                connection_count: db.connection_count(),
-            })
-            .collect()
+        databases.values().map(|db| db.info()).collect()
+    }
+
+    /// Info for a single database, without opening it (connection count
+    /// is left untouched)
+    pub fn database_info(&self, name: &str) -> Result<DatabaseInfo> {
+        self.get_database(name).map(|db| db.info())
+    }
+
+    /// Snapshot of all open databases. Unlike `list_databases`, this does
+    /// not touch any engine lock.
+    pub fn databases(&self) -> Vec<Arc<Database>> {
+        self.databases.read().unwrap().values().cloned().collect()
+    }
+
+    /// Flush every open database, returning the names of those whose flush
+    /// failed along with the error
+    pub fn flush_all(&self) -> Vec<(String, GraphError)> {
+        let mut failures = Vec::new();
+        for db in self.databases() {
+            let mut engine = db.engine.write().unwrap();
+            if let Err(e) = engine.flush() {
+                failures.push((db.name.clone(), e));
+            }
+        }
+        failures
     }
 
     /// Cleanup ephemeral database if it has no connections
@@ -477,6 +889,111 @@ mod database_tests {
         assert_eq!(db.edge_count(), 0);
     }
 
+    /// The merge step of `Database::compact` shares the lock with readers:
+    /// it completes while another thread holds a read guard, and only the
+    /// install has to wait for that reader to leave.
+    #[test]
+    fn test_compact_does_not_stall_readers() {
+        use std::sync::mpsc;
+        use std::thread;
+
+        const FLUSHES: u128 = 4;
+        const PER_FLUSH: u128 = 1_000;
+
+        let mut engine = GraphEngineV2::create_ephemeral();
+        for batch in 0..FLUSHES {
+            engine.add_nodes((0..PER_FLUSH).map(|i| NodeRecord {
+                id: batch * PER_FLUSH + i + 1,
+                node_type: Some("FUNCTION".to_string()),
+                file_id: 0,
+                name_offset: 0,
+                version: "main".to_string(),
+                exported: false,
+                replaces: None,
+                deleted: false,
+                name: Some(format!("fn{}", i)),
+                file: Some(format!("src/f{}.js", i % 64)),
+                metadata: Some(format!(r#"{{"line":{}}}"#, i)),
+                semantic_id: None,
+            }).collect());
+            engine.flush().unwrap();
+        }
+        let expected = (FLUSHES * PER_FLUSH) as usize;
+        let db = Arc::new(Database::new("test".to_string(), Box::new(engine), true));
+        let l0_segments = |db: &Database| {
+            let engine = db.engine.read().unwrap();
+            engine.as_any().downcast_ref::<GraphEngineV2>().unwrap().segment_counts_by_level()[0]
+        };
+        let before = l0_segments(&db);
+
+        let (held_tx, held_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let reader = {
+            let db = Arc::clone(&db);
+            thread::spawn(move || {
+                let engine = db.engine.read().unwrap();
+                held_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+                engine.node_count()
+            })
+        };
+        held_rx.recv().unwrap();
+
+        // The reader's guard is held for the whole merge
+        let pending = db.merge_for_compaction(false)
+            .expect("the merge must not need exclusive access")
+            .unwrap()
+            .expect("v2 engine");
+        assert_eq!(l0_segments(&db), before);
+        assert!(db.install_for_compaction(pending, false).is_none());
+
+        release_tx.send(()).unwrap();
+        assert_eq!(reader.join().unwrap(), expected);
+
+        db.compact().unwrap();
+        let engine = db.engine.read().unwrap();
+        assert!(!engine.needs_compaction());
+        assert_eq!(engine.node_count(), expected);
+        drop(engine);
+        assert_eq!(l0_segments(&db), 0);
+    }
+
+    #[test]
+    fn test_try_compact_gives_up_on_contended_lock() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        for id in 1..=2 {
+            engine.add_nodes(vec![NodeRecord {
+                id,
+                node_type: Some("FUNCTION".to_string()),
+                file_id: 0,
+                name_offset: 0,
+                version: "main".to_string(),
+                exported: false,
+                replaces: None,
+                deleted: false,
+                name: Some(format!("fn{}", id)),
+                file: Some("src/a.js".to_string()),
+                metadata: None,
+                semantic_id: None,
+            }]);
+            engine.flush().unwrap();
+        }
+        let db = Database::new("test".to_string(), Box::new(engine), true);
+
+        let reader = db.engine.read().unwrap();
+        assert!(db.try_compact().is_none());
+        drop(reader);
+
+        let l0_segments = |db: &Database| {
+            let engine = db.engine.read().unwrap();
+            engine.as_any().downcast_ref::<GraphEngineV2>().unwrap().segment_counts_by_level()[0]
+        };
+        assert!(l0_segments(&db) > 0);
+        assert!(matches!(db.try_compact(), Some(Ok(()))));
+        assert_eq!(l0_segments(&db), 0);
+        assert_eq!(db.node_count(), 2);
+    }
+
     #[test]
     fn test_database_ephemeral_flag() {
         let engine: Box<dyn GraphStore> = Box::new(GraphEngineV2::create_ephemeral());
@@ -493,6 +1010,7 @@ mod database_tests {
 #[cfg(test)]
 mod manager_tests {
     use super::*;
+    use crate::storage::NodeRecord;
     use tempfile::tempdir;
 
     // ============================================================================
@@ -663,6 +1181,207 @@ mod manager_tests {
         assert!(matches!(result.unwrap_err(), GraphError::DatabaseNotFound(_)));
     }
 
+    // ============================================================================
+    // Database Renaming
+    // ============================================================================
+
+    #[test]
+    fn test_rename_persistent_database_keeps_data() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("before", false).unwrap();
+        {
+            let db = manager.get_database("before").unwrap();
+            let mut engine = db.engine.write().unwrap();
+            engine.add_nodes(vec![test_node(42)]);
+        }
+
+        manager.rename_database("before", "after").unwrap();
+
+        assert!(!manager.database_exists("before"));
+        assert!(!dir.path().join("before.rfdb").exists());
+        assert!(dir.path().join("after.rfdb").exists());
+        let db = manager.get_database("after").unwrap();
+        assert_eq!(db.name, "after");
+        assert!(db.engine.read().unwrap().node_exists(42));
+    }
+
+    #[test]
+    fn test_rename_database_moves_directory_back_when_reopen_fails() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("before", false).unwrap();
+        std::fs::write(dir.path().join("before.rfdb").join("db_config.json"), b"garbage").unwrap();
+
+        assert!(manager.rename_database("before", "after").is_err());
+        assert!(dir.path().join("before.rfdb").exists());
+        assert!(!dir.path().join("after.rfdb").exists());
+        // Neither name stays reserved
+        manager.create_database("after", true).unwrap();
+        manager.create_database("before", true).unwrap();
+    }
+
+    #[test]
+    fn test_rename_ephemeral_database() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("scratch", true).unwrap();
+        manager.rename_database("scratch", "scratch-2").unwrap();
+
+        let db = manager.get_database("scratch-2").unwrap();
+        assert!(db.ephemeral);
+        assert_eq!(db.name, "scratch-2");
+        assert!(!manager.database_exists("scratch"));
+        assert!(!dir.path().join("scratch-2.rfdb").exists());
+    }
+
+    #[test]
+    fn test_rename_database_errors() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("a", false).unwrap();
+        manager.create_database("b", true).unwrap();
+
+        assert!(matches!(manager.rename_database("missing", "c"), Err(GraphError::DatabaseNotFound(_))));
+        assert!(matches!(manager.rename_database("a", "b"), Err(GraphError::DatabaseExists(_))));
+        assert!(matches!(manager.rename_database("a", "bad name"), Err(GraphError::InvalidDatabaseName(_))));
+
+        let db = manager.get_database("a").unwrap();
+        db.add_connection();
+        assert!(matches!(manager.rename_database("a", "c"), Err(GraphError::DatabaseInUse(_))));
+        db.remove_connection();
+
+        // A lingering handle (no connection) still pins the entry
+        assert!(matches!(manager.rename_database("a", "c"), Err(GraphError::DatabaseInUse(_))));
+        drop(db);
+        manager.rename_database("a", "c").unwrap();
+
+        // An unregistered directory under the target name blocks the rename
+        std::fs::create_dir_all(dir.path().join("d.rfdb")).unwrap();
+        assert!(matches!(manager.rename_database("c", "d"), Err(GraphError::DatabaseExists(_))));
+        assert!(manager.database_exists("c"));
+    }
+
+    // ============================================================================
+    // Database Copying
+    // ============================================================================
+
+    fn test_node(id: u128) -> NodeRecord {
+        NodeRecord {
+            id,
+            node_type: Some("FUNCTION".to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".to_string(),
+            exported: false,
+            replaces: None,
+            deleted: false,
+            name: Some(format!("fn{}", id)),
+            file: None,
+            metadata: None,
+            semantic_id: None,
+        }
+    }
+
+    #[test]
+    fn test_copy_database_is_independent() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("original", false).unwrap();
+        let original = manager.get_database("original").unwrap();
+        original.engine.write().unwrap().add_nodes(vec![test_node(1), test_node(2)]);
+
+        manager.copy_database("original", "copy").unwrap();
+        assert!(dir.path().join("copy.rfdb").exists());
+
+        let copy = manager.get_database("copy").unwrap();
+        assert!(!copy.ephemeral);
+        {
+            let mut engine = copy.engine.write().unwrap();
+            assert_eq!(engine.node_count(), 2);
+            engine.delete_node(1);
+            engine.add_nodes(vec![test_node(3)]);
+            engine.flush().unwrap();
+        }
+
+        let engine = original.engine.read().unwrap();
+        assert!(engine.node_exists(1));
+        assert!(!engine.node_exists(3));
+        assert_eq!(engine.node_count(), 2);
+    }
+
+    #[test]
+    fn test_copy_ephemeral_database() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("scratch", true).unwrap();
+        let original = manager.get_database("scratch").unwrap();
+        original.engine.write().unwrap().add_nodes(vec![test_node(1)]);
+
+        manager.copy_database("scratch", "scratch-copy").unwrap();
+        let copy = manager.get_database("scratch-copy").unwrap();
+        assert!(copy.ephemeral);
+        assert!(!dir.path().join("scratch-copy.rfdb").exists());
+
+        copy.engine.write().unwrap().add_nodes(vec![test_node(2)]);
+        assert_eq!(copy.node_count(), 2);
+        assert_eq!(original.node_count(), 1);
+    }
+
+    #[test]
+    fn test_copy_database_errors() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("a", false).unwrap();
+        manager.create_database("b", true).unwrap();
+
+        assert!(matches!(manager.copy_database("missing", "c"), Err(GraphError::DatabaseNotFound(_))));
+        assert!(matches!(manager.copy_database("a", "b"), Err(GraphError::DatabaseExists(_))));
+
+        std::fs::create_dir_all(dir.path().join("c.rfdb")).unwrap();
+        assert!(matches!(manager.copy_database("a", "c"), Err(GraphError::DatabaseExists(_))));
+        assert!(!manager.database_exists("c"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_copy_removes_partial_directory() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+        manager.create_database("a", false).unwrap();
+        // A dangling symlink makes the file copy fail midway
+        std::os::unix::fs::symlink(dir.path().join("gone"), dir.path().join("a.rfdb").join("zz-dangling")).unwrap();
+
+        assert!(manager.copy_database("a", "b").is_err());
+        assert!(!dir.path().join("b.rfdb").exists());
+        assert!(!manager.database_exists("b"));
+        assert!(!manager.get_database("a").unwrap().is_in_use());
+        // The name was released with the failed copy
+        manager.create_database("b", true).unwrap();
+    }
+
+    #[test]
+    fn test_reserved_name_cannot_be_taken() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+        manager.create_database("a", true).unwrap();
+
+        let reservation = manager.reserve(&manager.databases.read().unwrap(), "b").unwrap();
+        assert!(matches!(manager.create_database("b", true), Err(GraphError::DatabaseExists(_))));
+        assert!(matches!(manager.copy_database("a", "b"), Err(GraphError::DatabaseExists(_))));
+        drop(reservation);
+
+        manager.copy_database("a", "b").unwrap();
+        assert!(manager.database_exists("b"));
+    }
+
     // ============================================================================
     // Database Listing
     // ============================================================================
@@ -714,6 +1433,20 @@ mod manager_tests {
         assert_eq!(info.edge_count, 0);
     }
 
+    #[test]
+    fn test_database_info_by_name() {
+        let dir = tempdir().unwrap();
+        let manager = DatabaseManager::new(dir.path().to_path_buf());
+
+        manager.create_database("testdb", true).unwrap();
+        manager.get_database("testdb").unwrap().add_connection();
+
+        let info = manager.database_info("testdb").unwrap();
+        assert_eq!(info.name, "testdb");
+        assert_eq!(info.connection_count, 1);
+        assert!(matches!(manager.database_info("missing"), Err(GraphError::DatabaseNotFound(_))));
+    }
+
     // ============================================================================
     // Ephemeral Database Cleanup (Linus Fix #2)
     // ============================================================================
@@ -805,6 +1538,16 @@ mod access_mode_tests {
         assert!(AccessMode::ReadWrite.is_write());
         assert!(!AccessMode::ReadOnly.is_write());
     }
+
+    #[test]
+    fn test_read_only_manager_downgrades_mode() {
+        let manager = DatabaseManager::new(PathBuf::from("/tmp")).with_read_only(true);
+        assert!(manager.is_read_only());
+        assert_eq!(manager.effective_mode(AccessMode::ReadWrite), AccessMode::ReadOnly);
+
+        let manager = DatabaseManager::new(PathBuf::from("/tmp"));
+        assert_eq!(manager.effective_mode(AccessMode::ReadWrite), AccessMode::ReadWrite);
+    }
 }
 
 // ============================================================================
@@ -0,0 +1,130 @@
+//! Edge metadata facts for the `edge_meta(Src, Dst, Type, Key, Value)` predicate
+//!
+//! Each edge's JSON metadata is flattened into one fact per primitive
+//! value. Nested objects use dotted keys (`cardinality.scale`), matching
+//! the paths accepted by `attr_edge`. Arrays and nulls are skipped.
+//!
+//! Evaluators build the fact base lazily, on the first `edge_meta`
+//! literal with an unbound source, so queries that never reference the
+//! predicate do not pay for parsing edge metadata.
+
+use serde_json::Value as JsonValue;
+
+use super::eval::{Bindings, Value};
+use super::types::{Atom, Term};
+use crate::storage::EdgeRecord;
+
+/// One flattened metadata key/value of one edge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct EdgeMetaFact {
+    pub src: u128,
+    pub dst: u128,
+    pub edge_type: String,
+    pub key: String,
+    pub value: String,
+}
+
+/// Flatten the metadata of `edges` into facts. Edges without metadata or
+/// with unparseable metadata contribute nothing.
+pub(crate) fn edge_meta_facts(edges: &[EdgeRecord]) -> Vec<EdgeMetaFact> {
+    let mut facts = Vec::new();
+    for edge in edges {
+        let Some(metadata) = edge.metadata.as_deref() else {
+            continue;
+        };
+        let Ok(json) = serde_json::from_str::<JsonValue>(metadata) else {
+            continue;
+        };
+        let edge_type = edge.edge_type.clone().unwrap_or_default();
+        let mut pairs = Vec::new();
+        flatten("", &json, &mut pairs);
+        facts.extend(pairs.into_iter().map(|(key, value)| EdgeMetaFact {
+            src: edge.src,
+            dst: edge.dst,
+            edge_type: edge_type.clone(),
+            key,
+            value,
+        }));
+    }
+    facts
+}
+
+fn flatten(prefix: &str, value: &JsonValue, out: &mut Vec<(String, String)>) {
+    match value {
+        JsonValue::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&path, child, out);
+            }
+        }
+        JsonValue::String(s) if !prefix.is_empty() => out.push((prefix.to_string(), s.clone())),
+        JsonValue::Number(n) if !prefix.is_empty() => out.push((prefix.to_string(), n.to_string())),
+        JsonValue::Bool(b) if !prefix.is_empty() => out.push((prefix.to_string(), b.to_string())),
+        _ => {}
+    }
+}
+
+/// Match `edge_meta(Src, Dst, Type, Key, Value)` against `facts`,
+/// binding every variable argument.
+pub(crate) fn match_edge_meta(facts: &[EdgeMetaFact], atom: &Atom) -> Vec<Bindings> {
+    let args = atom.args();
+    if args.len() < 5 {
+        return vec![];
+    }
+
+    facts
+        .iter()
+        .filter_map(|fact| {
+            let mut b = Bindings::new();
+            bind_id(&mut b, &args[0], fact.src)?;
+            bind_id(&mut b, &args[1], fact.dst)?;
+            bind_str(&mut b, &args[2], &fact.edge_type)?;
+            bind_str(&mut b, &args[3], &fact.key)?;
+            bind_str(&mut b, &args[4], &fact.value)?;
+            Some(b)
+        })
+        .collect()
+}
+
+/// Source ID when the `Src` argument is a constant, for callers that can
+/// answer from the source's outgoing edges without the full fact base.
+pub(crate) fn bound_source(atom: &Atom) -> Option<u128> {
+    match atom.args().first() {
+        Some(Term::Const(s)) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Edge type when the `Type` argument is a constant.
+pub(crate) fn bound_edge_type(atom: &Atom) -> Option<&str> {
+    match atom.args().get(2) {
+        Some(Term::Const(s)) => Some(s.as_str()),
+        _ => None,
+    }
+}
+
+fn bind_id(b: &mut Bindings, term: &Term, id: u128) -> Option<()> {
+    match term {
+        Term::Var(var) => b.set(var, Value::Id(id)),
+        Term::Const(s) => {
+            if s.parse::<u128>().ok() != Some(id) {
+                return None;
+            }
+        }
+        Term::Wildcard => {}
+    }
+    Some(())
+}
+
+fn bind_str(b: &mut Bindings, term: &Term, value: &str) -> Option<()> {
+    match term {
+        Term::Var(var) => b.set(var, Value::from_term_const(value)),
+        Term::Const(s) => {
+            if s != value {
+                return None;
+            }
+        }
+        Term::Wildcard => {}
+    }
+    Some(())
+}
@@ -2,6 +2,7 @@
 //!
 //! Evaluates Datalog queries against a GraphStore.
 
+use std::cell::{Cell, OnceCell};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -9,7 +10,8 @@ use std::time::Instant;
 use crate::graph::GraphStore;
 use crate::storage::AttrQuery;
 use crate::datalog::types::*;
-use super::utils::reorder_literals;
+use super::edge_meta::{self, EdgeMetaFact};
+use super::utils::{aggregate_bindings, compare_values, reorder_literals};
 
 /// Minimum number of current bindings to trigger hash join instead of nested-loop.
 pub const HASH_JOIN_THRESHOLD: usize = 16;
@@ -148,10 +150,18 @@ struct EvalState {
 }
 
 /// Datalog evaluator
+///
+/// Evaluation is top-down. Programs from `parse_program` are stratified, so a
+/// negated derived predicate always sits in a lower stratum than the rule
+/// negating it and is fully evaluated before the negation is tested.
 pub struct Evaluator<'a> {
     engine: &'a dyn GraphStore,
     rules: HashMap<String, Vec<Rule>>,
     limits: EvalLimits,
+    /// Flattened edge metadata, built on first unbound-source `edge_meta` use
+    edge_meta: OnceCell<Vec<EdgeMetaFact>>,
+    /// Set when evaluation stopped at `limits.deadline`
+    deadline_exceeded: Cell<bool>,
 }
 
 impl<'a> Evaluator<'a> {
@@ -161,6 +171,8 @@ impl<'a> Evaluator<'a> {
             engine,
             rules: HashMap::new(),
             limits: EvalLimits::default(),
+            edge_meta: OnceCell::new(),
+            deadline_exceeded: Cell::new(false),
         }
     }
 
@@ -170,6 +182,8 @@ impl<'a> Evaluator<'a> {
             engine,
             rules: HashMap::new(),
             limits,
+            edge_meta: OnceCell::new(),
+            deadline_exceeded: Cell::new(false),
         }
     }
 
@@ -179,17 +193,22 @@ impl<'a> Evaluator<'a> {
         self.rules.entry(predicate).or_default().push(rule);
     }
 
-    /// Load multiple rules
-    pub fn load_rules(&mut self, rules: Vec<Rule>) {
+    /// Load multiple rules. Fails, loading none of them, if together with
+    /// the rules already loaded they are not stratifiable.
+    pub fn load_rules(&mut self, rules: Vec<Rule>) -> Result<(), String> {
+        let combined = self.rules.values().flatten().chain(&rules).cloned().collect();
+        Program::new(combined).stratify()?;
         for rule in rules {
             self.add_rule(rule);
         }
+        Ok(())
     }
 
     /// Check cooperative limits. Called at hot paths during evaluation.
     fn check_limits(&self, state: &EvalState, current_count: usize) -> Result<(), String> {
         if let Some(deadline) = self.limits.deadline {
             if Instant::now() >= deadline {
+                self.deadline_exceeded.set(true);
                 return Err("Query execution timeout (deadline exceeded)".to_string());
             }
         }
@@ -213,6 +232,12 @@ impl<'a> Evaluator<'a> {
         Ok(())
     }
 
+    /// Whether an error from this evaluator came from running past
+    /// `EvalLimits::deadline` rather than from another limit or the rules.
+    pub fn deadline_exceeded(&self) -> bool {
+        self.deadline_exceeded.get()
+    }
+
     /// Query for all bindings satisfying an atom
     pub fn query(&self, goal: &Atom) -> Result<Vec<Bindings>, String> {
         let state = EvalState { recursion_depth: 0 };
@@ -688,7 +713,8 @@ impl<'a> Evaluator<'a> {
             "path" => self.eval_path(atom),
             "attr" => self.eval_attr(atom),
             "attr_edge" => self.eval_attr_edge(atom),
-            "neq" => self.eval_neq(atom),
+            "edge_meta" => self.eval_edge_meta(atom),
+            "lt" | "gt" | "le" | "ge" | "eq" | "neq" => self.eval_comparison(atom),
             "starts_with" => self.eval_starts_with(atom),
             "not_starts_with" => self.eval_not_starts_with(atom),
             "string_contains" => self.eval_string_contains(atom),
@@ -1337,6 +1363,29 @@ impl<'a> Evaluator<'a> {
         }
     }
 
+    /// Evaluate edge_meta(Src, Dst, Type, Key, Value) - one fact per edge
+    /// metadata key. A bound source only parses that node's outgoing edges;
+    /// otherwise the evaluator's edge metadata fact base is built once.
+    fn eval_edge_meta(&self, atom: &Atom) -> Vec<Bindings> {
+        if let Some(src) = edge_meta::bound_source(atom) {
+            let edge_type = edge_meta::bound_edge_type(atom);
+            let types = edge_type.as_ref().map(std::slice::from_ref);
+            let edges = self.engine.get_outgoing_edges(src, types);
+            return edge_meta::match_edge_meta(&edge_meta::edge_meta_facts(&edges), atom);
+        }
+
+        let facts = self
+            .edge_meta
+            .get_or_init(|| edge_meta::edge_meta_facts(&self.engine.get_all_edges()));
+        edge_meta::match_edge_meta(facts, atom)
+    }
+
+    /// Whether the edge metadata fact base has been materialized
+    #[cfg(test)]
+    pub(crate) fn edge_meta_materialized(&self) -> bool {
+        self.edge_meta.get().is_some()
+    }
+
     /// Evaluate path(Src, Dst) predicate using BFS
     fn eval_path(&self, atom: &Atom) -> Vec<Bindings> {
         let args = atom.args();
@@ -1407,30 +1456,20 @@ impl<'a> Evaluator<'a> {
         }
     }
 
-    /// Evaluate neq(X, Y) - inequality constraint
+    /// Evaluate a comparison built-in: lt/gt/le/ge/eq/neq(A, B)
     /// Both arguments must be bound (either constants or bound variables)
-    fn eval_neq(&self, atom: &Atom) -> Vec<Bindings> {
+    fn eval_comparison(&self, atom: &Atom) -> Vec<Bindings> {
         let args = atom.args();
         if args.len() < 2 {
             return vec![];
         }
 
-        let left = &args[0];
-        let right = &args[1];
-
-        // Get string values from terms (both must be constants at this point)
-        let left_val = match left {
-            Term::Const(s) => s.as_str(),
-            _ => return vec![], // Variables must be bound before neq check
-        };
-
-        let right_val = match right {
-            Term::Const(s) => s.as_str(),
-            _ => return vec![], // Variables must be bound before neq check
+        let (left, right) = match (&args[0], &args[1]) {
+            (Term::Const(l), Term::Const(r)) => (l.as_str(), r.as_str()),
+            _ => return vec![], // Variables must be bound before comparison
         };
 
-        // Return success (empty bindings) if not equal, fail otherwise
-        if left_val != right_val {
+        if compare_values(atom.predicate(), left, right) {
             vec![Bindings::new()]
         } else {
             vec![]
@@ -1647,7 +1686,10 @@ impl<'a> Evaluator<'a> {
         for rule in rules {
             let initial = self.bind_from_query(rule, atom);
 
-            let body_results = self.eval_rule_body_with(rule, initial, &child_state)?;
+            let mut body_results = self.eval_rule_body_with(rule, initial, &child_state)?;
+            if let Some(aggregate) = rule.aggregate() {
+                body_results = aggregate_bindings(rule, aggregate, body_results)?;
+            }
 
             for bindings in body_results {
                 if let Some(head_bindings) = self.project_to_head(rule, atom, &bindings) {
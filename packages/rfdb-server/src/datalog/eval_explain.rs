@@ -14,7 +14,8 @@ use crate::graph::GraphStore;
 use crate::storage::AttrQuery;
 use crate::datalog::types::*;
 use crate::datalog::eval::{Value, Bindings, EvalLimits};
-use super::utils::reorder_literals;
+use super::edge_meta::{self, EdgeMetaFact};
+use super::utils::{aggregate_bindings, compare_values, reorder_literals};
 use super::eval::HASH_JOIN_THRESHOLD;
 
 /// Statistics collected during query execution
@@ -123,6 +124,8 @@ pub struct EvaluatorExplain<'a> {
     limits: EvalLimits,
     /// Current recursion depth (for eval_derived nesting)
     recursion_depth: usize,
+    /// Flattened edge metadata, built on first unbound-source `edge_meta` use
+    edge_meta: Option<Vec<EdgeMetaFact>>,
 }
 
 impl<'a> EvaluatorExplain<'a> {
@@ -140,6 +143,7 @@ impl<'a> EvaluatorExplain<'a> {
             warnings: Vec::new(),
             limits: EvalLimits::default(),
             recursion_depth: 0,
+            edge_meta: None,
         }
     }
 
@@ -157,6 +161,7 @@ impl<'a> EvaluatorExplain<'a> {
             warnings: Vec::new(),
             limits,
             recursion_depth: 0,
+            edge_meta: None,
         }
     }
 
@@ -193,11 +198,15 @@ impl<'a> EvaluatorExplain<'a> {
         self.rules.entry(predicate).or_default().push(rule);
     }
 
-    /// Load multiple rules
-    pub fn load_rules(&mut self, rules: Vec<Rule>) {
+    /// Load multiple rules. Fails, loading none of them, if together with
+    /// the rules already loaded they are not stratifiable.
+    pub fn load_rules(&mut self, rules: Vec<Rule>) -> Result<(), String> {
+        let combined = self.rules.values().flatten().chain(&rules).cloned().collect();
+        Program::new(combined).stratify()?;
         for rule in rules {
             self.add_rule(rule);
         }
+        Ok(())
     }
 
     /// Query for all bindings satisfying an atom, with explain and profiling
@@ -580,7 +589,8 @@ impl<'a> EvaluatorExplain<'a> {
             "incoming" => self.eval_incoming(atom),
             "path" => self.eval_path(atom),
             "attr" => self.eval_attr(atom),
-            "neq" => self.eval_neq(atom),
+            "edge_meta" => self.eval_edge_meta(atom),
+            "lt" | "gt" | "le" | "ge" | "eq" | "neq" => self.eval_comparison(atom),
             "starts_with" => self.eval_starts_with(atom),
             "not_starts_with" => self.eval_not_starts_with(atom),
             "string_contains" => self.eval_string_contains(atom),
@@ -604,7 +614,8 @@ impl<'a> EvaluatorExplain<'a> {
             "incoming" => self.eval_incoming(atom),
             "path" => self.eval_path(atom),
             "attr" => self.eval_attr(atom),
-            "neq" => self.eval_neq(atom),
+            "edge_meta" => self.eval_edge_meta(atom),
+            "lt" | "gt" | "le" | "ge" | "eq" | "neq" => self.eval_comparison(atom),
             "starts_with" => self.eval_starts_with(atom),
             "not_starts_with" => self.eval_not_starts_with(atom),
             "string_contains" => self.eval_string_contains(atom),
@@ -1021,6 +1032,24 @@ impl<'a> EvaluatorExplain<'a> {
         query
     }
 
+    /// Evaluate edge_meta(Src, Dst, Type, Key, Value)
+    fn eval_edge_meta(&mut self, atom: &Atom) -> Vec<Bindings> {
+        if let Some(src) = edge_meta::bound_source(atom) {
+            let edge_type = edge_meta::bound_edge_type(atom);
+            let types = edge_type.as_ref().map(std::slice::from_ref);
+            let edges = self.engine.get_outgoing_edges(src, types);
+            self.stats.edges_traversed += edges.len();
+            return edge_meta::match_edge_meta(&edge_meta::edge_meta_facts(&edges), atom);
+        }
+
+        if self.edge_meta.is_none() {
+            let edges = self.engine.get_all_edges();
+            self.stats.edges_traversed += edges.len();
+            self.edge_meta = Some(edge_meta::edge_meta_facts(&edges));
+        }
+        edge_meta::match_edge_meta(self.edge_meta.as_deref().unwrap_or_default(), atom)
+    }
+
     /// Evaluate path(Src, Dst) predicate using BFS
     fn eval_path(&mut self, atom: &Atom) -> Vec<Bindings> {
         let args = atom.args();
@@ -1092,24 +1121,20 @@ impl<'a> EvaluatorExplain<'a> {
         }
     }
 
-    /// Evaluate neq(X, Y)
-    fn eval_neq(&mut self, atom: &Atom) -> Vec<Bindings> {
+    /// Evaluate a comparison built-in: lt/gt/le/ge/eq/neq(A, B)
+    /// Both arguments must be bound (either constants or bound variables)
+    fn eval_comparison(&mut self, atom: &Atom) -> Vec<Bindings> {
         let args = atom.args();
         if args.len() < 2 {
             return vec![];
         }
 
-        let left_val = match &args[0] {
-            Term::Const(s) => s.as_str(),
-            _ => return vec![],
+        let (left, right) = match (&args[0], &args[1]) {
+            (Term::Const(l), Term::Const(r)) => (l.as_str(), r.as_str()),
+            _ => return vec![], // Variables must be bound before comparison
         };
 
-        let right_val = match &args[1] {
-            Term::Const(s) => s.as_str(),
-            _ => return vec![],
-        };
-
-        if left_val != right_val {
+        if compare_values(atom.predicate(), left, right) {
             vec![Bindings::new()]
         } else {
             vec![]
@@ -1329,7 +1354,10 @@ impl<'a> EvaluatorExplain<'a> {
 
             let initial = self.bind_from_query(&rule, atom);
 
-            let body_results = self.eval_rule_body_with(&rule, initial)?;
+            let mut body_results = self.eval_rule_body_with(rule, initial)?;
+            if let Some(aggregate) = rule.aggregate() {
+                body_results = aggregate_bindings(rule, aggregate, body_results)?;
+            }
 
             for bindings in body_results {
                 if let Some(head_bindings) = self.project_to_head(&rule, atom, &bindings) {
@@ -11,12 +11,15 @@ mod types;
 mod parser;
 mod eval;
 mod eval_explain;
+mod edge_meta;
 mod utils;
+mod rule_sets;
 
 pub use types::*;
 pub use parser::*;
 pub use eval::*;
 pub use eval_explain::*;
+pub use rule_sets::RuleSets;
 
 #[cfg(test)]
 mod tests;
@@ -3,17 +3,49 @@
 //! Supports:
 //! - Terms: variables (X, Y), constants ("foo"), wildcard (_)
 //! - Atoms: predicate(arg1, arg2, ...)
-//! - Literals: atom or \+ atom
+//! - Literals: atom, \+ atom or not atom
+//! - Comparisons: lt/gt/le/ge/eq/neq(A, B) over bound variables
 //! - Rules: head :- body. or head.
+//! - Aggregates: `N = count` or `S = sum(V)` in a rule body
 //! - Programs: multiple rules
 
 use crate::datalog::types::*;
 
+/// What a parse error is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// Malformed input (unexpected token, missing delimiter, ...)
+    Syntax,
+    /// Negated literal using variables no positive literal binds
+    UnsafeNegation,
+    /// Comparison using variables no positive literal binds
+    UnsafeComparison,
+    /// Invalid aggregate clause
+    Aggregate,
+    /// Negation through recursion
+    Stratification,
+}
+
+impl ParseErrorKind {
+    /// Wire name of the kind
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParseErrorKind::Syntax => "syntax",
+            ParseErrorKind::UnsafeNegation => "unsafe_negation",
+            ParseErrorKind::UnsafeComparison => "unsafe_comparison",
+            ParseErrorKind::Aggregate => "aggregate",
+            ParseErrorKind::Stratification => "stratification",
+        }
+    }
+}
+
 /// Parse error
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseError {
     pub message: String,
+    /// Byte offset into the parsed input
     pub position: usize,
+    pub kind: ParseErrorKind,
 }
 
 impl ParseError {
@@ -21,7 +53,26 @@ impl ParseError {
         ParseError {
             message: message.to_string(),
             position,
+            kind: ParseErrorKind::Syntax,
+        }
+    }
+
+    fn with_kind(mut self, kind: ParseErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// 1-based line and column (in characters) of `position` within
+    /// `input`, the source this error came from
+    pub fn line_col(&self, input: &str) -> (usize, usize) {
+        let mut end = self.position.min(input.len());
+        while !input.is_char_boundary(end) {
+            end -= 1;
         }
+        let before = &input[..end];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
     }
 }
 
@@ -33,6 +84,14 @@ impl std::fmt::Display for ParseError {
 
 impl std::error::Error for ParseError {}
 
+/// Datalog source as submitted by a client: rules to evaluate, or a bare
+/// query
+#[derive(Debug, Clone)]
+pub enum Source {
+    Program(Program),
+    Query(Vec<Literal>),
+}
+
 /// Parser state
 struct Parser<'a> {
     input: &'a str,
@@ -184,9 +243,19 @@ impl<'a> Parser<'a> {
     fn parse_literal(&mut self) -> Result<Literal, ParseError> {
         self.skip_whitespace();
 
-        // Check for negation
-        if self.remaining().starts_with("\\+") {
-            self.pos += 2;
+        // Check for negation: `\+ atom` or `not atom` (but not `not_starts_with(...)`)
+        let negation_len = if self.remaining().starts_with("\\+") {
+            Some(2)
+        } else if self.remaining().starts_with("not")
+            && self.remaining()[3..].starts_with(char::is_whitespace)
+        {
+            Some(3)
+        } else {
+            None
+        };
+
+        if let Some(len) = negation_len {
+            self.pos += len;
             self.skip_whitespace();
             let atom = self.parse_atom()?;
             Ok(Literal::Negative(atom))
@@ -196,7 +265,53 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Try to parse an aggregation clause `Var = count` or `Var = sum(V)`.
+    /// Leaves the position untouched and returns None if the input is not
+    /// of the form `Var =`.
+    fn try_parse_aggregate(&mut self) -> Result<Option<Aggregate>, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if !self.remaining().starts_with(|c: char| c.is_uppercase()) {
+            return Ok(None);
+        }
+        let result = self.parse_identifier()?;
+        self.skip_whitespace();
+        if !self.remaining().starts_with('=') {
+            self.pos = start;
+            return Ok(None);
+        }
+        self.pos += 1;
+
+        self.skip_whitespace();
+        let func_pos = self.pos;
+        let func = match self.parse_identifier()?.as_str() {
+            "count" => AggregateFn::Count,
+            "sum" => {
+                self.expect("(")?;
+                let var = match self.parse_term()? {
+                    Term::Var(v) => v,
+                    _ => {
+                        return Err(ParseError::new("sum() expects a variable", func_pos)
+                            .with_kind(ParseErrorKind::Aggregate))
+                    }
+                };
+                self.expect(")")?;
+                AggregateFn::Sum(var)
+            }
+            other => {
+                return Err(ParseError::new(
+                    &format!("unknown aggregate '{}' (expected count or sum)", other),
+                    func_pos,
+                )
+                .with_kind(ParseErrorKind::Aggregate))
+            }
+        };
+
+        Ok(Some(Aggregate { result, func }))
+    }
+
     fn parse_rule(&mut self) -> Result<Rule, ParseError> {
+        let start = self.pos;
         let head = self.parse_atom()?;
 
         self.skip_whitespace();
@@ -206,26 +321,44 @@ impl<'a> Parser<'a> {
             self.pos += 2;
 
             let mut body = Vec::new();
-            body.push(self.parse_literal()?);
+            let mut aggregate = None;
 
             loop {
+                if let Some(agg) = self.try_parse_aggregate()? {
+                    if aggregate.replace(agg).is_some() {
+                        return Err(ParseError::new("only one aggregate per rule is supported", self.pos)
+                            .with_kind(ParseErrorKind::Aggregate));
+                    }
+                } else {
+                    body.push(self.parse_literal()?);
+                }
+
                 self.skip_whitespace();
                 if self.peek() == Some(',') {
                     self.expect(",")?;
-                    body.push(self.parse_literal()?);
                 } else {
                     break;
                 }
             }
 
             self.expect(".")?;
-            Ok(Rule::new(head, body))
+            let rule = Rule::new(head, body);
+            match aggregate {
+                Some(agg) => {
+                    check_aggregate(&rule, &agg)
+                        .map_err(|m| ParseError::new(&m, start).with_kind(ParseErrorKind::Aggregate))?;
+                    Ok(rule.with_aggregate(agg))
+                }
+                None => Ok(rule),
+            }
         } else {
             self.expect(".")?;
             Ok(Rule::fact(head))
         }
     }
 
+    /// Parse rules until end of input, then check negation safety and
+    /// stratification so unsound programs are rejected before evaluation.
     fn parse_program(&mut self) -> Result<Program, ParseError> {
         let mut rules = Vec::new();
 
@@ -234,10 +367,52 @@ impl<'a> Parser<'a> {
             if self.pos >= self.input.len() {
                 break;
             }
-            rules.push(self.parse_rule()?);
+            let start = self.pos;
+            let rule = self.parse_rule()?;
+            let unsafe_vars = rule.unsafe_negated_variables();
+            if !unsafe_vars.is_empty() {
+                return Err(ParseError::new(
+                    &format!(
+                        "unsafe negation in rule for '{}': variable(s) {} must also appear in a positive literal",
+                        rule.head().predicate(),
+                        unsafe_vars.join(", ")
+                    ),
+                    start,
+                )
+                .with_kind(ParseErrorKind::UnsafeNegation));
+            }
+            let unsafe_vars = rule.unsafe_comparison_variables();
+            if !unsafe_vars.is_empty() {
+                return Err(ParseError::new(
+                    &format!(
+                        "unsafe comparison in rule for '{}': variable(s) {} must be bound by a positive literal",
+                        rule.head().predicate(),
+                        unsafe_vars.join(", ")
+                    ),
+                    start,
+                )
+                .with_kind(ParseErrorKind::UnsafeComparison));
+            }
+            rules.push(rule);
         }
 
-        Ok(Program::new(rules))
+        let program = Program::new(rules);
+        program
+            .stratify()
+            .map_err(|message| ParseError::new(&message, 0).with_kind(ParseErrorKind::Stratification))?;
+        Ok(program)
+    }
+
+    /// Whether the input starts with a rule: an atom followed by `:-` or
+    /// `.`. A query literal can be followed by neither. Leaves `pos` alone.
+    fn starts_with_rule(&mut self) -> bool {
+        let start = self.pos;
+        let is_rule = self.parse_atom().is_ok() && {
+            self.skip_whitespace();
+            self.remaining().starts_with(":-") || self.remaining().starts_with('.')
+        };
+        self.pos = start;
+        is_rule
     }
 
     /// Parse a query (conjunction of literals without rule head)
@@ -262,6 +437,31 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Validate an aggregation clause against its rule: the result variable
+/// must be a head variable computed only by the aggregate, and a summed
+/// variable must be bound by the body.
+fn check_aggregate(rule: &Rule, agg: &Aggregate) -> Result<(), String> {
+    if rule.body().is_empty() {
+        return Err("aggregate requires at least one body literal".to_string());
+    }
+    if !rule.head().variables().contains(&agg.result) {
+        return Err(format!("aggregate variable {} must appear in the rule head", agg.result));
+    }
+    if rule.body().iter().any(|l| l.variables().contains(&agg.result)) {
+        return Err(format!("aggregate variable {} must not appear in the rule body", agg.result));
+    }
+    if let AggregateFn::Sum(var) = &agg.func {
+        let bound = rule
+            .body()
+            .iter()
+            .any(|l| l.is_positive() && !l.atom().is_comparison() && l.variables().contains(var));
+        if !bound {
+            return Err(format!("sum({}) requires {} to be bound by a positive literal", var, var));
+        }
+    }
+    Ok(())
+}
+
 // ============================================================================
 // Public API
 // ============================================================================
@@ -296,6 +496,17 @@ pub fn parse_program(input: &str) -> Result<Program, ParseError> {
     parser.parse_program()
 }
 
+/// Parse source that is either a program or a query, deciding by its
+/// first clause: a head followed by `:-` or `.` starts a program, so
+/// errors in later rules are reported as program errors.
+pub fn parse_source(input: &str) -> Result<Source, ParseError> {
+    if Parser::new(input).starts_with_rule() {
+        parse_program(input).map(Source::Program)
+    } else {
+        parse_query(input).map(Source::Query)
+    }
+}
+
 /// Parse a query (conjunction of literals)
 ///
 /// Supports single atoms or comma-separated conjunctions:
@@ -314,5 +525,16 @@ pub fn parse_query(input: &str) -> Result<Vec<Literal>, ParseError> {
             parser.pos,
         ));
     }
+    let unsafe_vars = unsafe_comparison_variables(&result);
+    if !unsafe_vars.is_empty() {
+        return Err(ParseError::new(
+            &format!(
+                "unsafe comparison: variable(s) {} must be bound by a positive literal",
+                unsafe_vars.join(", ")
+            ),
+            0,
+        )
+        .with_kind(ParseErrorKind::UnsafeComparison));
+    }
     Ok(result)
 }
@@ -0,0 +1,79 @@
+//! Named Datalog rule sets stored with a database.
+//!
+//! Saved as `rule_sets.json` in the database root, so standing guarantees
+//! survive reconnects and restarts. Ephemeral databases keep them in
+//! memory only.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+const RULE_SETS_FILE: &str = "rule_sets.json";
+
+/// Rule set name -> Datalog source
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleSets {
+    sets: BTreeMap<String, String>,
+}
+
+impl RuleSets {
+    /// Read rule sets from database root; empty if none were saved.
+    pub fn read_from(db_path: &Path) -> Result<Self> {
+        let path = db_path.join(RULE_SETS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Write rule sets to database root.
+    pub fn write_to(&self, db_path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(db_path.join(RULE_SETS_FILE), json)?;
+        Ok(())
+    }
+
+    /// Source of rule set `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.sets.get(name).map(String::as_str)
+    }
+
+    /// Saved rule set names, sorted
+    pub fn names(&self) -> Vec<String> {
+        self.sets.keys().cloned().collect()
+    }
+
+    /// Save `source` under `name`, replacing any previous rule set
+    pub fn insert(&mut self, name: &str, source: &str) {
+        self.sets.insert(name.to_string(), source.to_string());
+    }
+
+    /// Drop rule set `name`; false if there was none
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.sets.remove(name).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_through_db_root() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(RuleSets::read_from(dir.path()).unwrap(), RuleSets::default());
+
+        let mut sets = RuleSets::default();
+        sets.insert("no_orphans", "violation(X) :- node(X, \"FUNCTION\").");
+        sets.insert("all", "violation(X) :- node(X, _).");
+        sets.write_to(dir.path()).unwrap();
+
+        let read = RuleSets::read_from(dir.path()).unwrap();
+        assert_eq!(read.names(), vec!["all".to_string(), "no_orphans".to_string()]);
+        assert_eq!(read.get("all"), Some("violation(X) :- node(X, _)."));
+    }
+}
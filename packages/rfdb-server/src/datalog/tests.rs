@@ -217,6 +217,74 @@ mod program_tests {
         assert!(preds.contains("node"));
         assert!(preds.contains("violation"));
     }
+
+    #[test]
+    fn test_program_stratify_orders_negated_predicates_first() {
+        let program = parse_program(r#"
+            dead(F) :- function(F), not called(F).
+            called(F) :- edge(_, F, "CALLS").
+            function(F) :- node(F, "FUNCTION").
+            report(F) :- dead(F).
+        "#).unwrap();
+        let strata = program.stratify().unwrap();
+        assert_eq!(strata, vec![
+            vec!["called".to_string(), "function".to_string()],
+            vec!["dead".to_string(), "report".to_string()],
+        ]);
+    }
+
+    #[test]
+    fn test_program_stratify_rejects_negative_cycle() {
+        // win(X) negates itself directly
+        let program = Program::new(vec![
+            parse_rule("win(X) :- move(X, Y), \\+ win(Y).").unwrap(),
+        ]);
+        let err = program.stratify().unwrap_err();
+        assert!(err.contains("not stratifiable"), "{}", err);
+
+        let program = Program::new(vec![
+            parse_rule("p(X) :- node(X, _), \\+ q(X).").unwrap(),
+            parse_rule("q(X) :- r(X).").unwrap(),
+            parse_rule("r(X) :- p(X).").unwrap(),
+        ]);
+        assert!(program.stratify().is_err());
+    }
+
+    #[test]
+    fn test_rule_type_deps_follows_derived_predicates() {
+        let program = parse_program(r#"
+            called(F) :- edge(_, F, "CALLS").
+            violation(F) :- node(F, "FUNCTION"), \+ called(F).
+            violation(X) :- node(X, "CLASS"), attr(X, "name", N), starts_with(N, "_").
+        "#).unwrap();
+        let types = |v: &[&str]| v.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        let dead = program.rule_type_deps(&program.rules()[1]);
+        assert_eq!(dead.node_types, ["FUNCTION".to_string()].into_iter().collect());
+        assert_eq!(dead.edge_types, ["CALLS".to_string()].into_iter().collect());
+        assert!(!dead.any_node && !dead.any_edge);
+        assert!(dead.affected_by(&[], &types(&["CALLS"])));
+        assert!(!dead.affected_by(&types(&["CLASS"]), &types(&["IMPORTS"])));
+
+        // attr() can see any node, so any node change affects the rule
+        let named = program.rule_type_deps(&program.rules()[2]);
+        assert!(named.any_node && !named.any_edge);
+        assert!(named.affected_by(&types(&["VARIABLE"]), &[]));
+        assert!(!named.affected_by(&[], &types(&["CALLS"])));
+    }
+
+    #[test]
+    fn test_rule_type_deps_wildcard_matches_by_prefix() {
+        let program = parse_program(r#"
+            violation(X) :- node(X, "http:*"), \+ edge(X, _, "http:*").
+        "#).unwrap();
+        let types = |v: &[&str]| v.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        let deps = program.rule_type_deps(&program.rules()[0]);
+        assert!(deps.affected_by(&types(&["http:route"]), &[]));
+        assert!(deps.affected_by(&[], &types(&["http:routes_to"])));
+        assert!(!deps.affected_by(&types(&["https:route", "FUNCTION"]), &types(&["CALLS"])));
+    }
 }
 
 // ============================================================================
@@ -273,6 +341,52 @@ mod parser_tests {
         assert_eq!(lit.atom().predicate(), "path");
     }
 
+    #[test]
+    fn test_parse_literal_not_keyword() {
+        let lit = parse_literal("not called(F)").unwrap();
+        assert!(lit.is_negative());
+        assert_eq!(lit.atom().predicate(), "called");
+
+        // Built-ins whose name starts with "not" stay positive
+        let lit = parse_literal("not_starts_with(N, \"x\")").unwrap();
+        assert!(lit.is_positive());
+        assert_eq!(lit.atom().predicate(), "not_starts_with");
+    }
+
+    #[test]
+    fn test_parse_program_rejects_unsafe_negation() {
+        let err = parse_program("dead(F) :- function(F), not calls(G, F).").unwrap_err();
+        assert!(err.message.contains("unsafe negation"), "{}", err);
+        assert!(err.message.contains('G'), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_program_rejects_unstratifiable() {
+        let source = r#"
+            p(X) :- node(X, _), not q(X).
+            q(X) :- node(X, _), not p(X).
+        "#;
+        let err = parse_program(source).unwrap_err();
+        assert!(err.message.contains("not stratifiable"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_source_tells_rules_from_queries() {
+        let rules = parse_source("v(X) :- node(X, \"FUNCTION\").").unwrap();
+        assert!(matches!(rules, Source::Program(p) if p.rules().len() == 1));
+        assert!(matches!(parse_source("f(\"a\")."), Ok(Source::Program(_))));
+
+        // ":-" inside a constant does not make a query a rule
+        let query = parse_source(r#"attr(X, "name", ":-"), \+ node(X, "CLASS")"#).unwrap();
+        assert!(matches!(query, Source::Query(literals) if literals.len() == 2));
+
+        // Errors come from the grammar the source starts with
+        let err = parse_source("p(X) :- node(X, _), not p(X).").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Stratification);
+        let err = parse_source("node(X, \"FUNCTION\") attr(X)").unwrap_err();
+        assert!(err.message.contains("unexpected input after query"), "{}", err);
+    }
+
     #[test]
     fn test_parse_fact() {
         let rule = parse_rule("node(\"n1\", \"FUNCTION\").").unwrap();
@@ -348,6 +462,18 @@ mod parser_tests {
         assert_eq!(err.position, 13); // position of ":-"
     }
 
+    #[test]
+    fn test_parse_error_line_col_and_kind() {
+        let source = "ok(X) :- node(X, \"A\").\nbad(X) :- node(X \"B\").";
+        let err = parse_program(source).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Syntax);
+        // The string after the missing comma, on line 2
+        assert_eq!(err.line_col(source), (2, 18));
+
+        let err = parse_program("v(X) :- node(X, \"A\"), \\+ edge(X, Y, \"E\").").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnsafeNegation);
+    }
+
     #[test]
     fn test_parse_query_accepts_valid_input() {
         // Trailing whitespace should be fine
@@ -657,6 +783,37 @@ mod eval_tests {
         assert_eq!(results[0].get("X"), Some(&Value::Id(3)));
     }
 
+    #[test]
+    fn test_eval_stratified_negation_of_derived_predicate() {
+        let engine = setup_test_graph();
+        let mut evaluator = Evaluator::new(&engine);
+
+        // Nodes 2 and 4 are CALLS targets; 1 and 3 are roots
+        let program = parse_program(r#"
+            root(X) :- node(X, _), not called(X).
+            called(X) :- node(X, _), incoming(X, _, "CALLS").
+        "#).unwrap();
+        evaluator.load_rules(program.rules().to_vec()).unwrap();
+
+        let results = evaluator.query(&parse_atom("root(X)").unwrap()).unwrap();
+        let mut ids: Vec<_> = results.iter().filter_map(|b| b.get("X").and_then(Value::as_id)).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_load_rules_rejects_unstratifiable_rules() {
+        let engine = setup_test_graph();
+        let mut evaluator = Evaluator::new(&engine);
+        evaluator.load_rules(vec![parse_rule("p(X) :- node(X, _), \\+ q(X).").unwrap()]).unwrap();
+
+        // q(X) :- p(X) closes a cycle through p's negation of q
+        let err = evaluator.load_rules(vec![parse_rule("q(X) :- p(X).").unwrap()]).unwrap_err();
+        assert!(err.contains("not stratifiable"), "{}", err);
+        assert!(evaluator.query(&parse_atom("q(X)").unwrap()).unwrap().is_empty());
+        assert_eq!(evaluator.query(&parse_atom("p(X)").unwrap()).unwrap().len(), 4);
+    }
+
     #[test]
     fn test_eval_incoming() {
         let engine = setup_test_graph();
@@ -1515,6 +1672,90 @@ mod eval_tests {
         assert_eq!(results[0].get("X"), Some(&Value::Id(1)));
     }
 
+    #[test]
+    fn test_guarantee_gt_on_edge_arg_index() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+
+        let node = |id: u128, node_type: &str| NodeRecord {
+            id,
+            node_type: Some(node_type.to_string()),
+            name: Some(format!("n{}", id)),
+            file: Some("test.js".to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".into(),
+            exported: false,
+            replaces: None,
+            deleted: false,
+            metadata: None,
+            semantic_id: None,
+        };
+        engine.add_nodes(vec![node(10, "CALL"), node(20, "VARIABLE"), node(21, "VARIABLE"), node(22, "VARIABLE")]);
+
+        let arg = |dst: u128, index: u32| EdgeRecord {
+            src: 10,
+            dst,
+            edge_type: Some("PASSES_ARGUMENT".to_string()),
+            version: "main".into(),
+            metadata: Some(format!(r#"{{"argIndex": {}}}"#, index)),
+            deleted: false,
+        };
+        engine.add_edges(vec![arg(20, 0), arg(21, 2), arg(22, 12)], false);
+
+        // 12 > 2 only holds numerically ("12" < "2" as strings)
+        let program = parse_program(r#"
+            violation(X) :- node(C, "CALL"), edge(C, X, "PASSES_ARGUMENT"),
+                attr_edge(C, X, "PASSES_ARGUMENT", "argIndex", I), gt(I, "2").
+        "#).unwrap();
+        let mut evaluator = Evaluator::new(&engine);
+        evaluator.load_rules(program.rules().to_vec()).unwrap();
+
+        let results = evaluator.query(&parse_atom("violation(X)").unwrap()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get("X"), Some(&Value::Id(22)));
+    }
+
+    #[test]
+    fn test_eval_comparison_builtins() {
+        let engine = setup_test_graph();
+        let evaluator = Evaluator::new(&engine);
+        let holds = |source: &str| !evaluator.eval_query(&parse_query(source).unwrap()).unwrap().is_empty();
+
+        assert!(holds(r#"lt("9", "10")"#));
+        assert!(holds(r#"ge("1.0", "1")"#));
+        assert!(holds(r#"eq("1.0", "1")"#));
+        assert!(holds(r#"le("apple", "banana")"#));
+        assert!(!holds(r#"gt("10", "9x")"#)); // mixed: string order
+        assert!(!holds(r#"neq("queue", "queue")"#));
+
+        // eq/neq compare numbers by value, not by spelling
+        assert!(!holds(r#"neq("1", "1.0")"#));
+        assert!(!holds(r#"neq("01", "1")"#));
+        assert!(holds(r#"neq("1", "1.5")"#));
+        // IDs beyond f64 precision still compare exactly
+        assert!(holds(r#"neq("340282366920938463463374607431768211455", "340282366920938463463374607431768211454")"#));
+        assert!(holds(r#"gt("340282366920938463463374607431768211455", "340282366920938463463374607431768211454")"#));
+        assert!(holds(r#"lt("-5", "3")"#));
+        assert!(holds(r#"eq("-0", "0")"#));
+
+        // Bound variable on one side
+        let results = evaluator
+            .eval_query(&parse_query(r#"node(X, "queue:publish"), gt(X, "1")"#).unwrap())
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get("X"), Some(&Value::Id(3)));
+    }
+
+    #[test]
+    fn test_comparison_requires_bound_variables() {
+        let err = parse_program(r#"big(X) :- node(X, "CALL"), gt(N, "3")."#).unwrap_err();
+        assert!(err.message.contains("unsafe comparison"), "{}", err);
+        assert!(err.message.contains('N'), "{}", err);
+
+        let err = parse_query(r#"node(X, "CALL"), lt(X, Y)"#).unwrap_err();
+        assert!(err.message.contains("unsafe comparison"), "{}", err);
+    }
+
     #[test]
     fn test_eval_edge_variable_source() {
         let engine = setup_test_graph();
@@ -4384,7 +4625,7 @@ mod hash_join_tests {
         let program = parse_program(
             "db_caller(F, Q) :- node(F, \"FUNCTION\"), edge(F, C, \"CALLS\"), edge(C, Q, \"QUERIES_DB\")."
         ).unwrap();
-        evaluator.load_rules(program.rules().to_vec());
+        evaluator.load_rules(program.rules().to_vec()).unwrap();
 
         let goal = parse_atom("db_caller(F, Q)").unwrap();
         let results = evaluator.query(&goal).unwrap();
@@ -4422,6 +4663,7 @@ mod eval_limits_tests {
 
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("timeout"), "should contain 'timeout'");
+        assert!(evaluator.deadline_exceeded());
     }
 
     #[test]
@@ -4443,6 +4685,7 @@ mod eval_limits_tests {
 
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("intermediate result limit"));
+        assert!(!evaluator.deadline_exceeded());
     }
 
     #[test]
@@ -4550,3 +4793,270 @@ mod eval_limits_tests {
         assert!(result.unwrap_err().contains("cancelled"));
     }
 }
+
+// ============================================================================
+// Aggregation Tests
+// ============================================================================
+
+mod aggregate_tests {
+    use super::*;
+    use crate::graph::{GraphEngineV2, GraphStore};
+    use crate::storage::{NodeRecord, EdgeRecord};
+
+    /// FUNCTION 1 contains CALLs 10 and 11; FUNCTION 2 contains CALL 12.
+    /// Each CALL carries a numeric `cost` in its metadata.
+    fn setup_aggregate_graph() -> GraphEngineV2 {
+        let mut engine = GraphEngineV2::create_ephemeral();
+
+        let node = |id: u128, node_type: &str, metadata: Option<&str>| NodeRecord {
+            id,
+            node_type: Some(node_type.to_string()),
+            name: Some(format!("n{}", id)),
+            file: Some("test.js".to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".into(),
+            exported: false,
+            replaces: None,
+            deleted: false,
+            metadata: metadata.map(|m| m.to_string()),
+            semantic_id: None,
+        };
+        engine.add_nodes(vec![
+            node(1, "FUNCTION", None),
+            node(2, "FUNCTION", None),
+            node(10, "CALL", Some(r#"{"cost": 3}"#)),
+            node(11, "CALL", Some(r#"{"cost": 4}"#)),
+            node(12, "CALL", Some(r#"{"cost": 10}"#)),
+        ]);
+
+        let contains = |src: u128, dst: u128| EdgeRecord {
+            src,
+            dst,
+            edge_type: Some("CONTAINS".to_string()),
+            version: "main".into(),
+            metadata: None,
+            deleted: false,
+        };
+        engine.add_edges(vec![contains(1, 10), contains(1, 11), contains(2, 12)], false);
+
+        engine
+    }
+
+    fn query_pairs(engine: &GraphEngineV2, source: &str, goal: &str, key: &str, value: &str) -> Vec<(String, String)> {
+        let program = parse_program(source).unwrap();
+        let mut evaluator = Evaluator::new(engine);
+        evaluator.load_rules(program.rules().to_vec()).unwrap();
+        let mut pairs: Vec<(String, String)> = evaluator
+            .query(&parse_atom(goal).unwrap())
+            .unwrap()
+            .iter()
+            .map(|b| (b.get(key).unwrap().as_str(), b.get(value).unwrap().as_str()))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn test_parse_aggregate_clause() {
+        let rule = parse_rule("count_calls(F, N) :- callsite(F, _), N = count.").unwrap();
+        assert_eq!(rule.body().len(), 1);
+        assert_eq!(
+            rule.aggregate(),
+            Some(&Aggregate { result: "N".to_string(), func: AggregateFn::Count })
+        );
+        assert!(rule.is_safe());
+
+        let rule = parse_rule("total(F, S) :- cost(F, C), S = sum(C).").unwrap();
+        assert_eq!(rule.aggregate().unwrap().func, AggregateFn::Sum("C".to_string()));
+    }
+
+    #[test]
+    fn test_parse_aggregate_errors() {
+        assert!(parse_rule("c(F, N) :- f(F), N = avg.").unwrap_err().message.contains("unknown aggregate"));
+        assert!(parse_rule("c(F) :- f(F), N = count.").unwrap_err().message.contains("head"));
+        assert!(parse_rule("c(F, N) :- f(F, N), N = count.").unwrap_err().message.contains("body"));
+        assert!(parse_rule("c(F, S) :- f(F), S = sum(C).").unwrap_err().message.contains("sum(C)"));
+
+        // Aggregating over a recursive predicate cannot be stratified
+        let err = parse_program("c(F, N) :- c(F, _), N = count.").unwrap_err();
+        assert!(err.message.contains("not stratifiable"), "{}", err);
+    }
+
+    #[test]
+    fn test_count_per_group() {
+        let engine = setup_aggregate_graph();
+        let source = r#"call_count(F, N) :- node(F, "FUNCTION"), edge(F, _, "CONTAINS"), N = count."#;
+
+        let counts = query_pairs(&engine, source, "call_count(F, N)", "F", "N");
+        assert_eq!(counts, vec![("1".to_string(), "2".to_string()), ("2".to_string(), "1".to_string())]);
+
+        // Fixing the aggregate value filters groups
+        let program = parse_program(source).unwrap();
+        let mut evaluator = Evaluator::new(&engine);
+        evaluator.load_rules(program.rules().to_vec()).unwrap();
+        let results = evaluator.query(&parse_atom("call_count(F, \"1\")").unwrap()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get("F"), Some(&Value::Id(2)));
+    }
+
+    #[test]
+    fn test_sum_over_metadata_field() {
+        let engine = setup_aggregate_graph();
+        let source = r#"
+            total_cost(F, S) :- node(F, "FUNCTION"), edge(F, C, "CONTAINS"), attr(C, "cost", K), S = sum(K).
+        "#;
+
+        let totals = query_pairs(&engine, source, "total_cost(F, S)", "F", "S");
+        assert_eq!(totals, vec![("1".to_string(), "7".to_string()), ("2".to_string(), "10".to_string())]);
+
+        // Bound grouping variable restricts evaluation to one group
+        let totals = query_pairs(&engine, source, "total_cost(\"1\", S)", "S", "S");
+        assert_eq!(totals, vec![("7".to_string(), "7".to_string())]);
+    }
+
+    #[test]
+    fn test_explain_evaluator_supports_aggregates() {
+        let engine = setup_aggregate_graph();
+        let program = parse_program(
+            r#"call_count(F, N) :- node(F, "FUNCTION"), edge(F, _, "CONTAINS"), N = count."#,
+        ).unwrap();
+        let mut evaluator = EvaluatorExplain::new(&engine, false);
+        for rule in program.rules() {
+            evaluator.add_rule(rule.clone());
+        }
+        let result = evaluator.query(&parse_atom("call_count(\"1\", N)").unwrap());
+        assert_eq!(result.bindings.len(), 1);
+        assert_eq!(result.bindings[0].get("N").map(String::as_str), Some("2"));
+    }
+}
+
+// ============================================================================
+// edge_meta() Predicate Tests
+// ============================================================================
+
+mod edge_meta_tests {
+    use super::*;
+    use crate::graph::{GraphEngineV2, GraphStore};
+    use crate::storage::{NodeRecord, EdgeRecord};
+
+    /// CALL 10 calls `eval` (20) with a spread argument from VARIABLE 1.
+    /// CALL 11 calls `eval` with a plain argument from VARIABLE 2.
+    /// CALL 12 calls `log` (21) with a spread argument from VARIABLE 1.
+    fn setup_spread_graph() -> GraphEngineV2 {
+        let mut engine = GraphEngineV2::create_ephemeral();
+
+        let node = |id: u128, node_type: &str, name: &str| NodeRecord {
+            id,
+            node_type: Some(node_type.to_string()),
+            name: Some(name.to_string()),
+            file: Some("app.js".to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".into(),
+            exported: false,
+            replaces: None,
+            deleted: false,
+            metadata: None,
+            semantic_id: None,
+        };
+        engine.add_nodes(vec![
+            node(1, "VARIABLE", "args"),
+            node(2, "VARIABLE", "input"),
+            node(10, "CALL", "eval"),
+            node(11, "CALL", "eval"),
+            node(12, "CALL", "log"),
+            node(20, "FUNCTION", "eval"),
+            node(21, "FUNCTION", "log"),
+        ]);
+
+        let edge = |src: u128, dst: u128, edge_type: &str, metadata: Option<&str>| EdgeRecord {
+            src,
+            dst,
+            edge_type: Some(edge_type.to_string()),
+            version: "main".into(),
+            metadata: metadata.map(|m| m.to_string()),
+            deleted: false,
+        };
+        engine.add_edges(vec![
+            edge(1, 10, "PASSES_ARGUMENT", Some(r#"{"argIndex": 0, "isSpread": true}"#)),
+            edge(2, 11, "PASSES_ARGUMENT", Some(r#"{"argIndex": 0, "isSpread": false}"#)),
+            edge(1, 12, "PASSES_ARGUMENT", Some(r#"{"argIndex": 1, "isSpread": true, "loc": {"line": 7}}"#)),
+            edge(10, 20, "CALLS", None),
+            edge(11, 20, "CALLS", None),
+            edge(12, 21, "CALLS", None),
+        ], false);
+
+        engine
+    }
+
+    #[test]
+    fn test_guarantee_spread_argument_into_untrusted_sink() {
+        let engine = setup_spread_graph();
+        let program = parse_program(r#"
+            untrusted_sink(F) :- node(F, "FUNCTION"), attr(F, "name", "eval").
+            violation(C) :- node(C, "CALL"), edge(C, F, "CALLS"), untrusted_sink(F),
+                edge_meta(_, C, "PASSES_ARGUMENT", "isSpread", "true").
+        "#).unwrap();
+        let mut evaluator = Evaluator::new(&engine);
+        evaluator.load_rules(program.rules().to_vec()).unwrap();
+
+        let results = evaluator.query(&parse_atom("violation(X)").unwrap()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get("X"), Some(&Value::Id(10)));
+    }
+
+    #[test]
+    fn test_edge_meta_enumerates_keys_and_nested_paths() {
+        let engine = setup_spread_graph();
+        let evaluator = Evaluator::new(&engine);
+
+        let results = evaluator
+            .eval_query(&parse_query(r#"edge_meta("1", "12", T, K, V)"#).unwrap())
+            .unwrap();
+        let mut pairs: Vec<(String, String)> = results
+            .iter()
+            .map(|b| (b.get("K").unwrap().as_str(), b.get("V").unwrap().as_str()))
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![
+            ("argIndex".to_string(), "1".to_string()),
+            ("isSpread".to_string(), "true".to_string()),
+            ("loc.line".to_string(), "7".to_string()),
+        ]);
+        assert!(results.iter().all(|b| b.get("T") == Some(&Value::Str("PASSES_ARGUMENT".to_string()))));
+
+        // Combined with a comparison built-in
+        let results = evaluator
+            .eval_query(&parse_query(r#"edge_meta(S, D, "PASSES_ARGUMENT", "argIndex", I), gt(I, "0")"#).unwrap())
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get("D"), Some(&Value::Id(12)));
+    }
+
+    #[test]
+    fn test_edge_meta_materialized_lazily() {
+        let engine = setup_spread_graph();
+        let evaluator = Evaluator::new(&engine);
+
+        evaluator.eval_query(&parse_query(r#"node(X, "CALL")"#).unwrap()).unwrap();
+        assert!(!evaluator.edge_meta_materialized());
+
+        // Bound source reads only that node's edges
+        evaluator.eval_query(&parse_query(r#"edge_meta("2", D, _, "isSpread", V)"#).unwrap()).unwrap();
+        assert!(!evaluator.edge_meta_materialized());
+
+        evaluator.eval_query(&parse_query(r#"edge_meta(S, "11", _, "isSpread", V)"#).unwrap()).unwrap();
+        assert!(evaluator.edge_meta_materialized());
+    }
+
+    #[test]
+    fn test_explain_evaluator_supports_edge_meta() {
+        let engine = setup_spread_graph();
+        let mut evaluator = EvaluatorExplain::new(&engine, false);
+        let result = evaluator
+            .eval_query(&parse_query(r#"edge_meta(S, D, "PASSES_ARGUMENT", "isSpread", "true")"#).unwrap())
+            .unwrap();
+        assert_eq!(result.bindings.len(), 2);
+    }
+}
@@ -1,6 +1,6 @@
-//! Core Datalog types: Term, Atom, Literal, Rule, Program
+//! Core Datalog types: Term, Atom, Literal, Rule, Aggregate, Program
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// A term in Datalog - variable, constant, or wildcard
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -61,6 +61,11 @@ impl Term {
     }
 }
 
+/// Built-in comparison predicates: `lt(A, B)`, `gt`, `le`, `ge`, `eq`, `neq`.
+///
+/// They only test already-bound values and never bind variables.
+pub const COMPARISON_PREDICATES: &[&str] = &["lt", "gt", "le", "ge", "eq", "neq"];
+
 /// An atom (predicate with arguments)
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Atom {
@@ -104,6 +109,11 @@ impl Atom {
     pub fn is_ground(&self) -> bool {
         self.args.iter().all(|t| !t.is_var())
     }
+
+    /// Check if this is a comparison built-in (see `COMPARISON_PREDICATES`)
+    pub fn is_comparison(&self) -> bool {
+        COMPARISON_PREDICATES.contains(&self.predicate.as_str())
+    }
 }
 
 /// A literal - positive or negative atom
@@ -147,17 +157,39 @@ impl Literal {
     }
 }
 
+/// Aggregate function applied to the solutions of a rule body
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AggregateFn {
+    /// `N = count` - number of satisfying substitutions
+    Count,
+    /// `S = sum(V)` - numeric sum of body variable V
+    Sum(String),
+}
+
+/// Aggregation clause of a rule, e.g. `N = count` in
+/// `count_calls(F, N) :- callsite(F, _), N = count.`
+///
+/// Solutions are grouped by the remaining head variables and the
+/// aggregate is bound to `result` once per group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Aggregate {
+    /// Head variable receiving the aggregate value
+    pub result: String,
+    pub func: AggregateFn,
+}
+
 /// A Datalog rule: head :- body.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Rule {
     head: Atom,
     body: Vec<Literal>,
+    aggregate: Option<Aggregate>,
 }
 
 impl Rule {
     /// Create a new rule with head and body
     pub fn new(head: Atom, body: Vec<Literal>) -> Self {
-        Rule { head, body }
+        Rule { head, body, aggregate: None }
     }
 
     /// Create a fact (rule with empty body)
@@ -165,9 +197,21 @@ impl Rule {
         Rule {
             head,
             body: Vec::new(),
+            aggregate: None,
         }
     }
 
+    /// Attach an aggregation clause to this rule
+    pub fn with_aggregate(mut self, aggregate: Aggregate) -> Self {
+        self.aggregate = Some(aggregate);
+        self
+    }
+
+    /// Get the aggregation clause, if any
+    pub fn aggregate(&self) -> Option<&Aggregate> {
+        self.aggregate.as_ref()
+    }
+
     /// Get the head atom
     pub fn head(&self) -> &Atom {
         &self.head
@@ -192,13 +236,9 @@ impl Rule {
         vars
     }
 
-    /// Get variables that appear in positive body literals
+    /// Get variables bound by positive body literals (comparisons bind nothing)
     fn positive_body_variables(&self) -> HashSet<String> {
-        self.body
-            .iter()
-            .filter(|l| l.is_positive())
-            .flat_map(|l| l.variables())
-            .collect()
+        binding_variables(&self.body)
     }
 
     /// Check if rule is safe (all head vars appear in positive body literals)
@@ -211,11 +251,64 @@ impl Rule {
 
         let head_vars = self.head.variables();
         let positive_vars = self.positive_body_variables();
+        let aggregate_var = self.aggregate.as_ref().map(|a| a.result.as_str());
+
+        head_vars
+            .iter()
+            .all(|v| positive_vars.contains(v) || aggregate_var == Some(v.as_str()))
+    }
+
+    /// Variables of negated literals that no positive body literal binds.
+    ///
+    /// A negated atom can only be checked once all its variables are bound,
+    /// so a non-empty result means the rule is unsafe. Sorted for stable
+    /// error messages.
+    pub fn unsafe_negated_variables(&self) -> Vec<String> {
+        let positive_vars = self.positive_body_variables();
+        let mut unsafe_vars: Vec<String> = self
+            .body
+            .iter()
+            .filter(|l| l.is_negative())
+            .flat_map(|l| l.variables())
+            .filter(|v| !positive_vars.contains(v))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        unsafe_vars.sort();
+        unsafe_vars
+    }
 
-        head_vars.iter().all(|v| positive_vars.contains(v))
+    /// Variables of comparison literals that no positive body literal binds.
+    /// Sorted; a non-empty result means the rule is unsafe.
+    pub fn unsafe_comparison_variables(&self) -> Vec<String> {
+        unsafe_comparison_variables(&self.body)
     }
 }
 
+/// Variables bound by the positive, non-comparison literals of a body.
+fn binding_variables(body: &[Literal]) -> HashSet<String> {
+    body.iter()
+        .filter(|l| l.is_positive() && !l.atom().is_comparison())
+        .flat_map(|l| l.variables())
+        .collect()
+}
+
+/// Variables used by comparison literals in `body` but never bound by it.
+/// Applies to rule bodies and to top-level queries alike.
+pub fn unsafe_comparison_variables(body: &[Literal]) -> Vec<String> {
+    let bound = binding_variables(body);
+    let mut unsafe_vars: Vec<String> = body
+        .iter()
+        .filter(|l| l.atom().is_comparison())
+        .flat_map(|l| l.variables())
+        .filter(|v| !bound.contains(v))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    unsafe_vars.sort();
+    unsafe_vars
+}
+
 /// A Datalog program - collection of rules
 #[derive(Clone, Debug)]
 pub struct Program {
@@ -250,4 +343,154 @@ impl Program {
     pub fn is_safe(&self) -> bool {
         self.rules.iter().all(|r| r.is_safe())
     }
+    /// Partition derived predicates into strata for stratified negation.
+    ///
+    /// A predicate is placed in a stratum at least as high as every
+    /// predicate it depends on, and strictly higher than every predicate it
+    /// negates or aggregates over. Fails if a predicate depends negatively
+    /// (or through an aggregate) on itself through a recursive cycle, since
+    /// such a program has no stratified meaning.
+    /// Built-in predicates (those without rules) are not part of any stratum.
+    ///
+    /// Returns strata lowest first; predicates within a stratum are sorted.
+    pub fn stratify(&self) -> Result<Vec<Vec<String>>, String> {
+        let defined = self.defined_predicates();
+
+        // head -> (body predicate, strict), restricted to derived predicates.
+        // A dependency is strict when negated or aggregated over.
+        let mut deps: HashMap<&str, Vec<(&str, bool)>> = HashMap::new();
+        for rule in &self.rules {
+            let entry = deps.entry(rule.head.predicate()).or_default();
+            for lit in &rule.body {
+                let pred = lit.atom().predicate();
+                if defined.contains(pred) {
+                    entry.push((pred, lit.is_negative() || rule.aggregate.is_some()));
+                }
+            }
+        }
+
+        let reaches = |from: &str, to: &str| -> bool {
+            let mut seen: HashSet<&str> = HashSet::new();
+            let mut stack = vec![from];
+            while let Some(p) = stack.pop() {
+                if p == to {
+                    return true;
+                }
+                if seen.insert(p) {
+                    if let Some(next) = deps.get(p) {
+                        stack.extend(next.iter().map(|(q, _)| *q));
+                    }
+                }
+            }
+            false
+        };
+
+        let mut heads: Vec<&str> = deps.keys().copied().collect();
+        heads.sort_unstable();
+        for &head in &heads {
+            for &(negated, _) in deps[head].iter().filter(|(_, neg)| *neg) {
+                if reaches(negated, head) {
+                    return Err(format!(
+                        "program is not stratifiable: '{}' depends on the negation or aggregate of '{}' through recursion",
+                        head, negated
+                    ));
+                }
+            }
+        }
+
+        // No negative cycles, so this fixpoint converges within |heads| rounds.
+        let mut stratum: HashMap<&str, usize> = heads.iter().map(|h| (*h, 0)).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &head in &heads {
+                let required = deps[head]
+                    .iter()
+                    .map(|(q, neg)| stratum[q] + usize::from(*neg))
+                    .max()
+                    .unwrap_or(0);
+                if required > stratum[head] {
+                    stratum.insert(head, required);
+                    changed = true;
+                }
+            }
+        }
+
+        let levels = stratum.values().max().map_or(0, |m| m + 1);
+        let mut strata = vec![Vec::new(); levels];
+        for &head in &heads {
+            strata[stratum[head]].push(head.to_string());
+        }
+        Ok(strata)
+    }
+
+    /// Node and edge types `rule` can observe, following derived body
+    /// predicates through their rules. Built-ins that do not pin a type
+    /// (a variable type argument, `attr`, `path`, ...) depend on every
+    /// node or edge type.
+    pub fn rule_type_deps(&self, rule: &Rule) -> TypeDeps {
+        let mut deps = TypeDeps::default();
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&Rule> = vec![rule];
+        while let Some(rule) = stack.pop() {
+            for lit in &rule.body {
+                let atom = lit.atom();
+                let type_arg = |i: usize| atom.args().get(i).and_then(Term::const_value).map(str::to_string);
+                match atom.predicate() {
+                    "node" | "type" => match type_arg(1) {
+                        Some(t) => { deps.node_types.insert(t); }
+                        None => deps.any_node = true,
+                    },
+                    "edge" | "incoming" => match type_arg(2) {
+                        Some(t) => { deps.edge_types.insert(t); }
+                        None => deps.any_edge = true,
+                    },
+                    "attr" => deps.any_node = true,
+                    "path" | "attr_edge" | "edge_meta" => deps.any_edge = true,
+                    "parent_function" => {
+                        deps.any_node = true;
+                        deps.any_edge = true;
+                    }
+                    "lt" | "gt" | "le" | "ge" | "eq" | "neq"
+                    | "starts_with" | "not_starts_with" | "string_contains" => {}
+                    derived => {
+                        if seen.insert(derived) {
+                            stack.extend(self.rules_for(derived));
+                        }
+                    }
+                }
+            }
+        }
+        deps
+    }
+}
+
+/// Graph types a rule depends on (`Program::rule_type_deps`)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TypeDeps {
+    pub node_types: HashSet<String>,
+    pub edge_types: HashSet<String>,
+    /// Depends on nodes of every type
+    pub any_node: bool,
+    /// Depends on edges of every type
+    pub any_edge: bool,
+}
+
+impl TypeDeps {
+    /// Whether a change touching these node and edge types can alter the
+    /// rule's results. A dependency ending in `*` is a prefix wildcard, as
+    /// in `node(X, "http:*")`.
+    pub fn affected_by(&self, node_types: &[String], edge_types: &[String]) -> bool {
+        (self.any_node && !node_types.is_empty())
+            || (self.any_edge && !edge_types.is_empty())
+            || node_types.iter().any(|t| Self::depends_on(&self.node_types, t))
+            || edge_types.iter().any(|t| Self::depends_on(&self.edge_types, t))
+    }
+
+    fn depends_on(deps: &HashSet<String>, changed: &str) -> bool {
+        deps.iter().any(|dep| match dep.strip_suffix('*') {
+            Some(prefix) => changed.starts_with(prefix),
+            None => dep == changed,
+        })
+    }
 }
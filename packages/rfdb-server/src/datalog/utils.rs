@@ -6,7 +6,8 @@
 use std::collections::HashSet;
 use serde_json::Value;
 
-use super::types::{Literal, Term};
+use super::eval::{Bindings, Value as DatalogValue};
+use super::types::{Aggregate, AggregateFn, Literal, Rule, Term};
 
 /// Extracts a value from JSON metadata, supporting both direct keys and nested paths.
 ///
@@ -92,6 +93,132 @@ fn value_to_string(value: &Value) -> Option<String> {
     }
 }
 
+/// Evaluate a comparison built-in (`lt`, `gt`, `le`, `ge`, `eq`, `neq`)
+/// over two bound values.
+///
+/// Values are compared as numbers when both parse as numbers, otherwise
+/// as strings. Integers (node IDs included) are compared exactly; any
+/// other pair of numbers as finite `f64`. This applies to `eq` and `neq`
+/// too, so `neq("1", "1.0")` and `neq("01", "1")` fail where a plain
+/// string comparison would succeed. Returns `false` for an unknown
+/// operator.
+pub(crate) fn compare_values(op: &str, left: &str, right: &str) -> bool {
+    use std::cmp::Ordering;
+
+    let numeric = match (parse_integer(left), parse_integer(right)) {
+        (Some(l), Some(r)) => Some(compare_integers(l, r)),
+        _ => match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+            (Ok(l), Ok(r)) if l.is_finite() && r.is_finite() => l.partial_cmp(&r),
+            _ => None,
+        },
+    };
+    let ordering = numeric.unwrap_or_else(|| left.cmp(right));
+
+    match op {
+        "lt" => ordering == Ordering::Less,
+        "gt" => ordering == Ordering::Greater,
+        "le" => ordering != Ordering::Greater,
+        "ge" => ordering != Ordering::Less,
+        "eq" => ordering == Ordering::Equal,
+        "neq" => ordering != Ordering::Equal,
+        _ => false,
+    }
+}
+
+/// Group rule body solutions and compute the rule's aggregate per group.
+///
+/// Groups are keyed by the head variables other than the aggregate result,
+/// in first-seen order. Each satisfying substitution counts once, so
+/// wildcard matches (`callsite(F, _)`) are counted separately. If the query
+/// already fixed the result (e.g. `count_calls(F, "2")`), only groups with
+/// that value are kept. Returns one binding per group with the grouping
+/// variables and the result variable set.
+pub(crate) fn aggregate_bindings(
+    rule: &Rule,
+    aggregate: &Aggregate,
+    solutions: Vec<Bindings>,
+) -> Result<Vec<Bindings>, String> {
+    let group_vars: Vec<&str> = rule
+        .head()
+        .args()
+        .iter()
+        .filter_map(|t| t.var_name())
+        .filter(|v| *v != aggregate.result)
+        .collect();
+
+    let mut index: std::collections::HashMap<Vec<Option<DatalogValue>>, usize> =
+        std::collections::HashMap::new();
+    let mut groups: Vec<(Bindings, u64, f64)> = Vec::new();
+
+    for solution in &solutions {
+        let key: Vec<Option<DatalogValue>> = group_vars.iter().map(|v| solution.get(v).cloned()).collect();
+        let slot = *index.entry(key).or_insert_with(|| {
+            let mut group = Bindings::new();
+            for var in &group_vars {
+                if let Some(value) = solution.get(var) {
+                    group.set(var, value.clone());
+                }
+            }
+            if let Some(fixed) = solution.get(&aggregate.result) {
+                group.set(&aggregate.result, fixed.clone());
+            }
+            groups.push((group, 0, 0.0));
+            groups.len() - 1
+        });
+
+        let (_, count, sum) = &mut groups[slot];
+        *count += 1;
+        if let AggregateFn::Sum(var) = &aggregate.func {
+            let raw = solution.get(var).map(|v| v.as_str()).unwrap_or_default();
+            *sum += raw
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("sum({}): non-numeric value '{}'", var, raw))?;
+        }
+    }
+
+    let mut results = Vec::with_capacity(groups.len());
+    for (group, count, sum) in groups {
+        let value = match aggregate.func {
+            AggregateFn::Count => count.to_string(),
+            AggregateFn::Sum(_) if sum.fract() == 0.0 && sum.abs() < 1e15 => (sum as i64).to_string(),
+            AggregateFn::Sum(_) => sum.to_string(),
+        };
+        let mut computed = Bindings::new();
+        computed.set(&aggregate.result, DatalogValue::from_term_const(&value));
+        if let Some(merged) = group.extend(&computed) {
+            results.push(merged);
+        }
+    }
+    Ok(results)
+}
+
+/// Sign and magnitude of a decimal integer, wide enough for `u128` IDs
+/// (which `f64` cannot tell apart).
+fn parse_integer(value: &str) -> Option<(bool, u128)> {
+    let value = value.trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let magnitude = digits.parse::<u128>().ok()?;
+    Some((negative && magnitude != 0, magnitude))
+}
+
+fn compare_integers(left: (bool, u128), right: (bool, u128)) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    match (left, right) {
+        ((false, l), (false, r)) => l.cmp(&r),
+        ((true, l), (true, r)) => r.cmp(&l),
+        ((true, _), (false, _)) => Ordering::Less,
+        ((false, _), (true, _)) => Ordering::Greater,
+    }
+}
+
 /// Reorder query literals so that predicates requiring bound variables come after
 /// the predicates that provide those bindings.
 ///
@@ -218,8 +345,8 @@ fn positive_can_place_and_provides(
             }
             (can_place, provides)
         }
-        "edge" => {
-            // edge is always placeable (full scan if src unbound)
+        "edge" | "edge_meta" => {
+            // edge/edge_meta are always placeable (full scan if src unbound)
             let provides = free_vars(args, bound);
             (true, provides)
         }
@@ -260,7 +387,7 @@ fn positive_can_place_and_provides(
             }
             (can_place, provides)
         }
-        "neq" | "starts_with" | "not_starts_with" | "string_contains" => {
+        "lt" | "gt" | "le" | "ge" | "eq" | "neq" | "starts_with" | "not_starts_with" | "string_contains" => {
             // All Var args must be in bound
             let all_bound = args.iter().all(|t| match t {
                 Term::Var(v) => bound.contains(v),
@@ -63,6 +63,21 @@ pub enum GraphError {
 
     #[error("Query limit exceeded: {0}")]
     QueryLimitExceeded(String),
+
+    #[error("Snapshot version {0} not found")]
+    SnapshotNotFound(u64),
+
+    #[error("Shard {shard_id} out of range (database has {shard_count} shards)")]
+    ShardOutOfRange { shard_id: u16, shard_count: usize },
+
+    #[error("Invalid shard count {0} (must be 1..={max})", max = crate::database_manager::MAX_SHARD_COUNT)]
+    InvalidShardCount(u32),
+
+    #[error("Write version conflict: expected {expected}, current version is {actual}")]
+    VersionConflict { expected: u64, actual: u64 },
+
+    #[error("{0} is not supported on ephemeral databases")]
+    EphemeralUnsupported(String),
 }
 
 impl GraphError {
@@ -79,6 +94,11 @@ impl GraphError {
             GraphError::QueryTimeout(_) => "QUERY_TIMEOUT",
             GraphError::QueryCancelled => "QUERY_CANCELLED",
             GraphError::QueryLimitExceeded(_) => "QUERY_LIMIT_EXCEEDED",
+            GraphError::SnapshotNotFound(_) => "SNAPSHOT_NOT_FOUND",
+            GraphError::ShardOutOfRange { .. } => "SHARD_OUT_OF_RANGE",
+            GraphError::VersionConflict { .. } => "VERSION_CONFLICT",
+            GraphError::InvalidShardCount(_) => "INVALID_SHARD_COUNT",
+            GraphError::EphemeralUnsupported(_) => "EPHEMERAL_UNSUPPORTED",
             _ => "INTERNAL_ERROR",
         }
     }
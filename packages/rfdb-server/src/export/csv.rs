@@ -0,0 +1,118 @@
+//! CSV serialization (pandas, spreadsheets)
+//!
+//! Nodes and edges are separate documents since they have different
+//! columns. Quoting follows RFC 4180: fields containing a comma, quote,
+//! CR or LF are wrapped in double quotes with inner quotes doubled, and
+//! records end with CRLF.
+
+use crate::storage::{EdgeRecord, NodeRecord};
+
+/// Column header for node CSV.
+pub const NODE_COLUMNS: &str = "id,semantic_id,node_type,name,file,exported,metadata";
+
+/// Column header for edge CSV.
+pub const EDGE_COLUMNS: &str = "src,dst,edge_type,metadata";
+
+/// Serialize nodes as CSV with a header row. Missing values are empty fields.
+pub fn nodes_to_csv(nodes: &[NodeRecord]) -> String {
+    let mut out = String::new();
+    out.push_str(NODE_COLUMNS);
+    out.push_str("\r\n");
+    for node in nodes {
+        let id = node.id.to_string();
+        let exported = node.exported.to_string();
+        write_record(&mut out, &[
+            &id,
+            node.semantic_id.as_deref().unwrap_or(""),
+            node.node_type.as_deref().unwrap_or(""),
+            node.name.as_deref().unwrap_or(""),
+            node.file.as_deref().unwrap_or(""),
+            &exported,
+            node.metadata.as_deref().unwrap_or(""),
+        ]);
+    }
+    out
+}
+
+/// Serialize edges as CSV with a header row. Missing values are empty fields.
+pub fn edges_to_csv(edges: &[EdgeRecord]) -> String {
+    let mut out = String::new();
+    out.push_str(EDGE_COLUMNS);
+    out.push_str("\r\n");
+    for edge in edges {
+        let src = edge.src.to_string();
+        let dst = edge.dst.to_string();
+        write_record(&mut out, &[
+            &src,
+            &dst,
+            edge.edge_type.as_deref().unwrap_or(""),
+            edge.metadata.as_deref().unwrap_or(""),
+        ]);
+    }
+    out
+}
+
+fn write_record(out: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_field(out, field);
+    }
+    out.push_str("\r\n");
+}
+
+fn write_field(out: &mut String, field: &str) {
+    if field.contains([',', '"', '\r', '\n']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nodes_csv_escapes_metadata_with_comma_and_quote() {
+        let nodes = vec![NodeRecord {
+            id: 7,
+            node_type: Some("FUNCTION".to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".to_string(),
+            exported: true,
+            replaces: None,
+            deleted: false,
+            name: Some("multi\nline".to_string()),
+            file: Some("src/app.js".to_string()),
+            metadata: Some(r#"{"a":1,"b":"x"}"#.to_string()),
+            semantic_id: Some("FUNCTION:f@src/app.js".to_string()),
+        }];
+
+        let csv = nodes_to_csv(&nodes);
+        assert_eq!(
+            csv,
+            "id,semantic_id,node_type,name,file,exported,metadata\r\n\
+             7,FUNCTION:f@src/app.js,FUNCTION,\"multi\nline\",src/app.js,true,\"{\"\"a\"\":1,\"\"b\"\":\"\"x\"\"}\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_edges_csv_empty_fields() {
+        let edges = vec![EdgeRecord {
+            src: 1,
+            dst: 2,
+            edge_type: Some("CALLS".to_string()),
+            version: "main".to_string(),
+            metadata: None,
+            deleted: false,
+        }];
+
+        assert_eq!(edges_to_csv(&edges), "src,dst,edge_type,metadata\r\n1,2,CALLS,\r\n");
+        assert_eq!(edges_to_csv(&[]), "src,dst,edge_type,metadata\r\n");
+    }
+}
@@ -0,0 +1,158 @@
+//! Graphviz DOT serialization for quick diagrams
+//!
+//! Output is a single `digraph` with quoted IDs, so it renders with
+//! `dot -Tsvg` regardless of what characters names contain.
+
+use std::fmt::Write;
+
+use super::ExportSubgraph;
+use crate::storage::NodeRecord;
+
+/// Serialize a subgraph as a Graphviz `digraph`.
+///
+/// Node labels are `name`, falling back to the semantic ID and then the
+/// numeric ID. Edge labels are the edge type. Node shape depends on type.
+pub fn to_dot(graph: &ExportSubgraph) -> String {
+    let mut out = String::new();
+    out.push_str("digraph G {\n");
+
+    for node in &graph.nodes {
+        let _ = writeln!(
+            out,
+            "  \"{}\" [label=\"{}\", shape={}];",
+            node.id,
+            escape_dot(&node_label(node)),
+            node_shape(node.node_type.as_deref().unwrap_or("")),
+        );
+    }
+
+    for edge in &graph.edges {
+        let _ = writeln!(
+            out,
+            "  \"{}\" -> \"{}\" [label=\"{}\"];",
+            edge.src,
+            edge.dst,
+            escape_dot(edge.edge_type.as_deref().unwrap_or("")),
+        );
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn node_label(node: &NodeRecord) -> String {
+    node.name.clone()
+        .filter(|n| !n.is_empty())
+        .or_else(|| node.semantic_id.clone())
+        .unwrap_or_else(|| node.id.to_string())
+}
+
+/// Graphviz shape for a node type.
+fn node_shape(node_type: &str) -> &'static str {
+    match node_type {
+        "FUNCTION" | "METHOD" => "box",
+        "CLASS" => "component",
+        "MODULE" => "ellipse",
+        t if t.starts_with("http:") => "diamond",
+        _ => "ellipse",
+    }
+}
+
+/// Escape a string for use inside a double-quoted DOT ID.
+///
+/// Backslashes are doubled so `\n`-style label escapes can't be injected;
+/// real newlines become DOT's `\n` line break.
+fn escape_dot(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::EdgeRecord;
+
+    fn node(id: u128, node_type: &str, name: Option<&str>, semantic_id: Option<&str>) -> NodeRecord {
+        NodeRecord {
+            id,
+            node_type: Some(node_type.to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".to_string(),
+            exported: false,
+            replaces: None,
+            deleted: false,
+            name: name.map(String::from),
+            file: None,
+            metadata: None,
+            semantic_id: semantic_id.map(String::from),
+        }
+    }
+
+    /// Braces outside quoted strings must balance, and every quoted
+    /// string must be closed — the two ways hand-built DOT usually breaks.
+    fn assert_well_formed(dot: &str) {
+        let mut depth = 0i32;
+        let mut in_string = false;
+        let mut chars = dot.chars();
+        while let Some(c) = chars.next() {
+            match (in_string, c) {
+                (true, '\\') => { chars.next(); }
+                (_, '"') => in_string = !in_string,
+                (false, '{') => depth += 1,
+                (false, '}') => depth -= 1,
+                _ => {}
+            }
+            assert!(depth >= 0, "closing brace without opening: {}", dot);
+        }
+        assert!(!in_string, "unterminated quoted string: {}", dot);
+        assert_eq!(depth, 0, "unbalanced braces: {}", dot);
+    }
+
+    #[test]
+    fn test_dot_escapes_labels_and_stays_well_formed() {
+        let graph = ExportSubgraph {
+            nodes: vec![
+                node(1, "FUNCTION", Some("say \"hi\" {x}"), None),
+                node(2, "http:route", Some("GET /a\\b\nnext"), None),
+                node(3, "MODULE", None, Some("MODULE:app@app.js")),
+            ],
+            edges: vec![EdgeRecord {
+                src: 3,
+                dst: 1,
+                edge_type: Some("CONTAINS".to_string()),
+                version: "main".to_string(),
+                metadata: None,
+                deleted: false,
+            }],
+        };
+
+        let dot = to_dot(&graph);
+        assert_well_formed(&dot);
+        assert!(dot.starts_with("digraph G {\n"));
+        assert!(dot.contains(r#""1" [label="say \"hi\" {x}", shape=box];"#));
+        assert!(dot.contains(r#""2" [label="GET /a\\b\nnext", shape=diamond];"#));
+        assert!(dot.contains(r#""3" [label="MODULE:app@app.js", shape=ellipse];"#));
+        assert!(dot.contains(r#""3" -> "1" [label="CONTAINS"];"#));
+    }
+
+    #[test]
+    fn test_dot_label_falls_back_to_id() {
+        let graph = ExportSubgraph {
+            nodes: vec![node(42, "VARIABLE", Some(""), None)],
+            edges: vec![],
+        };
+        let dot = to_dot(&graph);
+        assert_well_formed(&dot);
+        assert!(dot.contains(r#""42" [label="42", shape=ellipse];"#));
+    }
+}
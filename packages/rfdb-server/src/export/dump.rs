@@ -0,0 +1,241 @@
+//! Portable single-file database dump (backups, format migrations)
+//!
+//! A dump is a logical export of live nodes and edges, independent of
+//! segment layout and shard count, plus the database's declared fields,
+//! metadata schemas and rule sets. Layout:
+//!
+//! ```text
+//! "RFDBDUMP" | version: u32 LE | record* | end record
+//! record     = tag: u8 | len: u32 LE | payload[len]
+//! ```
+//!
+//! The metadata (`M`), node (`N`) and edge (`E`) payloads are JSON, so
+//! fields added later are read back with their defaults. Version 1 dumps
+//! have no metadata record. The end record (`Z`) carries the node and
+//! edge counts (u64 LE each) and guards against truncated files.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{GraphError, Result};
+use crate::storage::{EdgeRecord, FieldDecl, MetadataSchema, NodeRecord};
+
+use super::ExportSubgraph;
+
+/// File signature at offset 0.
+pub const MAGIC: &[u8; 8] = b"RFDBDUMP";
+
+/// Current dump format version.
+pub const FORMAT_VERSION: u32 = 2;
+
+const TAG_META: u8 = b'M';
+const TAG_NODE: u8 = b'N';
+const TAG_EDGE: u8 = b'E';
+const TAG_END: u8 = b'Z';
+
+/// Per-database settings carried by a dump alongside the records.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DumpMetadata {
+    #[serde(default)]
+    pub declared_fields: Vec<FieldDecl>,
+    /// Metadata schema per node type
+    #[serde(default)]
+    pub metadata_schemas: BTreeMap<String, MetadataSchema>,
+    /// Rule set name -> Datalog source
+    #[serde(default)]
+    pub rule_sets: BTreeMap<String, String>,
+}
+
+/// Contents of a dump.
+#[derive(Debug, Default)]
+pub struct Dump {
+    pub graph: ExportSubgraph,
+    pub metadata: DumpMetadata,
+}
+
+/// Serialize a graph and its database settings as a dump. Tombstoned
+/// records are skipped.
+pub fn write_dump(graph: &ExportSubgraph, metadata: &DumpMetadata) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    write_record(&mut out, TAG_META, &serde_json::to_vec(metadata)?);
+
+    let mut node_count: u64 = 0;
+    for node in graph.nodes.iter().filter(|n| !n.deleted) {
+        write_record(&mut out, TAG_NODE, &serde_json::to_vec(node)?);
+        node_count += 1;
+    }
+    let mut edge_count: u64 = 0;
+    for edge in graph.edges.iter().filter(|e| !e.deleted) {
+        write_record(&mut out, TAG_EDGE, &serde_json::to_vec(edge)?);
+        edge_count += 1;
+    }
+
+    let mut end = Vec::with_capacity(16);
+    end.extend_from_slice(&node_count.to_le_bytes());
+    end.extend_from_slice(&edge_count.to_le_bytes());
+    write_record(&mut out, TAG_END, &end);
+    Ok(out)
+}
+
+/// Parse a dump produced by [`write_dump`] (current or version 1).
+pub fn read_dump(bytes: &[u8]) -> Result<Dump> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not an RFDB dump"));
+    }
+    let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+    if !(1..=FORMAT_VERSION).contains(&version) {
+        return Err(invalid(&format!("unsupported dump version {}", version)));
+    }
+
+    let mut dump = Dump::default();
+    let graph = &mut dump.graph;
+    loop {
+        let tag = reader.take(1)?[0];
+        let len = u32::from_le_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+        let payload = reader.take(len)?;
+        match tag {
+            TAG_META => dump.metadata = serde_json::from_slice(payload)?,
+            TAG_NODE => graph.nodes.push(serde_json::from_slice::<NodeRecord>(payload)?),
+            TAG_EDGE => graph.edges.push(serde_json::from_slice::<EdgeRecord>(payload)?),
+            TAG_END => {
+                if len != 16 {
+                    return Err(invalid("malformed end record"));
+                }
+                let nodes = u64::from_le_bytes(payload[..8].try_into().unwrap());
+                let edges = u64::from_le_bytes(payload[8..].try_into().unwrap());
+                if nodes != graph.nodes.len() as u64 || edges != graph.edges.len() as u64 {
+                    return Err(invalid("record count mismatch"));
+                }
+                return Ok(dump);
+            }
+            other => return Err(invalid(&format!("unknown record tag 0x{:02x}", other))),
+        }
+    }
+}
+
+fn write_record(out: &mut Vec<u8>, tag: u8, payload: &[u8]) {
+    out.push(tag);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+fn invalid(reason: &str) -> GraphError {
+    GraphError::InvalidFormat(format!("dump: {}", reason))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u128, name: &str, metadata: Option<&str>) -> NodeRecord {
+        NodeRecord {
+            id,
+            node_type: Some("FUNCTION".to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".to_string(),
+            exported: true,
+            replaces: None,
+            deleted: false,
+            name: Some(name.to_string()),
+            file: Some("src/app.js".to_string()),
+            metadata: metadata.map(str::to_string),
+            semantic_id: Some(format!("src/app.js->FUNCTION->{}", name)),
+        }
+    }
+
+    fn edge(src: u128, dst: u128) -> EdgeRecord {
+        EdgeRecord {
+            src,
+            dst,
+            edge_type: Some("CALLS".to_string()),
+            version: "main".to_string(),
+            metadata: Some("{\"line\":3}".to_string()),
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn test_dump_round_trip() {
+        let mut dead = node(3, "gone", None);
+        dead.deleted = true;
+        let graph = ExportSubgraph {
+            nodes: vec![node(u128::MAX, "a", Some("{\"async\":true}")), node(2, "b", None), dead],
+            edges: vec![edge(u128::MAX, 2)],
+        };
+
+        let metadata = DumpMetadata {
+            declared_fields: vec![FieldDecl {
+                name: "async".to_string(),
+                field_type: Default::default(),
+                node_types: None,
+                entity: Default::default(),
+            }],
+            metadata_schemas: BTreeMap::from([("FUNCTION".to_string(), MetadataSchema {
+                required_keys: vec!["async".to_string()],
+                allowed_keys: None,
+            })]),
+            rule_sets: BTreeMap::from([("dead".to_string(), "dead(X) :- node(X, \"FUNCTION\").".to_string())]),
+        };
+
+        let bytes = write_dump(&graph, &metadata).unwrap();
+        assert_eq!(&bytes[..8], MAGIC);
+
+        let Dump { graph: read, metadata: read_metadata } = read_dump(&bytes).unwrap();
+        assert_eq!(read_metadata, metadata);
+        assert_eq!(read.nodes.len(), 2);
+        assert_eq!(read.nodes[0].id, u128::MAX);
+        assert_eq!(read.nodes[0].metadata.as_deref(), Some("{\"async\":true}"));
+        assert_eq!(read.nodes[0].semantic_id.as_deref(), Some("src/app.js->FUNCTION->a"));
+        assert!(read.nodes[0].exported);
+        assert_eq!(read.edges.len(), 1);
+        assert_eq!((read.edges[0].src, read.edges[0].dst), (u128::MAX, 2));
+        assert_eq!(read.edges[0].metadata.as_deref(), Some("{\"line\":3}"));
+    }
+
+    #[test]
+    fn test_read_version_1_dump_without_metadata() {
+        let graph = ExportSubgraph { nodes: vec![node(1, "a", None)], edges: vec![edge(1, 1)] };
+        let mut v1 = Vec::new();
+        v1.extend_from_slice(MAGIC);
+        v1.extend_from_slice(&1u32.to_le_bytes());
+        write_record(&mut v1, TAG_NODE, &serde_json::to_vec(&graph.nodes[0]).unwrap());
+        write_record(&mut v1, TAG_EDGE, &serde_json::to_vec(&graph.edges[0]).unwrap());
+        write_record(&mut v1, TAG_END, &[1u64.to_le_bytes(), 1u64.to_le_bytes()].concat());
+
+        let dump = read_dump(&v1).unwrap();
+        assert_eq!((dump.graph.nodes.len(), dump.graph.edges.len()), (1, 1));
+        assert_eq!(dump.metadata, DumpMetadata::default());
+    }
+
+    #[test]
+    fn test_read_dump_rejects_bad_input() {
+        let graph = ExportSubgraph { nodes: vec![node(1, "a", None)], edges: vec![] };
+        let bytes = write_dump(&graph, &DumpMetadata::default()).unwrap();
+        assert!(read_dump(b"GRAPHML!\x01\x00\x00\x00").is_err());
+        assert!(read_dump(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut future = bytes.clone();
+        future[8] = 99;
+        assert!(read_dump(&future).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! GraphML serialization (Gephi, yEd, NetworkX)
+//!
+//! Node and edge IDs are the decimal u128 IDs used on the wire, so an
+//! exported file can be cross-referenced with `GetNode` results.
+
+use std::fmt::Write;
+
+use super::ExportSubgraph;
+
+/// Serialize a subgraph as a GraphML document.
+///
+/// Declares `<key>` entries for node_type, name, file (nodes) and
+/// edge_type (edges); each node/edge carries one `<data>` element per
+/// attribute it has.
+pub fn to_graphml(graph: &ExportSubgraph) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    out.push_str("  <key id=\"node_type\" for=\"node\" attr.name=\"node_type\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"file\" for=\"node\" attr.name=\"file\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"edge_type\" for=\"edge\" attr.name=\"edge_type\" attr.type=\"string\"/>\n");
+    out.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+
+    for node in &graph.nodes {
+        let _ = writeln!(out, "    <node id=\"{}\">", node.id);
+        write_data(&mut out, "node_type", node.node_type.as_deref());
+        write_data(&mut out, "name", node.name.as_deref());
+        write_data(&mut out, "file", node.file.as_deref());
+        out.push_str("    </node>\n");
+    }
+
+    for edge in &graph.edges {
+        let _ = writeln!(out, "    <edge source=\"{}\" target=\"{}\">", edge.src, edge.dst);
+        write_data(&mut out, "edge_type", edge.edge_type.as_deref());
+        out.push_str("    </edge>\n");
+    }
+
+    out.push_str("  </graph>\n");
+    out.push_str("</graphml>\n");
+    out
+}
+
+fn write_data(out: &mut String, key: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        let _ = writeln!(out, "      <data key=\"{}\">{}</data>", key, escape_xml(value));
+    }
+}
+
+/// Escape the five XML special characters.
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{EdgeRecord, NodeRecord};
+
+    #[test]
+    fn test_graphml_snapshot() {
+        let graph = ExportSubgraph {
+            nodes: vec![
+                NodeRecord {
+                    id: 1,
+                    node_type: Some("FUNCTION".to_string()),
+                    file_id: 0,
+                    name_offset: 0,
+                    version: "main".to_string(),
+                    exported: false,
+                    replaces: None,
+                    deleted: false,
+                    name: Some("a<b>&\"c\"".to_string()),
+                    file: Some("src/app.js".to_string()),
+                    metadata: None,
+                    semantic_id: None,
+                },
+                NodeRecord {
+                    id: 2,
+                    node_type: Some("MODULE".to_string()),
+                    file_id: 0,
+                    name_offset: 0,
+                    version: "main".to_string(),
+                    exported: false,
+                    replaces: None,
+                    deleted: false,
+                    name: None,
+                    file: None,
+                    metadata: None,
+                    semantic_id: None,
+                },
+            ],
+            edges: vec![EdgeRecord {
+                src: 2,
+                dst: 1,
+                edge_type: Some("CONTAINS".to_string()),
+                version: "main".to_string(),
+                metadata: None,
+                deleted: false,
+            }],
+        };
+
+        let expected = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">
+  <key id=\"node_type\" for=\"node\" attr.name=\"node_type\" attr.type=\"string\"/>
+  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>
+  <key id=\"file\" for=\"node\" attr.name=\"file\" attr.type=\"string\"/>
+  <key id=\"edge_type\" for=\"edge\" attr.name=\"edge_type\" attr.type=\"string\"/>
+  <graph id=\"G\" edgedefault=\"directed\">
+    <node id=\"1\">
+      <data key=\"node_type\">FUNCTION</data>
+      <data key=\"name\">a&lt;b&gt;&amp;&quot;c&quot;</data>
+      <data key=\"file\">src/app.js</data>
+    </node>
+    <node id=\"2\">
+      <data key=\"node_type\">MODULE</data>
+    </node>
+    <edge source=\"2\" target=\"1\">
+      <data key=\"edge_type\">CONTAINS</data>
+    </edge>
+  </graph>
+</graphml>
+";
+        assert_eq!(to_graphml(&graph), expected);
+    }
+
+    #[test]
+    fn test_graphml_empty_graph() {
+        let xml = to_graphml(&ExportSubgraph::default());
+        assert!(xml.contains("<graph id=\"G\" edgedefault=\"directed\">"));
+        assert!(!xml.contains("<node "));
+        assert!(xml.ends_with("</graphml>\n"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Graph export formats for external tooling
+//!
+//! Serializes the whole graph (or a subgraph induced by a node set) into
+//! text formats understood by visualization and analysis tools, or into a
+//! binary dump for backups ([`dump`]). Serializers are pure
+//! functions over an [`ExportSubgraph`], so they can be tested without a
+//! running server.
+
+pub mod csv;
+pub mod dot;
+pub mod dump;
+pub mod graphml;
+
+use std::collections::HashSet;
+
+use crate::graph::GraphStore;
+use crate::storage::{EdgeRecord, NodeRecord};
+
+/// Nodes and edges selected for export, in deterministic order.
+#[derive(Debug, Default)]
+pub struct ExportSubgraph {
+    pub nodes: Vec<NodeRecord>,
+    pub edges: Vec<EdgeRecord>,
+}
+
+impl ExportSubgraph {
+    /// Keep only edges whose type matches one of `edge_types`.
+    ///
+    /// Supports wildcard patterns (e.g. "http:*"), same as CountEdgesByType.
+    pub fn retain_edge_types(&mut self, edge_types: &[String]) {
+        self.edges.retain(|e| {
+            let et = e.edge_type.as_deref().unwrap_or("");
+            edge_types.iter().any(|f| match f.strip_suffix('*') {
+                Some(prefix) => et.starts_with(prefix),
+                None => et == f,
+            })
+        });
+    }
+}
+
+/// Collect the nodes and edges to export.
+///
+/// With `node_ids`, returns only those nodes (missing IDs are skipped) and
+/// the edges whose both endpoints are in the set. Without it, returns the
+/// whole graph. Nodes are sorted by ID and edges by (src, dst, type) so
+/// the serialized output is stable across runs.
+pub fn collect_subgraph(engine: &dyn GraphStore, node_ids: Option<&[u128]>) -> ExportSubgraph {
+    let mut nodes: Vec<NodeRecord> = match node_ids {
+        Some(ids) => {
+            let unique: HashSet<u128> = ids.iter().copied().collect();
+            unique.into_iter().filter_map(|id| engine.get_node(id)).collect()
+        }
+        None => engine.all_node_ids().into_iter().filter_map(|id| engine.get_node(id)).collect(),
+    };
+    nodes.sort_by_key(|n| n.id);
+    nodes.dedup_by_key(|n| n.id);
+
+    let mut edges: Vec<EdgeRecord> = match node_ids {
+        Some(_) => {
+            let included: HashSet<u128> = nodes.iter().map(|n| n.id).collect();
+            nodes.iter()
+                .flat_map(|n| engine.get_outgoing_edges(n.id, None))
+                .filter(|e| included.contains(&e.dst))
+                .collect()
+        }
+        None => engine.get_all_edges(),
+    };
+    edges.sort_by(|a, b| {
+        (a.src, a.dst, a.edge_type.as_deref()).cmp(&(b.src, b.dst, b.edge_type.as_deref()))
+    });
+
+    ExportSubgraph { nodes, edges }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::GraphEngineV2;
+
+    fn node(id: u128, node_type: &str, name: &str) -> NodeRecord {
+        NodeRecord {
+            id,
+            node_type: Some(node_type.to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".to_string(),
+            exported: false,
+            replaces: None,
+            deleted: false,
+            name: Some(name.to_string()),
+            file: Some("src/app.js".to_string()),
+            metadata: None,
+            semantic_id: None,
+        }
+    }
+
+    fn edge(src: u128, dst: u128, edge_type: &str) -> EdgeRecord {
+        EdgeRecord {
+            src,
+            dst,
+            edge_type: Some(edge_type.to_string()),
+            version: "main".to_string(),
+            metadata: None,
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn test_collect_subgraph_filters_edges_to_node_set() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.add_nodes(vec![node(1, "FUNCTION", "a"), node(2, "FUNCTION", "b"), node(3, "MODULE", "m")]);
+        engine.add_edges(vec![edge(1, 2, "CALLS"), edge(3, 1, "CONTAINS")], true);
+
+        let full = collect_subgraph(&engine, None);
+        assert_eq!(full.nodes.len(), 3);
+        assert_eq!(full.edges.len(), 2);
+
+        let sub = collect_subgraph(&engine, Some(&[1, 2, 2, 99]));
+        assert_eq!(sub.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(sub.edges.len(), 1);
+        assert_eq!(sub.edges[0].edge_type.as_deref(), Some("CALLS"));
+    }
+
+    #[test]
+    fn test_collect_subgraph_includes_untyped_nodes() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        let untyped = |id: u128| NodeRecord { node_type: None, ..node(id, "", "anon") };
+        engine.add_nodes(vec![node(1, "FUNCTION", "a"), untyped(2), node(3, "FUNCTION", "c")]);
+        engine.compact().unwrap();
+        engine.add_nodes(vec![untyped(4)]);
+        engine.delete_node(3);
+
+        let full = collect_subgraph(&engine, None);
+        assert_eq!(full.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_retain_edge_types_with_wildcard() {
+        let mut graph = ExportSubgraph {
+            nodes: vec![],
+            edges: vec![edge(1, 2, "CALLS"), edge(1, 3, "http:routes_to"), edge(2, 3, "CONTAINS")],
+        };
+        graph.retain_edge_types(&["http:*".to_string(), "CALLS".to_string()]);
+        let types: Vec<_> = graph.edges.iter().map(|e| e.edge_type.clone().unwrap()).collect();
+        assert_eq!(types, vec!["CALLS", "http:routes_to"]);
+    }
+}
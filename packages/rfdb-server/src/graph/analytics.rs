@@ -0,0 +1,147 @@
+//! Whole-graph analytics
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+/// Weakly connected components of the graph formed by `edges`, computed with
+/// union-find. Direction is ignored: `a -> b` joins `a` and `b`.
+///
+/// Only nodes that appear on some edge are included, so isolated nodes are
+/// not reported as singleton components. Each component is sorted, and
+/// components are ordered by their smallest node ID.
+pub fn union_find<I>(edges: I) -> Vec<Vec<u128>>
+where
+    I: IntoIterator<Item = (u128, u128)>,
+{
+    let mut index: HashMap<u128, usize> = HashMap::new();
+    let mut nodes: Vec<u128> = Vec::new();
+    let mut parent: Vec<usize> = Vec::new();
+
+    let mut slot = |id: u128, nodes: &mut Vec<u128>, parent: &mut Vec<usize>| {
+        *index.entry(id).or_insert_with(|| {
+            nodes.push(id);
+            parent.push(parent.len());
+            parent.len() - 1
+        })
+    };
+
+    for (src, dst) in edges {
+        let a = slot(src, &mut nodes, &mut parent);
+        let b = slot(dst, &mut nodes, &mut parent);
+        let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+        if ra != rb {
+            // Smaller root wins so the result does not depend on edge order
+            let (lo, hi) = if ra < rb { (ra, rb) } else { (rb, ra) };
+            parent[hi] = lo;
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<u128>> = HashMap::new();
+    for (i, &id) in nodes.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(id);
+    }
+
+    let mut components: Vec<Vec<u128>> = groups.into_values().collect();
+    for component in &mut components {
+        component.sort_unstable();
+    }
+    components.sort_unstable_by_key(|c| c[0]);
+    components
+}
+
+/// The `n` entries of `degrees` with the highest degree, highest first.
+///
+/// Keeps a min-heap of size `n` instead of sorting every node. Ties are
+/// broken by smaller node ID so the result is deterministic.
+pub fn top_n_by_degree<I>(degrees: I, n: usize) -> Vec<(u128, usize)>
+where
+    I: IntoIterator<Item = (u128, usize)>,
+{
+    if n == 0 {
+        return Vec::new();
+    }
+    // Min-heap on (degree, Reverse(id)): the root is the entry to evict
+    let mut heap: BinaryHeap<Reverse<(usize, Reverse<u128>)>> = BinaryHeap::with_capacity(n + 1);
+    for (id, degree) in degrees {
+        heap.push(Reverse((degree, Reverse(id))));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((degree, Reverse(id)))| (id, degree))
+        .collect()
+}
+
+/// Collapse parallel edges: each distinct key (typically
+/// `(src, dst, edge_type)`) paired with how many times it occurs, in order
+/// of first occurrence.
+pub fn count_parallel_edges<K, I>(edges: I) -> Vec<(K, usize)>
+where
+    K: Hash + Eq + Clone,
+    I: IntoIterator<Item = K>,
+{
+    let mut index: HashMap<K, usize> = HashMap::new();
+    let mut counts: Vec<(K, usize)> = Vec::new();
+    for key in edges {
+        match index.get(&key) {
+            Some(&i) => counts[i].1 += 1,
+            None => {
+                index.insert(key.clone(), counts.len());
+                counts.push((key, 1));
+            }
+        }
+    }
+    counts
+}
+
+/// Root of `i`, halving the path on the way up.
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_union_find_islands_and_bridge() {
+        // Island A: 1 - 2 - 3, island B: 10 - 11
+        let mut edges = vec![(1, 2), (3, 2), (10, 11)];
+        assert_eq!(union_find(edges.clone()), vec![vec![1, 2, 3], vec![10, 11]]);
+
+        // Bridge edge merges both islands
+        edges.push((11, 3));
+        assert_eq!(union_find(edges), vec![vec![1, 2, 3, 10, 11]]);
+    }
+
+    #[test]
+    fn test_top_n_by_degree_bounded_and_ordered() {
+        let degrees = vec![(1, 3), (2, 7), (3, 1), (4, 7), (5, 5)];
+        assert_eq!(top_n_by_degree(degrees.clone(), 3), vec![(2, 7), (4, 7), (5, 5)]);
+        assert_eq!(top_n_by_degree(degrees.clone(), 10).len(), 5);
+        assert!(top_n_by_degree(degrees, 0).is_empty());
+    }
+
+    #[test]
+    fn test_count_parallel_edges() {
+        let calls = |src: u128, dst: u128| (src, dst, "CALLS");
+        let edges = vec![calls(1, 2), calls(1, 2), (1, 2, "READS"), calls(1, 2), calls(2, 1)];
+        assert_eq!(
+            count_parallel_edges(edges),
+            vec![(calls(1, 2), 3), ((1, 2, "READS"), 1), (calls(2, 1), 1)]
+        );
+    }
+
+    #[test]
+    fn test_union_find_empty() {
+        assert!(union_find(Vec::new()).is_empty());
+    }
+}
@@ -7,15 +7,24 @@
 
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::Instant;
 
-use crate::error::Result;
-use crate::storage::{AttrQuery, EdgeRecord, FieldDecl, NodeRecord};
-use crate::storage_v2::manifest::{ManifestStore, SnapshotDiff, SnapshotInfo};
-use crate::storage_v2::multi_shard::MultiShardStore;
+use crate::datalog::RuleSets;
+use crate::error::{GraphError, Result};
+use crate::export::dump::DumpMetadata;
+use crate::storage::{AttrQuery, EdgeRecord, FieldDecl, FieldEntity, FieldType, MetadataSchema, NodeRecord};
+use crate::storage_v2::manifest::{DurabilityMode, ManifestStore, NodeDiff, SnapshotDiff, SnapshotInfo};
+use crate::storage_v2::multi_shard::{DatabaseConfig, MultiShardStore, PreparedCompaction};
+use crate::storage_v2::shard::Shard;
+use crate::storage_v2::shard_planner::RoutingStrategy;
 use crate::storage_v2::resource::{ResourceManager, SystemResources, TuningProfile};
-use crate::storage_v2::compaction::{CompactionConfig, CompactionResult};
-use crate::storage_v2::types::{CommitDelta, EdgeRecordV2, NodeRecordV2};
+use crate::storage_v2::compaction::{CompactionConfig, CompactionEstimate, CompactionResult};
+use crate::storage_v2::explain::QueryPlan;
+use crate::storage_v2::types::{
+    CommitDelta, CompressionKind, EdgeRecordV2, NodeRecordV2, DEFAULT_BLOOM_FP_RATE,
+};
+use super::query_cache::QueryCache;
 use super::{GraphStore, traversal};
 
 /// Fallback shard count when adaptive tuning is bypassed (tests, etc.).
@@ -24,27 +33,27 @@ const DEFAULT_SHARD_COUNT: u16 = 4;
 // ── Type Conversion ────────────────────────────────────────────────
 
 /// Convert v2 node record to v1 (for GraphStore return values).
-fn node_v2_to_v1(v2: &NodeRecordV2) -> NodeRecord {
+fn node_v2_to_v1(v2: NodeRecordV2) -> NodeRecord {
     // Extract `exported` from metadata JSON (v2 stores it there).
     let (exported, clean_metadata) = extract_exported_from_metadata(&v2.metadata);
 
     NodeRecord {
         id: v2.id,
-        node_type: Some(v2.node_type.clone()),
+        node_type: Some(v2.node_type),
         file_id: 0,
         name_offset: 0,
         version: "main".to_string(),
         exported,
         replaces: None,
         deleted: false,
-        name: Some(v2.name.clone()),
-        file: Some(v2.file.clone()),
+        name: Some(v2.name),
+        file: Some(v2.file),
         metadata: if clean_metadata.is_empty() {
             None
         } else {
             Some(clean_metadata)
         },
-        semantic_id: Some(v2.semantic_id.clone()),
+        semantic_id: Some(v2.semantic_id),
     }
 }
 
@@ -115,16 +124,16 @@ fn node_v1_to_v2(v1: &NodeRecord) -> NodeRecordV2 {
 }
 
 /// Convert v2 edge record to v1 (for GraphStore return values).
-fn edge_v2_to_v1(v2: &EdgeRecordV2) -> EdgeRecord {
+fn edge_v2_to_v1(v2: EdgeRecordV2) -> EdgeRecord {
     EdgeRecord {
         src: v2.src,
         dst: v2.dst,
-        edge_type: Some(v2.edge_type.clone()),
+        edge_type: Some(v2.edge_type),
         version: "main".to_string(),
         metadata: if v2.metadata.is_empty() {
             None
         } else {
-            Some(v2.metadata.clone())
+            Some(v2.metadata)
         },
         deleted: false,
     }
@@ -140,6 +149,14 @@ fn edge_v1_to_v2(v1: &EdgeRecord) -> EdgeRecordV2 {
     }
 }
 
+/// A compaction merged by `GraphEngineV2::prepare_compaction` and not yet
+/// installed.
+pub struct PendingCompaction {
+    store: PreparedCompaction,
+    superseded_nodes: usize,
+    superseded_edges: usize,
+}
+
 // ── GraphEngineV2 ──────────────────────────────────────────────────
 
 /// Graph engine backed by v2 sharded columnar storage.
@@ -152,9 +169,7 @@ fn edge_v1_to_v2(v1: &EdgeRecord) -> EdgeRecordV2 {
 pub struct GraphEngineV2 {
     store: MultiShardStore,
     manifest: ManifestStore,
-    #[allow(dead_code)]
     path: Option<PathBuf>,
-    #[allow(dead_code)]
     ephemeral: bool,
     /// Node IDs marked for deletion but not yet flushed.
     pending_tombstone_nodes: HashSet<u128>,
@@ -168,10 +183,56 @@ pub struct GraphEngineV2 {
     superseded_edge_count: usize,
     /// Declared metadata fields for indexing (v1 compat).
     declared_fields: Vec<FieldDecl>,
+    /// Metadata schemas by node type (SetMetadataSchema); types without
+    /// one accept any metadata.
+    metadata_schemas: HashMap<String, MetadataSchema>,
+    /// Named Datalog rule sets (SaveRuleSet), persisted in the database root.
+    rule_sets: RuleSets,
     /// Cached tuning profile — avoids re-probing sysinfo on every write.
     cached_profile: TuningProfile,
     /// Timestamp of last resource re-detection (rate-limits sysinfo calls).
     last_resource_check: Instant,
+    /// `find_by_attr` results (`DatabaseConfig::query_cache_capacity`).
+    /// Every `&mut self` path that can change a query result must call
+    /// `invalidate_query_cache`.
+    query_cache: Option<Mutex<QueryCache>>,
+    /// Bumped by every write that can change what a read returns, flushed
+    /// or not (CommitBatchIfUnchanged). Starts at the manifest version.
+    write_version: u64,
+}
+
+fn new_query_cache(capacity: Option<usize>) -> Option<Mutex<QueryCache>> {
+    capacity.filter(|&c| c > 0).map(|c| Mutex::new(QueryCache::new(c)))
+}
+
+/// Recursively copy `src` into a new directory `dest`, skipping LOCK.
+fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if entry.file_name() != "LOCK" {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Total size in bytes of the files under `path`, recursively.
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
 }
 
 // ── Constructors ────────────────────────────────────────────────────
@@ -182,11 +243,70 @@ impl GraphEngineV2 {
     /// Uses `ResourceManager::auto_tune()` to determine shard count
     /// based on available RAM and CPU cores.
     pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::create_with_compression(path, CompressionKind::None)
+    }
+
+    /// Create a new database on disk whose segments are written with the
+    /// given compression (persisted in `db_config.json`).
+    ///
+    /// Compressed segments are decompressed into memory on open instead of
+    /// being memory-mapped: smaller `.rfdb` directories at the cost of RAM
+    /// and open time.
+    pub fn create_with_compression<P: AsRef<Path>>(
+        path: P,
+        compression: CompressionKind,
+    ) -> Result<Self> {
+        let config = DatabaseConfig {
+            shard_count: ResourceManager::auto_tune().shard_count,
+            compression,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        Self::create_with_config(path, config)
+    }
+
+    /// Create a new database on disk with a fixed number of shards instead
+    /// of the auto-tuned count.
+    pub fn create_with_shard_count<P: AsRef<Path>>(path: P, shard_count: u16) -> Result<Self> {
+        let config = DatabaseConfig {
+            shard_count,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        Self::create_with_config(path, config)
+    }
+
+    /// Create a new database on disk with an explicit `DatabaseConfig`
+    /// (shard count, compression, bloom sizing, optional indexes).
+    pub fn create_with_config<P: AsRef<Path>>(path: P, config: DatabaseConfig) -> Result<Self> {
         let path = path.as_ref();
         std::fs::create_dir_all(path)?;
         let profile = ResourceManager::auto_tune();
-        let store = MultiShardStore::create(path, profile.shard_count)?;
-        let manifest = ManifestStore::create(path)?;
+        let durability = config.durability.unwrap_or_default();
+        let mut store = MultiShardStore::create_with_config(path, config)?;
+        store.set_mmap_advice(store.configured_mmap_advice().unwrap_or(profile.mmap_advice));
+        let manifest = ManifestStore::create_with_config(path, durability)?;
+        let query_cache = new_query_cache(store.configured_query_cache_capacity());
+        let write_version = manifest.current().version;
 
         Ok(Self {
             store,
@@ -198,15 +318,24 @@ impl GraphEngineV2 {
             superseded_node_count: 0,
             superseded_edge_count: 0,
             declared_fields: Vec::new(),
+            metadata_schemas: HashMap::new(),
+            rule_sets: RuleSets::default(),
             cached_profile: profile,
             last_resource_check: Instant::now(),
+            query_cache,
+            write_version,
         })
     }
 
     /// Create an ephemeral (in-memory only) engine for tests.
     pub fn create_ephemeral() -> Self {
+        Self::create_ephemeral_with_shard_count(DEFAULT_SHARD_COUNT)
+    }
+
+    /// Create an ephemeral engine spread over `shard_count` shards.
+    pub fn create_ephemeral_with_shard_count(shard_count: u16) -> Self {
         Self {
-            store: MultiShardStore::ephemeral(DEFAULT_SHARD_COUNT),
+            store: MultiShardStore::ephemeral(shard_count),
             manifest: ManifestStore::ephemeral(),
             path: None,
             ephemeral: true,
@@ -215,16 +344,57 @@ impl GraphEngineV2 {
             superseded_node_count: 0,
             superseded_edge_count: 0,
             declared_fields: Vec::new(),
+            metadata_schemas: HashMap::new(),
+            rule_sets: RuleSets::default(),
             cached_profile: TuningProfile::default(),
             last_resource_check: Instant::now(),
+            query_cache: None,
+            write_version: 0,
         }
     }
 
     /// Open an existing database from disk.
+    ///
+    /// Uses `DatabaseConfig::durability`, `Strict` if unset.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
-        let manifest = ManifestStore::open(path)?;
-        let store = MultiShardStore::open(path, &manifest)?;
+        let durability = DatabaseConfig::read_from(path)?
+            .and_then(|config| config.durability)
+            .unwrap_or_default();
+        Self::open_with_durability(path, durability)
+    }
+
+    /// Open an existing database with an explicit durability mode.
+    ///
+    /// `DurabilityMode::Verified` checks every segment's checksum on open
+    /// and fails with `InvalidFormat` naming the corrupt segment file.
+    pub fn open_with_durability<P: AsRef<Path>>(path: P, durability: DurabilityMode) -> Result<Self> {
+        let path = path.as_ref();
+        let manifest = ManifestStore::open_with_config(path, durability)?;
+        Self::from_manifest(path, manifest)
+    }
+
+    /// Open a read-only view of this database as of a historical version.
+    ///
+    /// The view sees only the segments and tombstones recorded in that
+    /// version's manifest. Its manifest store is detached from disk, so
+    /// nothing written through the view can reach the database; callers
+    /// are expected to treat it as read-only.
+    pub fn snapshot_at(&self, version: u64) -> Result<Self> {
+        let path = match (&self.path, self.ephemeral) {
+            (Some(path), false) => path,
+            _ => {
+                return Err(GraphError::InvalidFormat(
+                    "Cannot read historical snapshot on ephemeral database".to_string(),
+                ))
+            }
+        };
+        let manifest = self.manifest.snapshot_view(version)?;
+        Self::from_manifest(path, manifest)
+    }
+
+    fn from_manifest(path: &Path, manifest: ManifestStore) -> Result<Self> {
+        let mut store = MultiShardStore::open(path, &manifest)?;
 
         // Restore tombstones from manifest so deleted nodes/edges stay deleted.
         let current = manifest.current();
@@ -234,6 +404,10 @@ impl GraphEngineV2 {
             current.tombstoned_edge_keys.iter().cloned().collect();
 
         let profile = ResourceManager::auto_tune();
+        store.set_mmap_advice(store.configured_mmap_advice().unwrap_or(profile.mmap_advice));
+        let rule_sets = RuleSets::read_from(path)?;
+        let query_cache = new_query_cache(store.configured_query_cache_capacity());
+        let write_version = current.version;
 
         Ok(Self {
             store,
@@ -245,8 +419,12 @@ impl GraphEngineV2 {
             superseded_node_count: 0,
             superseded_edge_count: 0,
             declared_fields: Vec::new(),
+            metadata_schemas: HashMap::new(),
+            rule_sets,
             cached_profile: profile,
             last_resource_check: Instant::now(),
+            query_cache,
+            write_version,
         })
     }
 }
@@ -272,12 +450,22 @@ impl GraphEngineV2 {
             .collect()
     }
 
+    /// Config for explicit compactions: every shard with L0 segments
+    /// (threshold 1), throttled by `DatabaseConfig::compaction_max_bytes_per_sec`.
+    fn forced_compaction_config(&self) -> CompactionConfig {
+        CompactionConfig {
+            segment_threshold: 1,
+            max_bytes_per_sec: self.store.compaction_max_bytes_per_sec(),
+            ..Default::default()
+        }
+    }
 }
 
 // ── GraphStore Implementation ───────────────────────────────────────
 
 impl GraphStore for GraphEngineV2 {
     fn add_nodes(&mut self, nodes: Vec<NodeRecord>) {
+        self.note_write();
         let v2_nodes: Vec<NodeRecordV2> = nodes.iter().map(node_v1_to_v2).collect();
         // Re-adding a node in the same session must resurrect it immediately.
         // Without this, delete->add keeps the node hidden until flush.
@@ -297,6 +485,7 @@ impl GraphStore for GraphEngineV2 {
     }
 
     fn delete_node(&mut self, id: u128) {
+        self.note_write();
         // Tombstone the node
         self.pending_tombstone_nodes.insert(id);
 
@@ -323,7 +512,7 @@ impl GraphStore for GraphEngineV2 {
         if self.is_node_tombstoned(id) {
             return None;
         }
-        self.store.get_node(id).map(|v2| node_v2_to_v1(&v2))
+        self.store.get_node(id).map(node_v2_to_v1)
     }
 
     fn node_exists(&self, id: u128) -> bool {
@@ -343,29 +532,14 @@ impl GraphStore for GraphEngineV2 {
     }
 
     fn find_by_attr(&self, query: &AttrQuery) -> Vec<u128> {
-        let node_type_filter = query.node_type.as_deref();
-
-        // Handle wildcard node_type — storage path accepts exact+prefix separately.
-        let (exact_type, wildcard_prefix) = match node_type_filter {
-            Some(t) if t.ends_with('*') => (None, Some(t.trim_end_matches('*'))),
-            other => (other, None),
+        let Some(cache) = &self.query_cache else {
+            return self.find_by_attr_with_shard_counts(query).0;
         };
-
-        let mut ids = self.store.find_node_ids_by_attr(
-            exact_type,
-            wildcard_prefix,
-            query.file.as_deref(),
-            query.name.as_deref(),
-            query.exported,
-            &query.metadata_filters,
-            query.substring_match,
-        );
-
-        if self.pending_tombstone_nodes.is_empty() {
+        if let Some(ids) = cache.lock().unwrap().get(query) {
             return ids;
         }
-
-        ids.retain(|id| !self.is_node_tombstoned(*id));
+        let ids = self.find_by_attr_with_shard_counts(query).0;
+        cache.lock().unwrap().insert(query, ids.clone());
         ids
     }
 
@@ -375,6 +549,17 @@ impl GraphStore for GraphEngineV2 {
         chunk_size: usize,
         callback: &mut dyn FnMut(&[u128]) -> bool,
     ) {
+        // Sorting needs the full result; materialize and chunk it.
+        if query.sorted {
+            let ids = self.find_by_attr(query);
+            for chunk in ids.chunks(chunk_size) {
+                if !callback(chunk) {
+                    break;
+                }
+            }
+            return;
+        }
+
         let node_type_filter = query.node_type.as_deref();
         let (exact_type, wildcard_prefix) = match node_type_filter {
             Some(t) if t.ends_with('*') => (None, Some(t.trim_end_matches('*'))),
@@ -414,6 +599,16 @@ impl GraphStore for GraphEngineV2 {
         }
     }
 
+    fn count_by_attr(&self, query: &AttrQuery) -> usize {
+        // Counts chunk by chunk so the full ID list is never materialized.
+        let mut count = 0;
+        self.find_by_attr_chunked(query, 4096, &mut |ids| {
+            count += ids.len();
+            true
+        });
+        count
+    }
+
     fn find_by_type(&self, node_type: &str) -> Vec<u128> {
         let mut ids = if node_type.ends_with('*') {
             self.store.find_node_ids_by_attr(
@@ -424,7 +619,7 @@ impl GraphStore for GraphEngineV2 {
                 None,
                 &[],
                 false,
-            )
+            ).0
         } else {
             self.store.find_node_ids_by_type(node_type)
         };
@@ -437,7 +632,15 @@ impl GraphStore for GraphEngineV2 {
         ids
     }
 
+    fn all_node_ids(&self) -> Vec<u128> {
+        self.store.all_node_ids()
+            .into_iter()
+            .filter(|&id| !self.is_node_tombstoned(id))
+            .collect()
+    }
+
     fn add_edges(&mut self, edges: Vec<EdgeRecord>, skip_validation: bool) {
+        self.note_write();
         let v2_edges: Vec<EdgeRecordV2> = edges.iter().map(edge_v1_to_v2).collect();
         // Re-adding an edge in the same session must clear any pending tombstone
         // for the same (src, dst, type) triple.
@@ -461,6 +664,7 @@ impl GraphStore for GraphEngineV2 {
     }
 
     fn delete_edge(&mut self, src: u128, dst: u128, edge_type: &str) {
+        self.note_write();
         self.pending_tombstone_edges.insert((
             src,
             dst,
@@ -485,7 +689,7 @@ impl GraphStore for GraphEngineV2 {
     fn get_outgoing_edges(&self, node_id: u128, edge_types: Option<&[&str]>) -> Vec<EdgeRecord> {
         let edges = self.store.get_outgoing_edges(node_id, edge_types);
         self.filter_edges(edges)
-            .iter()
+            .into_iter()
             .map(edge_v2_to_v1)
             .collect()
     }
@@ -493,14 +697,18 @@ impl GraphStore for GraphEngineV2 {
     fn get_incoming_edges(&self, node_id: u128, edge_types: Option<&[&str]>) -> Vec<EdgeRecord> {
         let edges = self.store.get_incoming_edges(node_id, edge_types);
         self.filter_edges(edges)
-            .iter()
+            .into_iter()
             .map(edge_v2_to_v1)
             .collect()
     }
 
+    fn edge_exists(&self, src: u128, dst: u128, edge_type: &str) -> bool {
+        !self.is_edge_tombstoned(src, dst, edge_type) && self.store.edge_exists(src, dst, edge_type)
+    }
+
     fn get_all_edges(&self) -> Vec<EdgeRecord> {
         self.store.iter_all_edges()
-            .iter()
+            .into_iter()
             .filter(|e| !self.is_edge_tombstoned(e.src, e.dst, &e.edge_type))
             .map(edge_v2_to_v1)
             .collect()
@@ -508,7 +716,7 @@ impl GraphStore for GraphEngineV2 {
 
     fn get_edges_by_type(&self, edge_type: &str) -> Vec<EdgeRecord> {
         self.store.get_edges_by_type(edge_type)
-            .iter()
+            .into_iter()
             .filter(|e| !self.is_edge_tombstoned(e.src, e.dst, &e.edge_type))
             .map(edge_v2_to_v1)
             .collect()
@@ -600,16 +808,8 @@ impl GraphStore for GraphEngineV2 {
     }
 
     fn flush(&mut self) -> Result<()> {
-        // Apply pending tombstones to shards before flushing to disk.
-        // This ensures delete_node/delete_edge operations are persisted.
-        if !self.pending_tombstone_nodes.is_empty() || !self.pending_tombstone_edges.is_empty() {
-            self.store.set_tombstones(
-                &self.pending_tombstone_nodes,
-                &self.pending_tombstone_edges,
-            );
-            self.pending_tombstone_nodes.clear();
-            self.pending_tombstone_edges.clear();
-        }
+        self.invalidate_query_cache();
+        self.apply_pending_tombstones();
         self.store.flush_all(&mut self.manifest)?;
         Ok(())
     }
@@ -622,18 +822,37 @@ impl GraphStore for GraphEngineV2 {
         // Force-compact all shards with any L0 segments (threshold=1).
         // The default threshold (4) skips shards with few L0 segments,
         // leaving old L1 + new L0 = double-counted nodes/edges.
-        let config = CompactionConfig { segment_threshold: 1 };
+        let config = self.forced_compaction_config();
         self.store.compact(&mut self.manifest, &config)?;
+        self.invalidate_query_cache();
         // Compaction deduplicates segments — old superseded versions are removed.
         self.superseded_node_count = 0;
         self.superseded_edge_count = 0;
         Ok(())
     }
 
-    /// V2 engine: rebuild_indexes is a no-op (v2 handles indexes differently).
+    fn needs_compaction(&self) -> bool {
+        self.store.needs_compaction(&CompactionConfig::from_profile(&self.cached_profile))
+    }
+
+    /// V2 engine: flush, then rebuild the optional reverse edge index.
     fn rebuild_indexes(&mut self) -> Result<()> {
-        // V2 engine manages indexes internally — full flush is the rebuild.
-        self.flush()
+        // V2 engine manages other indexes internally — full flush is the rebuild.
+        self.flush()?;
+        self.store.rebuild_reverse_edge_index();
+        Ok(())
+    }
+
+    /// V2 engine: flush only the shards buffering nodes of `node_types` or
+    /// edges of `edge_types`, then rebuild the reverse edge index entries
+    /// for those edges and for edges into those nodes. Other shards keep
+    /// their buffered writes.
+    fn rebuild_indexes_for_types(&mut self, node_types: &[String], edge_types: &[String]) -> Result<()> {
+        self.invalidate_query_cache();
+        self.apply_pending_tombstones();
+        self.store.flush_shards_holding(&mut self.manifest, node_types, edge_types)?;
+        self.store.rebuild_reverse_edge_index_for_types(node_types, edge_types);
+        Ok(())
     }
 
     fn node_count(&self) -> usize {
@@ -656,9 +875,14 @@ impl GraphStore for GraphEngineV2 {
         self.superseded_node_count = 0;
         self.superseded_edge_count = 0;
         self.declared_fields.clear();
+        self.metadata_schemas.clear();
+        self.note_write();
     }
 
     fn declare_fields(&mut self, fields: Vec<FieldDecl>) {
+        self.invalidate_query_cache();
+        self.store.set_int_fields(Self::int_field_names(&fields));
+        self.store.set_edge_index_fields(Self::edge_field_names(&fields));
         self.declared_fields = fields;
     }
 
@@ -740,6 +964,351 @@ impl GraphEngineV2 {
         &self.declared_fields
     }
 
+    /// Names of declared `FieldType::Int` fields; these get min/max zone
+    /// map ranges in new node segments.
+    fn int_field_names(fields: &[FieldDecl]) -> Vec<String> {
+        fields.iter()
+            .filter(|f| f.entity == FieldEntity::Node && f.field_type == FieldType::Int)
+            .map(|f| f.name.clone())
+            .collect()
+    }
+
+    /// Names of declared edge metadata fields; these get a metadata index
+    /// in every edge segment.
+    fn edge_field_names(fields: &[FieldDecl]) -> Vec<String> {
+        fields.iter()
+            .filter(|f| f.entity == FieldEntity::Edge)
+            .map(|f| f.name.clone())
+            .collect()
+    }
+
+    /// Live edges whose metadata field `field` equals `value` (compared as
+    /// metadata filters compare: numbers and bools by their JSON text),
+    /// optionally of one edge type.
+    ///
+    /// Uses the per-segment edge metadata index when `field` was declared
+    /// with `FieldEntity::Edge`, and a row scan otherwise.
+    pub fn find_edges_by_metadata(&self, field: &str, value: &str, edge_type: Option<&str>) -> Vec<EdgeRecord> {
+        let edges = self.store.find_edges_by_metadata(field, value, edge_type);
+        self.filter_edges(edges)
+            .into_iter()
+            .map(edge_v2_to_v1)
+            .collect()
+    }
+
+    /// Live edges whose metadata matches every `(field, value)` filter,
+    /// optionally of one edge type. Probes the index of a declared edge
+    /// field when one is filtered on (the first filter otherwise) and
+    /// checks the remaining filters on the probed edges only. No filters
+    /// match nothing.
+    pub fn find_edges_by_metadata_filters(
+        &self,
+        filters: &[(String, String)],
+        edge_type: Option<&str>,
+    ) -> Vec<EdgeRecord> {
+        let probe = filters.iter()
+            .position(|(key, _)| {
+                self.declared_fields.iter().any(|d| d.entity == FieldEntity::Edge && &d.name == key)
+            })
+            .unwrap_or(0);
+        let Some((field, value)) = filters.get(probe) else {
+            return Vec::new();
+        };
+        let rest: Vec<(String, String)> = filters.iter()
+            .enumerate()
+            .filter(|&(i, _)| i != probe)
+            .map(|(_, filter)| filter.clone())
+            .collect();
+        let edges = self.store.find_edges_by_metadata(field, value, edge_type)
+            .into_iter()
+            .filter(|e| Shard::metadata_matches(&e.metadata, &rest))
+            .collect();
+        self.filter_edges(edges)
+            .into_iter()
+            .map(edge_v2_to_v1)
+            .collect()
+    }
+
+    /// Edge segments read row by row by `find_edges_by_metadata` so far.
+    pub fn edge_metadata_segment_scans(&self) -> usize {
+        self.store.edge_metadata_segment_scans()
+    }
+
+    /// Set the metadata schema for `node_type`; an empty schema removes it.
+    pub fn set_metadata_schema(&mut self, node_type: &str, schema: MetadataSchema) {
+        if schema.is_empty() {
+            self.metadata_schemas.remove(node_type);
+        } else {
+            self.metadata_schemas.insert(node_type.to_string(), schema);
+        }
+    }
+
+    /// Check a node's metadata against the schema for its type, if any.
+    /// Returns a description of the violation.
+    pub fn check_metadata_schema(&self, node_type: &str, metadata: &str) -> std::result::Result<(), String> {
+        match self.metadata_schemas.get(node_type) {
+            Some(schema) => schema.check(metadata),
+            None => Ok(()),
+        }
+    }
+
+    /// Save Datalog `source` as rule set `name`, replacing any previous
+    /// one. On-disk databases write it through to `rule_sets.json`.
+    pub fn save_rule_set(&mut self, name: &str, source: &str) -> Result<()> {
+        self.rule_sets.insert(name, source);
+        self.persist_rule_sets()
+    }
+
+    /// Source of the saved rule set `name`.
+    pub fn rule_set(&self, name: &str) -> Option<&str> {
+        self.rule_sets.get(name)
+    }
+
+    /// Names of all saved rule sets, sorted.
+    pub fn rule_set_names(&self) -> Vec<String> {
+        self.rule_sets.names()
+    }
+
+    /// Delete rule set `name`; false if it did not exist.
+    pub fn delete_rule_set(&mut self, name: &str) -> Result<bool> {
+        if !self.rule_sets.remove(name) {
+            return Ok(false);
+        }
+        self.persist_rule_sets()?;
+        Ok(true)
+    }
+
+    fn persist_rule_sets(&self) -> Result<()> {
+        match (&self.path, self.ephemeral) {
+            (Some(path), false) => self.rule_sets.write_to(path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Declared fields, metadata schemas and rule sets, for a database dump.
+    pub fn dump_metadata(&self) -> DumpMetadata {
+        DumpMetadata {
+            declared_fields: self.declared_fields.clone(),
+            metadata_schemas: self.metadata_schemas.iter()
+                .map(|(node_type, schema)| (node_type.clone(), schema.clone()))
+                .collect(),
+            rule_sets: self.rule_set_names().into_iter()
+                .filter_map(|name| self.rule_set(&name).map(|source| (name.clone(), source.to_string())))
+                .collect(),
+        }
+    }
+
+    /// Apply the settings from a database dump, replacing the declared
+    /// fields and adding its schemas and rule sets. Call before adding the
+    /// dump's records so new segments pick up the declared fields.
+    pub fn restore_dump_metadata(&mut self, metadata: DumpMetadata) -> Result<()> {
+        self.declare_fields(metadata.declared_fields);
+        for (node_type, schema) in metadata.metadata_schemas {
+            self.set_metadata_schema(&node_type, schema);
+        }
+        for (name, source) in &metadata.rule_sets {
+            self.rule_sets.insert(name, source);
+        }
+        self.persist_rule_sets()
+    }
+
+    /// `find_by_attr`, plus how many matches each shard contributed as
+    /// `(shard_id, count)` in shard order, for spotting skewed shards.
+    pub fn find_by_attr_with_shard_counts(&self, query: &AttrQuery) -> (Vec<u128>, Vec<(u16, usize)>) {
+        // Handle wildcard node_type — storage path accepts exact+prefix separately.
+        let (exact_type, wildcard_prefix) = match query.node_type.as_deref() {
+            Some(t) if t.ends_with('*') => (None, Some(t.trim_end_matches('*'))),
+            other => (other, None),
+        };
+
+        let (mut ids, mut shard_counts) = self.store.find_node_ids_by_attr(
+            exact_type,
+            wildcard_prefix,
+            query.file.as_deref(),
+            query.name.as_deref(),
+            query.exported,
+            &query.metadata_filters,
+            query.substring_match,
+        );
+
+        if !self.pending_tombstone_nodes.is_empty() {
+            // IDs come grouped by shard, so filter each shard's run and
+            // shrink its count to match.
+            let mut live = Vec::with_capacity(ids.len());
+            let mut start = 0;
+            for (_, count) in &mut shard_counts {
+                let before = live.len();
+                live.extend(ids[start..start + *count].iter().filter(|id| !self.is_node_tombstoned(**id)));
+                start += *count;
+                *count = live.len() - before;
+            }
+            ids = live;
+        }
+        // Shard/segment iteration order changes across flushes; sort once
+        // after the cross-shard merge.
+        if query.sorted {
+            ids.sort_unstable();
+        }
+        (ids, shard_counts)
+    }
+
+    /// `get_node` that also returns soft-deleted nodes, with `deleted`
+    /// set. A deleted node's record survives until compaction drops it.
+    pub fn get_node_including_deleted(&self, id: u128) -> Option<NodeRecord> {
+        if let Some(node) = self.get_node(id) {
+            return Some(node);
+        }
+        let mut node = node_v2_to_v1(self.store.get_node_including_tombstoned(id)?);
+        node.deleted = true;
+        Some(node)
+    }
+
+    /// `find_by_attr` followed by the soft-deleted nodes that match the
+    /// same filters (only those whose records compaction has not dropped).
+    pub fn find_by_attr_including_deleted(&self, query: &AttrQuery) -> Vec<u128> {
+        let mut ids = self.find_by_attr(query);
+        let (exact_type, wildcard_prefix) = match query.node_type.as_deref() {
+            Some(t) if t.ends_with('*') => (None, Some(t.trim_end_matches('*'))),
+            other => (other, None),
+        };
+
+        let mut deleted: Vec<u128> = self.store.tombstoned_node_ids();
+        deleted.extend(self.pending_tombstone_nodes.iter().copied());
+        deleted.sort_unstable();
+        deleted.dedup();
+        for id in deleted {
+            // A re-added node is live again and already in `ids`
+            if self.get_node(id).is_some() {
+                continue;
+            }
+            let Some(node) = self.store.get_node_including_tombstoned(id) else {
+                continue;
+            };
+            if Shard::matches_attr_filters(
+                &node.node_type, &node.file, &node.name, &node.metadata,
+                exact_type, wildcard_prefix,
+                query.file.as_deref(), query.name.as_deref(), query.exported,
+                &query.metadata_filters, query.substring_match,
+            ) {
+                ids.push(id);
+            }
+        }
+        if query.sorted {
+            ids.sort_unstable();
+        }
+        ids
+    }
+
+    /// Node IDs whose integer metadata field `field` lies in `[min, max]`.
+    ///
+    /// Segments written after `field` was declared `FieldType::Int` carry a
+    /// min/max range for it and are skipped when the range cannot overlap.
+    pub fn find_by_int_range(&self, field: &str, min: i64, max: i64) -> Vec<u128> {
+        let mut ids = self.store.find_node_ids_by_int_range(field, min, max);
+        if !self.pending_tombstone_nodes.is_empty() {
+            ids.retain(|id| !self.is_node_tombstoned(*id));
+        }
+        ids
+    }
+
+    /// Up to `limit` node IDs whose name starts with `prefix`
+    /// (case-insensitive), optionally of one node type, in name order.
+    ///
+    /// Uses the per-segment name prefix index rather than a full scan.
+    pub fn autocomplete_names(&self, prefix: &str, node_type: Option<&str>, limit: usize) -> Vec<u128> {
+        if self.pending_tombstone_nodes.is_empty() {
+            return self.store.find_node_ids_by_name_prefix(prefix, node_type, limit);
+        }
+        // Over-fetch by the pending tombstones so the cap still holds after
+        // filtering them out
+        let fetch = limit.saturating_add(self.pending_tombstone_nodes.len());
+        let mut ids = self.store.find_node_ids_by_name_prefix(prefix, node_type, fetch);
+        ids.retain(|id| !self.is_node_tombstoned(*id));
+        ids.truncate(limit);
+        ids
+    }
+
+    /// Groups of live nodes sharing the same non-zero `content_hash`,
+    /// optionally restricted to one node type. Only groups with more than
+    /// one member are returned; each group is sorted, and groups are
+    /// ordered by their smallest node ID.
+    pub fn find_duplicate_content(&self, node_type: Option<&str>) -> Vec<Vec<u128>> {
+        let mut by_hash: HashMap<u64, Vec<u128>> = HashMap::new();
+        for (id, hash) in self.store.node_content_hashes(node_type) {
+            if !self.is_node_tombstoned(id) {
+                by_hash.entry(hash).or_default().push(id);
+            }
+        }
+        let mut groups: Vec<Vec<u128>> = by_hash.into_values()
+            .filter(|group| group.len() > 1)
+            .collect();
+        for group in &mut groups {
+            group.sort_unstable();
+        }
+        groups.sort_unstable_by_key(|g| g[0]);
+        groups
+    }
+
+    /// Node segments scanned (not pruned) by `find_by_int_range` so far.
+    pub fn int_range_segment_scans(&self) -> usize {
+        self.store.int_range_segment_scans()
+    }
+
+    /// Explain how `find_by_attr` evaluates `query`: per-shard access
+    /// paths, candidate counts, and survivors after each filter.
+    pub fn explain_attr_query(&self, query: &AttrQuery) -> QueryPlan {
+        let (exact_type, wildcard_prefix) = match query.node_type.as_deref() {
+            Some(t) if t.ends_with('*') => (None, Some(t.trim_end_matches('*'))),
+            other => (other, None),
+        };
+        let op = if query.substring_match { "~" } else { "=" };
+
+        let mut filters = Vec::new();
+        if let Some(t) = exact_type {
+            filters.push(format!("node_type = {}", t));
+        }
+        if let Some(p) = wildcard_prefix {
+            filters.push(format!("node_type_prefix = {}", p));
+        }
+        if let Some(f) = &query.file {
+            filters.push(format!("file {} {}", op, f));
+        }
+        if let Some(n) = &query.name {
+            filters.push(format!("name {} {}", op, n));
+        }
+        if let Some(e) = query.exported {
+            filters.push(format!("exported = {}", e));
+        }
+        for (key, value) in &query.metadata_filters {
+            filters.push(format!("metadata.{} = {}", key, value));
+        }
+
+        let metadata_fields = query
+            .metadata_filters
+            .iter()
+            .map(|(key, _)| {
+                let declared = self.declared_fields.iter()
+                    .any(|d| d.entity == FieldEntity::Node && &d.name == key);
+                (key.clone(), declared)
+            })
+            .collect();
+
+        QueryPlan {
+            filters,
+            metadata_fields,
+            shards: self.store.explain_attr_query(
+                exact_type,
+                wildcard_prefix,
+                query.file.as_deref(),
+                query.name.as_deref(),
+                query.exported,
+                &query.metadata_filters,
+                query.substring_match,
+            ),
+            result_count: self.find_by_attr(query).len(),
+        }
+    }
+
     /// Atomic batch commit (v2-native API).
     pub fn commit_batch(
         &mut self,
@@ -748,6 +1317,7 @@ impl GraphEngineV2 {
         changed_files: &[String],
         tags: HashMap<String, String>,
     ) -> Result<CommitDelta> {
+        self.note_write();
         let delta = self.store
             .commit_batch(nodes, edges, changed_files, tags, &mut self.manifest)?;
 
@@ -768,13 +1338,100 @@ impl GraphEngineV2 {
     pub fn compact_with_stats(&mut self) -> Result<CompactionResult> {
         // Flush write buffers to L0 first (same reason as compact()).
         self.flush()?;
-        let config = CompactionConfig { segment_threshold: 1 };
+        let config = self.forced_compaction_config();
         let result = self.store.compact(&mut self.manifest, &config)?;
+        self.invalidate_query_cache();
         self.superseded_node_count = 0;
         self.superseded_edge_count = 0;
         Ok(result)
     }
 
+    /// `flush()`, committing the manifest with `durability` instead of the
+    /// mode the database was opened with. `Relaxed` skips the fsyncs: a
+    /// crash may then lose this flush, but not earlier ones.
+    pub fn flush_with_durability(&mut self, durability: DurabilityMode) -> Result<()> {
+        let opened_with = self.manifest.durability();
+        self.manifest.set_durability(durability);
+        let result = self.flush();
+        self.manifest.set_durability(opened_with);
+        result
+    }
+
+    /// Merge segments for `compact()` without modifying the engine.
+    ///
+    /// Call `flush()` first; writes still in the write buffer are not
+    /// merged. Takes `&self`, so the merge can run under a shared lock
+    /// while other readers proceed. Install with `install_compaction`.
+    pub fn prepare_compaction(&self) -> Result<PendingCompaction> {
+        let config = self.forced_compaction_config();
+        Ok(PendingCompaction {
+            store: self.store.prepare_compaction(&self.manifest, &config, None)?,
+            superseded_nodes: self.superseded_node_count,
+            superseded_edges: self.superseded_edge_count,
+        })
+    }
+
+    /// Swap in segments merged by `prepare_compaction` and commit the
+    /// manifest. Returns `Ok(false)`, changing nothing, if a flush or
+    /// delete since the merge made it stale.
+    pub fn install_compaction(&mut self, pending: PendingCompaction) -> Result<bool> {
+        if self.store.install_compaction(&mut self.manifest, pending.store)?.is_none() {
+            return Ok(false);
+        }
+        self.invalidate_query_cache();
+        // Only the superseded copies that existed at merge time are gone;
+        // re-adds since then still shadow a flushed version.
+        self.superseded_node_count = self.superseded_node_count.saturating_sub(pending.superseded_nodes);
+        self.superseded_edge_count = self.superseded_edge_count.saturating_sub(pending.superseded_edges);
+        Ok(true)
+    }
+
+    /// What background compaction would reclaim right now: shards eligible
+    /// under the same policy as `needs_compaction`, without merging.
+    /// Unflushed writes and pending tombstones are not counted.
+    pub fn compaction_estimate(&self) -> CompactionEstimate {
+        self.store.compaction_estimate(&CompactionConfig::from_profile(&self.cached_profile))
+    }
+
+    /// Flush, then compact only `shard_id` (see
+    /// `MultiShardStore::compact_single_shard`).
+    ///
+    /// The superseded node/edge corrections are engine-wide, so unlike
+    /// `compact()` this leaves them in place.
+    pub fn compact_shard(&mut self, shard_id: u16) -> Result<CompactionResult> {
+        self.flush()?;
+        let config = self.forced_compaction_config();
+        let result = self.store.compact_single_shard(&mut self.manifest, &config, shard_id);
+        self.invalidate_query_cache();
+        result
+    }
+
+    /// Flush only `shard_id`'s write buffer (see
+    /// `MultiShardStore::flush_shard`). Returns whether it held anything.
+    ///
+    /// Pending deletes are engine-wide, so unlike `flush()` this leaves
+    /// them for the next full flush.
+    pub fn flush_shard(&mut self, shard_id: u16) -> Result<bool> {
+        self.invalidate_query_cache();
+        self.store.flush_shard(&mut self.manifest, shard_id)
+    }
+
+    /// Flush, then redistribute all data over `shard_count` shards (see
+    /// `MultiShardStore::reshard`).
+    ///
+    /// Deleted records are dropped by the rewrite, so pending tombstones
+    /// and superseded counts are reset.
+    pub fn reshard(&mut self, shard_count: u16) -> Result<()> {
+        self.flush()?;
+        self.store.reshard(&mut self.manifest, shard_count)?;
+        self.invalidate_query_cache();
+        self.pending_tombstone_nodes.clear();
+        self.pending_tombstone_edges.clear();
+        self.superseded_node_count = 0;
+        self.superseded_edge_count = 0;
+        Ok(())
+    }
+
     /// Tag an existing snapshot.
     pub fn tag_snapshot(
         &mut self,
@@ -794,6 +1451,96 @@ impl GraphEngineV2 {
         self.manifest.list_snapshots(filter_tag)
     }
 
+    /// Restore the database to a historical snapshot version.
+    ///
+    /// Unflushed writes and pending tombstones are discarded. The restored
+    /// state is committed as a new manifest version (see
+    /// `ManifestStore::restore_snapshot`), then shards and their in-memory
+    /// indexes are reopened from it. Returns the new current version.
+    pub fn restore_snapshot(&mut self, version: u64) -> Result<u64> {
+        // Ephemeral manifests reject the restore, so path is always set below
+        let new_version = self.manifest.restore_snapshot(version)?;
+        self.note_write();
+        if let Some(path) = &self.path {
+            self.store = MultiShardStore::open(path, &self.manifest)?;
+        }
+
+        let current = self.manifest.current();
+        self.pending_tombstone_nodes = current.tombstoned_node_ids.iter().copied().collect();
+        self.pending_tombstone_edges = current.tombstoned_edge_keys.iter().cloned().collect();
+        self.superseded_node_count = 0;
+        self.superseded_edge_count = 0;
+
+        Ok(new_version)
+    }
+
+    /// Copy this database's files (segments, manifests, config) into
+    /// `dest`, which must not exist yet. The LOCK file is not copied.
+    ///
+    /// Only flushed data is copied; callers flush first and hold off
+    /// writers until the copy returns. Segment files are immutable, so
+    /// concurrent readers are fine.
+    pub fn copy_to_dir(&self, dest: &Path) -> Result<()> {
+        let src = match (&self.path, self.ephemeral) {
+            (Some(path), false) => path,
+            _ => {
+                return Err(GraphError::InvalidFormat(
+                    "Cannot copy files of ephemeral database".to_string(),
+                ))
+            }
+        };
+        copy_dir(src, dest)
+    }
+
+    /// Deep copy of the live graph into a new ephemeral engine.
+    pub fn copy_ephemeral(&self) -> Self {
+        let graph = crate::export::collect_subgraph(self, None);
+        let mut copy = Self::create_ephemeral();
+        copy.declare_fields(self.declared_fields.clone());
+        copy.metadata_schemas = self.metadata_schemas.clone();
+        copy.rule_sets = self.rule_sets.clone();
+        copy.add_nodes(graph.nodes);
+        copy.add_edges(graph.edges, true);
+        copy
+    }
+
+    /// Current manifest version.
+    pub fn current_version(&self) -> u64 {
+        self.manifest.current().version
+    }
+
+    /// Counter bumped by every write, including ones not yet flushed, so
+    /// unlike `current_version` it changes whenever the data may have.
+    /// Flushes and compactions leave it alone.
+    pub fn write_version(&self) -> u64 {
+        self.write_version
+    }
+
+    /// Nodes and edges written since the last flush, as `(nodes, edges)`.
+    pub fn unflushed_counts(&self) -> (usize, usize) {
+        self.store.total_write_buffer_size()
+    }
+
+    /// Segments referenced by the current manifest per level, as
+    /// `[L0, L1, L2]` (node and edge segments together).
+    pub fn segment_counts_by_level(&self) -> [usize; 3] {
+        let m = self.manifest.current();
+        [
+            m.node_segments.len() + m.edge_segments.len(),
+            m.l1_node_segments.len() + m.l1_edge_segments.len(),
+            m.l2_node_segments.len() + m.l2_edge_segments.len(),
+        ]
+    }
+
+    /// Bytes used by the database directory, including segments kept for
+    /// older snapshots. 0 for ephemeral databases.
+    pub fn disk_bytes(&self) -> Result<u64> {
+        match &self.path {
+            Some(path) if !self.ephemeral => dir_size(path),
+            _ => Ok(0),
+        }
+    }
+
     /// Diff two snapshots.
     pub fn diff_snapshots(
         &self,
@@ -803,13 +1550,68 @@ impl GraphEngineV2 {
         self.manifest.diff_snapshots(from_version, to_version)
     }
 
+    /// Node-level diff of two snapshots: IDs added, removed and modified
+    /// (same ID, different content_hash). Each ID list holds at most
+    /// `max_ids` entries; see `NodeDiff::truncated`.
+    pub fn diff_snapshot_nodes(
+        &self,
+        from_version: u64,
+        to_version: u64,
+        max_ids: usize,
+    ) -> Result<NodeDiff> {
+        self.manifest.diff_snapshot_nodes(from_version, to_version, max_ids)
+    }
+
     /// Whether this engine is ephemeral (in-memory only).
     pub fn is_ephemeral(&self) -> bool {
         self.ephemeral
     }
 
+    /// Capacity of the `find_by_attr` cache, or None when it is disabled.
+    pub fn query_cache_capacity(&self) -> Option<usize> {
+        self.query_cache.as_ref().map(|c| c.lock().unwrap().capacity())
+    }
+
+    /// Enable (with the given capacity) or disable the `find_by_attr`
+    /// cache for this session, dropping anything cached so far. The
+    /// persisted setting lives in `DatabaseConfig::query_cache_capacity`.
+    pub fn set_query_cache_capacity(&mut self, capacity: Option<usize>) {
+        self.query_cache = new_query_cache(capacity);
+    }
+
+    /// `find_by_attr` calls answered from the cache since it was enabled.
+    pub fn query_cache_hits(&self) -> u64 {
+        self.query_cache.as_ref().map_or(0, |c| c.lock().unwrap().hits())
+    }
+
     // ── Private helpers ──────────────────────────────────────────────
 
+    /// Record a write that can change query results.
+    fn note_write(&mut self) {
+        self.write_version += 1;
+        self.invalidate_query_cache();
+    }
+
+    /// Drop every cached `find_by_attr` result.
+    fn invalidate_query_cache(&mut self) {
+        if let Some(cache) = &mut self.query_cache {
+            cache.get_mut().unwrap().clear();
+        }
+    }
+
+    /// Apply pending tombstones to shards before flushing to disk.
+    /// This ensures delete_node/delete_edge operations are persisted.
+    fn apply_pending_tombstones(&mut self) {
+        if !self.pending_tombstone_nodes.is_empty() || !self.pending_tombstone_edges.is_empty() {
+            self.store.set_tombstones(
+                &self.pending_tombstone_nodes,
+                &self.pending_tombstone_edges,
+            );
+            self.pending_tombstone_nodes.clear();
+            self.pending_tombstone_edges.clear();
+        }
+    }
+
     /// Auto-flush write buffers if adaptive limits or memory pressure exceeded.
     ///
     /// Probes system resources to determine thresholds. Flushes all shards
@@ -831,10 +1633,12 @@ impl GraphEngineV2 {
             self.last_resource_check = Instant::now();
         }
 
-        // Check if any shard's buffer exceeds the adaptive limits.
+        // Check if any shard's buffer exceeds the configured limits
+        // (DatabaseConfig), falling back to the adaptive ones.
+        let (node_limit, byte_limit) = self.store.auto_flush_limits();
         let exceeds_limits = self.store.any_shard_needs_flush(
-            self.cached_profile.write_buffer_node_limit,
-            self.cached_profile.write_buffer_byte_limit,
+            node_limit.unwrap_or(self.cached_profile.write_buffer_node_limit),
+            byte_limit.unwrap_or(self.cached_profile.write_buffer_byte_limit),
         );
 
         // Under high memory pressure, flush earlier but only if buffer
@@ -888,6 +1692,7 @@ impl GraphEngineV2 {
 mod tests {
     use super::*;
     use crate::storage::FieldType;
+    use crate::storage_v2::types::MmapAdvice;
 
     // ── Helpers ──────────────────────────────────────────────────────
 
@@ -944,7 +1749,7 @@ mod tests {
             metadata: r#"{"async":true}"#.to_string(),
         };
 
-        let v1 = node_v2_to_v1(&v2);
+        let v1 = node_v2_to_v1(v2.clone());
         assert_eq!(v1.id, 42);
         assert_eq!(v1.node_type, Some("FUNCTION".to_string()));
         assert_eq!(v1.name, Some("foo".to_string()));
@@ -991,7 +1796,7 @@ mod tests {
         assert_eq!(v2.content_hash, 0);
 
         // Roundtrip: v1 -> v2 -> v1 preserves exported
-        let back = node_v2_to_v1(&v2);
+        let back = node_v2_to_v1(v2.clone());
         assert!(back.exported, "exported should survive v1->v2->v1 roundtrip");
         assert_eq!(back.metadata, None, "__exported should be stripped from metadata");
     }
@@ -1005,7 +1810,7 @@ mod tests {
             metadata: r#"{"argIndex":0}"#.to_string(),
         };
 
-        let v1 = edge_v2_to_v1(&v2);
+        let v1 = edge_v2_to_v1(v2.clone());
         assert_eq!(v1.src, 1);
         assert_eq!(v1.dst, 2);
         assert_eq!(v1.edge_type, Some("CALLS".to_string()));
@@ -1139,6 +1944,73 @@ mod tests {
         assert!(result.contains(&21));
     }
 
+    #[test]
+    fn test_count_by_attr_matches_find_by_attr() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        let mut nodes = Vec::new();
+        for i in 0..30u128 {
+            let node_type = if i % 3 == 0 { "http:route" } else { "FUNCTION" };
+            let mut node = make_v1_node(100 + i, node_type, &format!("fn{}", i % 5), &format!("src/dir{}/f.js", i % 4));
+            node.exported = i % 2 == 0;
+            if i % 7 == 0 {
+                node.metadata = Some(r#"{"async":true}"#.to_string());
+            }
+            nodes.push(node);
+        }
+        engine.add_nodes(nodes);
+        engine.flush().unwrap();
+        engine.add_nodes(vec![make_v1_node(200, "FUNCTION", "fn0", "src/dir0/f.js")]);
+        engine.delete_node(101);
+
+        let queries = vec![
+            AttrQuery::new(),
+            AttrQuery::new().node_type("FUNCTION"),
+            AttrQuery::new().node_type("http:*"),
+            AttrQuery::new().name("fn0"),
+            AttrQuery::new().node_type("FUNCTION").exported(true),
+            AttrQuery { file: Some("src/dir1/f.js".to_string()), ..AttrQuery::new().exported(false) },
+            AttrQuery::new().metadata_filter("async", "true"),
+            AttrQuery::new().name("nothing"),
+        ];
+        for query in &queries {
+            assert_eq!(engine.count_by_attr(query), engine.find_by_attr(query).len(), "{:?}", query);
+        }
+
+        // Same results once the tombstone is flushed
+        engine.flush().unwrap();
+        for query in &queries {
+            assert_eq!(engine.count_by_attr(query), engine.find_by_attr(query).len(), "{:?}", query);
+        }
+    }
+
+    #[test]
+    fn test_sorted_find_by_attr_stable_across_flush() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        // Two write batches across several directories (shards)
+        for batch in 0..2u128 {
+            engine.add_nodes((0..40u128)
+                .map(|i| make_v1_node(1000 + batch * 100 + (i * 37) % 100, "FUNCTION", "f", &format!("src/dir{}/f.js", i % 8)))
+                .collect());
+            if batch == 0 {
+                engine.flush().unwrap();
+            }
+        }
+
+        let query = AttrQuery::new().node_type("FUNCTION").sorted(true);
+        let before = engine.find_by_attr(&query);
+        assert!(before.windows(2).all(|w| w[0] < w[1]));
+
+        engine.flush().unwrap();
+        assert_eq!(engine.find_by_attr(&query), before);
+
+        let mut chunked = Vec::new();
+        engine.find_by_attr_chunked(&query, 7, &mut |ids| {
+            chunked.extend_from_slice(ids);
+            true
+        });
+        assert_eq!(chunked, before);
+    }
+
     #[test]
     fn test_add_get_edges() {
         let mut engine = GraphEngineV2::create_ephemeral();
@@ -1271,6 +2143,35 @@ mod tests {
         assert!(engine.pending_tombstone_edges.is_empty());
     }
 
+    #[test]
+    fn test_flush_with_durability_controls_fsync() {
+        use crate::storage_v2::manifest::FSYNC_CALLS;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("durability.rfdb");
+        let relaxed = make_v2_node("FUNCTION:relaxed@src/a.js", "FUNCTION", "relaxed", "src/a.js");
+        let strict = make_v2_node("FUNCTION:strict@src/a.js", "FUNCTION", "strict", "src/a.js");
+        let fsyncs = || FSYNC_CALLS.with(|calls| calls.get());
+
+        {
+            let mut engine = GraphEngineV2::create(&db_path).unwrap();
+            engine.store.add_nodes(vec![relaxed.clone()]);
+            let before = fsyncs();
+            engine.flush_with_durability(DurabilityMode::Relaxed).unwrap();
+            assert_eq!(fsyncs(), before, "relaxed flush must not fsync");
+            assert_eq!(engine.manifest.durability(), DurabilityMode::Strict);
+
+            engine.store.add_nodes(vec![strict.clone()]);
+            let before = fsyncs();
+            engine.flush_with_durability(DurabilityMode::Strict).unwrap();
+            assert!(fsyncs() > before, "strict flush must fsync");
+        }
+
+        let engine = GraphEngineV2::open(&db_path).unwrap();
+        assert!(engine.node_exists(relaxed.id));
+        assert!(engine.node_exists(strict.id));
+    }
+
     #[test]
     fn test_commit_batch_v2() {
         let mut engine = GraphEngineV2::create_ephemeral();
@@ -1400,6 +2301,239 @@ mod tests {
         assert_json_eq(edge_metadata, edge.metadata.as_deref().unwrap());
     }
 
+    #[test]
+    fn test_restore_snapshot_discards_later_writes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("test.rfdb");
+
+        let mut engine = GraphEngineV2::create(&db_path).unwrap();
+        engine.add_nodes(vec![make_v1_node(1, "FUNCTION", "a", "src/a.js")]);
+        engine.flush().unwrap();
+        let v1 = engine.current_version();
+        engine.tag_snapshot(v1, HashMap::from([("release".to_string(), "v1".to_string())])).unwrap();
+        let v1_count = engine.node_count();
+
+        engine.add_nodes(vec![make_v1_node(2, "FUNCTION", "b", "src/b.js")]);
+        engine.flush().unwrap();
+        engine.add_nodes(vec![make_v1_node(3, "FUNCTION", "c", "src/c.js")]);
+
+        engine.restore_snapshot(v1).unwrap();
+        assert!(engine.get_node(2).is_none());
+        assert!(engine.get_node(3).is_none());
+        assert_eq!(engine.node_count(), v1_count);
+
+        // New writes descend from the restored state and survive reopen
+        engine.add_nodes(vec![make_v1_node(4, "FUNCTION", "d", "src/d.js")]);
+        engine.flush().unwrap();
+        drop(engine);
+
+        let engine = GraphEngineV2::open(&db_path).unwrap();
+        assert!(engine.get_node(1).is_some());
+        assert!(engine.get_node(2).is_none());
+        assert!(engine.get_node(4).is_some());
+        assert!(matches!(
+            GraphEngineV2::open(&db_path).unwrap().restore_snapshot(999),
+            Err(GraphError::SnapshotNotFound(999))
+        ));
+    }
+
+    #[test]
+    fn test_write_version_moves_on_writes_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("test.rfdb");
+
+        let mut engine = GraphEngineV2::create(&db_path).unwrap();
+        let (start, manifest_start) = (engine.write_version(), engine.current_version());
+        engine.add_nodes(vec![make_v1_node(1, "FUNCTION", "a", "src/a.js")]);
+        let after_add = engine.write_version();
+        assert!(after_add > start);
+        // Unflushed: the manifest has not moved, the write version has
+        assert_eq!(engine.current_version(), manifest_start);
+
+        engine.flush().unwrap();
+        engine.compact().unwrap();
+        assert_eq!(engine.write_version(), after_add);
+
+        engine.delete_node(1);
+        assert!(engine.write_version() > after_add);
+        engine.flush().unwrap();
+        drop(engine);
+
+        let engine = GraphEngineV2::open(&db_path).unwrap();
+        assert_eq!(engine.write_version(), engine.current_version());
+    }
+
+    #[test]
+    fn test_metadata_schema_opt_in_per_type() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.set_metadata_schema("FUNCTION", MetadataSchema {
+            required_keys: vec!["async".to_string()],
+            allowed_keys: Some(vec!["line".to_string()]),
+        });
+
+        assert!(engine.check_metadata_schema("FUNCTION", r#"{"async":true,"line":3}"#).is_ok());
+        let missing = engine.check_metadata_schema("FUNCTION", r#"{"asyncc":true}"#).unwrap_err();
+        assert!(missing.contains("async"), "{}", missing);
+        let disallowed = engine.check_metadata_schema("FUNCTION", r#"{"async":true,"col":1}"#).unwrap_err();
+        assert!(disallowed.contains("col"), "{}", disallowed);
+        assert!(engine.check_metadata_schema("FUNCTION", "").is_err());
+        assert!(engine.check_metadata_schema("FUNCTION", "[1]").is_err());
+
+        // Types without a schema pass freely; an empty schema clears it
+        assert!(engine.check_metadata_schema("CLASS", r#"{"anything":1}"#).is_ok());
+        engine.set_metadata_schema("FUNCTION", MetadataSchema::default());
+        assert!(engine.check_metadata_schema("FUNCTION", "").is_ok());
+    }
+
+    #[test]
+    fn test_rule_sets_survive_reopen() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("test.rfdb");
+        {
+            let mut engine = GraphEngineV2::create(&db_path).unwrap();
+            engine.save_rule_set("keep", "violation(X) :- node(X, \"FUNCTION\").").unwrap();
+            engine.save_rule_set("drop", "violation(X) :- node(X, _).").unwrap();
+            assert!(engine.delete_rule_set("drop").unwrap());
+            assert!(!engine.delete_rule_set("drop").unwrap());
+        }
+
+        let engine = GraphEngineV2::open(&db_path).unwrap();
+        assert_eq!(engine.rule_set_names(), vec!["keep".to_string()]);
+        assert_eq!(engine.rule_set("keep"), Some("violation(X) :- node(X, \"FUNCTION\")."));
+        assert_eq!(engine.rule_set("drop"), None);
+    }
+
+    #[test]
+    fn test_find_duplicate_content_groups_by_hash() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut engine = GraphEngineV2::create(dir.path().join("test.rfdb")).unwrap();
+        let files = ["src/a.js".to_string()];
+
+        let node = |name: &str, hash: u64| NodeRecordV2 {
+            content_hash: hash,
+            ..make_v2_node(&format!("FUNCTION:{}@src/a.js", name), "FUNCTION", name, "src/a.js")
+        };
+        let (copy1, copy2) = (node("copy1", 7), node("copy2", 7));
+        engine.commit_batch(
+            vec![copy1.clone(), copy2.clone(), node("unique", 8), node("unhashed", 0)],
+            vec![],
+            &files,
+            HashMap::new(),
+        ).unwrap();
+
+        let mut expected = vec![copy1.id, copy2.id];
+        expected.sort_unstable();
+        assert_eq!(engine.find_duplicate_content(None), vec![expected.clone()]);
+        assert_eq!(engine.find_duplicate_content(Some("FUNCTION")), vec![expected]);
+        assert!(engine.find_duplicate_content(Some("CLASS")).is_empty());
+
+        // Deleting one copy dissolves the group
+        engine.delete_node(copy2.id);
+        assert!(engine.find_duplicate_content(None).is_empty());
+    }
+
+    #[test]
+    fn test_diff_snapshot_nodes_buckets() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut engine = GraphEngineV2::create(dir.path().join("test.rfdb")).unwrap();
+        let files = ["src/a.js".to_string()];
+
+        let node = |name: &str, hash: u64| NodeRecordV2 {
+            content_hash: hash,
+            ..make_v2_node(&format!("FUNCTION:{}@src/a.js", name), "FUNCTION", name, "src/a.js")
+        };
+        let (edited, kept, deleted, added) =
+            (node("edited", 1), node("kept", 1), node("deleted", 1), node("added", 1));
+
+        engine.commit_batch(vec![edited.clone(), kept.clone(), deleted.clone()], vec![], &files, HashMap::new()).unwrap();
+        let v1 = engine.current_version();
+        engine.commit_batch(vec![node("edited", 2), kept.clone(), added.clone()], vec![], &files, HashMap::new()).unwrap();
+        let v2 = engine.current_version();
+
+        let diff = engine.diff_snapshot_nodes(v1, v2, 100).unwrap();
+        assert_eq!(diff.added, vec![added.id]);
+        assert_eq!(diff.removed, vec![deleted.id]);
+        assert_eq!(diff.modified, vec![edited.id]);
+        assert!(!diff.truncated);
+
+        let reverse = engine.diff_snapshot_nodes(v2, v1, 0).unwrap();
+        assert_eq!(reverse.added_count, 1);
+        assert!(reverse.added.is_empty());
+        assert!(reverse.truncated);
+    }
+
+    #[test]
+    fn test_compressed_database_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("zstd.rfdb");
+
+        {
+            let mut engine = GraphEngineV2::create_with_compression(&db_path, CompressionKind::Zstd).unwrap();
+            engine.add_nodes(vec![make_v1_node(1, "FUNCTION", "a", "src/a.js")]);
+            engine.flush().unwrap();
+        }
+
+        let config = DatabaseConfig::read_from(&db_path).unwrap().unwrap();
+        assert_eq!(config.compression, CompressionKind::Zstd);
+
+        let mut engine = GraphEngineV2::open(&db_path).unwrap();
+        assert_eq!(engine.get_node(1).unwrap().name.as_deref(), Some("a"));
+        engine.add_nodes(vec![make_v1_node(2, "FUNCTION", "b", "src/b.js")]);
+        engine.compact().unwrap();
+        assert_eq!(engine.node_count(), 2);
+        let l1 = &engine.manifest.current().l1_node_segments;
+        assert!(!l1.is_empty());
+        assert!(l1.iter().all(|d| d.compression == CompressionKind::Zstd));
+    }
+
+    #[test]
+    fn test_verified_open_rejects_corrupt_segment() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("test.rfdb");
+        {
+            let mut engine = GraphEngineV2::create(&db_path).unwrap();
+            engine.add_nodes(vec![make_v1_node(1, "FUNCTION", "a", "src/a.js")]);
+            engine.flush().unwrap();
+        }
+
+        let manifest = ManifestStore::open(&db_path).unwrap();
+        let seg_path = manifest.current().node_segments[0].file_path(&db_path);
+        let mut bytes = std::fs::read(&seg_path).unwrap();
+        let last = bytes.len() - 60; // inside the string table
+        bytes[last] ^= 0xFF;
+        std::fs::write(&seg_path, bytes).unwrap();
+
+        let err = GraphEngineV2::open_with_durability(&db_path, DurabilityMode::Verified)
+            .err()
+            .expect("verified open must fail")
+            .to_string();
+        assert!(err.contains("checksum mismatch"), "{}", err);
+        assert!(err.contains("seg_"), "{}", err);
+    }
+
+    #[test]
+    fn test_open_uses_durability_from_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("test.rfdb");
+        let mut engine = GraphEngineV2::create(&db_path).unwrap();
+        engine.add_nodes(vec![make_v1_node(1, "FUNCTION", "a", "src/a.js")]);
+        engine.flush().unwrap();
+        drop(engine);
+
+        let manifest = ManifestStore::open(&db_path).unwrap();
+        let seg_path = manifest.current().node_segments[0].file_path(&db_path);
+        let mut bytes = std::fs::read(&seg_path).unwrap();
+        let last = bytes.len() - 60;
+        bytes[last] ^= 0xFF;
+        std::fs::write(&seg_path, bytes).unwrap();
+
+        let mut config = DatabaseConfig::read_from(&db_path).unwrap().unwrap();
+        config.durability = Some(DurabilityMode::Verified);
+        config.write_to(&db_path).unwrap();
+        let err = GraphEngineV2::open(&db_path).err().expect("verified open must fail").to_string();
+        assert!(err.contains("checksum mismatch"), "{}", err);
+    }
+
     // ── Extra Method Tests ───────────────────────────────────────────
 
     #[test]
@@ -1465,6 +2599,153 @@ mod tests {
         assert_eq!(bwd.len(), 3);
     }
 
+    #[test]
+    fn test_explain_attr_query_declared_vs_undeclared() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        let mut get = make_v1_node(1, "CALL", "app.get", "src/app.js");
+        get.metadata = Some(r#"{"object":"express","method":"get"}"#.to_string());
+        let mut query = make_v1_node(2, "CALL", "db.query", "src/app.js");
+        query.metadata = Some(r#"{"object":"pg","method":"query"}"#.to_string());
+        engine.add_nodes(vec![get, query, make_v1_node(3, "FUNCTION", "main", "src/app.js")]);
+        engine.flush().unwrap();
+        engine.declare_fields(vec![FieldDecl {
+            name: "object".to_string(),
+            field_type: Default::default(),
+            node_types: None,
+            entity: FieldEntity::Node,
+        }]);
+
+        let mut q = AttrQuery::new();
+        q.node_type = Some("CALL".to_string());
+        q.metadata_filters = vec![("object".to_string(), "express".to_string())];
+        let declared = engine.explain_attr_query(&q);
+        assert_eq!(declared.metadata_fields, vec![("object".to_string(), true)]);
+        assert_eq!(declared.result_count, 1);
+        let text = declared.render();
+        assert!(text.contains("Filters: node_type = CALL, metadata.object = express"), "{}", text);
+        assert!(text.contains("Metadata field 'object': declared"), "{}", text);
+        assert!(
+            text.contains("full segment scan, 3 candidates -> node_type 2 -> metadata.object 1"),
+            "{}", text
+        );
+
+        q.metadata_filters = vec![("method".to_string(), "get".to_string())];
+        let text = engine.explain_attr_query(&q).render();
+        assert!(text.contains("Metadata field 'method': undeclared"), "{}", text);
+        assert!(text.contains("-> metadata.method 1"), "{}", text);
+
+        // Zone maps prune the segment for a type it does not contain
+        let mut q = AttrQuery::new();
+        q.node_type = Some("CLASS".to_string());
+        let text = engine.explain_attr_query(&q).render();
+        assert!(text.contains("pruned by zone map, 0 candidates"), "{}", text);
+
+        // After compaction, name lookups go through the L1 by_name index
+        engine.compact().unwrap();
+        let mut q = AttrQuery::new();
+        q.name = Some("main".to_string());
+        let text = engine.explain_attr_query(&q).render();
+        assert!(text.contains("by_name index lookup, 1 candidates -> name 1"), "{}", text);
+    }
+
+    #[test]
+    fn test_find_by_attr_shard_counts_skip_deleted_nodes() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.add_nodes((1..=20).map(|i| make_v1_node(i, "FUNCTION", "f", &format!("src/f{}.js", i % 4))).collect());
+        engine.flush().unwrap();
+        engine.delete_node(3);
+
+        let mut q = AttrQuery::new();
+        q.node_type = Some("FUNCTION".to_string());
+        let (ids, counts) = engine.find_by_attr_with_shard_counts(&q);
+        assert_eq!(ids.len(), 19);
+        assert_eq!(counts.iter().map(|(_, c)| c).sum::<usize>(), 19);
+        assert_eq!(counts.len(), engine.store.shard_count() as usize);
+        assert!(counts.iter().enumerate().all(|(i, (shard, _))| *shard as usize == i));
+    }
+
+    #[test]
+    fn test_query_cache_served_until_next_write() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.set_query_cache_capacity(Some(8));
+        engine.add_nodes((1..=5).map(|i| make_v1_node(i, "FUNCTION", "f", "src/a.js")).collect());
+
+        let mut q = AttrQuery::new();
+        q.node_type = Some("FUNCTION".to_string());
+        q.sorted = true;
+        assert_eq!(engine.find_by_attr(&q), vec![1, 2, 3, 4, 5]);
+        assert_eq!(engine.find_by_attr(&q), vec![1, 2, 3, 4, 5]);
+        assert_eq!(engine.query_cache_hits(), 1);
+
+        engine.add_nodes(vec![make_v1_node(6, "FUNCTION", "g", "src/a.js")]);
+        assert_eq!(engine.find_by_attr(&q), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(engine.query_cache_hits(), 1);
+
+        engine.delete_node(2);
+        assert_eq!(engine.find_by_attr(&q), vec![1, 3, 4, 5, 6]);
+        engine.flush().unwrap();
+        assert_eq!(engine.find_by_attr(&q), vec![1, 3, 4, 5, 6]);
+        assert_eq!(engine.find_by_attr(&q), vec![1, 3, 4, 5, 6]);
+        assert_eq!(engine.query_cache_hits(), 2);
+    }
+
+    #[test]
+    fn test_query_cache_capacity_from_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = DatabaseConfig {
+            shard_count: 2,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: Some(16),
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        let db_path = dir.path().join("cached.rfdb");
+        let engine = GraphEngineV2::create_with_config(&db_path, config).unwrap();
+        assert_eq!(engine.query_cache_capacity(), Some(16));
+        drop(engine);
+
+        let engine = GraphEngineV2::open(&db_path).unwrap();
+        assert_eq!(engine.query_cache_capacity(), Some(16));
+        assert_eq!(GraphEngineV2::create_ephemeral().query_cache_capacity(), None);
+    }
+
+    #[test]
+    fn test_compaction_throttle_from_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = DatabaseConfig {
+            shard_count: 2,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: Some(64 << 20),
+            durability: None,
+        };
+        let db_path = dir.path().join("throttled.rfdb");
+        drop(GraphEngineV2::create_with_config(&db_path, config).unwrap());
+
+        let mut engine = GraphEngineV2::open(&db_path).unwrap();
+        assert_eq!(engine.forced_compaction_config().max_bytes_per_sec, Some(64 << 20));
+        engine.add_nodes(vec![make_v1_node(1, "FUNCTION", "f", "src/a.js")]);
+        engine.compact().unwrap();
+        assert!(engine.node_exists(1));
+        assert_eq!(GraphEngineV2::create_ephemeral().forced_compaction_config().max_bytes_per_sec, None);
+    }
+
     #[test]
     fn test_declare_fields() {
         let mut engine = GraphEngineV2::create_ephemeral();
@@ -1474,12 +2755,105 @@ mod tests {
             name: "async".to_string(),
             field_type: FieldType::Bool,
             node_types: Some(vec!["FUNCTION".to_string()]),
+            entity: FieldEntity::Node,
         }]);
 
         assert_eq!(engine.declared_fields_ref().len(), 1);
         assert_eq!(engine.declared_fields_ref()[0].name, "async");
     }
 
+    #[test]
+    fn test_int_range_query_prunes_segments_by_zone_map() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.declare_fields(vec![FieldDecl {
+            name: "line".to_string(),
+            field_type: FieldType::Int,
+            node_types: None,
+            entity: FieldEntity::Node,
+        }]);
+
+        // Two segments in the same shard with disjoint line ranges
+        let segment = |engine: &mut GraphEngineV2, base: u128, lines: std::ops::Range<i64>| {
+            let nodes: Vec<NodeRecord> = lines
+                .enumerate()
+                .map(|(i, line)| {
+                    let mut node = make_v1_node(base + i as u128, "FUNCTION", "f", "src/app.js");
+                    node.metadata = Some(format!(r#"{{"line":{}}}"#, line));
+                    node
+                })
+                .collect();
+            engine.add_nodes(nodes);
+            engine.flush().unwrap();
+        };
+        segment(&mut engine, 1000, 1..11);
+        segment(&mut engine, 2000, 100..111);
+
+        let mut ids = engine.find_by_int_range("line", 100, 200);
+        ids.sort();
+        assert_eq!(ids, (2000..2011).collect::<Vec<u128>>());
+        assert_eq!(engine.int_range_segment_scans(), 1, "the 1..10 segment must be pruned");
+
+        // A range spanning both segments scans both
+        assert_eq!(engine.find_by_int_range("line", 5, 105).len(), 12);
+        assert_eq!(engine.int_range_segment_scans(), 3);
+    }
+
+    #[test]
+    fn test_edge_metadata_filter_uses_declared_index() {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.declare_fields(vec![FieldDecl {
+            name: "argIndex".to_string(),
+            field_type: FieldType::Int,
+            node_types: None,
+            entity: FieldEntity::Edge,
+        }]);
+
+        engine.add_nodes((1..=5).map(|i| make_v1_node(i, "CALL", "f", "src/app.js")).collect());
+        let edge = |src: u128, dst: u128, metadata: Option<&str>| EdgeRecord {
+            src, dst,
+            edge_type: Some("PASSES_ARGUMENT".to_string()),
+            version: "main".to_string(),
+            metadata: metadata.map(str::to_string),
+            deleted: false,
+        };
+        engine.add_edges(vec![
+            edge(1, 2, Some(r#"{"argIndex":0}"#)),
+            edge(1, 3, Some(r#"{"argIndex":1,"isSpread":true}"#)),
+            edge(2, 3, Some(r#"{"argIndex":0}"#)),
+            edge(3, 4, None),
+            edge(4, 5, Some(r#"{"isSpread":true}"#)),
+        ], false);
+        engine.flush().unwrap();
+
+        let pairs = |edges: Vec<EdgeRecord>| {
+            let mut pairs: Vec<(u128, u128)> = edges.iter().map(|e| (e.src, e.dst)).collect();
+            pairs.sort_unstable();
+            pairs
+        };
+        assert_eq!(pairs(engine.find_edges_by_metadata("argIndex", "0", None)), vec![(1, 2), (2, 3)]);
+        assert_eq!(pairs(engine.find_edges_by_metadata("argIndex", "1", Some("PASSES_ARGUMENT"))), vec![(1, 3)]);
+        assert!(engine.find_edges_by_metadata("argIndex", "0", Some("CALLS")).is_empty());
+        assert_eq!(engine.edge_metadata_segment_scans(), 0);
+
+        // With several filters the declared field is probed, the rest checked
+        let filters = [("isSpread".to_string(), "true".to_string()), ("argIndex".to_string(), "1".to_string())];
+        assert_eq!(pairs(engine.find_edges_by_metadata_filters(&filters, None)), vec![(1, 3)]);
+        assert_eq!(engine.edge_metadata_segment_scans(), 0);
+
+        // A newer version of an edge without the field shadows the indexed
+        // one; the shadow check is a keyed lookup, not a row scan
+        engine.add_edges(vec![edge(2, 3, None)], false);
+        engine.flush().unwrap();
+        assert_eq!(pairs(engine.find_edges_by_metadata("argIndex", "0", None)), vec![(1, 2)]);
+        engine.delete_edge(1, 2, "PASSES_ARGUMENT");
+        assert!(engine.find_edges_by_metadata("argIndex", "0", None).is_empty());
+        assert_eq!(engine.edge_metadata_segment_scans(), 0);
+
+        // Undeclared fields still work, by scanning segment rows
+        assert_eq!(pairs(engine.find_edges_by_metadata("isSpread", "true", None)), vec![(1, 3), (4, 5)]);
+        assert!(engine.edge_metadata_segment_scans() > 0);
+    }
+
     #[test]
     fn test_delete_edge_tombstone() {
         let mut engine = GraphEngineV2::create_ephemeral();
@@ -1670,6 +3044,75 @@ mod tests {
         assert!(!store.any_shard_needs_flush(usize::MAX, 2000));
     }
 
+    #[test]
+    fn test_auto_flush_node_limit_from_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = |node_limit| DatabaseConfig {
+            shard_count: 1,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: node_limit,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        let mut tuned = GraphEngineV2::create_with_config(dir.path().join("tuned.rfdb"), config(Some(5))).unwrap();
+        let mut default = GraphEngineV2::create_with_config(dir.path().join("default.rfdb"), config(None)).unwrap();
+
+        for i in 0..6u128 {
+            tuned.add_nodes(vec![make_v1_node(500 + i, "FUNCTION", "f", "src/a.js")]);
+            default.add_nodes(vec![make_v1_node(500 + i, "FUNCTION", "f", "src/a.js")]);
+        }
+
+        // Tiny limit: the buffer was flushed on the way; default still holds all
+        assert!(tuned.store.total_write_buffer_nodes() < 6);
+        assert_eq!(default.store.total_write_buffer_nodes(), 6);
+        assert_eq!(tuned.node_count(), 6);
+    }
+
+    #[test]
+    fn test_mmap_advice_from_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("advised.rfdb");
+        let config = DatabaseConfig {
+            shard_count: 1,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: Some(MmapAdvice::Random),
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        {
+            let mut engine = GraphEngineV2::create_with_config(&db_path, config).unwrap();
+            assert_eq!(engine.store.mmap_advice(), MmapAdvice::Random);
+            for i in 0..3u128 {
+                engine.add_nodes(vec![make_v1_node(700 + i, "FUNCTION", "f", "src/a.js")]);
+                engine.flush().unwrap();
+            }
+            // Compaction advises its inputs sequentially, then restores
+            engine.compact().unwrap();
+            assert_eq!(engine.store.mmap_advice(), MmapAdvice::Random);
+            assert!(engine.get_node(701).is_some());
+        }
+
+        let engine = GraphEngineV2::open(&db_path).unwrap();
+        assert_eq!(engine.store.mmap_advice(), MmapAdvice::Random);
+        assert_eq!(engine.node_count(), 3);
+    }
+
     // ── flush_data_only No-op ──────────────────────────────────────
 
     #[test]
@@ -2,12 +2,15 @@
 
 pub mod engine_v2;
 pub mod traversal;
+pub mod analytics;
 pub mod id_gen;
+pub mod query_cache;
 
 pub use engine_v2::GraphEngineV2;
 pub use id_gen::{compute_node_id, string_id_to_u128};
 
 use std::any::Any;
+use std::collections::HashMap;
 use crate::storage::{NodeRecord, EdgeRecord, AttrQuery, FieldDecl};
 use crate::storage_v2::ShardDiagnostics;
 use crate::error::Result;
@@ -55,9 +58,49 @@ pub trait GraphStore: Send + Sync {
         }
     }
 
+    /// Count nodes matching an attribute query.
+    ///
+    /// Default implementation materializes the IDs; engines that can count
+    /// during the scan (e.g. GraphEngineV2) override it.
+    fn count_by_attr(&self, query: &AttrQuery) -> usize {
+        self.find_by_attr(query).len()
+    }
+
+    /// Distinct values of metadata field `field` among nodes matching
+    /// `query`, each with its node count, most frequent first (ties by
+    /// value). Nodes without the field are skipped. Values are rendered the
+    /// same way metadata filters compare them, so each one can be fed back
+    /// as a filter.
+    ///
+    /// Metadata has no value index, so this reads every matching node.
+    fn distinct_values(&self, query: &AttrQuery, field: &str) -> Vec<(String, usize)> {
+        let mut tally: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+        self.find_by_attr_chunked(query, 4096, &mut |ids| {
+            for &id in ids {
+                let value = self.get_node(id)
+                    .and_then(|node| node.metadata)
+                    .and_then(|meta| serde_json::from_str::<serde_json::Value>(&meta).ok())
+                    .and_then(|meta| meta.get(field).cloned());
+                let value = match value {
+                    Some(serde_json::Value::String(s)) => s,
+                    Some(serde_json::Value::Null) | None => continue,
+                    Some(other) => other.to_string(),
+                };
+                *tally.entry(value).or_insert(0) += 1;
+            }
+            true
+        });
+        let mut values: Vec<(String, usize)> = tally.into_iter().collect();
+        values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        values
+    }
+
     /// Найти ноды по типу (поддерживает wildcard, e.g., "http:*")
     fn find_by_type(&self, node_type: &str) -> Vec<u128>;
 
+    /// IDs of every live node, typed or not, in no particular order
+    fn all_node_ids(&self) -> Vec<u128>;
+
     // === EDGE OPERATIONS ===
 
     /// Добавить рёбра batch'ом
@@ -78,6 +121,13 @@ pub trait GraphStore: Send + Sync {
     /// Получить ВСЕ рёбра из графа
     fn get_all_edges(&self) -> Vec<EdgeRecord>;
 
+    /// Check whether the edge `src -[edge_type]-> dst` exists
+    fn edge_exists(&self, src: u128, dst: u128, edge_type: &str) -> bool {
+        self.get_outgoing_edges(src, Some(&[edge_type]))
+            .iter()
+            .any(|e| e.dst == dst)
+    }
+
     /// Get edges by type using edge-type index (avoids full scan).
     fn get_edges_by_type(&self, edge_type: &str) -> Vec<EdgeRecord>;
 
@@ -110,9 +160,21 @@ pub trait GraphStore: Send + Sync {
     /// Rebuild all secondary indexes from current segment (called after bulk load).
     fn rebuild_indexes(&mut self) -> Result<()>;
 
+    /// Rebuild only the indexes affected by nodes of `node_types` and edges
+    /// of `edge_types` (e.g. the `changedNodeTypes` / `changedEdgeTypes` of
+    /// a deferred commit). Results for those types match a full rebuild.
+    /// Default: full rebuild.
+    fn rebuild_indexes_for_types(&mut self, _node_types: &[String], _edge_types: &[String]) -> Result<()> {
+        self.rebuild_indexes()
+    }
+
     /// Компактировать delta log в immutable segments
     fn compact(&mut self) -> Result<()>;
 
+    /// Whether enough unmerged data has accumulated for `compact()` to be
+    /// worthwhile. Used by the background compaction scheduler.
+    fn needs_compaction(&self) -> bool { false }
+
     // === STATS ===
 
     /// Количество живых нод (без deleted, с дедупликацией segment/delta)
@@ -190,3 +252,61 @@ pub fn reachability(
         engine.bfs(start, max_depth, edge_types)
     }
 }
+
+/// `reachability` where each edge type adds its cost from `edge_costs`
+/// (1 for unlisted types) to the path instead of 1; `max_depth` bounds
+/// the accumulated cost.
+pub fn reachability_weighted(
+    engine: &dyn GraphStore,
+    start: &[u128],
+    max_depth: usize,
+    edge_types: &[&str],
+    backward: bool,
+    edge_costs: &HashMap<String, u32>,
+) -> Vec<u128> {
+    let cost_of = |edge: &EdgeRecord| {
+        edge.edge_type.as_ref()
+            .and_then(|t| edge_costs.get(t))
+            .map_or(1, |&cost| cost as usize)
+    };
+    // Empty edge_types means all types
+    let types = (!edge_types.is_empty()).then_some(edge_types);
+    traversal::weighted_bfs(start, max_depth, |id| {
+        if backward {
+            engine.get_incoming_edges(id, types)
+                .iter()
+                .map(|e| (e.src, cost_of(e)))
+                .collect()
+        } else {
+            engine.get_outgoing_edges(id, types)
+                .iter()
+                .map(|e| (e.dst, cost_of(e)))
+                .collect()
+        }
+    })
+}
+
+/// Whether `dst` is reachable from `src` within `max_depth` edges of the
+/// given types (all types if empty). Same answer as checking `reachability`
+/// from `src` for `dst`, but searches from both ends.
+pub fn are_connected(
+    engine: &dyn GraphStore,
+    src: u128,
+    dst: u128,
+    max_depth: usize,
+    edge_types: &[&str],
+) -> bool {
+    let incoming_types = if edge_types.is_empty() { None } else { Some(edge_types) };
+    traversal::bidirectional_bfs(
+        src,
+        dst,
+        max_depth,
+        |id| engine.neighbors(id, edge_types),
+        |id| {
+            engine.get_incoming_edges(id, incoming_types)
+                .into_iter()
+                .map(|e| e.src)
+                .collect()
+        },
+    )
+}
@@ -0,0 +1,128 @@
+//! Bounded cache of `find_by_attr` results for `GraphEngineV2`.
+//!
+//! Analysis passes issue the same attribute queries many times between
+//! writes, and each one re-scans the shards. The cache maps a query to the
+//! IDs it returned. Results depend on every node and pending tombstone, so
+//! the engine clears the whole cache on any mutation rather than tracking
+//! which entries a write could affect.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::storage::AttrQuery;
+
+/// LRU map from query to result IDs, evicting the least recently used
+/// entry once `capacity` is reached. Same tick scheme as `NodeCache`.
+#[derive(Debug)]
+pub struct QueryCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<AttrQuery, (Vec<u128>, u64)>,
+    order: BTreeMap<u64, AttrQuery>,
+    hits: u64,
+    misses: u64,
+}
+
+impl QueryCache {
+    /// Create an empty cache holding at most `capacity` results.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Maximum number of cached results.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of cached results.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache holds no results.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lookups answered from the cache since it was created.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups that had to run the query since the cache was created.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Cached result for `query`, marking it most recently used.
+    pub fn get(&mut self, query: &AttrQuery) -> Option<Vec<u128>> {
+        self.tick += 1;
+        let Some((ids, used)) = self.entries.get_mut(query) else {
+            self.misses += 1;
+            return None;
+        };
+        let key = self.order.remove(used).expect("query cache order out of sync");
+        *used = self.tick;
+        self.order.insert(self.tick, key);
+        self.hits += 1;
+        Some(ids.clone())
+    }
+
+    /// Cache the result of `query`, evicting the least recently used
+    /// result if full.
+    pub fn insert(&mut self, query: &AttrQuery, ids: Vec<u128>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some((_, used)) = self.entries.remove(query) {
+            self.order.remove(&used);
+        } else if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, query.clone());
+        self.entries.insert(query.clone(), (ids, self.tick));
+    }
+
+    /// Drop every cached result. Hit and miss counters are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str) -> AttrQuery {
+        AttrQuery { name: Some(name.to_string()), ..AttrQuery::default() }
+    }
+
+    #[test]
+    fn test_hit_miss_and_eviction() {
+        let mut cache = QueryCache::new(2);
+        assert_eq!(cache.get(&query("a")), None);
+        cache.insert(&query("a"), vec![1]);
+        cache.insert(&query("b"), vec![2]);
+        assert_eq!(cache.get(&query("a")), Some(vec![1])); // b is now oldest
+        cache.insert(&query("c"), vec![3]);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&query("b")), None);
+        assert_eq!(cache.get(&query("c")), Some(vec![3]));
+        assert_eq!((cache.hits(), cache.misses()), (2, 2));
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&query("a")), None);
+    }
+}
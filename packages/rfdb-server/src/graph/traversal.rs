@@ -1,6 +1,7 @@
 //! Граф traversal алгоритмы
 
-use std::collections::{HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
 /// BFS traversal от start нод
 pub fn bfs<F>(
@@ -42,6 +43,186 @@ where
     result
 }
 
+/// BFS traversal that keeps distance information.
+///
+/// `levels[d]` holds the nodes first reached at depth `d`; start nodes are
+/// level 0. A node reachable at several depths only appears at the smallest
+/// one. Stops early once a level comes up empty.
+pub fn bfs_levels<F>(
+    start: &[u128],
+    max_depth: usize,
+    mut get_neighbors: F,
+) -> Vec<Vec<u128>>
+where
+    F: FnMut(u128) -> Vec<u128>,
+{
+    let mut visited = HashSet::new();
+    let mut frontier: Vec<u128> = start.iter().copied().filter(|id| visited.insert(*id)).collect();
+    let mut levels = Vec::new();
+
+    while !frontier.is_empty() {
+        let depth = levels.len();
+        let mut next = Vec::new();
+        if depth < max_depth {
+            for &node in &frontier {
+                for neighbor in get_neighbors(node) {
+                    if visited.insert(neighbor) {
+                        next.push(neighbor);
+                    }
+                }
+            }
+        }
+        levels.push(frontier);
+        frontier = next;
+    }
+
+    levels
+}
+
+/// BFS that stops at the first level containing a node for which
+/// `is_target` holds.
+///
+/// Returns every matching node of that level with its depth (start nodes
+/// are depth 0), or None if nothing matches within `max_depth`. Nodes of
+/// the matching level are not expanded, so the rest of the component is
+/// never visited.
+pub fn bfs_until<F, P>(
+    start: &[u128],
+    max_depth: usize,
+    mut get_neighbors: F,
+    mut is_target: P,
+) -> Option<(Vec<u128>, usize)>
+where
+    F: FnMut(u128) -> Vec<u128>,
+    P: FnMut(u128) -> bool,
+{
+    let mut visited = HashSet::new();
+    let mut frontier: Vec<u128> = start.iter().copied().filter(|id| visited.insert(*id)).collect();
+    let mut depth = 0;
+
+    while !frontier.is_empty() {
+        let matches: Vec<u128> = frontier.iter().copied().filter(|&id| is_target(id)).collect();
+        if !matches.is_empty() {
+            return Some((matches, depth));
+        }
+        if depth == max_depth {
+            break;
+        }
+        let mut next = Vec::new();
+        for &node in &frontier {
+            for neighbor in get_neighbors(node) {
+                if visited.insert(neighbor) {
+                    next.push(neighbor);
+                }
+            }
+        }
+        frontier = next;
+        depth += 1;
+    }
+
+    None
+}
+
+/// Cost-aware BFS: every edge adds its own cost to the path instead of 1.
+///
+/// `get_neighbors` returns `(neighbor, edge_cost)` pairs. A node is
+/// reached when the cheapest path to it costs at most `max_cost`; nodes
+/// come out in order of that cost (start nodes first, at cost 0). With
+/// every cost 1 this returns the same set as `bfs`.
+pub fn weighted_bfs<F>(
+    start: &[u128],
+    max_cost: usize,
+    mut get_neighbors: F,
+) -> Vec<u128>
+where
+    F: FnMut(u128) -> Vec<(u128, usize)>,
+{
+    let mut best: HashMap<u128, usize> = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    for &id in start {
+        best.insert(id, 0);
+        heap.push(Reverse((0usize, id)));
+    }
+    let mut visited = HashSet::new();
+    let mut result = Vec::new();
+
+    while let Some(Reverse((cost, node))) = heap.pop() {
+        if !visited.insert(node) {
+            continue;
+        }
+        result.push(node);
+
+        for (neighbor, edge_cost) in get_neighbors(node) {
+            let next = cost.saturating_add(edge_cost);
+            if next > max_cost || visited.contains(&neighbor) {
+                continue;
+            }
+            if best.get(&neighbor).is_none_or(|&known| next < known) {
+                best.insert(neighbor, next);
+                heap.push(Reverse((next, neighbor)));
+            }
+        }
+    }
+
+    result
+}
+
+/// Point-to-point reachability: is there a path `src -> dst` of at most
+/// `max_depth` edges?
+///
+/// Expands one BFS level at a time from `src` (via `fwd_neighbors`) and
+/// from `dst` (via `bwd_neighbors`, i.e. predecessors), always growing the
+/// smaller frontier, and stops as soon as the two meet. On sparse graphs
+/// this touches far fewer nodes than a one-sided BFS from `src`.
+pub fn bidirectional_bfs<F, B>(
+    src: u128,
+    dst: u128,
+    max_depth: usize,
+    mut fwd_neighbors: F,
+    mut bwd_neighbors: B,
+) -> bool
+where
+    F: FnMut(u128) -> Vec<u128>,
+    B: FnMut(u128) -> Vec<u128>,
+{
+    if src == dst {
+        return true;
+    }
+
+    let mut fwd_visited = HashSet::from([src]);
+    let mut bwd_visited = HashSet::from([dst]);
+    let mut fwd_frontier = vec![src];
+    let mut bwd_frontier = vec![dst];
+    // Edges covered so far by both searches together
+    let mut depth = 0;
+
+    while depth < max_depth && !fwd_frontier.is_empty() && !bwd_frontier.is_empty() {
+        let forward = fwd_frontier.len() <= bwd_frontier.len();
+        let (frontier, visited, other_visited) = if forward {
+            (&mut fwd_frontier, &mut fwd_visited, &bwd_visited)
+        } else {
+            (&mut bwd_frontier, &mut bwd_visited, &fwd_visited)
+        };
+
+        let mut next = Vec::new();
+        for &node in frontier.iter() {
+            let neighbors = if forward { fwd_neighbors(node) } else { bwd_neighbors(node) };
+            for neighbor in neighbors {
+                if other_visited.contains(&neighbor) {
+                    return true;
+                }
+                if visited.insert(neighbor) {
+                    next.push(neighbor);
+                }
+            }
+        }
+        *frontier = next;
+        depth += 1;
+    }
+
+    false
+}
+
 /// DFS traversal (для обратной трассировки)
 pub fn dfs<F>(
     start: &[u128],
@@ -80,8 +261,29 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
     use std::collections::HashMap;
 
+    #[test]
+    fn test_weighted_bfs_prunes_by_accumulated_cost() {
+        // 1 -(1)-> 2 -(2)-> 3, 1 -(3)-> 4 -(1)-> 5, 2 -(1)-> 5
+        let edges: HashMap<u128, Vec<(u128, usize)>> = [
+            (1, vec![(2, 1), (4, 3)]),
+            (2, vec![(3, 2), (5, 1)]),
+            (4, vec![(5, 1)]),
+        ]
+        .into_iter()
+        .collect();
+        let neighbors = |id| edges.get(&id).cloned().unwrap_or_default();
+
+        assert_eq!(weighted_bfs(&[1], 0, neighbors), vec![1]);
+        // 5 is reached through 2 at cost 2, before 4 (cost 3)
+        assert_eq!(weighted_bfs(&[1], 2, neighbors), vec![1, 2, 5]);
+        let mut all = weighted_bfs(&[1], 3, neighbors);
+        all.sort();
+        assert_eq!(all, vec![1, 2, 3, 4, 5]);
+    }
+
     #[test]
     fn test_bfs_simple_graph() {
         // Граф: 1 -> 2 -> 3
@@ -128,4 +330,132 @@ mod tests {
         assert_eq!(result.len(), 3);
         assert!(!result.contains(&4));
     }
+
+    #[test]
+    fn test_bfs_levels_layered_graph() {
+        // Граф: 1 -> 2, 1 -> 3, 2 -> 4, 3 -> 4, 4 -> 5, 1 -> 5
+        // 5 достижима на глубине 1 и 3 — должна попасть только в уровень 1
+        let edges: HashMap<u128, Vec<u128>> = [
+            (1, vec![2, 3, 5]),
+            (2, vec![4]),
+            (3, vec![4]),
+            (4, vec![5, 1]),
+            (5, vec![]),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let neighbors = |id| edges.get(&id).cloned().unwrap_or_default();
+
+        let mut levels = bfs_levels(&[1], 10, neighbors);
+        for level in &mut levels {
+            level.sort();
+        }
+        assert_eq!(levels, vec![vec![1], vec![2, 3, 5], vec![4]]);
+
+        // max_depth ограничивает число уровней
+        let levels = bfs_levels(&[1], 1, neighbors);
+        assert_eq!(levels.len(), 2);
+    }
+
+    #[test]
+    fn test_bfs_until_stops_at_first_matching_level() {
+        // 1 -> 2, 1 -> 3, 2 -> 4, 3 -> 5, 4 -> 6, 5 -> 7; цели: 4 и 6
+        let edges: HashMap<u128, Vec<u128>> = [
+            (1, vec![2, 3]),
+            (2, vec![4]),
+            (3, vec![5]),
+            (4, vec![6]),
+            (5, vec![7]),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let expanded = Cell::new(Vec::new());
+        let neighbors = |id| {
+            let mut seen = expanded.take();
+            seen.push(id);
+            expanded.set(seen);
+            edges.get(&id).cloned().unwrap_or_default()
+        };
+        let is_target = |id| id == 4 || id == 6;
+
+        assert_eq!(bfs_until(&[1], 10, neighbors, is_target), Some((vec![4], 2)));
+        // Уровень 2 (4 и 5) не раскрывается
+        let mut seen = expanded.take();
+        seen.sort();
+        assert_eq!(seen, vec![1, 2, 3]);
+
+        assert_eq!(bfs_until(&[1], 1, neighbors, is_target), None);
+        assert_eq!(bfs_until(&[4], 0, neighbors, is_target), Some((vec![4], 0)));
+    }
+
+    /// Forward and backward adjacency of a directed edge list
+    fn adjacency(edges: &[(u128, u128)]) -> (HashMap<u128, Vec<u128>>, HashMap<u128, Vec<u128>>) {
+        let mut fwd: HashMap<u128, Vec<u128>> = HashMap::new();
+        let mut bwd: HashMap<u128, Vec<u128>> = HashMap::new();
+        for &(a, b) in edges {
+            fwd.entry(a).or_default().push(b);
+            bwd.entry(b).or_default().push(a);
+        }
+        (fwd, bwd)
+    }
+
+    #[test]
+    fn test_bidirectional_bfs_matches_bfs() {
+        let graphs: Vec<Vec<(u128, u128)>> = vec![
+            // Цепочка 1 -> 2 -> 3 -> 4 -> 5
+            vec![(1, 2), (2, 3), (3, 4), (4, 5)],
+            // Ромб с циклом и изолированным ребром
+            vec![(1, 2), (1, 3), (2, 4), (3, 4), (4, 1), (6, 7)],
+            // Встречные рёбра: 5 не достижима из 1
+            vec![(1, 2), (3, 2), (4, 3), (5, 4)],
+            // Псевдослучайный разреженный граф
+            (0..40u128).map(|i| (i % 13, (i * 7 + 3) % 17)).collect(),
+        ];
+
+        for edges in &graphs {
+            let (fwd, bwd) = adjacency(edges);
+            let fwd_n = |id| fwd.get(&id).cloned().unwrap_or_default();
+            let bwd_n = |id| bwd.get(&id).cloned().unwrap_or_default();
+            let nodes: HashSet<u128> = edges.iter().flat_map(|&(a, b)| [a, b]).collect();
+
+            for &src in &nodes {
+                for &dst in &nodes {
+                    for max_depth in 0..6 {
+                        let expected = bfs(&[src], max_depth, fwd_n).contains(&dst);
+                        assert_eq!(
+                            bidirectional_bfs(src, dst, max_depth, fwd_n, bwd_n),
+                            expected,
+                            "{} -> {} within {} on {:?}", src, dst, max_depth, edges
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_bfs_visits_fewer_nodes() {
+        // Бинарное дерево глубины 10 от корня 1; ищем путь до одного листа
+        let edges: Vec<(u128, u128)> = (1..1024u128).flat_map(|i| [(i, 2 * i), (i, 2 * i + 1)]).collect();
+        let (fwd, bwd) = adjacency(&edges);
+        let leaf = 1500;
+
+        let bfs_expanded = Cell::new(0);
+        let bidi_expanded = Cell::new(0);
+        let neighbors = |map: &HashMap<u128, Vec<u128>>, id, counter: &Cell<usize>| {
+            counter.set(counter.get() + 1);
+            map.get(&id).cloned().unwrap_or_default()
+        };
+
+        assert!(bfs(&[1], 20, |id| neighbors(&fwd, id, &bfs_expanded)).contains(&leaf));
+        assert!(bidirectional_bfs(
+            1, leaf, 20,
+            |id| neighbors(&fwd, id, &bidi_expanded),
+            |id| neighbors(&bwd, id, &bidi_expanded),
+        ));
+        let (bfs_expanded, bidi_expanded) = (bfs_expanded.get(), bidi_expanded.get());
+        assert!(bidi_expanded * 10 < bfs_expanded, "{} vs {}", bidi_expanded, bfs_expanded);
+    }
 }
@@ -0,0 +1,73 @@
+//! JSON-lines (one JSON object per line) parsing
+//!
+//! Malformed lines are skipped and counted rather than failing the whole
+//! import — external tools that emit JSONL often produce a few bad lines
+//! and losing the rest of a large file over them is worse.
+
+use serde::de::DeserializeOwned;
+
+/// Records decoded from a JSONL document plus the number of rejected lines.
+#[derive(Debug)]
+pub struct JsonlBatch<T> {
+    pub records: Vec<T>,
+    pub errors: u32,
+}
+
+/// Parse each non-blank line of `content` as a `T`.
+///
+/// Blank (whitespace-only) lines are ignored and not counted as errors.
+pub fn parse_jsonl<T: DeserializeOwned>(content: &str) -> JsonlBatch<T> {
+    let mut records = Vec::new();
+    let mut errors = 0u32;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<T>(line) {
+            Ok(record) => records.push(record),
+            Err(_) => errors += 1,
+        }
+    }
+
+    JsonlBatch { records, errors }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        id: String,
+        #[serde(default)]
+        weight: u32,
+    }
+
+    #[test]
+    fn test_parse_jsonl_mixed_lines() {
+        let content = "\
+{\"id\":\"a\",\"weight\":1}
+not json at all
+
+{\"id\":\"b\"}
+{\"weight\":3}
+{\"id\":\"c\",\"weight\":2
+  {\"id\":\"d\",\"weight\":4}  \r
+";
+        let batch: JsonlBatch<Item> = parse_jsonl(content);
+        let ids: Vec<&str> = batch.records.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "d"]);
+        assert_eq!(batch.records[1].weight, 0);
+        assert_eq!(batch.errors, 3, "garbage, missing id, and truncated object");
+    }
+
+    #[test]
+    fn test_parse_jsonl_empty_content() {
+        let batch: JsonlBatch<Item> = parse_jsonl("\n\n");
+        assert!(batch.records.is_empty());
+        assert_eq!(batch.errors, 0);
+    }
+}
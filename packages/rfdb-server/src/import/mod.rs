@@ -0,0 +1,7 @@
+//! Bulk import formats for bootstrapping a database
+//!
+//! Parsers here only decode text into records; converting to engine
+//! records and inserting them is left to the caller, so the same parser
+//! serves both the wire-level import command and offline tooling.
+
+pub mod jsonl;
@@ -45,10 +45,13 @@ pub mod datalog;
 pub mod cypher;
 pub mod database_manager;
 pub mod session;
+pub mod transaction;
 pub mod metrics;
+pub mod export;
+pub mod import;
 
 pub use graph::{GraphStore, GraphEngineV2};
-pub use storage::{NodeRecord, EdgeRecord, AttrQuery, FieldDecl, FieldType};
+pub use storage::{NodeRecord, EdgeRecord, AttrQuery, FieldDecl, FieldEntity, FieldType, MetadataSchema};
 pub use error::{GraphError, Result};
 
 // Re-export основных типов
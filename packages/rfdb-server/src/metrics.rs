@@ -1,16 +1,18 @@
 //! Performance metrics for RFDB server
 //!
 //! Provides lightweight, thread-safe metrics collection with zero-cost
-//! when disabled. Metrics are collected per-server (not per-database)
-//! to track overall system performance.
+//! when disabled. The server keeps one aggregate instance, and each
+//! `Database` owns another so slow queries can be attributed to it.
 //!
 //! # Overview
 //!
 //! This module implements a metrics collection system for tracking:
 //! - Query latencies with percentile calculations (p50, p95, p99)
+//! - Cumulative latency histogram over fixed buckets
 //! - Slow query detection and logging
 //! - Per-operation type statistics
 //! - Flush operation timing
+//! - Background compactions (`--auto-compact`)
 //!
 //! # Design Decisions
 //!
@@ -28,7 +30,6 @@
 //! - To get percentile latency data for performance analysis
 //!
 //! When should you NOT use this module?
-//! - For per-database metrics (this is server-wide)
 //! - For detailed query profiling (use tracing instead)
 //!
 //! # Example
@@ -46,11 +47,13 @@
 //! println!("p50: {}ms", stats.query_p50_ms);
 //! ```
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 use std::time::Instant;
 
+pub mod prometheus;
+
 /// Maximum number of query latencies to retain for percentile calculation.
 ///
 /// This creates a rolling window of the last 1000 queries, which provides
@@ -62,6 +65,12 @@ const LATENCY_WINDOW_SIZE: usize = 1000;
 /// Only the 10 most recent slow queries are kept to avoid unbounded growth.
 const MAX_SLOW_QUERIES: usize = 10;
 
+/// Maximum length in bytes of a slow query's parameter summary.
+///
+/// Longer summaries (e.g. a large Datalog program) are cut at a char
+/// boundary so the slow query buffer stays small.
+pub const MAX_SLOW_QUERY_PARAMS_LEN: usize = 200;
+
 /// Slow query threshold in milliseconds.
 ///
 /// Queries taking longer than this are recorded as "slow" and tracked
@@ -69,10 +78,20 @@ const MAX_SLOW_QUERIES: usize = 10;
 /// slow queries (higher than the existing 50ms debug threshold).
 pub const SLOW_QUERY_THRESHOLD_MS: u64 = 100;
 
+/// Upper bounds (inclusive, ms) of the latency histogram buckets.
+///
+/// A final `+Inf` bucket, reported as `u64::MAX`, catches everything above
+/// the last bound.
+pub const LATENCY_BUCKET_BOUNDS_MS: [u64; 7] = [1, 5, 10, 50, 100, 500, 1000];
+
+/// Number of histogram buckets including the trailing `+Inf` bucket.
+const LATENCY_BUCKET_COUNT: usize = LATENCY_BUCKET_BOUNDS_MS.len() + 1;
+
 /// Thread-safe performance metrics collector.
 ///
 /// This is the main entry point for metrics collection. Create one instance
-/// per server and pass it (wrapped in `Arc`) to all request handlers.
+/// per server and pass it (wrapped in `Arc`) to all request handlers; every
+/// `Database` additionally owns one for its share of the traffic.
 ///
 /// # Thread Safety
 ///
@@ -102,6 +121,10 @@ pub struct Metrics {
     /// Sum of all latencies in window (for average calculation)
     latency_sum_ms: AtomicU64,
 
+    /// Per-bucket query counts since start (not cumulative; see
+    /// LATENCY_BUCKET_BOUNDS_MS). Unlike the window these never evict.
+    latency_buckets: [AtomicU64; LATENCY_BUCKET_COUNT],
+
     // ========================================================================
     // Operation Counters (by type)
     // ========================================================================
@@ -111,6 +134,10 @@ pub struct Metrics {
     /// Sum of latencies by operation type (for per-op averages)
     op_latency_sums: OperationLatencies,
 
+    /// Count per exact operation name. Unlike `op_counts`, operations
+    /// without a dedicated counter are not folded into "Other".
+    operation_counts: Mutex<HashMap<String, u64>>,
+
     // ========================================================================
     // Flush Metrics
     // ========================================================================
@@ -145,6 +172,21 @@ pub struct Metrics {
     /// Number of queries cancelled by client
     cancelled_count: AtomicU64,
 
+    // ========================================================================
+    // Compaction Metrics
+    // ========================================================================
+    /// Number of compactions run by the background scheduler
+    auto_compaction_count: AtomicU64,
+
+    // ========================================================================
+    // Data Quality
+    // ========================================================================
+    /// Nodes written with metadata that is not valid JSON. Such nodes never
+    /// match metadata filters.
+    malformed_metadata_count: AtomicU64,
+    /// Nodes whose u128 ID was already held by a different semantic ID
+    id_collision_count: AtomicU64,
+
     // ========================================================================
     // Timestamps
     // ========================================================================
@@ -206,6 +248,9 @@ pub struct SlowQuery {
     pub duration_ms: u64,
     /// When the query occurred (ms since metrics started)
     pub timestamp_ms: u64,
+    /// Short summary of the request parameters, if the caller gave one
+    /// (truncated to MAX_SLOW_QUERY_PARAMS_LEN bytes)
+    pub params: Option<String>,
 }
 
 /// Snapshot of current metrics (for GetStats response).
@@ -227,6 +272,10 @@ pub struct MetricsSnapshot {
     pub query_p99_ms: u64,
     /// Average query latency over the recent window
     pub query_avg_ms: u64,
+    /// Cumulative latency histogram as (upper_bound_ms, count) pairs in
+    /// ascending bound order; the last bound is `u64::MAX` (+Inf) and its
+    /// count equals `query_count`.
+    pub latency_buckets: Vec<(u64, u64)>,
 
     // Flush stats
     /// Total number of flush operations
@@ -254,9 +303,21 @@ pub struct MetricsSnapshot {
     /// Number of queries cancelled by client
     pub cancelled_count: u64,
 
+    // Compaction stats
+    /// Number of compactions run by the background scheduler
+    pub auto_compaction_count: u64,
+
+    // Data quality
+    /// Nodes written with metadata that is not valid JSON
+    pub malformed_metadata_count: u64,
+    /// Nodes whose u128 ID was already held by a different semantic ID
+    pub id_collision_count: u64,
+
     // Per-operation averages (top 5 by count)
     /// Statistics for the top operations by count
     pub op_stats: Vec<OperationStat>,
+    /// Number of queries per operation name, covering every operation
+    pub operation_counts: HashMap<String, u64>,
 }
 
 /// Statistics for a single operation type.
@@ -332,8 +393,10 @@ impl Metrics {
             slow_query_count: AtomicU64::new(0),
             latencies_ms: Mutex::new(VecDeque::with_capacity(LATENCY_WINDOW_SIZE)),
             latency_sum_ms: AtomicU64::new(0),
+            latency_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
             op_counts: OperationCounters::default(),
             op_latency_sums: OperationLatencies::default(),
+            operation_counts: Mutex::new(HashMap::new()),
             flush_count: AtomicU64::new(0),
             flush_total_ms: AtomicU64::new(0),
             last_flush_ms: AtomicU64::new(0),
@@ -342,6 +405,9 @@ impl Metrics {
             slow_queries: Mutex::new(VecDeque::with_capacity(MAX_SLOW_QUERIES)),
             timed_out_count: AtomicU64::new(0),
             cancelled_count: AtomicU64::new(0),
+            auto_compaction_count: AtomicU64::new(0),
+            malformed_metadata_count: AtomicU64::new(0),
+            id_collision_count: AtomicU64::new(0),
             started_at: Instant::now(),
         }
     }
@@ -352,6 +418,7 @@ impl Metrics {
     /// - Total query count
     /// - Per-operation counters
     /// - Latency window (for percentile calculation)
+    /// - Latency histogram bucket
     /// - Slow query tracking (if duration >= threshold)
     ///
     /// # Arguments
@@ -372,12 +439,30 @@ impl Metrics {
     /// metrics.record_query("Bfs", 15);  // 15ms BFS query
     /// ```
     pub fn record_query(&self, operation: &str, duration_ms: u64) {
+        self.record_query_with_params(operation, duration_ms, None);
+    }
+
+    /// Record a query execution along with a summary of its parameters.
+    ///
+    /// Same as `record_query`, but if the query turns out slow, `params`
+    /// (truncated to MAX_SLOW_QUERY_PARAMS_LEN bytes) is kept with it so the
+    /// slow case can be reproduced.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rfdb::metrics::Metrics;
+    /// let metrics = Metrics::new();
+    /// metrics.record_query_with_params("FindByType", 150, Some("nodeType=FUNCTION"));
+    /// ```
+    pub fn record_query_with_params(&self, operation: &str, duration_ms: u64, params: Option<&str>) {
         // Increment total count
         self.query_count.fetch_add(1, Ordering::Relaxed);
 
         // Update operation-specific counters
         self.increment_op_counter(operation);
         self.add_op_latency(operation, duration_ms);
+        *self.operation_counts.lock().unwrap().entry(operation.to_string()).or_insert(0) += 1;
 
         // Update latency window (mutex-protected)
         {
@@ -394,6 +479,12 @@ impl Metrics {
             self.latency_sum_ms.fetch_add(duration_ms, Ordering::Relaxed);
         }
 
+        let bucket = LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound| duration_ms <= bound)
+            .unwrap_or(LATENCY_BUCKET_COUNT - 1);
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+
         // Track slow queries
         if duration_ms >= SLOW_QUERY_THRESHOLD_MS {
             self.slow_query_count.fetch_add(1, Ordering::Relaxed);
@@ -402,6 +493,7 @@ impl Metrics {
                 operation: operation.to_string(),
                 duration_ms,
                 timestamp_ms: self.started_at.elapsed().as_millis() as u64,
+                params: params.map(truncate_params),
             };
 
             let mut slow_queries = self.slow_queries.lock().unwrap();
@@ -422,6 +514,21 @@ impl Metrics {
         self.cancelled_count.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record a compaction run by the background scheduler.
+    pub fn record_auto_compaction(&self) {
+        self.auto_compaction_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record `count` nodes written with metadata that is not valid JSON.
+    pub fn record_malformed_metadata(&self, count: u64) {
+        self.malformed_metadata_count.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Record `count` nodes whose ID collided with a different semantic ID.
+    pub fn record_id_collisions(&self, count: u64) {
+        self.id_collision_count.fetch_add(count, Ordering::Relaxed);
+    }
+
     /// Record a flush operation.
     ///
     /// This should be called after every flush completes.
@@ -489,6 +596,19 @@ impl Metrics {
             }
         };
 
+        // Histogram (cumulative, Prometheus-style)
+        let mut cumulative = 0u64;
+        let latency_buckets = self
+            .latency_buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                let bound = LATENCY_BUCKET_BOUNDS_MS.get(i).copied().unwrap_or(u64::MAX);
+                (bound, cumulative)
+            })
+            .collect();
+
         // Flush stats
         let flush_count = self.flush_count.load(Ordering::Relaxed);
         let flush_avg = if flush_count > 0 {
@@ -505,6 +625,7 @@ impl Metrics {
 
         // Per-operation stats (top 5 by count)
         let op_stats = self.get_top_operations(5);
+        let operation_counts = self.operation_counts.lock().unwrap().clone();
 
         MetricsSnapshot {
             query_count,
@@ -513,6 +634,7 @@ impl Metrics {
             query_p95_ms: p95,
             query_p99_ms: p99,
             query_avg_ms: avg,
+            latency_buckets,
             flush_count,
             flush_avg_ms: flush_avg,
             last_flush_ms: self.last_flush_ms.load(Ordering::Relaxed),
@@ -522,7 +644,11 @@ impl Metrics {
             uptime_secs: self.started_at.elapsed().as_secs(),
             timed_out_count: self.timed_out_count.load(Ordering::Relaxed),
             cancelled_count: self.cancelled_count.load(Ordering::Relaxed),
+            auto_compaction_count: self.auto_compaction_count.load(Ordering::Relaxed),
+            malformed_metadata_count: self.malformed_metadata_count.load(Ordering::Relaxed),
+            id_collision_count: self.id_collision_count.load(Ordering::Relaxed),
             op_stats,
+            operation_counts,
         }
     }
 
@@ -654,6 +780,19 @@ impl Metrics {
     }
 }
 
+/// Cut `params` to at most MAX_SLOW_QUERY_PARAMS_LEN bytes on a char
+/// boundary.
+fn truncate_params(params: &str) -> String {
+    if params.len() <= MAX_SLOW_QUERY_PARAMS_LEN {
+        return params.to_string();
+    }
+    let mut end = MAX_SLOW_QUERY_PARAMS_LEN;
+    while !params.is_char_boundary(end) {
+        end -= 1;
+    }
+    params[..end].to_string()
+}
+
 impl Default for Metrics {
     fn default() -> Self {
         Self::new()
@@ -773,6 +912,41 @@ mod tests {
         assert_eq!(snap.query_p99_ms, 100);
     }
 
+    #[test]
+    fn test_latency_buckets_cumulative() {
+        let m = Metrics::new();
+
+        for ms in [0, 1, 3, 7, 20, 60, 200, 800, 5000, 5000] {
+            m.record_query("Test", ms);
+        }
+
+        let snap = m.snapshot();
+        let bounds: Vec<u64> = snap.latency_buckets.iter().map(|(b, _)| *b).collect();
+        assert_eq!(bounds, vec![1, 5, 10, 50, 100, 500, 1000, u64::MAX]);
+
+        let counts: Vec<u64> = snap.latency_buckets.iter().map(|(_, c)| *c).collect();
+        assert_eq!(counts, vec![2, 3, 4, 5, 6, 7, 8, 10]);
+        assert!(counts.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(*counts.last().unwrap(), snap.query_count);
+    }
+
+    #[test]
+    fn test_operation_counts_by_name() {
+        let m = Metrics::new();
+
+        for op in ["Bfs", "FindByAttr", "Bfs", "CommitBatch", "Bfs", "CommitBatch"] {
+            m.record_query(op, 1);
+        }
+
+        let snap = m.snapshot();
+        assert_eq!(snap.operation_counts.len(), 3);
+        assert_eq!(snap.operation_counts["Bfs"], 3);
+        assert_eq!(snap.operation_counts["FindByAttr"], 1);
+        // No dedicated counter, but still broken out by name
+        assert_eq!(snap.operation_counts["CommitBatch"], 2);
+        assert_eq!(snap.operation_counts.values().sum::<u64>(), snap.query_count);
+    }
+
     #[test]
     fn test_average_calculation() {
         let m = Metrics::new();
@@ -1072,22 +1246,41 @@ mod tests {
             operation: "Bfs".to_string(),
             duration_ms: 100,
             timestamp_ms: 0,
+            params: None,
         };
         let sq2 = SlowQuery {
             operation: "Bfs".to_string(),
             duration_ms: 100,
             timestamp_ms: 0,
+            params: None,
         };
         let sq3 = SlowQuery {
             operation: "Dfs".to_string(),
             duration_ms: 100,
             timestamp_ms: 0,
+            params: None,
         };
 
         assert_eq!(sq1, sq2);
         assert_ne!(sq1, sq3);
     }
 
+    #[test]
+    fn test_slow_query_params_captured_and_truncated() {
+        let m = Metrics::new();
+
+        m.record_query_with_params("FindByType", 150, Some("nodeType=FUNCTION"));
+        m.record_query_with_params("DatalogQuery", 150, Some(&"é".repeat(MAX_SLOW_QUERY_PARAMS_LEN)));
+        m.record_query_with_params("GetNode", 5, Some("id=1"));
+
+        let snap = m.snapshot();
+        assert_eq!(snap.top_slow_queries.len(), 2);
+        assert_eq!(snap.top_slow_queries[0].params.as_deref(), Some("nodeType=FUNCTION"));
+        let long = snap.top_slow_queries[1].params.as_ref().unwrap();
+        assert!(long.len() <= MAX_SLOW_QUERY_PARAMS_LEN);
+        assert!(long.chars().all(|c| c == 'é'));
+    }
+
     // ========================================================================
     // Timeout/Cancelled Counter Tests (RFD-45)
     // ========================================================================
@@ -0,0 +1,299 @@
+//! Prometheus text exposition format (version 0.0.4)
+//!
+//! Pure formatting over `MetricsSnapshot` plus graph sizes. Each metric
+//! family is written once with its `# HELP` / `# TYPE` header followed by
+//! one sample per source, so a scrape that covers the server-wide metrics
+//! and several databases never repeats a family. Samples from a database
+//! carry a `database` label; per-operation samples add `operation`.
+
+use std::fmt::Write as _;
+
+use super::MetricsSnapshot;
+
+/// Metrics and graph size of one scrape source.
+pub struct PrometheusSource<'a> {
+    /// Database name (None = server-wide aggregate).
+    pub database: Option<&'a str>,
+    /// Query/flush metrics (None when this source has no metrics).
+    pub snapshot: Option<&'a MetricsSnapshot>,
+    /// (node_count, edge_count), when the source is a database.
+    pub graph: Option<(u64, u64)>,
+}
+
+enum Kind {
+    Counter,
+    Gauge,
+    Summary,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Summary => "summary",
+        }
+    }
+}
+
+type Sample = (Vec<(&'static str, String)>, u64);
+
+/// Single snapshot field rendered as one sample per source.
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+    value: fn(&MetricsSnapshot) -> u64,
+}
+
+const SNAPSHOT_FAMILIES: &[Family] = &[
+    Family {
+        name: "rfdb_query_total",
+        help: "Queries processed.",
+        kind: Kind::Counter,
+        value: |s| s.query_count,
+    },
+    Family {
+        name: "rfdb_slow_query_total",
+        help: "Queries slower than the slow query threshold.",
+        kind: Kind::Counter,
+        value: |s| s.slow_query_count,
+    },
+    Family {
+        name: "rfdb_query_timeout_total",
+        help: "Queries that timed out.",
+        kind: Kind::Counter,
+        value: |s| s.timed_out_count,
+    },
+    Family {
+        name: "rfdb_query_cancelled_total",
+        help: "Queries cancelled by the client.",
+        kind: Kind::Counter,
+        value: |s| s.cancelled_count,
+    },
+    Family {
+        name: "rfdb_auto_compaction_total",
+        help: "Compactions run by the background scheduler.",
+        kind: Kind::Counter,
+        value: |s| s.auto_compaction_count,
+    },
+    Family {
+        name: "rfdb_malformed_metadata_total",
+        help: "Nodes written with metadata that is not valid JSON.",
+        kind: Kind::Counter,
+        value: |s| s.malformed_metadata_count,
+    },
+    Family {
+        name: "rfdb_id_collisions_total",
+        help: "Nodes whose ID was already held by a different semantic ID.",
+        kind: Kind::Counter,
+        value: |s| s.id_collision_count,
+    },
+    Family {
+        name: "rfdb_flush_total",
+        help: "Flush operations.",
+        kind: Kind::Counter,
+        value: |s| s.flush_count,
+    },
+    Family {
+        name: "rfdb_last_flush_duration_ms",
+        help: "Duration of the most recent flush in milliseconds.",
+        kind: Kind::Gauge,
+        value: |s| s.last_flush_ms,
+    },
+    Family {
+        name: "rfdb_last_flush_nodes",
+        help: "Nodes written by the most recent flush.",
+        kind: Kind::Gauge,
+        value: |s| s.last_flush_nodes,
+    },
+    Family {
+        name: "rfdb_last_flush_edges",
+        help: "Edges written by the most recent flush.",
+        kind: Kind::Gauge,
+        value: |s| s.last_flush_edges,
+    },
+    Family {
+        name: "rfdb_uptime_seconds",
+        help: "Seconds since metrics collection started.",
+        kind: Kind::Gauge,
+        value: |s| s.uptime_secs,
+    },
+];
+
+/// Render all sources as Prometheus exposition text.
+pub fn render(sources: &[PrometheusSource]) -> String {
+    let mut out = String::new();
+    let base = |src: &PrometheusSource| -> Vec<(&'static str, String)> {
+        src.database.map(|db| vec![("database", db.to_string())]).unwrap_or_default()
+    };
+    let with_snapshot = || sources.iter().filter_map(|s| s.snapshot.map(|snap| (s, snap)));
+
+    for family in SNAPSHOT_FAMILIES {
+        let samples: Vec<Sample> = with_snapshot()
+            .map(|(src, snap)| (base(src), (family.value)(snap)))
+            .collect();
+        write_family(&mut out, family.name, family.help, &family.kind, &samples);
+    }
+
+    let quantiles: Vec<Sample> = with_snapshot()
+        .flat_map(|(src, snap)| {
+            [("0.5", snap.query_p50_ms), ("0.95", snap.query_p95_ms), ("0.99", snap.query_p99_ms)]
+                .into_iter()
+                .map(move |(q, v)| {
+                    let mut labels = base(src);
+                    labels.push(("quantile", q.to_string()));
+                    (labels, v)
+                })
+        })
+        .collect();
+    write_family(
+        &mut out,
+        "rfdb_query_duration_ms",
+        "Query latency quantiles over the recent window, in milliseconds.",
+        &Kind::Summary,
+        &quantiles,
+    );
+
+    let op_label = |src: &PrometheusSource, op: &str| {
+        let mut labels = base(src);
+        labels.push(("operation", op.to_string()));
+        labels
+    };
+    let op_counts: Vec<Sample> = with_snapshot()
+        .flat_map(|(src, snap)| snap.op_stats.iter().map(move |op| (op_label(src, &op.operation), op.count)))
+        .collect();
+    write_family(
+        &mut out,
+        "rfdb_operation_total",
+        "Requests per operation (top operations by count).",
+        &Kind::Counter,
+        &op_counts,
+    );
+    let op_avgs: Vec<Sample> = with_snapshot()
+        .flat_map(|(src, snap)| snap.op_stats.iter().map(move |op| (op_label(src, &op.operation), op.avg_ms)))
+        .collect();
+    write_family(
+        &mut out,
+        "rfdb_operation_duration_avg_ms",
+        "Average latency per operation in milliseconds.",
+        &Kind::Gauge,
+        &op_avgs,
+    );
+
+    let graphs = || sources.iter().filter_map(|s| s.graph.map(|g| (s, g)));
+    let nodes: Vec<Sample> = graphs().map(|(src, (n, _))| (base(src), n)).collect();
+    write_family(&mut out, "rfdb_node_count", "Nodes in the graph.", &Kind::Gauge, &nodes);
+    let edges: Vec<Sample> = graphs().map(|(src, (_, e))| (base(src), e)).collect();
+    write_family(&mut out, "rfdb_edge_count", "Edges in the graph.", &Kind::Gauge, &edges);
+
+    out
+}
+
+fn write_family(out: &mut String, name: &str, help: &str, kind: &Kind, samples: &[Sample]) {
+    if samples.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind.as_str());
+    for (labels, value) in samples {
+        out.push_str(name);
+        if !labels.is_empty() {
+            out.push('{');
+            for (i, (key, val)) in labels.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{}=\"{}\"", key, escape_label(val));
+            }
+            out.push('}');
+        }
+        let _ = writeln!(out, " {}", value);
+    }
+}
+
+/// Escape a label value: backslash, double quote and newline.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use std::collections::HashSet;
+
+    /// Minimal exposition-format check: every sample belongs to a family
+    /// declared by a preceding HELP + TYPE pair, and no family is declared twice.
+    fn assert_valid_exposition(text: &str) {
+        let mut declared: HashSet<String> = HashSet::new();
+        let mut current: Option<String> = None;
+        let mut pending_help: Option<String> = None;
+        let mut seen_samples: HashSet<String> = HashSet::new();
+
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# HELP ") {
+                let name = rest.split(' ').next().unwrap().to_string();
+                pending_help = Some(name);
+            } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let mut parts = rest.split(' ');
+                let name = parts.next().unwrap().to_string();
+                let kind = parts.next().unwrap();
+                assert!(["counter", "gauge", "summary", "histogram", "untyped"].contains(&kind));
+                assert_eq!(pending_help.take().as_deref(), Some(name.as_str()), "TYPE without HELP: {}", line);
+                assert!(declared.insert(name.clone()), "duplicate TYPE for {}", name);
+                current = Some(name);
+            } else {
+                let (series, value) = line.rsplit_once(' ').expect("sample needs a value");
+                value.parse::<f64>().expect("numeric sample value");
+                let name = series.split('{').next().unwrap();
+                assert!(
+                    name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                    "bad metric name {}", name
+                );
+                assert_eq!(current.as_deref(), Some(name), "sample outside its family: {}", line);
+                if let Some(labels) = series.strip_prefix(name) {
+                    if !labels.is_empty() {
+                        assert!(labels.starts_with('{') && labels.ends_with('}'), "{}", line);
+                    }
+                }
+                assert!(seen_samples.insert(series.to_string()), "duplicate series {}", series);
+            }
+        }
+        assert!(pending_help.is_none());
+    }
+
+    #[test]
+    fn test_render_is_valid_exposition() {
+        let server = Metrics::new();
+        server.record_query("Bfs", 20);
+        server.record_query("GetNode", 150);
+        server.record_flush(12, 100, 200);
+        let db = Metrics::new();
+        db.record_query("Bfs", 20);
+
+        let server_snap = server.snapshot();
+        let db_snap = db.snapshot();
+        let text = render(&[
+            PrometheusSource { database: None, snapshot: Some(&server_snap), graph: None },
+            PrometheusSource { database: Some("main \"db\""), snapshot: Some(&db_snap), graph: Some((10, 4)) },
+        ]);
+
+        assert_valid_exposition(&text);
+        assert!(text.contains("# TYPE rfdb_query_total counter\n"));
+        assert!(text.contains("rfdb_query_total 2\n"));
+        assert!(text.contains("rfdb_query_total{database=\"main \\\"db\\\"\"} 1\n"));
+        assert!(text.contains("rfdb_query_duration_ms{quantile=\"0.95\"} "));
+        assert!(text.contains("rfdb_operation_total{operation=\"Bfs\"} 1\n"));
+        assert!(text.contains("rfdb_node_count{database=\"main \\\"db\\\"\"} 10\n"));
+        assert!(text.contains("rfdb_last_flush_nodes 100\n"));
+    }
+
+    #[test]
+    fn test_render_without_metrics_has_only_graph_gauges() {
+        let text = render(&[PrometheusSource { database: Some("default"), snapshot: None, graph: Some((1, 0)) }]);
+        assert_valid_exposition(&text);
+        assert!(!text.contains("rfdb_query_total"));
+        assert!(text.contains("rfdb_edge_count{database=\"default\"} 0\n"));
+    }
+}
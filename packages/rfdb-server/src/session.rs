@@ -5,6 +5,21 @@
 
 use std::sync::Arc;
 use crate::database_manager::{Database, AccessMode, ClientId};
+use crate::graph::GraphEngineV2;
+use crate::transaction::{
+    CrossDbBatch, StreamingCommit, Transaction, DEFAULT_STREAMING_COMMIT_MAX_BYTES, DEFAULT_TRANSACTION_MAX_BYTES,
+};
+
+/// Read-only view of the current database pinned to a historical version
+///
+/// Set by PinSnapshot. While present, reads on the session resolve against
+/// `engine` and writes are rejected.
+pub struct PinnedSnapshot {
+    /// Manifest version the view was opened at
+    pub version: u64,
+    /// Engine opened from that version's manifest
+    pub engine: GraphEngineV2,
+}
 
 /// Session state for a client connection
 ///
@@ -19,8 +34,30 @@ pub struct ClientSession {
     pub access_mode: AccessMode,
     /// Protocol version negotiated with client (1 = legacy, 2 = multi-db)
     pub protocol_version: u32,
-    /// Pending batch ID (set by BeginBatch, cleared by AbortBatch or CommitBatch)
-    pub pending_batch_id: Option<String>,
+    /// Open transaction (set by BeginBatch, cleared by CommitTransaction or AbortBatch)
+    pub pending_batch: Option<Transaction>,
+    /// Open cross-database batch (set by BeginCrossDbBatch, cleared by
+    /// CommitCrossDbBatch or AbortBatch). Independent of the current database.
+    pub pending_cross_db: Option<CrossDbBatch>,
+    /// Historical snapshot reads are pinned to (set by PinSnapshot)
+    pub pinned_snapshot: Option<PinnedSnapshot>,
+    /// Open streaming commit (set by BeginStreamingCommit, cleared by
+    /// FinishStreamingCommit or AbortBatch)
+    pub pending_stream: Option<StreamingCommit>,
+    /// Dump bytes received so far from ImportDatabase frames sent with
+    /// `more` (cleared by the final frame or AbortBatch)
+    pub pending_import: Option<Vec<u8>>,
+    /// Byte limit for streaming commits started on this session (and for
+    /// a database dump sent over several ImportDatabase frames)
+    pub max_stream_bytes: usize,
+    /// Byte limit for the writes one batch (BeginBatch) may stage
+    pub max_batch_bytes: usize,
+    /// Cumulative request bytes read from this connection
+    pub bytes_read: u64,
+    /// Limit on `bytes_read` (`--max-conn-bytes`), None = unlimited
+    pub byte_quota: Option<u64>,
+    /// Client asked for idle heartbeats in Hello (`--idle-timeout-secs`)
+    pub heartbeat: bool,
 }
 
 impl ClientSession {
@@ -34,16 +71,37 @@ impl ClientSession {
             current_db: None,
             access_mode: AccessMode::ReadWrite,
             protocol_version: 1, // Default to v1 for backwards compatibility
-            pending_batch_id: None,
+            pending_batch: None,
+            pending_cross_db: None,
+            pinned_snapshot: None,
+            pending_stream: None,
+            pending_import: None,
+            max_stream_bytes: DEFAULT_STREAMING_COMMIT_MAX_BYTES,
+            max_batch_bytes: DEFAULT_TRANSACTION_MAX_BYTES,
+            bytes_read: 0,
+            byte_quota: None,
+            heartbeat: false,
         }
     }
 
+    /// Account for `len` bytes of request data read from the connection.
+    ///
+    /// Returns false once the cumulative total exceeds the byte quota.
+    pub fn record_bytes_read(&mut self, len: usize) -> bool {
+        self.bytes_read = self.bytes_read.saturating_add(len as u64);
+        !matches!(self.byte_quota, Some(quota) if self.bytes_read > quota)
+    }
+
     /// Set current database and access mode
     ///
     /// Called when client opens a database.
     pub fn set_database(&mut self, db: Arc<Database>, mode: AccessMode) {
         self.current_db = Some(db);
         self.access_mode = mode;
+        self.pending_batch = None;
+        self.pinned_snapshot = None;
+        self.pending_stream = None;
+        self.pending_import = None;
     }
 
     /// Clear current database
@@ -52,7 +110,10 @@ impl ClientSession {
     pub fn clear_database(&mut self) {
         self.current_db = None;
         self.access_mode = AccessMode::ReadWrite;
-        self.pending_batch_id = None;
+        self.pending_batch = None;
+        self.pinned_snapshot = None;
+        self.pending_stream = None;
+        self.pending_import = None;
     }
 
     /// Get current database name
@@ -74,22 +135,97 @@ impl ClientSession {
     ///
     /// Returns None if a batch is already pending.
     pub fn begin_batch(&mut self) -> Option<String> {
-        if self.pending_batch_id.is_some() {
+        if self.pending_batch.is_some() {
             return None;
         }
         let batch_id = format!("batch-{}-{}", self.id, std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis());
-        self.pending_batch_id = Some(batch_id.clone());
+        self.pending_batch = Some(Transaction::new(batch_id.clone(), self.max_batch_bytes));
         Some(batch_id)
     }
 
-    /// Abort the current batch, returning the aborted batch ID.
+    /// ID of the pending batch, if any
+    pub fn pending_batch_id(&self) -> Option<&str> {
+        self.pending_batch.as_ref().map(Transaction::id)
+    }
+
+    /// Abort the current batch, discarding its staged writes and returning
+    /// the aborted batch ID.
     ///
     /// Returns None if no batch is pending.
     pub fn abort_batch(&mut self) -> Option<String> {
-        self.pending_batch_id.take()
+        self.pending_batch.take().map(|txn| txn.id().to_string())
+    }
+
+    /// Close the current batch and hand back its staged writes for commit.
+    ///
+    /// Returns None if no batch is pending.
+    pub fn take_batch(&mut self) -> Option<Transaction> {
+        self.pending_batch.take()
+    }
+
+    /// Begin a cross-database batch over `versions` (database name and its
+    /// current manifest version), returning the new batch ID.
+    ///
+    /// Returns None if a cross-database batch is already pending.
+    pub fn begin_cross_db_batch(&mut self, versions: Vec<(String, u64)>) -> Option<String> {
+        if self.pending_cross_db.is_some() {
+            return None;
+        }
+        let batch_id = format!("xbatch-{}-{}", self.id, std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis());
+        self.pending_cross_db = Some(CrossDbBatch::new(batch_id.clone(), versions));
+        Some(batch_id)
+    }
+
+    /// Close the current cross-database batch and hand it back for commit.
+    ///
+    /// Returns None if no cross-database batch is pending.
+    pub fn take_cross_db_batch(&mut self) -> Option<CrossDbBatch> {
+        self.pending_cross_db.take()
+    }
+
+    /// Begin a streaming commit replacing `changed_files`, returning its ID.
+    ///
+    /// Returns None if a streaming commit is already pending.
+    pub fn begin_streaming_commit(&mut self, changed_files: Vec<String>) -> Option<String> {
+        if self.pending_stream.is_some() {
+            return None;
+        }
+        let stream_id = format!("stream-{}-{}", self.id, std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis());
+        self.pending_stream = Some(StreamingCommit::new(stream_id.clone(), changed_files, self.max_stream_bytes));
+        Some(stream_id)
+    }
+
+    /// Close the current streaming commit and hand it back for commit.
+    ///
+    /// Returns None if no streaming commit is pending.
+    pub fn take_streaming_commit(&mut self) -> Option<StreamingCommit> {
+        self.pending_stream.take()
+    }
+
+    /// Version reads are pinned to, if any
+    pub fn pinned_version(&self) -> Option<u64> {
+        self.pinned_snapshot.as_ref().map(|p| p.version)
+    }
+
+    /// Pin reads to a historical snapshot view, replacing any existing pin.
+    pub fn pin_snapshot(&mut self, version: u64, engine: GraphEngineV2) {
+        self.pinned_snapshot = Some(PinnedSnapshot { version, engine });
+    }
+
+    /// Unpin, returning the previously pinned version.
+    ///
+    /// Returns None if no snapshot is pinned.
+    pub fn unpin_snapshot(&mut self) -> Option<u64> {
+        self.pinned_snapshot.take().map(|p| p.version)
     }
 }
 
@@ -97,7 +233,6 @@ impl ClientSession {
 mod session_tests {
     use super::*;
     use crate::database_manager::Database;
-    use crate::graph::GraphEngineV2;
 
     fn make_test_database(name: &str) -> Arc<Database> {
         let engine: Box<dyn crate::graph::GraphStore> = Box::new(GraphEngineV2::create_ephemeral());
@@ -114,6 +249,19 @@ mod session_tests {
         assert_eq!(session.access_mode, AccessMode::ReadWrite);
     }
 
+    #[test]
+    fn test_session_byte_quota() {
+        let mut unlimited = ClientSession::new(1);
+        assert!(unlimited.record_bytes_read(usize::MAX));
+
+        let mut session = ClientSession::new(2);
+        session.byte_quota = Some(100);
+        assert!(session.record_bytes_read(60));
+        assert!(session.record_bytes_read(40));
+        assert!(!session.record_bytes_read(1));
+        assert_eq!(session.bytes_read, 101);
+    }
+
     #[test]
     fn test_session_set_database() {
         let mut session = ClientSession::new(1);
@@ -165,4 +313,17 @@ mod session_tests {
         session.protocol_version = 2;
         assert_eq!(session.protocol_version, 2);
     }
+
+    #[test]
+    fn test_session_pin_cleared_on_database_change() {
+        let mut session = ClientSession::new(1);
+        session.set_database(make_test_database("testdb"), AccessMode::ReadWrite);
+
+        session.pin_snapshot(3, GraphEngineV2::create_ephemeral());
+        assert_eq!(session.pinned_version(), Some(3));
+
+        session.clear_database();
+        assert_eq!(session.pinned_version(), None);
+        assert_eq!(session.unpin_snapshot(), None);
+    }
 }
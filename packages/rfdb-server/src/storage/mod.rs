@@ -77,7 +77,7 @@ pub struct EdgeRecord {
 }
 
 /// Query for filtering nodes by attributes
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct AttrQuery {
     /// Deprecated node-level version filter.
     /// Kept for wire compatibility; v2 query engine ignores this field.
@@ -97,6 +97,10 @@ pub struct AttrQuery {
     /// instead of exact equality. Default: false (exact match).
     #[serde(default)]
     pub substring_match: bool,
+    /// When true, results are sorted by node ID so the same query returns
+    /// the same order regardless of shard/segment layout. Default: false.
+    #[serde(default)]
+    pub sorted: bool,
 }
 
 impl AttrQuery {
@@ -135,6 +139,11 @@ impl AttrQuery {
         self.metadata_filters.push((key.into(), value.into()));
         self
     }
+
+    pub fn sorted(mut self, s: bool) -> Self {
+        self.sorted = s;
+        self
+    }
 }
 
 /// Declaration of a metadata field to be indexed.
@@ -153,6 +162,18 @@ pub struct FieldDecl {
     /// If None, the field is indexed for all node types.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub node_types: Option<Vec<String>>,
+    /// Whether the field lives in node or edge metadata.
+    #[serde(default)]
+    pub entity: FieldEntity,
+}
+
+/// Record kind a declared metadata field belongs to.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldEntity {
+    #[default]
+    Node,
+    Edge,
 }
 
 /// Type hint for declared metadata fields.
@@ -165,3 +186,47 @@ pub enum FieldType {
     Int,
     Id,
 }
+
+/// Metadata schema for one node type (opt-in per type).
+///
+/// Nodes of the type must carry every key in `required_keys`; when
+/// `allowed_keys` is set, keys outside `required_keys` and `allowed_keys`
+/// are rejected too. Catches typos like `asyncc` that would otherwise
+/// silently break metadata queries.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetadataSchema {
+    #[serde(default)]
+    pub required_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_keys: Option<Vec<String>>,
+}
+
+impl MetadataSchema {
+    /// True if the schema constrains nothing.
+    pub fn is_empty(&self) -> bool {
+        self.required_keys.is_empty() && self.allowed_keys.is_none()
+    }
+
+    /// Check a node's metadata JSON (empty string = no keys) against the
+    /// schema. Returns a description of the first violation.
+    pub fn check(&self, metadata: &str) -> Result<(), String> {
+        let object = if metadata.is_empty() {
+            serde_json::Map::new()
+        } else {
+            match serde_json::from_str::<serde_json::Value>(metadata) {
+                Ok(serde_json::Value::Object(object)) => object,
+                _ => return Err("metadata is not a JSON object".to_string()),
+            }
+        };
+        if let Some(key) = self.required_keys.iter().find(|k| !object.contains_key(k.as_str())) {
+            return Err(format!("missing required key '{}'", key));
+        }
+        if let Some(allowed) = &self.allowed_keys {
+            let permitted = |key: &String| allowed.contains(key) || self.required_keys.contains(key);
+            if let Some(key) = object.keys().find(|k| !permitted(k)) {
+                return Err(format!("key '{}' is not allowed", key));
+            }
+        }
+        Ok(())
+    }
+}
@@ -55,7 +55,29 @@ impl BloomFilter {
     /// a minimum of 64 bits. An empty filter (0 keys) is valid and always
     /// returns false from `maybe_contains`.
     pub fn new(num_keys: usize) -> Self {
-        let raw_bits = num_keys.saturating_mul(BLOOM_BITS_PER_KEY);
+        Self::with_sizing(num_keys.saturating_mul(BLOOM_BITS_PER_KEY), BLOOM_NUM_HASHES)
+    }
+
+    /// Create a bloom filter sized for `num_keys` insertions at a target
+    /// false-positive rate, using the standard formulas:
+    ///
+    /// ```text
+    /// m = -n * ln(p) / ln(2)^2    (bits)
+    /// k = (m / n) * ln(2)         (hash functions)
+    /// ```
+    ///
+    /// `fp_rate` is clamped to `[1e-9, 0.5]`; the same word alignment and
+    /// 64-bit minimum as `new()` apply.
+    pub fn with_fp_rate(num_keys: usize, fp_rate: f64) -> Self {
+        let p = fp_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits_per_key = -p.ln() / (ln2 * ln2);
+        let num_hashes = ((bits_per_key * ln2).round() as usize).max(1);
+        let raw_bits = (num_keys as f64 * bits_per_key).ceil() as usize;
+        Self::with_sizing(raw_bits, num_hashes)
+    }
+
+    fn with_sizing(raw_bits: usize, num_hashes: usize) -> Self {
         let min_bits = raw_bits.max(64);
         // Round up to next multiple of 64.
         let num_bits = (min_bits + 63) & !63;
@@ -63,7 +85,7 @@ impl BloomFilter {
         Self {
             bits: vec![0u64; word_count],
             num_bits,
-            num_hashes: BLOOM_NUM_HASHES,
+            num_hashes,
         }
     }
 
@@ -282,6 +304,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bloom_with_fp_rate_sizing() {
+        let n = 1000;
+        let keys: Vec<u128> = (0..n as u128).map(|i| {
+            i.wrapping_mul(0x9e3779b97f4a7c15_u128) ^ (i << 64)
+        }).collect();
+
+        let mut tight = BloomFilter::with_fp_rate(n, 0.001);
+        let mut loose = BloomFilter::with_fp_rate(n, 0.1);
+        assert!(loose.num_bits() < tight.num_bits());
+        assert!(loose.num_hashes() < tight.num_hashes());
+        // 0.01 lands next to the fixed 10 bits/key, k=7 sizing.
+        assert_eq!(BloomFilter::with_fp_rate(n, 0.01).num_hashes(), BLOOM_NUM_HASHES);
+
+        for &k in &keys {
+            tight.insert(k);
+            loose.insert(k);
+        }
+        for &k in &keys {
+            assert!(tight.maybe_contains(k), "false negative at 0.001 for key {}", k);
+            assert!(loose.maybe_contains(k), "false negative at 0.1 for key {}", k);
+        }
+    }
+
     #[test]
     fn test_bloom_fpr_under_2_percent() {
         let n = 10_000;
@@ -2,7 +2,8 @@
 //!
 //! Decides when a shard needs compaction and executes the merge:
 //! 1. `should_compact()` — checks L0 segment count against threshold
-//! 2. `compact_shard()` — merges L0 + existing L1 into new L1 segment
+//! 2. `compact_shard()` — merges L0 + existing L1 into new L1 segment, or
+//!    L0 + L1 + L2 into a new L2 segment once L1 outgrows its threshold
 //!
 //! The coordinator does NOT own Shard or ManifestStore. It receives
 //! references to segment data, performs the merge, and returns the
@@ -12,7 +13,10 @@
 use std::io::Cursor;
 
 use crate::error::Result;
-use crate::storage_v2::compaction::merge::{merge_edge_segments, merge_node_segments};
+use crate::storage_v2::compaction::merge::{
+    merge_edge_segments_throttled, merge_node_segments_throttled,
+};
+use crate::storage_v2::compaction::throttle::Throttle;
 use crate::storage_v2::compaction::types::CompactionConfig;
 use crate::storage_v2::manifest::SegmentDescriptor;
 use crate::storage_v2::segment::{EdgeSegmentV2, NodeSegmentV2};
@@ -22,15 +26,19 @@ use crate::storage_v2::writer::{EdgeSegmentWriter, NodeSegmentWriter};
 
 // ── Policy ──────────────────────────────────────────────────────────
 
-/// Check if a shard should be compacted based on L0 segment count.
+/// Check if a shard should be compacted based on L0 segment count or
+/// accumulated tombstones.
 ///
-/// Returns true when the total L0 segments (nodes + edges) >= threshold.
-/// Typical threshold: 4 (default in CompactionConfig).
+/// Returns true when the total L0 segments (nodes + edges) >= threshold
+/// (typically 4, the CompactionConfig default), or when tombstoned
+/// records outnumber live ones by more than `tombstone_ratio_threshold`.
+/// The latter reclaims space in shards that see deletes but few writes.
 ///
 /// Complexity: O(1)
 pub fn should_compact(shard: &Shard, config: &CompactionConfig) -> bool {
     let total_l0 = shard.l0_node_segment_count() + shard.l0_edge_segment_count();
     total_l0 >= config.segment_threshold
+        || shard.tombstone_ratio() > config.tombstone_ratio_threshold
 }
 
 // ── Compaction Result ───────────────────────────────────────────────
@@ -52,28 +60,54 @@ pub struct ShardCompactionResult {
     pub l0_segments_merged: u32,
     /// Number of tombstones that were physically removed
     pub tombstones_removed: u64,
+    /// True if the merged segments replace L2 (and L1 is left empty),
+    /// false if they replace L1
+    pub into_l2: bool,
 }
 
 // ── Compact Shard ───────────────────────────────────────────────────
 
 /// Compact a single shard: merge L0 segments + existing L1 into new L1.
 ///
+/// When the existing L1 holds more than `config.l1_record_threshold`
+/// records, L0 + L1 + L2 are merged into a new L2 instead. The same
+/// happens whenever the shard has L2 data and pending tombstones: the
+/// manifest drops tombstones after compaction, so a merge that left L2
+/// untouched would resurrect deleted L2 records.
+///
 /// Algorithm:
-/// 1. Collect all L0 node segments (newest first) + L1 node segment (oldest)
+/// 1. Collect all L0 node segments (newest first) + L1 node segment
+///    (+ L2 node segment, oldest, when merging into L2)
 /// 2. Merge with tombstone filtering via `merge_node_segments()`
 /// 3. Write merged records into a new in-memory segment
 /// 4. Repeat for edges
 /// 5. Return merged bytes + metadata
 ///
+/// Input segment bytes are rate-limited to `config.max_bytes_per_sec`
+/// when set.
+///
 /// The caller (MultiShardStore) is responsible for:
 /// - Allocating segment IDs
 /// - Writing bytes to disk
 /// - Updating the manifest
-/// - Swapping shard state (set_l1_segments + clear_l0_after_compaction)
+/// - Swapping shard state (set_l1_segments / set_l2_segments +
+///   clear_l0_after_compaction)
 ///
 /// Complexity: O(N log N + M log M) where N = total nodes, M = total edges
-pub fn compact_shard(shard: &Shard) -> Result<ShardCompactionResult> {
+pub fn compact_shard(shard: &Shard, config: &CompactionConfig) -> Result<ShardCompactionResult> {
+    compact_shard_throttled(shard, config, &Throttle::new(config.max_bytes_per_sec))
+}
+
+/// `compact_shard` charging merged bytes to a caller-owned `throttle`,
+/// so several shards compacted in one run share a single rate cap.
+pub fn compact_shard_throttled(
+    shard: &Shard,
+    config: &CompactionConfig,
+    throttle: &Throttle,
+) -> Result<ShardCompactionResult> {
     let tombstones = shard.tombstones();
+    let into_l2 = shard.l1_record_count() > config.l1_record_threshold
+        || (shard.has_l2() && !tombstones.is_empty());
 
     // Count L0 segments being merged
     let l0_segments_merged =
@@ -95,13 +129,18 @@ pub fn compact_shard(shard: &Shard) -> Result<ShardCompactionResult> {
     if let Some(l1) = shard.l1_node_segment() {
         all_node_segs.push(l1);
     }
+    if into_l2 {
+        all_node_segs.extend(shard.l2_node_segment());
+    }
 
-    let merged_nodes = merge_node_segments(&all_node_segs, tombstones);
+    let merged_nodes = merge_node_segments_throttled(&all_node_segs, tombstones, throttle);
 
     let (node_segment_bytes, node_meta) = if merged_nodes.is_empty() {
         (None, None)
     } else {
-        let mut writer = NodeSegmentWriter::new();
+        let mut writer = NodeSegmentWriter::with_compression(shard.compression())
+            .with_bloom_fp_rate(shard.bloom_fp_rate())
+            .with_int_fields(shard.int_fields().to_vec());
         for record in merged_nodes {
             writer.add(record);
         }
@@ -122,13 +161,17 @@ pub fn compact_shard(shard: &Shard) -> Result<ShardCompactionResult> {
     if let Some(l1) = shard.l1_edge_segment() {
         all_edge_segs.push(l1);
     }
+    if into_l2 {
+        all_edge_segs.extend(shard.l2_edge_segment());
+    }
 
-    let merged_edges = merge_edge_segments(&all_edge_segs, tombstones);
+    let merged_edges = merge_edge_segments_throttled(&all_edge_segs, tombstones, throttle);
 
     let (edge_segment_bytes, edge_meta) = if merged_edges.is_empty() {
         (None, None)
     } else {
-        let mut writer = EdgeSegmentWriter::new();
+        let mut writer = EdgeSegmentWriter::with_compression(shard.compression())
+            .with_bloom_fp_rate(shard.bloom_fp_rate());
         for record in merged_edges {
             writer.add(record);
         }
@@ -144,6 +187,7 @@ pub fn compact_shard(shard: &Shard) -> Result<ShardCompactionResult> {
         edge_meta,
         l0_segments_merged,
         tombstones_removed: tombstones_before,
+        into_l2,
     })
 }
 
@@ -165,6 +209,7 @@ pub fn build_l1_descriptor(
         node_types: meta.node_types.clone(),
         file_paths: meta.file_paths.clone(),
         edge_types: meta.edge_types.clone(),
+        compression: meta.compression,
     }
 }
 
@@ -173,8 +218,8 @@ pub fn build_l1_descriptor(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::storage_v2::shard::Shard;
-    use crate::storage_v2::types::{EdgeRecordV2, NodeRecordV2};
+    use crate::storage_v2::shard::{Shard, TombstoneSet};
+    use crate::storage_v2::types::{CompressionKind, EdgeRecordV2, NodeRecordV2};
 
     fn make_node(semantic_id: &str, node_type: &str, name: &str, file: &str) -> NodeRecordV2 {
         let hash = blake3::hash(semantic_id.as_bytes());
@@ -212,7 +257,7 @@ mod tests {
     #[test]
     fn test_should_compact_below_threshold() {
         let shard = Shard::ephemeral();
-        let config = CompactionConfig { segment_threshold: 4 };
+        let config = CompactionConfig { segment_threshold: 4, ..Default::default() };
         assert!(!should_compact(&shard, &config));
     }
 
@@ -225,14 +270,48 @@ mod tests {
             shard.add_nodes(vec![node]);
             shard.flush_with_ids(Some(i as u64 + 1), None).unwrap();
         }
-        let config = CompactionConfig { segment_threshold: 4 };
+        let config = CompactionConfig { segment_threshold: 4, ..Default::default() };
+        assert!(should_compact(&shard, &config));
+    }
+
+    #[test]
+    fn test_should_compact_on_tombstone_ratio() {
+        let mut shard = Shard::ephemeral();
+        let nodes: Vec<NodeRecordV2> = (0..10)
+            .map(|i| make_node(&format!("node_{}", i), "FUNCTION", "fn", "file.rs"))
+            .collect();
+        shard.add_nodes(nodes.clone());
+        shard.flush_with_ids(Some(1), None).unwrap();
+        let config = CompactionConfig { segment_threshold: 4, ..Default::default() };
+
+        // Tombstones for records held by other shards do not count
+        let foreign = make_node("elsewhere", "FUNCTION", "fn", "other.rs");
+        shard.set_tombstones(TombstoneSet::from_manifest(vec![foreign.id], vec![]));
+        assert_eq!(shard.tombstoned_record_count(), 0);
+
+        // 1 deleted of 10: below the 0.5 ratio
+        shard.set_tombstones(TombstoneSet::from_manifest(vec![foreign.id, nodes[0].id], vec![]));
+        assert_eq!(shard.tombstoned_record_count(), 1);
+        assert!(!should_compact(&shard, &config));
+
+        // 8 of 10 deleted with no new segments: 8 tombstoned vs 2 live
+        let deleted: Vec<u128> = nodes[..8].iter().map(|n| n.id).collect();
+        shard.set_tombstones(TombstoneSet::from_manifest(deleted, vec![]));
+        assert_eq!(shard.tombstoned_record_count(), 8);
+        assert_eq!(shard.l0_node_segment_count(), 1);
         assert!(should_compact(&shard, &config));
+
+        // Compaction drops the tombstoned records and resets the ratio
+        let result = compact_shard(&shard, &config).unwrap();
+        assert_eq!(result.node_meta.unwrap().record_count, 2);
+        shard.clear_l0_after_compaction();
+        assert_eq!(shard.tombstone_ratio(), 0.0);
     }
 
     #[test]
     fn test_compact_empty_shard() {
         let shard = Shard::ephemeral();
-        let result = compact_shard(&shard).unwrap();
+        let result = compact_shard(&shard, &CompactionConfig::default()).unwrap();
         assert!(result.node_segment_bytes.is_none());
         assert!(result.edge_segment_bytes.is_none());
         assert_eq!(result.l0_segments_merged, 0);
@@ -255,7 +334,7 @@ mod tests {
         shard.add_nodes(vec![n3.clone(), n1_updated.clone()]);
         shard.flush_with_ids(Some(2), None).unwrap();
 
-        let result = compact_shard(&shard).unwrap();
+        let result = compact_shard(&shard, &CompactionConfig::default()).unwrap();
 
         assert!(result.node_segment_bytes.is_some());
         let meta = result.node_meta.unwrap();
@@ -276,7 +355,7 @@ mod tests {
         shard.upsert_edges(vec![e3.clone()]);
         shard.flush_with_ids(None, Some(2)).unwrap();
 
-        let result = compact_shard(&shard).unwrap();
+        let result = compact_shard(&shard, &CompactionConfig::default()).unwrap();
 
         assert!(result.edge_segment_bytes.is_some());
         let meta = result.edge_meta.unwrap();
@@ -293,15 +372,46 @@ mod tests {
         shard.flush_with_ids(Some(1), None).unwrap();
 
         // Add tombstone for n2
-        shard.tombstones_mut().add_nodes(vec![n2.id]);
+        shard.set_tombstones(TombstoneSet::from_manifest(vec![n2.id], vec![]));
 
-        let result = compact_shard(&shard).unwrap();
+        let result = compact_shard(&shard, &CompactionConfig::default()).unwrap();
 
         let meta = result.node_meta.unwrap();
         assert_eq!(meta.record_count, 1); // Only "keep" survives
         assert_eq!(result.tombstones_removed, 1); // One tombstone was present
     }
 
+    #[test]
+    fn test_compact_shard_respects_byte_rate() {
+        let mut shard = Shard::ephemeral();
+        for flush in 0..4u64 {
+            let nodes: Vec<NodeRecordV2> = (0..200)
+                .map(|i| make_node(&format!("node_{}_{}", flush, i), "FUNCTION", "fn", "file.rs"))
+                .collect();
+            shard.add_nodes(nodes);
+            shard.flush_with_ids(Some(flush + 1), None).unwrap();
+        }
+        let volume: usize = shard.l0_node_segments().iter().map(|s| s.byte_size()).sum();
+
+        // Cap so the merge should take ~200 ms
+        let rate = (volume as u64 * 5).max(1);
+        let throttle = Throttle::new(Some(rate));
+        let start = std::time::Instant::now();
+        let result = compact_shard_throttled(&shard, &CompactionConfig::default(), &throttle).unwrap();
+        let elapsed = start.elapsed();
+
+        assert_eq!(result.node_meta.unwrap().record_count, 800);
+        assert!(throttle.consumed() >= volume as u64);
+        assert!(throttle.slept() > std::time::Duration::ZERO);
+        let lower_bound = std::time::Duration::from_secs_f64(volume as f64 / rate as f64);
+        assert!(elapsed >= lower_bound, "{:?} < {:?}", elapsed, lower_bound);
+
+        // Unthrottled the same merge never sleeps
+        let throttle = Throttle::unlimited();
+        compact_shard_throttled(&shard, &CompactionConfig::default(), &throttle).unwrap();
+        assert_eq!(throttle.slept(), std::time::Duration::ZERO);
+    }
+
     #[test]
     fn test_build_l1_descriptor() {
         let meta = SegmentMeta {
@@ -311,6 +421,7 @@ mod tests {
             node_types: ["FUNCTION".to_string()].into(),
             file_paths: ["file.rs".to_string()].into(),
             edge_types: Default::default(),
+            compression: CompressionKind::None,
         };
 
         let desc = build_l1_descriptor(42, SegmentType::Nodes, Some(0), &meta);
@@ -2,10 +2,12 @@
 //!
 //! Merges multiple segments into a single sorted, deduplicated list,
 //! filtering tombstoned records. Used by the compaction pipeline to
-//! produce L1 segments from L0 flush segments.
+//! produce L1 segments from L0 flush segments. The `_throttled` variants
+//! charge the input segment bytes to a `Throttle` as they merge.
 
 use std::collections::HashMap;
 
+use crate::storage_v2::compaction::throttle::{SegmentMeter, Throttle};
 use crate::storage_v2::segment::{EdgeSegmentV2, NodeSegmentV2};
 use crate::storage_v2::shard::TombstoneSet;
 use crate::storage_v2::types::{EdgeRecordV2, NodeRecordV2};
@@ -22,15 +24,27 @@ use crate::storage_v2::types::{EdgeRecordV2, NodeRecordV2};
 pub fn merge_node_segments(
     segments: &[&NodeSegmentV2],
     tombstones: &TombstoneSet,
+) -> Vec<NodeRecordV2> {
+    merge_node_segments_throttled(segments, tombstones, &Throttle::unlimited())
+}
+
+/// `merge_node_segments` rate-limited by `throttle`.
+pub fn merge_node_segments_throttled(
+    segments: &[&NodeSegmentV2],
+    tombstones: &TombstoneSet,
+    throttle: &Throttle,
 ) -> Vec<NodeRecordV2> {
     let mut records: HashMap<u128, NodeRecordV2> = HashMap::new();
 
     // Insert from each segment -- first insert wins (HashMap::entry().or_insert)
     // Caller must provide segments in newest-first order
     for seg in segments {
+        let mut meter = SegmentMeter::new(throttle, seg.byte_size(), seg.record_count());
         for record in seg.iter() {
             records.entry(record.id).or_insert(record);
+            meter.record();
         }
+        meter.finish();
     }
 
     // Filter tombstones
@@ -52,15 +66,27 @@ pub fn merge_node_segments(
 pub fn merge_edge_segments(
     segments: &[&EdgeSegmentV2],
     tombstones: &TombstoneSet,
+) -> Vec<EdgeRecordV2> {
+    merge_edge_segments_throttled(segments, tombstones, &Throttle::unlimited())
+}
+
+/// `merge_edge_segments` rate-limited by `throttle`.
+pub fn merge_edge_segments_throttled(
+    segments: &[&EdgeSegmentV2],
+    tombstones: &TombstoneSet,
+    throttle: &Throttle,
 ) -> Vec<EdgeRecordV2> {
     let mut records: HashMap<(u128, u128, String), EdgeRecordV2> = HashMap::new();
 
     // Insert from each segment -- first insert wins
     for seg in segments {
+        let mut meter = SegmentMeter::new(throttle, seg.byte_size(), seg.record_count());
         for record in seg.iter() {
             let key = (record.src, record.dst, record.edge_type.clone());
             records.entry(key).or_insert(record);
+            meter.record();
         }
+        meter.finish();
     }
 
     // Filter tombstones
@@ -5,8 +5,13 @@
 
 pub mod coordinator;
 pub mod merge;
+pub mod throttle;
 pub mod types;
 
-pub use coordinator::{compact_shard, should_compact, ShardCompactionResult};
-pub use merge::{merge_edge_segments, merge_node_segments};
+pub use coordinator::{compact_shard, compact_shard_throttled, should_compact, ShardCompactionResult};
+pub use merge::{
+    merge_edge_segments, merge_edge_segments_throttled, merge_node_segments,
+    merge_node_segments_throttled,
+};
+pub use throttle::Throttle;
 pub use types::*;
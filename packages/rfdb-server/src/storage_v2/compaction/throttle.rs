@@ -0,0 +1,150 @@
+//! IO throttling for compaction merges.
+//!
+//! A `Throttle` caps the rate at which segment data flows through a
+//! compaction run. Merges charge bytes as they go, in record chunks,
+//! and the throttle sleeps whenever the run gets ahead of the cap.
+//! One throttle is shared by all shards of a run, so the cap holds for
+//! the whole compaction, not per rayon thread.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Records merged between two throttle checks.
+pub const THROTTLE_CHUNK_RECORDS: u64 = 1024;
+
+/// Byte-rate limiter shared by the shards of one compaction run.
+#[derive(Debug)]
+pub struct Throttle {
+    /// Cap in bytes per second (None = unlimited, `consume` is a no-op).
+    bytes_per_sec: Option<u64>,
+    start: Instant,
+    consumed: AtomicU64,
+    slept_nanos: AtomicU64,
+}
+
+impl Throttle {
+    /// Create a throttle; the rate clock starts now.
+    pub fn new(bytes_per_sec: Option<u64>) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.filter(|&rate| rate > 0),
+            start: Instant::now(),
+            consumed: AtomicU64::new(0),
+            slept_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Throttle that never sleeps.
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// Total bytes charged so far.
+    pub fn consumed(&self) -> u64 {
+        self.consumed.load(Ordering::Relaxed)
+    }
+
+    /// Total time `consume` has spent sleeping, across all threads.
+    pub fn slept(&self) -> Duration {
+        Duration::from_nanos(self.slept_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Charge `bytes`, sleeping until the run is back under the cap.
+    pub fn consume(&self, bytes: u64) {
+        let Some(rate) = self.bytes_per_sec else {
+            return;
+        };
+        let total = self.consumed.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let due = Duration::from_secs_f64(total as f64 / rate as f64);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            let pause = due - elapsed;
+            thread::sleep(pause);
+            self.slept_nanos.fetch_add(pause.as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Charges one segment's bytes to a throttle as its records are merged.
+///
+/// Bytes are spread evenly over the records and charged every
+/// `THROTTLE_CHUNK_RECORDS`; `finish` charges the remainder, so the
+/// whole segment is accounted for exactly once.
+pub(crate) struct SegmentMeter<'a> {
+    throttle: &'a Throttle,
+    bytes: u64,
+    records: u64,
+    seen: u64,
+    /// Records seen since the last charge
+    unmetered: u64,
+    charged: u64,
+}
+
+impl<'a> SegmentMeter<'a> {
+    pub(crate) fn new(throttle: &'a Throttle, bytes: usize, records: usize) -> Self {
+        Self { throttle, bytes: bytes as u64, records: records as u64, seen: 0, unmetered: 0, charged: 0 }
+    }
+
+    /// Count one merged record.
+    pub(crate) fn record(&mut self) {
+        self.seen += 1;
+        self.unmetered += 1;
+        if self.unmetered >= THROTTLE_CHUNK_RECORDS && self.seen < self.records {
+            self.unmetered = 0;
+            let due = (self.bytes as u128 * self.seen as u128 / self.records as u128) as u64;
+            self.throttle.consume(due - self.charged);
+            self.charged = due;
+        }
+    }
+
+    /// Charge whatever part of the segment has not been charged yet.
+    pub(crate) fn finish(self) {
+        self.throttle.consume(self.bytes - self.charged);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited_never_sleeps() {
+        let throttle = Throttle::unlimited();
+        throttle.consume(u64::MAX / 2);
+        assert_eq!(throttle.slept(), Duration::ZERO);
+        // Unlimited throttles do not track volume either
+        assert_eq!(throttle.consumed(), 0);
+    }
+
+    #[test]
+    fn test_zero_rate_is_unlimited() {
+        let throttle = Throttle::new(Some(0));
+        throttle.consume(1 << 40);
+        assert_eq!(throttle.consumed(), 0);
+    }
+
+    #[test]
+    fn test_meter_charges_whole_segment() {
+        let throttle = Throttle::new(Some(u64::MAX));
+        let records = THROTTLE_CHUNK_RECORDS as usize * 3 + 7;
+        let mut meter = SegmentMeter::new(&throttle, 100_003, records);
+        for _ in 0..records {
+            meter.record();
+        }
+        assert!(throttle.consumed() < 100_003);
+        meter.finish();
+        assert_eq!(throttle.consumed(), 100_003);
+    }
+
+    #[test]
+    fn test_consume_holds_rate() {
+        let throttle = Throttle::new(Some(10_000));
+        let start = Instant::now();
+        for _ in 0..5 {
+            throttle.consume(100);
+        }
+        // 500 bytes at 10 KB/s; sleeps only ever lengthen the run
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(throttle.slept() > Duration::ZERO);
+    }
+}
@@ -1,7 +1,9 @@
 //! Types for LSM-style background compaction.
 //!
 //! Compaction merges multiple L0 (flush) segments into a single L1 (compacted)
-//! segment per shard, removing tombstones and deduplicating records.
+//! segment per shard, removing tombstones and deduplicating records. Once a
+//! shard's L1 grows past `l1_record_threshold`, the next compaction merges
+//! L0 + L1 down into the shard's L2 segment instead, leaving L1 empty.
 
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +14,15 @@ use crate::storage_v2::resource::TuningProfile;
 pub struct CompactionConfig {
     /// Minimum L0 segment count per shard to trigger compaction (default: 4)
     pub segment_threshold: usize,
+    /// L1 size (node + edge records) above which the next compaction of
+    /// the shard merges into L2 instead of rewriting L1 (default: 1_000_000)
+    pub l1_record_threshold: usize,
+    /// Cap on segment bytes merged per second, to keep compaction IO from
+    /// starving queries (default: None = unthrottled)
+    pub max_bytes_per_sec: Option<u64>,
+    /// Tombstoned-to-live record ratio above which a shard is compacted
+    /// regardless of its L0 segment count (default: 0.5)
+    pub tombstone_ratio_threshold: f64,
 }
 
 impl CompactionConfig {
@@ -22,6 +33,7 @@ impl CompactionConfig {
     pub fn from_profile(profile: &TuningProfile) -> Self {
         Self {
             segment_threshold: profile.segment_threshold,
+            ..Self::default()
         }
     }
 }
@@ -30,6 +42,9 @@ impl Default for CompactionConfig {
     fn default() -> Self {
         Self {
             segment_threshold: 4,
+            l1_record_threshold: 1_000_000,
+            max_bytes_per_sec: None,
+            tombstone_ratio_threshold: 0.5,
         }
     }
 }
@@ -49,6 +64,20 @@ pub struct CompactionResult {
     pub duration_ms: u64,
 }
 
+/// What a compaction would reclaim, computed without merging anything
+/// (see `MultiShardStore::compaction_estimate`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactionEstimate {
+    /// Shards `should_compact` selects under the given config
+    pub shards_eligible: Vec<u16>,
+    /// Tombstoned segment records in those shards (bloom-based, may
+    /// overcount slightly)
+    pub estimated_tombstones_removed: u64,
+    /// Segment bytes held by those records, assuming they take their
+    /// proportional share of the shard's segment bytes
+    pub estimated_bytes_saved: u64,
+}
+
 /// Compaction metadata stored in manifest.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CompactionInfo {
@@ -76,6 +105,7 @@ mod tests {
     fn test_compaction_config_custom_threshold() {
         let config = CompactionConfig {
             segment_threshold: 8,
+            ..Default::default()
         };
         assert_eq!(config.segment_threshold, 8);
     }
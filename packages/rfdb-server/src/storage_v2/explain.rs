@@ -0,0 +1,114 @@
+//! Query plans for attribute searches (`FindByAttr`).
+//!
+//! Shards optionally record, per data source they visit (write buffer,
+//! L0 segments, L1/L2 segments), which access path was taken and how many
+//! candidate nodes survived each filter. `QueryPlan::render` turns the
+//! trace into the text returned by `ExplainQuery`.
+
+use std::fmt::Write as _;
+
+/// How a data source was accessed during an attribute search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessPath {
+    /// Linear scan of the in-memory write buffer.
+    BufferScan,
+    /// Segment skipped entirely by zone map / descriptor pruning.
+    Pruned,
+    /// Columnar scan of every record in the segment.
+    FullScan,
+    /// Lookup through a compacted segment's by_name inverted index.
+    NameIndex,
+}
+
+impl AccessPath {
+    fn label(self) -> &'static str {
+        match self {
+            AccessPath::BufferScan => "buffer scan",
+            AccessPath::Pruned => "pruned by zone map",
+            AccessPath::FullScan => "full segment scan",
+            AccessPath::NameIndex => "by_name index lookup",
+        }
+    }
+}
+
+/// One data source visited by a shard.
+#[derive(Debug, Clone)]
+pub struct ScanStep {
+    /// Human-readable source, e.g. `write buffer`, `L0 segment 12`.
+    pub source: String,
+    pub access: AccessPath,
+    /// Records examined (after newest-wins dedup and tombstones).
+    pub candidates: usize,
+    /// Survivors after each active filter, in evaluation order.
+    pub filters: Vec<(String, usize)>,
+}
+
+impl ScanStep {
+    pub fn new(source: impl Into<String>, access: AccessPath, stages: &[String]) -> Self {
+        Self {
+            source: source.into(),
+            access,
+            candidates: 0,
+            filters: stages.iter().map(|s| (s.clone(), 0)).collect(),
+        }
+    }
+
+    /// Nodes that passed every filter.
+    pub fn matched(&self) -> usize {
+        self.filters.last().map_or(self.candidates, |(_, n)| *n)
+    }
+}
+
+/// Trace of one shard's part of an attribute search.
+#[derive(Debug, Clone, Default)]
+pub struct ShardPlan {
+    pub shard_id: u16,
+    pub steps: Vec<ScanStep>,
+}
+
+/// Full plan for an attribute search across all shards.
+#[derive(Debug, Clone, Default)]
+pub struct QueryPlan {
+    /// Active filters, e.g. `node_type = FUNCTION`.
+    pub filters: Vec<String>,
+    /// Metadata filter fields and whether each was declared via `DeclareFields`.
+    pub metadata_fields: Vec<(String, bool)>,
+    pub shards: Vec<ShardPlan>,
+    /// Final result count (after pending tombstones).
+    pub result_count: usize,
+}
+
+impl QueryPlan {
+    /// Render the plan as indented text. Shards with no data are omitted.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if self.filters.is_empty() {
+            out.push_str("Filters: (none)\n");
+        } else {
+            let _ = writeln!(out, "Filters: {}", self.filters.join(", "));
+        }
+        for (field, declared) in &self.metadata_fields {
+            let status = if *declared { "declared" } else { "undeclared (see DeclareFields)" };
+            let _ = writeln!(out, "Metadata field '{}': {}", field, status);
+        }
+        for shard in self.shards.iter().filter(|s| !s.steps.is_empty()) {
+            let matched: usize = shard.steps.iter().map(ScanStep::matched).sum();
+            let _ = writeln!(out, "Shard {}: {} matched", shard.shard_id, matched);
+            for step in &shard.steps {
+                let _ = write!(
+                    out,
+                    "  {}: {}, {} candidates",
+                    step.source,
+                    step.access.label(),
+                    step.candidates,
+                );
+                for (filter, survivors) in &step.filters {
+                    let _ = write!(out, " -> {} {}", filter, survivors);
+                }
+                out.push('\n');
+            }
+        }
+        let _ = writeln!(out, "Result: {} nodes", self.result_count);
+        out
+    }
+}
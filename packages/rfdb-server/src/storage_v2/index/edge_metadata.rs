@@ -0,0 +1,87 @@
+//! Edge metadata index -- rows of an edge segment by metadata field value.
+//!
+//! Built per edge segment and per declared edge field (eagerly on flush,
+//! lazily for segments opened from disk or fields declared later). A
+//! lookup is a single hash probe, so filtering edges by `argIndex` or
+//! `isSpread` never decodes the metadata of non-matching rows.
+
+use std::collections::HashMap;
+
+/// Comparable string form of a metadata value, as used by metadata
+/// filters: strings are taken verbatim, everything else is rendered as
+/// JSON (`true`, `0`, ...).
+pub fn metadata_value_key(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Segment rows grouped by the value of one metadata field.
+///
+/// Rows whose metadata is empty, unparsable or lacks the field are left out.
+#[derive(Debug, Default)]
+pub struct EdgeMetadataIndex {
+    /// value key -> row indices, ascending
+    rows: HashMap<String, Vec<u32>>,
+}
+
+impl EdgeMetadataIndex {
+    /// Build for `field` from the metadata of every row, in row order.
+    pub fn build<'a, I>(field: &str, metadata: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut rows: HashMap<String, Vec<u32>> = HashMap::new();
+        for (row, meta) in metadata.into_iter().enumerate() {
+            if meta.is_empty() {
+                continue;
+            }
+            let Ok(parsed) = serde_json::from_str::<serde_json::Value>(meta) else {
+                continue;
+            };
+            if let Some(value) = parsed.get(field) {
+                rows.entry(metadata_value_key(value))
+                    .or_default()
+                    .push(row as u32);
+            }
+        }
+        Self { rows }
+    }
+
+    /// Rows whose field value renders as `value`.
+    pub fn lookup(&self, value: &str) -> &[u32] {
+        self.rows.get(value).map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of distinct values.
+    pub fn key_count(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_rendered_value() {
+        let index = EdgeMetadataIndex::build("argIndex", [
+            r#"{"argIndex":0}"#,
+            "",
+            r#"{"argIndex":1,"isSpread":true}"#,
+            r#"{"isSpread":true}"#,
+            r#"{"argIndex":0}"#,
+            "not json",
+        ]);
+        assert_eq!(index.key_count(), 2);
+        assert_eq!(index.lookup("0"), &[0, 4]);
+        assert_eq!(index.lookup("1"), &[2]);
+        assert!(index.lookup("2").is_empty());
+
+        let spread = EdgeMetadataIndex::build("isSpread", [r#"{"isSpread":true}"#, r#"{"isSpread":"true"}"#]);
+        assert_eq!(spread.lookup("true"), &[0, 1]);
+    }
+}
@@ -5,13 +5,22 @@
 //! - `builder` -- inverted index construction during compaction
 //! - `query` -- inverted index loading and O(log K) key lookup
 //! - `global` -- global index for O(log N) point lookups across shards
+//! - `reverse` -- optional dst -> shard index for incoming-edge queries
+//! - `name_prefix` -- per-segment sorted lowercased names for prefix lookups
+//! - `edge_metadata` -- per-edge-segment rows by declared metadata field value
 
 pub mod builder;
+pub mod edge_metadata;
 pub mod format;
 pub mod global;
+pub mod name_prefix;
 pub mod query;
+pub mod reverse;
 
 pub use builder::{build_inverted_indexes, BuiltIndexes};
+pub use edge_metadata::EdgeMetadataIndex;
 pub use format::{IndexEntry, IndexFileHeader, LookupTableEntry};
 pub use global::GlobalIndex;
+pub use name_prefix::NamePrefixIndex;
 pub use query::InvertedIndex;
+pub use reverse::ReverseEdgeIndex;
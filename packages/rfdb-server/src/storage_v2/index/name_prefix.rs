@@ -0,0 +1,74 @@
+//! Name prefix index -- sorted lowercased names for type-ahead lookups.
+//!
+//! Built per node segment (eagerly on flush/compaction, lazily for
+//! segments opened from disk). A prefix lookup is a binary search for the
+//! first key >= prefix followed by a scan while keys still match, so it
+//! never touches rows outside the matching range.
+
+/// Segment rows sorted by lowercased node name.
+///
+/// Rows with an empty name are left out.
+#[derive(Debug, Default)]
+pub struct NamePrefixIndex {
+    /// (lowercased name, row index), sorted by name then row
+    entries: Vec<(String, u32)>,
+}
+
+impl NamePrefixIndex {
+    /// Build from the name of every row, in row order.
+    pub fn build<'a, I>(names: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut entries: Vec<(String, u32)> = names
+            .into_iter()
+            .enumerate()
+            .filter(|(_, name)| !name.is_empty())
+            .map(|(row, name)| (name.to_lowercase(), row as u32))
+            .collect();
+        entries.sort_unstable();
+        Self { entries }
+    }
+
+    /// `(lowercased name, row)` of rows whose name starts with `prefix`,
+    /// compared case-insensitively, in name order.
+    ///
+    /// O(log N) to find the range plus O(M) for the M matches.
+    pub fn lookup_prefix(&self, prefix: &str) -> impl Iterator<Item = (&str, u32)> {
+        let prefix = prefix.to_lowercase();
+        let start = self.entries.partition_point(|(name, _)| name.as_str() < prefix.as_str());
+        self.entries[start..]
+            .iter()
+            .take_while(move |(name, _)| name.starts_with(&prefix))
+            .map(|(name, row)| (name.as_str(), *row))
+    }
+
+    /// Number of indexed rows.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_prefix_case_insensitive() {
+        let index = NamePrefixIndex::build(["helper", "handlePost", "", "HandleGet", "other"]);
+        assert_eq!(index.len(), 4);
+
+        let rows: Vec<u32> = index.lookup_prefix("handle").map(|(_, row)| row).collect();
+        assert_eq!(rows, vec![3, 1]);
+        assert_eq!(index.lookup_prefix("HANDLEP").count(), 1);
+        assert_eq!(index.lookup_prefix("h").count(), 3);
+        assert_eq!(index.lookup_prefix("").count(), 4);
+        assert_eq!(index.lookup_prefix("zzz").count(), 0);
+    }
+}
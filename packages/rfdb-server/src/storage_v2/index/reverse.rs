@@ -0,0 +1,290 @@
+//! Reverse adjacency index -- dst -> incoming edge keys and their shards.
+//!
+//! Edges live in the shard of their source node (or an enrichment shard),
+//! so incoming-edge queries otherwise fan out to every shard and scan for
+//! the destination. The index records, per destination node, the source,
+//! edge type and shard of every edge pointing at it, letting
+//! `get_incoming_edges` query only those shards for exactly those edges.
+//!
+//! Entries are a superset: edges deleted after insertion leave stale
+//! entries, which only cost a lookup that finds nothing (the shard filters
+//! tombstones). `MultiShardStore` rebuilds the index after compaction
+//! and on `RebuildIndexes` to drop them.
+//!
+//! Each full rebuild is saved to `reverse_edge_index.bin` in the database
+//! root along with the edge segments it covers. On open the saved index is
+//! loaded and only segments written since are scanned.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::storage_v2::types::EdgeRecordV2;
+
+/// File in the database root holding the last full build.
+pub const REVERSE_EDGE_INDEX_FILE: &str = "reverse_edge_index.bin";
+
+/// Bumped whenever the saved layout changes; other versions are ignored.
+const FORMAT_VERSION: u32 = 2;
+
+/// dst -> edge type ID -> (src, shard_id) of each incoming edge
+type Entries = HashMap<u128, HashMap<u32, HashSet<(u128, u16)>>>;
+
+/// Saved index, valid for databases with `shard_count` shards. Covers the
+/// edges of `segments`; any other segment must be scanned on load.
+#[derive(Serialize, Deserialize)]
+struct SavedIndex<T, E> {
+    format_version: u32,
+    shard_count: u16,
+    segments: Vec<u64>,
+    edge_types: T,
+    entries: E,
+}
+
+/// In-memory reverse adjacency index: dst -> edge type -> (src, shard_id).
+///
+/// Edge types are interned, so an entry costs a source ID plus two small
+/// integers. Metadata is not stored: the shard lookup returns the full
+/// edge record.
+#[derive(Debug, Default)]
+pub struct ReverseEdgeIndex {
+    entries: Entries,
+    /// Interned edge type names, indexed by type ID
+    edge_types: Vec<String>,
+    type_ids: HashMap<String, u32>,
+}
+
+impl ReverseEdgeIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build an index from `(shard_id, edges)` pairs.
+    pub fn build<'a, I>(shards: I) -> Self
+    where
+        I: IntoIterator<Item = (u16, &'a [EdgeRecordV2])>,
+    {
+        let mut index = Self::new();
+        for (shard_id, edges) in shards {
+            for edge in edges {
+                index.insert(edge.dst, edge.src, &edge.edge_type, shard_id);
+            }
+        }
+        index
+    }
+
+    /// Rebuild only the entries for edges of `edge_types` and edges into
+    /// `dsts` from `(shard_id, edges)` pairs holding every live such edge;
+    /// all other entries are left as they were.
+    ///
+    /// The result for those edge types and destinations matches a full
+    /// `build`.
+    pub fn rebuild_for<'a, I>(&mut self, edge_types: &HashSet<String>, dsts: &HashSet<u128>, shards: I)
+    where
+        I: IntoIterator<Item = (u16, &'a [EdgeRecordV2])>,
+    {
+        let type_ids: HashSet<u32> = edge_types.iter().filter_map(|t| self.type_ids.get(t).copied()).collect();
+        self.entries.retain(|dst, by_type| {
+            if dsts.contains(dst) {
+                return false;
+            }
+            by_type.retain(|type_id, _| !type_ids.contains(type_id));
+            !by_type.is_empty()
+        });
+        for (shard_id, edges) in shards {
+            for edge in edges.iter().filter(|e| edge_types.contains(&e.edge_type) || dsts.contains(&e.dst)) {
+                self.insert(edge.dst, edge.src, &edge.edge_type, shard_id);
+            }
+        }
+    }
+
+    /// Record that `shard_id` holds the edge `src -[edge_type]-> dst`.
+    pub fn insert(&mut self, dst: u128, src: u128, edge_type: &str, shard_id: u16) {
+        let type_id = match self.type_ids.get(edge_type) {
+            Some(&id) => id,
+            None => {
+                let id = self.edge_types.len() as u32;
+                self.edge_types.push(edge_type.to_string());
+                self.type_ids.insert(edge_type.to_string(), id);
+                id
+            }
+        };
+        self.entries.entry(dst).or_default().entry(type_id).or_default().insert((src, shard_id));
+    }
+
+    /// `(src, edge_type)` keys of the edges into `dst`, grouped by the
+    /// shard holding them and restricted to the given edge types when
+    /// provided. Sorted by shard_id.
+    pub fn incoming(&self, dst: u128, edge_types: Option<&[&str]>) -> BTreeMap<u16, Vec<(u128, &str)>> {
+        let mut by_shard: BTreeMap<u16, Vec<(u128, &str)>> = BTreeMap::new();
+        let Some(by_type) = self.entries.get(&dst) else {
+            return by_shard;
+        };
+        let wanted: Vec<(&u32, &HashSet<(u128, u16)>)> = match edge_types {
+            Some(types) => types.iter()
+                .filter_map(|t| self.type_ids.get(*t))
+                .filter_map(|type_id| by_type.get_key_value(type_id))
+                .collect(),
+            None => by_type.iter().collect(),
+        };
+        for (&type_id, incoming) in wanted {
+            let edge_type = self.edge_types[type_id as usize].as_str();
+            for &(src, shard_id) in incoming {
+                by_shard.entry(shard_id).or_default().push((src, edge_type));
+            }
+        }
+        by_shard
+    }
+
+    /// Shards that may hold edges into `dst`, restricted to the given
+    /// edge types when provided. Sorted by shard_id.
+    pub fn shards_for(&self, dst: u128, edge_types: Option<&[&str]>) -> Vec<u16> {
+        self.incoming(dst, edge_types).into_keys().collect()
+    }
+
+    /// Save to `db_path`, recording that it covers every edge of
+    /// `segments` in a database of `shard_count` shards. Written to a
+    /// temporary file first, so a crash leaves the previous save intact.
+    pub fn write_to(&self, db_path: &Path, shard_count: u16, segments: Vec<u64>) -> Result<()> {
+        let saved = SavedIndex {
+            format_version: FORMAT_VERSION,
+            shard_count,
+            segments,
+            edge_types: &self.edge_types,
+            entries: &self.entries,
+        };
+        let tmp = db_path.join(format!("{}.tmp", REVERSE_EDGE_INDEX_FILE));
+        std::fs::write(&tmp, bincode::serialize(&saved)?)?;
+        std::fs::rename(&tmp, db_path.join(REVERSE_EDGE_INDEX_FILE))?;
+        Ok(())
+    }
+
+    /// Load the index saved in `db_path` with the segments it covers.
+    /// None if there is none, or it was saved for another shard count or
+    /// format version.
+    pub fn read_from(db_path: &Path, shard_count: u16) -> Result<Option<(Self, HashSet<u64>)>> {
+        let path = db_path.join(REVERSE_EDGE_INDEX_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        // The version is checked before the rest of the layout is trusted
+        let bytes = std::fs::read(&path)?;
+        let format_version: u32 = bincode::deserialize(&bytes)?;
+        if format_version != FORMAT_VERSION {
+            return Ok(None);
+        }
+        let saved: SavedIndex<Vec<String>, Entries> = bincode::deserialize(&bytes)?;
+        if saved.shard_count != shard_count {
+            return Ok(None);
+        }
+        let type_ids = saved.edge_types.iter().enumerate().map(|(id, t)| (t.clone(), id as u32)).collect();
+        let index = Self { entries: saved.entries, edge_types: saved.edge_types, type_ids };
+        Ok(Some((index, saved.segments.into_iter().collect())))
+    }
+
+    /// Number of destination nodes in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(src: u128, dst: u128, edge_type: &str) -> EdgeRecordV2 {
+        EdgeRecordV2 {
+            src,
+            dst,
+            edge_type: edge_type.to_string(),
+            metadata: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_shards_for_filters_by_edge_type() {
+        let shard0 = [edge(1, 100, "CALLS"), edge(2, 100, "CALLS")];
+        let shard3 = [edge(3, 100, "IMPORTS_FROM"), edge(3, 200, "CALLS")];
+        let index = ReverseEdgeIndex::build([(0, &shard0[..]), (3, &shard3[..])]);
+
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.shards_for(100, None), vec![0, 3]);
+        assert_eq!(index.shards_for(100, Some(&["CALLS"])), vec![0]);
+        assert_eq!(index.shards_for(100, Some(&["CONTAINS"])), Vec::<u16>::new());
+        assert_eq!(index.shards_for(200, None), vec![3]);
+        assert!(index.shards_for(300, None).is_empty());
+    }
+
+    #[test]
+    fn test_incoming_lists_edge_keys_by_shard() {
+        let shard0 = [edge(1, 100, "CALLS"), edge(2, 100, "CALLS")];
+        let shard3 = [edge(3, 100, "IMPORTS_FROM"), edge(3, 200, "CALLS")];
+        // Re-inserting an edge (e.g. an upsert of a superseded version) adds nothing
+        let index = ReverseEdgeIndex::build([(0, &shard0[..]), (3, &shard3[..]), (0, &shard0[..1])]);
+
+        let mut incoming = index.incoming(100, None);
+        incoming.values_mut().for_each(|keys| keys.sort_unstable());
+        assert_eq!(incoming, BTreeMap::from([
+            (0, vec![(1, "CALLS"), (2, "CALLS")]),
+            (3, vec![(3, "IMPORTS_FROM")]),
+        ]));
+        assert_eq!(index.incoming(100, Some(&["IMPORTS_FROM"])), BTreeMap::from([(3, vec![(3, "IMPORTS_FROM")])]));
+        assert!(index.incoming(100, Some(&["EXTENDS"])).is_empty());
+    }
+
+    #[test]
+    fn test_saved_index_round_trips_for_same_shard_count() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let edges = [edge(1, 100, "CALLS"), edge(2, 200, "IMPORTS_FROM")];
+        let index = ReverseEdgeIndex::build([(3, &edges[..])]);
+        index.write_to(dir.path(), 4, vec![7, 9]).unwrap();
+
+        let (loaded, segments) = ReverseEdgeIndex::read_from(dir.path(), 4).unwrap().unwrap();
+        assert_eq!(segments, HashSet::from([7, 9]));
+        assert_eq!(loaded.shards_for(100, Some(&["CALLS"])), vec![3]);
+        assert_eq!(loaded.shards_for(200, None), vec![3]);
+        assert_eq!(loaded.incoming(200, None), BTreeMap::from([(3, vec![(2, "IMPORTS_FROM")])]));
+        assert_eq!(loaded.len(), 2);
+
+        assert!(ReverseEdgeIndex::read_from(dir.path(), 8).unwrap().is_none(), "resharded");
+        assert!(ReverseEdgeIndex::read_from(&dir.path().join("missing"), 4).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rebuild_for_only_touches_given_types() {
+        let before = [edge(1, 100, "CALLS"), edge(2, 200, "CALLS"), edge(3, 200, "EXTENDS")];
+        let mut index = ReverseEdgeIndex::build([(0, &before[..])]);
+
+        // Every CALLS edge is gone but a new one into 100 now lives in shard 2
+        let after = [edge(5, 100, "CALLS")];
+        index.rebuild_for(&HashSet::from(["CALLS".to_string()]), &HashSet::new(), [(2, &after[..])]);
+
+        assert_eq!(index.shards_for(100, None), vec![2]);
+        assert_eq!(index.shards_for(200, Some(&["CALLS"])), Vec::<u16>::new());
+        assert_eq!(index.shards_for(200, Some(&["EXTENDS"])), vec![0], "other edge types kept");
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_rebuild_for_only_touches_given_destinations() {
+        let before = [edge(1, 100, "CALLS"), edge(2, 200, "CALLS")];
+        let mut index = ReverseEdgeIndex::build([(0, &before[..])]);
+
+        // Both edges are gone; only dst 100 is rebuilt (now held by shard 2)
+        let after = [edge(5, 100, "EXTENDS")];
+        index.rebuild_for(&HashSet::new(), &HashSet::from([100]), [(2, &after[..])]);
+
+        assert_eq!(index.shards_for(100, None), vec![2]);
+        assert_eq!(index.shards_for(200, None), vec![0], "untouched entry kept");
+    }
+}
@@ -43,7 +43,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::error::{GraphError, Result};
 use crate::storage_v2::compaction::CompactionInfo;
-use crate::storage_v2::types::{SegmentMeta, SegmentType};
+use crate::storage_v2::segment::NodeSegmentV2;
+use crate::storage_v2::types::{CompressionKind, SegmentMeta, SegmentType};
 
 // ── Durability Mode ────────────────────────────────────────────────
 
@@ -54,13 +55,32 @@ use crate::storage_v2::types::{SegmentMeta, SegmentType};
 ///
 /// Relaxed: Skip fsync (OS buffers writes). Best-effort durability.
 ///          Faster commits (~1ms), but crash may lose recent commits.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+///
+/// Verified: Strict, plus segment checksums are verified when the
+///           database is opened. Costs a full read of every segment on
+///           open, so it is opt-in (e.g. after copying `.rfdb` dirs).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DurabilityMode {
     /// Fsync everything (safe default)
     #[default]
     Strict,
     /// Skip fsync (OS handles flush)
     Relaxed,
+    /// Fsync everything and verify segment checksums on open
+    Verified,
+}
+
+impl DurabilityMode {
+    /// Whether writes are fsynced.
+    pub fn fsyncs(&self) -> bool {
+        *self != DurabilityMode::Relaxed
+    }
+
+    /// Whether segment checksums are verified on open.
+    pub fn verifies_checksums(&self) -> bool {
+        *self == DurabilityMode::Verified
+    }
 }
 
 // ── Manifest ───────────────────────────────────────────────────────
@@ -121,6 +141,15 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub l1_edge_segments: Vec<SegmentDescriptor>,
 
+    /// L2 node segment descriptors — at most one per shard. Populated when
+    /// compaction merges an oversized L1 down; older than L1.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub l2_node_segments: Vec<SegmentDescriptor>,
+
+    /// L2 edge segment descriptors — at most one per shard.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub l2_edge_segments: Vec<SegmentDescriptor>,
+
     /// Metadata about the last compaction (None if never compacted).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_compaction: Option<CompactionInfo>,
@@ -167,6 +196,10 @@ pub struct SegmentDescriptor {
     /// Zone map: edge types (empty for node segments)
     #[serde(default, skip_serializing_if = "HashSet::is_empty")]
     pub edge_types: HashSet<String>,
+
+    /// Body compression of the segment file (absent = uncompressed)
+    #[serde(default, skip_serializing_if = "CompressionKind::is_none")]
+    pub compression: CompressionKind,
 }
 
 impl SegmentDescriptor {
@@ -197,6 +230,7 @@ impl SegmentDescriptor {
             node_types: meta.node_types,
             file_paths: meta.file_paths,
             edge_types: meta.edge_types,
+            compression: meta.compression,
         }
     }
 
@@ -420,6 +454,8 @@ impl ManifestIndex {
             .chain(manifest.edge_segments.iter())
             .chain(manifest.l1_node_segments.iter())
             .chain(manifest.l1_edge_segments.iter())
+            .chain(manifest.l2_node_segments.iter())
+            .chain(manifest.l2_edge_segments.iter())
         {
             self.referenced_segments.insert(seg.segment_id);
         }
@@ -493,7 +529,7 @@ impl CurrentPointer {
         let path = db_path.join("current.json");
         atomic_write_json(&path, self, durability)?;
 
-        if durability == DurabilityMode::Strict {
+        if durability.fsyncs() {
             fsync_directory(db_path)?;
         }
 
@@ -606,6 +642,83 @@ impl SnapshotDiff {
     }
 }
 
+// ── Node Diff ──────────────────────────────────────────────────────
+
+/// Node-level diff between two snapshots (from_version -> to_version).
+///
+/// Computed by materializing each version's live node set
+/// (`ManifestStore::live_node_hashes`) and comparing by ID. ID lists are
+/// sorted and capped at `max_ids` each; the `*_count` fields are always
+/// exact.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeDiff {
+    pub from_version: u64,
+    pub to_version: u64,
+
+    /// Nodes live in to_version but not in from_version
+    pub added: Vec<u128>,
+
+    /// Nodes live in from_version but not in to_version
+    pub removed: Vec<u128>,
+
+    /// Nodes live in both with a different content_hash
+    pub modified: Vec<u128>,
+
+    pub added_count: usize,
+    pub removed_count: usize,
+    pub modified_count: usize,
+
+    /// True if any ID list was cut at `max_ids`
+    pub truncated: bool,
+}
+
+impl NodeDiff {
+    /// Compute diff between two live node sets (id -> content_hash).
+    ///
+    /// Complexity: O(N log N) where N = nodes in both sets (sorting IDs)
+    pub fn compute(
+        from_version: u64,
+        to_version: u64,
+        from: &HashMap<u128, u64>,
+        to: &HashMap<u128, u64>,
+        max_ids: usize,
+    ) -> Self {
+        let mut added: Vec<u128> = to.keys().filter(|id| !from.contains_key(id)).copied().collect();
+        let mut removed: Vec<u128> = from.keys().filter(|id| !to.contains_key(id)).copied().collect();
+        let mut modified: Vec<u128> = to
+            .iter()
+            .filter(|(id, hash)| from.get(id).is_some_and(|h| h != *hash))
+            .map(|(id, _)| *id)
+            .collect();
+
+        let (added_count, removed_count, modified_count) =
+            (added.len(), removed.len(), modified.len());
+        let truncated = added_count > max_ids || removed_count > max_ids || modified_count > max_ids;
+
+        for ids in [&mut added, &mut removed, &mut modified] {
+            ids.sort_unstable();
+            ids.truncate(max_ids);
+        }
+
+        Self {
+            from_version,
+            to_version,
+            added,
+            removed,
+            modified,
+            added_count,
+            removed_count,
+            modified_count,
+            truncated,
+        }
+    }
+
+    /// Is this a no-op diff (no node changes)?
+    pub fn is_empty(&self) -> bool {
+        self.added_count == 0 && self.removed_count == 0 && self.modified_count == 0
+    }
+}
+
 // ── Manifest Store ─────────────────────────────────────────────────
 
 /// ManifestStore: manages manifest chain + index + current pointer +
@@ -626,7 +739,7 @@ pub struct ManifestStore {
     /// Next segment ID to allocate (thread-safe atomic counter)
     next_segment_id: AtomicU64,
 
-    /// Durability mode (Strict = fsync, Relaxed = no fsync)
+    /// Durability mode (Strict = fsync, Relaxed = no fsync, Verified = fsync + checksums)
     durability: DurabilityMode,
 }
 
@@ -709,6 +822,8 @@ impl ManifestStore {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -762,6 +877,8 @@ impl ManifestStore {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -788,10 +905,22 @@ impl ManifestStore {
         &self.current
     }
 
+    /// Durability mode used by commits.
+    pub fn durability(&self) -> DurabilityMode {
+        self.durability
+    }
+
+    /// Change the durability mode used by subsequent commits, e.g. to skip
+    /// fsync for a single flush and restore the previous mode afterwards.
+    pub fn set_durability(&mut self, durability: DurabilityMode) {
+        self.durability = durability;
+    }
+
     /// Create new manifest (not yet committed).
     ///
     /// Constructs manifest with version = current.version + 1 and provided
-    /// segments/tags. Does NOT write to disk or update self.current.
+    /// segments/tags; L1/L2 descriptors are copied from the current manifest.
+    /// Does NOT write to disk or update self.current.
     ///
     /// Complexity: O(S) where S = total segments (for computing stats)
     pub fn create_manifest(
@@ -823,8 +952,12 @@ impl ManifestStore {
             parent_version: Some(self.current.version),
             tombstoned_node_ids: Vec::new(),
             tombstoned_edge_keys: Vec::new(),
-            l1_node_segments: Vec::new(),
-            l1_edge_segments: Vec::new(),
+            // Compacted tiers are only rewritten by compaction; every other
+            // commit carries them over unchanged.
+            l1_node_segments: self.current.l1_node_segments.clone(),
+            l1_edge_segments: self.current.l1_edge_segments.clone(),
+            l2_node_segments: self.current.l2_node_segments.clone(),
+            l2_edge_segments: self.current.l2_edge_segments.clone(),
             last_compaction: None,
         })
     }
@@ -961,6 +1094,63 @@ impl ManifestStore {
         Ok(())
     }
 
+    /// Restore a historical snapshot as the new current state.
+    ///
+    /// History is append-only: instead of moving the current pointer back
+    /// (which would let the next commit overwrite later manifests), the
+    /// target manifest's segments and tombstones are committed as a new
+    /// version whose parent is the current version. The new manifest is
+    /// tagged `restored_from = <version>`.
+    ///
+    /// Returns the new current version. Ephemeral stores have no manifest
+    /// files to restore from and fail with `EphemeralUnsupported`.
+    ///
+    /// Complexity: O(S + I) where S = segments in manifest, I = index size
+    pub fn restore_snapshot(&mut self, version: u64) -> Result<u64> {
+        if !self.index.snapshots.iter().any(|s| s.version == version) {
+            return Err(GraphError::SnapshotNotFound(version));
+        }
+        if self.db_path.is_none() {
+            return Err(GraphError::EphemeralUnsupported("RestoreSnapshot".to_string()));
+        }
+
+        let target = self.load_manifest(version)?;
+        let mut tags = HashMap::new();
+        tags.insert("restored_from".to_string(), version.to_string());
+
+        let manifest = Manifest {
+            version: self.current.version + 1,
+            created_at: current_timestamp(),
+            tags,
+            parent_version: Some(self.current.version),
+            ..target
+        };
+        let new_version = manifest.version;
+        self.commit(manifest)?;
+
+        Ok(new_version)
+    }
+
+    /// Detached read-only view whose current manifest is `version`.
+    ///
+    /// The view has no `db_path`, so a commit through it behaves like an
+    /// ephemeral store (cache only) and can never move the on-disk pointer.
+    /// Used to open shards as of a historical version.
+    pub fn snapshot_view(&self, version: u64) -> Result<ManifestStore> {
+        if !self.index.snapshots.iter().any(|s| s.version == version) {
+            return Err(GraphError::SnapshotNotFound(version));
+        }
+        let current = self.load_manifest(version)?;
+
+        Ok(ManifestStore {
+            db_path: None,
+            current,
+            index: self.index.clone(),
+            next_segment_id: AtomicU64::new(self.next_segment_id.load(Ordering::SeqCst)),
+            durability: self.durability,
+        })
+    }
+
     /// Compute diff between two snapshots.
     ///
     /// Complexity: O(S) where S = total segments in both manifests
@@ -973,6 +1163,52 @@ impl ManifestStore {
         let to = self.load_manifest(to_version)?;
         Ok(SnapshotDiff::compute(&from, &to))
     }
+
+    /// Materialize the live node set of a snapshot as id -> content_hash.
+    ///
+    /// Reads the version's segments oldest-first (L1, then L0 in segment ID
+    /// order) so newer records win, then drops tombstoned IDs. Requires a
+    /// disk-backed database, since segments are read from files.
+    ///
+    /// Complexity: O(R) where R = records in the version's node segments
+    pub fn live_node_hashes(&self, version: u64) -> Result<HashMap<u128, u64>> {
+        let db_path = self.db_path.as_ref().ok_or_else(|| {
+            GraphError::InvalidFormat(
+                "Node-level snapshot diff requires a disk-backed database".to_string(),
+            )
+        })?;
+        let manifest = self.load_manifest(version)?;
+
+        let mut l0: Vec<&SegmentDescriptor> = manifest.node_segments.iter().collect();
+        l0.sort_by_key(|d| d.segment_id);
+
+        let mut live = HashMap::new();
+        for desc in manifest.l2_node_segments.iter().chain(&manifest.l1_node_segments).chain(l0) {
+            let segment = NodeSegmentV2::open(&desc.file_path(db_path))?;
+            for i in segment.iter_indices() {
+                live.insert(segment.get_id(i), segment.get_content_hash(i));
+            }
+        }
+        for id in &manifest.tombstoned_node_ids {
+            live.remove(id);
+        }
+
+        Ok(live)
+    }
+
+    /// Compute the node-level diff between two snapshots.
+    ///
+    /// Complexity: O(R + N log N), see `live_node_hashes` and `NodeDiff::compute`
+    pub fn diff_snapshot_nodes(
+        &self,
+        from_version: u64,
+        to_version: u64,
+        max_ids: usize,
+    ) -> Result<NodeDiff> {
+        let from = self.live_node_hashes(from_version)?;
+        let to = self.live_node_hashes(to_version)?;
+        Ok(NodeDiff::compute(from_version, to_version, &from, &to, max_ids))
+    }
 }
 
 // ── ManifestStore: Garbage Collection ──────────────────────────────
@@ -1072,8 +1308,10 @@ fn atomic_write_json<T: Serialize>(
     let file = File::create(&temp_path)?;
     serde_json::to_writer_pretty(&file, data)?;
 
-    if durability == DurabilityMode::Strict {
+    if durability.fsyncs() {
         file.sync_all()?;
+        #[cfg(test)]
+        FSYNC_CALLS.with(|calls| calls.set(calls.get() + 1));
     }
 
     std::fs::rename(&temp_path, path)?;
@@ -1081,6 +1319,13 @@ fn atomic_write_json<T: Serialize>(
     Ok(())
 }
 
+#[cfg(test)]
+thread_local! {
+    /// Files fsynced by `atomic_write_json` on this thread, so tests can
+    /// tell whether a commit took the sync path.
+    pub(crate) static FSYNC_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 /// Read and deserialize JSON from file.
 fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
     let file = File::open(path)?;
@@ -1211,6 +1456,7 @@ mod tests {
             node_types: HashSet::from(["FUNCTION".to_string()]),
             file_paths: HashSet::from(["src/main.rs".to_string()]),
             edge_types: HashSet::new(),
+            compression: CompressionKind::None,
         }
     }
 
@@ -1224,6 +1470,7 @@ mod tests {
             node_types: HashSet::new(),
             file_paths: HashSet::new(),
             edge_types: HashSet::from(["CALLS".to_string()]),
+            compression: CompressionKind::None,
         }
     }
 
@@ -1248,6 +1495,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -1277,6 +1526,7 @@ mod tests {
             node_types: HashSet::from(["CLASS".to_string()]),
             file_paths: HashSet::from(["lib.rs".to_string()]),
             edge_types: HashSet::new(),
+            compression: CompressionKind::None,
         };
 
         let desc = SegmentDescriptor::from_meta(7, SegmentType::Nodes, None, meta);
@@ -1333,6 +1583,7 @@ mod tests {
             node_types: HashSet::from(["FUNCTION".to_string(), "CLASS".to_string()]),
             file_paths: HashSet::from(["src/main.rs".to_string()]),
             edge_types: HashSet::new(),
+            compression: CompressionKind::None,
         };
 
         assert!(desc.may_contain(Some("FUNCTION"), None, None));
@@ -1363,6 +1614,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
         index.add_snapshot(&m1);
@@ -1385,6 +1638,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
         index.add_snapshot(&m2);
@@ -1411,6 +1666,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
         index.add_snapshot(&m);
@@ -1442,6 +1699,8 @@ mod tests {
                 tombstoned_edge_keys: Vec::new(),
                 l1_node_segments: Vec::new(),
                 l1_edge_segments: Vec::new(),
+                l2_node_segments: Vec::new(),
+                l2_edge_segments: Vec::new(),
                 last_compaction: None,
             };
             index.add_snapshot(&m);
@@ -1468,6 +1727,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
         index.add_snapshot(&m);
@@ -1688,6 +1949,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -1870,8 +2133,70 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_restore_snapshot_ephemeral_errors() {
+        let mut store = ManifestStore::ephemeral();
+        let m2 = store.create_manifest(vec![make_node_descriptor(1, 10)], vec![], None).unwrap();
+        store.commit(m2).unwrap();
+        let err = store.restore_snapshot(2).unwrap_err();
+        assert!(matches!(err, GraphError::EphemeralUnsupported(_)));
+        assert_eq!(err.code(), "EPHEMERAL_UNSUPPORTED");
+    }
+
+    #[test]
+    fn test_restore_snapshot_appends_new_version() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("test.rfdb");
+        let mut store = ManifestStore::create(&db_path).unwrap();
+
+        let m2 = store
+            .create_manifest(vec![make_node_descriptor(1, 10)], vec![], None)
+            .unwrap();
+        store.commit(m2).unwrap();
+        let m3 = store
+            .create_manifest(
+                vec![make_node_descriptor(1, 10), make_node_descriptor(2, 5)],
+                vec![],
+                None,
+            )
+            .unwrap();
+        store.commit(m3).unwrap();
+
+        let restored = store.restore_snapshot(2).unwrap();
+        assert_eq!(restored, 4);
+        let current = store.current();
+        assert_eq!(current.parent_version, Some(3));
+        assert_eq!(current.node_segments.len(), 1);
+        assert_eq!(current.stats.total_nodes, 10);
+        assert_eq!(store.find_snapshot("restored_from", "2"), Some(4));
+
+        // History is kept: version 3 is still loadable
+        assert_eq!(store.load_manifest(3).unwrap().node_segments.len(), 2);
+        assert!(matches!(
+            store.restore_snapshot(99),
+            Err(GraphError::SnapshotNotFound(99))
+        ));
+    }
+
     // ── Phase 5: Diff Computation ─────────────────────────────────
 
+    #[test]
+    fn test_node_diff_buckets_and_truncation() {
+        let from = HashMap::from([(1u128, 10u64), (2, 20), (3, 30)]);
+        let to = HashMap::from([(1u128, 10u64), (2, 21), (4, 40), (5, 50)]);
+
+        let diff = NodeDiff::compute(1, 2, &from, &to, 10);
+        assert_eq!(diff.added, vec![4, 5]);
+        assert_eq!(diff.removed, vec![3]);
+        assert_eq!(diff.modified, vec![2]);
+        assert!(!diff.truncated);
+
+        let capped = NodeDiff::compute(1, 2, &from, &to, 1);
+        assert_eq!(capped.added, vec![4]);
+        assert_eq!(capped.added_count, 2);
+        assert!(capped.truncated);
+    }
+
     #[test]
     fn test_diff_empty_to_populated() {
         let from = Manifest {
@@ -1886,6 +2211,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -1903,6 +2230,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -1930,6 +2259,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -1953,6 +2284,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -1971,6 +2304,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -1998,6 +2333,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -2014,6 +2351,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -2194,6 +2533,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -2287,6 +2628,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
         let manifest_path = manifest_file_path(&db_path, 3);
@@ -2496,6 +2839,8 @@ mod tests {
             ],
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
@@ -2592,6 +2937,7 @@ mod tests {
             node_types: HashSet::from(["FUNCTION".to_string(), "CLASS".to_string()]),
             file_paths: HashSet::from(["src/main.rs".to_string(), "src/lib.rs".to_string()]),
             edge_types: HashSet::new(),
+            compression: CompressionKind::None,
         };
 
         let l1_edge = SegmentDescriptor {
@@ -2603,6 +2949,7 @@ mod tests {
             node_types: HashSet::new(),
             file_paths: HashSet::new(),
             edge_types: HashSet::from(["CALLS".to_string(), "IMPORTS_FROM".to_string()]),
+            compression: CompressionKind::None,
         };
 
         let compaction_info = CompactionInfo {
@@ -2628,6 +2975,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: vec![l1_node],
             l1_edge_segments: vec![l1_edge],
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: Some(compaction_info),
         };
 
@@ -2669,6 +3018,8 @@ mod tests {
             tombstoned_edge_keys: Vec::new(),
             l1_node_segments: Vec::new(),
             l1_edge_segments: Vec::new(),
+            l2_node_segments: Vec::new(),
+            l2_edge_segments: Vec::new(),
             last_compaction: None,
         };
 
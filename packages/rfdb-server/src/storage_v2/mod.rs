@@ -14,9 +14,12 @@ pub mod write_buffer;
 pub mod shard;
 pub mod shard_planner;
 pub mod multi_shard;
+pub mod node_cache;
+pub mod shard_map;
 pub mod compaction;
 pub mod index;
 pub mod resource;
+pub mod explain;
 
 pub use types::*;
 pub use string_table::StringTableV2;
@@ -27,12 +30,13 @@ pub use segment::{NodeSegmentV2, EdgeSegmentV2};
 
 pub use manifest::{
     CurrentPointer, DurabilityMode, Manifest, ManifestIndex, ManifestStats, ManifestStore,
-    SegmentDescriptor, SnapshotDiff, SnapshotInfo,
+    NodeDiff, SegmentDescriptor, SnapshotDiff, SnapshotInfo,
 };
 pub use write_buffer::WriteBuffer;
 pub use shard::{Shard, FlushResult, ShardDiagnostics, TombstoneSet};
 pub use shard_planner::ShardPlanner;
-pub use multi_shard::{DatabaseConfig, MultiShardStore, ShardStats};
+pub use multi_shard::{DatabaseConfig, MultiShardStore, PreparedCompaction, ShardStats};
 pub use compaction::{CompactionConfig, CompactionInfo, CompactionResult, merge_node_segments, merge_edge_segments};
 pub use index::{IndexEntry, IndexFileHeader, LookupTableEntry};
 pub use resource::{ResourceManager, SystemResources, TuningProfile};
+pub use explain::{AccessPath, QueryPlan, ScanStep, ShardPlan};
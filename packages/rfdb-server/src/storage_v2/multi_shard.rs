@@ -15,6 +15,7 @@
 //! +-- db_config.json          # DatabaseConfig (shard_count)
 //! +-- current.json            # Manifest pointer
 //! +-- manifest_index.json     # ManifestIndex
+//! +-- reverse_edge_index.bin  # Saved ReverseEdgeIndex (if enabled)
 //! +-- manifests/
 //! +-- segments/
 //! |   +-- 00/                 # Shard 0
@@ -27,17 +28,32 @@
 
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::{GraphError, Result};
-use crate::storage_v2::compaction::{CompactionConfig, CompactionResult};
-use crate::storage_v2::index::{build_inverted_indexes, GlobalIndex, IndexEntry, InvertedIndex};
-use crate::storage_v2::manifest::{ManifestStore, SegmentDescriptor};
+use crate::storage_v2::compaction::{
+    CompactionConfig, CompactionEstimate, CompactionResult, ShardCompactionResult,
+};
+use crate::storage_v2::explain::ShardPlan;
+use crate::storage_v2::index::{
+    build_inverted_indexes, GlobalIndex, IndexEntry, InvertedIndex, ReverseEdgeIndex,
+};
+use crate::storage_v2::manifest::{DurabilityMode, ManifestStore, SegmentDescriptor};
+use crate::storage_v2::node_cache::NodeCache;
+use crate::storage_v2::shard_map::ShardMap;
 use crate::storage_v2::segment::{self, EdgeSegmentV2, NodeSegmentV2};
 use crate::storage_v2::shard::{Shard, ShardDiagnostics, TombstoneSet};
-use crate::storage_v2::shard_planner::ShardPlanner;
-use crate::storage_v2::types::{CommitDelta, EdgeRecordV2, NodeRecordV2, SegmentType, extract_file_context};
+use crate::storage_v2::shard_planner::{RoutingStrategy, ShardPlanner};
+use crate::storage_v2::types::{
+    CommitDelta, CompressionKind, EdgeRecordV2, MmapAdvice, NodeRecordV2, SegmentType,
+    DEFAULT_BLOOM_FP_RATE, extract_file_context,
+};
+
+/// Shard count from which `find_node_ids_by_attr` scans shards in parallel.
+/// Below it, thread-pool overhead outweighs the gain.
+const PARALLEL_ATTR_SCAN_MIN_SHARDS: usize = 8;
 
 // ── Database Config ────────────────────────────────────────────────
 
@@ -45,10 +61,76 @@ use crate::storage_v2::types::{CommitDelta, EdgeRecordV2, NodeRecordV2, SegmentT
 ///
 /// Written once at database creation time to `db_config.json`.
 /// Read on every open to determine shard count.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DatabaseConfig {
     /// Number of shards for this database.
     pub shard_count: u16,
+
+    /// Compression for newly written segments. Absent in configs written
+    /// before compression existed, which keeps them uncompressed.
+    #[serde(default, skip_serializing_if = "CompressionKind::is_none")]
+    pub compression: CompressionKind,
+
+    /// Target false-positive rate for segment bloom filters. Lower rates
+    /// cost more bits per key but skip more segments on point lookups.
+    #[serde(default = "default_bloom_fp_rate")]
+    pub bloom_fp_rate: f64,
+
+    /// Maintain a reverse adjacency index (dst -> incoming edge keys) so
+    /// incoming-edge queries look up only the matching edges. Off by
+    /// default: costs memory proportional to the number of edges.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reverse_edge_index: bool,
+
+    /// How nodes are assigned to shards. Edges follow their source node
+    /// under either strategy.
+    #[serde(default, skip_serializing_if = "RoutingStrategy::is_by_file_dir")]
+    pub routing: RoutingStrategy,
+
+    /// Auto-flush once any shard's write buffer holds this many nodes.
+    /// None uses the adaptive limit from `TuningProfile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_flush_node_limit: Option<usize>,
+
+    /// Auto-flush once any shard's write buffer holds this many bytes.
+    /// None uses the adaptive limit from `TuningProfile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_flush_byte_limit: Option<usize>,
+
+    /// Keep up to this many recently fetched nodes in an LRU cache for
+    /// `get_node`. None disables the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_cache_capacity: Option<usize>,
+
+    /// `madvise` hint for query-time segment access. None uses the
+    /// adaptive choice from `TuningProfile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mmap_advice: Option<MmapAdvice>,
+
+    /// Keep at most this many node -> shard routing entries in memory,
+    /// evicting the least recently inserted. Evicted IDs are routed via the
+    /// global index or a shard fan-out. None keeps every entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_to_shard_capacity: Option<usize>,
+
+    /// Cache up to this many `find_by_attr` results, cleared on every
+    /// write. None disables the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_cache_capacity: Option<usize>,
+
+    /// Cap on segment bytes merged per second during compaction, so a
+    /// large merge does not starve queries of IO. None is unthrottled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compaction_max_bytes_per_sec: Option<u64>,
+
+    /// Durability the database is opened and created with; `Verified`
+    /// checks every segment's checksum on open. None means `Strict`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub durability: Option<DurabilityMode>,
+}
+
+fn default_bloom_fp_rate() -> f64 {
+    DEFAULT_BLOOM_FP_RATE
 }
 
 impl DatabaseConfig {
@@ -91,9 +173,7 @@ pub type ShardStats = ShardDiagnostics;
 /// `Arc<Mutex<MultiShardStore>>`.
 pub struct MultiShardStore {
     /// Database root path. None for ephemeral stores.
-    /// Used by create/open constructors; will be needed for future
-    /// operations (e.g., shard rebalancing).
-    #[allow(dead_code)]
+    /// Used by create/open constructors and by `reshard()`.
     db_path: Option<PathBuf>,
 
     /// Shard planner for routing nodes to shards.
@@ -104,7 +184,9 @@ pub struct MultiShardStore {
 
     /// Reverse index: node_id -> shard_id.
     /// Built during add_nodes() and rebuilt from all_node_ids() on open().
-    node_to_shard: HashMap<u128, u16>,
+    /// May be capped by `DatabaseConfig::node_to_shard_capacity`, in which
+    /// case a miss does not mean the node is absent.
+    node_to_shard: ShardMap,
 
     /// Global index for O(log N) point lookups across shards.
     /// Built during compaction from all shards' L1 entries.
@@ -114,6 +196,31 @@ pub struct MultiShardStore {
     /// containing enrichment edges FROM that node.
     /// Used for cross-shard edge queries.
     enrichment_edge_to_shard: HashMap<u128, HashSet<u16>>,
+
+    /// Reverse adjacency index for `get_incoming_edges`.
+    /// None when disabled in `DatabaseConfig` (queries fan out instead).
+    reverse_edge_index: Option<ReverseEdgeIndex>,
+
+    /// Write buffer auto-flush thresholds from `DatabaseConfig`.
+    /// None = caller's adaptive default.
+    auto_flush_node_limit: Option<usize>,
+    auto_flush_byte_limit: Option<usize>,
+
+    /// Query-time `madvise` hint from `DatabaseConfig`.
+    /// None = caller's adaptive default.
+    configured_mmap_advice: Option<MmapAdvice>,
+
+    /// `find_by_attr` cache size from `DatabaseConfig`, applied by the
+    /// engine. None = disabled.
+    configured_query_cache_capacity: Option<usize>,
+
+    /// Compaction IO cap from `DatabaseConfig`. None = unthrottled.
+    compaction_max_bytes_per_sec: Option<u64>,
+
+    /// LRU cache for `get_node`. None when disabled in `DatabaseConfig`.
+    /// Every `&mut self` path that changes which record an ID resolves to
+    /// must invalidate it.
+    node_cache: Option<Mutex<NodeCache>>,
 }
 
 // ── Constructors ───────────────────────────────────────────────────
@@ -126,25 +233,60 @@ impl MultiShardStore {
     ///
     /// Does NOT create ManifestStore — caller manages that separately.
     pub fn create(db_path: &Path, shard_count: u16) -> Result<Self> {
-        assert!(shard_count > 0, "shard_count must be > 0");
+        Self::create_with_config(db_path, DatabaseConfig {
+            shard_count,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        })
+    }
 
-        let config = DatabaseConfig { shard_count };
+    /// Create a new multi-shard database with an explicit config.
+    ///
+    /// The config is persisted to `db_config.json`, so settings such as
+    /// `compression` also apply to every later open.
+    pub fn create_with_config(db_path: &Path, config: DatabaseConfig) -> Result<Self> {
+        assert!(config.shard_count > 0, "shard_count must be > 0");
         config.write_to(db_path)?;
+        Self::with_empty_shards(db_path, &config)
+    }
 
+    /// Empty shards under `<db_path>/segments/NN/` for `config`, without
+    /// touching `db_config.json`.
+    fn with_empty_shards(db_path: &Path, config: &DatabaseConfig) -> Result<Self> {
+        let shard_count = config.shard_count;
         let mut shards = Vec::with_capacity(shard_count as usize);
         for i in 0..shard_count {
             let shard_path = shard_dir(db_path, i);
-            let shard = Shard::create_for_shard(&shard_path, i)?;
+            let mut shard = Shard::create_for_shard(&shard_path, i)?;
+            shard.set_compression(config.compression);
+            shard.set_bloom_fp_rate(config.bloom_fp_rate);
             shards.push(shard);
         }
 
         Ok(Self {
             db_path: Some(db_path.to_path_buf()),
-            planner: ShardPlanner::new(shard_count),
+            planner: ShardPlanner::with_strategy(shard_count, config.routing),
             shards,
-            node_to_shard: HashMap::new(),
+            node_to_shard: ShardMap::new(config.node_to_shard_capacity),
             global_index: None,
             enrichment_edge_to_shard: HashMap::new(),
+            reverse_edge_index: config.reverse_edge_index.then(ReverseEdgeIndex::new),
+            auto_flush_node_limit: config.auto_flush_node_limit,
+            auto_flush_byte_limit: config.auto_flush_byte_limit,
+            configured_mmap_advice: config.mmap_advice,
+            configured_query_cache_capacity: config.query_cache_capacity,
+            compaction_max_bytes_per_sec: config.compaction_max_bytes_per_sec,
+            node_cache: new_node_cache(config.node_cache_capacity),
         })
     }
 
@@ -160,6 +302,19 @@ impl MultiShardStore {
 
         let current = manifest_store.current();
 
+        // Manifests written before a reshard (historical snapshots, or a
+        // crash between the reshard commit and the config rewrite) may
+        // reference shards beyond the configured count.
+        let shard_count = current.node_segments.iter()
+            .chain(&current.edge_segments)
+            .chain(&current.l1_node_segments)
+            .chain(&current.l1_edge_segments)
+            .chain(&current.l2_node_segments)
+            .chain(&current.l2_edge_segments)
+            .filter_map(|d| d.shard_id)
+            .map(|id| id + 1)
+            .fold(config.shard_count, u16::max);
+
         // Group segment descriptors by shard_id
         let mut node_descs_by_shard: HashMap<u16, Vec<SegmentDescriptor>> = HashMap::new();
         let mut edge_descs_by_shard: HashMap<u16, Vec<SegmentDescriptor>> = HashMap::new();
@@ -179,22 +334,19 @@ impl MultiShardStore {
                 .push(desc.clone());
         }
 
-        // Group L1 segment descriptors by shard_id
-        let mut l1_node_descs_by_shard: HashMap<u16, SegmentDescriptor> = HashMap::new();
-        let mut l1_edge_descs_by_shard: HashMap<u16, SegmentDescriptor> = HashMap::new();
-
-        for desc in &current.l1_node_segments {
-            let shard_id = desc.shard_id.unwrap_or(0);
-            l1_node_descs_by_shard.insert(shard_id, desc.clone());
-        }
-        for desc in &current.l1_edge_segments {
-            let shard_id = desc.shard_id.unwrap_or(0);
-            l1_edge_descs_by_shard.insert(shard_id, desc.clone());
-        }
+        // Group L1 / L2 segment descriptors by shard_id
+        let by_shard = |descs: &[SegmentDescriptor]| -> HashMap<u16, SegmentDescriptor> {
+            descs.iter().map(|d| (d.shard_id.unwrap_or(0), d.clone())).collect()
+        };
+        let mut l1_node_descs_by_shard = by_shard(&current.l1_node_segments);
+        let mut l1_edge_descs_by_shard = by_shard(&current.l1_edge_segments);
+        let mut l2_node_descs_by_shard = by_shard(&current.l2_node_segments);
+        let mut l2_edge_descs_by_shard = by_shard(&current.l2_edge_segments);
 
         // Open each shard
-        let mut shards = Vec::with_capacity(config.shard_count as usize);
-        for i in 0..config.shard_count {
+        let verify = manifest_store.durability().verifies_checksums();
+        let mut shards = Vec::with_capacity(shard_count as usize);
+        for i in 0..shard_count {
             let shard_path = shard_dir(db_path, i);
             let node_descs = node_descs_by_shard.remove(&i).unwrap_or_default();
             let edge_descs = edge_descs_by_shard.remove(&i).unwrap_or_default();
@@ -204,30 +356,18 @@ impl MultiShardStore {
                 i,
                 node_descs,
                 edge_descs,
+                verify,
             )?;
 
-            // Load L1 segments if present in manifest
+            // Load L1 and L2 segments if present in manifest
             let l1_node_desc = l1_node_descs_by_shard.remove(&i);
             let l1_edge_desc = l1_edge_descs_by_shard.remove(&i);
-
-            let l1_node_seg = if let Some(desc) = &l1_node_desc {
-                let seg_path = shard_path.join(
-                    format!("seg_{:06}_nodes.seg", desc.segment_id),
-                );
-                Some(NodeSegmentV2::open(&seg_path)?)
-            } else {
-                None
-            };
-
-            let l1_edge_seg = if let Some(desc) = &l1_edge_desc {
-                let seg_path = shard_path.join(
-                    format!("seg_{:06}_edges.seg", desc.segment_id),
-                );
-                Some(EdgeSegmentV2::open(&seg_path)?)
-            } else {
-                None
-            };
-
+            let (l1_node_seg, l1_edge_seg) = open_compacted_segments(
+                &shard_path,
+                l1_node_desc.as_ref(),
+                l1_edge_desc.as_ref(),
+                verify,
+            )?;
             if l1_node_seg.is_some() || l1_edge_seg.is_some() {
                 shard.set_l1_segments(
                     l1_node_seg,
@@ -237,11 +377,30 @@ impl MultiShardStore {
                 );
             }
 
+            let l2_node_desc = l2_node_descs_by_shard.remove(&i);
+            let l2_edge_desc = l2_edge_descs_by_shard.remove(&i);
+            let (l2_node_seg, l2_edge_seg) = open_compacted_segments(
+                &shard_path,
+                l2_node_desc.as_ref(),
+                l2_edge_desc.as_ref(),
+                verify,
+            )?;
+            if l2_node_seg.is_some() || l2_edge_seg.is_some() {
+                shard.set_l2_segments(
+                    l2_node_seg,
+                    l2_node_desc,
+                    l2_edge_seg,
+                    l2_edge_desc,
+                );
+            }
+
+            shard.set_compression(config.compression);
+            shard.set_bloom_fp_rate(config.bloom_fp_rate);
             shards.push(shard);
         }
 
         // Rebuild node_to_shard from all shards
-        let mut node_to_shard = HashMap::new();
+        let mut node_to_shard = ShardMap::new(config.node_to_shard_capacity);
         for (shard_id, shard) in shards.iter().enumerate() {
             for node_id in shard.all_node_ids() {
                 node_to_shard.insert(node_id, shard_id as u16);
@@ -259,13 +418,24 @@ impl MultiShardStore {
             }
         }
 
+        let reverse_edge_index = config
+            .reverse_edge_index
+            .then(|| load_reverse_edge_index(db_path, &shards));
+
         Ok(Self {
             db_path: Some(db_path.to_path_buf()),
-            planner: ShardPlanner::new(config.shard_count),
+            planner: ShardPlanner::with_strategy(shard_count, config.routing),
             shards,
             node_to_shard,
             global_index: None,
             enrichment_edge_to_shard,
+            reverse_edge_index,
+            auto_flush_node_limit: config.auto_flush_node_limit,
+            auto_flush_byte_limit: config.auto_flush_byte_limit,
+            configured_mmap_advice: config.mmap_advice,
+            configured_query_cache_capacity: config.query_cache_capacity,
+            compaction_max_bytes_per_sec: config.compaction_max_bytes_per_sec,
+            node_cache: new_node_cache(config.node_cache_capacity),
         })
     }
 
@@ -273,17 +443,30 @@ impl MultiShardStore {
     ///
     /// Used for unit tests and temporary analysis graphs.
     pub fn ephemeral(shard_count: u16) -> Self {
+        Self::ephemeral_with_routing(shard_count, RoutingStrategy::ByFileDir)
+    }
+
+    /// Create ephemeral multi-shard store with an explicit node routing
+    /// strategy.
+    pub fn ephemeral_with_routing(shard_count: u16, routing: RoutingStrategy) -> Self {
         assert!(shard_count > 0, "shard_count must be > 0");
 
         let shards = (0..shard_count).map(|_| Shard::ephemeral()).collect();
 
         Self {
             db_path: None,
-            planner: ShardPlanner::new(shard_count),
+            planner: ShardPlanner::with_strategy(shard_count, routing),
             shards,
-            node_to_shard: HashMap::new(),
+            node_to_shard: ShardMap::default(),
             global_index: None,
             enrichment_edge_to_shard: HashMap::new(),
+            reverse_edge_index: None,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            configured_mmap_advice: None,
+            configured_query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            node_cache: None,
         }
     }
 }
@@ -291,14 +474,17 @@ impl MultiShardStore {
 // ── Write Operations ───────────────────────────────────────────────
 
 impl MultiShardStore {
-    /// Add nodes, routing each to its shard by file directory hash.
+    /// Add nodes, routing each to its shard per the routing strategy
+    /// (file directory hash by default).
     ///
     /// Updates `node_to_shard` for subsequent edge routing.
     pub fn add_nodes(&mut self, records: Vec<NodeRecordV2>) {
         // Group nodes by shard
+        self.invalidate_cached_nodes(records.iter().map(|n| n.id));
+
         let mut by_shard: HashMap<u16, Vec<NodeRecordV2>> = HashMap::new();
         for node in records {
-            let shard_id = self.planner.compute_shard_id(&node.file);
+            let shard_id = self.planner.compute_node_shard_id(&node);
             self.node_to_shard.insert(node.id, shard_id);
             by_shard.entry(shard_id).or_default().push(node);
         }
@@ -332,7 +518,7 @@ impl MultiShardStore {
                 by_shard.entry(shard_id).or_default().push(edge);
             } else {
                 // Normal edge: route to source node's shard
-                match self.node_to_shard.get(&edge.src).copied() {
+                match self.route_node(edge.src) {
                     Some(shard_id) => {
                         by_shard.entry(shard_id).or_default().push(edge);
                     }
@@ -351,6 +537,11 @@ impl MultiShardStore {
         }
 
         for (shard_id, edges) in by_shard {
+            if let Some(reverse) = &mut self.reverse_edge_index {
+                for edge in &edges {
+                    reverse.insert(edge.dst, edge.src, &edge.edge_type, shard_id);
+                }
+            }
             self.shards[shard_id as usize].upsert_edges(edges);
         }
 
@@ -371,6 +562,8 @@ impl MultiShardStore {
         node_ids: &HashSet<u128>,
         edge_keys: &HashSet<(u128, u128, String)>,
     ) {
+        self.invalidate_cached_nodes(node_ids.iter().copied());
+
         for shard in &mut self.shards {
             let mut merged_nodes: HashSet<u128> =
                 shard.tombstones().node_ids.iter().copied().collect();
@@ -391,118 +584,245 @@ impl MultiShardStore {
 // ── Flush ──────────────────────────────────────────────────────────
 
 impl MultiShardStore {
-    /// Flush all shards and commit a new manifest version.
-    ///
-    /// Uses the correct two-step ManifestStore protocol:
-    /// 1. Start with current manifest's segments
-    /// 2. Extend with NEW segments from flush
-    /// 3. Create manifest (takes FULL list)
-    /// 4. Commit the manifest
+    /// Flush all shards and commit a new manifest version (see
+    /// `commit_new_segments`).
     ///
     /// Returns the number of shards that actually flushed data.
     pub fn flush_all(&mut self, manifest_store: &mut ManifestStore) -> Result<usize> {
-        let shard_count = self.shards.len();
+        let (flushed_count, new_node_descs, new_edge_descs) = self.flush_shards(manifest_store)?;
+        if flushed_count == 0 {
+            return Ok(0);
+        }
+        commit_new_segments(manifest_store, new_node_descs, new_edge_descs)?;
+        Ok(flushed_count)
+    }
+
+    /// Flush only `shard_id`'s write buffer and commit a manifest that adds
+    /// just its new segments; other shards keep their buffered writes.
+    ///
+    /// Returns whether the shard had anything to flush. Errors with
+    /// `ShardOutOfRange` if `shard_id` is not a shard of this store.
+    pub fn flush_shard(&mut self, manifest_store: &mut ManifestStore, shard_id: u16) -> Result<bool> {
+        if shard_id as usize >= self.shards.len() {
+            return Err(GraphError::ShardOutOfRange {
+                shard_id,
+                shard_count: self.shards.len(),
+            });
+        }
+        let mut new_node_descs = Vec::new();
+        let mut new_edge_descs = Vec::new();
+        if !self.flush_one_shard(manifest_store, shard_id, &mut new_node_descs, &mut new_edge_descs)? {
+            return Ok(false);
+        }
+        commit_new_segments(manifest_store, new_node_descs, new_edge_descs)?;
+        Ok(true)
+    }
+
+    /// Flush only the shards whose write buffers hold nodes of `node_types`
+    /// or edges of `edge_types`, and commit a manifest adding their new
+    /// segments; other shards keep their buffered writes.
+    ///
+    /// Returns the number of shards that flushed data.
+    pub fn flush_shards_holding(
+        &mut self,
+        manifest_store: &mut ManifestStore,
+        node_types: &[String],
+        edge_types: &[String],
+    ) -> Result<usize> {
+        let mut new_node_descs = Vec::new();
+        let mut new_edge_descs = Vec::new();
+        let mut flushed_count = 0;
+        for shard_id in 0..self.shards.len() as u16 {
+            if self.shards[shard_id as usize].write_buffer_holds(node_types, edge_types)
+                && self.flush_one_shard(manifest_store, shard_id, &mut new_node_descs, &mut new_edge_descs)?
+            {
+                flushed_count += 1;
+            }
+        }
+        if flushed_count > 0 {
+            commit_new_segments(manifest_store, new_node_descs, new_edge_descs)?;
+        }
+        Ok(flushed_count)
+    }
+
+    /// Flush every shard's write buffer to new L0 segments, without
+    /// committing a manifest.
+    ///
+    /// Returns the number of shards that flushed data and the new node and
+    /// edge segment descriptors.
+    fn flush_shards(
+        &mut self,
+        manifest_store: &ManifestStore,
+    ) -> Result<(usize, Vec<SegmentDescriptor>, Vec<SegmentDescriptor>)> {
         let mut new_node_descs: Vec<SegmentDescriptor> = Vec::new();
         let mut new_edge_descs: Vec<SegmentDescriptor> = Vec::new();
         let mut flushed_count = 0;
 
-        for shard_idx in 0..shard_count {
-            let shard_id = shard_idx as u16;
+        for shard_id in 0..self.shards.len() as u16 {
+            if self.flush_one_shard(manifest_store, shard_id, &mut new_node_descs, &mut new_edge_descs)? {
+                flushed_count += 1;
+            }
+        }
 
-            // Determine segment IDs before flush
-            let (wb_nodes, wb_edges) = self.shards[shard_idx].write_buffer_size();
-            let node_seg_id = if wb_nodes > 0 {
-                Some(manifest_store.next_segment_id())
-            } else {
-                None
-            };
-            let edge_seg_id = if wb_edges > 0 {
-                Some(manifest_store.next_segment_id())
-            } else {
-                None
-            };
+        Ok((flushed_count, new_node_descs, new_edge_descs))
+    }
 
-            let flush_result = self.shards[shard_idx]
-                .flush_with_ids(node_seg_id, edge_seg_id)?;
+    /// Flush one shard's write buffer to new L0 segments, appending their
+    /// descriptors. Returns whether the shard flushed anything.
+    fn flush_one_shard(
+        &mut self,
+        manifest_store: &ManifestStore,
+        shard_id: u16,
+        new_node_descs: &mut Vec<SegmentDescriptor>,
+        new_edge_descs: &mut Vec<SegmentDescriptor>,
+    ) -> Result<bool> {
+        let shard_idx = shard_id as usize;
+
+        // Determine segment IDs before flush
+        let (wb_nodes, wb_edges) = self.shards[shard_idx].write_buffer_size();
+        let node_seg_id = if wb_nodes > 0 {
+            Some(manifest_store.next_segment_id())
+        } else {
+            None
+        };
+        let edge_seg_id = if wb_edges > 0 {
+            Some(manifest_store.next_segment_id())
+        } else {
+            None
+        };
 
-            if let Some(result) = flush_result {
-                flushed_count += 1;
+        let Some(result) = self.shards[shard_idx].flush_with_ids(node_seg_id, edge_seg_id)? else {
+            return Ok(false);
+        };
 
-                if let (Some(meta), Some(seg_id)) = (&result.node_meta, node_seg_id) {
-                    new_node_descs.push(SegmentDescriptor::from_meta(
-                        seg_id,
-                        SegmentType::Nodes,
-                        Some(shard_id),
-                        meta.clone(),
-                    ));
-                }
-                if let (Some(meta), Some(seg_id)) = (&result.edge_meta, edge_seg_id) {
-                    new_edge_descs.push(SegmentDescriptor::from_meta(
-                        seg_id,
-                        SegmentType::Edges,
-                        Some(shard_id),
-                        meta.clone(),
-                    ));
-                }
-            }
+        if let (Some(meta), Some(seg_id)) = (&result.node_meta, node_seg_id) {
+            new_node_descs.push(SegmentDescriptor::from_meta(
+                seg_id,
+                SegmentType::Nodes,
+                Some(shard_id),
+                meta.clone(),
+            ));
         }
-
-        if flushed_count == 0 {
-            return Ok(0);
+        if let (Some(meta), Some(seg_id)) = (&result.edge_meta, edge_seg_id) {
+            new_edge_descs.push(SegmentDescriptor::from_meta(
+                seg_id,
+                SegmentType::Edges,
+                Some(shard_id),
+                meta.clone(),
+            ));
         }
+        Ok(true)
+    }
+}
 
-        // Two-step ManifestStore protocol:
-        // Step 1: Start with current segments
-        let mut all_node_segs = manifest_store.current().node_segments.clone();
-        let mut all_edge_segs = manifest_store.current().edge_segments.clone();
+// ── Resharding ─────────────────────────────────────────────────────
 
-        // Step 2: Extend with NEW segments
-        all_node_segs.extend(new_node_descs);
-        all_edge_segs.extend(new_edge_descs);
+impl MultiShardStore {
+    /// Redistribute all live data over `new_shard_count` shards.
+    ///
+    /// Reads every live node and edge and routes them with a fresh
+    /// `ShardPlanner`: nodes first, so that edges can follow their source
+    /// node's shard (enrichment edges follow their file context, as in
+    /// `upsert_edges`). The new shards are flushed to fresh L0 segments and
+    /// a manifest referencing only those segments is committed; compacted
+    /// tiers and tombstones are dropped, deleted records are simply not
+    /// copied. `db_config.json` is rewritten after the commit.
+    ///
+    /// Old segment files stay on disk for historical snapshots. The caller
+    /// must flush pending writes first and hold exclusive access throughout.
+    pub fn reshard(&mut self, manifest_store: &mut ManifestStore, new_shard_count: u16) -> Result<()> {
+        if new_shard_count == 0 {
+            return Err(GraphError::InvalidFormat("shard_count must be > 0".to_string()));
+        }
 
-        // Step 3: Create manifest (full list)
-        let manifest = manifest_store.create_manifest(
-            all_node_segs,
-            all_edge_segs,
-            None,
-        )?;
+        let nodes: Vec<NodeRecordV2> = self.all_node_ids().into_iter().filter_map(|id| self.lookup_node(id)).collect();
+        let edges = self.iter_all_edges();
+
+        let (mut resharded, config) = match &self.db_path {
+            Some(db_path) => {
+                let mut config = DatabaseConfig::read_from(db_path)?
+                    .ok_or_else(|| GraphError::InvalidFormat("Missing db_config.json".to_string()))?;
+                config.shard_count = new_shard_count;
+                (Self::with_empty_shards(db_path, &config)?, Some(config))
+            }
+            None => {
+                let mut store = Self::ephemeral_with_routing(new_shard_count, self.planner.strategy());
+                store.reverse_edge_index = self.reverse_edge_index.as_ref().map(|_| ReverseEdgeIndex::new());
+                store.auto_flush_node_limit = self.auto_flush_node_limit;
+                store.auto_flush_byte_limit = self.auto_flush_byte_limit;
+                store.configured_mmap_advice = self.configured_mmap_advice;
+                store.configured_query_cache_capacity = self.configured_query_cache_capacity;
+                store.compaction_max_bytes_per_sec = self.compaction_max_bytes_per_sec;
+                store.node_to_shard = ShardMap::new(self.node_to_shard.capacity());
+                (store, None)
+            }
+        };
 
-        // Step 4: Commit
+        if let Some(shard) = self.shards.first() {
+            resharded.set_int_fields(shard.int_fields().to_vec());
+            resharded.set_edge_index_fields(shard.edge_index_fields().to_vec());
+        }
+        resharded.node_cache = new_node_cache(self.node_cache_capacity());
+        resharded.set_mmap_advice(self.mmap_advice());
+        resharded.add_nodes(nodes);
+        resharded.upsert_edges(edges)?;
+
+        let (_, node_descs, edge_descs) = resharded.flush_shards(manifest_store)?;
+        let mut manifest = manifest_store.create_manifest(node_descs, edge_descs, None)?;
+        manifest.l1_node_segments.clear();
+        manifest.l1_edge_segments.clear();
+        manifest.l2_node_segments.clear();
+        manifest.l2_edge_segments.clear();
         manifest_store.commit(manifest)?;
 
-        Ok(flushed_count)
+        if let (Some(db_path), Some(config)) = (&self.db_path, config) {
+            config.write_to(db_path)?;
+        }
+
+        *self = resharded;
+        Ok(())
     }
 }
 
 // ── Point Lookup ───────────────────────────────────────────────────
 
 impl MultiShardStore {
-    /// Get node by id. Checks node_to_shard first for O(1) routing,
-    /// then global index for O(log N) L1 lookup, falls back to fan-out.
+    /// Get node by id. Served from the node cache when enabled, otherwise
+    /// looked up as in `lookup_node` and cached.
     pub fn get_node(&self, id: u128) -> Option<NodeRecordV2> {
+        let Some(cache) = &self.node_cache else {
+            return self.lookup_node(id);
+        };
+        if let Some(node) = cache.lock().unwrap().get(id) {
+            return Some(node);
+        }
+        // Misses are not cached: a later add_nodes would have to know about
+        // them, and absent IDs are cheap to reject via bloom filters.
+        let node = self.lookup_node(id)?;
+        cache.lock().unwrap().insert(node.clone());
+        Some(node)
+    }
+
+    /// Uncached point lookup. Checks node_to_shard first for O(1) routing,
+    /// then global index for O(log N) L1/L2 lookup, falls back to fan-out.
+    fn lookup_node(&self, id: u128) -> Option<NodeRecordV2> {
         // Fast path: node_to_shard has the mapping (covers write buffer + L0)
         if let Some(&shard_id) = self.node_to_shard.get(&id) {
             return self.shards[shard_id as usize].get_node(id);
         }
 
-        // O(log N) path: global index for L1 direct lookup
-        if let Some(global_idx) = &self.global_index {
-            if let Some(entry) = global_idx.lookup(id) {
-                let shard = &self.shards[entry.shard as usize];
-                if let Some(l1) = shard.l1_node_segment() {
-                    // Check tombstone before returning
-                    if !shard.tombstones().contains_node(id) {
-                        return Some(l1.get_record(entry.offset as usize));
-                    } else {
-                        return None;
-                    }
-                }
+        // O(log N) path: the global index names the shard that held the
+        // node at compaction time. Only the shard is taken from it: the
+        // write buffer or L0 may hold a newer version than the L1/L2 record
+        // it points at, and Shard::get_node checks those first.
+        if let Some(entry) = self.global_index.as_ref().and_then(|idx| idx.lookup(id)) {
+            if let Some(node) = self.shards[entry.shard as usize].get_node(id) {
+                return Some(node);
             }
         }
 
-        // Slow path: fan-out (node might exist in a segment not yet
-        // indexed in node_to_shard — shouldn't happen in normal flow,
-        // but defensive)
+        // Slow path: fan-out (node's routing entry was evicted, or it
+        // exists in a segment not yet indexed in node_to_shard)
         for shard in &self.shards {
             if let Some(node) = shard.get_node(id) {
                 return Some(node);
@@ -512,6 +832,49 @@ impl MultiShardStore {
         None
     }
 
+    /// Shard holding `id` for edge routing. Misses in a bounded
+    /// `node_to_shard` are resolved via the global index, then a fan-out,
+    /// and re-inserted.
+    fn route_node(&mut self, id: u128) -> Option<u16> {
+        if let Some(&shard_id) = self.node_to_shard.get(&id) {
+            return Some(shard_id);
+        }
+        if !self.node_to_shard.is_bounded() {
+            return None;
+        }
+        let shard_id = self.global_index.as_ref()
+            .and_then(|idx| idx.lookup(id))
+            .map(|entry| entry.shard)
+            .or_else(|| {
+                self.shards.iter()
+                    .position(|shard| shard.get_node_including_tombstoned(id).is_some())
+                    .map(|i| i as u16)
+            })?;
+        self.node_to_shard.insert(id, shard_id);
+        Some(shard_id)
+    }
+
+    /// Node record even if tombstoned (see
+    /// `Shard::get_node_including_tombstoned`). Bypasses the node cache.
+    pub fn get_node_including_tombstoned(&self, id: u128) -> Option<NodeRecordV2> {
+        if let Some(&shard_id) = self.node_to_shard.get(&id) {
+            return self.shards[shard_id as usize].get_node_including_tombstoned(id);
+        }
+        self.shards.iter().find_map(|shard| shard.get_node_including_tombstoned(id))
+    }
+
+    /// IDs tombstoned in any shard. Pending deletes that have not been
+    /// flushed yet live in the engine, not here.
+    pub fn tombstoned_node_ids(&self) -> Vec<u128> {
+        let mut ids: Vec<u128> = self.shards
+            .iter()
+            .flat_map(|shard| shard.tombstones().node_ids.iter().copied())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
     /// Check if node exists across all shards.
     pub fn node_exists(&self, id: u128) -> bool {
         if let Some(&shard_id) = self.node_to_shard.get(&id) {
@@ -584,6 +947,14 @@ impl MultiShardStore {
     ///
     /// Same logical filters as `GraphEngineV2::find_by_attr`, but returns IDs
     /// directly for lower allocation overhead on hot query paths.
+    ///
+    /// Also returns how many IDs each shard contributed, as `(shard_id,
+    /// count)` in shard order; the IDs are grouped by shard in that same
+    /// order. A node matching in several shards counts for the first.
+    ///
+    /// With `PARALLEL_ATTR_SCAN_MIN_SHARDS` or more shards, shards are scanned
+    /// in parallel (rayon) and merged in shard order, so the result is the
+    /// same as the sequential scan.
     pub fn find_node_ids_by_attr(
         &self,
         node_type: Option<&str>,
@@ -593,15 +964,55 @@ impl MultiShardStore {
         exported: Option<bool>,
         metadata_filters: &[(String, String)],
         substring_match: bool,
-    ) -> Vec<u128> {
-        let mut results: Vec<u128> = Vec::new();
-        self.find_node_ids_by_attr_chunked(
-            node_type, node_type_prefix, file, name,
-            exported, metadata_filters, substring_match,
-            usize::MAX,
-            &mut |chunk| { results.extend_from_slice(chunk); true },
-        );
-        results
+    ) -> (Vec<u128>, Vec<(u16, usize)>) {
+        let scan = |shard: &Shard| {
+            shard.find_node_ids_by_attr(
+                node_type, node_type_prefix, file, name,
+                exported, metadata_filters, substring_match,
+            )
+        };
+        let per_shard: Vec<Vec<u128>> = if self.shards.len() >= PARALLEL_ATTR_SCAN_MIN_SHARDS {
+            use rayon::prelude::*;
+            self.shards.par_iter().map(scan).collect()
+        } else {
+            self.shards.iter().map(scan).collect()
+        };
+
+        let mut seen: HashSet<u128> = HashSet::new();
+        let mut ids: Vec<u128> = Vec::new();
+        let mut shard_counts = Vec::with_capacity(per_shard.len());
+        for (shard_id, shard_ids) in per_shard.into_iter().enumerate() {
+            let before = ids.len();
+            ids.extend(shard_ids.into_iter().filter(|id| seen.insert(*id)));
+            shard_counts.push((shard_id as u16, ids.len() - before));
+        }
+        (ids, shard_counts)
+    }
+
+    /// Explain an attribute search: per-shard access paths and filter
+    /// survivor counts (see `Shard::explain_attr_query`).
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain_attr_query(
+        &self,
+        node_type: Option<&str>,
+        node_type_prefix: Option<&str>,
+        file: Option<&str>,
+        name: Option<&str>,
+        exported: Option<bool>,
+        metadata_filters: &[(String, String)],
+        substring_match: bool,
+    ) -> Vec<ShardPlan> {
+        self.shards
+            .iter()
+            .enumerate()
+            .map(|(shard_id, shard)| {
+                shard.explain_attr_query(
+                    shard_id as u16,
+                    node_type, node_type_prefix, file, name,
+                    exported, metadata_filters, substring_match,
+                )
+            })
+            .collect()
     }
 
     /// Iterate matching node IDs in chunks via callback, without materializing all results.
@@ -672,16 +1083,40 @@ impl MultiShardStore {
         node_id: u128,
         edge_types: Option<&[&str]>,
     ) -> Vec<EdgeRecordV2> {
+        let mut results = Vec::new();
+        for sid in self.outgoing_edge_shards(node_id) {
+            results.extend(
+                self.shards[sid as usize].get_outgoing_edges(node_id, edge_types),
+            );
+        }
+        results
+    }
+
+    /// Check whether the edge `src -[edge_type]-> dst` exists.
+    ///
+    /// Only the shards that can hold edges out of `src` are consulted
+    /// (see `get_outgoing_edges`), and the scan stops at the first hit.
+    pub fn edge_exists(&self, src: u128, dst: u128, edge_type: &str) -> bool {
+        let types = [edge_type];
+        self.outgoing_edge_shards(src).into_iter().any(|sid| {
+            self.shards[sid as usize]
+                .get_outgoing_edges(src, Some(&types))
+                .iter()
+                .any(|e| e.dst == dst)
+        })
+    }
+
+    /// Shards that may hold edges out of `node_id`: the node's own shard
+    /// plus any enrichment shards. Every shard if the node is in neither
+    /// index.
+    fn outgoing_edge_shards(&self, node_id: u128) -> Vec<u16> {
         let source_shard = self.node_to_shard.get(&node_id).copied();
         let enrichment_shards = self.enrichment_edge_to_shard.get(&node_id);
 
-        // If node is in neither index, fall back to fan-out
-        if source_shard.is_none() && enrichment_shards.is_none() {
-            let mut results = Vec::new();
-            for shard in &self.shards {
-                results.extend(shard.get_outgoing_edges(node_id, edge_types));
-            }
-            return results;
+        // If node is in neither index, fall back to fan-out. An evicted
+        // routing entry hides the node's own shard, so fan out then too.
+        if source_shard.is_none() && (enrichment_shards.is_none() || self.node_to_shard.is_bounded()) {
+            return (0..self.shards.len() as u16).collect();
         }
 
         // Collect unique shard IDs to query
@@ -692,33 +1127,44 @@ impl MultiShardStore {
         if let Some(enrichment) = enrichment_shards {
             shard_ids.extend(enrichment);
         }
-
-        let mut results = Vec::new();
-        for sid in shard_ids {
-            results.extend(
-                self.shards[sid as usize].get_outgoing_edges(node_id, edge_types),
-            );
-        }
-        results
+        shard_ids.into_iter().collect()
     }
 
     /// Get incoming edges to a node.
     ///
     /// Incoming edges can be in ANY shard (because edge is stored in
     /// the source node's shard, and any node from any shard can point
-    /// to this node). Must always fan out.
+    /// to this node). With the reverse edge index enabled, only the edges
+    /// it records for `node_id` (and matching `edge_types`) are looked up,
+    /// in the shards holding them; otherwise all shards are scanned.
     pub fn get_incoming_edges(
         &self,
         node_id: u128,
         edge_types: Option<&[&str]>,
     ) -> Vec<EdgeRecordV2> {
         let mut results = Vec::new();
+        if let Some(reverse) = &self.reverse_edge_index {
+            for (sid, keys) in reverse.incoming(node_id, edge_types) {
+                results.extend(
+                    self.shards[sid as usize].get_incoming_edges_by_keys(node_id, &keys),
+                );
+            }
+            return results;
+        }
         for shard in &self.shards {
             results.extend(shard.get_incoming_edges(node_id, edge_types));
         }
         results
     }
 
+    /// IDs of all live nodes across all shards, sorted and deduplicated.
+    pub fn all_node_ids(&self) -> Vec<u128> {
+        let mut node_ids: Vec<u128> = self.shards.iter().flat_map(Shard::all_node_ids).collect();
+        node_ids.sort_unstable();
+        node_ids.dedup();
+        node_ids
+    }
+
     /// Iterate all edges across all shards.
     /// Each shard handles its own dedup and tombstone filtering.
     pub fn iter_all_edges(&self) -> Vec<EdgeRecordV2> {
@@ -919,6 +1365,7 @@ impl MultiShardStore {
                 edge_keys: all_tomb_edges.clone(),
             });
         }
+        self.invalidate_cached_nodes(tombstone_node_ids.iter().copied());
 
         // ── Phase 5: Add new data ──
         // Clone edges before upsert_edges (which takes ownership).
@@ -1046,18 +1493,20 @@ impl MultiShardStore {
     ///
     /// For each shard:
     /// 1. Check if L0 segment count >= config threshold
-    /// 2. Merge L0 + existing L1 into new L1 segment (in-memory)
-    /// 3. Write L1 segment files to shard directory (or in-memory for ephemeral)
-    /// 4. Build inverted indexes (by_type, by_file) for the L1 node segment
-    /// 5. Swap shard state: set L1, clear L0 + tombstones
+    /// 2. Merge L0 + existing L1 into new L1 segment (in-memory), or
+    ///    L0 + L1 + L2 into new L2 once L1 exceeds `l1_record_threshold`
+    /// 3. Write merged segment files to shard directory (or in-memory for ephemeral)
+    /// 4. Build inverted indexes (by_type, by_file) for the merged node segment
+    /// 5. Swap shard state: set L1 (or L2, emptying L1), clear L0 + tombstones
     ///
     /// After all shards are processed:
-    /// 6. Build global index from all L1 entries for O(log N) point lookups
+    /// 6. Build global index from all L1/L2 entries for O(log N) point lookups
     ///
     /// Then commit a new manifest with:
-    /// - L0 segments removed (compacted into L1)
-    /// - L1 segment descriptors added
-    /// - Tombstones cleared
+    /// - L0 segments removed (compacted into L1/L2)
+    /// - L1 and L2 segment descriptors added
+    /// - Tombstones cleared, unless a shard that was not compacted still
+    ///   holds some
     /// - CompactionInfo recorded
     ///
     /// Returns CompactionResult with stats.
@@ -1081,65 +1530,90 @@ impl MultiShardStore {
         config: &CompactionConfig,
         thread_count: Option<usize>,
     ) -> Result<CompactionResult> {
-        use crate::storage_v2::compaction::coordinator::{
-            compact_shard, should_compact, ShardCompactionResult,
-        };
-        use crate::storage_v2::compaction::CompactionInfo;
-        use crate::storage_v2::resource::ResourceManager;
-        use rayon::prelude::*;
-        use std::time::Instant;
-
-        let start = Instant::now();
-        let mut shards_compacted = Vec::new();
-        let mut total_nodes_merged: u64 = 0;
-        let mut total_edges_merged: u64 = 0;
-        let mut total_tombstones_removed: u64 = 0;
-
-        // Collect L1 descriptors for manifest
-        let mut l1_node_descs: Vec<SegmentDescriptor> = Vec::new();
-        let mut l1_edge_descs: Vec<SegmentDescriptor> = Vec::new();
-
-        // Track which shards were compacted so we know which L0 segments to remove
-        let mut compacted_shard_ids: HashSet<u16> = HashSet::new();
-
-        // Collect entries for global index from all compacted shards
-        let mut global_index_entries: Vec<IndexEntry> = Vec::new();
+        use crate::storage_v2::compaction::coordinator::should_compact;
 
         // ── Phase 1: Classify shards ────────────────────────────────────
-        // Collect non-compacted shard data and identify compaction targets.
+        // Identify compaction targets. Non-compacted shards keep their L1/L2
+        // state untouched; it is collected for the manifest after Phase 3.
 
-        let mut shards_to_compact: Vec<usize> = Vec::new();
-
-        for shard_idx in 0..self.shards.len() {
-            let shard_id = shard_idx as u16;
+        let shards_to_compact: Vec<usize> = (0..self.shards.len())
+            .filter(|&idx| should_compact(&self.shards[idx], config))
+            .collect();
 
-            if !should_compact(&self.shards[shard_idx], config) {
-                // Preserve existing L1 descriptors for non-compacted shards
-                if let Some(desc) = self.shards[shard_idx].l1_node_descriptor() {
-                    l1_node_descs.push(desc.clone());
-                }
-                if let Some(desc) = self.shards[shard_idx].l1_edge_descriptor() {
-                    l1_edge_descs.push(desc.clone());
-                }
-                // Collect existing L1 entries for global index
-                if let Some(l1_seg) = self.shards[shard_idx].l1_node_segment() {
-                    let l1_seg_id = self.shards[shard_idx]
-                        .l1_node_descriptor()
-                        .map_or(0, |d| d.segment_id);
-                    for i in 0..l1_seg.record_count() {
-                        global_index_entries.push(IndexEntry::new(
-                            l1_seg.get_id(i),
-                            l1_seg_id,
-                            i as u32,
-                            shard_id,
-                        ));
-                    }
-                }
-                continue;
-            }
+        self.compact_shards(manifest_store, config, shards_to_compact, thread_count)
+    }
 
-            shards_to_compact.push(shard_idx);
+    /// Compact one shard regardless of its L0 segment count.
+    ///
+    /// Other shards are left as they are: the committed manifest only
+    /// replaces this shard's L0 segments. Errors with `ShardOutOfRange`
+    /// if `shard_id` is not a shard of this store.
+    pub fn compact_single_shard(
+        &mut self,
+        manifest_store: &mut ManifestStore,
+        config: &CompactionConfig,
+        shard_id: u16,
+    ) -> Result<CompactionResult> {
+        if shard_id as usize >= self.shards.len() {
+            return Err(GraphError::ShardOutOfRange {
+                shard_id,
+                shard_count: self.shards.len(),
+            });
         }
+        self.compact_shards(manifest_store, config, vec![shard_id as usize], Some(1))
+    }
+
+    /// Phases 2-3 of compaction for an explicit list of shard indices.
+    fn compact_shards(
+        &mut self,
+        manifest_store: &mut ManifestStore,
+        config: &CompactionConfig,
+        shards_to_compact: Vec<usize>,
+        thread_count: Option<usize>,
+    ) -> Result<CompactionResult> {
+        let prepared = self.prepare_shards(manifest_store, config, shards_to_compact, thread_count)?;
+        let result = self.install_compaction(manifest_store, prepared)?;
+        // Nothing can change between the two steps under &mut self
+        Ok(result.expect("compaction inputs changed under &mut self"))
+    }
+
+    /// Everything `compact_with_threads` does that readers can't observe:
+    /// pick the shards to compact, merge them, write the merged segments
+    /// and build their indexes.
+    ///
+    /// Only needs `&self`, so the expensive part of compaction can run
+    /// under a shared lock while queries keep being served. Hand the result
+    /// to `install_compaction` to swap it in.
+    pub fn prepare_compaction(
+        &self,
+        manifest_store: &ManifestStore,
+        config: &CompactionConfig,
+        thread_count: Option<usize>,
+    ) -> Result<PreparedCompaction> {
+        use crate::storage_v2::compaction::coordinator::should_compact;
+
+        let shards_to_compact: Vec<usize> = (0..self.shards.len())
+            .filter(|&idx| should_compact(&self.shards[idx], config))
+            .collect();
+        self.prepare_shards(manifest_store, config, shards_to_compact, thread_count)
+    }
+
+    /// Phase 2 plus the segment-building half of phase 3 for the given
+    /// shards, recording which segments and tombstones each merge read.
+    fn prepare_shards(
+        &self,
+        manifest_store: &ManifestStore,
+        config: &CompactionConfig,
+        shards_to_compact: Vec<usize>,
+        thread_count: Option<usize>,
+    ) -> Result<PreparedCompaction> {
+        use crate::storage_v2::compaction::coordinator::compact_shard_throttled;
+        use crate::storage_v2::compaction::Throttle;
+        use crate::storage_v2::resource::ResourceManager;
+        use rayon::prelude::*;
+        use std::time::Instant;
+
+        let start = Instant::now();
 
         // ── Prefetch segment files ─────────────────────────────────────
         // Hint the OS to asynchronously read segment files into the page
@@ -1164,6 +1638,8 @@ impl MultiShardStore {
                     segment::prefetch_file(&p).ok();
                 }
             }
+            // Merges read every input front to back
+            self.shards[shard_idx].advise_segments(MmapAdvice::Sequential);
         }
 
         // ── Phase 2: Parallel compaction ────────────────────────────────
@@ -1172,6 +1648,8 @@ impl MultiShardStore {
 
         let threads = thread_count
             .unwrap_or_else(|| ResourceManager::auto_tune().compaction_threads);
+        // One throttle for the whole run: the byte cap is global, not per thread.
+        let throttle = Throttle::new(config.max_bytes_per_sec);
 
         let compaction_results: Vec<(usize, Result<ShardCompactionResult>)> = if threads <= 1
             || shards_to_compact.len() <= 1
@@ -1179,7 +1657,7 @@ impl MultiShardStore {
             // Sequential path: no thread pool overhead for single shard/thread
             shards_to_compact
                 .iter()
-                .map(|&idx| (idx, compact_shard(&self.shards[idx])))
+                .map(|&idx| (idx, compact_shard_throttled(&self.shards[idx], config, &throttle)))
                 .collect()
         } else {
             let pool = rayon::ThreadPoolBuilder::new()
@@ -1190,108 +1668,208 @@ impl MultiShardStore {
             pool.install(|| {
                 shards_to_compact
                     .par_iter()
-                    .map(|&idx| (idx, compact_shard(&self.shards[idx])))
+                    .map(|&idx| (idx, compact_shard_throttled(&self.shards[idx], config, &throttle)))
                     .collect()
             })
         };
 
-        // ── Phase 3: Apply results (sequential) ────────────────────────
-        // Write segments to disk, update shard state, build indexes.
+        // Inputs go back to query-time advice; if the merge is installed
+        // they are dropped anyway
+        for &shard_idx in &shards_to_compact {
+            let shard = &self.shards[shard_idx];
+            shard.advise_segments(shard.mmap_advice());
+        }
 
-        for (shard_idx, result) in compaction_results {
-            let result = result?;
-            let shard_id = shard_idx as u16;
-            let shard_path_owned = self.shards[shard_idx].path().map(|p| p.to_path_buf());
-
-            // Build L1 node segment (if any merged nodes)
-            let mut l1_node_seg: Option<NodeSegmentV2> = None;
-            let mut l1_node_desc: Option<SegmentDescriptor> = None;
-            let mut by_type_idx: Option<InvertedIndex> = None;
-            let mut by_file_idx: Option<InvertedIndex> = None;
-            let mut by_name_idx: Option<InvertedIndex> = None;
-
-            if let (Some(bytes), Some(meta)) = (&result.node_segment_bytes, &result.node_meta) {
-                let seg_id = manifest_store.next_segment_id();
-
-                let seg = if let Some(shard_path) = &shard_path_owned {
-                    let seg_path = shard_path.join(format!("seg_{:06}_nodes.seg", seg_id));
-                    std::fs::write(&seg_path, bytes)?;
-                    NodeSegmentV2::open(&seg_path)?
-                } else {
-                    NodeSegmentV2::from_bytes(bytes)?
-                };
-
-                let desc = SegmentDescriptor::from_meta(
-                    seg_id, SegmentType::Nodes, Some(shard_id), meta.clone(),
-                );
+        // ── Phase 3a: Build merged segments ────────────────────────────
+        // Write segments to disk and build their indexes. Segment IDs come
+        // from an atomic counter, so this needs no write access either.
 
-                // Build inverted indexes from the L1 segment
-                let records: Vec<NodeRecordV2> = seg.iter().collect();
-                let built = build_inverted_indexes(&records, shard_id, seg_id)?;
-                by_type_idx = Some(InvertedIndex::from_bytes(&built.by_type)?);
-                by_file_idx = Some(InvertedIndex::from_bytes(&built.by_file)?);
-                by_name_idx = Some(InvertedIndex::from_bytes(&built.by_name)?);
-
-                // Collect entries for global index
-                for (offset, record) in records.iter().enumerate() {
-                    global_index_entries.push(IndexEntry::new(
-                        record.id, seg_id, offset as u32, shard_id,
-                    ));
+        let mut prepared = PreparedCompaction {
+            shard_count: self.shards.len(),
+            shards: Vec::with_capacity(compaction_results.len()),
+            started: start,
+        };
+        for (shard_idx, result) in compaction_results {
+            let merged = self.build_merged_shard(manifest_store, shard_idx, result?);
+            // On error, drop what was already written for other shards
+            match merged {
+                Ok(merged) => prepared.shards.push(merged),
+                Err(e) => {
+                    prepared.discard();
+                    return Err(e);
                 }
-
-                l1_node_descs.push(desc.clone());
-                total_nodes_merged += meta.record_count;
-                l1_node_seg = Some(seg);
-                l1_node_desc = Some(desc);
             }
+        }
+        Ok(prepared)
+    }
 
-            // Build L1 edge segment (if any merged edges)
-            let mut l1_edge_seg: Option<EdgeSegmentV2> = None;
-            let mut l1_edge_desc: Option<SegmentDescriptor> = None;
-            if let (Some(bytes), Some(meta)) = (&result.edge_segment_bytes, &result.edge_meta) {
-                let seg_id = manifest_store.next_segment_id();
-
-                let seg = if let Some(shard_path) = &shard_path_owned {
-                    let seg_path = shard_path.join(format!("seg_{:06}_edges.seg", seg_id));
-                    std::fs::write(&seg_path, bytes)?;
-                    EdgeSegmentV2::open(&seg_path)?
-                } else {
-                    EdgeSegmentV2::from_bytes(bytes)?
-                };
-
-                let desc = SegmentDescriptor::from_meta(
-                    seg_id, SegmentType::Edges, Some(shard_id), meta.clone(),
-                );
+    /// Turn one shard's merge result into opened segments plus indexes.
+    fn build_merged_shard(
+        &self,
+        manifest_store: &ManifestStore,
+        shard_idx: usize,
+        result: ShardCompactionResult,
+    ) -> Result<MergedShard> {
+        let shard_id = shard_idx as u16;
+        let shard_path = self.shards[shard_idx].path();
+        let mut merged = MergedShard {
+            shard_idx,
+            inputs: CompactionInputs::of(&self.shards[shard_idx]),
+            into_l2: result.into_l2,
+            tombstones_removed: result.tombstones_removed,
+            node_seg: None,
+            node_desc: None,
+            edge_seg: None,
+            edge_desc: None,
+            indexes: (None, None, None),
+            written: Vec::new(),
+        };
 
-                l1_edge_descs.push(desc.clone());
-                total_edges_merged += meta.record_count;
-                l1_edge_seg = Some(seg);
-                l1_edge_desc = Some(desc);
-            }
+        // Build merged node segment (if any merged nodes)
+        if let (Some(bytes), Some(meta)) = (&result.node_segment_bytes, &result.node_meta) {
+            let seg_id = manifest_store.next_segment_id();
+
+            let seg = if let Some(shard_path) = shard_path {
+                let seg_path = shard_path.join(format!("seg_{:06}_nodes.seg", seg_id));
+                std::fs::write(&seg_path, bytes)?;
+                merged.written.push(seg_path.clone());
+                NodeSegmentV2::open(&seg_path)?
+            } else {
+                NodeSegmentV2::from_bytes(bytes)?
+            };
 
-            // Set L1 segments and indexes on shard
-            self.shards[shard_idx].set_l1_segments(
-                l1_node_seg, l1_node_desc,
-                l1_edge_seg, l1_edge_desc,
+            // Build inverted indexes from the merged segment
+            seg.name_prefix_index();
+            let records: Vec<NodeRecordV2> = seg.iter().collect();
+            let built = build_inverted_indexes(&records, shard_id, seg_id)?;
+            merged.indexes = (
+                Some(InvertedIndex::from_bytes(&built.by_type)?),
+                Some(InvertedIndex::from_bytes(&built.by_file)?),
+                Some(InvertedIndex::from_bytes(&built.by_name)?),
             );
-            self.shards[shard_idx].set_l1_indexes(by_type_idx, by_file_idx, by_name_idx);
 
-            total_tombstones_removed += result.tombstones_removed;
+            merged.node_desc = Some(SegmentDescriptor::from_meta(
+                seg_id, SegmentType::Nodes, Some(shard_id), meta.clone(),
+            ));
+            merged.node_seg = Some(seg);
+        }
+
+        // Build merged edge segment (if any merged edges)
+        if let (Some(bytes), Some(meta)) = (&result.edge_segment_bytes, &result.edge_meta) {
+            let seg_id = manifest_store.next_segment_id();
+
+            let seg = if let Some(shard_path) = shard_path {
+                let seg_path = shard_path.join(format!("seg_{:06}_edges.seg", seg_id));
+                std::fs::write(&seg_path, bytes)?;
+                merged.written.push(seg_path.clone());
+                EdgeSegmentV2::open(&seg_path)?
+            } else {
+                EdgeSegmentV2::from_bytes(bytes)?
+            };
+
+            merged.edge_desc = Some(SegmentDescriptor::from_meta(
+                seg_id, SegmentType::Edges, Some(shard_id), meta.clone(),
+            ));
+            merged.edge_seg = Some(seg);
+        }
+
+        Ok(merged)
+    }
+
+    /// Swap segments built by `prepare_compaction` into their shards and
+    /// commit the manifest (the rest of phase 3). Cheap compared to the
+    /// merge: this is the only part of compaction that needs `&mut self`.
+    ///
+    /// Returns `Ok(None)` without changing anything if a merged shard's
+    /// segments or tombstones changed since the merge (a flush, delete or
+    /// reshard ran in between); the merge is stale and must be redone.
+    pub fn install_compaction(
+        &mut self,
+        manifest_store: &mut ManifestStore,
+        prepared: PreparedCompaction,
+    ) -> Result<Option<CompactionResult>> {
+        use crate::storage_v2::compaction::CompactionInfo;
+
+        let stale = prepared.shard_count != self.shards.len()
+            || prepared
+                .shards
+                .iter()
+                .any(|m| m.inputs != CompactionInputs::of(&self.shards[m.shard_idx]));
+        if stale {
+            prepared.discard();
+            return Ok(None);
+        }
+        let PreparedCompaction { shards: merged_shards, started: start, .. } = prepared;
+
+        let mut shards_compacted = Vec::new();
+        let mut total_nodes_merged: u64 = 0;
+        let mut total_edges_merged: u64 = 0;
+        let mut total_tombstones_removed: u64 = 0;
+
+        // Track which shards were compacted so we know which L0 segments to remove
+        let mut compacted_shard_ids: HashSet<u16> = HashSet::new();
+
+        // ── Phase 3b: Swap in merged segments ──────────────────────────
+
+        for merged in merged_shards {
+            let shard_id = merged.shard_idx as u16;
+            total_nodes_merged += merged.node_desc.as_ref().map_or(0, |d| d.record_count);
+            total_edges_merged += merged.edge_desc.as_ref().map_or(0, |d| d.record_count);
+
+            // Swap in the merged segments: they replace L1, or replace L2
+            // and leave L1 empty when L1 was merged down.
+            let (by_type_idx, by_file_idx, by_name_idx) = merged.indexes;
+            let shard = &mut self.shards[merged.shard_idx];
+            if merged.into_l2 {
+                shard.set_l1_segments(None, None, None, None);
+                shard.set_l1_indexes(None, None, None);
+                shard.set_l2_segments(
+                    merged.node_seg, merged.node_desc,
+                    merged.edge_seg, merged.edge_desc,
+                );
+                shard.set_l2_indexes(by_type_idx, by_file_idx, by_name_idx);
+            } else {
+                shard.set_l1_segments(
+                    merged.node_seg, merged.node_desc,
+                    merged.edge_seg, merged.edge_desc,
+                );
+                shard.set_l1_indexes(by_type_idx, by_file_idx, by_name_idx);
+            }
+
+            total_tombstones_removed += merged.tombstones_removed;
             compacted_shard_ids.insert(shard_id);
             shards_compacted.push(shard_id);
         }
 
         if compacted_shard_ids.is_empty() {
-            return Ok(CompactionResult {
+            return Ok(Some(CompactionResult {
                 shards_compacted: Vec::new(),
                 nodes_merged: 0,
                 edges_merged: 0,
                 tombstones_removed: 0,
                 duration_ms: start.elapsed().as_millis() as u64,
-            });
+            }));
+        }
+
+        // Collect L1/L2 descriptors and global index entries from every
+        // shard (compacted + preserved)
+        let mut l1_node_descs: Vec<SegmentDescriptor> = Vec::new();
+        let mut l1_edge_descs: Vec<SegmentDescriptor> = Vec::new();
+        let mut l2_node_descs: Vec<SegmentDescriptor> = Vec::new();
+        let mut l2_edge_descs: Vec<SegmentDescriptor> = Vec::new();
+        let mut global_index_entries: Vec<IndexEntry> = Vec::new();
+        for (shard_idx, shard) in self.shards.iter().enumerate() {
+            l1_node_descs.extend(shard.l1_node_descriptor().cloned());
+            l1_edge_descs.extend(shard.l1_edge_descriptor().cloned());
+            l2_node_descs.extend(shard.l2_node_descriptor().cloned());
+            l2_edge_descs.extend(shard.l2_edge_descriptor().cloned());
+            global_index_entries.extend(compacted_index_entries(shard, shard_idx as u16));
         }
 
-        // Build global index from all L1 entries (compacted + preserved)
+        // Cached records may point into the replaced segments
+        self.clear_node_cache();
+
+        // Build global index from all L1/L2 entries
         if !global_index_entries.is_empty() {
             self.global_index = Some(GlobalIndex::build(global_index_entries));
         }
@@ -1318,9 +1896,11 @@ impl MultiShardStore {
             None,
         )?;
 
-        // Inject L1 descriptors and compaction info
+        // Inject L1/L2 descriptors and compaction info
         manifest.l1_node_segments = l1_node_descs;
         manifest.l1_edge_segments = l1_edge_descs;
+        manifest.l2_node_segments = l2_node_descs;
+        manifest.l2_edge_segments = l2_edge_descs;
         manifest.last_compaction = Some(CompactionInfo {
             manifest_version: manifest.version,
             timestamp_ms: std::time::SystemTime::now()
@@ -1330,10 +1910,16 @@ impl MultiShardStore {
             l0_segments_merged: compacted_shard_ids.len() as u32,
         });
 
-        // Clear tombstones in manifest for compacted shards
-        // (tombstones were applied during merge)
-        manifest.tombstoned_node_ids.clear();
-        manifest.tombstoned_edge_keys.clear();
+        // Clear tombstones in manifest (they were applied during merge).
+        // Tombstones are shared by all shards, so keep them while a shard
+        // left out of this run still has to apply them.
+        let tombstones_pending = self.shards.iter().enumerate().any(|(idx, shard)| {
+            !compacted_shard_ids.contains(&(idx as u16)) && !shard.tombstones().is_empty()
+        });
+        if !tombstones_pending {
+            manifest.tombstoned_node_ids.clear();
+            manifest.tombstoned_edge_keys.clear();
+        }
 
         manifest_store.commit(manifest)?;
 
@@ -1342,14 +1928,264 @@ impl MultiShardStore {
             self.shards[shard_id as usize].clear_l0_after_compaction();
         }
 
-        Ok(CompactionResult {
+        // Drop reverse index entries for edges removed by the merge
+        self.rebuild_reverse_edge_index();
+
+        Ok(Some(CompactionResult {
             shards_compacted,
             nodes_merged: total_nodes_merged,
             edges_merged: total_edges_merged,
             tombstones_removed: total_tombstones_removed,
             duration_ms: start.elapsed().as_millis() as u64,
-        })
+        }))
+    }
+}
+
+/// Shard merges built by `MultiShardStore::prepare_compaction`, not yet
+/// visible to readers. Install with `MultiShardStore::install_compaction`.
+pub struct PreparedCompaction {
+    shard_count: usize,
+    shards: Vec<MergedShard>,
+    started: std::time::Instant,
+}
+
+impl PreparedCompaction {
+    /// Drop a merge that won't be installed, removing its segment files.
+    fn discard(self) {
+        for merged in self.shards {
+            for path in merged.written {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// One shard's merged segments and indexes, ready to swap in.
+struct MergedShard {
+    shard_idx: usize,
+    inputs: CompactionInputs,
+    into_l2: bool,
+    tombstones_removed: u64,
+    node_seg: Option<NodeSegmentV2>,
+    node_desc: Option<SegmentDescriptor>,
+    edge_seg: Option<EdgeSegmentV2>,
+    edge_desc: Option<SegmentDescriptor>,
+    /// by_type, by_file, by_name
+    indexes: (Option<InvertedIndex>, Option<InvertedIndex>, Option<InvertedIndex>),
+    /// Segment files written for this merge (disk shards only)
+    written: Vec<PathBuf>,
+}
+
+/// What a shard merge read: its segment IDs and tombstone counts. If these
+/// still match at install time, the merge reflects the shard's current data.
+#[derive(Debug, PartialEq, Eq)]
+struct CompactionInputs {
+    l0_segment_ids: Vec<u64>,
+    upper_segment_ids: [Option<u64>; 4],
+    tombstones: (usize, usize),
+}
+
+impl CompactionInputs {
+    fn of(shard: &Shard) -> Self {
+        let id = |d: Option<&SegmentDescriptor>| d.map(|d| d.segment_id);
+        let tombstones = shard.tombstones();
+        Self {
+            l0_segment_ids: shard
+                .l0_node_descriptors()
+                .iter()
+                .chain(shard.l0_edge_descriptors())
+                .map(|d| d.segment_id)
+                .collect(),
+            upper_segment_ids: [
+                id(shard.l1_node_descriptor()),
+                id(shard.l1_edge_descriptor()),
+                id(shard.l2_node_descriptor()),
+                id(shard.l2_edge_descriptor()),
+            ],
+            tombstones: (tombstones.node_count(), tombstones.edge_count()),
+        }
+    }
+}
+
+// ── Node Cache ─────────────────────────────────────────────────────
+
+impl MultiShardStore {
+    /// Capacity of the `get_node` cache, or None when it is disabled.
+    pub fn node_cache_capacity(&self) -> Option<usize> {
+        self.node_cache.as_ref().map(|c| c.lock().unwrap().capacity())
+    }
+
+    /// Enable (with the given capacity) or disable the `get_node` cache for
+    /// this session, dropping anything cached so far. The persisted
+    /// setting lives in `DatabaseConfig::node_cache_capacity`.
+    pub fn set_node_cache_capacity(&mut self, capacity: Option<usize>) {
+        self.node_cache = new_node_cache(capacity);
+    }
+
+    /// `madvise` hint set in `DatabaseConfig`; None defers to the caller's
+    /// adaptive choice.
+    pub fn configured_mmap_advice(&self) -> Option<MmapAdvice> {
+        self.configured_mmap_advice
+    }
+
+    /// `find_by_attr` cache size set in `DatabaseConfig`; None when the
+    /// cache is disabled.
+    pub fn configured_query_cache_capacity(&self) -> Option<usize> {
+        self.configured_query_cache_capacity
+    }
+
+    /// Compaction IO cap in bytes per second set in `DatabaseConfig`;
+    /// None when compaction is unthrottled.
+    pub fn compaction_max_bytes_per_sec(&self) -> Option<u64> {
+        self.compaction_max_bytes_per_sec
+    }
+
+    /// Query-time `madvise` hint currently applied to the shards' segments.
+    pub fn mmap_advice(&self) -> MmapAdvice {
+        self.shards.first().map_or(MmapAdvice::Normal, Shard::mmap_advice)
+    }
+
+    /// Apply a query-time `madvise` hint to every shard's segments, now
+    /// and as new segments are loaded.
+    pub fn set_mmap_advice(&mut self, advice: MmapAdvice) {
+        for shard in &mut self.shards {
+            shard.set_mmap_advice(advice);
+        }
+    }
+
+    /// Number of nodes currently cached.
+    pub fn cached_node_count(&self) -> usize {
+        self.node_cache.as_ref().map_or(0, |c| c.lock().unwrap().len())
+    }
+
+    fn invalidate_cached_nodes(&mut self, ids: impl IntoIterator<Item = u128>) {
+        if let Some(cache) = &mut self.node_cache {
+            let cache = cache.get_mut().unwrap();
+            for id in ids {
+                cache.remove(id);
+            }
+        }
+    }
+
+    fn clear_node_cache(&mut self) {
+        if let Some(cache) = &mut self.node_cache {
+            cache.get_mut().unwrap().clear();
+        }
+    }
+}
+
+fn new_node_cache(capacity: Option<usize>) -> Option<Mutex<NodeCache>> {
+    capacity.filter(|&c| c > 0).map(|c| Mutex::new(NodeCache::new(c)))
+}
+
+// ── Reverse Edge Index ─────────────────────────────────────────────
+
+impl MultiShardStore {
+    /// Whether the reverse edge index is maintained.
+    pub fn has_reverse_edge_index(&self) -> bool {
+        self.reverse_edge_index.is_some()
+    }
+
+    /// Enable or disable the reverse edge index for this session.
+    ///
+    /// Enabling builds it from all shards; the persisted setting lives in
+    /// `DatabaseConfig::reverse_edge_index`.
+    pub fn set_reverse_edge_index(&mut self, enabled: bool) {
+        self.reverse_edge_index = enabled.then(|| build_reverse_edge_index(&self.shards));
+        self.save_reverse_edge_index();
+    }
+
+    /// Rebuild the reverse edge index from shard contents, dropping stale
+    /// entries for deleted edges, and save it. No-op when the index is
+    /// disabled.
+    ///
+    /// Complexity: O(E) over all live edges.
+    pub fn rebuild_reverse_edge_index(&mut self) {
+        if self.reverse_edge_index.is_some() {
+            self.reverse_edge_index = Some(build_reverse_edge_index(&self.shards));
+            self.save_reverse_edge_index();
+        }
+    }
+
+    /// Save the reverse edge index for the next open (on-disk stores only).
+    /// It holds every edge of the current segments, so those are recorded
+    /// as covered. A failed save is logged: the next open scans more.
+    fn save_reverse_edge_index(&self) {
+        let (Some(reverse), Some(db_path)) = (&self.reverse_edge_index, &self.db_path) else {
+            return;
+        };
+        let segments = self.shards.iter().flat_map(|s| s.edge_segment_ids()).collect();
+        if let Err(e) = reverse.write_to(db_path, self.shards.len() as u16, segments) {
+            tracing::warn!("Could not save reverse edge index: {}", e);
+        }
+    }
+
+    /// Rebuild reverse edge index entries only for edges of `edge_types`
+    /// and edges into nodes of `node_types`, dropping stale entries for
+    /// deleted edges and nodes of those types; all other entries are left
+    /// as they are. No-op when the index is disabled.
+    ///
+    /// Complexity: O(S * T) edge-type index lookups plus the matching
+    /// edges, and one pass over the index entries. With `node_types`, an
+    /// O(E) scan of live edges for the ones into those nodes.
+    pub fn rebuild_reverse_edge_index_for_types(&mut self, node_types: &[String], edge_types: &[String]) {
+        if self.reverse_edge_index.is_none() {
+            return;
+        }
+        let node_types: HashSet<&str> = node_types.iter().map(String::as_str).collect();
+        let mut dsts: HashSet<u128> = node_types
+            .iter()
+            .flat_map(|t| self.find_node_ids_by_type(t))
+            .collect();
+        dsts.extend(self.tombstoned_node_ids().into_iter().filter(|&id| {
+            self.get_node_including_tombstoned(id)
+                .is_some_and(|node| node_types.contains(node.node_type.as_str()))
+        }));
+        let edges: Vec<Vec<EdgeRecordV2>> = self.shards
+            .iter()
+            .map(|s| if dsts.is_empty() {
+                edge_types.iter().flat_map(|t| s.get_edges_by_type(t)).collect()
+            } else {
+                s.iter_all_edges()
+            })
+            .collect();
+        let edge_types: HashSet<String> = edge_types.iter().cloned().collect();
+        if let Some(reverse) = &mut self.reverse_edge_index {
+            reverse.rebuild_for(
+                &edge_types,
+                &dsts,
+                edges.iter().enumerate().map(|(sid, e)| (sid as u16, e.as_slice())),
+            );
+        }
+        self.save_reverse_edge_index();
+    }
+}
+
+/// Reverse edge index saved in `db_path`, topped up from the edge segments
+/// written since it was saved; a full build if none was saved for this
+/// shard count or it cannot be read.
+fn load_reverse_edge_index(db_path: &Path, shards: &[Shard]) -> ReverseEdgeIndex {
+    let (mut index, covered) = match ReverseEdgeIndex::read_from(db_path, shards.len() as u16) {
+        Ok(Some(saved)) => saved,
+        Ok(None) => return build_reverse_edge_index(shards),
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable reverse edge index: {}", e);
+            return build_reverse_edge_index(shards);
+        }
+    };
+    for (shard_id, shard) in shards.iter().enumerate() {
+        for (src, dst, edge_type) in shard.segment_edge_keys(&covered) {
+            index.insert(dst, src, &edge_type, shard_id as u16);
+        }
     }
+    index
+}
+
+fn build_reverse_edge_index(shards: &[Shard]) -> ReverseEdgeIndex {
+    let edges: Vec<Vec<EdgeRecordV2>> = shards.iter().map(|s| s.iter_all_edges()).collect();
+    ReverseEdgeIndex::build(
+        edges.iter().enumerate().map(|(sid, e)| (sid as u16, e.as_slice())),
+    )
 }
 
 // ── Stats ──────────────────────────────────────────────────────────
@@ -1370,6 +2206,86 @@ impl MultiShardStore {
         self.shards.len() as u16
     }
 
+    /// Track zone map ranges for these integer metadata fields in node
+    /// segments written from now on, in every shard.
+    pub fn set_int_fields(&mut self, fields: Vec<String>) {
+        for shard in &mut self.shards {
+            shard.set_int_fields(fields.clone());
+        }
+    }
+
+    /// Node IDs whose integer metadata field `field` lies in `[min, max]`,
+    /// pruning segments by their zone map range (see
+    /// `Shard::find_node_ids_by_int_range`).
+    pub fn find_node_ids_by_int_range(&self, field: &str, min: i64, max: i64) -> Vec<u128> {
+        let mut seen: HashSet<u128> = HashSet::new();
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.find_node_ids_by_int_range(field, min, max))
+            .filter(|id| seen.insert(*id))
+            .collect()
+    }
+
+    /// Up to `limit` live nodes whose name starts with `prefix`
+    /// (case-insensitive), optionally of one node type, ordered by
+    /// lowercased name then ID (see `Shard::find_node_ids_by_name_prefix`).
+    pub fn find_node_ids_by_name_prefix(
+        &self,
+        prefix: &str,
+        node_type: Option<&str>,
+        limit: usize,
+    ) -> Vec<u128> {
+        let mut matches: Vec<(String, u128)> = self.shards
+            .iter()
+            .flat_map(|shard| shard.find_node_ids_by_name_prefix(prefix, node_type, limit))
+            .collect();
+        matches.sort_unstable();
+        matches.truncate(limit);
+        matches.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// `(id, content_hash)` of live nodes with a non-zero content hash,
+    /// optionally of one node type (see `Shard::node_content_hashes`).
+    pub fn node_content_hashes(&self, node_type: Option<&str>) -> Vec<(u128, u64)> {
+        let mut results = Vec::new();
+        for shard in &self.shards {
+            results.extend(shard.node_content_hashes(node_type));
+        }
+        results
+    }
+
+    /// Total node segments scanned (not pruned) by int range queries.
+    pub fn int_range_segment_scans(&self) -> usize {
+        self.shards.iter().map(Shard::int_range_segment_scans).sum()
+    }
+
+    /// Index these edge metadata fields in every edge segment, eagerly for
+    /// segments written from now on and lazily for existing ones.
+    pub fn set_edge_index_fields(&mut self, fields: Vec<String>) {
+        for shard in &mut self.shards {
+            shard.set_edge_index_fields(fields.clone());
+        }
+    }
+
+    /// Live edges whose metadata field `field` renders as `value`,
+    /// optionally of one edge type (see `Shard::find_edges_by_metadata`).
+    pub fn find_edges_by_metadata(
+        &self,
+        field: &str,
+        value: &str,
+        edge_type: Option<&str>,
+    ) -> Vec<EdgeRecordV2> {
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.find_edges_by_metadata(field, value, edge_type))
+            .collect()
+    }
+
+    /// Total edge segments read row by row by edge metadata queries.
+    pub fn edge_metadata_segment_scans(&self) -> usize {
+        self.shards.iter().map(Shard::edge_metadata_segment_scans).sum()
+    }
+
     /// Check if any shard's write buffer exceeds the given limits.
     ///
     /// Used by `GraphEngineV2` to trigger auto-flush after `add_nodes()`.
@@ -1380,11 +2296,50 @@ impl MultiShardStore {
             .any(|s| s.write_buffer_exceeds(node_limit, byte_limit))
     }
 
+    /// Configured auto-flush `(node_limit, byte_limit)`; None entries
+    /// defer to the caller's adaptive limits.
+    pub fn auto_flush_limits(&self) -> (Option<usize>, Option<usize>) {
+        (self.auto_flush_node_limit, self.auto_flush_byte_limit)
+    }
+
     /// Total node count across all write buffers (unflushed records only).
     pub fn total_write_buffer_nodes(&self) -> usize {
         self.shards.iter().map(|s| s.write_buffer_size().0).sum()
     }
 
+    /// Total `(nodes, edges)` across all write buffers (unflushed records only).
+    pub fn total_write_buffer_size(&self) -> (usize, usize) {
+        self.shards.iter().map(|s| s.write_buffer_size()).fold((0, 0), |(n, e), (sn, se)| (n + sn, e + se))
+    }
+
+    /// True if any shard has accumulated enough L0 segments to compact
+    /// under `config`. Cheap enough to poll from a scheduler.
+    pub fn needs_compaction(&self, config: &CompactionConfig) -> bool {
+        use crate::storage_v2::compaction::coordinator::should_compact;
+        self.shards.iter().any(|shard| should_compact(shard, config))
+    }
+
+    /// Dry run of `compact_with_threads`: the shards its classification
+    /// phase would pick under `config`, and what merging them would
+    /// reclaim. Reads shard state only; nothing is merged or written.
+    pub fn compaction_estimate(&self, config: &CompactionConfig) -> CompactionEstimate {
+        use crate::storage_v2::compaction::coordinator::should_compact;
+        let mut estimate = CompactionEstimate::default();
+        for (idx, shard) in self.shards.iter().enumerate() {
+            if !should_compact(shard, config) {
+                continue;
+            }
+            let tombstoned = shard.tombstoned_record_count() as u64;
+            let (bytes, records) = shard.segment_footprint();
+            estimate.shards_eligible.push(idx as u16);
+            estimate.estimated_tombstones_removed += tombstoned;
+            estimate.estimated_bytes_saved += (bytes * tombstoned.min(records))
+                .checked_div(records)
+                .unwrap_or(0);
+        }
+        estimate
+    }
+
     /// Per-shard statistics for monitoring.
     /// Per-shard diagnostics for lifecycle visibility.
     pub fn shard_diagnostics(&self) -> Vec<ShardDiagnostics> {
@@ -1403,11 +2358,87 @@ impl MultiShardStore {
 
 // ── Private Helpers ────────────────────────────────────────────────
 
+/// Commit a manifest adding freshly flushed segments to the current ones,
+/// using the two-step ManifestStore protocol:
+/// 1. Start with current manifest's segments
+/// 2. Extend with NEW segments from flush
+/// 3. Create manifest (takes FULL list)
+/// 4. Commit the manifest
+fn commit_new_segments(
+    manifest_store: &mut ManifestStore,
+    new_node_descs: Vec<SegmentDescriptor>,
+    new_edge_descs: Vec<SegmentDescriptor>,
+) -> Result<()> {
+    let mut all_node_segs = manifest_store.current().node_segments.clone();
+    let mut all_edge_segs = manifest_store.current().edge_segments.clone();
+    all_node_segs.extend(new_node_descs);
+    all_edge_segs.extend(new_edge_descs);
+
+    let manifest = manifest_store.create_manifest(all_node_segs, all_edge_segs, None)?;
+    manifest_store.commit(manifest)
+}
+
 /// Compute shard directory path: `<db_path>/segments/<shard_id>/`
 fn shard_dir(db_path: &Path, shard_id: u16) -> PathBuf {
     db_path.join("segments").join(format!("{:02}", shard_id))
 }
 
+/// Open the compacted (L1 or L2) node/edge segments named by a shard's
+/// manifest descriptors.
+fn open_compacted_segments(
+    shard_path: &Path,
+    node_desc: Option<&SegmentDescriptor>,
+    edge_desc: Option<&SegmentDescriptor>,
+    verify: bool,
+) -> Result<(Option<NodeSegmentV2>, Option<EdgeSegmentV2>)> {
+    let node_seg = match node_desc {
+        Some(desc) => {
+            let seg_path = shard_path.join(format!("seg_{:06}_nodes.seg", desc.segment_id));
+            Some(if verify {
+                NodeSegmentV2::open_verified(&seg_path)?
+            } else {
+                NodeSegmentV2::open(&seg_path)?
+            })
+        }
+        None => None,
+    };
+    let edge_seg = match edge_desc {
+        Some(desc) => {
+            let seg_path = shard_path.join(format!("seg_{:06}_edges.seg", desc.segment_id));
+            Some(if verify {
+                EdgeSegmentV2::open_verified(&seg_path)?
+            } else {
+                EdgeSegmentV2::open(&seg_path)?
+            })
+        }
+        None => None,
+    };
+    Ok((node_seg, edge_seg))
+}
+
+/// Global index entries for a shard's compacted segments: every L1 record,
+/// plus each L2 record not shadowed by a newer version in L1 (so a lookup
+/// never lands on a stale L2 copy).
+fn compacted_index_entries(shard: &Shard, shard_id: u16) -> Vec<IndexEntry> {
+    let mut entries = Vec::new();
+    let mut l1_ids = HashSet::new();
+    if let (Some(seg), Some(desc)) = (shard.l1_node_segment(), shard.l1_node_descriptor()) {
+        for i in 0..seg.record_count() {
+            l1_ids.insert(seg.get_id(i));
+            entries.push(IndexEntry::new(seg.get_id(i), desc.segment_id, i as u32, shard_id));
+        }
+    }
+    if let (Some(seg), Some(desc)) = (shard.l2_node_segment(), shard.l2_node_descriptor()) {
+        for i in 0..seg.record_count() {
+            let id = seg.get_id(i);
+            if !l1_ids.contains(&id) {
+                entries.push(IndexEntry::new(id, desc.segment_id, i as u32, shard_id));
+            }
+        }
+    }
+    entries
+}
+
 // ── Tests ──────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -1463,13 +2494,46 @@ mod tests {
     #[test]
     fn test_config_roundtrip() {
         let dir = tempfile::TempDir::new().unwrap();
-        let config = DatabaseConfig { shard_count: 8 };
+        let config = DatabaseConfig {
+            shard_count: 8,
+            compression: CompressionKind::None,
+            bloom_fp_rate: 0.001,
+            reverse_edge_index: true,
+            routing: RoutingStrategy::BySemanticId,
+            auto_flush_node_limit: Some(1_000),
+            auto_flush_byte_limit: Some(1 << 20),
+            node_cache_capacity: Some(4_096),
+            mmap_advice: Some(MmapAdvice::Random),
+            node_to_shard_capacity: Some(1_000_000),
+            query_cache_capacity: Some(256),
+            compaction_max_bytes_per_sec: Some(64 << 20),
+            durability: Some(DurabilityMode::Verified),
+        };
         config.write_to(dir.path()).unwrap();
 
         let loaded = DatabaseConfig::read_from(dir.path()).unwrap().unwrap();
         assert_eq!(loaded, config);
     }
 
+    #[test]
+    fn test_config_legacy_defaults() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("db_config.json"), r#"{"shard_count": 4}"#).unwrap();
+
+        let loaded = DatabaseConfig::read_from(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.compression, CompressionKind::None);
+        assert_eq!(loaded.bloom_fp_rate, DEFAULT_BLOOM_FP_RATE);
+        assert_eq!(loaded.routing, RoutingStrategy::ByFileDir);
+        assert_eq!(loaded.auto_flush_node_limit, None);
+        assert_eq!(loaded.auto_flush_byte_limit, None);
+        assert_eq!(loaded.node_cache_capacity, None);
+        assert_eq!(loaded.mmap_advice, None);
+        assert_eq!(loaded.node_to_shard_capacity, None);
+        assert_eq!(loaded.query_cache_capacity, None);
+        assert_eq!(loaded.compaction_max_bytes_per_sec, None);
+        assert_eq!(loaded.durability, None);
+    }
+
     #[test]
     fn test_config_read_nonexistent() {
         let dir = tempfile::TempDir::new().unwrap();
@@ -1691,58 +2755,315 @@ mod tests {
         let n2 = make_node("lib/b/callee", "FUNCTION", "callee", "lib/b/file.js");
         store.add_nodes(vec![n1.clone(), n2.clone()]);
 
-        // Cross-shard edge: n1 -> n2
-        let edge = make_edge("src/a/caller", "lib/b/callee", "CALLS");
-        store.upsert_edges(vec![edge]).unwrap();
+        // Cross-shard edge: n1 -> n2
+        let edge = make_edge("src/a/caller", "lib/b/callee", "CALLS");
+        store.upsert_edges(vec![edge]).unwrap();
+
+        // Outgoing from n1 should work
+        let outgoing = store.get_outgoing_edges(n1.id, None);
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].dst, n2.id);
+
+        // Incoming to n2 should work (fan-out)
+        let incoming = store.get_incoming_edges(n2.id, None);
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].src, n1.id);
+    }
+
+    #[test]
+    fn test_incoming_edges_fan_out() {
+        let mut store = MultiShardStore::ephemeral(4);
+
+        // Create 4 nodes in different directories
+        let target = make_node("lib/target", "FUNCTION", "target", "lib/file.js");
+        let callers: Vec<NodeRecordV2> = (0..4)
+            .map(|i| {
+                make_node(
+                    &format!("src_{}/caller_{}", i, i),
+                    "FUNCTION",
+                    &format!("caller_{}", i),
+                    &format!("src_{}/file.js", i),
+                )
+            })
+            .collect();
+
+        let caller_ids: Vec<u128> = callers.iter().map(|n| n.id).collect();
+        let mut all_nodes = vec![target.clone()];
+        all_nodes.extend(callers);
+        store.add_nodes(all_nodes);
+
+        // Each caller calls target
+        let edges: Vec<EdgeRecordV2> = caller_ids
+            .iter()
+            .map(|src| EdgeRecordV2 {
+                src: *src,
+                dst: target.id,
+                edge_type: "CALLS".to_string(),
+                metadata: String::new(),
+            })
+            .collect();
+        store.upsert_edges(edges).unwrap();
+
+        // Incoming edges to target should find all 4 (from different shards)
+        let incoming = store.get_incoming_edges(target.id, None);
+        assert_eq!(incoming.len(), 4);
+    }
+
+    /// Hub node with 10k incoming edges from one directory (one shard),
+    /// alongside 40k unrelated edges spread over the remaining shards.
+    fn build_hub_store(reverse_index: bool) -> (MultiShardStore, u128) {
+        let mut store = MultiShardStore::ephemeral(8);
+        store.set_reverse_edge_index(reverse_index);
+        let mut manifest_store = ManifestStore::ephemeral();
+
+        let hub = make_node("lib/hub", "MODULE", "hub", "lib/hub.js");
+        let hub_id = hub.id;
+        store.add_nodes(vec![hub]);
+
+        for batch in 0..4 {
+            let mut nodes = Vec::new();
+            let mut edges = Vec::new();
+            for i in 0..2_500 {
+                let caller = make_node(
+                    &format!("app/caller_{}_{}", batch, i), "FUNCTION", "caller", "app/file.js",
+                );
+                edges.push(EdgeRecordV2 {
+                    src: caller.id, dst: hub_id,
+                    edge_type: "IMPORTS_FROM".to_string(), metadata: String::new(),
+                });
+                nodes.push(caller);
+            }
+            for i in 0..10_000 {
+                let dir = format!("pkg_{}", i % 64);
+                let a = make_node(&format!("{}/a_{}_{}", dir, batch, i), "FUNCTION", "a", &format!("{}/f.js", dir));
+                let b = make_node(&format!("{}/b_{}_{}", dir, batch, i), "FUNCTION", "b", &format!("{}/f.js", dir));
+                edges.push(EdgeRecordV2 {
+                    src: a.id, dst: b.id,
+                    edge_type: "CALLS".to_string(), metadata: String::new(),
+                });
+                nodes.push(a);
+                nodes.push(b);
+            }
+            store.add_nodes(nodes);
+            store.upsert_edges(edges).unwrap();
+            store.flush_all(&mut manifest_store).unwrap();
+        }
+        (store, hub_id)
+    }
+
+    // get_incoming_edges on build_hub_store(), release build, avg of 500 runs:
+    //
+    //   query                       fan-out     reverse index
+    //   hub, all types (10k edges)  5.9 ms      2.0 ms
+    //   hub, CALLS only (0 edges)   2.5 ms      90 ns
+    //   leaf node (1 edge)          2.2 µs      2.5 µs
+    //
+    // The fan-out scan allocates a dedup key and a tombstone probe key per
+    // matching row; the index hands each shard its exact (src, type) keys,
+    // so rows are matched without allocating and typed misses never reach
+    // a shard.
+    #[test]
+    fn test_reverse_edge_index_matches_fan_out() {
+        let (plain, hub) = build_hub_store(false);
+        let (indexed, _) = build_hub_store(true);
+        assert!(indexed.has_reverse_edge_index());
+
+        let sorted = |mut edges: Vec<EdgeRecordV2>| {
+            edges.sort_by_key(|e| (e.src, e.dst));
+            edges
+        };
+        assert_eq!(indexed.get_incoming_edges(hub, None).len(), 10_000);
+        assert_eq!(
+            sorted(indexed.get_incoming_edges(hub, None)),
+            sorted(plain.get_incoming_edges(hub, None)),
+        );
+        assert!(indexed.get_incoming_edges(hub, Some(&["CALLS"])).is_empty());
+
+        let leaf = node_id("pkg_3/b_0_3");
+        assert_eq!(indexed.get_incoming_edges(leaf, None).len(), 1);
+        assert_eq!(
+            indexed.get_incoming_edges(leaf, None),
+            plain.get_incoming_edges(leaf, None),
+        );
+    }
+
+    #[test]
+    fn test_reverse_edge_index_partial_rebuild_by_edge_type() {
+        let mut store = MultiShardStore::ephemeral(4);
+        store.set_reverse_edge_index(true);
+        let caller = make_node("src/main.js->main", "FUNCTION", "main", "src/main.js");
+        let func = make_node("lib/a.js->helper", "FUNCTION", "helper", "lib/a.js");
+        let class = make_node("lib/b.js->Widget", "CLASS", "Widget", "lib/b.js");
+        let (caller_id, func_id, class_id) = (caller.id, func.id, class.id);
+        store.add_nodes(vec![caller, func, class]);
+        let edge = |src: u128, dst: u128, edge_type: &str| EdgeRecordV2 {
+            src, dst, edge_type: edge_type.to_string(), metadata: String::new(),
+        };
+        store.upsert_edges(vec![
+            edge(caller_id, func_id, "CALLS"),
+            edge(caller_id, class_id, "INSTANTIATES"),
+            edge(func_id, class_id, "RETURNS"),
+        ]).unwrap();
+        // Built before the deletes, so it must not serve them afterwards
+        assert_eq!(store.get_edges_by_type("RETURNS").len(), 1);
+
+        // The caller is deleted with its edges; the FUNCTION -> CLASS edge
+        // is replaced. Only the types of those edges are rebuilt.
+        let deleted: HashSet<(u128, u128, String)> = [
+            (caller_id, func_id, "CALLS".to_string()),
+            (caller_id, class_id, "INSTANTIATES".to_string()),
+            (func_id, class_id, "RETURNS".to_string()),
+        ].into_iter().collect();
+        store.set_tombstones(&HashSet::from([caller_id]), &deleted);
+        store.rebuild_reverse_edge_index_for_types(&[], &["CALLS".to_string(), "RETURNS".to_string()]);
+
+        let reverse = store.reverse_edge_index.as_ref().unwrap();
+        assert!(reverse.shards_for(func_id, None).is_empty(), "deleted node's edges dropped");
+        assert!(reverse.shards_for(class_id, Some(&["RETURNS"])).is_empty(), "FUNCTION -> CLASS dropped");
+        assert_eq!(reverse.shards_for(class_id, Some(&["INSTANTIATES"])).len(), 1, "other types untouched");
+        assert!(store.get_incoming_edges(class_id, None).is_empty());
+
+        store.rebuild_reverse_edge_index_for_types(&[], &["INSTANTIATES".to_string()]);
+        let reverse = store.reverse_edge_index.as_ref().unwrap();
+        assert!(reverse.is_empty());
+    }
+
+    #[test]
+    fn test_reverse_edge_index_partial_rebuild_by_node_type() {
+        let mut store = MultiShardStore::ephemeral(4);
+        store.set_reverse_edge_index(true);
+        let caller = make_node("src/main.js->main", "FUNCTION", "main", "src/main.js");
+        let func = make_node("lib/a.js->helper", "FUNCTION", "helper", "lib/a.js");
+        let class = make_node("lib/b.js->Widget", "CLASS", "Widget", "lib/b.js");
+        let (caller_id, func_id, class_id) = (caller.id, func.id, class.id);
+        store.add_nodes(vec![caller, func, class]);
+        let edge = |dst: u128, edge_type: &str| EdgeRecordV2 {
+            src: caller_id, dst, edge_type: edge_type.to_string(), metadata: String::new(),
+        };
+        store.upsert_edges(vec![edge(func_id, "CALLS"), edge(class_id, "INSTANTIATES")]).unwrap();
+
+        // Both edges and the FUNCTION they point at are deleted; only
+        // FUNCTION entries are rebuilt
+        let deleted: HashSet<(u128, u128, String)> = [
+            (caller_id, func_id, "CALLS".to_string()),
+            (caller_id, class_id, "INSTANTIATES".to_string()),
+        ].into_iter().collect();
+        store.set_tombstones(&HashSet::from([func_id]), &deleted);
+        store.rebuild_reverse_edge_index_for_types(&["FUNCTION".to_string()], &[]);
+
+        let reverse = store.reverse_edge_index.as_ref().unwrap();
+        assert!(reverse.shards_for(func_id, None).is_empty(), "deleted FUNCTION entry dropped");
+        assert_eq!(reverse.shards_for(class_id, None).len(), 1, "CLASS entry untouched");
+        assert!(store.get_incoming_edges(class_id, None).is_empty());
+    }
+
+    #[test]
+    fn test_flush_shards_holding_leaves_other_shards_buffered() {
+        let mut store = MultiShardStore::ephemeral(4);
+        let mut manifest = ManifestStore::ephemeral();
+        let func = make_node("src/a.js->helper", "FUNCTION", "helper", "src/a.js");
+        let class = make_node("lib/b.js->Widget", "CLASS", "Widget", "lib/b.js");
+        store.add_nodes(vec![func, class]);
+        let buffered = |store: &MultiShardStore| -> usize {
+            store.shards.iter().map(|s| s.write_buffer_size().0).sum()
+        };
+        assert_eq!(buffered(&store), 2);
+
+        assert_eq!(store.flush_shards_holding(&mut manifest, &["FUNCTION".to_string()], &[]).unwrap(), 1);
+        assert_eq!(buffered(&store), 1, "CLASS shard not flushed");
+        assert_eq!(store.find_node_ids_by_type("FUNCTION").len(), 1);
+        assert_eq!(store.find_node_ids_by_type("CLASS").len(), 1);
+        assert_eq!(manifest.current().node_segments.len(), 1);
+    }
+
+    #[test]
+    fn test_reverse_edge_index_rebuilt_on_open() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path();
+        let mut manifest_store = ManifestStore::create(db_path).unwrap();
+        let config = DatabaseConfig {
+            shard_count: 4,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: true,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        let mut store = MultiShardStore::create_with_config(db_path, config).unwrap();
+        assert!(store.has_reverse_edge_index());
 
-        // Outgoing from n1 should work
-        let outgoing = store.get_outgoing_edges(n1.id, None);
-        assert_eq!(outgoing.len(), 1);
-        assert_eq!(outgoing[0].dst, n2.id);
+        let n1 = make_node("src/a/fn1", "FUNCTION", "fn1", "src/a/file.js");
+        let n2 = make_node("lib/b/fn2", "FUNCTION", "fn2", "lib/b/file.js");
+        store.add_nodes(vec![n1.clone(), n2.clone()]);
+        store.upsert_edges(vec![make_edge("src/a/fn1", "lib/b/fn2", "CALLS")]).unwrap();
+        store.flush_all(&mut manifest_store).unwrap();
+        drop(store);
 
-        // Incoming to n2 should work (fan-out)
+        let store = MultiShardStore::open(db_path, &manifest_store).unwrap();
+        assert!(store.has_reverse_edge_index());
         let incoming = store.get_incoming_edges(n2.id, None);
         assert_eq!(incoming.len(), 1);
         assert_eq!(incoming[0].src, n1.id);
     }
 
     #[test]
-    fn test_incoming_edges_fan_out() {
-        let mut store = MultiShardStore::ephemeral(4);
-
-        // Create 4 nodes in different directories
-        let target = make_node("lib/target", "FUNCTION", "target", "lib/file.js");
-        let callers: Vec<NodeRecordV2> = (0..4)
-            .map(|i| {
-                make_node(
-                    &format!("src_{}/caller_{}", i, i),
-                    "FUNCTION",
-                    &format!("caller_{}", i),
-                    &format!("src_{}/file.js", i),
-                )
-            })
-            .collect();
-
-        let caller_ids: Vec<u128> = callers.iter().map(|n| n.id).collect();
-        let mut all_nodes = vec![target.clone()];
-        all_nodes.extend(callers);
-        store.add_nodes(all_nodes);
-
-        // Each caller calls target
-        let edges: Vec<EdgeRecordV2> = caller_ids
-            .iter()
-            .map(|src| EdgeRecordV2 {
-                src: *src,
-                dst: target.id,
-                edge_type: "CALLS".to_string(),
-                metadata: String::new(),
-            })
-            .collect();
-        store.upsert_edges(edges).unwrap();
-
-        // Incoming edges to target should find all 4 (from different shards)
-        let incoming = store.get_incoming_edges(target.id, None);
-        assert_eq!(incoming.len(), 4);
+    fn test_reverse_edge_index_loaded_and_topped_up_on_open() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path();
+        let mut manifest_store = ManifestStore::create(db_path).unwrap();
+        let config = DatabaseConfig {
+            shard_count: 4,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: true,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        let mut store = MultiShardStore::create_with_config(db_path, config).unwrap();
+        let n1 = make_node("src/a/fn1", "FUNCTION", "fn1", "src/a/file.js");
+        let n2 = make_node("lib/b/fn2", "FUNCTION", "fn2", "lib/b/file.js");
+        let n3 = make_node("lib/c/fn3", "FUNCTION", "fn3", "lib/c/file.js");
+        store.add_nodes(vec![n1.clone(), n2.clone(), n3.clone()]);
+        store.upsert_edges(vec![make_edge("src/a/fn1", "lib/b/fn2", "CALLS")]).unwrap();
+        store.flush_all(&mut manifest_store).unwrap();
+        store.rebuild_reverse_edge_index();
+        let covered: Vec<u64> = store.shards.iter().flat_map(|s| s.edge_segment_ids()).collect();
+        assert!(!covered.is_empty());
+
+        // Replace the save with one holding an entry no segment has: it can
+        // only survive the reopen if the saved index is loaded, not rebuilt
+        let phantom = 0xdead_u128;
+        let mut saved = build_reverse_edge_index(&store.shards);
+        saved.insert(phantom, 1, "CALLS", 2);
+        saved.write_to(db_path, 4, covered).unwrap();
+
+        // Written after the save: picked up from its segment on open
+        store.upsert_edges(vec![make_edge("src/a/fn1", "lib/c/fn3", "CALLS")]).unwrap();
+        store.flush_all(&mut manifest_store).unwrap();
+        drop(store);
+
+        let store = MultiShardStore::open(db_path, &manifest_store).unwrap();
+        let reverse = store.reverse_edge_index.as_ref().unwrap();
+        assert_eq!(reverse.shards_for(phantom, None), vec![2]);
+        for target in [&n2, &n3] {
+            let incoming = store.get_incoming_edges(target.id, None);
+            assert_eq!(incoming.len(), 1);
+            assert_eq!(incoming[0].src, n1.id);
+        }
     }
 
     #[test]
@@ -1986,6 +3307,56 @@ mod tests {
         assert_eq!(outgoing[0].metadata, e1.metadata);
     }
 
+    #[test]
+    fn test_reshard_two_to_four() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("test.rfdb");
+        std::fs::create_dir_all(&db_path).unwrap();
+        let mut manifest_store = ManifestStore::create(&db_path).unwrap();
+
+        let dirs = ["src/a", "src/b", "lib/c", "lib/d", "test/e", "test/f", "docs/g", "bin/h"];
+        let semantic = |i: usize| format!("{}/fn{}", dirs[i % dirs.len()], i);
+        let mut store = MultiShardStore::create(&db_path, 2).unwrap();
+        store.add_nodes((0..40).map(|i| {
+            let dir = dirs[i % dirs.len()];
+            make_node(&semantic(i), "FUNCTION", &format!("fn{}", i), &format!("{}/file.js", dir))
+        }).collect());
+        store.upsert_edges((0..39).map(|i| make_edge(&semantic(i), &semantic(i + 1), "CALLS")).collect()).unwrap();
+        store.flush_all(&mut manifest_store).unwrap();
+        store.compact(&mut manifest_store, &CompactionConfig { segment_threshold: 1, ..Default::default() }).unwrap();
+
+        // A deleted node is not carried over
+        store.set_tombstones(&[node_id(&semantic(39))].into_iter().collect(), &HashSet::new());
+
+        store.reshard(&mut manifest_store, 4).unwrap();
+
+        let check = |store: &MultiShardStore| {
+            assert_eq!(store.shard_count(), 4);
+            assert_eq!(store.node_count(), 39);
+            assert_eq!(store.edge_count(), 39);
+            let planner = ShardPlanner::new(4);
+            for i in 0..39 {
+                let id = node_id(&semantic(i));
+                let node = store.get_node(id).expect("node survives reshard");
+                let expected = planner.compute_node_shard_id(&node) as usize;
+                assert!(store.shards[expected].node_exists(id), "node {} on wrong shard", i);
+
+                // Edges live on their source node's shard
+                let outgoing = store.shards[expected].get_outgoing_edges(id, None);
+                assert_eq!(outgoing.len(), 1, "edge from node {}", i);
+                assert_eq!(outgoing[0].dst, node_id(&semantic(i + 1)));
+            }
+            assert!(!store.node_exists(node_id(&semantic(39))));
+        };
+        check(&store);
+
+        // The new layout is what a reopen sees
+        assert_eq!(DatabaseConfig::read_from(&db_path).unwrap().unwrap().shard_count, 4);
+        let manifest = manifest_store.current();
+        assert!(manifest.l1_node_segments.is_empty() && manifest.tombstoned_node_ids.is_empty());
+        check(&MultiShardStore::open(&db_path, &manifest_store).unwrap());
+    }
+
     #[test]
     fn test_equivalence_single_vs_multi() {
         // Same data added to both a single shard and multi-shard store
@@ -2048,6 +3419,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_routing_strategies_on_skewed_workload() {
+        // Monorepo shape: almost everything in one directory
+        let semantic = |i: usize| if i < 95 { format!("src/giant/fn{}", i) } else { format!("lib/x{}/fn{}", i, i) };
+        let file = |i: usize| if i < 95 { "src/giant/index.js".to_string() } else { format!("lib/x{}/index.js", i) };
+        let nodes: Vec<NodeRecordV2> = (0..100)
+            .map(|i| make_node(&semantic(i), if i % 2 == 0 { "FUNCTION" } else { "CLASS" }, &format!("fn{}", i), &file(i)))
+            .collect();
+        let edges: Vec<EdgeRecordV2> = (0..99).map(|i| make_edge(&semantic(i), &semantic(i + 1), "CALLS")).collect();
+
+        let build = |routing| {
+            let mut store = MultiShardStore::ephemeral_with_routing(8, routing);
+            store.add_nodes(nodes.clone());
+            store.upsert_edges(edges.clone()).unwrap();
+            store
+        };
+        let by_dir = build(RoutingStrategy::ByFileDir);
+        let by_id = build(RoutingStrategy::BySemanticId);
+
+        let largest_shard = |store: &MultiShardStore| {
+            store.shard_diagnostics().iter().map(|d| d.node_count).max().unwrap()
+        };
+        assert!(largest_shard(&by_dir) >= 95);
+        assert!(largest_shard(&by_id) < 50, "semantic ID routing should spread the hot directory");
+
+        // Edges still live on their source node's shard
+        for edge in &edges {
+            let shard = *by_id.node_to_shard.get(&edge.src).unwrap() as usize;
+            assert!(by_id.shards[shard].get_outgoing_edges(edge.src, None).iter().any(|e| e.dst == edge.dst));
+        }
+
+        // Placement is invisible to queries
+        assert_eq!(by_dir.node_count(), by_id.node_count());
+        assert_eq!(by_dir.edge_count(), by_id.edge_count());
+        let ids = |found: Vec<NodeRecordV2>| found.into_iter().map(|n| n.id).collect::<HashSet<u128>>();
+        assert_eq!(ids(by_dir.find_nodes(Some("FUNCTION"), None)), ids(by_id.find_nodes(Some("FUNCTION"), None)));
+        assert_eq!(
+            ids(by_dir.find_nodes(None, Some("src/giant/index.js"))),
+            ids(by_id.find_nodes(None, Some("src/giant/index.js"))),
+        );
+        for i in 0..100 {
+            let id = node_id(&semantic(i));
+            assert_eq!(by_dir.get_node(id), by_id.get_node(id));
+            let targets = |store: &MultiShardStore| {
+                let mut out: Vec<u128> = store.get_outgoing_edges(id, None).iter().map(|e| e.dst).collect();
+                out.extend(store.get_incoming_edges(id, None).iter().map(|e| e.src));
+                out.sort();
+                out
+            };
+            assert_eq!(targets(&by_dir), targets(&by_id), "edges of node {}", i);
+        }
+    }
+
     #[test]
     fn test_empty_shards_ok() {
         // Even with 8 shards and 1 node, should work fine
@@ -2127,6 +3551,34 @@ mod tests {
         assert!(current.node_segments.len() >= 2);
     }
 
+    #[test]
+    fn test_flush_shard_commits_only_its_segment() {
+        let mut store = MultiShardStore::ephemeral(4);
+        let mut manifest_store = ManifestStore::ephemeral();
+        store.add_nodes((0..16)
+            .map(|i| make_node(&format!("dir{i}/fn"), "FUNCTION", "fn", &format!("dir{i}/file.js")))
+            .collect());
+
+        let stats = store.shard_stats();
+        let target = stats.iter().max_by_key(|s| s.write_buffer_nodes).unwrap().shard_id;
+        assert!(store.flush_shard(&mut manifest_store, target).unwrap());
+
+        let segments = &manifest_store.current().node_segments;
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].shard_id, Some(target));
+        for (old, new) in stats.iter().zip(store.shard_stats()) {
+            let expected = if new.shard_id == target { 0 } else { old.write_buffer_nodes };
+            assert_eq!(new.write_buffer_nodes, expected);
+        }
+        assert_eq!(store.node_count(), 16);
+
+        assert!(!store.flush_shard(&mut manifest_store, target).unwrap());
+        assert!(matches!(
+            store.flush_shard(&mut manifest_store, 4),
+            Err(GraphError::ShardOutOfRange { shard_id: 4, shard_count: 4 })
+        ));
+    }
+
     // -- find_edge_keys_by_src_ids Tests (RFD-8 T3.1 Commit 2) -------------------
 
     #[test]
@@ -2921,7 +4373,7 @@ mod tests {
     fn test_compact_builds_indexes() {
         // Setup: ephemeral store with 1 shard, add enough data to trigger compaction
         let mut store = MultiShardStore::ephemeral(1);
-        let config = CompactionConfig { segment_threshold: 4 };
+        let config = CompactionConfig { segment_threshold: 4, ..Default::default() };
 
         let n1 = make_node("fn_a", "FUNCTION", "a", "src/lib.rs");
         let n2 = make_node("fn_b", "FUNCTION", "b", "src/lib.rs");
@@ -2983,7 +4435,7 @@ mod tests {
         // Setup: compact, then find_nodes should return correct results
         // via the inverted index path
         let mut store = MultiShardStore::ephemeral(1);
-        let config = CompactionConfig { segment_threshold: 4 };
+        let config = CompactionConfig { segment_threshold: 4, ..Default::default() };
 
         let nodes = vec![
             make_node("fn_1", "FUNCTION", "one", "src/a.rs"),
@@ -3051,7 +4503,7 @@ mod tests {
     #[test]
     fn test_global_index_point_lookup_after_compact() {
         let mut store = MultiShardStore::ephemeral(2);
-        let config = CompactionConfig { segment_threshold: 4 };
+        let config = CompactionConfig { segment_threshold: 4, ..Default::default() };
 
         // Add nodes to different shards (files in different dirs)
         let _n1 = make_node("fn_a", "FUNCTION", "a", "src/a.rs");
@@ -3101,6 +4553,100 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_second_compaction_merges_oversized_l1_into_l2() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("l2.rfdb");
+        std::fs::create_dir_all(&db_path).unwrap();
+        let mut manifest_store = ManifestStore::create(&db_path).unwrap();
+        let config = CompactionConfig { segment_threshold: 2, l1_record_threshold: 3, ..Default::default() };
+
+        let mut store = MultiShardStore::create(&db_path, 1).unwrap();
+        let names = ["a", "b", "c", "d"];
+        for name in names {
+            store.add_nodes(vec![make_node(&format!("fn_{}", name), "FUNCTION", name, "src/lib.rs")]);
+            store.flush_all(&mut manifest_store).unwrap();
+        }
+        store.upsert_edges(vec![make_edge("fn_a", "fn_b", "CALLS")]).unwrap();
+        store.flush_all(&mut manifest_store).unwrap();
+
+        // Round 1: L0 -> L1 (no L1 yet, nothing to merge down)
+        store.compact(&mut manifest_store, &config).unwrap();
+        assert!(store.shards[0].has_l1());
+        assert!(!store.shards[0].has_l2());
+        assert!(manifest_store.current().l2_node_segments.is_empty());
+
+        // Round 2: L1 holds 5 records > 3, so L0 + L1 merge into L2
+        store.add_nodes(vec![make_node("fn_a", "FUNCTION", "a_v2", "src/lib.rs")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        store.add_nodes(vec![make_node("fn_e", "CLASS", "e", "src/lib.rs")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        store.compact(&mut manifest_store, &config).unwrap();
+
+        let manifest = manifest_store.current();
+        assert_eq!(manifest.l2_node_segments.len(), 1);
+        assert_eq!(manifest.l2_node_segments[0].record_count, 5);
+        assert_eq!(manifest.l2_edge_segments.len(), 1);
+        assert!(manifest.l1_node_segments.is_empty());
+        assert!(manifest.l1_edge_segments.is_empty());
+        assert!(!store.shards[0].has_l1());
+        let diag = &store.shard_diagnostics()[0];
+        assert_eq!((diag.l2_node_records, diag.l2_edge_records), (5, 1));
+
+        // Round 3: pending tombstones force a merge into L2 even though L1
+        // is empty, so the deleted L2 record is dropped rather than revived
+        store.set_tombstones(&HashSet::from([node_id("fn_d")]), &HashSet::new());
+        store.add_nodes(vec![make_node("fn_f", "FUNCTION", "f", "src/lib.rs")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        store.add_nodes(vec![make_node("fn_b", "FUNCTION", "b_v2", "src/lib.rs")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        store.compact(&mut manifest_store, &config).unwrap();
+        assert!(!store.shards[0].has_l1());
+        assert!(store.get_node(node_id("fn_d")).is_none());
+
+        // Round 4: small L1 again on top of L2
+        store.add_nodes(vec![make_node("fn_g", "FUNCTION", "g", "src/lib.rs")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        store.add_nodes(vec![make_node("fn_c", "FUNCTION", "c_v2", "src/lib.rs")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        store.compact(&mut manifest_store, &config).unwrap();
+
+        // A later flush must carry both compacted tiers into the manifest
+        store.add_nodes(vec![make_node("fn_h", "FUNCTION", "h", "src/lib.rs")]);
+        store.flush_all(&mut manifest_store).unwrap();
+
+        let check = |store: &MultiShardStore| {
+            assert_eq!(store.get_node(node_id("fn_a")).unwrap().name, "a_v2");
+            assert_eq!(store.get_node(node_id("fn_b")).unwrap().name, "b_v2");
+            assert_eq!(store.get_node(node_id("fn_c")).unwrap().name, "c_v2");
+            assert!(store.get_node(node_id("fn_d")).is_none());
+            assert_eq!(store.get_node(node_id("fn_e")).unwrap().name, "e");
+            assert_eq!(store.get_node(node_id("fn_f")).unwrap().name, "f");
+            assert_eq!(store.get_node(node_id("fn_g")).unwrap().name, "g");
+            assert_eq!(store.get_node(node_id("fn_h")).unwrap().name, "h");
+
+            let funcs: HashSet<String> = store
+                .find_nodes(Some("FUNCTION"), None)
+                .into_iter()
+                .map(|n| n.name)
+                .collect();
+            let expected: HashSet<String> =
+                ["a_v2", "b_v2", "c_v2", "f", "g", "h"].iter().map(|s| s.to_string()).collect();
+            assert_eq!(funcs, expected);
+            assert_eq!(store.find_node_ids_by_type("CLASS"), vec![node_id("fn_e")]);
+
+            let calls = store.get_outgoing_edges(node_id("fn_a"), Some(&["CALLS"]));
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].dst, node_id("fn_b"));
+        };
+        check(&store);
+        assert!(store.shards[0].has_l1() && store.shards[0].has_l2());
+
+        let reopened = MultiShardStore::open(&db_path, &manifest_store).unwrap();
+        assert!(reopened.shards[0].has_l1() && reopened.shards[0].has_l2());
+        check(&reopened);
+    }
+
     // -- RFD-15: Enrichment Virtual Shards Tests ----------------------------------
 
     fn make_enrichment_edge(
@@ -3242,6 +4788,37 @@ mod tests {
         assert!(edge_types.contains("FLOWS_INTO"), "Should include enrichment FLOWS_INTO edge");
     }
 
+    #[test]
+    fn test_edge_exists_normal_and_enrichment() {
+        use crate::storage_v2::types::enrichment_file_context;
+
+        let mut store = MultiShardStore::ephemeral(4);
+
+        let n_a = make_node("src/a/fn_a", "FUNCTION", "fn_a", "src/a/file.js");
+        let n_b = make_node("src/b/fn_b", "FUNCTION", "fn_b", "src/b/file.js");
+        store.add_nodes(vec![n_a.clone(), n_b.clone()]);
+
+        store.upsert_edges(vec![make_edge("src/a/fn_a", "src/b/fn_b", "CALLS")]).unwrap();
+        let file_context = enrichment_file_context("data-flow", "src/z/other.js");
+        store.upsert_edges(vec![make_enrichment_edge(
+            "src/a/fn_a",
+            "src/b/fn_b",
+            "FLOWS_INTO",
+            &file_context,
+        )]).unwrap();
+
+        assert!(store.edge_exists(n_a.id, n_b.id, "CALLS"));
+        assert!(store.edge_exists(n_a.id, n_b.id, "FLOWS_INTO"));
+        assert!(!store.edge_exists(n_b.id, n_a.id, "CALLS"), "direction matters");
+        assert!(!store.edge_exists(n_a.id, n_b.id, "IMPORTS"), "type matters");
+
+        // Still found once flushed to segments
+        let mut manifest = ManifestStore::ephemeral();
+        store.flush_all(&mut manifest).unwrap();
+        assert!(store.edge_exists(n_a.id, n_b.id, "CALLS"));
+        assert!(store.edge_exists(n_a.id, n_b.id, "FLOWS_INTO"));
+    }
+
     #[test]
     fn test_get_outgoing_edges_enrichment_only() {
         use crate::storage_v2::types::enrichment_file_context;
@@ -3580,13 +5157,137 @@ mod tests {
         assert!(store.node_exists(b.id), "Node B should still exist");
     }
 
+    #[test]
+    fn test_prepared_compaction_is_dropped_when_stale() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("stale.rfdb");
+        std::fs::create_dir_all(&db_path).unwrap();
+        let mut manifest_store = ManifestStore::create(&db_path).unwrap();
+        let mut store = MultiShardStore::create(&db_path, 1).unwrap();
+        let config = CompactionConfig { segment_threshold: 1, ..Default::default() };
+        let seg_files = |path: &Path| -> usize {
+            std::fs::read_dir(path.join("segments").join("00")).unwrap().count()
+        };
+
+        store.add_nodes(vec![make_node("a", "FUNCTION", "a", "src/a.js")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        let files_before = seg_files(&db_path);
+
+        // A flush between prepare and install invalidates the merge
+        let prepared = store.prepare_compaction(&manifest_store, &config, Some(1)).unwrap();
+        assert!(seg_files(&db_path) > files_before, "prepare writes merged segments");
+        store.add_nodes(vec![make_node("b", "FUNCTION", "b", "src/b.js")]);
+        store.flush_all(&mut manifest_store).unwrap();
+        let files_after_flush = seg_files(&db_path);
+        let manifest_version = manifest_store.current().version;
+
+        assert!(store.install_compaction(&mut manifest_store, prepared).unwrap().is_none());
+        assert_eq!(manifest_store.current().version, manifest_version);
+        assert!(!store.shards[0].has_l1());
+        assert_eq!(seg_files(&db_path), files_after_flush - 1, "stale merged segment removed");
+
+        // Redone against the current state, it installs and sees both nodes
+        let prepared = store.prepare_compaction(&manifest_store, &config, Some(1)).unwrap();
+        let result = store.install_compaction(&mut manifest_store, prepared).unwrap().unwrap();
+        assert_eq!(result.shards_compacted, vec![0]);
+        assert_eq!(result.nodes_merged, 2);
+        assert!(store.shards[0].has_l1());
+        assert!(store.get_node(node_id("a")).is_some());
+        assert!(store.get_node(node_id("b")).is_some());
+    }
+
+    #[test]
+    fn test_compact_single_shard_leaves_other_shards_untouched() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("single.rfdb");
+        std::fs::create_dir_all(&db_path).unwrap();
+        let mut manifest_store = ManifestStore::create(&db_path).unwrap();
+        let mut store = MultiShardStore::create(&db_path, 4).unwrap();
+
+        // dir_0 gets far more nodes than the other directories
+        let mut all_ids = Vec::new();
+        for batch in 0..2 {
+            let nodes: Vec<NodeRecordV2> = (0..8)
+                .flat_map(|d| {
+                    let count = if d == 0 { 40 } else { 2 };
+                    (0..count).map(move |i| {
+                        make_node(
+                            &format!("dir_{}/fn_{}_{}", d, i, batch),
+                            "FUNCTION",
+                            &format!("fn_{}_{}", i, batch),
+                            &format!("dir_{}/file.js", d),
+                        )
+                    })
+                })
+                .collect();
+            all_ids.extend(nodes.iter().map(|n| n.id));
+            store.add_nodes(nodes);
+            store.flush_all(&mut manifest_store).unwrap();
+        }
+
+        let heaviest = store
+            .shard_diagnostics()
+            .iter()
+            .max_by_key(|d| d.node_count)
+            .unwrap()
+            .shard_id;
+        let l0_before: Vec<Vec<u64>> = store
+            .shards
+            .iter()
+            .map(|s| s.l0_node_descriptors().iter().map(|d| d.segment_id).collect())
+            .collect();
+        assert!(
+            l0_before.iter().enumerate().any(|(i, ids)| i != heaviest as usize && !ids.is_empty()),
+            "test needs L0 data outside the heaviest shard"
+        );
+
+        let config = CompactionConfig { segment_threshold: 1, ..Default::default() };
+        let result = store
+            .compact_single_shard(&mut manifest_store, &config, heaviest)
+            .unwrap();
+        assert_eq!(result.shards_compacted, vec![heaviest]);
+
+        for (i, shard) in store.shards.iter().enumerate() {
+            let l0_after: Vec<u64> =
+                shard.l0_node_descriptors().iter().map(|d| d.segment_id).collect();
+            if i == heaviest as usize {
+                assert!(l0_after.is_empty());
+                assert!(shard.has_l1());
+            } else {
+                assert_eq!(l0_after, l0_before[i], "shard {} L0 changed", i);
+                assert!(!shard.has_l1(), "shard {} was compacted", i);
+            }
+        }
+
+        let manifest = manifest_store.current();
+        assert!(manifest.node_segments.iter().all(|d| d.shard_id != Some(heaviest)));
+        assert_eq!(
+            manifest.node_segments.len(),
+            l0_before.iter().enumerate().filter(|(i, _)| *i != heaviest as usize).map(|(_, ids)| ids.len()).sum::<usize>()
+        );
+        assert_eq!(manifest.l1_node_segments.len(), 1);
+
+        let reopened = MultiShardStore::open(&db_path, &manifest_store).unwrap();
+        for s in [&store, &reopened] {
+            assert_eq!(s.node_count(), all_ids.len());
+            for id in &all_ids {
+                assert!(s.get_node(*id).is_some());
+            }
+        }
+
+        match store.compact_single_shard(&mut manifest_store, &config, 4) {
+            Err(GraphError::ShardOutOfRange { shard_id: 4, shard_count: 4 }) => {}
+            other => panic!("expected ShardOutOfRange, got {:?}", other.map(|r| r.shards_compacted)),
+        }
+    }
+
     // -- Parallel Compaction Tests ------------------------------------------------
 
     #[test]
     fn test_parallel_compaction_correctness() {
         // Verify parallel compaction (threads=4) produces identical results
         // to sequential compaction (threads=1).
-        let config = CompactionConfig { segment_threshold: 2 };
+        let config = CompactionConfig { segment_threshold: 2, ..Default::default() };
 
         // Build identical stores for sequential and parallel runs
         let build_store = || {
@@ -3678,7 +5379,7 @@ mod tests {
         // Nodes are queryable from write buffer; no flush needed.
 
         // Verify chunked produces same total as non-chunked
-        let all_ids = store.find_node_ids_by_attr(
+        let (all_ids, _) = store.find_node_ids_by_attr(
             Some("FUNCTION"), None, None, None, None, &[], false,
         );
 
@@ -3704,6 +5405,53 @@ mod tests {
         assert!(max_chunk_size <= 7, "No chunk should exceed chunk_size");
     }
 
+    #[test]
+    fn test_parallel_attr_scan_matches_sequential() {
+        let shard_count = PARALLEL_ATTR_SCAN_MIN_SHARDS as u16 * 2;
+        let mut store = MultiShardStore::ephemeral(shard_count);
+        let mut manifest = ManifestStore::ephemeral();
+        let mut nodes = Vec::new();
+        for i in 0..2000 {
+            let mut node = make_node(
+                &format!("n{i}"),
+                if i % 3 == 0 { "FUNCTION" } else { "VARIABLE" },
+                &format!("name_{}", i % 50),
+                &format!("src/f{}.js", i % 7),
+            );
+            node.metadata = format!(r#"{{"async":{}}}"#, i % 2 == 0);
+            nodes.push(node);
+        }
+        store.add_nodes(nodes[..1000].to_vec());
+        store.flush_all(&mut manifest).unwrap();
+        store.add_nodes(nodes[1000..].to_vec());
+
+        let async_filter = vec![("async".to_string(), "true".to_string())];
+        // node type, file, name, metadata filters
+        type Query<'a> = (Option<&'a str>, Option<&'a str>, Option<&'a str>, &'a [(String, String)]);
+        let queries: Vec<Query> = vec![
+            (None, None, None, &[]),
+            (Some("FUNCTION"), None, None, &[]),
+            (Some("VARIABLE"), Some("src/f3.js"), None, &[]),
+            (None, None, Some("name_7"), &[]),
+            (Some("FUNCTION"), None, None, &async_filter),
+        ];
+        for (node_type, file, name, filters) in queries {
+            let (mut parallel, _) = store.find_node_ids_by_attr(
+                node_type, None, file, name, None, filters, false,
+            );
+            let mut sequential = Vec::new();
+            store.find_node_ids_by_attr_chunked(
+                node_type, None, file, name, None, filters, false,
+                usize::MAX,
+                &mut |chunk| { sequential.extend_from_slice(chunk); true },
+            );
+            assert!(!sequential.is_empty());
+            parallel.sort_unstable();
+            sequential.sort_unstable();
+            assert_eq!(parallel, sequential, "{node_type:?} {file:?} {name:?}");
+        }
+    }
+
     #[test]
     fn test_chunked_callback_early_stop() {
         let mut store = MultiShardStore::ephemeral(2);
@@ -3732,4 +5480,177 @@ mod tests {
 
         assert_eq!(collected.len(), 5, "Should stop after first chunk of 5");
     }
+
+    // -- Node Cache Tests ---------------------------------------------------------
+
+    #[test]
+    fn test_node_cache_drops_deleted_node() {
+        let mut store = MultiShardStore::ephemeral(2);
+        store.set_node_cache_capacity(Some(16));
+        let mut manifest = ManifestStore::ephemeral();
+        let node = make_node("src/a/fn1", "FUNCTION", "fn1", "src/a/file.js");
+        store.add_nodes(vec![node.clone()]);
+        store.flush_all(&mut manifest).unwrap();
+
+        assert_eq!(store.get_node(node.id), Some(node.clone()));
+        assert_eq!(store.cached_node_count(), 1);
+
+        store.set_tombstones(&[node.id].into_iter().collect(), &HashSet::new());
+        assert_eq!(store.get_node(node.id), None);
+    }
+
+    #[test]
+    fn test_node_cache_follows_writes_commits_and_compaction() {
+        let mut store = MultiShardStore::ephemeral(2);
+        store.set_node_cache_capacity(Some(16));
+        let mut manifest = ManifestStore::ephemeral();
+        let v1 = make_node("src/a/fn1", "FUNCTION", "fn1", "src/a/file.js");
+        let other = make_node("src/a/fn2", "FUNCTION", "fn2", "src/a/file.js");
+        store.add_nodes(vec![v1.clone(), other.clone()]);
+        store.flush_all(&mut manifest).unwrap();
+        assert_eq!(store.get_node(v1.id), Some(v1.clone()));
+
+        // Re-adding the ID replaces the cached copy
+        let v2 = make_node("src/a/fn1", "FUNCTION", "fn1_v2", "src/a/file.js");
+        store.add_nodes(vec![v2.clone()]);
+        assert_eq!(store.get_node(v1.id), Some(v2.clone()));
+
+        // A commit that drops the node from its file deletes it
+        assert_eq!(store.get_node(other.id), Some(other.clone()));
+        store.commit_batch(
+            vec![v2.clone()], vec![], &["src/a/file.js".to_string()], HashMap::new(), &mut manifest,
+        ).unwrap();
+        assert_eq!(store.get_node(other.id), None);
+        assert_eq!(store.get_node(v2.id), Some(v2.clone()));
+
+        // Compaction swaps segments out from under the cache
+        store.compact(&mut manifest, &CompactionConfig { segment_threshold: 1, ..Default::default() }).unwrap();
+        assert_eq!(store.cached_node_count(), 0);
+        assert_eq!(store.get_node(v2.id), Some(v2));
+        assert_eq!(store.get_node(other.id), None);
+    }
+
+    #[test]
+    fn test_node_cache_capacity_from_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = DatabaseConfig {
+            shard_count: 2,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: Some(2),
+            mmap_advice: None,
+            node_to_shard_capacity: None,
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        let mut store = MultiShardStore::create_with_config(dir.path(), config).unwrap();
+        assert_eq!(store.node_cache_capacity(), Some(2));
+
+        let nodes: Vec<_> = (0..3)
+            .map(|i| make_node(&format!("src/a/fn{i}"), "FUNCTION", &format!("fn{i}"), "src/a/file.js"))
+            .collect();
+        store.add_nodes(nodes.clone());
+        for node in &nodes {
+            assert_eq!(store.get_node(node.id).as_ref(), Some(node));
+        }
+        assert_eq!(store.cached_node_count(), 2);
+    }
+
+    #[test]
+    fn test_bounded_node_to_shard_routes_evicted_nodes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut manifest_store = ManifestStore::create(dir.path()).unwrap();
+        let config = DatabaseConfig {
+            shard_count: 4,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: Some(2),
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        let mut store = MultiShardStore::create_with_config(dir.path(), config).unwrap();
+
+        let nodes: Vec<_> = (0..8)
+            .map(|i| make_node(&format!("dir{i}/fn{i}"), "FUNCTION", &format!("fn{i}"), &format!("dir{i}/file.js")))
+            .collect();
+        store.add_nodes(nodes.clone());
+        assert_eq!(store.node_to_shard.len(), 2);
+        assert!(store.node_to_shard.get(&nodes[0].id).is_none());
+
+        // Unflushed nodes: fan-out finds them
+        for node in &nodes {
+            assert_eq!(store.get_node(node.id).as_ref(), Some(node));
+        }
+
+        // Edge from an evicted source still lands on the source's shard
+        store.upsert_edges(vec![make_edge("dir0/fn0", "dir1/fn1", "CALLS")]).unwrap();
+        assert_eq!(store.get_outgoing_edges(nodes[0].id, None).len(), 1);
+
+        // Flushed + compacted nodes: global index finds them
+        store.flush_all(&mut manifest_store).unwrap();
+        store.compact(&mut manifest_store, &CompactionConfig { segment_threshold: 1, ..Default::default() }).unwrap();
+        for node in &nodes {
+            assert_eq!(store.get_node(node.id).as_ref(), Some(node));
+            assert!(store.node_exists(node.id));
+        }
+        assert_eq!(store.get_outgoing_edges(nodes[0].id, None).len(), 1);
+
+        // The cap is persisted and applies on reopen
+        let reopened = MultiShardStore::open(dir.path(), &manifest_store).unwrap();
+        assert_eq!(reopened.node_to_shard.capacity(), Some(2));
+        assert_eq!(reopened.node_count(), 8);
+        for node in &nodes {
+            assert_eq!(reopened.get_node(node.id).as_ref(), Some(node));
+        }
+    }
+
+    #[test]
+    fn test_bounded_node_to_shard_reads_update_after_compaction() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut manifest_store = ManifestStore::create(dir.path()).unwrap();
+        let config = DatabaseConfig {
+            shard_count: 4,
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            reverse_edge_index: false,
+            routing: RoutingStrategy::ByFileDir,
+            auto_flush_node_limit: None,
+            auto_flush_byte_limit: None,
+            node_cache_capacity: None,
+            mmap_advice: None,
+            node_to_shard_capacity: Some(1),
+            query_cache_capacity: None,
+            compaction_max_bytes_per_sec: None,
+            durability: None,
+        };
+        let mut store = MultiShardStore::create_with_config(dir.path(), config).unwrap();
+
+        let mut node = make_node("dir0/fn0", "FUNCTION", "fn0", "dir0/file.js");
+        store.add_nodes(vec![node.clone()]);
+        store.flush_all(&mut manifest_store).unwrap();
+        store.compact(&mut manifest_store, &CompactionConfig { segment_threshold: 1, ..Default::default() }).unwrap();
+
+        // Newer version lands in the write buffer, then its routing entry is evicted
+        node.content_hash = 42;
+        node.metadata = r#"{"v":2}"#.to_string();
+        store.add_nodes(vec![node.clone()]);
+        store.add_nodes(vec![make_node("dir1/fn1", "FUNCTION", "fn1", "dir1/file.js")]);
+        assert!(store.node_to_shard.get(&node.id).is_none());
+
+        assert_eq!(store.get_node(node.id).as_ref(), Some(&node));
+        store.flush_all(&mut manifest_store).unwrap();
+        assert_eq!(store.get_node(node.id).as_ref(), Some(&node));
+    }
 }
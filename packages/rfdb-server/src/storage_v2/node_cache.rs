@@ -0,0 +1,139 @@
+//! Bounded LRU cache of node records for `MultiShardStore::get_node`.
+//!
+//! Traversals fetch the same nodes over and over, and each miss may probe
+//! the global index and a segment. The cache holds decoded records keyed
+//! by node ID. It is only a copy: `MultiShardStore` drops entries whenever
+//! the ID is re-added or tombstoned, and clears it when segments are
+//! swapped by compaction or resharding.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::storage_v2::types::NodeRecordV2;
+
+/// LRU map from node ID to record, evicting the least recently used entry
+/// once `capacity` is reached.
+///
+/// Recency is a monotonically increasing tick; `order` maps each entry's
+/// last-use tick back to its ID so the oldest entry is the first key.
+#[derive(Debug)]
+pub struct NodeCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<u128, (NodeRecordV2, u64)>,
+    order: BTreeMap<u64, u128>,
+}
+
+impl NodeCache {
+    /// Create an empty cache holding at most `capacity` records.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    /// Maximum number of cached records.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of cached records.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache holds no records.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Cached record for `id`, marking it most recently used.
+    pub fn get(&mut self, id: u128) -> Option<NodeRecordV2> {
+        self.tick += 1;
+        let (node, used) = self.entries.get_mut(&id)?;
+        self.order.remove(used);
+        *used = self.tick;
+        self.order.insert(self.tick, id);
+        Some(node.clone())
+    }
+
+    /// Cache `node`, evicting the least recently used record if full.
+    pub fn insert(&mut self, node: NodeRecordV2) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(node.id);
+        if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, node.id);
+        self.entries.insert(node.id, (node, self.tick));
+    }
+
+    /// Drop the cached record for `id`, if any.
+    pub fn remove(&mut self, id: u128) {
+        if let Some((_, used)) = self.entries.remove(&id) {
+            self.order.remove(&used);
+        }
+    }
+
+    /// Drop every cached record.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u128, name: &str) -> NodeRecordV2 {
+        NodeRecordV2 {
+            semantic_id: format!("FUNCTION:{name}@src/lib.js"),
+            id,
+            node_type: "FUNCTION".to_string(),
+            name: name.to_string(),
+            file: "src/lib.js".to_string(),
+            content_hash: 0,
+            metadata: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = NodeCache::new(2);
+        cache.insert(node(1, "a"));
+        cache.insert(node(2, "b"));
+        // Touch 1 so 2 becomes the eviction candidate
+        assert!(cache.get(1).is_some());
+        cache.insert(node(3, "c"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(3).is_some());
+    }
+
+    #[test]
+    fn test_reinsert_replaces_record() {
+        let mut cache = NodeCache::new(2);
+        cache.insert(node(1, "old"));
+        cache.insert(node(1, "new"));
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(1).unwrap().name, "new");
+    }
+
+    #[test]
+    fn test_zero_capacity_caches_nothing() {
+        let mut cache = NodeCache::new(0);
+        cache.insert(node(1, "a"));
+        assert!(cache.is_empty());
+    }
+}
@@ -6,6 +6,8 @@
 
 use sysinfo::{MemoryRefreshKind, RefreshKind, System};
 
+use crate::storage_v2::types::MmapAdvice;
+
 // ── Constants ───────────────────────────────────────────────────────
 
 const MB: usize = 1024 * 1024;
@@ -23,6 +25,13 @@ const WRITE_BUFFER_MAX: usize = 100 * MB;
 /// Fraction of available memory allocated to the write buffer.
 const WRITE_BUFFER_FRACTION: f64 = 0.02;
 
+/// Shard count ceiling for the recommendation.
+const MAX_RECOMMENDED_SHARDS: u16 = 16;
+
+/// Available memory budgeted per recommended shard (write buffer, bloom
+/// filters and index caches).
+const MEMORY_PER_SHARD: u64 = 256 * MB as u64;
+
 // ── SystemResources ─────────────────────────────────────────────────
 
 /// Snapshot of detected hardware resources.
@@ -84,27 +93,26 @@ pub struct TuningProfile {
     pub compaction_threads: usize,
     /// Memory pressure at detection time (0.0 = no pressure, 1.0 = critical).
     pub memory_pressure: f64,
+    /// `madvise` hint for query-time access to mapped segments. Compaction
+    /// reads its inputs with `MmapAdvice::Sequential` regardless.
+    pub mmap_advice: MmapAdvice,
 }
 
 impl TuningProfile {
     /// Compute a tuning profile from detected resources.
     ///
     /// Heuristics:
-    /// - `shard_count`: `min(16, next_power_of_two(cpu_count))` if RAM >= 2 GB, else 1.
+    /// - `shard_count`: `ResourceManager::recommended_shard_count`.
     /// - `segment_threshold`: RAM < 4 GB -> 2, < 16 GB -> 4, else 8.
     /// - `write_buffer_byte_limit`: `clamp(available * 0.02, 10 MB, 100 MB)`.
     /// - `write_buffer_node_limit`: `buffer_bytes / 220`.
     /// - `compaction_threads`: RAM < 4 GB -> 1, else `clamp(cpu / 2, 1, 4)`.
+    /// - `mmap_advice`: RAM < 4 GB -> `Random` (readahead would crowd out
+    ///   the page cache on point lookups), else `Normal`.
     pub fn from_resources(res: &SystemResources) -> Self {
         let total_gb = res.total_memory_bytes as f64 / GB as f64;
 
-        // Shard count
-        let shard_count = if res.total_memory_bytes >= 2 * GB {
-            let raw = res.cpu_count.next_power_of_two();
-            raw.min(16) as u16
-        } else {
-            1
-        };
+        let shard_count = ResourceManager::recommended_shard_count(res);
 
         // Segment threshold
         let segment_threshold = if total_gb < 4.0 {
@@ -129,6 +137,13 @@ impl TuningProfile {
             (res.cpu_count / 2).clamp(1, 4)
         };
 
+        // Query-time mmap advice
+        let mmap_advice = if total_gb < 4.0 {
+            MmapAdvice::Random
+        } else {
+            MmapAdvice::Normal
+        };
+
         Self {
             shard_count,
             segment_threshold,
@@ -136,6 +151,7 @@ impl TuningProfile {
             write_buffer_byte_limit,
             compaction_threads,
             memory_pressure: res.memory_pressure(),
+            mmap_advice,
         }
     }
 }
@@ -150,6 +166,7 @@ impl Default for TuningProfile {
             write_buffer_byte_limit: 10 * MB,
             compaction_threads: 1,
             memory_pressure: 0.0,
+            mmap_advice: MmapAdvice::Normal,
         }
     }
 }
@@ -165,6 +182,20 @@ impl ResourceManager {
         let resources = SystemResources::detect();
         TuningProfile::from_resources(&resources)
     }
+
+    /// Shard count for a new database on this machine.
+    ///
+    /// `next_power_of_two(cpu_count)`, capped at 16 and at one shard per
+    /// 256 MB of available memory. Machines with < 2 GB RAM get 1 shard.
+    pub fn recommended_shard_count(res: &SystemResources) -> u16 {
+        if res.total_memory_bytes < 2 * GB {
+            return 1;
+        }
+        let by_cpu = res.cpu_count.max(1).next_power_of_two().min(MAX_RECOMMENDED_SHARDS as usize) as u16;
+        let by_memory = (res.available_memory_bytes / MEMORY_PER_SHARD)
+            .clamp(1, MAX_RECOMMENDED_SHARDS as u64) as u16;
+        by_cpu.min(by_memory)
+    }
 }
 
 // ── Tests ───────────────────────────────────────────────────────────
@@ -198,6 +229,7 @@ mod tests {
         assert_eq!(profile.shard_count, 1);
         assert_eq!(profile.segment_threshold, 2);
         assert_eq!(profile.compaction_threads, 1);
+        assert_eq!(profile.mmap_advice, MmapAdvice::Random);
     }
 
     #[test]
@@ -209,6 +241,7 @@ mod tests {
         assert_eq!(profile.shard_count, 4);
         assert_eq!(profile.segment_threshold, 4);
         assert_eq!(profile.compaction_threads, 2);
+        assert_eq!(profile.mmap_advice, MmapAdvice::Normal);
     }
 
     #[test]
@@ -253,6 +286,28 @@ mod tests {
         assert_eq!(profile.compaction_threads, 1);
     }
 
+    #[test]
+    fn test_recommended_shard_count_scales_with_cpus() {
+        let counts: Vec<u16> = [1, 2, 3, 4, 6, 8, 16, 64]
+            .iter()
+            .map(|&cpus| ResourceManager::recommended_shard_count(&make_resources(32.0, 16.0, cpus)))
+            .collect();
+        assert_eq!(counts, vec![1, 2, 4, 4, 8, 8, 16, 16]);
+
+        // Never zero, even for a bogus CPU count
+        assert_eq!(ResourceManager::recommended_shard_count(&make_resources(32.0, 16.0, 0)), 1);
+    }
+
+    #[test]
+    fn test_recommended_shard_count_bounded_by_memory() {
+        // 16 CPUs, but only 1 GB available -> 4 shards at 256 MB each
+        assert_eq!(ResourceManager::recommended_shard_count(&make_resources(8.0, 1.0, 16)), 4);
+        // Almost nothing available still yields one shard
+        assert_eq!(ResourceManager::recommended_shard_count(&make_resources(8.0, 0.01, 16)), 1);
+        // Small machines stay unsharded regardless of CPUs
+        assert_eq!(ResourceManager::recommended_shard_count(&make_resources(1.5, 1.0, 8)), 1);
+    }
+
     #[test]
     fn test_memory_pressure() {
         // 1 GB total, 256 MB available -> pressure = 0.75
@@ -4,12 +4,16 @@
 //! columnar segments with memory-mapped or in-memory byte access.
 
 use std::fs::File;
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
 
 use memmap2::Mmap;
 
 use crate::error::{GraphError, Result};
 use crate::storage_v2::bloom::BloomFilter;
+use crate::storage_v2::index::edge_metadata::EdgeMetadataIndex;
+use crate::storage_v2::index::name_prefix::NamePrefixIndex;
 use crate::storage_v2::string_table::StringTableV2;
 use crate::storage_v2::types::*;
 use crate::storage_v2::zone_map::ZoneMap;
@@ -68,17 +72,22 @@ fn read_u128_at(data: &[u8], offset: usize) -> u128 {
 
 /// Compute byte offsets for node segment columns.
 ///
+/// `version` selects the node_type column width: u32 string table indices
+/// in v2, u16 dictionary codes from v3 on.
+///
 /// Returns: (semantic_id, node_type, name, file, metadata, ids, content_hash).
 fn compute_node_column_offsets(
     record_count: usize,
+    version: u16,
 ) -> (usize, usize, usize, usize, usize, usize, usize) {
     let n = record_count;
+    let node_type_width = if version >= 3 { 2 } else { 4 };
     let semantic_id_offset = HEADER_SIZE; // 32
     let node_type_offset = semantic_id_offset + 4 * n; // 32 + 4N
-    let name_offset = node_type_offset + 4 * n; // 32 + 8N
-    let file_offset = name_offset + 4 * n; // 32 + 12N
-    let metadata_offset = file_offset + 4 * n; // 32 + 16N
-    let u32_end = metadata_offset + 4 * n; // 32 + 20N
+    let name_offset = node_type_offset + node_type_width * n;
+    let file_offset = name_offset + 4 * n;
+    let metadata_offset = file_offset + 4 * n;
+    let u32_end = metadata_offset + 4 * n; // 32 + 18N (v3) / 32 + 20N (v2)
     let padding = compute_padding(u32_end, 16);
     let ids_offset = u32_end + padding; // 16-byte aligned
     let content_hash_offset = ids_offset + 16 * n;
@@ -126,6 +135,27 @@ impl std::ops::Deref for SegmentData {
     }
 }
 
+impl SegmentData {
+    /// Apply `advice` to the mapping. Owned data has no mapping to advise.
+    fn advise(&self, advice: MmapAdvice) -> std::io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            SegmentData::Mapped(m) => {
+                use memmap2::Advice;
+                m.advise(match advice {
+                    MmapAdvice::Normal => Advice::Normal,
+                    MmapAdvice::Random => Advice::Random,
+                    MmapAdvice::Sequential => Advice::Sequential,
+                })
+            }
+            _ => {
+                let _ = advice;
+                Ok(())
+            }
+        }
+    }
+}
+
 impl std::fmt::Debug for SegmentData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -135,6 +165,68 @@ impl std::fmt::Debug for SegmentData {
     }
 }
 
+/// Inflate a zstd-compressed segment into an owned buffer with the
+/// uncompressed layout. Uncompressed segments are returned unchanged.
+fn decompress_segment(data: SegmentData) -> Result<SegmentData> {
+    if data.len() < HEADER_SIZE {
+        // Size check in from_mapped reports the error
+        return Ok(data);
+    }
+    match CompressionKind::from_u8(data[7]) {
+        Some(CompressionKind::None) => Ok(data),
+        Some(CompressionKind::Zstd) => {
+            let body = zstd::decode_all(&data[HEADER_SIZE..])?;
+            let mut bytes = Vec::with_capacity(HEADER_SIZE + body.len());
+            bytes.extend_from_slice(&data[..HEADER_SIZE]);
+            bytes.extend_from_slice(&body);
+            bytes[7] = CompressionKind::None as u8;
+            Ok(SegmentData::Owned(bytes))
+        }
+        None => Err(GraphError::InvalidFormat(format!(
+            "Unknown segment compression: {}",
+            data[7]
+        ))),
+    }
+}
+
+/// Memory-map a segment file.
+fn map_file(path: &Path) -> Result<SegmentData> {
+    let file = File::open(path).map_err(GraphError::Io)?;
+    let mmap = unsafe { Mmap::map(&file) }.map_err(GraphError::Io)?;
+    Ok(SegmentData::Mapped(mmap))
+}
+
+/// Read a segment for a verified open: inflate it and check the header
+/// checksum against the body. Segments written before checksums existed
+/// (checksum 0) pass. Errors name the segment path.
+fn read_verified(path: &Path) -> Result<SegmentData> {
+    let data = map_file(path)?;
+    let data = decompress_segment(data).map_err(|e| {
+        GraphError::InvalidFormat(format!("Corrupt segment {}: {}", path.display(), e))
+    })?;
+    if data.len() < HEADER_SIZE {
+        return Err(GraphError::InvalidFormat(format!(
+            "Corrupt segment {}: file too small ({} bytes)",
+            path.display(),
+            data.len()
+        )));
+    }
+
+    let stored = read_u64_at(&data, CHECKSUM_OFFSET);
+    if stored != 0 {
+        let actual = segment_checksum(&data[HEADER_SIZE..]);
+        if stored != actual {
+            return Err(GraphError::InvalidFormat(format!(
+                "Corrupt segment {}: checksum mismatch (header {:016x}, computed {:016x})",
+                path.display(),
+                stored,
+                actual
+            )));
+        }
+    }
+    Ok(data)
+}
+
 // ── NodeSegmentV2 ──────────────────────────────────────────────────
 
 /// Immutable node segment reader (memory-mapped or from bytes).
@@ -154,14 +246,29 @@ pub struct NodeSegmentV2 {
     metadata_offset: usize,
     ids_offset: usize,
     content_hash_offset: usize,
+
+    /// node_type dictionary: string table index per u16 code.
+    /// `None` for v2 segments, whose node_type column holds string
+    /// table indices directly.
+    type_dict: Option<Vec<u32>>,
+
+    /// In-memory name prefix index, built on first `name_prefix_index`
+    name_prefix: OnceLock<NamePrefixIndex>,
 }
 
 impl NodeSegmentV2 {
-    /// Open a node segment from a file path (memory-mapped, zero-copy).
+    /// Open a node segment from a file path (memory-mapped, zero-copy;
+    /// compressed segments are read fully into memory).
     pub fn open(path: &Path) -> Result<Self> {
-        let file = File::open(path).map_err(GraphError::Io)?;
-        let mmap = unsafe { Mmap::map(&file) }.map_err(GraphError::Io)?;
-        Self::from_mapped(SegmentData::Mapped(mmap))
+        Self::from_mapped(map_file(path)?)
+    }
+
+    /// Open a node segment and verify its body checksum first.
+    ///
+    /// Reads the whole file, so this is for integrity checks (see
+    /// `DurabilityMode::Verified`) rather than hot-path opens.
+    pub fn open_verified(path: &Path) -> Result<Self> {
+        Self::from_mapped(read_verified(path)?)
     }
 
     /// Open a node segment from a byte slice (for testing / embedding).
@@ -169,8 +276,18 @@ impl NodeSegmentV2 {
         Self::from_mapped(SegmentData::Owned(bytes.to_vec()))
     }
 
+    /// Hint the expected access pattern to the kernel (`madvise`).
+    /// No-op for segments held in memory.
+    pub fn advise(&self, advice: MmapAdvice) -> std::io::Result<()> {
+        self.data.advise(advice)
+    }
+
     /// Shared construction from either mmap or owned bytes.
+    ///
+    /// Compressed segments are inflated into memory first.
     fn from_mapped(data: SegmentData) -> Result<Self> {
+        let data = decompress_segment(data)?;
+
         // 1. Minimum size check
         if data.len() < HEADER_SIZE + FOOTER_INDEX_SIZE {
             return Err(GraphError::InvalidFormat(
@@ -197,10 +314,36 @@ impl NodeSegmentV2 {
             ));
         }
 
-        // 5. Validate data_end_offset
+        // 5. Validate data_end_offset (v3+: columns are followed by the
+        //    node_type dictionary)
         let n = header.record_count as usize;
-        let (_, _, _, _, _, _, content_hash_offset) = compute_node_column_offsets(n);
-        let expected_data_end = content_hash_offset + 8 * n;
+        let (_, _, _, _, _, _, content_hash_offset) =
+            compute_node_column_offsets(n, header.version);
+        let columns_end = content_hash_offset + 8 * n;
+        let type_dict = if header.version >= 3 {
+            if data.len() < columns_end + 4 {
+                return Err(GraphError::InvalidFormat(
+                    "node_type dictionary truncated".into(),
+                ));
+            }
+            let dict_len = read_u32_at(&data, columns_end) as usize;
+            let dict_start = columns_end + 4;
+            if data.len() < dict_start + 4 * dict_len {
+                return Err(GraphError::InvalidFormat(
+                    "node_type dictionary truncated".into(),
+                ));
+            }
+            let dict: Vec<u32> = (0..dict_len)
+                .map(|i| read_u32_at(&data, dict_start + 4 * i))
+                .collect();
+            Some(dict)
+        } else {
+            None
+        };
+        let expected_data_end = match &type_dict {
+            Some(dict) => columns_end + 4 + 4 * dict.len(),
+            None => columns_end,
+        };
         if footer_index.data_end_offset != expected_data_end as u64 {
             return Err(GraphError::InvalidFormat(
                 "data_end_offset does not match column layout".into(),
@@ -230,7 +373,15 @@ impl NodeSegmentV2 {
             metadata_offset,
             ids_offset,
             content_hash_offset,
-        ) = compute_node_column_offsets(n);
+        ) = compute_node_column_offsets(n, header.version);
+
+        if let Some(dict) = &type_dict {
+            if dict.iter().any(|&idx| string_table.get(idx).is_none()) {
+                return Err(GraphError::InvalidFormat(
+                    "node_type dictionary references missing string".into(),
+                ));
+            }
+        }
 
         Ok(Self {
             data,
@@ -245,6 +396,8 @@ impl NodeSegmentV2 {
             metadata_offset,
             ids_offset,
             content_hash_offset,
+            type_dict,
+            name_prefix: OnceLock::new(),
         })
     }
 
@@ -253,6 +406,11 @@ impl NodeSegmentV2 {
         self.header.record_count as usize
     }
 
+    /// Size of the segment data in bytes (uncompressed layout).
+    pub fn byte_size(&self) -> usize {
+        self.data.len()
+    }
+
     // ── Column Accessors (O(1)) ────────────────────────────────────
 
     /// Get node id (u128) at given index.
@@ -270,8 +428,55 @@ impl NodeSegmentV2 {
 
     /// Get node_type string at given index.
     pub fn get_node_type(&self, index: usize) -> &str {
+        self.node_type_for_code(self.get_node_type_code(index))
+    }
+
+    /// Get the segment-local node_type code at given index.
+    ///
+    /// Two records in the same segment have equal codes exactly when their
+    /// node types are equal, so scans can compare codes instead of strings.
+    /// Codes are not comparable across segments.
+    pub fn get_node_type_code(&self, index: usize) -> u32 {
         debug_assert!(index < self.record_count(), "index out of bounds");
-        self.read_string_at(self.node_type_offset, index)
+        match self.type_dict {
+            Some(_) => {
+                let offset = self.node_type_offset + index * 2;
+                u16::from_le_bytes([self.data[offset], self.data[offset + 1]]) as u32
+            }
+            None => read_u32_at(&self.data, self.node_type_offset + index * 4),
+        }
+    }
+
+    /// Resolve a code from `get_node_type_code` back to its node_type.
+    pub fn node_type_for_code(&self, code: u32) -> &str {
+        let str_index = match &self.type_dict {
+            Some(dict) => dict[code as usize],
+            None => code,
+        };
+        self.string_table
+            .get(str_index)
+            .expect("invalid node_type code")
+    }
+
+    /// Find the segment-local code for `node_type`, or `None` if no record
+    /// in this segment has that type.
+    pub fn node_type_code(&self, node_type: &str) -> Option<u32> {
+        match &self.type_dict {
+            Some(dict) => dict
+                .iter()
+                .position(|&idx| self.string_table.get(idx) == Some(node_type))
+                .map(|code| code as u32),
+            None => (0..self.string_table.len() as u32)
+                .find(|&idx| self.string_table.get(idx) == Some(node_type)),
+        }
+    }
+
+    /// Rows sorted by lowercased name, for prefix lookups. Built on first
+    /// call and cached; segments are immutable so it never goes stale.
+    pub fn name_prefix_index(&self) -> &NamePrefixIndex {
+        self.name_prefix.get_or_init(|| {
+            NamePrefixIndex::build((0..self.record_count()).map(|i| self.get_name(i)))
+        })
     }
 
     /// Get name string at given index.
@@ -334,6 +539,13 @@ impl NodeSegmentV2 {
         self.zone_map.contains("file", file)
     }
 
+    /// Check if integer metadata field `field` may have a value in
+    /// `[min, max]` here. Always true when the field's range was not
+    /// tracked at write time.
+    pub fn may_contain_int_range(&self, field: &str, min: i64, max: i64) -> bool {
+        self.zone_map.may_contain_int_range(field, min, max)
+    }
+
     // ── Iteration ──────────────────────────────────────────────────
 
     /// Iterate over record indices (0..record_count).
@@ -374,14 +586,28 @@ pub struct EdgeSegmentV2 {
     dst_offset: usize,
     edge_type_offset: usize,
     metadata_offset: usize,
+
+    /// In-memory metadata indexes by field name, built on first
+    /// `metadata_index` call for that field
+    metadata_indexes: Mutex<HashMap<String, Arc<EdgeMetadataIndex>>>,
+
+    /// Row numbers sorted by source id, built on first `rows_for_src`
+    src_order: OnceLock<Vec<u32>>,
 }
 
 impl EdgeSegmentV2 {
-    /// Open an edge segment from a file path (memory-mapped, zero-copy).
+    /// Open an edge segment from a file path (memory-mapped, zero-copy;
+    /// compressed segments are read fully into memory).
     pub fn open(path: &Path) -> Result<Self> {
-        let file = File::open(path).map_err(GraphError::Io)?;
-        let mmap = unsafe { Mmap::map(&file) }.map_err(GraphError::Io)?;
-        Self::from_mapped(SegmentData::Mapped(mmap))
+        Self::from_mapped(map_file(path)?)
+    }
+
+    /// Open an edge segment and verify its body checksum first.
+    ///
+    /// Reads the whole file, so this is for integrity checks (see
+    /// `DurabilityMode::Verified`) rather than hot-path opens.
+    pub fn open_verified(path: &Path) -> Result<Self> {
+        Self::from_mapped(read_verified(path)?)
     }
 
     /// Open an edge segment from a byte slice (for testing / embedding).
@@ -389,8 +615,18 @@ impl EdgeSegmentV2 {
         Self::from_mapped(SegmentData::Owned(bytes.to_vec()))
     }
 
+    /// Hint the expected access pattern to the kernel (`madvise`).
+    /// No-op for segments held in memory.
+    pub fn advise(&self, advice: MmapAdvice) -> std::io::Result<()> {
+        self.data.advise(advice)
+    }
+
     /// Shared construction from either mmap or owned bytes.
+    ///
+    /// Compressed segments are inflated into memory first.
     fn from_mapped(data: SegmentData) -> Result<Self> {
+        let data = decompress_segment(data)?;
+
         // 1. Minimum size check
         if data.len() < HEADER_SIZE + FOOTER_INDEX_SIZE {
             return Err(GraphError::InvalidFormat(
@@ -460,6 +696,8 @@ impl EdgeSegmentV2 {
             dst_offset,
             edge_type_offset,
             metadata_offset,
+            metadata_indexes: Mutex::new(HashMap::new()),
+            src_order: OnceLock::new(),
         })
     }
 
@@ -468,6 +706,11 @@ impl EdgeSegmentV2 {
         self.header.record_count as usize
     }
 
+    /// Size of the segment data in bytes (uncompressed layout).
+    pub fn byte_size(&self) -> usize {
+        self.data.len()
+    }
+
     // ── Column Accessors (O(1)) ────────────────────────────────────
 
     /// Get source node id (u128) at given index.
@@ -496,6 +739,37 @@ impl EdgeSegmentV2 {
         self.read_string_at(self.metadata_offset, index)
     }
 
+    /// Rows by value of metadata field `field`. Built on first call for
+    /// the field and cached; segments are immutable so it never goes stale.
+    pub fn metadata_index(&self, field: &str) -> Arc<EdgeMetadataIndex> {
+        let mut indexes = self.metadata_indexes.lock().unwrap();
+        if let Some(index) = indexes.get(field) {
+            return Arc::clone(index);
+        }
+        let index = Arc::new(EdgeMetadataIndex::build(
+            field,
+            (0..self.record_count()).map(|i| self.get_metadata(i)),
+        ));
+        indexes.insert(field.to_string(), Arc::clone(&index));
+        index
+    }
+
+    /// Rows whose source id is `src`, via a row order sorted by source
+    /// that is built on first call and cached (4 bytes per row); segments
+    /// are immutable so it never goes stale.
+    pub fn rows_for_src(&self, src: u128) -> impl Iterator<Item = usize> + '_ {
+        let order = self.src_order.get_or_init(|| {
+            let mut order: Vec<u32> = (0..self.record_count() as u32).collect();
+            order.sort_by_key(|&row| self.get_src(row as usize));
+            order
+        });
+        let start = order.partition_point(|&row| self.get_src(row as usize) < src);
+        order[start..]
+            .iter()
+            .map(|&row| row as usize)
+            .take_while(move |&row| self.get_src(row) == src)
+    }
+
     /// Reconstruct a full record at given index (allocates strings).
     pub fn get_record(&self, index: usize) -> EdgeRecordV2 {
         EdgeRecordV2 {
@@ -665,6 +939,80 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_node_type_dictionary_roundtrip() {
+        let types = ["FUNCTION", "CLASS", "http:route", "FUNCTION"];
+        let records: Vec<NodeRecordV2> = (0..40)
+            .map(|i| make_node(&format!("n{}", i), types[i % types.len()], "x", "f.rs"))
+            .collect();
+        let bytes = write_node_segment(records.clone());
+        assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), FORMAT_VERSION);
+        let seg = NodeSegmentV2::from_bytes(&bytes).unwrap();
+
+        // One dictionary entry per distinct type, codes in first-seen order
+        assert_eq!(seg.type_dict.as_ref().map(|d| d.len()), Some(3));
+        assert_eq!(seg.node_type_code("FUNCTION"), Some(0));
+        assert_eq!(seg.node_type_code("CLASS"), Some(1));
+        assert_eq!(seg.node_type_code("http:route"), Some(2));
+        assert_eq!(seg.node_type_code("MODULE"), None);
+
+        for (i, expected) in records.iter().enumerate() {
+            let code = seg.get_node_type_code(i);
+            assert_eq!(seg.node_type_for_code(code), expected.node_type);
+            assert_eq!(seg.get_record(i), *expected);
+        }
+    }
+
+    /// Rewrite a v3 node segment into the v2 layout (u32 string table
+    /// index per node_type, no dictionary) to exercise the legacy path.
+    fn downgrade_node_segment_to_v2(bytes: &[u8]) -> Vec<u8> {
+        let seg = NodeSegmentV2::from_bytes(bytes).unwrap();
+        let n = seg.record_count();
+        let dict = seg.type_dict.clone().unwrap();
+        let fi_start = bytes.len() - FOOTER_INDEX_SIZE;
+        let mut footer = FooterIndex::from_bytes(&bytes[fi_start..]).unwrap();
+
+        let mut out = bytes[..HEADER_SIZE].to_vec();
+        out[4..6].copy_from_slice(&2u16.to_le_bytes());
+        out[24..32].copy_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&bytes[seg.semantic_id_offset..seg.node_type_offset]);
+        for j in 0..n {
+            out.extend_from_slice(&dict[seg.get_node_type_code(j) as usize].to_le_bytes());
+        }
+        out.extend_from_slice(&bytes[seg.name_offset..seg.name_offset + 12 * n]);
+        out.resize(out.len() + compute_padding(out.len(), 16), 0);
+        out.extend_from_slice(&bytes[seg.ids_offset..seg.content_hash_offset + 8 * n]);
+
+        let old_start = footer.bloom_offset;
+        let new_start = out.len() as u64;
+        out.extend_from_slice(&bytes[old_start as usize..fi_start]);
+        footer.bloom_offset = new_start;
+        footer.zone_maps_offset = footer.zone_maps_offset - old_start + new_start;
+        footer.string_table_offset = footer.string_table_offset - old_start + new_start;
+        footer.data_end_offset = new_start;
+        let footer_offset = out.len() as u64;
+        footer.write_to(&mut out).unwrap();
+        out[16..24].copy_from_slice(&footer_offset.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn test_v2_node_segment_still_readable() {
+        let records: Vec<NodeRecordV2> = (0..7)
+            .map(|i| make_node(&format!("n{}", i), if i % 3 == 0 { "CLASS" } else { "FUNCTION" }, "x", "f.rs"))
+            .collect();
+        let v2 = downgrade_node_segment_to_v2(&write_node_segment(records.clone()));
+        let seg = NodeSegmentV2::from_bytes(&v2).unwrap();
+
+        assert!(seg.type_dict.is_none());
+        for (i, expected) in records.iter().enumerate() {
+            assert_eq!(seg.get_record(i), *expected);
+        }
+        let class = seg.node_type_code("CLASS").unwrap();
+        let classes = (0..seg.record_count()).filter(|&j| seg.get_node_type_code(j) == class).count();
+        assert_eq!(classes, 3);
+    }
+
     #[test]
     fn test_edge_roundtrip_100() {
         let mut records = Vec::new();
@@ -740,6 +1088,28 @@ mod tests {
         assert_eq!(record, edge);
     }
 
+    #[test]
+    fn test_rows_for_src() {
+        let edges = vec![
+            make_edge("a", "x", "CALLS"),
+            make_edge("b", "x", "CALLS"),
+            make_edge("a", "y", "CONTAINS"),
+            make_edge("c", "a", "CALLS"),
+            make_edge("a", "z", "CALLS"),
+        ];
+        let bytes = write_edge_segment(edges.clone());
+        let seg = EdgeSegmentV2::from_bytes(&bytes).unwrap();
+
+        let src_a = edges[0].src;
+        let mut dsts: Vec<u128> = seg.rows_for_src(src_a).map(|j| seg.get_dst(j)).collect();
+        dsts.sort();
+        let mut expected = vec![edges[0].dst, edges[2].dst, edges[4].dst];
+        expected.sort();
+        assert_eq!(dsts, expected);
+        assert_eq!(seg.rows_for_src(edges[1].src).count(), 1);
+        assert_eq!(seg.rows_for_src(edges[3].dst.wrapping_add(1)).count(), 0);
+    }
+
     // ── Phase 3: Alignment + Binary Stability ──────────────────────
 
     #[test]
@@ -1091,6 +1461,106 @@ mod tests {
         assert_eq!(seg.get_record(0), node);
     }
 
+    // ── Compressed Segments ───────────────────────────────────────────
+
+    #[test]
+    fn test_zstd_segments_roundtrip() {
+        use tempfile::NamedTempFile;
+
+        let nodes: Vec<NodeRecordV2> = (0..200)
+            .map(|i| NodeRecordV2 {
+                content_hash: i as u64,
+                metadata: format!(r#"{{"line":{}}}"#, i),
+                ..make_node(&format!("node_{}", i), "FUNCTION", &format!("fn_{}", i % 7), "src/app.js")
+            })
+            .collect();
+        let edges: Vec<EdgeRecordV2> = (0..199)
+            .map(|i| make_edge(&format!("node_{}", i), &format!("node_{}", i + 1), "CALLS"))
+            .collect();
+
+        let mut writer = NodeSegmentWriter::with_compression(CompressionKind::Zstd);
+        for n in &nodes {
+            writer.add(n.clone());
+        }
+        let mut buf = Cursor::new(Vec::new());
+        let meta = writer.finish(&mut buf).unwrap();
+        let compressed = buf.into_inner();
+
+        assert_eq!(meta.compression, CompressionKind::Zstd);
+        assert_eq!(meta.byte_size, compressed.len() as u64);
+        assert!(compressed.len() < write_node_segment(nodes.clone()).len());
+
+        // File open path: decompressed into memory instead of mmapped
+        let mut temp = NamedTempFile::new().unwrap();
+        use std::io::Write;
+        temp.write_all(&compressed).unwrap();
+        temp.flush().unwrap();
+        let seg = NodeSegmentV2::open(temp.path()).unwrap();
+        assert_eq!(seg.iter().collect::<Vec<_>>(), nodes);
+        assert!(seg.maybe_contains(nodes[42].id));
+
+        let mut writer = EdgeSegmentWriter::with_compression(CompressionKind::Zstd);
+        for e in &edges {
+            writer.add(e.clone());
+        }
+        let mut buf = Cursor::new(Vec::new());
+        writer.finish(&mut buf).unwrap();
+        let seg = EdgeSegmentV2::from_bytes(&buf.into_inner()).unwrap();
+        assert_eq!(seg.iter().collect::<Vec<_>>(), edges);
+    }
+
+    #[test]
+    fn test_unknown_compression_rejected() {
+        let mut bytes = write_node_segment(vec![make_node("id", "FUNCTION", "name", "file.rs")]);
+        bytes[7] = 0xFF;
+        let err = NodeSegmentV2::from_bytes(&bytes).unwrap_err();
+        assert!(err.to_string().contains("Unknown segment compression"), "{}", err);
+    }
+
+    // ── Checksums ─────────────────────────────────────────────────────
+
+    #[test]
+    fn test_open_verified_detects_flipped_byte() {
+        use tempfile::NamedTempFile;
+        use std::io::Write;
+
+        let node = make_node("id", "FUNCTION", "name", "file.rs");
+        let mut bytes = write_node_segment(vec![node.clone()]);
+        assert_ne!(read_u64_at(&bytes, CHECKSUM_OFFSET), 0);
+
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(&bytes).unwrap();
+        temp.flush().unwrap();
+        assert_eq!(NodeSegmentV2::open_verified(temp.path()).unwrap().get_record(0), node);
+
+        // Flip a bit in the id column (starts at 64 for a single record)
+        bytes[64] ^= 0x01;
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(&bytes).unwrap();
+        temp.flush().unwrap();
+
+        // Unverified open happily returns the garbage id
+        assert_ne!(NodeSegmentV2::open(temp.path()).unwrap().get_id(0), node.id);
+
+        let err = NodeSegmentV2::open_verified(temp.path()).unwrap_err().to_string();
+        assert!(err.contains("checksum mismatch"), "{}", err);
+        assert!(err.contains(&temp.path().display().to_string()), "{}", err);
+    }
+
+    #[test]
+    fn test_open_verified_accepts_legacy_segment_without_checksum() {
+        use tempfile::NamedTempFile;
+        use std::io::Write;
+
+        let mut bytes = write_edge_segment(vec![make_edge("a", "b", "CALLS")]);
+        bytes[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 8].fill(0);
+
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(&bytes).unwrap();
+        temp.flush().unwrap();
+        assert_eq!(EdgeSegmentV2::open_verified(temp.path()).unwrap().record_count(), 1);
+    }
+
     // ── Prefetch Smoke Test ───────────────────────────────────────────
 
     #[test]
@@ -1113,4 +1583,25 @@ mod tests {
         let result = super::prefetch_file(Path::new("/nonexistent/path/file.seg"));
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_advise_mapped_segment() {
+        use tempfile::NamedTempFile;
+
+        let node = make_node("id", "FUNCTION", "name", "file.rs");
+        let mut temp = NamedTempFile::new().unwrap();
+        use std::io::Write;
+        temp.write_all(&write_node_segment(vec![node.clone()])).unwrap();
+        temp.flush().unwrap();
+
+        let seg = NodeSegmentV2::open(temp.path()).unwrap();
+        for advice in [MmapAdvice::Random, MmapAdvice::Sequential, MmapAdvice::Normal] {
+            seg.advise(advice).unwrap();
+        }
+        assert_eq!(seg.get_record(0), node);
+
+        // In-memory segments accept any advice
+        NodeSegmentV2::from_bytes(&write_node_segment(vec![node])).unwrap()
+            .advise(MmapAdvice::Random).unwrap();
+    }
 }
@@ -13,16 +13,22 @@
 //! results; the caller updates the manifest.
 
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufWriter, Cursor};
 use std::path::{Path, PathBuf};
 
 use crate::error::Result;
+use crate::storage_v2::explain::{AccessPath, ScanStep, ShardPlan};
+use crate::storage_v2::index::edge_metadata::metadata_value_key;
 use crate::storage_v2::index::InvertedIndex;
 use crate::storage_v2::manifest::SegmentDescriptor;
 use crate::storage_v2::segment::{EdgeSegmentV2, NodeSegmentV2};
-use crate::storage_v2::types::{EdgeRecordV2, NodeRecordV2, SegmentMeta, SegmentType};
+use crate::storage_v2::types::{
+    CompressionKind, EdgeRecordV2, MmapAdvice, NodeRecordV2, SegmentMeta, SegmentType,
+    DEFAULT_BLOOM_FP_RATE,
+};
 use crate::storage_v2::write_buffer::WriteBuffer;
 use crate::storage_v2::writer::{EdgeSegmentWriter, NodeSegmentWriter};
 use serde::Serialize;
@@ -49,9 +55,12 @@ pub struct ShardDiagnostics {
     pub l0_edge_segment_count: usize,
     pub l1_node_records: usize,
     pub l1_edge_records: usize,
+    pub l2_node_records: usize,
+    pub l2_edge_records: usize,
     // Tombstones
     pub tombstone_node_count: usize,
     pub tombstone_edge_count: usize,
+    pub tombstoned_records: usize,
     // Indexes
     pub has_l1_by_type: bool,
     pub has_l1_by_file: bool,
@@ -165,6 +174,72 @@ pub struct FlushResult {
     pub edge_segment_path: Option<PathBuf>,
 }
 
+/// One compacted tier of a shard (L1 or L2): at most one node segment and
+/// one edge segment — sorted, deduplicated, tombstones removed — plus the
+/// inverted indexes built over the node segment during compaction.
+struct CompactedLevel {
+    /// Tier name used in query plans ("L1", "L2").
+    label: &'static str,
+
+    /// Compacted node segment.
+    node_segment: Option<NodeSegmentV2>,
+
+    /// Node segment descriptor (for manifest tracking / zone map pruning).
+    node_descriptor: Option<SegmentDescriptor>,
+
+    /// Compacted edge segment.
+    edge_segment: Option<EdgeSegmentV2>,
+
+    /// Edge segment descriptor.
+    edge_descriptor: Option<SegmentDescriptor>,
+
+    /// Inverted index: node_type -> IndexEntry list (built during compaction).
+    by_type_index: Option<InvertedIndex>,
+
+    /// Inverted index: file -> IndexEntry list (built during compaction).
+    by_file_index: Option<InvertedIndex>,
+
+    /// Inverted index: name -> IndexEntry list (built during compaction).
+    by_name_index: Option<InvertedIndex>,
+}
+
+impl CompactedLevel {
+    fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            node_segment: None,
+            node_descriptor: None,
+            edge_segment: None,
+            edge_descriptor: None,
+            by_type_index: None,
+            by_file_index: None,
+            by_name_index: None,
+        }
+    }
+
+    /// Best-effort `madvise` on both segments.
+    fn advise(&self, advice: MmapAdvice) {
+        if let Some(seg) = &self.node_segment {
+            seg.advise(advice).ok();
+        }
+        if let Some(seg) = &self.edge_segment {
+            seg.advise(advice).ok();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.node_segment.is_none() && self.edge_segment.is_none()
+    }
+
+    fn node_records(&self) -> usize {
+        self.node_segment.as_ref().map_or(0, |s| s.record_count())
+    }
+
+    fn edge_records(&self) -> usize {
+        self.edge_segment.as_ref().map_or(0, |s| s.record_count())
+    }
+}
+
 /// A shard is the primary read/write unit for RFDB v2.
 ///
 /// Segments are stored in Vec, ordered by creation time (oldest first,
@@ -198,31 +273,47 @@ pub struct Shard {
     /// Tombstone state (loaded from manifest on open).
     tombstones: TombstoneSet,
 
-    /// L1 (compacted) node segment — sorted, deduplicated, tombstones removed.
-    /// None if shard has never been compacted.
-    l1_node_segment: Option<NodeSegmentV2>,
+    /// Estimated segment records (L0/L1/L2) hidden by `tombstones`.
+    /// Maintained by `set_tombstones`, reset by compaction.
+    tombstoned_records: usize,
 
-    /// L1 node segment descriptor (for manifest tracking / zone map pruning).
-    l1_node_descriptor: Option<SegmentDescriptor>,
+    /// L1 (compacted) tier. Empty if shard has never been compacted.
+    l1: CompactedLevel,
 
-    /// L1 (compacted) edge segment.
-    l1_edge_segment: Option<EdgeSegmentV2>,
+    /// L2 tier: older compacted data merged down from L1 once L1 outgrows
+    /// `CompactionConfig::l1_record_threshold`. Scanned after L1.
+    l2: CompactedLevel,
 
-    /// L1 edge segment descriptor.
-    l1_edge_descriptor: Option<SegmentDescriptor>,
+    /// Lazy edge-type index: edge_type → [(src, dst)].
+    /// Built on first `get_edges_by_type()` call, invalidated on mutation.
+    edge_type_index: Mutex<Option<HashMap<String, Vec<(u128, u128)>>>>,
 
-    /// Inverted index: node_type -> IndexEntry list (built during compaction).
-    l1_by_type_index: Option<InvertedIndex>,
+    /// Compression for segments written by flush (and compaction).
+    /// Existing segments are read according to their own header.
+    compression: CompressionKind,
 
-    /// Inverted index: file -> IndexEntry list (built during compaction).
-    l1_by_file_index: Option<InvertedIndex>,
+    /// Target bloom filter false-positive rate for newly written segments.
+    bloom_fp_rate: f64,
 
-    /// Inverted index: name -> IndexEntry list (built during compaction).
-    l1_by_name_index: Option<InvertedIndex>,
+    /// `madvise` hint applied to every segment this shard serves queries
+    /// from, including ones loaded later by flush and compaction.
+    mmap_advice: MmapAdvice,
 
-    /// Lazy edge-type index: edge_type → [(src, dst)].
-    /// Built on first `get_edges_by_type()` call, invalidated on mutation.
-    edge_type_index: Mutex<Option<HashMap<String, Vec<(u128, u128)>>>>,
+    /// Integer metadata fields whose min/max is tracked in the zone map
+    /// of newly written node segments (declared `FieldType::Int` fields).
+    int_fields: Vec<String>,
+
+    /// Node segments whose records were read by `find_node_ids_by_int_range`,
+    /// i.e. not pruned by their int range. Diagnostic counter.
+    int_range_segment_scans: AtomicUsize,
+
+    /// Edge metadata fields with a per-segment metadata index (declared
+    /// `FieldEntity::Edge` fields). Built eagerly for new edge segments.
+    edge_index_fields: Vec<String>,
+
+    /// Edge segments read row by row by `find_edges_by_metadata` because
+    /// the field had no metadata index. Diagnostic counter.
+    edge_metadata_segment_scans: AtomicUsize,
 }
 
 // -- Constructors -------------------------------------------------------------
@@ -242,14 +333,17 @@ impl Shard {
             node_descriptors: Vec::new(),
             edge_descriptors: Vec::new(),
             tombstones: TombstoneSet::new(),
-            l1_node_segment: None,
-            l1_node_descriptor: None,
-            l1_edge_segment: None,
-            l1_edge_descriptor: None,
-            l1_by_type_index: None,
-            l1_by_file_index: None,
-            l1_by_name_index: None,
+            tombstoned_records: 0,
+            l1: CompactedLevel::new("L1"),
+            l2: CompactedLevel::new("L2"),
             edge_type_index: Mutex::new(None),
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            mmap_advice: MmapAdvice::Normal,
+            int_fields: Vec::new(),
+            int_range_segment_scans: AtomicUsize::new(0),
+            edge_index_fields: Vec::new(),
+            edge_metadata_segment_scans: AtomicUsize::new(0),
         })
     }
 
@@ -290,14 +384,17 @@ impl Shard {
             node_descriptors,
             edge_descriptors,
             tombstones: TombstoneSet::new(),
-            l1_node_segment: None,
-            l1_node_descriptor: None,
-            l1_edge_segment: None,
-            l1_edge_descriptor: None,
-            l1_by_type_index: None,
-            l1_by_file_index: None,
-            l1_by_name_index: None,
+            tombstoned_records: 0,
+            l1: CompactedLevel::new("L1"),
+            l2: CompactedLevel::new("L2"),
             edge_type_index: Mutex::new(None),
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            mmap_advice: MmapAdvice::Normal,
+            int_fields: Vec::new(),
+            int_range_segment_scans: AtomicUsize::new(0),
+            edge_index_fields: Vec::new(),
+            edge_metadata_segment_scans: AtomicUsize::new(0),
         })
     }
 
@@ -313,14 +410,17 @@ impl Shard {
             node_descriptors: Vec::new(),
             edge_descriptors: Vec::new(),
             tombstones: TombstoneSet::new(),
-            l1_node_segment: None,
-            l1_node_descriptor: None,
-            l1_edge_segment: None,
-            l1_edge_descriptor: None,
-            l1_by_type_index: None,
-            l1_by_file_index: None,
-            l1_by_name_index: None,
+            tombstoned_records: 0,
+            l1: CompactedLevel::new("L1"),
+            l2: CompactedLevel::new("L2"),
             edge_type_index: Mutex::new(None),
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            mmap_advice: MmapAdvice::Normal,
+            int_fields: Vec::new(),
+            int_range_segment_scans: AtomicUsize::new(0),
+            edge_index_fields: Vec::new(),
+            edge_metadata_segment_scans: AtomicUsize::new(0),
         }
     }
 
@@ -339,14 +439,17 @@ impl Shard {
             node_descriptors: Vec::new(),
             edge_descriptors: Vec::new(),
             tombstones: TombstoneSet::new(),
-            l1_node_segment: None,
-            l1_node_descriptor: None,
-            l1_edge_segment: None,
-            l1_edge_descriptor: None,
-            l1_by_type_index: None,
-            l1_by_file_index: None,
-            l1_by_name_index: None,
+            tombstoned_records: 0,
+            l1: CompactedLevel::new("L1"),
+            l2: CompactedLevel::new("L2"),
             edge_type_index: Mutex::new(None),
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            mmap_advice: MmapAdvice::Normal,
+            int_fields: Vec::new(),
+            int_range_segment_scans: AtomicUsize::new(0),
+            edge_index_fields: Vec::new(),
+            edge_metadata_segment_scans: AtomicUsize::new(0),
         })
     }
 
@@ -358,24 +461,34 @@ impl Shard {
     /// `path` is the shard directory (e.g., `<db_path>/segments/NN/`).
     /// `db_path` is the database root (for resolving segment file paths
     /// via `SegmentDescriptor::file_path()`).
+    /// `verify_checksums` opens segments with `open_verified()`.
     pub fn open_for_shard(
         path: &Path,
         db_path: &Path,
         shard_id: u16,
         node_descriptors: Vec<SegmentDescriptor>,
         edge_descriptors: Vec<SegmentDescriptor>,
+        verify_checksums: bool,
     ) -> Result<Self> {
         let mut node_segments = Vec::with_capacity(node_descriptors.len());
         for desc in &node_descriptors {
             let file_path = desc.file_path(db_path);
-            let seg = NodeSegmentV2::open(&file_path)?;
+            let seg = if verify_checksums {
+                NodeSegmentV2::open_verified(&file_path)?
+            } else {
+                NodeSegmentV2::open(&file_path)?
+            };
             node_segments.push(seg);
         }
 
         let mut edge_segments = Vec::with_capacity(edge_descriptors.len());
         for desc in &edge_descriptors {
             let file_path = desc.file_path(db_path);
-            let seg = EdgeSegmentV2::open(&file_path)?;
+            let seg = if verify_checksums {
+                EdgeSegmentV2::open_verified(&file_path)?
+            } else {
+                EdgeSegmentV2::open(&file_path)?
+            };
             edge_segments.push(seg);
         }
 
@@ -388,14 +501,17 @@ impl Shard {
             node_descriptors,
             edge_descriptors,
             tombstones: TombstoneSet::new(),
-            l1_node_segment: None,
-            l1_node_descriptor: None,
-            l1_edge_segment: None,
-            l1_edge_descriptor: None,
-            l1_by_type_index: None,
-            l1_by_file_index: None,
-            l1_by_name_index: None,
+            tombstoned_records: 0,
+            l1: CompactedLevel::new("L1"),
+            l2: CompactedLevel::new("L2"),
             edge_type_index: Mutex::new(None),
+            compression: CompressionKind::None,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            mmap_advice: MmapAdvice::Normal,
+            int_fields: Vec::new(),
+            int_range_segment_scans: AtomicUsize::new(0),
+            edge_index_fields: Vec::new(),
+            edge_metadata_segment_scans: AtomicUsize::new(0),
         })
     }
 }
@@ -422,11 +538,30 @@ impl Shard {
     /// Set tombstone state (called by MultiShardStore after commit).
     ///
     /// Replaces the entire tombstone set. Used when loading from manifest
-    /// or after commit_batch updates tombstones.
+    /// or after commit_batch updates tombstones. Tombstones added or
+    /// removed relative to the previous set are probed against segment
+    /// bloom filters to keep `tombstoned_record_count()` current.
     ///
-    /// Complexity: O(1) (pointer swap)
+    /// Complexity: O(T + D * S) where T = tombstones, D = tombstones
+    ///   added or removed, S = segments
     pub fn set_tombstones(&mut self, tombstones: TombstoneSet) {
+        let old = &self.tombstones;
+        let added = tombstones.node_ids.difference(&old.node_ids)
+            .filter(|&&id| self.segments_may_hold_node(id))
+            .count()
+            + tombstones.edge_keys.difference(&old.edge_keys)
+                .filter(|(src, _, _)| self.segments_may_hold_edge_src(*src))
+                .count();
+        let removed = old.node_ids.difference(&tombstones.node_ids)
+            .filter(|&&id| self.segments_may_hold_node(id))
+            .count()
+            + old.edge_keys.difference(&tombstones.edge_keys)
+                .filter(|(src, _, _)| self.segments_may_hold_edge_src(*src))
+                .count();
+        self.tombstoned_records = (self.tombstoned_records + added).saturating_sub(removed);
         self.tombstones = tombstones;
+        // Built with the old tombstones filtered out
+        *self.edge_type_index.lock().unwrap() = None;
     }
 
     /// Get reference to current tombstone set (for reading).
@@ -434,9 +569,50 @@ impl Shard {
         &self.tombstones
     }
 
-    /// Get mutable reference to tombstone set.
-    pub fn tombstones_mut(&mut self) -> &mut TombstoneSet {
-        &mut self.tombstones
+    /// Estimated number of segment records hidden by tombstones.
+    ///
+    /// Tombstone sets are shared by all shards; this counts only the
+    /// entries whose record lives in this shard's segments. Based on
+    /// bloom filters, so it may overcount slightly.
+    pub fn tombstoned_record_count(&self) -> usize {
+        self.tombstoned_records
+    }
+
+    /// Tombstoned segment records per live record (0.0 without tombstones).
+    pub fn tombstone_ratio(&self) -> f64 {
+        if self.tombstoned_records == 0 {
+            return 0.0;
+        }
+        let total = self.node_count() + self.edge_count();
+        let live = total.saturating_sub(self.tombstoned_records);
+        self.tombstoned_records as f64 / live.max(1) as f64
+    }
+
+    /// `(bytes, records)` across all node and edge segments (L0, L1, L2).
+    /// The write buffer is not included.
+    pub fn segment_footprint(&self) -> (u64, u64) {
+        let compacted = self.compacted_levels();
+        let nodes = self.node_segments.iter()
+            .chain(compacted.iter().filter_map(|level| level.node_segment.as_ref()))
+            .map(|seg| (seg.byte_size() as u64, seg.record_count() as u64));
+        let edges = self.edge_segments.iter()
+            .chain(compacted.iter().filter_map(|level| level.edge_segment.as_ref()))
+            .map(|seg| (seg.byte_size() as u64, seg.record_count() as u64));
+        nodes.chain(edges).fold((0, 0), |(bytes, records), (b, r)| (bytes + b, records + r))
+    }
+
+    fn segments_may_hold_node(&self, id: u128) -> bool {
+        self.node_segments.iter().any(|seg| seg.maybe_contains(id))
+            || self.compacted_levels().iter().any(|level| {
+                level.node_segment.as_ref().is_some_and(|seg| seg.maybe_contains(id))
+            })
+    }
+
+    fn segments_may_hold_edge_src(&self, src: u128) -> bool {
+        self.edge_segments.iter().any(|seg| seg.maybe_contains_src(src))
+            || self.compacted_levels().iter().any(|level| {
+                level.edge_segment.as_ref().is_some_and(|seg| seg.maybe_contains_src(src))
+            })
     }
 
     /// Find edge keys (src, dst, edge_type) where src is in the given ID set.
@@ -506,15 +682,15 @@ impl Shard {
             }
         }
 
-        // Step 2: Scan L1 edge segment
-        if let Some(l1_seg) = &self.l1_edge_segment {
-            let may_match = src_ids.iter().any(|id| l1_seg.maybe_contains_src(*id));
+        // Step 2: Scan compacted edge segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.edge_segment.as_ref()) {
+            let may_match = src_ids.iter().any(|id| cseg.maybe_contains_src(*id));
             if may_match {
-                for j in 0..l1_seg.record_count() {
-                    let src = l1_seg.get_src(j);
+                for j in 0..cseg.record_count() {
+                    let src = cseg.get_src(j);
                     if src_ids.contains(&src) {
-                        let dst = l1_seg.get_dst(j);
-                        let edge_type = l1_seg.get_edge_type(j).to_string();
+                        let dst = cseg.get_dst(j);
+                        let edge_type = cseg.get_edge_type(j).to_string();
                         keys.push((src, dst, edge_type));
                     }
                 }
@@ -607,7 +783,7 @@ impl Shard {
     }
 }
 
-// -- L1 Accessors + Management ------------------------------------------------
+// -- L1/L2 Accessors + Management ---------------------------------------------
 
 impl Shard {
     /// Number of L0 node segments.
@@ -622,7 +798,22 @@ impl Shard {
 
     /// Whether this shard has been compacted (has L1 segments).
     pub fn has_l1(&self) -> bool {
-        self.l1_node_segment.is_some() || self.l1_edge_segment.is_some()
+        !self.l1.is_empty()
+    }
+
+    /// Whether this shard has L2 segments.
+    pub fn has_l2(&self) -> bool {
+        !self.l2.is_empty()
+    }
+
+    /// Records held by the L1 tier (nodes + edges).
+    pub fn l1_record_count(&self) -> usize {
+        self.l1.node_records() + self.l1.edge_records()
+    }
+
+    /// Compacted tiers in read order: L1 (newer) before L2 (older).
+    fn compacted_levels(&self) -> [&CompactedLevel; 2] {
+        [&self.l1, &self.l2]
     }
 
     /// Get shard ID.
@@ -635,14 +826,92 @@ impl Shard {
         self.path.as_deref()
     }
 
+    /// Compression applied to newly written segments.
+    pub fn compression(&self) -> CompressionKind {
+        self.compression
+    }
+
+    /// Set compression for segments written from now on.
+    pub fn set_compression(&mut self, compression: CompressionKind) {
+        self.compression = compression;
+    }
+
+    /// Bloom filter false-positive rate for newly written segments.
+    pub fn bloom_fp_rate(&self) -> f64 {
+        self.bloom_fp_rate
+    }
+
+    /// Set the bloom filter false-positive rate for segments written from now on.
+    pub fn set_bloom_fp_rate(&mut self, fp_rate: f64) {
+        self.bloom_fp_rate = fp_rate;
+    }
+
+    /// Query-time `madvise` hint for this shard's segments.
+    pub fn mmap_advice(&self) -> MmapAdvice {
+        self.mmap_advice
+    }
+
+    /// Set the query-time `madvise` hint and apply it to all loaded segments.
+    pub fn set_mmap_advice(&mut self, advice: MmapAdvice) {
+        self.mmap_advice = advice;
+        self.advise_segments(advice);
+    }
+
+    /// Apply `advice` to every L0, L1 and L2 segment without changing the
+    /// shard's own setting (compaction reads inputs sequentially, then
+    /// restores it). Best-effort: the hint has no correctness impact.
+    pub fn advise_segments(&self, advice: MmapAdvice) {
+        for seg in &self.node_segments {
+            seg.advise(advice).ok();
+        }
+        for seg in &self.edge_segments {
+            seg.advise(advice).ok();
+        }
+        for level in self.compacted_levels() {
+            level.advise(advice);
+        }
+    }
+
+    /// Integer metadata fields with zone map ranges in new node segments.
+    pub fn int_fields(&self) -> &[String] {
+        &self.int_fields
+    }
+
+    /// Track zone map ranges for these integer metadata fields in node
+    /// segments written from now on.
+    pub fn set_int_fields(&mut self, fields: Vec<String>) {
+        self.int_fields = fields;
+    }
+
+    /// Node segments scanned (not pruned) by int range queries so far.
+    pub fn int_range_segment_scans(&self) -> usize {
+        self.int_range_segment_scans.load(Ordering::Relaxed)
+    }
+
+    /// Edge metadata fields indexed per segment.
+    pub fn edge_index_fields(&self) -> &[String] {
+        &self.edge_index_fields
+    }
+
+    /// Set the edge metadata fields to index. Existing segments build
+    /// their index for a field on first lookup.
+    pub fn set_edge_index_fields(&mut self, fields: Vec<String>) {
+        self.edge_index_fields = fields;
+    }
+
+    /// Edge segments read row by row by `find_edges_by_metadata`.
+    pub fn edge_metadata_segment_scans(&self) -> usize {
+        self.edge_metadata_segment_scans.load(Ordering::Relaxed)
+    }
+
     /// Get L1 node descriptor.
     pub fn l1_node_descriptor(&self) -> Option<&SegmentDescriptor> {
-        self.l1_node_descriptor.as_ref()
+        self.l1.node_descriptor.as_ref()
     }
 
     /// Get L1 edge descriptor.
     pub fn l1_edge_descriptor(&self) -> Option<&SegmentDescriptor> {
-        self.l1_edge_descriptor.as_ref()
+        self.l1.edge_descriptor.as_ref()
     }
 
     /// Get references to L0 node segments (for merge).
@@ -667,12 +936,43 @@ impl Shard {
 
     /// Get L1 node segment (for merge input during re-compaction).
     pub fn l1_node_segment(&self) -> Option<&NodeSegmentV2> {
-        self.l1_node_segment.as_ref()
+        self.l1.node_segment.as_ref()
     }
 
     /// Get L1 edge segment (for merge input during re-compaction).
     pub fn l1_edge_segment(&self) -> Option<&EdgeSegmentV2> {
-        self.l1_edge_segment.as_ref()
+        self.l1.edge_segment.as_ref()
+    }
+
+    /// Get L2 node descriptor.
+    pub fn l2_node_descriptor(&self) -> Option<&SegmentDescriptor> {
+        self.l2.node_descriptor.as_ref()
+    }
+
+    /// Get L2 edge descriptor.
+    pub fn l2_edge_descriptor(&self) -> Option<&SegmentDescriptor> {
+        self.l2.edge_descriptor.as_ref()
+    }
+
+    /// Get L2 node segment (for merge input when L1 is merged down).
+    pub fn l2_node_segment(&self) -> Option<&NodeSegmentV2> {
+        self.l2.node_segment.as_ref()
+    }
+
+    /// Get L2 edge segment (for merge input when L1 is merged down).
+    pub fn l2_edge_segment(&self) -> Option<&EdgeSegmentV2> {
+        self.l2.edge_segment.as_ref()
+    }
+
+    /// Compacted (L1 or L2) node segment with the given segment ID.
+    ///
+    /// Used to resolve `GlobalIndex` entries, which record the segment ID
+    /// alongside the record offset.
+    pub fn compacted_node_segment(&self, segment_id: u64) -> Option<&NodeSegmentV2> {
+        self.compacted_levels()
+            .into_iter()
+            .find(|l| l.node_descriptor.as_ref().map(|d| d.segment_id) == Some(segment_id))
+            .and_then(|l| l.node_segment.as_ref())
     }
 
     /// Set L1 segments after compaction or on shard open.
@@ -683,15 +983,32 @@ impl Shard {
         edge_segment: Option<EdgeSegmentV2>,
         edge_descriptor: Option<SegmentDescriptor>,
     ) {
-        self.l1_node_segment = node_segment;
-        self.l1_node_descriptor = node_descriptor;
-        self.l1_edge_segment = edge_segment;
-        self.l1_edge_descriptor = edge_descriptor;
+        self.l1.node_segment = node_segment;
+        self.l1.node_descriptor = node_descriptor;
+        self.l1.edge_segment = edge_segment;
+        self.l1.edge_descriptor = edge_descriptor;
+        self.l1.advise(self.mmap_advice);
         // Rebuild edge-type index eagerly — L0 segments it was built from
         // are replaced by L1 after compaction.
         *self.edge_type_index.lock().unwrap() = Some(self.build_edge_type_index());
     }
 
+    /// Set L2 segments after an L1 → L2 merge or on shard open.
+    pub fn set_l2_segments(
+        &mut self,
+        node_segment: Option<NodeSegmentV2>,
+        node_descriptor: Option<SegmentDescriptor>,
+        edge_segment: Option<EdgeSegmentV2>,
+        edge_descriptor: Option<SegmentDescriptor>,
+    ) {
+        self.l2.node_segment = node_segment;
+        self.l2.node_descriptor = node_descriptor;
+        self.l2.edge_segment = edge_segment;
+        self.l2.edge_descriptor = edge_descriptor;
+        self.l2.advise(self.mmap_advice);
+        *self.edge_type_index.lock().unwrap() = Some(self.build_edge_type_index());
+    }
+
     /// Set inverted indexes for L1 node segment (built during compaction).
     pub fn set_l1_indexes(
         &mut self,
@@ -699,24 +1016,36 @@ impl Shard {
         by_file_index: Option<InvertedIndex>,
         by_name_index: Option<InvertedIndex>,
     ) {
-        self.l1_by_type_index = by_type_index;
-        self.l1_by_file_index = by_file_index;
-        self.l1_by_name_index = by_name_index;
+        self.l1.by_type_index = by_type_index;
+        self.l1.by_file_index = by_file_index;
+        self.l1.by_name_index = by_name_index;
+    }
+
+    /// Set inverted indexes for L2 node segment.
+    pub fn set_l2_indexes(
+        &mut self,
+        by_type_index: Option<InvertedIndex>,
+        by_file_index: Option<InvertedIndex>,
+        by_name_index: Option<InvertedIndex>,
+    ) {
+        self.l2.by_type_index = by_type_index;
+        self.l2.by_file_index = by_file_index;
+        self.l2.by_name_index = by_name_index;
     }
 
     /// Get reference to L1 by_type inverted index (for query optimization).
     pub fn l1_by_type_index(&self) -> Option<&InvertedIndex> {
-        self.l1_by_type_index.as_ref()
+        self.l1.by_type_index.as_ref()
     }
 
     /// Get reference to L1 by_file inverted index (for query optimization).
     pub fn l1_by_file_index(&self) -> Option<&InvertedIndex> {
-        self.l1_by_file_index.as_ref()
+        self.l1.by_file_index.as_ref()
     }
 
     /// Get reference to L1 by_name inverted index (for query optimization).
     pub fn l1_by_name_index(&self) -> Option<&InvertedIndex> {
-        self.l1_by_name_index.as_ref()
+        self.l1.by_name_index.as_ref()
     }
 
     /// Clear L0 segments after compaction (they've been merged into L1).
@@ -727,6 +1056,7 @@ impl Shard {
         self.edge_segments.clear();
         self.edge_descriptors.clear();
         self.tombstones = TombstoneSet::new();
+        self.tombstoned_records = 0;
     }
 }
 
@@ -769,7 +1099,9 @@ impl Shard {
             let seg_id = node_segment_id
                 .expect("node_segment_id required when buffer has nodes");
 
-            let mut writer = NodeSegmentWriter::new();
+            let mut writer = NodeSegmentWriter::with_compression(self.compression)
+                .with_bloom_fp_rate(self.bloom_fp_rate)
+                .with_int_fields(self.int_fields.clone());
             for node in &nodes {
                 writer.add(node.clone());
             }
@@ -785,6 +1117,7 @@ impl Shard {
 
                 // Load the new segment immediately
                 let seg = NodeSegmentV2::open(&seg_path)?;
+                seg.advise(self.mmap_advice).ok();
                 let desc = build_descriptor(seg_id, SegmentType::Nodes, self.shard_id, &meta);
                 self.node_segments.push(seg);
                 self.node_descriptors.push(desc);
@@ -800,6 +1133,11 @@ impl Shard {
                 self.node_segments.push(seg);
                 self.node_descriptors.push(desc);
             }
+
+            // Build the name prefix index while the names are hot
+            if let Some(seg) = self.node_segments.last() {
+                seg.name_prefix_index();
+            }
         }
 
         // -- Flush edges ------------------------------------------------------
@@ -808,7 +1146,8 @@ impl Shard {
             let seg_id = edge_segment_id
                 .expect("edge_segment_id required when buffer has edges");
 
-            let mut writer = EdgeSegmentWriter::new();
+            let mut writer = EdgeSegmentWriter::with_compression(self.compression)
+                .with_bloom_fp_rate(self.bloom_fp_rate);
             for edge in &edges {
                 writer.add(edge.clone());
             }
@@ -822,6 +1161,7 @@ impl Shard {
                 result.edge_segment_path = Some(seg_path.clone());
 
                 let seg = EdgeSegmentV2::open(&seg_path)?;
+                seg.advise(self.mmap_advice).ok();
                 let desc = build_descriptor(seg_id, SegmentType::Edges, self.shard_id, &meta);
                 self.edge_segments.push(seg);
                 self.edge_descriptors.push(desc);
@@ -836,6 +1176,13 @@ impl Shard {
                 self.edge_segments.push(seg);
                 self.edge_descriptors.push(desc);
             }
+
+            // Build the declared metadata indexes while the metadata is hot
+            if let Some(seg) = self.edge_segments.last() {
+                for field in &self.edge_index_fields {
+                    seg.metadata_index(field);
+                }
+            }
         }
 
         Ok(Some(result))
@@ -854,7 +1201,12 @@ impl Shard {
         if self.tombstones.contains_node(id) {
             return None;
         }
+        self.get_node_including_tombstoned(id)
+    }
 
+    /// `get_node` without the tombstone check: the newest stored version
+    /// of a tombstoned node is still returned, until compaction drops it.
+    pub fn get_node_including_tombstoned(&self, id: u128) -> Option<NodeRecordV2> {
         // Step 1: Check write buffer (O(1) HashMap lookup)
         if let Some(node) = self.write_buffer.get_node(id) {
             return Some(node.clone());
@@ -877,12 +1229,12 @@ impl Shard {
             }
         }
 
-        // Step 3: Check L1 node segment (oldest, compacted)
-        if let Some(l1_seg) = &self.l1_node_segment {
-            if l1_seg.maybe_contains(id) {
-                for j in 0..l1_seg.record_count() {
-                    if l1_seg.get_id(j) == id {
-                        return Some(l1_seg.get_record(j));
+        // Step 3: Check compacted node segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.node_segment.as_ref()) {
+            if cseg.maybe_contains(id) {
+                for j in 0..cseg.record_count() {
+                    if cseg.get_id(j) == id {
+                        return Some(cseg.get_record(j));
                     }
                 }
             }
@@ -917,11 +1269,11 @@ impl Shard {
             }
         }
 
-        // L1 segment (oldest, compacted)
-        if let Some(l1_seg) = &self.l1_node_segment {
-            if l1_seg.maybe_contains(id) {
-                for j in 0..l1_seg.record_count() {
-                    if l1_seg.get_id(j) == id {
+        // Compacted segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.node_segment.as_ref()) {
+            if cseg.maybe_contains(id) {
+                for j in 0..cseg.record_count() {
+                    if cseg.get_id(j) == id {
                         return true;
                     }
                 }
@@ -956,7 +1308,9 @@ impl Shard {
     }
 
     /// Check if metadata matches all filter pairs.
-    fn metadata_matches(metadata: &str, filters: &[(String, String)]) -> bool {
+    ///
+    /// Metadata that is not valid JSON never matches a non-empty filter.
+    pub(crate) fn metadata_matches(metadata: &str, filters: &[(String, String)]) -> bool {
         if filters.is_empty() {
             return true;
         }
@@ -970,13 +1324,7 @@ impl Shard {
         for (key, value) in filters {
             match parsed.get(key) {
                 Some(v) => {
-                    let v_str = match v {
-                        serde_json::Value::String(s) => s.clone(),
-                        serde_json::Value::Bool(b) => b.to_string(),
-                        serde_json::Value::Number(n) => n.to_string(),
-                        other => other.to_string(),
-                    };
-                    if v_str != *value {
+                    if metadata_value_key(v) != *value {
                         return false;
                     }
                 }
@@ -987,7 +1335,8 @@ impl Shard {
     }
 
     /// Match node fields against AttrQuery-compatible filters.
-    fn matches_attr_filters(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn matches_attr_filters(
         node_type_value: &str,
         file_value: &str,
         name_value: &str,
@@ -1140,16 +1489,17 @@ impl Shard {
             }
         }
 
-        // Step 3: Scan L1 node segment (oldest, compacted)
-        if let (Some(l1_desc), Some(l1_seg)) =
-            (&self.l1_node_descriptor, &self.l1_node_segment)
-        {
+        // Step 3: Scan compacted node segments (L1, then L2)
+        for level in self.compacted_levels() {
+            let (Some(cdesc), Some(cseg)) = (&level.node_descriptor, &level.node_segment) else {
+                continue;
+            };
             // Zone map pruning at descriptor level
-            if l1_desc.may_contain(node_type, file, None) {
+            if cdesc.may_contain(node_type, file, None) {
                 // Try inverted index path: use by_type, by_file, or by_name index
-                // to avoid full L1 scan when a filter is specified.
-                let used_index = self.find_nodes_via_l1_index(
-                    l1_seg,
+                // to avoid full segment scan when a filter is specified.
+                let used_index = self.find_nodes_via_index(
+                    level,
                     node_type,
                     file,
                     name,
@@ -1158,14 +1508,14 @@ impl Shard {
                 );
 
                 if !used_index {
-                    // Fallback: full L1 scan (no applicable index)
+                    // Fallback: full segment scan (no applicable index)
                     let type_ok =
-                        node_type.map_or(true, |nt| l1_seg.contains_node_type(nt));
-                    let file_ok = file.map_or(true, |f| l1_seg.contains_file(f));
+                        node_type.is_none_or(|nt| cseg.contains_node_type(nt));
+                    let file_ok = file.is_none_or(|f| cseg.contains_file(f));
 
                     if type_ok && file_ok {
-                        for j in 0..l1_seg.record_count() {
-                            let id = l1_seg.get_id(j);
+                        for j in 0..cseg.record_count() {
+                            let id = cseg.get_id(j);
 
                             if seen_ids.contains(&id) {
                                 continue;
@@ -1177,23 +1527,23 @@ impl Shard {
                             }
 
                             if let Some(nt) = node_type {
-                                if l1_seg.get_node_type(j) != nt {
+                                if cseg.get_node_type(j) != nt {
                                     continue;
                                 }
                             }
                             if let Some(f) = file {
-                                if l1_seg.get_file(j) != f {
+                                if cseg.get_file(j) != f {
                                     continue;
                                 }
                             }
                             if let Some(n) = name {
-                                if l1_seg.get_name(j) != n {
+                                if cseg.get_name(j) != n {
                                     continue;
                                 }
                             }
 
                             seen_ids.insert(id);
-                            results.push(l1_seg.get_record(j));
+                            results.push(cseg.get_record(j));
                         }
                     }
                 }
@@ -1203,9 +1553,9 @@ impl Shard {
         results
     }
 
-    /// Try to use inverted index for L1 node lookup.
+    /// Try to use a compacted level's inverted index for node lookup.
     ///
-    /// Returns true if an index was used (caller should skip full L1 scan).
+    /// Returns true if an index was used (caller should skip full segment scan).
     /// Returns false if no applicable index exists (caller falls back to scan).
     ///
     /// Strategy (priority: type > file > name, type is most selective):
@@ -1213,17 +1563,20 @@ impl Shard {
     /// - If file filter is set and by_file index exists, use it
     /// - If name filter is set and by_name index exists, use it
     /// - Other filters are applied as post-filters on index results
-    fn find_nodes_via_l1_index(
+    fn find_nodes_via_index(
         &self,
-        l1_seg: &NodeSegmentV2,
+        level: &CompactedLevel,
         node_type: Option<&str>,
         file: Option<&str>,
         name: Option<&str>,
         seen_ids: &mut HashSet<u128>,
         results: &mut Vec<NodeRecordV2>,
     ) -> bool {
+        let Some(cseg) = &level.node_segment else {
+            return false;
+        };
         // Prefer by_type index when node_type filter is specified
-        if let (Some(nt), Some(by_type_idx)) = (node_type, &self.l1_by_type_index) {
+        if let (Some(nt), Some(by_type_idx)) = (node_type, &level.by_type_index) {
             let index_entries = by_type_idx.lookup(nt);
             for entry in index_entries {
                 if seen_ids.contains(&entry.node_id) {
@@ -1233,7 +1586,7 @@ impl Shard {
                     seen_ids.insert(entry.node_id);
                     continue;
                 }
-                let record = l1_seg.get_record(entry.offset as usize);
+                let record = cseg.get_record(entry.offset as usize);
                 if let Some(f) = file {
                     if record.file != f {
                         continue;
@@ -1251,7 +1604,7 @@ impl Shard {
         }
 
         // Fall back to by_file index when file filter is specified
-        if let (Some(f), Some(by_file_idx)) = (file, &self.l1_by_file_index) {
+        if let (Some(f), Some(by_file_idx)) = (file, &level.by_file_index) {
             let index_entries = by_file_idx.lookup(f);
             for entry in index_entries {
                 if seen_ids.contains(&entry.node_id) {
@@ -1261,7 +1614,7 @@ impl Shard {
                     seen_ids.insert(entry.node_id);
                     continue;
                 }
-                let record = l1_seg.get_record(entry.offset as usize);
+                let record = cseg.get_record(entry.offset as usize);
                 if let Some(n) = name {
                     if record.name != n {
                         continue;
@@ -1274,7 +1627,7 @@ impl Shard {
         }
 
         // Fall back to by_name index when only name filter is specified
-        if let (Some(n), Some(by_name_idx)) = (name, &self.l1_by_name_index) {
+        if let (Some(n), Some(by_name_idx)) = (name, &level.by_name_index) {
             let index_entries = by_name_idx.lookup(n);
             for entry in index_entries {
                 if seen_ids.contains(&entry.node_id) {
@@ -1284,7 +1637,7 @@ impl Shard {
                     seen_ids.insert(entry.node_id);
                     continue;
                 }
-                let record = l1_seg.get_record(entry.offset as usize);
+                let record = cseg.get_record(entry.offset as usize);
                 seen_ids.insert(record.id);
                 results.push(record);
             }
@@ -1297,7 +1650,7 @@ impl Shard {
     /// Find node IDs by exact node type, avoiding full record clones.
     ///
     /// Optimized hot path for `find_by_type(\"EXACT\")`.
-    /// Scans write buffer → L0 segments → L1 segment.
+    /// Scans write buffer → L0 segments → L1 segment → L2 segment.
     pub fn find_node_ids_by_type(&self, node_type: &str) -> Vec<u128> {
         let mut seen_ids: HashSet<u128> = HashSet::new();
         let mut results: Vec<u128> = Vec::new();
@@ -1324,6 +1677,9 @@ impl Shard {
             if !seg.contains_node_type(node_type) {
                 continue;
             }
+            let Some(code) = seg.node_type_code(node_type) else {
+                continue;
+            };
 
             for j in 0..seg.record_count() {
                 let id = seg.get_id(j);
@@ -1334,7 +1690,7 @@ impl Shard {
                     seen_ids.insert(id);
                     continue;
                 }
-                if seg.get_node_type(j) != node_type {
+                if seg.get_node_type_code(j) != code {
                     continue;
                 }
                 seen_ids.insert(id);
@@ -1342,19 +1698,23 @@ impl Shard {
             }
         }
 
-        // Step 3: L1 segment (oldest, compacted).
-        if let (Some(l1_desc), Some(l1_seg)) =
-            (&self.l1_node_descriptor, &self.l1_node_segment)
-        {
-            if !l1_desc.may_contain(Some(node_type), None, None) {
-                return results;
+        // Step 3: compacted segments (L1, then L2).
+        for level in self.compacted_levels() {
+            let (Some(cdesc), Some(cseg)) = (&level.node_descriptor, &level.node_segment) else {
+                continue;
+            };
+            if !cdesc.may_contain(Some(node_type), None, None) {
+                continue;
             }
-            if !l1_seg.contains_node_type(node_type) {
-                return results;
+            if !cseg.contains_node_type(node_type) {
+                continue;
             }
+            let Some(code) = cseg.node_type_code(node_type) else {
+                continue;
+            };
 
-            for j in 0..l1_seg.record_count() {
-                let id = l1_seg.get_id(j);
+            for j in 0..cseg.record_count() {
+                let id = cseg.get_id(j);
                 if seen_ids.contains(&id) {
                     continue;
                 }
@@ -1362,7 +1722,7 @@ impl Shard {
                     seen_ids.insert(id);
                     continue;
                 }
-                if l1_seg.get_node_type(j) != node_type {
+                if cseg.get_node_type_code(j) != code {
                     continue;
                 }
                 seen_ids.insert(id);
@@ -1400,7 +1760,7 @@ impl Shard {
 
     /// Iterate matching node IDs via callback, without materializing a Vec.
     ///
-    /// Same scan logic as `find_node_ids_by_attr` (write buffer → segments → L1),
+    /// Same scan logic as `find_node_ids_by_attr` (write buffer → segments → L1 → L2),
     /// but calls `emit(id)` instead of collecting. Return `false` from `emit` to
     /// stop iteration early.
     ///
@@ -1416,13 +1776,317 @@ impl Shard {
         substring_match: bool,
         emit: &mut dyn FnMut(u128) -> bool,
     ) -> bool {
+        self.for_each_matching_id_traced(
+            node_type, node_type_prefix, file, name,
+            exported, metadata_filters, substring_match,
+            emit, None,
+        )
+    }
+
+    /// Run the attribute search and record its plan for this shard:
+    /// access path per data source and survivors after each filter.
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain_attr_query(
+        &self,
+        shard_id: u16,
+        node_type: Option<&str>,
+        node_type_prefix: Option<&str>,
+        file: Option<&str>,
+        name: Option<&str>,
+        exported: Option<bool>,
+        metadata_filters: &[(String, String)],
+        substring_match: bool,
+    ) -> ShardPlan {
+        let mut plan = ShardPlan { shard_id, steps: Vec::new() };
+        self.for_each_matching_id_traced(
+            node_type, node_type_prefix, file, name,
+            exported, metadata_filters, substring_match,
+            &mut |_| true, Some(&mut plan),
+        );
+        plan
+    }
+
+    /// Node IDs whose integer metadata field `field` lies in `[min, max]`.
+    ///
+    /// Same source order and newest-wins dedup as `find_node_ids_by_attr`.
+    /// Segments whose zone map range for `field` cannot overlap are pruned:
+    /// only their id column is read, so their nodes still shadow older
+    /// versions, but no metadata is decoded.
+    pub fn find_node_ids_by_int_range(&self, field: &str, min: i64, max: i64) -> Vec<u128> {
+        let in_range = |metadata: &str| {
+            !metadata.is_empty()
+                && serde_json::from_str::<serde_json::Value>(metadata)
+                    .ok()
+                    .and_then(|v| v.get(field).and_then(|x| x.as_i64()))
+                    .is_some_and(|x| min <= x && x <= max)
+        };
+        let mut seen_ids: HashSet<u128> = HashSet::new();
+        let mut results = Vec::new();
+
+        for node in self.write_buffer.iter_nodes() {
+            seen_ids.insert(node.id);
+            if !self.tombstones.contains_node(node.id) && in_range(&node.metadata) {
+                results.push(node.id);
+            }
+        }
+
+        let compacted = self.compacted_levels()
+            .into_iter()
+            .filter_map(|level| level.node_segment.as_ref());
+        for seg in self.node_segments.iter().rev().chain(compacted) {
+            let scan = seg.may_contain_int_range(field, min, max);
+            if scan {
+                self.int_range_segment_scans.fetch_add(1, Ordering::Relaxed);
+            }
+            for j in 0..seg.record_count() {
+                let id = seg.get_id(j);
+                if !seen_ids.insert(id) || !scan {
+                    continue;
+                }
+                if !self.tombstones.contains_node(id) && in_range(seg.get_metadata(j)) {
+                    results.push(id);
+                }
+            }
+        }
+
+        results
+    }
+
+    /// `(id, content_hash)` of live nodes with a non-zero content hash,
+    /// optionally restricted to one node type.
+    ///
+    /// Same source order and newest-wins dedup as `find_node_ids_by_attr`;
+    /// reads only the id, type and content_hash columns of segments.
+    pub fn node_content_hashes(&self, node_type: Option<&str>) -> Vec<(u128, u64)> {
         let mut seen_ids: HashSet<u128> = HashSet::new();
+        let mut results = Vec::new();
+
+        for node in self.write_buffer.iter_nodes() {
+            seen_ids.insert(node.id);
+            if node.content_hash == 0
+                || self.tombstones.contains_node(node.id)
+                || node_type.is_some_and(|t| node.node_type != t)
+            {
+                continue;
+            }
+            results.push((node.id, node.content_hash));
+        }
+
+        let compacted = self.compacted_levels()
+            .into_iter()
+            .filter_map(|level| level.node_segment.as_ref());
+        for seg in self.node_segments.iter().rev().chain(compacted) {
+            // A segment without the type only shadows older versions
+            let type_code = match node_type {
+                Some(t) => match seg.node_type_code(t) {
+                    Some(code) => Some(code),
+                    None => {
+                        seen_ids.extend((0..seg.record_count()).map(|j| seg.get_id(j)));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            for j in 0..seg.record_count() {
+                let id = seg.get_id(j);
+                if !seen_ids.insert(id) || self.tombstones.contains_node(id) {
+                    continue;
+                }
+                if type_code.is_some_and(|code| seg.get_node_type_code(j) != code) {
+                    continue;
+                }
+                let hash = seg.get_content_hash(j);
+                if hash != 0 {
+                    results.push((id, hash));
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Live nodes whose name starts with `prefix`, compared
+    /// case-insensitively, optionally of one node type. Returns up to
+    /// `limit` `(lowercased name, id)` pairs in name order.
+    ///
+    /// Segment candidates come from each segment's name prefix index; every
+    /// candidate is then checked against its newest version, so renamed,
+    /// retyped and tombstoned nodes drop out.
+    pub fn find_node_ids_by_name_prefix(
+        &self,
+        prefix: &str,
+        node_type: Option<&str>,
+        limit: usize,
+    ) -> Vec<(String, u128)> {
+        let prefix_lower = prefix.to_lowercase();
+        let mut candidates: Vec<(String, u128)> = Vec::new();
+
+        for node in self.write_buffer.iter_nodes() {
+            let name = node.name.to_lowercase();
+            if !name.is_empty() && name.starts_with(&prefix_lower) {
+                candidates.push((name, node.id));
+            }
+        }
+
+        let compacted = self.compacted_levels()
+            .into_iter()
+            .filter_map(|level| level.node_segment.as_ref());
+        for seg in self.node_segments.iter().chain(compacted) {
+            for (name, row) in seg.name_prefix_index().lookup_prefix(&prefix_lower) {
+                candidates.push((name.to_string(), seg.get_id(row as usize)));
+            }
+        }
+
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut results = Vec::new();
+        for (name, id) in candidates {
+            if results.len() >= limit {
+                break;
+            }
+            let current = match self.get_node(id) {
+                Some(node) => node,
+                None => continue,
+            };
+            if current.name.to_lowercase() != name
+                || node_type.is_some_and(|t| current.node_type != t)
+            {
+                continue;
+            }
+            results.push((name, id));
+        }
+        results
+    }
+
+    /// Labels of the active attribute filters, in evaluation order.
+    fn attr_filter_stages(
+        node_type: Option<&str>,
+        node_type_prefix: Option<&str>,
+        file: Option<&str>,
+        name: Option<&str>,
+        exported: Option<bool>,
+        metadata_filters: &[(String, String)],
+    ) -> Vec<String> {
+        let mut stages = Vec::new();
+        if node_type.is_some() {
+            stages.push("node_type".to_string());
+        }
+        if node_type_prefix.is_some() {
+            stages.push("node_type_prefix".to_string());
+        }
+        if file.is_some() {
+            stages.push("file".to_string());
+        }
+        if name.is_some() {
+            stages.push("name".to_string());
+        }
+        if exported.is_some() {
+            stages.push("exported".to_string());
+        }
+        for (key, _) in metadata_filters {
+            stages.push(format!("metadata.{}", key));
+        }
+        stages
+    }
+
+    /// Count a candidate against each filter stage of `step`, stopping at
+    /// the first filter it fails. Stages match `attr_filter_stages`.
+    #[allow(clippy::too_many_arguments)]
+    fn trace_attr_filters(
+        step: &mut ScanStep,
+        values: (&str, &str, &str, &str),
+        node_type: Option<&str>,
+        node_type_prefix: Option<&str>,
+        file: Option<&str>,
+        name: Option<&str>,
+        exported: Option<bool>,
+        metadata_filters: &[(String, String)],
+        substring_match: bool,
+    ) {
+        let (nt_value, file_value, name_value, metadata_value) = values;
+        let only = |nt, prefix, f, n, exp, meta: &[(String, String)]| {
+            Self::matches_attr_filters(
+                nt_value, file_value, name_value, metadata_value,
+                nt, prefix, f, n, exp, meta, substring_match,
+            )
+        };
+        let mut checks: Vec<bool> = Vec::new();
+        if node_type.is_some() {
+            checks.push(only(node_type, None, None, None, None, &[]));
+        }
+        if node_type_prefix.is_some() {
+            checks.push(only(None, node_type_prefix, None, None, None, &[]));
+        }
+        if file.is_some() {
+            checks.push(only(None, None, file, None, None, &[]));
+        }
+        if name.is_some() {
+            checks.push(only(None, None, None, name, None, &[]));
+        }
+        if exported.is_some() {
+            checks.push(only(None, None, None, None, exported, &[]));
+        }
+        for filter in metadata_filters {
+            checks.push(Self::metadata_matches(metadata_value, std::slice::from_ref(filter)));
+        }
+
+        step.candidates += 1;
+        for (stage, passed) in step.filters.iter_mut().zip(checks) {
+            if !passed {
+                break;
+            }
+            stage.1 += 1;
+        }
+    }
+
+    /// `for_each_matching_id` with an optional plan trace. Tracing costs an
+    /// extra per-filter evaluation for every candidate, so it is only used
+    /// by `explain_attr_query`.
+    #[allow(clippy::too_many_arguments)]
+    fn for_each_matching_id_traced(
+        &self,
+        node_type: Option<&str>,
+        node_type_prefix: Option<&str>,
+        file: Option<&str>,
+        name: Option<&str>,
+        exported: Option<bool>,
+        metadata_filters: &[(String, String)],
+        substring_match: bool,
+        emit: &mut dyn FnMut(u128) -> bool,
+        mut trace: Option<&mut ShardPlan>,
+    ) -> bool {
+        let mut seen_ids: HashSet<u128> = HashSet::new();
+        let stages = if trace.is_some() {
+            Self::attr_filter_stages(
+                node_type, node_type_prefix, file, name, exported, metadata_filters,
+            )
+        } else {
+            Vec::new()
+        };
+        let traced = |trace: &mut Option<&mut ShardPlan>, values: (&str, &str, &str, &str)| {
+            if let Some(step) = trace.as_mut().and_then(|t| t.steps.last_mut()) {
+                Self::trace_attr_filters(
+                    step, values,
+                    node_type, node_type_prefix, file, name,
+                    exported, metadata_filters, substring_match,
+                );
+            }
+        };
+        let begin_step = |trace: &mut Option<&mut ShardPlan>, source: String, access: AccessPath| {
+            if let Some(t) = trace.as_mut() {
+                t.steps.push(ScanStep::new(source, access, &stages));
+            }
+        };
 
         // When substring matching, file-based zone map pruning must be skipped
         // because zone maps store exact file paths and can't evaluate substrings.
         let prune_file = if substring_match { None } else { file };
 
         // Step 1: Scan write buffer (authoritative, scanned first)
+        if self.write_buffer.node_count() > 0 {
+            begin_step(&mut trace, "write buffer".to_string(), AccessPath::BufferScan);
+        }
         for node in self.write_buffer.iter_nodes() {
             // Mark ALL buffer node IDs as seen so segment versions are shadowed,
             // even if the buffer version doesn't match the current filter.
@@ -1431,6 +2095,7 @@ impl Shard {
             if self.tombstones.contains_node(node.id) {
                 continue;
             }
+            traced(&mut trace, (&node.node_type, &node.file, &node.name, &node.metadata));
 
             if Self::matches_attr_filters(
                 &node.node_type,
@@ -1455,32 +2120,36 @@ impl Shard {
         for i in (0..self.node_segments.len()).rev() {
             let desc = &self.node_descriptors[i];
             let seg = &self.node_segments[i];
+            let source = format!("L0 segment {}", desc.segment_id);
 
             // Descriptor-level zone map pruning.
-            if let Some(nt) = node_type {
-                if !desc.may_contain(Some(nt), prune_file, None) {
-                    continue;
-                }
-            } else if !desc.may_contain(None, prune_file, None) {
-                continue;
-            }
+            let mut pruned = if let Some(nt) = node_type {
+                !desc.may_contain(Some(nt), prune_file, None)
+            } else {
+                !desc.may_contain(None, prune_file, None)
+            };
             if let Some(prefix) = node_type_prefix {
                 if !desc.node_types.is_empty() && !desc.node_types.iter().any(|t| t.starts_with(prefix)) {
-                    continue;
+                    pruned = true;
                 }
             }
 
             // Segment-level zone map pruning where exact checks are available.
             if let Some(nt) = node_type {
                 if !seg.contains_node_type(nt) {
-                    continue;
+                    pruned = true;
                 }
             }
             if let Some(f) = prune_file {
                 if !seg.contains_file(f) {
-                    continue;
+                    pruned = true;
                 }
             }
+            if pruned {
+                begin_step(&mut trace, source, AccessPath::Pruned);
+                continue;
+            }
+            begin_step(&mut trace, source, AccessPath::FullScan);
 
             for j in 0..seg.record_count() {
                 let id = seg.get_id(j);
@@ -1494,6 +2163,10 @@ impl Shard {
                     seen_ids.insert(id);
                     continue;
                 }
+                traced(
+                    &mut trace,
+                    (seg.get_node_type(j), seg.get_file(j), seg.get_name(j), seg.get_metadata(j)),
+                );
 
                 if !Self::matches_attr_filters(
                     seg.get_node_type(j),
@@ -1518,28 +2191,34 @@ impl Shard {
             }
         }
 
-        // Step 3: Scan L1 node segment (oldest, compacted)
-        if let (Some(l1_desc), Some(l1_seg)) =
-            (&self.l1_node_descriptor, &self.l1_node_segment)
-        {
+        // Step 3: Scan compacted node segments (L1, then L2)
+        for level in self.compacted_levels() {
+            let (Some(cdesc), Some(cseg)) = (&level.node_descriptor, &level.node_segment) else {
+                continue;
+            };
+            let source = format!("{} segment {}", level.label, cdesc.segment_id);
+
             // Descriptor-level zone map pruning.
-            if let Some(nt) = node_type {
-                if !l1_desc.may_contain(Some(nt), prune_file, None) {
-                    return true;
-                }
-            } else if !l1_desc.may_contain(None, prune_file, None) {
-                return true;
-            }
+            let mut pruned = if let Some(nt) = node_type {
+                !cdesc.may_contain(Some(nt), prune_file, None)
+            } else {
+                !cdesc.may_contain(None, prune_file, None)
+            };
             if let Some(prefix) = node_type_prefix {
-                if !l1_desc.node_types.is_empty() && !l1_desc.node_types.iter().any(|t| t.starts_with(prefix)) {
-                    return true;
+                if !cdesc.node_types.is_empty() && !cdesc.node_types.iter().any(|t| t.starts_with(prefix)) {
+                    pruned = true;
                 }
             }
+            if pruned {
+                begin_step(&mut trace, source, AccessPath::Pruned);
+                continue;
+            }
 
-            // Try L1 by_name index when name filter is set (avoids full L1 scan).
+            // Try the by_name index when name filter is set (avoids full scan).
             let mut used_index = false;
-            if let (Some(n), Some(by_name_idx)) = (name, &self.l1_by_name_index) {
+            if let (Some(n), Some(by_name_idx)) = (name, &level.by_name_index) {
                 if !substring_match {
+                    begin_step(&mut trace, source.clone(), AccessPath::NameIndex);
                     let index_entries = by_name_idx.lookup(n);
                     for entry in index_entries {
                         if seen_ids.contains(&entry.node_id) {
@@ -1549,12 +2228,22 @@ impl Shard {
                             seen_ids.insert(entry.node_id);
                             continue;
                         }
+                        let offset = entry.offset as usize;
+                        traced(
+                            &mut trace,
+                            (
+                                cseg.get_node_type(offset),
+                                cseg.get_file(offset),
+                                cseg.get_name(offset),
+                                cseg.get_metadata(offset),
+                            ),
+                        );
                         // Post-filter by other attr filters
                         if !Self::matches_attr_filters(
-                            l1_seg.get_node_type(entry.offset as usize),
-                            l1_seg.get_file(entry.offset as usize),
-                            l1_seg.get_name(entry.offset as usize),
-                            l1_seg.get_metadata(entry.offset as usize),
+                            cseg.get_node_type(offset),
+                            cseg.get_file(offset),
+                            cseg.get_name(offset),
+                            cseg.get_metadata(offset),
                             node_type,
                             node_type_prefix,
                             file,
@@ -1577,18 +2266,21 @@ impl Shard {
             if !used_index {
                 // Segment-level zone map pruning.
                 if let Some(nt) = node_type {
-                    if !l1_seg.contains_node_type(nt) {
-                        return true;
+                    if !cseg.contains_node_type(nt) {
+                        begin_step(&mut trace, source, AccessPath::Pruned);
+                        continue;
                     }
                 }
                 if let Some(f) = prune_file {
-                    if !l1_seg.contains_file(f) {
-                        return true;
+                    if !cseg.contains_file(f) {
+                        begin_step(&mut trace, source, AccessPath::Pruned);
+                        continue;
                     }
                 }
+                begin_step(&mut trace, source, AccessPath::FullScan);
 
-                for j in 0..l1_seg.record_count() {
-                    let id = l1_seg.get_id(j);
+                for j in 0..cseg.record_count() {
+                    let id = cseg.get_id(j);
                     if seen_ids.contains(&id) {
                         continue;
                     }
@@ -1596,11 +2288,20 @@ impl Shard {
                         seen_ids.insert(id);
                         continue;
                     }
+                    traced(
+                        &mut trace,
+                        (
+                            cseg.get_node_type(j),
+                            cseg.get_file(j),
+                            cseg.get_name(j),
+                            cseg.get_metadata(j),
+                        ),
+                    );
                     if !Self::matches_attr_filters(
-                        l1_seg.get_node_type(j),
-                        l1_seg.get_file(j),
-                        l1_seg.get_name(j),
-                        l1_seg.get_metadata(j),
+                        cseg.get_node_type(j),
+                        cseg.get_file(j),
+                        cseg.get_name(j),
+                        cseg.get_metadata(j),
                         node_type,
                         node_type_prefix,
                         file,
@@ -1634,7 +2335,7 @@ impl Shard {
         edge_types: Option<&[&str]>,
     ) -> Vec<EdgeRecordV2> {
         let mut results: Vec<EdgeRecordV2> = Vec::new();
-        // Track seen edge keys for dedup across L0 and L1
+        // Track seen edge keys for dedup across L0, L1 and L2
         let mut seen_edge_keys: HashSet<(u128, u128, String)> = HashSet::new();
 
         // Step 1: Scan write buffer (authoritative, newest)
@@ -1697,19 +2398,19 @@ impl Shard {
             }
         }
 
-        // Step 3: Scan L1 edge segment (oldest, compacted)
-        if let Some(l1_seg) = &self.l1_edge_segment {
-            if l1_seg.maybe_contains_src(node_id) {
+        // Step 3: Scan compacted edge segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.edge_segment.as_ref()) {
+            if cseg.maybe_contains_src(node_id) {
                 let type_ok = edge_types.map_or(true, |types| {
-                    types.iter().any(|t| l1_seg.contains_edge_type(t))
+                    types.iter().any(|t| cseg.contains_edge_type(t))
                 });
                 if type_ok {
-                    for j in 0..l1_seg.record_count() {
-                        if l1_seg.get_src(j) != node_id {
+                    for j in 0..cseg.record_count() {
+                        if cseg.get_src(j) != node_id {
                             continue;
                         }
-                        let dst = l1_seg.get_dst(j);
-                        let edge_type = l1_seg.get_edge_type(j);
+                        let dst = cseg.get_dst(j);
+                        let edge_type = cseg.get_edge_type(j);
                         let key = (node_id, dst, edge_type.to_string());
 
                         if seen_edge_keys.contains(&key) {
@@ -1725,7 +2426,7 @@ impl Shard {
                                 continue;
                             }
                         }
-                        results.push(l1_seg.get_record(j));
+                        results.push(cseg.get_record(j));
                     }
                 }
             }
@@ -1742,7 +2443,7 @@ impl Shard {
         edge_types: Option<&[&str]>,
     ) -> Vec<EdgeRecordV2> {
         let mut results: Vec<EdgeRecordV2> = Vec::new();
-        // Track seen edge keys for dedup across L0 and L1
+        // Track seen edge keys for dedup across L0, L1 and L2
         let mut seen_edge_keys: HashSet<(u128, u128, String)> = HashSet::new();
 
         // Step 1: Scan write buffer (authoritative, newest)
@@ -1805,19 +2506,19 @@ impl Shard {
             }
         }
 
-        // Step 3: Scan L1 edge segment (oldest, compacted)
-        if let Some(l1_seg) = &self.l1_edge_segment {
-            if l1_seg.maybe_contains_dst(node_id) {
+        // Step 3: Scan compacted edge segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.edge_segment.as_ref()) {
+            if cseg.maybe_contains_dst(node_id) {
                 let type_ok = edge_types.map_or(true, |types| {
-                    types.iter().any(|t| l1_seg.contains_edge_type(t))
+                    types.iter().any(|t| cseg.contains_edge_type(t))
                 });
                 if type_ok {
-                    for j in 0..l1_seg.record_count() {
-                        if l1_seg.get_dst(j) != node_id {
+                    for j in 0..cseg.record_count() {
+                        if cseg.get_dst(j) != node_id {
                             continue;
                         }
-                        let src = l1_seg.get_src(j);
-                        let edge_type = l1_seg.get_edge_type(j);
+                        let src = cseg.get_src(j);
+                        let edge_type = cseg.get_edge_type(j);
                         let key = (src, node_id, edge_type.to_string());
 
                         if seen_edge_keys.contains(&key) {
@@ -1833,7 +2534,7 @@ impl Shard {
                                 continue;
                             }
                         }
-                        results.push(l1_seg.get_record(j));
+                        results.push(cseg.get_record(j));
                     }
                 }
             }
@@ -1842,7 +2543,48 @@ impl Shard {
         results
     }
 
-    /// Iterate all edges across write buffer + L0 segments + L1 segment.
+    /// Live edges into `dst` with the given `(src, edge_type)` keys, as
+    /// listed by the reverse edge index. Newest version wins; keys with no
+    /// live edge (stale index entries) are skipped.
+    ///
+    /// Unlike `get_incoming_edges` no key is allocated per row, and the
+    /// search stops as soon as every key has been found.
+    pub fn get_incoming_edges_by_keys(&self, dst: u128, keys: &[(u128, &str)]) -> Vec<EdgeRecordV2> {
+        let mut pending: HashSet<(u128, &str)> = keys.iter().copied().collect();
+        let mut results = Vec::with_capacity(keys.len());
+        let check_tombstones = !self.tombstones.edge_keys.is_empty();
+        let live = |src: u128, edge_type: &str| {
+            !check_tombstones || !self.tombstones.contains_edge(src, dst, edge_type)
+        };
+
+        for edge in self.write_buffer.find_edges_by_dst(dst) {
+            if pending.remove(&(edge.src, edge.edge_type.as_str())) && live(edge.src, &edge.edge_type) {
+                results.push(edge.clone());
+            }
+        }
+
+        let compacted = self.compacted_levels().into_iter().filter_map(|l| l.edge_segment.as_ref());
+        for seg in self.edge_segments.iter().rev().chain(compacted) {
+            if pending.is_empty() {
+                break;
+            }
+            if !seg.maybe_contains_dst(dst) {
+                continue;
+            }
+            for j in 0..seg.record_count() {
+                if seg.get_dst(j) != dst {
+                    continue;
+                }
+                let (src, edge_type) = (seg.get_src(j), seg.get_edge_type(j));
+                if pending.remove(&(src, edge_type)) && live(src, edge_type) {
+                    results.push(seg.get_record(j));
+                }
+            }
+        }
+        results
+    }
+
+    /// Iterate all edges across write buffer + L0 segments + L1/L2 segments.
     /// Deduplicates by (src, dst, edge_type) key — newest version wins.
     /// Skips tombstoned edges.
     pub fn iter_all_edges(&self) -> Vec<EdgeRecordV2> {
@@ -1879,12 +2621,12 @@ impl Shard {
             }
         }
 
-        // Step 3: L1 edge segment (oldest, compacted)
-        if let Some(l1_seg) = &self.l1_edge_segment {
-            for j in 0..l1_seg.record_count() {
-                let src = l1_seg.get_src(j);
-                let dst = l1_seg.get_dst(j);
-                let edge_type = l1_seg.get_edge_type(j);
+        // Step 3: compacted edge segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.edge_segment.as_ref()) {
+            for j in 0..cseg.record_count() {
+                let src = cseg.get_src(j);
+                let dst = cseg.get_dst(j);
+                let edge_type = cseg.get_edge_type(j);
                 let key = (src, dst, edge_type.to_string());
 
                 if seen_edge_keys.contains(&key) {
@@ -1895,17 +2637,120 @@ impl Shard {
                 if self.tombstones.contains_edge(src, dst, edge_type) {
                     continue;
                 }
-                results.push(l1_seg.get_record(j));
+                results.push(cseg.get_record(j));
+            }
+        }
+
+        results
+    }
+
+    /// IDs of the edge segments this shard reads (L0, L1 and L2).
+    pub fn edge_segment_ids(&self) -> Vec<u64> {
+        self.edge_segments_with_ids().map(|(id, _)| id).collect()
+    }
+
+    /// `(src, dst, edge_type)` of every edge row not hidden by a tombstone
+    /// in the edge segments whose ID is not in `skip`. Rows superseded by a
+    /// newer version are included; write-buffer edges are not.
+    pub fn segment_edge_keys(&self, skip: &HashSet<u64>) -> Vec<(u128, u128, String)> {
+        let mut keys = Vec::new();
+        for (_, seg) in self.edge_segments_with_ids().filter(|(id, _)| !skip.contains(id)) {
+            for j in 0..seg.record_count() {
+                let (src, dst, edge_type) = (seg.get_src(j), seg.get_dst(j), seg.get_edge_type(j));
+                if !self.tombstones.contains_edge(src, dst, edge_type) {
+                    keys.push((src, dst, edge_type.to_string()));
+                }
+            }
+        }
+        keys
+    }
+
+    fn edge_segments_with_ids(&self) -> impl Iterator<Item = (u64, &EdgeSegmentV2)> {
+        let l0 = self.edge_descriptors.iter().map(|d| d.segment_id).zip(&self.edge_segments);
+        let compacted = self.compacted_levels().into_iter().filter_map(|level| {
+            Some((level.edge_descriptor.as_ref()?.segment_id, level.edge_segment.as_ref()?))
+        });
+        l0.chain(compacted)
+    }
+
+    /// Live edges whose metadata field `field` renders as `value`,
+    /// optionally of one edge type.
+    ///
+    /// Segments are searched through their metadata index when `field` is
+    /// in `edge_index_fields`, otherwise row by row (counted in
+    /// `edge_metadata_segment_scans`). Newest version wins: a segment match
+    /// is dropped when the write buffer or a newer segment holds the same
+    /// edge key, whatever its metadata there; newer segments are probed
+    /// by source id (bloom filter, then their source order), never scanned.
+    pub fn find_edges_by_metadata(
+        &self,
+        field: &str,
+        value: &str,
+        edge_type: Option<&str>,
+    ) -> Vec<EdgeRecordV2> {
+        let filter = [(field.to_string(), value.to_string())];
+        let type_ok = |t: &str| edge_type.is_none_or(|wanted| wanted == t);
+        let mut results = Vec::new();
+
+        // Step 1: Write buffer (authoritative, newest)
+        let mut buffered: HashSet<(u128, u128, &str)> = HashSet::new();
+        for edge in self.write_buffer.iter_edges() {
+            buffered.insert((edge.src, edge.dst, edge.edge_type.as_str()));
+            if type_ok(&edge.edge_type)
+                && !self.tombstones.contains_edge(edge.src, edge.dst, &edge.edge_type)
+                && Self::metadata_matches(&edge.metadata, &filter)
+            {
+                results.push(edge.clone());
+            }
+        }
+
+        // Step 2: L0 segments newest first, then L1 and L2
+        let indexed = self.edge_index_fields.iter().any(|f| f == field);
+        let compacted = self.compacted_levels()
+            .into_iter()
+            .filter_map(|level| level.edge_segment.as_ref());
+        let segments: Vec<&EdgeSegmentV2> = self.edge_segments.iter().rev().chain(compacted).collect();
+        for (pos, seg) in segments.iter().enumerate() {
+            if edge_type.is_some_and(|t| !seg.contains_edge_type(t)) {
+                continue;
+            }
+            let rows: Vec<u32> = if indexed {
+                seg.metadata_index(field).lookup(value).to_vec()
+            } else {
+                self.edge_metadata_segment_scans.fetch_add(1, Ordering::Relaxed);
+                (0..seg.record_count())
+                    .filter(|&j| Self::metadata_matches(seg.get_metadata(j), &filter))
+                    .map(|j| j as u32)
+                    .collect()
+            };
+            for row in rows {
+                let j = row as usize;
+                let (src, dst, et) = (seg.get_src(j), seg.get_dst(j), seg.get_edge_type(j));
+                if !type_ok(et)
+                    || self.tombstones.contains_edge(src, dst, et)
+                    || buffered.contains(&(src, dst, et))
+                    || segments[..pos].iter().any(|newer| Self::segment_has_edge(newer, src, dst, et))
+                {
+                    continue;
+                }
+                results.push(seg.get_record(j));
             }
         }
 
         results
     }
 
+    /// Whether `seg` holds a record with edge key `(src, dst, edge_type)`.
+    /// Looked up through the segment's source order, not a row scan.
+    fn segment_has_edge(seg: &EdgeSegmentV2, src: u128, dst: u128, edge_type: &str) -> bool {
+        seg.maybe_contains_src(src)
+            && seg.rows_for_src(src).any(|j| seg.get_dst(j) == dst && seg.get_edge_type(j) == edge_type)
+    }
+
     /// Get edges filtered by edge type, using the lazy edge-type index.
     ///
     /// On first call, builds an in-memory index from all edges (write buffer +
-    /// L0 segments + L1 segment), grouped by edge type. Subsequent calls reuse
+    /// L0 segments + L1/L2 segments), grouped by edge type. Subsequent calls reuse
     /// the cached index until invalidated by `upsert_edges()` or `flush_with_ids()`.
     pub fn get_edges_by_type(&self, edge_type: &str) -> Vec<EdgeRecordV2> {
         let mut guard = self.edge_type_index.lock().unwrap();
@@ -1968,12 +2813,12 @@ impl Shard {
             }
         }
 
-        // Step 3: L1 edge segment (oldest, compacted)
-        if let Some(l1_seg) = &self.l1_edge_segment {
-            for j in 0..l1_seg.record_count() {
-                let src = l1_seg.get_src(j);
-                let dst = l1_seg.get_dst(j);
-                let et = l1_seg.get_edge_type(j);
+        // Step 3: compacted edge segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.edge_segment.as_ref()) {
+            for j in 0..cseg.record_count() {
+                let src = cseg.get_src(j);
+                let dst = cseg.get_dst(j);
+                let et = cseg.get_edge_type(j);
                 let key = (src, dst, et.to_string());
 
                 if seen_edge_keys.contains(&key) {
@@ -1998,27 +2843,30 @@ impl Shard {
 // -- Stats --------------------------------------------------------------------
 
 impl Shard {
-    /// Total node count (write buffer + all node segments + L1).
+    /// Total node count (write buffer + all node segments + L1 + L2).
     /// Note: may overcount if same node ID exists in multiple segments
     /// (exact count requires dedup scan). For stats purposes only.
     pub fn node_count(&self) -> usize {
         let l0_count: usize = self.node_segments.iter().map(|s| s.record_count()).sum();
-        let l1_count = self.l1_node_segment.as_ref().map_or(0, |s| s.record_count());
-        self.write_buffer.node_count() + l0_count + l1_count
+        let compacted_count = self.l1.node_records() + self.l2.node_records();
+        self.write_buffer.node_count() + l0_count + compacted_count
     }
 
-    /// Total edge count (write buffer + all edge segments + L1).
+    /// Total edge count (write buffer + all edge segments + L1 + L2).
     pub fn edge_count(&self) -> usize {
         let l0_count: usize = self.edge_segments.iter().map(|s| s.record_count()).sum();
-        let l1_count = self.l1_edge_segment.as_ref().map_or(0, |s| s.record_count());
-        self.write_buffer.edge_count() + l0_count + l1_count
+        let compacted_count = self.l1.edge_records() + self.l2.edge_records();
+        self.write_buffer.edge_count() + l0_count + compacted_count
     }
 
     /// Number of loaded segments: (node_segments, edge_segments).
-    /// Includes L1 segments in the count.
+    /// Includes L1 and L2 segments in the count.
     pub fn segment_count(&self) -> (usize, usize) {
-        let node_count = self.node_segments.len() + if self.l1_node_segment.is_some() { 1 } else { 0 };
-        let edge_count = self.edge_segments.len() + if self.l1_edge_segment.is_some() { 1 } else { 0 };
+        let levels = self.compacted_levels();
+        let node_count = self.node_segments.len()
+            + levels.iter().filter(|l| l.node_segment.is_some()).count();
+        let edge_count = self.edge_segments.len()
+            + levels.iter().filter(|l| l.edge_segment.is_some()).count();
         (node_count, edge_count)
     }
 
@@ -2027,6 +2875,13 @@ impl Shard {
         (self.write_buffer.node_count(), self.write_buffer.edge_count())
     }
 
+    /// Whether the write buffer holds a node of `node_types` or an edge of
+    /// `edge_types`.
+    pub fn write_buffer_holds(&self, node_types: &[String], edge_types: &[String]) -> bool {
+        node_types.iter().any(|t| !self.write_buffer.find_nodes_by_type(t).is_empty())
+            || edge_types.iter().any(|t| !self.write_buffer.find_edges_by_type(t).is_empty())
+    }
+
     /// Collect full diagnostic snapshot of this shard's lifecycle state.
     pub fn diagnostics(&self, shard_id: u16) -> ShardDiagnostics {
         let (wb_nodes, wb_edges) = self.write_buffer_size();
@@ -2041,16 +2896,19 @@ impl Shard {
             compacted: self.has_l1(),
             l0_node_segment_count: self.l0_node_segment_count(),
             l0_edge_segment_count: self.l0_edge_segment_count(),
-            l1_node_records: self.l1_node_segment.as_ref().map_or(0, |s| s.record_count()),
-            l1_edge_records: self.l1_edge_segment.as_ref().map_or(0, |s| s.record_count()),
+            l1_node_records: self.l1.node_records(),
+            l1_edge_records: self.l1.edge_records(),
+            l2_node_records: self.l2.node_records(),
+            l2_edge_records: self.l2.edge_records(),
             tombstone_node_count: self.tombstones.node_count(),
             tombstone_edge_count: self.tombstones.edge_count(),
-            has_l1_by_type: self.l1_by_type_index.is_some(),
-            has_l1_by_file: self.l1_by_file_index.is_some(),
-            has_l1_by_name: self.l1_by_name_index.is_some(),
-            l1_by_type_keys: self.l1_by_type_index.as_ref().map_or(0, |i| i.key_count()),
-            l1_by_file_keys: self.l1_by_file_index.as_ref().map_or(0, |i| i.key_count()),
-            l1_by_name_keys: self.l1_by_name_index.as_ref().map_or(0, |i| i.key_count()),
+            tombstoned_records: self.tombstoned_records,
+            has_l1_by_type: self.l1.by_type_index.is_some(),
+            has_l1_by_file: self.l1.by_file_index.is_some(),
+            has_l1_by_name: self.l1.by_name_index.is_some(),
+            l1_by_type_keys: self.l1.by_type_index.as_ref().map_or(0, |i| i.key_count()),
+            l1_by_file_keys: self.l1.by_file_index.as_ref().map_or(0, |i| i.key_count()),
+            l1_by_name_keys: self.l1.by_name_index.as_ref().map_or(0, |i| i.key_count()),
             has_edge_type_index,
         }
     }
@@ -2096,10 +2954,10 @@ impl Shard {
             }
         }
 
-        // L1 segment (oldest, compacted)
-        if let Some(l1_seg) = &self.l1_node_segment {
-            for j in 0..l1_seg.record_count() {
-                let id = l1_seg.get_id(j);
+        // Compacted segments (L1, then L2)
+        for cseg in self.compacted_levels().into_iter().filter_map(|l| l.node_segment.as_ref()) {
+            for j in 0..cseg.record_count() {
+                let id = cseg.get_id(j);
                 if seen.contains(&id) {
                     continue;
                 }
@@ -2117,7 +2975,7 @@ impl Shard {
     /// Count nodes by type without loading full records.
     ///
     /// Uses write buffer type iteration + L0 segment columnar scan +
-    /// L1 segment columnar scan for type counts.
+    /// L1/L2 segment columnar scan for type counts.
     /// Deduplicates by node ID (write buffer wins, newest segment wins).
     /// Skips tombstoned nodes.
     pub fn count_by_type(&self) -> HashMap<String, usize> {
@@ -2133,9 +2991,14 @@ impl Shard {
             *counts.entry(node.node_type.clone()).or_insert(0) += 1;
         }
 
-        // Step 2: L0 segments (newest-to-oldest)
-        for i in (0..self.node_segments.len()).rev() {
-            let seg = &self.node_segments[i];
+        // Step 2: L0 segments (newest-to-oldest), then compacted segments
+        // (L1, then L2). Tally by segment-local type code and resolve each
+        // code to its string once per segment.
+        let segments = self.node_segments.iter().rev().chain(
+            self.compacted_levels().into_iter().filter_map(|l| l.node_segment.as_ref()),
+        );
+        for seg in segments {
+            let mut by_code: HashMap<u32, usize> = HashMap::new();
             for j in 0..seg.record_count() {
                 let id = seg.get_id(j);
                 if seen_ids.contains(&id) {
@@ -2145,22 +3008,10 @@ impl Shard {
                 if self.tombstones.contains_node(id) {
                     continue;
                 }
-                *counts.entry(seg.get_node_type(j).to_string()).or_insert(0) += 1;
+                *by_code.entry(seg.get_node_type_code(j)).or_insert(0) += 1;
             }
-        }
-
-        // Step 3: L1 segment
-        if let Some(l1_seg) = &self.l1_node_segment {
-            for j in 0..l1_seg.record_count() {
-                let id = l1_seg.get_id(j);
-                if seen_ids.contains(&id) {
-                    continue;
-                }
-                seen_ids.insert(id);
-                if self.tombstones.contains_node(id) {
-                    continue;
-                }
-                *counts.entry(l1_seg.get_node_type(j).to_string()).or_insert(0) += 1;
+            for (code, count) in by_code {
+                *counts.entry(seg.node_type_for_code(code).to_string()).or_insert(0) += count;
             }
         }
 
@@ -2197,6 +3048,7 @@ fn build_descriptor(
         node_types: meta.node_types.clone(),
         file_paths: meta.file_paths.clone(),
         edge_types: meta.edge_types.clone(),
+        compression: meta.compression,
     }
 }
 
@@ -2598,6 +3450,42 @@ mod tests {
         assert_eq!(incoming_imports.len(), 1);
     }
 
+    #[test]
+    fn test_incoming_edges_by_keys_newest_version_wins() {
+        let mut shard = Shard::ephemeral();
+        let with_metadata = |src: &str, metadata: &str| EdgeRecordV2 {
+            metadata: metadata.to_string(),
+            ..make_edge(src, "dst1", "CALLS")
+        };
+        shard.upsert_edges(vec![with_metadata("src1", "old"), with_metadata("src2", "old")]);
+        shard.flush_with_ids(None, Some(1)).unwrap();
+        shard.upsert_edges(vec![with_metadata("src1", "new")]);
+        shard.flush_with_ids(None, Some(2)).unwrap();
+        shard.upsert_edges(vec![with_metadata("src3", "buffered")]);
+
+        let mut ts = TombstoneSet::new();
+        ts.add_edges(vec![(node_id("src2"), node_id("dst1"), "CALLS".to_string())]);
+        shard.set_tombstones(ts);
+
+        // src2 is tombstoned and src4 a stale key with no edge
+        let keys: Vec<(u128, &str)> = ["src1", "src2", "src3", "src4"]
+            .iter()
+            .map(|src| (node_id(src), "CALLS"))
+            .collect();
+        let mut found: Vec<(u128, String)> = shard
+            .get_incoming_edges_by_keys(node_id("dst1"), &keys)
+            .into_iter()
+            .map(|e| (e.src, e.metadata))
+            .collect();
+        found.sort_unstable();
+        let mut expected = vec![
+            (node_id("src1"), "new".to_string()),
+            (node_id("src3"), "buffered".to_string()),
+        ];
+        expected.sort_unstable();
+        assert_eq!(found, expected);
+    }
+
     #[test]
     fn test_edges_across_buffer_and_segments() {
         let mut shard = Shard::ephemeral();
@@ -2846,6 +3734,7 @@ mod tests {
             node_types: node_meta.node_types,
             file_paths: node_meta.file_paths,
             edge_types: node_meta.edge_types,
+            compression: node_meta.compression,
         };
         let edge_desc = SegmentDescriptor {
             segment_id: 2,
@@ -2856,6 +3745,7 @@ mod tests {
             node_types: edge_meta.node_types,
             file_paths: edge_meta.file_paths,
             edge_types: edge_meta.edge_types,
+            compression: edge_meta.compression,
         };
 
         // Phase 3: Open shard with descriptors
@@ -3348,6 +4238,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_malformed_metadata_never_matches_filters() {
+        let mut shard = Shard::ephemeral();
+        let mut bad = make_node("bad", "FUNCTION", "bad", "f.js");
+        bad.metadata = "{not json".to_string();
+        let mut good = make_node("good", "FUNCTION", "good", "f.js");
+        good.metadata = r#"{"async":true}"#.to_string();
+        shard.add_nodes(vec![bad.clone(), good.clone()]);
+
+        let filter = vec![("async".to_string(), "true".to_string())];
+        for _ in 0..2 {
+            assert_eq!(shard.find_node_ids_by_attr(None, None, None, None, None, &filter, false), vec![good.id]);
+            assert_eq!(shard.find_node_ids_by_attr(Some("FUNCTION"), None, None, None, Some(true), &[], false), vec![]);
+            let mut all = shard.find_node_ids_by_attr(None, None, None, None, None, &[], false);
+            all.sort();
+            let mut expected = vec![bad.id, good.id];
+            expected.sort();
+            assert_eq!(all, expected);
+            shard.flush_with_ids(Some(1), None).unwrap();
+        }
+    }
+
     #[test]
     fn test_for_each_matching_id_early_stop() {
         let mut shard = Shard::ephemeral();
@@ -3369,4 +4281,62 @@ mod tests {
         assert!(!completed, "Should have stopped early");
         assert_eq!(collected.len(), 2, "Should have collected exactly 2 IDs");
     }
+
+    #[test]
+    fn test_count_by_type_codes_match_string_path() {
+        let mut shard = Shard::ephemeral();
+        let types = ["FUNCTION", "CLASS", "METHOD"];
+        let records: Vec<NodeRecordV2> = (0..30)
+            .map(|i| make_node(&format!("n{}", i), types[i % 3], "x", "f.rs"))
+            .collect();
+        shard.add_nodes(records.clone());
+        shard.flush_with_ids(Some(1), None).unwrap();
+
+        // Newer segment re-types some nodes; its codes differ from the first
+        let retyped: Vec<NodeRecordV2> = (0..10)
+            .map(|i| make_node(&format!("n{}", i), "http:route", "x", "f.rs"))
+            .collect();
+        shard.add_nodes(retyped);
+        shard.flush_with_ids(Some(2), None).unwrap();
+        shard.add_nodes(vec![make_node("n29", "MODULE", "x", "f.rs")]);
+
+        let mut expected: HashMap<String, usize> = HashMap::new();
+        for r in &records {
+            let node = shard.get_node(r.id).unwrap();
+            *expected.entry(node.node_type).or_insert(0) += 1;
+        }
+        assert_eq!(shard.count_by_type(), expected);
+    }
+
+    #[test]
+    fn test_name_prefix_lookup_follows_newest_version() {
+        let mut shard = Shard::ephemeral();
+        let get = make_node("get", "FUNCTION", "handleGet", "f.rs");
+        let post = make_node("post", "FUNCTION", "handlePost", "f.rs");
+        let helper = make_node("helper", "FUNCTION", "helper", "f.rs");
+        shard.add_nodes(vec![get.clone(), post.clone(), helper.clone()]);
+        shard.flush_with_ids(Some(1), None).unwrap();
+
+        let ids = |shard: &Shard, prefix: &str| -> Vec<u128> {
+            shard.find_node_ids_by_name_prefix(prefix, None, 10)
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect()
+        };
+        assert_eq!(ids(&shard, "Handle"), vec![get.id, post.id]);
+
+        // Renamed in a newer segment: the old segment's index entry is stale
+        shard.add_nodes(vec![make_node("post", "FUNCTION", "submit", "f.rs")]);
+        shard.flush_with_ids(Some(2), None).unwrap();
+        assert_eq!(ids(&shard, "handle"), vec![get.id]);
+        assert_eq!(ids(&shard, "sub"), vec![post.id]);
+
+        // Write buffer version shadows segments, too
+        shard.add_nodes(vec![make_node("helper", "CLASS", "handleAll", "f.rs")]);
+        assert_eq!(ids(&shard, "handle"), vec![helper.id, get.id]);
+        assert!(shard.find_node_ids_by_name_prefix("handle", Some("CLASS"), 10)
+            .iter()
+            .all(|(_, id)| *id == helper.id));
+        assert_eq!(shard.find_node_ids_by_name_prefix("handle", None, 1).len(), 1);
+    }
 }
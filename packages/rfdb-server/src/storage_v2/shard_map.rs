@@ -0,0 +1,116 @@
+//! Node ID -> shard ID routing map for `MultiShardStore`.
+//!
+//! Unbounded by default, so every node routes in O(1). With a capacity the
+//! map evicts the least recently inserted entries, and `MultiShardStore`
+//! resolves evicted IDs through the global index or a shard fan-out. The
+//! map is only a routing hint: a miss never means the node is absent.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Recency bookkeeping for a bounded map. Same tick scheme as `NodeCache`:
+/// `order` maps each entry's last-insert tick back to its ID.
+#[derive(Debug)]
+struct Recency {
+    capacity: usize,
+    tick: u64,
+    used: HashMap<u128, u64>,
+    order: BTreeMap<u64, u128>,
+}
+
+/// Map from node ID to shard ID, optionally capped at `capacity` entries.
+///
+/// Recency is refreshed on insert only, so lookups stay `&self`.
+#[derive(Debug, Default)]
+pub struct ShardMap {
+    entries: HashMap<u128, u16>,
+    recency: Option<Recency>,
+}
+
+impl ShardMap {
+    /// Create an empty map; `None` (or zero) keeps every entry.
+    pub fn new(capacity: Option<usize>) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: capacity.filter(|&c| c > 0).map(|capacity| Recency {
+                capacity,
+                tick: 0,
+                used: HashMap::new(),
+                order: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Maximum number of entries, or None when unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.recency.as_ref().map(|r| r.capacity)
+    }
+
+    /// Whether entries may have been evicted, so a miss is inconclusive.
+    pub fn is_bounded(&self) -> bool {
+        self.recency.is_some()
+    }
+
+    /// Number of entries held.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the map holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Shard holding `id`, if the mapping is held.
+    pub fn get(&self, id: &u128) -> Option<&u16> {
+        self.entries.get(id)
+    }
+
+    /// Record that `id` lives in `shard_id`, evicting the least recently
+    /// inserted entry if the map is full.
+    pub fn insert(&mut self, id: u128, shard_id: u16) {
+        if let Some(recency) = &mut self.recency {
+            if let Some(used) = recency.used.remove(&id) {
+                recency.order.remove(&used);
+            } else if self.entries.len() >= recency.capacity {
+                if let Some((_, oldest)) = recency.order.pop_first() {
+                    recency.used.remove(&oldest);
+                    self.entries.remove(&oldest);
+                }
+            }
+            recency.tick += 1;
+            recency.order.insert(recency.tick, id);
+            recency.used.insert(id, recency.tick);
+        }
+        self.entries.insert(id, shard_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unbounded_keeps_everything() {
+        let mut map = ShardMap::new(None);
+        for id in 0..100 {
+            map.insert(id, (id % 4) as u16);
+        }
+        assert_eq!(map.len(), 100);
+        assert!(!map.is_bounded());
+        assert_eq!(map.get(&42), Some(&2));
+    }
+
+    #[test]
+    fn test_bounded_evicts_least_recently_inserted() {
+        let mut map = ShardMap::new(Some(2));
+        map.insert(1, 0);
+        map.insert(2, 1);
+        map.insert(1, 3); // refresh 1, 2 is now oldest
+        map.insert(3, 2);
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&1), Some(&3));
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.get(&3), Some(&2));
+    }
+}
@@ -7,16 +7,43 @@
 //!
 //! The hash is computed on the parent directory of the file path,
 //! then reduced to `[0, shard_count)` via modulo.
+//!
+//! Databases can opt into `RoutingStrategy::BySemanticId` instead, which
+//! hashes each node's semantic ID: no locality, but even distribution
+//! when one directory holds most of the code.
 
 use std::collections::HashMap;
 use std::path::Path;
 
-/// Deterministic shard planner: file path -> shard_id.
+use serde::{Deserialize, Serialize};
+
+use crate::storage_v2::types::NodeRecordV2;
+
+/// How nodes are assigned to shards (persisted in `db_config.json`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingStrategy {
+    /// Hash the parent directory of the node's file (co-locates a file's
+    /// nodes).
+    #[default]
+    ByFileDir,
+    /// Hash the node's semantic ID (even spread, no locality).
+    BySemanticId,
+}
+
+impl RoutingStrategy {
+    pub fn is_by_file_dir(&self) -> bool {
+        *self == Self::ByFileDir
+    }
+}
+
+/// Deterministic shard planner: node -> shard_id.
 ///
-/// Assigns files to shards based on their parent directory.
-/// Same directory = same shard (locality optimization).
+/// By default assigns nodes to shards based on their file's parent
+/// directory. Same directory = same shard (locality optimization).
 pub struct ShardPlanner {
     shard_count: u16,
+    strategy: RoutingStrategy,
 }
 
 impl ShardPlanner {
@@ -26,8 +53,17 @@ impl ShardPlanner {
     ///
     /// Panics if `shard_count` is 0.
     pub fn new(shard_count: u16) -> Self {
+        Self::with_strategy(shard_count, RoutingStrategy::ByFileDir)
+    }
+
+    /// Create a planner routing nodes with `strategy`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shard_count` is 0.
+    pub fn with_strategy(shard_count: u16, strategy: RoutingStrategy) -> Self {
         assert!(shard_count > 0, "shard_count must be > 0");
-        Self { shard_count }
+        Self { shard_count, strategy }
     }
 
     /// Number of shards this planner distributes across.
@@ -35,6 +71,19 @@ impl ShardPlanner {
         self.shard_count
     }
 
+    /// Node routing strategy.
+    pub fn strategy(&self) -> RoutingStrategy {
+        self.strategy
+    }
+
+    /// Compute shard ID for a node according to the routing strategy.
+    pub fn compute_node_shard_id(&self, node: &NodeRecordV2) -> u16 {
+        match self.strategy {
+            RoutingStrategy::ByFileDir => self.compute_shard_id(&node.file),
+            RoutingStrategy::BySemanticId => self.hash_to_shard(&node.semantic_id),
+        }
+    }
+
     /// Compute shard ID for a file path.
     ///
     /// Uses blake3 hash of the parent directory, reduced via modulo.
@@ -45,7 +94,12 @@ impl ShardPlanner {
             .parent()
             .and_then(|p| p.to_str())
             .unwrap_or("");
-        let hash = blake3::hash(dir.as_bytes());
+        self.hash_to_shard(dir)
+    }
+
+    /// blake3 hash of `key`, reduced to a shard ID via modulo.
+    fn hash_to_shard(&self, key: &str) -> u16 {
+        let hash = blake3::hash(key.as_bytes());
         let hash_u64 = u64::from_le_bytes(hash.as_bytes()[0..8].try_into().unwrap());
         (hash_u64 % self.shard_count as u64) as u16
     }
@@ -191,6 +245,29 @@ mod tests {
         assert_eq!(a, b);
     }
 
+    #[test]
+    fn test_semantic_id_routing_ignores_directory() {
+        let planner = ShardPlanner::with_strategy(16, RoutingStrategy::BySemanticId);
+        let node = |semantic_id: &str| NodeRecordV2 {
+            semantic_id: semantic_id.to_string(),
+            id: 0,
+            node_type: "FUNCTION".to_string(),
+            name: String::new(),
+            file: "src/giant/index.js".to_string(),
+            content_hash: 0,
+            metadata: String::new(),
+        };
+
+        let shard_ids: HashSet<u16> = (0..64)
+            .map(|i| planner.compute_node_shard_id(&node(&format!("src/giant/index.js->fn{}", i))))
+            .collect();
+        assert!(shard_ids.len() > 1, "one directory should spread over shards");
+
+        let by_dir = ShardPlanner::new(16);
+        assert_eq!(by_dir.strategy(), RoutingStrategy::ByFileDir);
+        assert_eq!(by_dir.compute_node_shard_id(&node("a")), by_dir.compute_shard_id("src/giant/index.js"));
+    }
+
     #[test]
     #[should_panic(expected = "shard_count must be > 0")]
     fn test_zero_shards_panics() {
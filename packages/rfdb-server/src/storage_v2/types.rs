@@ -18,8 +18,15 @@ pub const MAGIC_V2: [u8; 4] = *b"SGV2";
 /// Magic bytes for v1 segments (used in error detection)
 pub const MAGIC_V1: [u8; 4] = *b"SGRF";
 
-/// Format version
-pub const FORMAT_VERSION: u16 = 2;
+/// Format version written by this build.
+///
+/// v3 stores node_type as a u16 code into a per-segment type dictionary;
+/// v2 stored a u32 string table index per record. Edge segments are laid
+/// out the same in both.
+pub const FORMAT_VERSION: u16 = 3;
+
+/// Oldest format version this build can still read.
+pub const MIN_FORMAT_VERSION: u16 = 2;
 
 /// Header size in bytes (fixed, power-of-2, cache-line friendly)
 pub const HEADER_SIZE: usize = 32;
@@ -37,6 +44,10 @@ pub const BLOOM_BITS_PER_KEY: usize = 10;
 /// Bloom filter: number of hash functions (optimal for 10 bits/key)
 pub const BLOOM_NUM_HASHES: usize = 7;
 
+/// Default target false-positive rate for segment bloom filters
+/// (~9.6 bits/key, k=7 — close to the fixed sizing above).
+pub const DEFAULT_BLOOM_FP_RATE: f64 = 0.01;
+
 /// Zone map: max distinct values per field before omitting
 /// (Condition 2 from Steve Jobs review — prevents pathological zone maps)
 pub const MAX_ZONE_MAP_VALUES_PER_FIELD: usize = 10_000;
@@ -61,6 +72,93 @@ impl SegmentType {
     }
 }
 
+// ── Compression Kind ───────────────────────────────────────────────
+
+/// Segment body compression (stored as u8 in header byte 7).
+///
+/// `None` segments are memory-mapped and read in place. `Zstd` segments
+/// keep the 32-byte header uncompressed and zstd-compress everything after
+/// it (columns, bloom, zone map, string table, footer). They are smaller on
+/// disk but must be decompressed fully into memory on open, so they give up
+/// zero-copy mmap access and page-cache sharing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum CompressionKind {
+    #[default]
+    None = 0,
+    Zstd = 1,
+}
+
+impl CompressionKind {
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::None),
+            1 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::None
+    }
+}
+
+/// zstd level for compressed segments (favours write speed over ratio).
+pub const ZSTD_LEVEL: i32 = 3;
+
+/// Page-cache access hint (`madvise`) for memory-mapped segments.
+///
+/// `Random` turns off kernel readahead, so cold point lookups fault in
+/// only the pages they touch. `Sequential` reads ahead aggressively and
+/// is used for compaction inputs. Compressed segments live in owned
+/// memory and ignore the hint, as do platforms without `madvise`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MmapAdvice {
+    #[default]
+    Normal,
+    Random,
+    Sequential,
+}
+
+// ── Checksum ───────────────────────────────────────────────────────
+
+/// Byte offset of the checksum field in the segment header.
+pub const CHECKSUM_OFFSET: usize = 24;
+
+/// Segment body checksum: BLAKE3 of everything after the header
+/// (uncompressed layout), truncated to u64.
+///
+/// Never returns 0, which marks segments written before checksums existed.
+pub fn segment_checksum(body: &[u8]) -> u64 {
+    let mut checksum = SegmentChecksum::new();
+    checksum.update(body);
+    checksum.finish()
+}
+
+/// Incremental `segment_checksum`, for bodies written in pieces.
+#[derive(Debug, Clone, Default)]
+pub struct SegmentChecksum(blake3::Hasher);
+
+impl SegmentChecksum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next bytes of the body.
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    /// Checksum of everything added so far; never 0.
+    pub fn finish(&self) -> u64 {
+        let hash = self.0.finalize();
+        let value = u64::from_le_bytes(hash.as_bytes()[0..8].try_into().unwrap());
+        value.max(1)
+    }
+}
+
 // ── Header ─────────────────────────────────────────────────────────
 
 /// V2 segment header — exactly 32 bytes on disk.
@@ -68,12 +166,12 @@ impl SegmentType {
 /// ```text
 /// Offset  Size  Field
 /// 0       4     magic: b"SGV2"
-/// 4       2     version: u16 = 2
+/// 4       2     version: u16 (2..=FORMAT_VERSION)
 /// 6       1     segment_type: u8
-/// 7       1     reserved: 0x00
+/// 7       1     compression: u8 (CompressionKind, 0 = none)
 /// 8       8     record_count: u64
 /// 16      8     footer_offset: u64
-/// 24      8     reserved: 0x00
+/// 24      8     checksum: u64 (see `segment_checksum`, 0 = none)
 /// ```
 #[derive(Debug, Clone, Copy)]
 pub struct SegmentHeaderV2 {
@@ -108,7 +206,7 @@ impl SegmentHeaderV2 {
                 self.magic
             )));
         }
-        if self.version != FORMAT_VERSION {
+        if !(MIN_FORMAT_VERSION..=FORMAT_VERSION).contains(&self.version) {
             return Err(GraphError::InvalidFormat(format!(
                 "Unsupported segment version: {}",
                 self.version
@@ -153,7 +251,7 @@ impl SegmentHeaderV2 {
         writer.write_all(&[0u8])?; // reserved
         writer.write_all(&self.record_count.to_le_bytes())?;
         writer.write_all(&self.footer_offset.to_le_bytes())?;
-        writer.write_all(&[0u8; 8])?; // reserved
+        writer.write_all(&[0u8; 8])?; // checksum, filled in by the segment writer
         Ok(())
     }
 }
@@ -298,6 +396,7 @@ pub struct SegmentMeta {
     pub node_types: HashSet<String>,
     pub file_paths: HashSet<String>,
     pub edge_types: HashSet<String>,
+    pub compression: CompressionKind,
 }
 
 // ── Commit Delta ──────────────────────────────────────────────────
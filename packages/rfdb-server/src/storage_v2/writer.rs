@@ -3,15 +3,137 @@
 //! Provides `NodeSegmentWriter` and `EdgeSegmentWriter` for creating immutable
 //! columnar segments with bloom filters, zone maps, and string tables.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{Write, Seek, SeekFrom};
 
-use crate::error::Result;
+use crate::error::{GraphError, Result};
 use crate::storage_v2::bloom::BloomFilter;
 use crate::storage_v2::string_table::StringTableV2;
 use crate::storage_v2::types::*;
 use crate::storage_v2::zone_map::ZoneMap;
 
+// ── Checksum + Compression ─────────────────────────────────────────
+
+/// Where a segment writer's output goes: the header is buffered until the
+/// end, while the body streams through the checksum into `target`, as-is
+/// or zstd-compressed.
+///
+/// Compressed segments keep the header uncompressed except byte 7
+/// (compression kind), so readers can detect the format before inflating.
+/// Footer offsets and the checksum refer to the uncompressed layout,
+/// which is what the reader reconstructs, so `Seek` reports positions in
+/// that layout. Only the header may be rewritten; the body must be
+/// written in order.
+struct SegmentSink<'w, W: Write + Seek> {
+    body: SinkBody<'w, W>,
+    start: u64,
+    header: [u8; HEADER_SIZE],
+    body_len: u64,
+    pos: u64,
+    checksum: SegmentChecksum,
+}
+
+enum SinkBody<'w, W: Write> {
+    Plain(&'w mut W),
+    Zstd(zstd::stream::write::Encoder<'static, &'w mut W>),
+}
+
+impl<'w, W: Write + Seek> SegmentSink<'w, W> {
+    /// Reserve room for the header at `target`'s current position.
+    fn new(target: &'w mut W, compression: CompressionKind) -> Result<Self> {
+        let start = target.stream_position()?;
+        target.write_all(&[0u8; HEADER_SIZE])?;
+        let body = match compression {
+            CompressionKind::None => SinkBody::Plain(target),
+            CompressionKind::Zstd => SinkBody::Zstd(zstd::stream::write::Encoder::new(target, ZSTD_LEVEL)?),
+        };
+        Ok(Self {
+            body,
+            start,
+            header: [0u8; HEADER_SIZE],
+            body_len: 0,
+            pos: 0,
+            checksum: SegmentChecksum::new(),
+        })
+    }
+
+    /// Stamp the checksum into the header, write it at the reserved spot
+    /// and leave `target` positioned after the segment.
+    fn finish(mut self, meta: SegmentMeta) -> Result<SegmentMeta> {
+        self.header[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 8]
+            .copy_from_slice(&self.checksum.finish().to_le_bytes());
+        let (target, meta) = match self.body {
+            SinkBody::Plain(target) => (target, meta),
+            SinkBody::Zstd(encoder) => {
+                self.header[7] = CompressionKind::Zstd as u8;
+                let target = encoder.finish()?;
+                let byte_size = target.stream_position()? - self.start;
+                (target, SegmentMeta { byte_size, compression: CompressionKind::Zstd, ..meta })
+            }
+        };
+        let end = target.stream_position()?;
+        target.seek(SeekFrom::Start(self.start))?;
+        target.write_all(&self.header)?;
+        target.seek(SeekFrom::Start(end))?;
+        target.flush()?;
+        Ok(meta)
+    }
+}
+
+impl<W: Write + Seek> Write for SegmentSink<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.pos < HEADER_SIZE as u64 {
+            let at = self.pos as usize;
+            let n = buf.len().min(HEADER_SIZE - at);
+            self.header[at..at + n].copy_from_slice(&buf[..n]);
+            self.pos += n as u64;
+            return Ok(n);
+        }
+        if self.pos != HEADER_SIZE as u64 + self.body_len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "segment body must be written in order",
+            ));
+        }
+        let n = match &mut self.body {
+            SinkBody::Plain(target) => target.write(buf)?,
+            SinkBody::Zstd(encoder) => encoder.write(buf)?,
+        };
+        self.checksum.update(&buf[..n]);
+        self.body_len += n as u64;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.body {
+            SinkBody::Plain(target) => target.flush(),
+            SinkBody::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl<W: Write + Seek> Seek for SegmentSink<'_, W> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let end = HEADER_SIZE as u64 + self.body_len;
+        let target = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(d) => end.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        match target {
+            Some(p) if p <= HEADER_SIZE as u64 || p == end => {
+                self.pos = p;
+                Ok(p)
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "segment writers can only seek within the header or to the end",
+            )),
+        }
+    }
+}
+
 // ── NodeSegmentWriter ──────────────────────────────────────────────
 
 /// Writer for node segments (SegmentType::Nodes).
@@ -20,16 +142,41 @@ use crate::storage_v2::zone_map::ZoneMap;
 /// with associated indexes (bloom filter, zone map, string table) on `finish()`.
 pub struct NodeSegmentWriter {
     records: Vec<NodeRecordV2>,
+    compression: CompressionKind,
+    bloom_fp_rate: f64,
+    int_fields: Vec<String>,
 }
 
 impl NodeSegmentWriter {
     /// Create a new empty node segment writer.
     pub fn new() -> Self {
+        Self::with_compression(CompressionKind::None)
+    }
+
+    /// Create a new empty node segment writer that compresses the
+    /// segment body on `finish()` (see `CompressionKind`).
+    pub fn with_compression(compression: CompressionKind) -> Self {
         Self {
             records: Vec::new(),
+            compression,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
+            int_fields: Vec::new(),
         }
     }
 
+    /// Size the segment's bloom filter for the given target
+    /// false-positive rate (default `DEFAULT_BLOOM_FP_RATE`).
+    pub fn with_bloom_fp_rate(mut self, fp_rate: f64) -> Self {
+        self.bloom_fp_rate = fp_rate;
+        self
+    }
+
+    /// Track min/max zone map ranges for these integer metadata fields.
+    pub fn with_int_fields(mut self, fields: Vec<String>) -> Self {
+        self.int_fields = fields;
+        self
+    }
+
     /// Add a node record to the segment.
     ///
     /// Add a node record to the segment.
@@ -59,27 +206,36 @@ impl NodeSegmentWriter {
     /// ## Binary layout
     ///
     /// ```text
-    /// [Header 32 bytes]
+    /// [Header 32 bytes, incl. checksum of everything below]
     /// [semantic_id indices: u32 × N]
-    /// [node_type indices: u32 × N]
+    /// [node_type codes: u16 × N]
     /// [name indices: u32 × N]
     /// [file indices: u32 × N]
     /// [metadata indices: u32 × N]
     /// [padding to 16-byte boundary: 0x00 bytes]
     /// [id column: u128 × N]
     /// [content_hash column: u64 × N]
+    /// [type dictionary: u32 count, then u32 string index per code]
     /// [bloom filter]
     /// [zone map]
     /// [string table]
     /// [footer index 48 bytes]
     /// ```
     pub fn finish<W: Write + Seek>(self, writer: &mut W) -> Result<SegmentMeta> {
+        let mut sink = SegmentSink::new(writer, self.compression)?;
+        let meta = self.write_uncompressed(&mut sink)?;
+        sink.finish(meta)
+    }
+
+    fn write_uncompressed<W: Write + Seek>(self, writer: &mut W) -> Result<SegmentMeta> {
         let n = self.records.len();
 
         // Step 1: Build column arrays + intern strings.
         let mut string_table = StringTableV2::new();
         let mut semantic_id_indices: Vec<u32> = Vec::with_capacity(n);
-        let mut node_type_indices: Vec<u32> = Vec::with_capacity(n);
+        let mut node_type_codes: Vec<u16> = Vec::with_capacity(n);
+        let mut type_dict: Vec<u32> = Vec::new();
+        let mut type_codes: HashMap<u32, u16> = HashMap::new();
         let mut name_indices: Vec<u32> = Vec::with_capacity(n);
         let mut file_indices: Vec<u32> = Vec::with_capacity(n);
         let mut metadata_indices: Vec<u32> = Vec::with_capacity(n);
@@ -92,7 +248,22 @@ impl NodeSegmentWriter {
 
         for record in &self.records {
             semantic_id_indices.push(string_table.intern(&record.semantic_id));
-            node_type_indices.push(string_table.intern(&record.node_type));
+            let type_index = string_table.intern(&record.node_type);
+            let code = match type_codes.get(&type_index) {
+                Some(&code) => code,
+                None => {
+                    let code = u16::try_from(type_dict.len()).map_err(|_| {
+                        GraphError::InvalidFormat(format!(
+                            "Too many distinct node types for one segment (max {})",
+                            u16::MAX as usize + 1
+                        ))
+                    })?;
+                    type_dict.push(type_index);
+                    type_codes.insert(type_index, code);
+                    code
+                }
+            };
+            node_type_codes.push(code);
             name_indices.push(string_table.intern(&record.name));
             file_indices.push(string_table.intern(&record.file));
             metadata_indices.push(string_table.intern(&record.metadata));
@@ -101,12 +272,21 @@ impl NodeSegmentWriter {
 
             zone_map.add("node_type", &record.node_type);
             zone_map.add("file", &record.file);
+            if !self.int_fields.is_empty() && !record.metadata.is_empty() {
+                if let Ok(meta) = serde_json::from_str::<serde_json::Value>(&record.metadata) {
+                    for field in &self.int_fields {
+                        if let Some(value) = meta.get(field).and_then(|v| v.as_i64()) {
+                            zone_map.add_int(field, value);
+                        }
+                    }
+                }
+            }
             node_types_set.insert(record.node_type.clone());
             file_paths_set.insert(record.file.clone());
         }
 
         // Step 2: Build bloom filter.
-        let mut bloom = BloomFilter::new(n);
+        let mut bloom = BloomFilter::with_fp_rate(n, self.bloom_fp_rate);
         for &id in &ids {
             bloom.insert(id);
         }
@@ -115,12 +295,12 @@ impl NodeSegmentWriter {
         let header = SegmentHeaderV2::new(SegmentType::Nodes, n as u64, 0);
         header.write_to(writer)?;
 
-        // Step 4: Write u32 columns (5 × N values).
+        // Step 4: Write string columns (4 × u32 + 1 × u16 per record).
         for &idx in &semantic_id_indices {
             writer.write_all(&idx.to_le_bytes())?;
         }
-        for &idx in &node_type_indices {
-            writer.write_all(&idx.to_le_bytes())?;
+        for &code in &node_type_codes {
+            writer.write_all(&code.to_le_bytes())?;
         }
         for &idx in &name_indices {
             writer.write_all(&idx.to_le_bytes())?;
@@ -133,7 +313,7 @@ impl NodeSegmentWriter {
         }
 
         // Step 5: Padding to 16-byte boundary (0x00 bytes).
-        let current_offset = HEADER_SIZE + 20 * n;
+        let current_offset = HEADER_SIZE + 18 * n;
         let padding = compute_padding(current_offset, 16);
         // Use stack buffer, not heap allocation.
         writer.write_all(&[0u8; 16][..padding])?;
@@ -148,6 +328,12 @@ impl NodeSegmentWriter {
             writer.write_all(&hash.to_le_bytes())?;
         }
 
+        // Step 7b: Write node_type dictionary (code -> string table index).
+        writer.write_all(&(type_dict.len() as u32).to_le_bytes())?;
+        for &idx in &type_dict {
+            writer.write_all(&idx.to_le_bytes())?;
+        }
+
         // Record data_end_offset.
         let data_end_offset = writer.stream_position()?;
 
@@ -192,6 +378,7 @@ impl NodeSegmentWriter {
             node_types: node_types_set,
             file_paths: file_paths_set,
             edge_types: HashSet::new(),
+            compression: CompressionKind::None,
         })
     }
 }
@@ -211,16 +398,33 @@ impl Default for NodeSegmentWriter {
 /// on `finish()`.
 pub struct EdgeSegmentWriter {
     records: Vec<EdgeRecordV2>,
+    compression: CompressionKind,
+    bloom_fp_rate: f64,
 }
 
 impl EdgeSegmentWriter {
     /// Create a new empty edge segment writer.
     pub fn new() -> Self {
+        Self::with_compression(CompressionKind::None)
+    }
+
+    /// Create a new empty edge segment writer that compresses the
+    /// segment body on `finish()` (see `CompressionKind`).
+    pub fn with_compression(compression: CompressionKind) -> Self {
         Self {
             records: Vec::new(),
+            compression,
+            bloom_fp_rate: DEFAULT_BLOOM_FP_RATE,
         }
     }
 
+    /// Size the segment's bloom filters for the given target
+    /// false-positive rate (default `DEFAULT_BLOOM_FP_RATE`).
+    pub fn with_bloom_fp_rate(mut self, fp_rate: f64) -> Self {
+        self.bloom_fp_rate = fp_rate;
+        self
+    }
+
     /// Add an edge record to the segment.
     pub fn add(&mut self, record: EdgeRecordV2) {
         self.records.push(record);
@@ -244,7 +448,7 @@ impl EdgeSegmentWriter {
     /// ## Binary layout
     ///
     /// ```text
-    /// [Header 32 bytes]
+    /// [Header 32 bytes, incl. checksum of everything below]
     /// [src column: u128 × N]
     /// [dst column: u128 × N]
     /// [edge_type indices: u32 × N]
@@ -256,6 +460,12 @@ impl EdgeSegmentWriter {
     /// [footer index 48 bytes]
     /// ```
     pub fn finish<W: Write + Seek>(self, writer: &mut W) -> Result<SegmentMeta> {
+        let mut sink = SegmentSink::new(writer, self.compression)?;
+        let meta = self.write_uncompressed(&mut sink)?;
+        sink.finish(meta)
+    }
+
+    fn write_uncompressed<W: Write + Seek>(self, writer: &mut W) -> Result<SegmentMeta> {
         let n = self.records.len();
 
         // Step 1: Build column arrays + intern strings.
@@ -279,12 +489,12 @@ impl EdgeSegmentWriter {
         }
 
         // Step 2: Build bloom filters (src and dst).
-        let mut bloom = BloomFilter::new(n);
+        let mut bloom = BloomFilter::with_fp_rate(n, self.bloom_fp_rate);
         for &src in &srcs {
             bloom.insert(src);
         }
 
-        let mut dst_bloom = BloomFilter::new(n);
+        let mut dst_bloom = BloomFilter::with_fp_rate(n, self.bloom_fp_rate);
         for &dst in &dsts {
             dst_bloom.insert(dst);
         }
@@ -355,6 +565,7 @@ impl EdgeSegmentWriter {
             node_types: HashSet::new(),
             file_paths: HashSet::new(),
             edge_types: edge_types_set,
+            compression: CompressionKind::None,
         })
     }
 }
@@ -570,8 +781,8 @@ mod tests {
             writer.finish(&mut buf).unwrap();
 
             let bytes = buf.into_inner();
-            // u32 section: 20 * n bytes starting at offset 32.
-            let u32_end = HEADER_SIZE + 20 * n;
+            // String columns: 4 × u32 + 1 × u16 = 18 * n bytes from offset 32.
+            let u32_end = HEADER_SIZE + 18 * n;
             let padding = compute_padding(u32_end, 16);
             let ids_start = u32_end + padding;
 
@@ -640,6 +851,47 @@ mod tests {
         assert_eq!(footer.magic, FOOTER_INDEX_MAGIC);
     }
 
+    #[test]
+    fn test_write_checksum_streamed_at_offset() {
+        use crate::storage_v2::segment::NodeSegmentV2;
+
+        for compression in [CompressionKind::None, CompressionKind::Zstd] {
+            let mut writer = NodeSegmentWriter::with_compression(compression);
+            for i in 0..100 {
+                writer.add(make_node(&format!("id_{i}"), "TYPE", "name", "file.rs"));
+            }
+            // Segments need not start at the beginning of the writer
+            let mut buf = Cursor::new(b"prefix".to_vec());
+            buf.set_position(6);
+            let meta = writer.finish(&mut buf).unwrap();
+            assert_eq!(meta.compression, compression);
+            assert_eq!(buf.position(), buf.get_ref().len() as u64);
+
+            let bytes = &buf.get_ref()[6..];
+            assert_eq!(meta.byte_size, bytes.len() as u64);
+            assert_ne!(u64::from_le_bytes(bytes[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 8].try_into().unwrap()), 0);
+            let file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(file.path(), bytes).unwrap();
+            let segment = NodeSegmentV2::open_verified(file.path()).unwrap();
+            assert_eq!(segment.record_count(), 100);
+        }
+    }
+
+    #[test]
+    fn test_segment_sink_rejects_rewriting_the_body() {
+        let mut buf = Cursor::new(Vec::new());
+        let mut sink = SegmentSink::new(&mut buf, CompressionKind::None).unwrap();
+        sink.write_all(&[0u8; HEADER_SIZE + 8]).unwrap();
+        assert!(sink.seek(SeekFrom::Start(HEADER_SIZE as u64 + 4)).is_err());
+        // Header bytes can be patched, the body only appended to
+        sink.seek(SeekFrom::Start(16)).unwrap();
+        sink.write_all(&[1u8; 8]).unwrap();
+        sink.seek(SeekFrom::Start(HEADER_SIZE as u64)).unwrap();
+        assert!(sink.write_all(&[2u8]).is_err());
+        sink.seek(SeekFrom::End(0)).unwrap();
+        sink.write_all(&[2u8]).unwrap();
+    }
+
     #[test]
     fn test_node_segment_default() {
         let writer = NodeSegmentWriter::default();
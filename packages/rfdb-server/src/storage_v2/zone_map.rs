@@ -20,6 +20,20 @@
 //!
 //! Fields and values are sorted lexicographically for deterministic,
 //! byte-exact roundtrips.
+//!
+//! Integer fields (declared `FieldType::Int` metadata) additionally get a
+//! min/max range, so range queries can skip segments whose range does not
+//! overlap. The range section is only written when non-empty, which keeps
+//! segments without int fields byte-identical to the original format:
+//!
+//! ```text
+//! [range_count: u32 LE]                    // 4 bytes
+//! For each range (sorted by name):
+//!   [field_name_len: u16 LE]              // 2 bytes
+//!   [field_name: utf8 bytes]              // variable
+//!   [min: i64 LE]                         // 8 bytes
+//!   [max: i64 LE]                         // 8 bytes
+//! ```
 
 use std::collections::{HashMap, HashSet};
 use std::io::Write;
@@ -31,6 +45,7 @@ use crate::storage_v2::types::MAX_ZONE_MAP_VALUES_PER_FIELD;
 #[derive(Debug)]
 pub struct ZoneMap {
     fields: HashMap<String, HashSet<String>>,
+    ranges: HashMap<String, (i64, i64)>,
 }
 
 impl ZoneMap {
@@ -38,6 +53,7 @@ impl ZoneMap {
     pub fn new() -> Self {
         Self {
             fields: HashMap::new(),
+            ranges: HashMap::new(),
         }
     }
 
@@ -66,6 +82,33 @@ impl ZoneMap {
         self.fields.len()
     }
 
+    /// Widen the min/max range of integer field `field` to include `value`.
+    pub fn add_int(&mut self, field: &str, value: i64) {
+        self.ranges
+            .entry(field.to_string())
+            .and_modify(|(min, max)| {
+                *min = (*min).min(value);
+                *max = (*max).max(value);
+            })
+            .or_insert((value, value));
+    }
+
+    /// Min/max of integer field `field`, if tracked.
+    pub fn int_range(&self, field: &str) -> Option<(i64, i64)> {
+        self.ranges.get(field).copied()
+    }
+
+    /// Whether values of `field` in `[min, max]` may occur in this segment.
+    ///
+    /// Untracked fields are never ruled out: the segment may predate the
+    /// field being declared.
+    pub fn may_contain_int_range(&self, field: &str, min: i64, max: i64) -> bool {
+        match self.ranges.get(field) {
+            Some(&(lo, hi)) => lo <= max && min <= hi,
+            None => true,
+        }
+    }
+
     /// Serialize to the binary format described in the module docs.
     ///
     /// Fields with more than [`MAX_ZONE_MAP_VALUES_PER_FIELD`] distinct
@@ -120,6 +163,20 @@ impl ZoneMap {
             }
         }
 
+        if !self.ranges.is_empty() {
+            let mut sorted_ranges: Vec<(&String, &(i64, i64))> = self.ranges.iter().collect();
+            sorted_ranges.sort_by_key(|(name, _)| name.as_str());
+
+            writer.write_all(&(sorted_ranges.len() as u32).to_le_bytes())?;
+            for (name, (min, max)) in sorted_ranges {
+                let name_bytes = name.as_bytes();
+                writer.write_all(&(name_bytes.len() as u16).to_le_bytes())?;
+                writer.write_all(name_bytes)?;
+                writer.write_all(&min.to_le_bytes())?;
+                writer.write_all(&max.to_le_bytes())?;
+            }
+        }
+
         Ok(())
     }
 
@@ -210,7 +267,39 @@ impl ZoneMap {
             fields.insert(name, values);
         }
 
-        Ok(Self { fields })
+        let mut ranges = HashMap::new();
+        if pos < bytes.len() {
+            let truncated = || GraphError::InvalidFormat("Zone map range truncated".into());
+            let range_count = u32::from_le_bytes(
+                bytes.get(pos..pos + 4).ok_or_else(truncated)?.try_into().unwrap(),
+            ) as usize;
+            pos += 4;
+
+            for _ in 0..range_count {
+                let name_len = u16::from_le_bytes(
+                    bytes.get(pos..pos + 2).ok_or_else(truncated)?.try_into().unwrap(),
+                ) as usize;
+                pos += 2;
+                let name = std::str::from_utf8(bytes.get(pos..pos + name_len).ok_or_else(truncated)?)
+                    .map_err(|_| {
+                        GraphError::InvalidFormat(
+                            "Zone map contains invalid UTF-8".into(),
+                        )
+                    })?
+                    .to_string();
+                pos += name_len;
+                let min = i64::from_le_bytes(
+                    bytes.get(pos..pos + 8).ok_or_else(truncated)?.try_into().unwrap(),
+                );
+                let max = i64::from_le_bytes(
+                    bytes.get(pos + 8..pos + 16).ok_or_else(truncated)?.try_into().unwrap(),
+                );
+                pos += 16;
+                ranges.insert(name, (min, max));
+            }
+        }
+
+        Ok(Self { fields, ranges })
     }
 
     /// Compute the exact serialized byte count without writing.
@@ -235,6 +324,13 @@ impl ZoneMap {
             }
         }
 
+        if !self.ranges.is_empty() {
+            size += 4; // range_count: u32
+            for name in self.ranges.keys() {
+                size += 2 + name.len() + 16; // name_len + name + min + max
+            }
+        }
+
         size
     }
 }
@@ -398,6 +494,47 @@ mod tests {
         assert_eq!(buf1, buf2, "Zone map is not byte-exact after roundtrip");
     }
 
+    #[test]
+    fn test_zone_map_int_ranges_roundtrip() {
+        let mut zm = ZoneMap::new();
+        zm.add("node_type", "FUNCTION");
+        zm.add_int("metadata.line", 40);
+        zm.add_int("metadata.line", 12);
+        zm.add_int("metadata.line", 25);
+        zm.add_int("metadata.arity", -1);
+
+        assert_eq!(zm.int_range("metadata.line"), Some((12, 40)));
+        assert!(zm.may_contain_int_range("metadata.line", 0, 12));
+        assert!(zm.may_contain_int_range("metadata.line", 30, 100));
+        assert!(!zm.may_contain_int_range("metadata.line", 41, 100));
+        assert!(zm.may_contain_int_range("metadata.depth", 0, 100), "untracked fields are never pruned");
+
+        let mut buf = Vec::new();
+        zm.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), zm.serialized_size());
+
+        let zm2 = ZoneMap::from_bytes(&buf).unwrap();
+        assert!(zm2.contains("node_type", "FUNCTION"));
+        assert_eq!(zm2.int_range("metadata.line"), Some((12, 40)));
+        assert_eq!(zm2.int_range("metadata.arity"), Some((-1, -1)));
+
+        let mut buf2 = Vec::new();
+        zm2.write_to(&mut buf2).unwrap();
+        assert_eq!(buf, buf2);
+    }
+
+    #[test]
+    fn test_zone_map_without_ranges_never_prunes_ints() {
+        let mut zm = ZoneMap::new();
+        zm.add("node_type", "FUNCTION");
+        let mut buf = Vec::new();
+        zm.write_to(&mut buf).unwrap();
+
+        let zm2 = ZoneMap::from_bytes(&buf).unwrap();
+        assert!(zm2.int_range("metadata.line").is_none());
+        assert!(zm2.may_contain_int_range("metadata.line", 0, 10));
+    }
+
     #[test]
     fn test_zone_map_field_truncated() {
         // field_count = 1 but no field data follows.
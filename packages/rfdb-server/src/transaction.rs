@@ -0,0 +1,713 @@
+//! Session transactions - staged writes with read-your-writes
+//!
+//! While a client has a batch open (`BeginBatch`), its `AddNodes`,
+//! `AddEdges`, `DeleteNode` and `DeleteEdge` requests are recorded in a
+//! `Transaction` instead of reaching the engine. `CommitTransaction`
+//! replays them in order under a single engine write lock, so other
+//! clients never observe half a transaction; `AbortBatch` drops them
+//! without touching the engine.
+//!
+//! The transaction keeps an overlay of its staged state. Reads made
+//! through a `TransactionView` (point lookups, type lookups, edge lookups
+//! and counts) see the session's own uncommitted writes. Other reads
+//! (traversals, attribute search, Datalog) see committed data only.
+//!
+//! Staged writes are bounded by an approximate byte size, and a commit is
+//! validated against the engine before anything is replayed: an edge that
+//! was validated when staged but whose endpoint is gone by commit time
+//! fails the whole commit instead of being dropped.
+//!
+//! A `CrossDbBatch` is the multi-database counterpart: whole CommitBatch
+//! payloads staged per database (`BeginCrossDbBatch` /
+//! `StageCrossDbCommit`) and applied all-or-nothing by
+//! `CommitCrossDbBatch`.
+//!
+//! A `StreamingCommit` is a single CommitBatch too large for one frame:
+//! nodes and edges arrive over several `StreamCommitNodes` /
+//! `StreamCommitEdges` requests and `FinishStreamingCommit` applies them
+//! as one CommitBatch.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::graph::GraphStore;
+use crate::storage::{EdgeRecord, NodeRecord};
+
+type EdgeKey = (u128, u128, String);
+
+/// One buffered write, replayed against the engine on commit.
+#[derive(Debug, Clone)]
+pub enum WriteOp {
+    AddNodes(Vec<NodeRecord>),
+    AddEdges { edges: Vec<EdgeRecord>, skip_validation: bool },
+    DeleteNode(u128),
+    DeleteEdge { src: u128, dst: u128, edge_type: String },
+}
+
+impl WriteOp {
+    /// Apply this write to `engine`.
+    pub fn apply(self, engine: &mut dyn GraphStore) {
+        match self {
+            WriteOp::AddNodes(nodes) => engine.add_nodes(nodes),
+            WriteOp::AddEdges { edges, skip_validation } => engine.add_edges(edges, skip_validation),
+            WriteOp::DeleteNode(id) => engine.delete_node(id),
+            WriteOp::DeleteEdge { src, dst, edge_type } => engine.delete_edge(src, dst, &edge_type),
+        }
+    }
+}
+
+fn edge_key(edge: &EdgeRecord) -> EdgeKey {
+    (edge.src, edge.dst, edge.edge_type.clone().unwrap_or_default())
+}
+
+/// Writes buffered by one session between `BeginBatch` and commit/abort.
+pub struct Transaction {
+    id: String,
+    ops: Vec<WriteOp>,
+    /// Latest staged version of each added node
+    nodes: HashMap<u128, NodeRecord>,
+    /// Nodes deleted by the transaction (and not re-added since)
+    deleted_nodes: HashSet<u128>,
+    /// Nodes deleted at some point in the transaction: their committed
+    /// edges are gone on commit, even if the node is re-added later
+    detached_nodes: HashSet<u128>,
+    /// Staged edges by (src, dst, type)
+    edges: HashMap<EdgeKey, EdgeRecord>,
+    /// Edges deleted by the transaction (and not re-added since)
+    deleted_edges: HashSet<EdgeKey>,
+    /// Approximate size of the staged writes
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Transaction {
+    pub fn new(id: String, max_bytes: usize) -> Self {
+        Self {
+            id,
+            ops: Vec::new(),
+            nodes: HashMap::new(),
+            deleted_nodes: HashSet::new(),
+            detached_nodes: HashSet::new(),
+            edges: HashMap::new(),
+            deleted_edges: HashSet::new(),
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// Batch ID returned by `BeginBatch`
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Number of staged write requests
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Approximate in-memory size of the staged writes
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Buffer a write and fold it into the read overlay.
+    ///
+    /// Returns false, staging nothing, if the write would take the
+    /// transaction past its byte limit.
+    pub fn stage(&mut self, op: WriteOp) -> bool {
+        let bytes = match &op {
+            WriteOp::AddNodes(nodes) => nodes.iter().map(node_bytes).sum(),
+            WriteOp::AddEdges { edges, .. } => edges.iter().map(edge_bytes).sum(),
+            WriteOp::DeleteNode(_) => std::mem::size_of::<WriteOp>(),
+            WriteOp::DeleteEdge { edge_type, .. } => std::mem::size_of::<WriteOp>() + edge_type.len(),
+        };
+        match self.bytes.checked_add(bytes) {
+            Some(total) if total <= self.max_bytes => self.bytes = total,
+            _ => return false,
+        }
+        match &op {
+            WriteOp::AddNodes(nodes) => {
+                for node in nodes {
+                    self.deleted_nodes.remove(&node.id);
+                    self.nodes.insert(node.id, node.clone());
+                }
+            }
+            WriteOp::AddEdges { edges, .. } => {
+                for edge in edges {
+                    let key = edge_key(edge);
+                    self.deleted_edges.remove(&key);
+                    self.edges.insert(key, edge.clone());
+                }
+            }
+            WriteOp::DeleteNode(id) => {
+                // Engine delete_node also drops every edge touching the node
+                self.nodes.remove(id);
+                self.deleted_nodes.insert(*id);
+                self.detached_nodes.insert(*id);
+                self.edges.retain(|(src, dst, _), _| src != id && dst != id);
+            }
+            WriteOp::DeleteEdge { src, dst, edge_type } => {
+                let key = (*src, *dst, edge_type.clone());
+                self.edges.remove(&key);
+                self.deleted_edges.insert(key);
+            }
+        }
+        self.ops.push(op);
+        true
+    }
+
+    /// Every node staged by an AddNodes, in staging order (including
+    /// nodes a later write deleted or replaced).
+    pub fn staged_nodes(&self) -> Vec<NodeRecord> {
+        self.ops.iter()
+            .filter_map(|op| match op {
+                WriteOp::AddNodes(nodes) => Some(nodes.iter().cloned()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Validated staged edges (AddEdges without `skipValidation`) whose
+    /// source or destination would not exist when the edge is replayed
+    /// against `engine`. The engine would silently drop these, so a commit
+    /// with any of them must be refused.
+    pub fn dangling_edges(&self, engine: &dyn GraphStore) -> Vec<EdgeRecord> {
+        let mut added: HashSet<u128> = HashSet::new();
+        let mut deleted: HashSet<u128> = HashSet::new();
+        let mut dangling = Vec::new();
+        for op in &self.ops {
+            match op {
+                WriteOp::AddNodes(nodes) => {
+                    for node in nodes {
+                        added.insert(node.id);
+                        deleted.remove(&node.id);
+                    }
+                }
+                WriteOp::DeleteNode(id) => {
+                    added.remove(id);
+                    deleted.insert(*id);
+                }
+                WriteOp::AddEdges { edges, skip_validation: false } => {
+                    let exists = |id: u128| added.contains(&id) || (!deleted.contains(&id) && engine.node_exists(id));
+                    dangling.extend(edges.iter().filter(|e| !exists(e.src) || !exists(e.dst)).cloned());
+                }
+                WriteOp::AddEdges { .. } | WriteOp::DeleteEdge { .. } => {}
+            }
+        }
+        dangling
+    }
+
+    /// Replay every staged write against `engine`, in order. The caller
+    /// holds the engine write lock for the whole call, and has checked
+    /// `dangling_edges` under it.
+    ///
+    /// Returns the number of write requests applied.
+    pub fn commit(self, engine: &mut dyn GraphStore) -> usize {
+        let count = self.ops.len();
+        for op in self.ops {
+            op.apply(engine);
+        }
+        count
+    }
+
+    fn committed_edge_visible(&self, edge: &EdgeRecord) -> bool {
+        let key = edge_key(edge);
+        !self.edges.contains_key(&key)
+            && !self.deleted_edges.contains(&key)
+            && !self.detached_nodes.contains(&edge.src)
+            && !self.detached_nodes.contains(&edge.dst)
+    }
+
+    /// Staged edges matching `filter`, in key order.
+    fn staged_edges(&self, filter: impl Fn(&EdgeRecord) -> bool) -> Vec<EdgeRecord> {
+        let mut keys: Vec<&EdgeKey> = self.edges.iter()
+            .filter(|(_, edge)| filter(edge))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        keys.into_iter().map(|key| self.edges[key].clone()).collect()
+    }
+}
+
+/// CommitBatch payload staged for one database of a cross-database batch.
+#[derive(Debug, Clone, Default)]
+pub struct StagedCommit {
+    pub changed_files: Vec<String>,
+    pub nodes: Vec<NodeRecord>,
+    pub edges: Vec<EdgeRecord>,
+    pub file_context: Option<String>,
+}
+
+/// CommitBatch payloads staged per database between `BeginCrossDbBatch`
+/// and `CommitCrossDbBatch`.
+///
+/// Each database's manifest version is recorded when the batch begins;
+/// the commit only goes ahead if none of them has moved since.
+pub struct CrossDbBatch {
+    id: String,
+    /// Database name -> (write version at begin, staged payload).
+    /// Name order doubles as the lock order on commit.
+    databases: BTreeMap<String, (u64, Option<StagedCommit>)>,
+}
+
+impl CrossDbBatch {
+    pub fn new(id: String, versions: impl IntoIterator<Item = (String, u64)>) -> Self {
+        Self {
+            id,
+            databases: versions.into_iter().map(|(name, version)| (name, (version, None))).collect(),
+        }
+    }
+
+    /// Batch ID returned by `BeginCrossDbBatch`
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Stage `commit` for `database`, replacing any earlier payload for it.
+    ///
+    /// Returns false if the database is not part of the batch.
+    pub fn stage(&mut self, database: &str, commit: StagedCommit) -> bool {
+        match self.databases.get_mut(database) {
+            Some((_, staged)) => {
+                *staged = Some(commit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `(database, version at begin, payload)` for every database with a
+    /// staged payload, sorted by database name.
+    pub fn into_commits(self) -> Vec<(String, u64, StagedCommit)> {
+        self.databases
+            .into_iter()
+            .filter_map(|(name, (version, staged))| staged.map(|commit| (name, version, commit)))
+            .collect()
+    }
+}
+
+/// Default limit on the data one transaction may stage.
+pub const DEFAULT_TRANSACTION_MAX_BYTES: usize = 1 << 30;
+
+/// Default limit on the data one streaming commit may accumulate.
+pub const DEFAULT_STREAMING_COMMIT_MAX_BYTES: usize = 1 << 30;
+
+/// CommitBatch payload accumulated between `BeginStreamingCommit` and
+/// `FinishStreamingCommit`, bounded by an approximate byte size.
+pub struct StreamingCommit {
+    id: String,
+    commit: StagedCommit,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl StreamingCommit {
+    pub fn new(id: String, changed_files: Vec<String>, max_bytes: usize) -> Self {
+        Self {
+            id,
+            commit: StagedCommit { changed_files, ..Default::default() },
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// Commit ID returned by `BeginStreamingCommit`
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Approximate in-memory size of the staged records
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Stage `nodes`. Returns false, staging nothing, if they would take
+    /// the commit past its byte limit.
+    pub fn add_nodes(&mut self, nodes: Vec<NodeRecord>) -> bool {
+        let bytes = nodes.iter().map(node_bytes).sum();
+        if !self.reserve(bytes) {
+            return false;
+        }
+        self.commit.nodes.extend(nodes);
+        true
+    }
+
+    /// Stage `edges`. Returns false, staging nothing, if they would take
+    /// the commit past its byte limit.
+    pub fn add_edges(&mut self, edges: Vec<EdgeRecord>) -> bool {
+        let bytes = edges.iter().map(edge_bytes).sum();
+        if !self.reserve(bytes) {
+            return false;
+        }
+        self.commit.edges.extend(edges);
+        true
+    }
+
+    /// The accumulated payload so far
+    pub fn staged(&self) -> &StagedCommit {
+        &self.commit
+    }
+
+    /// The accumulated payload, for commit.
+    pub fn into_commit(self) -> StagedCommit {
+        self.commit
+    }
+
+    fn reserve(&mut self, bytes: usize) -> bool {
+        match self.bytes.checked_add(bytes) {
+            Some(total) if total <= self.max_bytes => {
+                self.bytes = total;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn opt_len(s: &Option<String>) -> usize {
+    s.as_ref().map_or(0, String::len)
+}
+
+fn node_bytes(node: &NodeRecord) -> usize {
+    std::mem::size_of::<NodeRecord>()
+        + node.version.len()
+        + opt_len(&node.node_type)
+        + opt_len(&node.name)
+        + opt_len(&node.file)
+        + opt_len(&node.metadata)
+        + opt_len(&node.semantic_id)
+}
+
+fn edge_bytes(edge: &EdgeRecord) -> usize {
+    std::mem::size_of::<EdgeRecord>()
+        + edge.version.len()
+        + opt_len(&edge.edge_type)
+        + opt_len(&edge.metadata)
+}
+
+fn type_matches(pattern: &str, node_type: Option<&str>) -> bool {
+    let node_type = node_type.unwrap_or("");
+    match pattern.strip_suffix('*') {
+        Some(prefix) => node_type.starts_with(prefix),
+        None => node_type == pattern,
+    }
+}
+
+fn edge_type_matches(edge_types: Option<&[&str]>, edge: &EdgeRecord) -> bool {
+    match edge_types {
+        Some(types) => types.contains(&edge.edge_type.as_deref().unwrap_or("")),
+        None => true,
+    }
+}
+
+fn base_has_edge(base: &dyn GraphStore, (src, dst, edge_type): &EdgeKey) -> bool {
+    base.get_outgoing_edges(*src, Some(&[edge_type.as_str()]))
+        .iter()
+        .any(|e| e.dst == *dst)
+}
+
+/// Committed graph as seen by a session, with its open transaction (if
+/// any) layered on top.
+pub struct TransactionView<'a> {
+    base: &'a dyn GraphStore,
+    txn: Option<&'a Transaction>,
+}
+
+impl<'a> TransactionView<'a> {
+    pub fn new(base: &'a dyn GraphStore, txn: Option<&'a Transaction>) -> Self {
+        Self { base, txn }
+    }
+
+    /// Committed engine, without the transaction overlay
+    pub fn base(&self) -> &'a dyn GraphStore {
+        self.base
+    }
+
+    pub fn get_node(&self, id: u128) -> Option<NodeRecord> {
+        let Some(txn) = self.txn else {
+            return self.base.get_node(id);
+        };
+        if let Some(node) = txn.nodes.get(&id) {
+            return Some(node.clone());
+        }
+        if txn.deleted_nodes.contains(&id) {
+            return None;
+        }
+        self.base.get_node(id)
+    }
+
+    /// `get_node` that also returns deleted nodes, marked `deleted`: a node
+    /// the transaction deletes comes back as its committed version, and
+    /// one deleted in the committed graph is read with `committed`, the
+    /// engine's soft-deleted lookup.
+    pub fn get_node_including_deleted(
+        &self,
+        id: u128,
+        committed: impl FnOnce(u128) -> Option<NodeRecord>,
+    ) -> Option<NodeRecord> {
+        if let Some(node) = self.get_node(id) {
+            return Some(node);
+        }
+        let mut node = committed(id)?;
+        node.deleted = true;
+        Some(node)
+    }
+
+    pub fn node_exists(&self, id: u128) -> bool {
+        let Some(txn) = self.txn else {
+            return self.base.node_exists(id);
+        };
+        txn.nodes.contains_key(&id)
+            || (!txn.deleted_nodes.contains(&id) && self.base.node_exists(id))
+    }
+
+    /// Node IDs of a type (trailing `*` matches a prefix, as in the engine).
+    pub fn find_by_type(&self, node_type: &str) -> Vec<u128> {
+        let mut ids = self.base.find_by_type(node_type);
+        let Some(txn) = self.txn else {
+            return ids;
+        };
+        // Staged versions replace committed ones, possibly with another type
+        ids.retain(|id| !txn.deleted_nodes.contains(id) && !txn.nodes.contains_key(id));
+        let mut staged: Vec<u128> = txn.nodes.values()
+            .filter(|n| type_matches(node_type, n.node_type.as_deref()))
+            .map(|n| n.id)
+            .collect();
+        staged.sort();
+        ids.extend(staged);
+        ids
+    }
+
+    pub fn get_outgoing_edges(&self, id: u128, edge_types: Option<&[&str]>) -> Vec<EdgeRecord> {
+        let mut edges = self.base.get_outgoing_edges(id, edge_types);
+        if let Some(txn) = self.txn {
+            edges.retain(|e| txn.committed_edge_visible(e));
+            edges.extend(txn.staged_edges(|e| e.src == id && edge_type_matches(edge_types, e)));
+        }
+        edges
+    }
+
+    pub fn edge_exists(&self, src: u128, dst: u128, edge_type: &str) -> bool {
+        if self.txn.is_none() {
+            return self.base.edge_exists(src, dst, edge_type);
+        }
+        self.get_outgoing_edges(src, Some(&[edge_type]))
+            .iter()
+            .any(|e| e.dst == dst)
+    }
+
+    pub fn get_incoming_edges(&self, id: u128, edge_types: Option<&[&str]>) -> Vec<EdgeRecord> {
+        let mut edges = self.base.get_incoming_edges(id, edge_types);
+        if let Some(txn) = self.txn {
+            edges.retain(|e| txn.committed_edge_visible(e));
+            edges.extend(txn.staged_edges(|e| e.dst == id && edge_type_matches(edge_types, e)));
+        }
+        edges
+    }
+
+    /// Targets of outgoing edges of the given types.
+    pub fn neighbors(&self, id: u128, edge_types: &[&str]) -> Vec<u128> {
+        if self.txn.is_none() {
+            return self.base.neighbors(id, edge_types);
+        }
+        let filter = (!edge_types.is_empty()).then_some(edge_types);
+        self.get_outgoing_edges(id, filter).into_iter().map(|e| e.dst).collect()
+    }
+
+    pub fn node_count(&self) -> usize {
+        let base_count = self.base.node_count();
+        let Some(txn) = self.txn else {
+            return base_count;
+        };
+        let added = txn.nodes.keys().filter(|id| !self.base.node_exists(**id)).count();
+        let removed = txn.deleted_nodes.iter().filter(|id| self.base.node_exists(**id)).count();
+        (base_count + added).saturating_sub(removed)
+    }
+
+    pub fn edge_count(&self) -> usize {
+        let base_count = self.base.edge_count();
+        let Some(txn) = self.txn else {
+            return base_count;
+        };
+
+        // Committed edges the transaction hides: explicit deletes plus
+        // every edge of a deleted node
+        let mut hidden: HashSet<EdgeKey> = txn.deleted_edges.iter()
+            .filter(|key| base_has_edge(self.base, key))
+            .cloned()
+            .collect();
+        for &id in &txn.detached_nodes {
+            let touching = self.base.get_outgoing_edges(id, None).into_iter()
+                .chain(self.base.get_incoming_edges(id, None));
+            hidden.extend(touching.map(|e| edge_key(&e)));
+        }
+
+        // Staged edges replacing a still-visible committed edge add nothing
+        let added = txn.edges.keys()
+            .filter(|key| hidden.contains(*key) || !base_has_edge(self.base, key))
+            .count();
+        (base_count + added).saturating_sub(hidden.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::GraphEngineV2;
+
+    fn node(id: u128, node_type: &str) -> NodeRecord {
+        NodeRecord {
+            id,
+            node_type: Some(node_type.to_string()),
+            file_id: 0,
+            name_offset: 0,
+            version: "main".into(),
+            exported: false,
+            replaces: None,
+            deleted: false,
+            name: Some(format!("n{}", id)),
+            file: None,
+            metadata: None,
+            semantic_id: None,
+        }
+    }
+
+    fn edge(src: u128, dst: u128, edge_type: &str) -> EdgeRecord {
+        EdgeRecord {
+            src,
+            dst,
+            edge_type: Some(edge_type.to_string()),
+            version: "main".into(),
+            metadata: None,
+            deleted: false,
+        }
+    }
+
+    fn committed_graph() -> GraphEngineV2 {
+        let mut engine = GraphEngineV2::create_ephemeral();
+        engine.add_nodes(vec![node(1, "FUNCTION"), node(2, "FUNCTION"), node(3, "CLASS")]);
+        engine.add_edges(vec![edge(1, 2, "CALLS"), edge(3, 1, "CONTAINS")], false);
+        engine
+    }
+
+    #[test]
+    fn test_view_reads_staged_writes() {
+        let engine = committed_graph();
+        let mut txn = Transaction::new("t".into(), DEFAULT_TRANSACTION_MAX_BYTES);
+        txn.stage(WriteOp::AddNodes(vec![node(4, "FUNCTION"), node(2, "METHOD")]));
+        txn.stage(WriteOp::AddEdges { edges: vec![edge(1, 4, "CALLS")], skip_validation: false });
+        txn.stage(WriteOp::DeleteNode(3));
+
+        let view = TransactionView::new(&engine, Some(&txn));
+        assert!(view.node_exists(4));
+        assert!(!view.node_exists(3));
+        assert_eq!(view.get_node(2).unwrap().node_type.as_deref(), Some("METHOD"));
+        assert!(view.get_node_including_deleted(3, |id| engine.get_node(id)).unwrap().deleted);
+        assert!(!view.get_node_including_deleted(4, |_| None).unwrap().deleted);
+        assert_eq!(view.find_by_type("FUNCTION"), vec![1, 4]);
+        assert_eq!(view.find_by_type("METH*"), vec![2]);
+
+        let mut targets = view.neighbors(1, &["CALLS"]);
+        targets.sort();
+        assert_eq!(targets, vec![2, 4]);
+        // CONTAINS 3 -> 1 went away with node 3
+        assert!(view.get_incoming_edges(1, None).is_empty());
+
+        assert_eq!(view.node_count(), 3);
+        assert_eq!(view.edge_count(), 2);
+
+        // The engine itself is untouched
+        let committed = TransactionView::new(&engine, None);
+        assert_eq!((committed.node_count(), committed.edge_count()), (3, 2));
+        assert!(committed.node_exists(3));
+    }
+
+    #[test]
+    fn test_commit_matches_view() {
+        let mut engine = committed_graph();
+        let mut txn = Transaction::new("t".into(), DEFAULT_TRANSACTION_MAX_BYTES);
+        txn.stage(WriteOp::DeleteEdge { src: 1, dst: 2, edge_type: "CALLS".into() });
+        txn.stage(WriteOp::AddEdges { edges: vec![edge(2, 1, "CALLS")], skip_validation: false });
+        txn.stage(WriteOp::AddNodes(vec![node(5, "CLASS")]));
+
+        let view = TransactionView::new(&engine, Some(&txn));
+        let expected = (view.node_count(), view.edge_count(), view.find_by_type("CLASS"));
+        assert_eq!(expected, (4, 2, vec![3, 5]));
+
+        assert_eq!(txn.commit(&mut engine), 3);
+        let mut classes = engine.find_by_type("CLASS");
+        classes.sort();
+        assert_eq!((engine.node_count(), engine.edge_count(), classes), expected);
+        assert!(engine.get_outgoing_edges(1, Some(&["CALLS"])).is_empty());
+    }
+
+    #[test]
+    fn test_dangling_edges_follow_replay_order() {
+        let mut engine = committed_graph();
+        let mut txn = Transaction::new("t".into(), DEFAULT_TRANSACTION_MAX_BYTES);
+        txn.stage(WriteOp::AddEdges { edges: vec![edge(1, 4, "CALLS")], skip_validation: false });
+        txn.stage(WriteOp::AddNodes(vec![node(4, "FUNCTION")]));
+        txn.stage(WriteOp::AddEdges { edges: vec![edge(4, 1, "CALLS")], skip_validation: false });
+        txn.stage(WriteOp::AddEdges { edges: vec![edge(1, 9, "CALLS")], skip_validation: true });
+        // Staged before 4 existed: dangling even though 4 is added later
+        let keys = |edges: Vec<EdgeRecord>| edges.iter().map(edge_key).collect::<Vec<_>>();
+        assert_eq!(keys(txn.dangling_edges(&engine)), vec![(1, 4, "CALLS".to_string())]);
+
+        // A committed endpoint that disappears before commit is caught too
+        let mut txn = Transaction::new("t".into(), DEFAULT_TRANSACTION_MAX_BYTES);
+        txn.stage(WriteOp::AddEdges { edges: vec![edge(2, 3, "CALLS")], skip_validation: false });
+        assert!(txn.dangling_edges(&engine).is_empty());
+        engine.delete_node(3);
+        assert_eq!(keys(txn.dangling_edges(&engine)), vec![(2, 3, "CALLS".to_string())]);
+    }
+
+    #[test]
+    fn test_transaction_enforces_byte_limit() {
+        let one_node = node_bytes(&node(1, "FUNCTION"));
+        let mut txn = Transaction::new("t".into(), one_node);
+        assert!(txn.stage(WriteOp::AddNodes(vec![node(1, "FUNCTION")])));
+        assert!(!txn.stage(WriteOp::AddNodes(vec![node(2, "FUNCTION")])));
+        assert_eq!((txn.len(), txn.bytes()), (1, one_node));
+
+        let engine = GraphEngineV2::create_ephemeral();
+        assert!(!TransactionView::new(&engine, Some(&txn)).node_exists(2));
+    }
+
+    #[test]
+    fn test_cross_db_batch_commits_sorted_by_name() {
+        let versions = vec![("zeta".to_string(), 4), ("alpha".to_string(), 2), ("idle".to_string(), 9)];
+        let mut batch = CrossDbBatch::new("x".into(), versions);
+        let payload = |n: u128| StagedCommit { nodes: vec![node(n, "FUNCTION")], ..Default::default() };
+
+        assert!(batch.stage("zeta", payload(1)));
+        assert!(batch.stage("alpha", payload(2)));
+        assert!(batch.stage("alpha", payload(3)));
+        assert!(!batch.stage("unknown", payload(4)));
+
+        // "idle" has nothing staged; the later "alpha" payload wins
+        let commits: Vec<(String, u64, u128)> = batch.into_commits().into_iter()
+            .map(|(name, version, commit)| (name, version, commit.nodes[0].id))
+            .collect();
+        assert_eq!(commits, vec![("alpha".to_string(), 2, 3), ("zeta".to_string(), 4, 1)]);
+    }
+
+    #[test]
+    fn test_streaming_commit_enforces_byte_limit() {
+        let one_node = node_bytes(&node(1, "FUNCTION"));
+        let mut stream = StreamingCommit::new("s".into(), vec!["a.js".into()], one_node * 2);
+        assert!(stream.add_nodes(vec![node(1, "FUNCTION")]));
+        assert!(stream.add_nodes(vec![node(2, "FUNCTION")]));
+        // Over the limit: refused as a whole
+        assert!(!stream.add_edges(vec![edge(1, 2, "CALLS")]));
+        assert_eq!(stream.bytes(), one_node * 2);
+
+        let commit = stream.into_commit();
+        assert_eq!(commit.changed_files, vec!["a.js".to_string()]);
+        assert_eq!(commit.nodes.len(), 2);
+        assert!(commit.edges.is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+//! Integration test: heap allocations on the node read path.
+//!
+//! Segment getters hand out `&str` slices of the (mmap-backed) string
+//! table; the owned copies are made once, when a segment record is turned
+//! into a `NodeRecordV2`. The v2 -> v1 conversion and the server's wire
+//! conversion then move those strings instead of cloning them again.
+//! Measured by fetching every 10th of 100k flushed nodes with empty
+//! metadata:
+//!
+//! - before (v2 -> v1 cloned every field): 9 allocations per `get_node`
+//! - after (fields moved): 5 allocations per `get_node` (semantic_id,
+//!   node_type, name and file from the segment, plus the v1 `version`)
+//!
+//! The test holds `get_node` to that budget of 5 allocations per node; a
+//! change that adds a copy anywhere on the path fails it, and one that
+//! removes a copy should lower `ALLOCATIONS_PER_NODE`.
+//!
+//! Allocations are counted by a global allocator, so this file holds a
+//! single test to keep other test threads out of the count.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rfdb::graph::GraphEngineV2;
+use rfdb::{GraphStore, NodeRecord};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const NODE_COUNT: u128 = 100_000;
+const SAMPLE_STEP: usize = 10;
+/// semantic_id, node_type, name and file, plus the v1 `version`.
+const ALLOCATIONS_PER_NODE: usize = 5;
+
+fn make_node(id: u128) -> NodeRecord {
+    NodeRecord {
+        id,
+        node_type: Some("FUNCTION".to_string()),
+        file_id: 0,
+        name_offset: 0,
+        version: "main".to_string(),
+        exported: false,
+        replaces: None,
+        deleted: false,
+        name: Some(format!("fn_{id}")),
+        file: Some(format!("src/dir_{}/file.js", id % 100)),
+        metadata: None,
+        semantic_id: Some(format!("FUNCTION:fn_{id}@src/dir_{}/file.js", id % 100)),
+    }
+}
+
+#[test]
+fn test_get_node_allocations_per_record() {
+    let mut engine = GraphEngineV2::create_ephemeral();
+    engine.add_nodes((1..=NODE_COUNT).map(make_node).collect());
+    engine.flush().unwrap();
+
+    let ids: Vec<u128> = (1..=NODE_COUNT).step_by(SAMPLE_STEP).collect();
+    let mut nodes = Vec::with_capacity(ids.len());
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for &id in &ids {
+        if let Some(node) = engine.get_node(id) {
+            nodes.push(node);
+        }
+    }
+    let fetched = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    assert_eq!(nodes.len(), ids.len());
+
+    assert!(
+        fetched <= ALLOCATIONS_PER_NODE * ids.len(),
+        "get_node made {fetched} allocations for {} nodes, budget is {ALLOCATIONS_PER_NODE} per node",
+        ids.len(),
+    );
+}
@@ -333,6 +333,7 @@ fn scenario_find_by_name(engine: &dyn GraphStore) -> (usize, Duration) {
                 name: Some(name),
                 metadata_filters: Vec::new(),
                 substring_match: false,
+                sorted: false,
             };
             total += engine.find_by_attr(&query).len();
         }
@@ -346,7 +347,7 @@ fn scenario_datalog_2hop(engine: &dyn GraphStore) -> (usize, Duration) {
     let program = parse_program(
         "db_caller(F, Q) :- node(F, \"FUNCTION\"), edge(F, C, \"CALLS\"), edge(C, Q, \"QUERIES_DB\")."
     ).expect("parse 2-hop program");
-    evaluator.load_rules(program.rules().to_vec());
+    evaluator.load_rules(program.rules().to_vec()).unwrap();
     let goal = rfdb::datalog::parse_atom("db_caller(F, Q)").expect("parse goal");
     timed(|| evaluator.query(&goal).unwrap().len())
 }
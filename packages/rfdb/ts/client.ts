@@ -175,6 +175,12 @@ export class RFDBClient extends BaseRFDBClient {
    * to StreamQueue or resolve single-response Promise.
    */
   private _handleResponse(response: RFDBResponse): void {
+    // Server-initiated idle check, not a reply to any request
+    if (response.requestId === RFDBClient.HEARTBEAT_REQUEST_ID) {
+      this._answerHeartbeat();
+      return;
+    }
+
     if (this.pending.size === 0 && this._pendingStreams.size === 0) {
       this.emit('error', new Error('Received response with no pending request'));
       return;
@@ -286,6 +292,15 @@ export class RFDBClient extends BaseRFDBClient {
     }
   }
 
+  /**
+   * Answer a server heartbeat. Any request counts as an answer; a failed
+   * ping surfaces through the connection's own close/error events.
+   */
+  private _answerHeartbeat(): void {
+    if (!this.connected || !this.socket) return;
+    this.ping().catch(() => {});
+  }
+
   private _parseRequestId(requestId: string): number | null {
     if (!requestId.startsWith('r')) return null;
     const num = parseInt(requestId.slice(1), 10);
@@ -294,6 +309,9 @@ export class RFDBClient extends BaseRFDBClient {
 
   private static readonly DEFAULT_TIMEOUT_MS = 60_000;
 
+  /** requestId of the server's idle heartbeat frame (see hello()) */
+  private static readonly HEARTBEAT_REQUEST_ID = '__heartbeat';
+
   /**
    * Send a request and wait for response with timeout
    */
@@ -349,10 +367,11 @@ export class RFDBClient extends BaseRFDBClient {
 
   /**
    * Negotiate protocol version with server.
-   * Overrides base to set streaming flag.
+   * Overrides base to set streaming flag and opt in to idle heartbeats,
+   * which this client answers with a ping.
    */
   override async hello(protocolVersion: number = 3): Promise<HelloResponse> {
-    const response = await this._send('hello' as RFDBCommand, { protocolVersion });
+    const response = await this._send('hello' as RFDBCommand, { protocolVersion, heartbeat: true });
     const hello = response as HelloResponse;
     this._supportsStreaming = hello.features?.includes('streaming') ?? false;
     return hello;
@@ -694,8 +694,60 @@ describe('RFDBClient — _handleData Framing (Locking)', () => {
 
     await assert.rejects(promise, { message: 'No database selected' });
   });
+
+  it('should answer a server heartbeat with a ping instead of emitting an error', () => {
+    const client = new RFDBClient('/tmp/test.sock');
+    const written: Buffer[] = [];
+    (client as any).connected = true;
+    (client as any).socket = {
+      write: (buf: Buffer) => { written.push(buf); },
+      removeListener: () => {},
+      once: () => {},
+    };
+    const errors: Error[] = [];
+    client.on('error', (err: Error) => errors.push(err));
+
+    const msgBytes = encode({ requestId: '__heartbeat', pong: true, version: '1.0.0' });
+    const header = Buffer.alloc(4);
+    header.writeUInt32BE(msgBytes.length);
+    (client as any)._handleData(Buffer.concat([header, Buffer.from(msgBytes)]));
+
+    assert.deepStrictEqual(errors, []);
+    assert.strictEqual(written.length, 1);
+    const ping = decode(written[0].subarray(4)) as Record<string, unknown>;
+    assert.strictEqual(ping.cmd, 'ping');
+
+    // Settle the ping so its timeout does not keep the test alive
+    (client as any)._handleData(buildResponseFrame({ requestId: ping.requestId, pong: true, version: '1.0.0' }));
+  });
+
+  it('hello() should opt in to heartbeats', () => {
+    const client = new RFDBClient('/tmp/test.sock');
+    const written: Buffer[] = [];
+    (client as any).connected = true;
+    (client as any).socket = {
+      write: (buf: Buffer) => { written.push(buf); },
+      removeListener: () => {},
+      once: () => {},
+    };
+
+    const hello = client.hello();
+    const request = decode(written[0].subarray(4)) as Record<string, unknown>;
+    assert.strictEqual(request.cmd, 'hello');
+    assert.strictEqual(request.heartbeat, true);
+
+    (client as any)._handleData(buildResponseFrame({ requestId: request.requestId, ok: true, protocolVersion: 3, features: [] }));
+    return hello;
+  });
 });
 
+function buildResponseFrame(response: Record<string, unknown>): Buffer {
+  const msgBytes = encode(response);
+  const header = Buffer.alloc(4);
+  header.writeUInt32BE(msgBytes.length);
+  return Buffer.concat([header, Buffer.from(msgBytes)]);
+}
+
 // =============================================================================
 // Part 8: close() and shutdown() behavior
 // =============================================================================